@@ -1,4 +1,4 @@
-import { expect, test } from '@playwright/test';
+import { expect, test, type Page } from '@playwright/test';
 
 test.describe('GitHub OAuth Integration', () => {
 	test('login page has GitHub OAuth button', async ({ page }) => {
@@ -33,26 +33,25 @@ test.describe('GitHub OAuth Integration', () => {
 		await expect(githubButton).toHaveClass(/bg-transparent/);
 	});
 
-	test('OAuth callback page shows loading state briefly with valid parameters', async ({
-		page
-	}) => {
-		// Navigate to OAuth callback page with valid parameters
-		// This will cause the page to show loading state briefly before processing
-		await page.goto(
-			'/auth/oauth/callback?token=valid_token&user_id=user123&email=test@example.com&is_new_user=false'
+	test('OAuth callback page exchanges the code for a session', async ({ page }) => {
+		// The callback only carries a one-time code; the session comes from exchanging it
+		const exchangeRequests = await mockApi(
+			page,
+			'**/api/auth/oauth/exchange',
+			exchangeResponse(false)
 		);
 
-		// The loading state might be very brief, so we'll check if the success state appears
-		// indicating the loading state was processed
+		await page.goto('/auth/oauth/callback?code=login_code&is_new_user=false');
+
 		const successHeading = page.locator('text=Sign In Successful!');
 		await expect(successHeading).toBeVisible();
+		expect(exchangeRequests).toEqual([{ code: 'login_code' }]);
 	});
 
 	test('OAuth callback page handles successful authentication', async ({ page }) => {
-		// Navigate to OAuth callback with success parameters
-		await page.goto(
-			'/auth/oauth/callback?token=mock_jwt_token&user_id=mock_user_id&email=test@example.com&is_new_user=true'
-		);
+		await mockApi(page, '**/api/auth/oauth/exchange', exchangeResponse(true));
+
+		await page.goto('/auth/oauth/callback?code=login_code&is_new_user=true');
 
 		// Should show success state
 		const successHeading = page.locator('text=Sign In Successful!');
@@ -62,6 +61,28 @@ test.describe('GitHub OAuth Integration', () => {
 		await expect(welcomeText).toBeVisible();
 	});
 
+	test('OAuth callback page asks for a second factor', async ({ page }) => {
+		await mockApi(page, '**/api/auth/oauth/exchange', {
+			status: 'mfa_pending',
+			mfa_token: 'pending_token',
+			expires_in: 300
+		});
+		const verifyRequests = await mockApi(
+			page,
+			'**/api/auth/mfa/verify',
+			exchangeResponse(false)
+		);
+
+		await page.goto('/auth/oauth/callback?code=login_code&is_new_user=false');
+
+		await expect(page.locator('text=Two-Factor Authentication')).toBeVisible();
+		await page.fill('#mfa-code', '123456');
+		await page.locator('button:has-text("Verify")').click();
+
+		await expect(page.locator('text=Sign In Successful!')).toBeVisible();
+		expect(verifyRequests).toEqual([{ mfa_token: 'pending_token', code: '123456' }]);
+	});
+
 	test('OAuth callback page handles authentication errors', async ({ page }) => {
 		// Navigate to OAuth callback with error parameter
 		await page.goto('/auth/oauth/callback?error=no_invite');
@@ -91,9 +112,9 @@ test.describe('GitHub OAuth Integration', () => {
 		await expect(errorMessage).toBeVisible();
 	});
 
-	test('OAuth callback page handles missing token error', async ({ page }) => {
-		// Navigate to OAuth callback with incomplete parameters (missing token)
-		await page.goto('/auth/oauth/callback?user_id=mock_user_id&email=test@example.com');
+	test('OAuth callback page handles missing code error', async ({ page }) => {
+		// Navigate to OAuth callback with incomplete parameters (missing code)
+		await page.goto('/auth/oauth/callback?is_new_user=false');
 
 		// Should show error state
 		const errorHeading = page.locator('text=Authentication Failed');
@@ -156,3 +177,47 @@ test.describe('GitHub OAuth Integration', () => {
 		await expect(oauthContainer).toContainText('Continue with GitHub');
 	});
 });
+
+/** Session the server returns for an exchanged login code */
+function exchangeResponse(isNewUser: boolean) {
+	return {
+		auth_token: 'mock_jwt_token',
+		refresh_token: 'mock_refresh_token',
+		expires_in: 900,
+		auth_user: {
+			id: 'mock_user_id',
+			email: 'test@example.com',
+			created_at: new Date().toISOString(),
+			updated_at: new Date().toISOString()
+		},
+		payment_user: {
+			payment_required: false,
+			has_valid_invite: false
+		},
+		is_new_user: isNewUser
+	};
+}
+
+/**
+ * Answer a cross-origin API call with a JSON body
+ * @returns The JSON bodies the page sent, filled in as requests arrive
+ */
+async function mockApi(page: Page, url: string, body: object): Promise<unknown[]> {
+	const requests: unknown[] = [];
+	const headers = {
+		'Access-Control-Allow-Origin': '*',
+		'Access-Control-Allow-Headers': '*',
+		'Access-Control-Allow-Methods': '*'
+	};
+
+	await page.route(url, async (route) => {
+		if (route.request().method() === 'OPTIONS') {
+			await route.fulfill({ status: 204, headers });
+			return;
+		}
+		requests.push(route.request().postDataJSON());
+		await route.fulfill({ json: body, headers });
+	});
+
+	return requests;
+}
@@ -7,3 +7,4 @@
 
 export { default as GoogleOAuthButton } from './GoogleOAuthButton.svelte';
 export { default as GitHubOAuthButton } from './GitHubOAuthButton.svelte';
+export { default as MfaVerifyForm } from './MfaVerifyForm.svelte';
@@ -18,8 +18,17 @@ export {
 	// isAuthenticated is already exported from stores
 	initiateGoogleOAuth,
 	initiateGitHubOAuth,
-	handleOAuthCallback,
-	getCurrentUser
+	exchangeOAuthCode,
+	verifyMfa,
+	isMfaChallenge,
+	getCurrentUser,
+	authFetch,
+	refreshSession,
+	getCliFlow,
+	approveCliFlow,
+	confirmEmail,
+	requestPasswordReset,
+	confirmPasswordReset
 	// AuthError and OAuthLoginResponse are already exported from types
 } from './services/apiAuth';
 
@@ -10,6 +10,7 @@ import type {
 	UsageStats,
 	AIInfo
 } from '$lib/types/chat.js';
+import { authFetch } from '$lib/services/apiAuth';
 
 // Configuration - same pattern as apiAuth.ts
 const SERVER_PORT = import.meta.env.SERVER_PORT || '8081';
@@ -17,13 +18,11 @@ const API_BASE_URL = `${window.location.protocol}//${window.location.hostname}:$
 const API_BASE = `${API_BASE_URL}/api/ai`;
 
 /**
- * Get authentication headers with JWT token
+ * Get request headers; `authFetch` adds the access token
  */
 function getAuthHeaders(): HeadersInit {
-	const token = localStorage.getItem('auth_token');
 	return {
-		'Content-Type': 'application/json',
-		...(token && { Authorization: `Bearer ${token}` })
+		'Content-Type': 'application/json'
 	};
 }
 
@@ -45,7 +44,7 @@ async function handleResponse<T>(response: Response): Promise<T> {
  * Send a chat message and get AI response
  */
 export async function sendChatMessage(request: ChatRequest): Promise<ChatResponse> {
-	const response = await fetch(`${API_BASE}/chat`, {
+	const response = await authFetch(`${API_BASE}/chat`, {
 		method: 'POST',
 		headers: getAuthHeaders(),
 		body: JSON.stringify(request)
@@ -68,7 +67,7 @@ export function sendChatMessageStream(
 	// Create URL with query parameters for streaming
 	const url = new URL(`${API_BASE}/chat/stream`);
 
-	fetch(url, {
+	authFetch(url, {
 		method: 'POST',
 		headers: getAuthHeaders(),
 		body: JSON.stringify({ ...request, stream: true }),
@@ -145,7 +144,7 @@ export async function getConversations(params?: {
 	if (params?.limit) url.searchParams.set('limit', params.limit.toString());
 	if (params?.archived !== undefined) url.searchParams.set('archived', params.archived.toString());
 
-	const response = await fetch(url, {
+	const response = await authFetch(url, {
 		headers: getAuthHeaders()
 	});
 
@@ -159,7 +158,7 @@ export async function getConversation(conversationId: string): Promise<{
 	conversation: Conversation;
 	messages: Conversation['messages'];
 }> {
-	const response = await fetch(`${API_BASE}/conversations/${conversationId}`, {
+	const response = await authFetch(`${API_BASE}/conversations/${conversationId}`, {
 		headers: getAuthHeaders()
 	});
 
@@ -172,7 +171,7 @@ export async function getConversation(conversationId: string): Promise<{
  * Archive a conversation
  */
 export async function archiveConversation(conversationId: string): Promise<void> {
-	const response = await fetch(`${API_BASE}/conversations/${conversationId}/archive`, {
+	const response = await authFetch(`${API_BASE}/conversations/${conversationId}/archive`, {
 		method: 'POST',
 		headers: getAuthHeaders()
 	});
@@ -187,7 +186,7 @@ export async function archiveConversation(conversationId: string): Promise<void>
  * Delete a conversation
  */
 export async function deleteConversation(conversationId: string): Promise<void> {
-	const response = await fetch(`${API_BASE}/conversations/${conversationId}`, {
+	const response = await authFetch(`${API_BASE}/conversations/${conversationId}`, {
 		method: 'DELETE',
 		headers: getAuthHeaders()
 	});
@@ -208,15 +207,8 @@ export async function uploadFiles(files: File[]): Promise<FileUploadResponse[]>
 		formData.append('files', file);
 	}
 
-	const token = localStorage.getItem('auth_token');
-	const headers: HeadersInit = {};
-	if (token) {
-		headers.Authorization = `Bearer ${token}`;
-	}
-
-	const response = await fetch(`${API_BASE}/upload`, {
+	const response = await authFetch(`${API_BASE}/upload`, {
 		method: 'POST',
-		headers,
 		body: formData
 	});
 
@@ -237,7 +229,7 @@ export async function sendContextualChat(
 		template?: string;
 	}
 ): Promise<ChatResponse> {
-	const response = await fetch(`${API_BASE}/chat/contextual`, {
+	const response = await authFetch(`${API_BASE}/chat/contextual`, {
 		method: 'POST',
 		headers: getAuthHeaders(),
 		body: JSON.stringify(request)
@@ -250,7 +242,7 @@ export async function sendContextualChat(
  * Get usage statistics
  */
 export async function getUsageStats(): Promise<UsageStats> {
-	const response = await fetch(`${API_BASE}/usage`, {
+	const response = await authFetch(`${API_BASE}/usage`, {
 		headers: getAuthHeaders()
 	});
 
@@ -261,7 +253,7 @@ export async function getUsageStats(): Promise<UsageStats> {
  * Get AI service information
  */
 export async function getAIInfo(): Promise<AIInfo> {
-	const response = await fetch(`${API_BASE}/info`, {
+	const response = await authFetch(`${API_BASE}/info`, {
 		headers: getAuthHeaders()
 	});
 
@@ -272,7 +264,7 @@ export async function getAIInfo(): Promise<AIInfo> {
  * Check AI service health
  */
 export async function checkAIHealth(): Promise<{ status: string; message?: string }> {
-	const response = await fetch(`${API_BASE}/health`, {
+	const response = await authFetch(`${API_BASE}/health`, {
 		headers: getAuthHeaders()
 	});
 
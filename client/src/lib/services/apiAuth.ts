@@ -5,10 +5,23 @@
  *
  * Handles authentication operations including login, registration, and JWT token management.
  * All auth operations update the global auth store automatically.
+ *
+ * Access tokens are short-lived; `authFetch` renews them with the stored refresh
+ * token when the server rejects one.
  */
 
 import { authStore } from '$lib/stores';
-import type { LoginRequest, RegisterRequest, UnifiedAuthResponse, User } from '$lib/types/auth';
+import type {
+	ApproveCliFlowResponse,
+	CliFlowInfo,
+	LoginRequest,
+	MfaChallengeResponse,
+	OAuthExchangeResponse,
+	RegisterRequest,
+	SessionTokenResponse,
+	UnifiedAuthResponse,
+	User
+} from '$lib/types/auth';
 import { StorageService } from '$lib/services/storageService';
 
 // API base URL - can be configured based on environment
@@ -35,6 +48,83 @@ export interface AuthError {
 
 // OAuthLoginResponse is now just UnifiedAuthResponse
 
+// Refresh in progress, shared by every request that needs it
+let refreshInFlight: Promise<boolean> | null = null;
+
+/**
+ * Exchange the stored refresh token for new tokens
+ *
+ * Concurrent callers share one request: a refresh token works only once, and
+ * presenting it again revokes the whole session.
+ * @returns true if new tokens were stored
+ */
+export function refreshSession(): Promise<boolean> {
+	if (!refreshInFlight) {
+		refreshInFlight = requestNewTokens().finally(() => {
+			refreshInFlight = null;
+		});
+	}
+	return refreshInFlight;
+}
+
+async function requestNewTokens(): Promise<boolean> {
+	const refreshToken = StorageService.getRefreshToken();
+	if (!refreshToken) {
+		return false;
+	}
+
+	try {
+		const response = await fetch(`${API_BASE_URL}/api/auth/refresh`, {
+			method: 'POST',
+			headers: {
+				'Content-Type': 'application/json'
+			},
+			body: JSON.stringify({ refresh_token: refreshToken })
+		});
+
+		if (!response.ok) {
+			// The session was revoked or expired, so signing in again is the only way back
+			if (response.status === 401) {
+				authStore.logout();
+			}
+			return false;
+		}
+
+		const tokens: SessionTokenResponse = await response.json();
+		authStore.setTokens(tokens.auth_token, tokens.refresh_token);
+		return true;
+	} catch (error) {
+		console.error('[apiAuth] Failed to refresh session:', error);
+		return false;
+	}
+}
+
+/**
+ * Fetch with the stored access token, renewing it once if it was rejected
+ * @param input Request URL
+ * @param init Request options; the Authorization header is set from storage
+ * @returns The response, retried with a new access token after a 401
+ */
+export async function authFetch(
+	input: RequestInfo | URL,
+	init: RequestInit = {}
+): Promise<Response> {
+	const send = () => {
+		const headers = new Headers(init.headers);
+		const token = StorageService.getAuthToken();
+		if (token) {
+			headers.set('Authorization', `Bearer ${token}`);
+		}
+		return fetch(input, { ...init, headers });
+	};
+
+	const response = await send();
+	if (response.status !== 401 || !(await refreshSession())) {
+		return response;
+	}
+	return send();
+}
+
 /**
  * Register a new user
  * @param data Registration data
@@ -88,9 +178,12 @@ export async function register(data: RegisterRequest): Promise<UnifiedAuthRespon
 /**
  * Login user with email and password
  * @param data Login credentials
- * @returns User data and JWT token on success
+ * @returns User data and JWT token on success, or a challenge when the user has to
+ * enter a second factor
  */
-export async function login(data: LoginRequest): Promise<UnifiedAuthResponse> {
+export async function login(
+	data: LoginRequest
+): Promise<UnifiedAuthResponse | MfaChallengeResponse> {
 	authStore.setLoading(true);
 	authStore.clearError();
 
@@ -118,10 +211,15 @@ export async function login(data: LoginRequest): Promise<UnifiedAuthResponse> {
 			);
 		}
 
-		const loginResponse: UnifiedAuthResponse = await response.json();
+		const loginResponse: UnifiedAuthResponse | MfaChallengeResponse = await response.json();
 
-		// Update auth store with unified response
-		authStore.handleAuthResponse(loginResponse);
+		if (isMfaChallenge(loginResponse)) {
+			// Not signed in until the second factor is verified
+			authStore.setLoading(false);
+		} else {
+			// Update auth store with unified response
+			authStore.handleAuthResponse(loginResponse);
+		}
 
 		return loginResponse;
 	} catch (error) {
@@ -136,6 +234,12 @@ export async function login(data: LoginRequest): Promise<UnifiedAuthResponse> {
  * Logout user - clears auth state and token
  */
 export async function logout(): Promise<void> {
+	// End the session on the server too, so its refresh token stops working
+	if (StorageService.getAuthToken()) {
+		await authFetch(`${API_BASE_URL}/api/auth/logout`, { method: 'POST' }).catch((error) =>
+			console.error('[apiAuth] Failed to end session:', error)
+		);
+	}
 	authStore.logout();
 	// Storage is cleared by authStore.logout()
 	// Redirect to login page
@@ -154,13 +258,17 @@ export async function verifyToken(token?: string): Promise<User> {
 		throw new ApiError('No token provided', 401);
 	}
 
-	const response = await fetch(`${API_BASE_URL}/api/auth/verify`, {
+	const request: RequestInit = {
 		method: 'GET',
 		headers: {
 			'Content-Type': 'application/json',
 			Authorization: `Bearer ${authToken}`
 		}
-	});
+	};
+	// Only the stored token can be renewed
+	const response = token
+		? await fetch(`${API_BASE_URL}/api/auth/verify`, request)
+		: await authFetch(`${API_BASE_URL}/api/auth/verify`, request);
 
 	if (!response.ok) {
 		let errorData: AuthError;
@@ -194,10 +302,10 @@ export async function refreshAuth(): Promise<void> {
 	}
 
 	try {
-		const user = await verifyToken(token);
+		const user = await verifyToken();
 		// Create a minimal auth response for refresh
 		const response: UnifiedAuthResponse = {
-			auth_token: token,
+			auth_token: StorageService.getAuthToken() ?? token,
 			auth_user: user,
 			payment_user: StorageService.getPaymentUser() || {
 				payment_required: false,
@@ -256,28 +364,74 @@ export function initiateGitHubOAuth(state?: string): void {
 }
 
 /**
- * Handle OAuth callback (called when user returns from OAuth provider)
- * This should be called on the OAuth callback page
+ * Exchange the one-time code the OAuth callback redirected with for a session
+ * @param code Code from the callback URL
+ * @returns The session, or a challenge when the user has to enter a second factor
  */
-export async function handleOAuthCallback(
-	code: string,
-	state?: string
-): Promise<UnifiedAuthResponse> {
+export async function exchangeOAuthCode(
+	code: string
+): Promise<OAuthExchangeResponse | MfaChallengeResponse> {
 	authStore.setLoading(true);
 	authStore.clearError();
 
 	try {
-		const url = new URL(`${API_BASE_URL}/api/auth/oauth/google/callback`);
-		url.searchParams.set('code', code);
-		if (state) {
-			url.searchParams.set('state', state);
+		const response = await fetch(`${API_BASE_URL}/api/auth/oauth/exchange`, {
+			method: 'POST',
+			headers: {
+				'Content-Type': 'application/json'
+			},
+			body: JSON.stringify({ code })
+		});
+
+		if (!response.ok) {
+			let errorData: AuthError;
+			try {
+				errorData = await response.json();
+			} catch {
+				errorData = { error: `HTTP ${response.status}: ${response.statusText}` };
+			}
+
+			throw new ApiError(
+				errorData.error || `OAuth code exchange failed with status ${response.status}`,
+				response.status,
+				errorData
+			);
 		}
 
-		const response = await fetch(url.toString(), {
-			method: 'GET',
+		const exchangeResponse: OAuthExchangeResponse | MfaChallengeResponse = await response.json();
+
+		if (isMfaChallenge(exchangeResponse)) {
+			authStore.setLoading(false);
+		} else {
+			authStore.handleAuthResponse(exchangeResponse);
+		}
+
+		return exchangeResponse;
+	} catch (error) {
+		authStore.setLoading(false);
+		const errorMessage = error instanceof ApiError ? error.message : 'OAuth login failed';
+		authStore.setError(errorMessage);
+		throw error;
+	}
+}
+
+/**
+ * Complete a sign-in that is waiting for its second factor
+ * @param mfaToken Token from the `mfa_pending` response
+ * @param code Authenticator app code or recovery code
+ * @returns User data and JWT token on success
+ */
+export async function verifyMfa(mfaToken: string, code: string): Promise<UnifiedAuthResponse> {
+	authStore.setLoading(true);
+	authStore.clearError();
+
+	try {
+		const response = await fetch(`${API_BASE_URL}/api/auth/mfa/verify`, {
+			method: 'POST',
 			headers: {
 				'Content-Type': 'application/json'
-			}
+			},
+			body: JSON.stringify({ mfa_token: mfaToken, code })
 		});
 
 		if (!response.ok) {
@@ -289,26 +443,32 @@ export async function handleOAuthCallback(
 			}
 
 			throw new ApiError(
-				errorData.error || `OAuth callback failed with status ${response.status}`,
+				errorData.error || `Verification failed with status ${response.status}`,
 				response.status,
 				errorData
 			);
 		}
 
-		const oauthResponse: UnifiedAuthResponse = await response.json();
+		const verifyResponse: UnifiedAuthResponse = await response.json();
 
-		// Update auth store with unified response
-		authStore.handleAuthResponse(oauthResponse);
+		authStore.handleAuthResponse(verifyResponse);
 
-		return oauthResponse;
+		return verifyResponse;
 	} catch (error) {
 		authStore.setLoading(false);
-		const errorMessage = error instanceof ApiError ? error.message : 'OAuth login failed';
+		const errorMessage = error instanceof ApiError ? error.message : 'Verification failed';
 		authStore.setError(errorMessage);
 		throw error;
 	}
 }
 
+/**
+ * Check whether a sign-in response asks for a second factor
+ */
+export function isMfaChallenge(response: object): response is MfaChallengeResponse {
+	return (response as MfaChallengeResponse).status === 'mfa_pending';
+}
+
 /**
  * Get current user data including payment status
  * @returns User data with payment status
@@ -319,11 +479,10 @@ export async function getCurrentUser(): Promise<UnifiedAuthResponse> {
 		throw new ApiError('No authentication token', 401);
 	}
 
-	const response = await fetch(`${API_BASE_URL}/api/users/me`, {
+	const response = await authFetch(`${API_BASE_URL}/api/users/me`, {
 		method: 'GET',
 		headers: {
-			'Content-Type': 'application/json',
-			Authorization: `Bearer ${token}`
+			'Content-Type': 'application/json'
 		}
 	});
 
@@ -359,3 +518,114 @@ export async function getCurrentUser(): Promise<UnifiedAuthResponse> {
 
 	return userData;
 }
+
+/**
+ * Get a command-line sign-in for the current user to approve
+ * @param state Flow handle from the verification URL
+ * @returns The device asking to sign in and the flow's status
+ */
+export async function getCliFlow(state: string): Promise<CliFlowInfo> {
+	const response = await authFetch(
+		`${API_BASE_URL}/api/auth/cli/flows/${encodeURIComponent(state)}`,
+		{ method: 'GET' }
+	);
+
+	if (!response.ok) {
+		throw await readApiError(response, 'Sign-in request');
+	}
+
+	return response.json();
+}
+
+/**
+ * Approve a command-line sign-in for the current user
+ * @param state Flow handle from the verification URL
+ * @returns The code for the tool, and where to send it if the tool is listening
+ */
+export async function approveCliFlow(state: string): Promise<ApproveCliFlowResponse> {
+	const response = await authFetch(
+		`${API_BASE_URL}/api/auth/cli/flows/${encodeURIComponent(state)}/approve`,
+		{ method: 'POST' }
+	);
+
+	if (!response.ok) {
+		throw await readApiError(response, 'Sign-in request');
+	}
+
+	return response.json();
+}
+
+/**
+ * Confirm the account's email address with the token from the emailed link
+ * @param token Token from the link
+ */
+export async function confirmEmail(token: string): Promise<void> {
+	const response = await fetch(`${API_BASE_URL}/api/auth/verify-email/confirm`, {
+		method: 'POST',
+		headers: {
+			'Content-Type': 'application/json'
+		},
+		body: JSON.stringify({ token })
+	});
+
+	if (!response.ok) {
+		throw await readApiError(response, 'Email verification');
+	}
+}
+
+/**
+ * Mail a password reset link; the server answers the same whether or not the
+ * email has an account
+ * @param email Email of the account
+ */
+export async function requestPasswordReset(email: string): Promise<void> {
+	const response = await fetch(`${API_BASE_URL}/api/auth/password-reset/request`, {
+		method: 'POST',
+		headers: {
+			'Content-Type': 'application/json'
+		},
+		body: JSON.stringify({ email })
+	});
+
+	if (!response.ok) {
+		throw await readApiError(response, 'Password reset request');
+	}
+}
+
+/**
+ * Set a new password with the token from the emailed link
+ *
+ * The server ends all of the user's sessions, so this device is signed out too.
+ * @param token Token from the link
+ * @param password New password
+ */
+export async function confirmPasswordReset(token: string, password: string): Promise<void> {
+	const response = await fetch(`${API_BASE_URL}/api/auth/password-reset/confirm`, {
+		method: 'POST',
+		headers: {
+			'Content-Type': 'application/json'
+		},
+		body: JSON.stringify({ token, password })
+	});
+
+	if (!response.ok) {
+		throw await readApiError(response, 'Password reset');
+	}
+
+	authStore.logout();
+}
+
+async function readApiError(response: Response, action: string): Promise<ApiError> {
+	let errorData: AuthError;
+	try {
+		errorData = await response.json();
+	} catch {
+		errorData = { error: `HTTP ${response.status}: ${response.statusText}` };
+	}
+
+	return new ApiError(
+		errorData.error || `${action} failed with status ${response.status}`,
+		response.status,
+		errorData
+	);
+}
@@ -63,6 +63,12 @@ export class AuthFlowManager {
 			return '/login';
 		}
 
+		// Pages that sent the user to sign in come first
+		const returnPath = StorageService.takeReturnPath();
+		if (returnPath) {
+			return returnPath;
+		}
+
 		const needsPayment = await this.needsPayment();
 		return needsPayment ? '/payment' : SUCCESS_ROUTE;
 	}
@@ -103,7 +109,8 @@ export class AuthFlowManager {
 		await authStore.waitForInit();
 
 		if (!StorageService.isAuthenticated()) {
-			// User needs to authenticate
+			// User needs to authenticate, then comes back here
+			StorageService.setReturnPath(window.location.pathname + window.location.search);
 			window.location.href = '/login';
 			return false;
 		}
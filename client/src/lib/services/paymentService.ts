@@ -18,7 +18,7 @@ import {
 const STRIPE_PUBLISHABLE_KEY = import.meta.env.VITE_STRIPE_PUBLISHABLE_KEY || '';
 
 // Import the ApiError from apiAuth
-import { ApiError } from './apiAuth';
+import { ApiError, authFetch } from './apiAuth';
 
 interface CreatePaymentIntentRequest {
 	amount_cents: number;
@@ -202,14 +202,8 @@ export class PaymentService {
 			Object.assign(headers, options.headers);
 		}
 
-		// Add Authorization header if available
-		// Use the same key as authStore uses: 'auth_token'
-		const token = localStorage.getItem('auth_token');
-		if (token) {
-			headers.Authorization = `Bearer ${token}`;
-		}
-
-		const response = await fetch(url, {
+		// authFetch adds the Authorization header and renews an expired token
+		const response = await authFetch(url, {
 			...options,
 			headers
 		});
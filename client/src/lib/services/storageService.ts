@@ -13,9 +13,11 @@ import type { User, PaymentUser } from '$lib/types/auth';
 
 // Storage keys
 const AUTH_TOKEN_KEY = 'auth_token';
+const REFRESH_TOKEN_KEY = 'refresh_token';
 const AUTH_USER_KEY = 'auth_user';
 const PAYMENT_USER_KEY = 'payment_user';
 const PAYMENT_USER_TIMESTAMP_KEY = 'payment_user_timestamp';
+const RETURN_PATH_KEY = 'auth_return_path';
 
 // Cache duration for payment status (5 minutes)
 const PAYMENT_CACHE_DURATION = 5 * 60 * 1000;
@@ -46,6 +48,31 @@ export class StorageService {
 		}
 	}
 
+	/**
+	 * Store refresh token in localStorage
+	 */
+	static setRefreshToken(token: string): void {
+		if (!browser) return;
+		try {
+			localStorage.setItem(REFRESH_TOKEN_KEY, token);
+		} catch (error) {
+			console.error('[StorageService] Failed to store refresh token:', error);
+		}
+	}
+
+	/**
+	 * Get refresh token from localStorage
+	 */
+	static getRefreshToken(): string | null {
+		if (!browser) return null;
+		try {
+			return localStorage.getItem(REFRESH_TOKEN_KEY);
+		} catch (error) {
+			console.error('[StorageService] Failed to get refresh token:', error);
+			return null;
+		}
+	}
+
 	/**
 	 * Store auth user in localStorage
 	 */
@@ -116,6 +143,35 @@ export class StorageService {
 		}
 	}
 
+	/**
+	 * Remember the page to return to after signing in, in sessionStorage
+	 */
+	static setReturnPath(path: string): void {
+		if (!browser) return;
+		try {
+			sessionStorage.setItem(RETURN_PATH_KEY, path);
+		} catch (error) {
+			console.error('[StorageService] Failed to store return path:', error);
+		}
+	}
+
+	/**
+	 * Get and forget the page to return to after signing in
+	 * @returns The path, if one was stored and it is on this site
+	 */
+	static takeReturnPath(): string | null {
+		if (!browser) return null;
+		try {
+			const path = sessionStorage.getItem(RETURN_PATH_KEY);
+			sessionStorage.removeItem(RETURN_PATH_KEY);
+			// Only same-site paths; '//host' would leave the site
+			return path?.startsWith('/') && !path.startsWith('//') ? path : null;
+		} catch (error) {
+			console.error('[StorageService] Failed to get return path:', error);
+			return null;
+		}
+	}
+
 	/**
 	 * Clear auth data from localStorage
 	 */
@@ -123,6 +179,7 @@ export class StorageService {
 		if (!browser) return;
 		try {
 			localStorage.removeItem(AUTH_TOKEN_KEY);
+			localStorage.removeItem(REFRESH_TOKEN_KEY);
 			localStorage.removeItem(AUTH_USER_KEY);
 		} catch (error) {
 			console.error('[StorageService] Failed to clear auth data:', error);
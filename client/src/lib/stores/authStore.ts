@@ -130,6 +130,9 @@ function createAuthStore() {
 			if (browser) {
 				console.log('[AuthStore] Storing auth data');
 				StorageService.setAuthToken(response.auth_token);
+				if (response.refresh_token) {
+					StorageService.setRefreshToken(response.refresh_token);
+				}
 				StorageService.setAuthUser(response.auth_user);
 				StorageService.setPaymentUser(response.payment_user);
 			}
@@ -148,6 +151,16 @@ function createAuthStore() {
 			});
 		},
 
+		// Replace the tokens after a refresh, keeping the user data
+		setTokens: (authToken: string, refreshToken: string) => {
+			if (browser) {
+				StorageService.setAuthToken(authToken);
+				StorageService.setRefreshToken(refreshToken);
+			}
+
+			update((state) => ({ ...state, token: authToken }));
+		},
+
 		// Legacy login success - for backward compatibility
 		loginSuccess: (user: User, token: string) => {
 			console.warn('[AuthStore] loginSuccess is deprecated, use handleAuthResponse instead');
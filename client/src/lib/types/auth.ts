@@ -25,6 +25,29 @@ export interface UnifiedAuthResponse {
 	auth_token: string;
 	auth_user: User;
 	payment_user: PaymentUser;
+	/** Long-lived token to get new access tokens with, when a session was started */
+	refresh_token?: string;
+	/** Access token lifetime in seconds */
+	expires_in?: number;
+}
+
+/** Returned instead of a session when the user still has to enter a second factor */
+export interface MfaChallengeResponse {
+	status: 'mfa_pending';
+	/** Token to send to the verify endpoint along with the code */
+	mfa_token: string;
+	/** Token lifetime in seconds */
+	expires_in: number;
+}
+
+export interface OAuthExchangeResponse extends UnifiedAuthResponse {
+	is_new_user: boolean;
+}
+
+export interface SessionTokenResponse {
+	auth_token: string;
+	refresh_token: string;
+	expires_in: number;
 }
 
 export interface AuthState {
@@ -54,3 +77,17 @@ export type OAuthLoginResponse = UnifiedAuthResponse;
 export interface AuthError {
 	error: string;
 }
+
+/** A command-line sign-in waiting for the user's approval */
+export interface CliFlowInfo {
+	device_name: string;
+	status: 'pending' | 'approved' | 'completed' | 'expired';
+	expires_at: string;
+}
+
+export interface ApproveCliFlowResponse {
+	/** One-time code the command-line tool exchanges for tokens */
+	code: string;
+	/** Loopback URL carrying the code, when the tool is listening for it */
+	redirect_url?: string;
+}
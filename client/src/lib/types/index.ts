@@ -11,6 +11,10 @@ export type {
 	RegisterRequest,
 	LoginResponse,
 	OAuthLoginResponse,
+	OAuthExchangeResponse,
+	MfaChallengeResponse,
+	CliFlowInfo,
+	ApproveCliFlowResponse,
 	AuthError
 } from './auth';
 
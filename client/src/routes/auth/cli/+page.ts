@@ -0,0 +1,8 @@
+import type { PageLoad } from './$types';
+
+export const load: PageLoad = async ({ url }) => {
+	// The command-line tool opens this page with the handle of its sign-in flow
+	return {
+		state: url.searchParams.get('state')
+	};
+};
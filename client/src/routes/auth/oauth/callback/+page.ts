@@ -1,16 +1,12 @@
 import type { PageLoad } from './$types';
 
 export const load: PageLoad = async ({ url }) => {
-	// Extract OAuth parameters from URL
+	// Extract OAuth parameters from URL; the session itself comes from exchanging the code
 	const params = url.searchParams;
 
 	return {
-		token: params.get('token'),
-		userId: params.get('user_id'),
-		email: params.get('email'),
+		code: params.get('code'),
 		error: params.get('error'),
-		isNewUser: params.get('is_new_user') === 'true',
-		paymentRequired: params.get('payment_required') === 'true',
-		hasValidInvite: params.get('has_valid_invite') === 'true'
+		isNewUser: params.get('is_new_user') === 'true'
 	};
 };
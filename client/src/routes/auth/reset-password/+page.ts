@@ -0,0 +1,8 @@
+import type { PageLoad } from './$types';
+
+export const load: PageLoad = async ({ url }) => {
+	// The emailed link carries a one-time token
+	return {
+		token: url.searchParams.get('token')
+	};
+};
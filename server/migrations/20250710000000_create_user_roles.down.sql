@@ -0,0 +1,3 @@
+-- Drop user_roles table and related objects
+DROP INDEX IF EXISTS idx_user_roles_role;
+DROP TABLE IF EXISTS user_roles;
@@ -0,0 +1,12 @@
+-- Create user_roles table for role-based authorization
+-- Permissions are derived from roles in code (see models/role.rs)
+CREATE TABLE user_roles (
+    user_id TEXT NOT NULL,                           -- Foreign key to users table
+    role TEXT NOT NULL CHECK (role IN ('admin', 'invite_manager')),
+    granted_by TEXT,                                 -- User ID of the granting admin, or 'system'
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, role),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_user_roles_role ON user_roles(role);
@@ -0,0 +1,36 @@
+-- Rebuild cli_auth_flows so a flow can start before the device is registered.
+-- The device row is only created once the approved flow is exchanged for tokens,
+-- so the flow records the requested device details and the approving user.
+DROP INDEX IF EXISTS idx_cli_auth_flows_status;
+DROP INDEX IF EXISTS idx_cli_auth_flows_expires_at;
+DROP INDEX IF EXISTS idx_cli_auth_flows_device_id;
+DROP INDEX IF EXISTS idx_cli_auth_flows_state;
+DROP TABLE IF EXISTS cli_auth_flows;
+
+CREATE TABLE cli_auth_flows (
+    id TEXT PRIMARY KEY NOT NULL,                    -- UUID
+    device_id TEXT,                                  -- Foreign key to cli_devices (set on token exchange)
+    device_name TEXT NOT NULL,                       -- Device name requested by the CLI
+    device_fingerprint TEXT NOT NULL,                -- Device fingerprint requested by the CLI
+    user_id TEXT,                                    -- User who approved the flow
+    state TEXT NOT NULL UNIQUE,                      -- Opaque flow handle, also used for CSRF protection
+    code_challenge TEXT NOT NULL,                    -- PKCE code challenge (SHA256 of verifier)
+    challenge_method TEXT NOT NULL DEFAULT 'S256',   -- PKCE challenge method (always S256)
+    redirect_uri TEXT,                               -- Optional loopback URI the browser returns the code to
+    status TEXT NOT NULL DEFAULT 'pending',          -- Flow status: pending, approved, completed, expired
+    auth_code TEXT,                                  -- SHA256 hash of the authorization code (set on approval)
+    expires_at DATETIME NOT NULL,                    -- Flow expiration time
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    approved_at DATETIME,                            -- When the user approved the flow
+    completed_at DATETIME,                           -- When the code was exchanged for tokens
+    FOREIGN KEY (device_id) REFERENCES cli_devices(id) ON DELETE SET NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    CHECK (status IN ('pending', 'approved', 'completed', 'expired')),
+    CHECK (challenge_method = 'S256')
+);
+
+-- Indexes for efficient querying
+CREATE INDEX idx_cli_auth_flows_state ON cli_auth_flows(state);
+CREATE INDEX idx_cli_auth_flows_device_id ON cli_auth_flows(device_id);
+CREATE INDEX idx_cli_auth_flows_expires_at ON cli_auth_flows(expires_at);
+CREATE INDEX idx_cli_auth_flows_status ON cli_auth_flows(status) WHERE status = 'pending';
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_issues_board_created;
+DROP TABLE IF EXISTS board_members;
+DROP TABLE IF EXISTS boards;
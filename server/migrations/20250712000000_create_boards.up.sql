@@ -0,0 +1,28 @@
+-- Create boards table; issues (see create_issues) belong to a board
+CREATE TABLE boards (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT,
+    owner_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_boards_owner_id ON boards(owner_id);
+
+-- Board membership; only members can see a board and its issues
+CREATE TABLE board_members (
+    board_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('owner', 'member')),
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (board_id, user_id),
+    FOREIGN KEY (board_id) REFERENCES boards(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_board_members_user_id ON board_members(user_id);
+
+-- Supports the default issue ordering and cursor pagination within a board
+CREATE INDEX idx_issues_board_created ON issues(board_id, created_at, id);
@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS delete_conversation_embeddings;
+DROP TRIGGER IF EXISTS delete_issue_embeddings;
+DROP TABLE IF EXISTS embeddings;
@@ -0,0 +1,27 @@
+-- Embedding vectors for similarity search over issues and conversations
+--
+-- Vectors are little-endian f32 arrays. Each entity keeps one vector per model,
+-- since vectors from different models cannot be compared; content_hash records
+-- the text a vector was computed from so stale vectors can be refreshed.
+CREATE TABLE embeddings (
+    entity_type TEXT NOT NULL CHECK (entity_type IN ('issue', 'conversation')),
+    entity_id TEXT NOT NULL,
+    model TEXT NOT NULL,
+    dimensions INTEGER NOT NULL,
+    vector BLOB NOT NULL,
+    content_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (entity_type, entity_id, model)
+);
+
+-- Embeddings have no foreign key to their entity, so remove them with it
+CREATE TRIGGER delete_issue_embeddings AFTER DELETE ON issues
+BEGIN
+    DELETE FROM embeddings WHERE entity_type = 'issue' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER delete_conversation_embeddings AFTER DELETE ON ai_conversations
+BEGIN
+    DELETE FROM embeddings WHERE entity_type = 'conversation' AND entity_id = OLD.id;
+END;
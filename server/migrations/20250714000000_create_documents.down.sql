@@ -0,0 +1,35 @@
+DROP TRIGGER IF EXISTS document_chunks_delete;
+DROP TRIGGER IF EXISTS document_chunks_insert_fts;
+DROP TABLE IF EXISTS document_chunks_fts;
+DROP TABLE IF EXISTS document_chunks;
+DROP TABLE IF EXISTS documents;
+
+-- Restore the embeddings table without document chunks
+DROP TRIGGER IF EXISTS delete_issue_embeddings;
+DROP TRIGGER IF EXISTS delete_conversation_embeddings;
+
+CREATE TABLE embeddings_old (
+    entity_type TEXT NOT NULL CHECK (entity_type IN ('issue', 'conversation')),
+    entity_id TEXT NOT NULL,
+    model TEXT NOT NULL,
+    dimensions INTEGER NOT NULL,
+    vector BLOB NOT NULL,
+    content_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (entity_type, entity_id, model)
+);
+
+INSERT INTO embeddings_old SELECT * FROM embeddings WHERE entity_type != 'document_chunk';
+DROP TABLE embeddings;
+ALTER TABLE embeddings_old RENAME TO embeddings;
+
+CREATE TRIGGER delete_issue_embeddings AFTER DELETE ON issues
+BEGIN
+    DELETE FROM embeddings WHERE entity_type = 'issue' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER delete_conversation_embeddings AFTER DELETE ON ai_conversations
+BEGIN
+    DELETE FROM embeddings WHERE entity_type = 'conversation' AND entity_id = OLD.id;
+END;
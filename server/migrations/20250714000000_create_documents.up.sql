@@ -0,0 +1,75 @@
+-- Documents uploaded for retrieval-augmented chat, with their text split into chunks
+CREATE TABLE documents (
+    id TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    content_type TEXT,
+    size INTEGER NOT NULL,
+    content_hash TEXT NOT NULL,
+    chunk_count INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_documents_user_created ON documents(user_id, created_at);
+
+-- The integer key doubles as the full-text index's rowid
+CREATE TABLE document_chunks (
+    id INTEGER PRIMARY KEY,
+    document_id TEXT NOT NULL,
+    chunk_index INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    UNIQUE (document_id, chunk_index),
+    FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+-- Full-text index over chunk contents, kept in step with document_chunks by triggers
+CREATE VIRTUAL TABLE document_chunks_fts USING fts5(
+    content,
+    content = 'document_chunks',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61'
+);
+
+CREATE TRIGGER document_chunks_insert_fts AFTER INSERT ON document_chunks
+BEGIN
+    INSERT INTO document_chunks_fts (rowid, content) VALUES (NEW.id, NEW.content);
+END;
+
+-- Rebuild embeddings so chunks can have vectors too; SQLite cannot alter a CHECK
+DROP TRIGGER delete_issue_embeddings;
+DROP TRIGGER delete_conversation_embeddings;
+
+CREATE TABLE embeddings_new (
+    entity_type TEXT NOT NULL CHECK (entity_type IN ('issue', 'conversation', 'document_chunk')),
+    entity_id TEXT NOT NULL,
+    model TEXT NOT NULL,
+    dimensions INTEGER NOT NULL,
+    vector BLOB NOT NULL,
+    content_hash TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (entity_type, entity_id, model)
+);
+
+INSERT INTO embeddings_new SELECT * FROM embeddings;
+DROP TABLE embeddings;
+ALTER TABLE embeddings_new RENAME TO embeddings;
+
+CREATE TRIGGER delete_issue_embeddings AFTER DELETE ON issues
+BEGIN
+    DELETE FROM embeddings WHERE entity_type = 'issue' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER delete_conversation_embeddings AFTER DELETE ON ai_conversations
+BEGIN
+    DELETE FROM embeddings WHERE entity_type = 'conversation' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER document_chunks_delete AFTER DELETE ON document_chunks
+BEGIN
+    INSERT INTO document_chunks_fts (document_chunks_fts, rowid, content)
+    VALUES ('delete', OLD.id, OLD.content);
+    DELETE FROM embeddings
+    WHERE entity_type = 'document_chunk' AND entity_id = CAST(OLD.id AS TEXT);
+END;
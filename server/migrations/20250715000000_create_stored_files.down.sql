@@ -0,0 +1,7 @@
+DROP TRIGGER IF EXISTS stored_files_delete_session_asset;
+DROP INDEX IF EXISTS idx_stored_files_conversation_id;
+DROP INDEX IF EXISTS idx_stored_files_session_id;
+DROP INDEX IF EXISTS idx_stored_files_content_hash;
+DROP INDEX IF EXISTS idx_stored_files_user_created;
+DROP TABLE IF EXISTS stored_files;
+DROP TABLE IF EXISTS stored_objects;
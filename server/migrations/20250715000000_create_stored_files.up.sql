@@ -0,0 +1,40 @@
+-- Blobs in the storage backend, one per distinct content
+CREATE TABLE stored_objects (
+    content_hash TEXT PRIMARY KEY NOT NULL,          -- SHA-256 of the bytes, hex
+    storage_key TEXT NOT NULL UNIQUE,                -- Key in the storage backend
+    size INTEGER NOT NULL,
+    last_used_at TEXT NOT NULL                       -- Last upload of this content; garbage collection spares recent blobs
+);
+
+-- Files users uploaded, each pointing at the blob holding its bytes
+CREATE TABLE stored_files (
+    id TEXT PRIMARY KEY NOT NULL,                    -- file_{uuid}
+    user_id TEXT NOT NULL,
+    content_hash TEXT NOT NULL,
+    name TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    session_id TEXT,                                 -- AI session the file was uploaded to
+    conversation_id TEXT,                            -- Conversation the file was uploaded to
+    document_id TEXT,                                -- Document made from the file's text
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (content_hash) REFERENCES stored_objects(content_hash),
+    FOREIGN KEY (session_id) REFERENCES ai_sessions(id) ON DELETE CASCADE,
+    FOREIGN KEY (conversation_id) REFERENCES ai_conversations(id) ON DELETE CASCADE,
+    FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_stored_files_user_created ON stored_files(user_id, created_at);
+CREATE INDEX idx_stored_files_content_hash ON stored_files(content_hash);
+CREATE INDEX idx_stored_files_session_id ON stored_files(session_id);
+CREATE INDEX idx_stored_files_conversation_id ON stored_files(conversation_id);
+
+-- A session's assets are files uploaded to it; deleting the file removes the asset
+CREATE TRIGGER stored_files_delete_session_asset
+    AFTER DELETE ON stored_files
+    FOR EACH ROW
+    WHEN OLD.session_id IS NOT NULL
+BEGIN
+    DELETE FROM ai_session_assets WHERE id = OLD.id;
+END;
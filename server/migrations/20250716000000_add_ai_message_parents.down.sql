@@ -0,0 +1,21 @@
+-- Restore ai_messages without parents
+DROP INDEX IF EXISTS idx_ai_messages_parent_message_id;
+
+CREATE TABLE ai_messages_old (
+	id TEXT PRIMARY KEY NOT NULL,
+	conversation_id TEXT NOT NULL,
+	role TEXT NOT NULL,
+	content TEXT NOT NULL,
+	token_count INTEGER,
+	created_at TEXT NOT NULL,
+	metadata TEXT,
+	FOREIGN KEY (conversation_id) REFERENCES ai_conversations(id) ON DELETE CASCADE
+);
+
+INSERT INTO ai_messages_old
+SELECT id, conversation_id, role, content, token_count, created_at, metadata FROM ai_messages;
+DROP TABLE ai_messages;
+ALTER TABLE ai_messages_old RENAME TO ai_messages;
+
+CREATE INDEX idx_ai_messages_conversation_id ON ai_messages(conversation_id);
+CREATE INDEX idx_ai_messages_created_at ON ai_messages(created_at);
@@ -0,0 +1,17 @@
+-- Messages form a tree: each follows its parent, and regenerating or editing an
+-- earlier turn forks a new branch from it
+ALTER TABLE ai_messages ADD COLUMN parent_message_id TEXT REFERENCES ai_messages(id) ON DELETE CASCADE;
+
+-- Existing conversations are a single branch in creation order
+UPDATE ai_messages
+SET parent_message_id = (
+    SELECT previous.id
+    FROM ai_messages previous
+    WHERE previous.conversation_id = ai_messages.conversation_id
+      AND (previous.created_at < ai_messages.created_at
+           OR (previous.created_at = ai_messages.created_at AND previous.rowid < ai_messages.rowid))
+    ORDER BY previous.created_at DESC, previous.rowid DESC
+    LIMIT 1
+);
+
+CREATE INDEX idx_ai_messages_parent_message_id ON ai_messages(parent_message_id);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS ai_user_quotas;
+DROP INDEX IF EXISTS idx_ai_usage_user_created;
+ALTER TABLE ai_usage DROP COLUMN cost_microcents;
@@ -0,0 +1,18 @@
+-- Exact cost of each request; cost_cents is this rounded to whole cents
+ALTER TABLE ai_usage ADD COLUMN cost_microcents INTEGER;
+
+-- Quotas sum a user's usage since the start of the day or month
+CREATE INDEX idx_ai_usage_user_created ON ai_usage(user_id, created_at);
+
+-- Quotas set for a single user, replacing their plan's limits; NULL keeps the
+-- plan's limit
+CREATE TABLE ai_user_quotas (
+    user_id TEXT PRIMARY KEY NOT NULL,
+    daily_tokens INTEGER,
+    monthly_tokens INTEGER,
+    daily_cents INTEGER,
+    monthly_cents INTEGER,
+    updated_by TEXT,                                 -- Admin who set the quota
+    updated_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,23 @@
+-- Sign-in identities from OAuth providers; a user may link several providers
+CREATE TABLE user_identities (
+    provider TEXT NOT NULL,                          -- e.g. 'google', 'github'
+    provider_user_id TEXT NOT NULL,                  -- The provider's ID for the account
+    user_id TEXT NOT NULL,                           -- Foreign key to users table
+    email TEXT,                                      -- Email the provider reported
+    email_verified BOOLEAN NOT NULL DEFAULT FALSE,   -- Whether the provider verified it
+    created_at DATETIME NOT NULL,
+    last_login_at DATETIME,
+    PRIMARY KEY (provider, provider_user_id),
+    UNIQUE (user_id, provider),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Users created by OAuth sign-in so far keep their provider account; whether
+-- its email was verified was not recorded
+INSERT INTO user_identities (provider, provider_user_id, user_id, email, created_at)
+SELECT provider, provider_user_id, id, email, created_at
+FROM users
+WHERE provider != 'local' AND provider_user_id IS NOT NULL;
+
+-- OAuth flows started by a signed-in user to link a provider to their account
+ALTER TABLE oauth_states ADD COLUMN link_user_id TEXT;
@@ -0,0 +1,3 @@
+-- PKCE code verifier and ID token nonce of OpenID Connect sign-ins
+ALTER TABLE oauth_states ADD COLUMN pkce_verifier TEXT;
+ALTER TABLE oauth_states ADD COLUMN nonce TEXT;
@@ -0,0 +1,14 @@
+-- One-time codes handing a completed OAuth sign-in over to the client.
+-- The callback redirects with the code instead of tokens; the client exchanges it
+-- once, shortly after, for a session.
+CREATE TABLE oauth_login_codes (
+    code_hash TEXT PRIMARY KEY NOT NULL,             -- SHA256 hash of the code
+    user_id TEXT NOT NULL,                           -- User who signed in
+    is_new_user BOOLEAN NOT NULL DEFAULT FALSE,      -- Whether the sign-in created the user
+    expires_at DATETIME NOT NULL,                    -- Code expiration time
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    used_at DATETIME,                                -- When the code was exchanged
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_oauth_login_codes_expires_at ON oauth_login_codes(expires_at);
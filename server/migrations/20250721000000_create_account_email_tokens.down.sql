@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_account_email_tokens_expires_at;
+DROP INDEX IF EXISTS idx_account_email_tokens_user;
+DROP TABLE IF EXISTS account_email_tokens;
+ALTER TABLE users DROP COLUMN email_verified_at;
@@ -0,0 +1,21 @@
+-- Email verification and password reset
+ALTER TABLE users ADD COLUMN email_verified_at DATETIME;
+
+-- Emails of provider accounts were verified by the provider
+UPDATE users SET email_verified_at = created_at WHERE provider != 'local';
+
+-- Tokens mailed to users to verify their email or reset their password
+CREATE TABLE account_email_tokens (
+    token_hash TEXT PRIMARY KEY NOT NULL,            -- SHA256 hash of the token
+    user_id TEXT NOT NULL,                           -- User the token was issued for
+    purpose TEXT NOT NULL,                           -- What the token confirms
+    email TEXT NOT NULL,                             -- Address the token was sent to
+    expires_at DATETIME NOT NULL,                    -- Token expiration time
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    used_at DATETIME,                                -- When the token was used
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    CHECK (purpose IN ('verify_email', 'reset_password'))
+);
+
+CREATE INDEX idx_account_email_tokens_user ON account_email_tokens(user_id, purpose);
+CREATE INDEX idx_account_email_tokens_expires_at ON account_email_tokens(expires_at);
@@ -0,0 +1,43 @@
+-- TOTP two-factor authentication
+-- A row is created when a user starts enrolling; the factor is only enabled
+-- once the user proves their authenticator app produces valid codes.
+CREATE TABLE user_mfa (
+    user_id TEXT PRIMARY KEY NOT NULL,               -- User the factor belongs to
+    secret TEXT NOT NULL,                            -- Base32 TOTP secret shared with the authenticator app
+    enabled_at DATETIME,                             -- When enrollment was verified, NULL while pending
+    last_used_step INTEGER,                          -- Time step of the last accepted code, to reject replays
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- One-time recovery codes for when the authenticator app is lost
+CREATE TABLE mfa_recovery_codes (
+    code_hash TEXT PRIMARY KEY NOT NULL,             -- SHA256 hash of the normalized code
+    user_id TEXT NOT NULL,                           -- User the code belongs to
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    used_at DATETIME,                                -- When the code was used
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_mfa_recovery_codes_user ON mfa_recovery_codes(user_id);
+
+-- Sign-ins waiting for their second factor. The password step hands out the
+-- token, which can only be exchanged for a session with a valid code.
+CREATE TABLE mfa_challenges (
+    token_hash TEXT PRIMARY KEY NOT NULL,            -- SHA256 hash of the mfa_pending token
+    user_id TEXT NOT NULL,                           -- User signing in
+    failed_attempts INTEGER NOT NULL DEFAULT 0,      -- Invalid codes tried with the token
+    expires_at DATETIME NOT NULL,                    -- Token expiration time
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    used_at DATETIME,                                -- When the sign-in was completed
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_mfa_challenges_expires_at ON mfa_challenges(expires_at);
+
+-- Roles whose holders must use two-factor authentication
+CREATE TABLE role_mfa_requirements (
+    role TEXT PRIMARY KEY NOT NULL CHECK (role IN ('admin', 'invite_manager')),
+    required_by TEXT,                                -- User ID of the admin who set the requirement
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,12 @@
+-- Accounts registered before email verification existed had no way to verify,
+-- so they keep the payment and board member access that now requires it. This
+-- is not proof of owning the address: linking provider sign-ins by email still
+-- needs a verified email.
+ALTER TABLE users ADD COLUMN verification_grandfathered BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Only accounts created before the verification migration ran
+UPDATE users SET verification_grandfathered = TRUE
+WHERE email_verified_at IS NULL
+  AND datetime(created_at) < (
+      SELECT datetime(installed_on) FROM _sqlx_migrations WHERE version = 20250721000000
+  );
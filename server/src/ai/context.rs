@@ -0,0 +1,134 @@
+//! Context window sizes of models
+//!
+//! A model's window is the number of tokens its prompt and reply share. Windows
+//! are found by the longest matching model name prefix, ignoring any
+//! `provider/` prefix, with `AI_CONTEXT_WINDOWS` checked before the built-in
+//! table and `AI_CONTEXT_WINDOW_DEFAULT` used for models in neither.
+
+use crate::ai::{AiError, AiResult};
+
+/// Window of models without a known one when `AI_CONTEXT_WINDOW_DEFAULT` is not set
+const DEFAULT_CONTEXT_WINDOW: usize = 8_192;
+
+/// Model name prefixes and the context windows of those models
+const MODEL_CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-5", 400_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("claude", 200_000),
+    ("gemini", 1_048_576),
+    ("llama-3.1", 131_072),
+    ("mistral-large", 128_000),
+];
+
+/// Context windows by model
+#[derive(Debug, Clone)]
+pub struct ContextWindows {
+    /// Model name prefixes and windows, checked before the built-in ones
+    models: Vec<(String, usize)>,
+    default_window: usize,
+}
+
+impl Default for ContextWindows {
+    fn default() -> Self {
+        Self {
+            models: Vec::new(),
+            default_window: DEFAULT_CONTEXT_WINDOW,
+        }
+    }
+}
+
+impl ContextWindows {
+    /// Load context windows from environment configuration
+    ///
+    /// - `AI_CONTEXT_WINDOWS`: extra `prefix=tokens` pairs, comma separated
+    /// - `AI_CONTEXT_WINDOW_DEFAULT`: window of other models (default 8192)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either variable is malformed
+    pub fn from_env() -> AiResult<Self> {
+        let mut windows = Self::default();
+
+        if let Ok(models) = std::env::var("AI_CONTEXT_WINDOWS") {
+            for pair in models.split(',').filter(|pair| !pair.trim().is_empty()) {
+                let (prefix, tokens) = pair
+                    .split_once('=')
+                    .and_then(|(prefix, tokens)| {
+                        let tokens = tokens.trim().parse().ok().filter(|tokens| *tokens > 0)?;
+                        Some((prefix.trim(), tokens)).filter(|(prefix, _)| !prefix.is_empty())
+                    })
+                    .ok_or_else(|| {
+                        AiError::Configuration(format!(
+                            "AI_CONTEXT_WINDOWS entries must be 'prefix=tokens', got '{pair}'"
+                        ))
+                    })?;
+                windows = windows.with_model(prefix, tokens);
+            }
+        }
+        if let Ok(value) = std::env::var("AI_CONTEXT_WINDOW_DEFAULT") {
+            windows.default_window = value
+                .trim()
+                .parse()
+                .ok()
+                .filter(|tokens| *tokens > 0)
+                .ok_or_else(|| {
+                    AiError::Configuration(format!(
+                        "AI_CONTEXT_WINDOW_DEFAULT must be a positive number, got '{value}'"
+                    ))
+                })?;
+        }
+        Ok(windows)
+    }
+
+    /// Give models whose names start with a prefix a context window
+    #[must_use]
+    pub fn with_model(mut self, prefix: &str, tokens: usize) -> Self {
+        self.models.push((prefix.to_lowercase(), tokens));
+        self
+    }
+
+    /// Context window of a model, in tokens
+    #[must_use]
+    pub fn window_for(&self, model: &str) -> usize {
+        let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+        let configured = self
+            .models
+            .iter()
+            .filter(|(prefix, _)| name.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, tokens)| *tokens);
+        configured
+            .or_else(|| {
+                MODEL_CONTEXT_WINDOWS
+                    .iter()
+                    .filter(|(prefix, _)| name.starts_with(prefix))
+                    .max_by_key(|(prefix, _)| prefix.len())
+                    .map(|(_, tokens)| *tokens)
+            })
+            .unwrap_or(self.default_window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_for_model() {
+        let windows = ContextWindows::default().with_model("gpt-4o-mini", 64_000);
+
+        assert_eq!(windows.window_for("gpt-4"), 8_192);
+        assert_eq!(windows.window_for("openai/gpt-4-turbo-preview"), 128_000);
+        assert_eq!(windows.window_for("anthropic/claude-sonnet-4"), 200_000);
+        assert_eq!(windows.window_for("openai/gpt-4o-mini"), 64_000);
+        assert_eq!(windows.window_for("unknown-model"), DEFAULT_CONTEXT_WINDOW);
+    }
+}
@@ -0,0 +1,218 @@
+//! Local text embeddings and vector helpers
+//!
+//! `LocalEmbedder` turns text into a fixed-size vector without a model or any
+//! network access, using the hashing trick: each word and each pair of adjacent
+//! words is hashed to one of the vector's dimensions and weighted by
+//! `1 + ln(count)`. Texts that share vocabulary end up with a high cosine
+//! similarity, which is enough to spot likely duplicates when no embedding
+//! model is configured.
+
+/// Model name recorded for vectors produced by `LocalEmbedder`
+pub const LOCAL_EMBEDDING_MODEL: &str = "local-hashing-v1";
+
+/// Dimensions of a local embedding
+pub const LOCAL_EMBEDDING_DIMENSIONS: usize = 512;
+
+/// Words too common to say anything about what a text is about
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "can", "do", "for", "from", "has",
+    "have", "i", "if", "in", "into", "is", "it", "its", "of", "on", "or", "our", "should", "so",
+    "that", "the", "their", "then", "there", "this", "to", "us", "was", "we", "when", "which",
+    "will", "with", "would", "you",
+];
+
+/// Bigrams count for less than single words, so shared vocabulary dominates
+const BIGRAM_WEIGHT: f32 = 0.5;
+
+/// Embeds text locally by feature hashing
+#[derive(Debug, Clone, Copy)]
+pub struct LocalEmbedder {
+    dimensions: usize,
+}
+
+impl Default for LocalEmbedder {
+    fn default() -> Self {
+        Self {
+            dimensions: LOCAL_EMBEDDING_DIMENSIONS,
+        }
+    }
+}
+
+impl LocalEmbedder {
+    /// Embed a text as a unit-length vector
+    ///
+    /// Text without any meaningful words embeds as the zero vector.
+    #[must_use]
+    pub fn embed(&self, text: &str) -> Vec<f32> {
+        let words = tokenize(text);
+        let mut counts = vec![0.0f32; self.dimensions];
+
+        for word in &words {
+            self.add_feature(&mut counts, word, 1.0);
+        }
+        for pair in words.windows(2) {
+            self.add_feature(
+                &mut counts,
+                &format!("{} {}", pair[0], pair[1]),
+                BIGRAM_WEIGHT,
+            );
+        }
+
+        // Sublinear term frequency keeps a repeated word from dominating
+        for value in &mut counts {
+            if *value != 0.0 {
+                *value = value.signum() * (1.0 + value.abs().ln());
+            }
+        }
+        normalize(&mut counts);
+        counts
+    }
+
+    fn add_feature(self, counts: &mut [f32], feature: &str, weight: f32) {
+        let hash = fnv1a(feature.as_bytes());
+        #[allow(clippy::cast_possible_truncation)]
+        let index = (hash % self.dimensions as u64) as usize;
+        // A second hash bit decides the sign, so collisions tend to cancel out
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        counts[index] += sign * weight;
+    }
+}
+
+/// Lowercase words of two or more letters or digits, without stop words
+///
+/// A trailing plural "s" is dropped so "issue" and "issues" match.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| word.chars().count() > 1 && !STOP_WORDS.contains(&word.as_str()))
+        .map(|word| {
+            if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+                word[..word.len() - 1].to_string()
+            } else {
+                word
+            }
+        })
+        .collect()
+}
+
+/// 64-bit FNV-1a, which is stable across platforms and releases so stored
+/// vectors stay comparable
+///
+/// FNV leaves short inputs poorly mixed in the low and high bits that pick the
+/// dimension and sign, so the result goes through the `MurmurHash3` finalizer.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+/// Scale a vector to unit length, leaving the zero vector as is
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        for value in vector {
+            *value /= norm;
+        }
+    }
+}
+
+/// Cosine similarity of two vectors, from -1.0 to 1.0
+///
+/// Vectors of different lengths, or where either is zero, have no similarity.
+#[must_use]
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let (dot, norm_a, norm_b) = a
+        .iter()
+        .zip(b)
+        .fold((0.0f32, 0.0f32, 0.0f32), |(dot, na, nb), (x, y)| {
+            (dot + x * y, na + x * x, nb + y * y)
+        });
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    (dot / (norm_a.sqrt() * norm_b.sqrt())).clamp(-1.0, 1.0)
+}
+
+/// Encode a vector for a BLOB column as little-endian `f32`s
+#[must_use]
+pub fn vector_to_bytes(vector: &[f32]) -> Vec<u8> {
+    vector
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+/// Decode a vector stored by `vector_to_bytes`
+///
+/// Returns `None` if the length is not a whole number of `f32`s.
+#[must_use]
+pub fn vector_from_bytes(bytes: &[u8]) -> Option<Vec<f32>> {
+    if !bytes.len().is_multiple_of(4) {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similar_texts_score_higher_than_unrelated_ones() {
+        let embedder = LocalEmbedder::default();
+        let login = embedder.embed("Users cannot log in with their Google account");
+        let login_again = embedder.embed("Login with a Google account fails for users");
+        let export = embedder.embed("Export the board as a CSV file");
+
+        let related = cosine_similarity(&login, &login_again);
+        let unrelated = cosine_similarity(&login, &export);
+        assert!(related > 0.4, "related similarity was {related}");
+        assert!(unrelated < 0.1, "unrelated similarity was {unrelated}");
+        assert!((cosine_similarity(&login, &login) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_embeddings_are_unit_length_and_stable() {
+        let embedder = LocalEmbedder::default();
+        let vector = embedder.embed("Dark mode for the settings page");
+        assert_eq!(vector.len(), LOCAL_EMBEDDING_DIMENSIONS);
+
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+        assert_eq!(vector, embedder.embed("dark MODE for the settings page!"));
+
+        let empty = embedder.embed("the and of");
+        assert!(empty.iter().all(|v| *v == 0.0));
+        assert!(cosine_similarity(&empty, &vector).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_tokenize_drops_stop_words_and_plurals() {
+        assert_eq!(
+            tokenize("The issues with boards, and a class!"),
+            ["issue", "board", "class"]
+        );
+    }
+
+    #[test]
+    fn test_vector_bytes_round_trip() {
+        let vector = vec![0.5, -1.25, 3.0];
+        let bytes = vector_to_bytes(&vector);
+        assert_eq!(bytes.len(), 12);
+        assert_eq!(vector_from_bytes(&bytes), Some(vector));
+        assert_eq!(vector_from_bytes(&bytes[..5]), None);
+    }
+}
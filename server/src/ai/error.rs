@@ -1,5 +1,6 @@
 //! AI-related error types
 
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,7 +15,16 @@ pub enum AiError {
     InvalidRequest(String),
 
     #[error("Rate limit exceeded")]
-    RateLimitExceeded,
+    RateLimitExceeded {
+        /// How long the provider asked us to wait, if it said
+        retry_after: Option<Duration>,
+    },
+
+    #[error("Request to the AI provider timed out")]
+    Timeout,
+
+    #[error("Upstream server error ({status}): {message}")]
+    Upstream { status: u16, message: String },
 
     #[error("OpenAI client error: {0}")]
     OpenAIClient(#[from] Box<dyn std::error::Error + Send + Sync>),
@@ -25,6 +35,13 @@ pub enum AiError {
     #[error("Schema validation error: {0}")]
     SchemaValidation(String),
 
+    #[error("Structured output still invalid after {} attempts: {message}", attempts.len())]
+    SchemaRepairFailed {
+        message: String,
+        /// Every attempt made, so their usage can still be recorded
+        attempts: Vec<crate::ai::models::SchemaAttempt>,
+    },
+
     #[error("Network error: {0}")]
     Network(#[from] reqwest::Error),
 
@@ -38,4 +55,83 @@ pub enum AiError {
     Unknown(String),
 }
 
+impl AiError {
+    /// Classify an unsuccessful HTTP response from a provider
+    #[must_use]
+    pub fn from_status(status: u16, retry_after: Option<Duration>, message: String) -> Self {
+        match status {
+            429 => Self::RateLimitExceeded { retry_after },
+            408 => Self::Timeout,
+            401 | 403 => Self::Configuration(format!(
+                "Provider rejected the credentials ({status}): {message}"
+            )),
+            400..=499 => Self::InvalidRequest(format!(
+                "Provider rejected the request ({status}): {message}"
+            )),
+            500..=599 => Self::Upstream { status, message },
+            _ => Self::Provider(format!("Unexpected response ({status}): {message}")),
+        }
+    }
+
+    /// Classify a transport failure, separating timeouts from other network errors
+    #[must_use]
+    pub fn from_transport(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            Self::Timeout
+        } else {
+            Self::Network(error)
+        }
+    }
+
+    /// Whether the same request may succeed if sent again
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimitExceeded { .. }
+                | Self::Timeout
+                | Self::Upstream { .. }
+                | Self::Network(_)
+        )
+    }
+
+    /// How long the provider asked us to wait before retrying, if it said
+    #[must_use]
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimitExceeded { retry_after } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
 pub type AiResult<T> = Result<T, AiError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_status_classifies_responses() {
+        let limited = AiError::from_status(429, Some(Duration::from_secs(3)), String::new());
+        assert!(limited.is_retryable());
+        assert_eq!(limited.retry_after(), Some(Duration::from_secs(3)));
+
+        assert!(matches!(
+            AiError::from_status(503, None, "overloaded".to_string()),
+            AiError::Upstream { status: 503, .. }
+        ));
+        assert!(matches!(
+            AiError::from_status(408, None, String::new()),
+            AiError::Timeout
+        ));
+
+        let rejected = AiError::from_status(400, None, "bad model".to_string());
+        assert!(matches!(rejected, AiError::InvalidRequest(_)));
+        assert!(!rejected.is_retryable());
+        assert!(matches!(
+            AiError::from_status(401, None, String::new()),
+            AiError::Configuration(_)
+        ));
+    }
+}
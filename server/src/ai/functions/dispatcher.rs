@@ -0,0 +1,504 @@
+//! Execution of function calls requested by the model
+//!
+//! `FunctionDispatcher` applies the typed `FunctionCall` variants to a session's
+//! context and issue draft. `run_function_loop` drives the conversation: each
+//! call the model makes is dispatched and its `FunctionResult` sent back, until
+//! the model replies with a final answer instead of another call.
+
+use super::definitions::{ContextType, FunctionCall, FunctionResult, IssueDraftUpdate};
+use crate::ai::models::TokenUsage;
+use crate::ai::models::chat::FunctionCall as RawFunctionCall;
+use crate::ai::{AiError, AiProvider, AiResult, ChatMessage, ChatRequest, ChatResponse, ChatRole};
+use crate::models::{IssueDraft, SessionContext};
+
+/// Maximum number of function calls handled before giving up on a final answer
+pub const MAX_FUNCTION_ROUNDS: usize = 8;
+
+/// Applies function calls to the state of an issue-creation conversation
+#[derive(Debug, Clone, Default)]
+pub struct FunctionDispatcher {
+    context: SessionContext,
+    draft: IssueDraft,
+    available_assets: Vec<String>,
+    issue_requested: bool,
+    handled: Vec<(RawFunctionCall, FunctionResult)>,
+}
+
+impl FunctionDispatcher {
+    /// Create a dispatcher over existing session state
+    #[must_use]
+    pub fn new(context: SessionContext, draft: IssueDraft) -> Self {
+        Self {
+            context,
+            draft,
+            available_assets: Vec::new(),
+            issue_requested: false,
+            handled: Vec::new(),
+        }
+    }
+
+    /// Set the asset IDs the model is allowed to link
+    #[must_use]
+    pub fn with_assets(mut self, asset_ids: Vec<String>) -> Self {
+        self.available_assets = asset_ids;
+        self
+    }
+
+    /// Current session context
+    #[must_use]
+    pub fn context(&self) -> &SessionContext {
+        &self.context
+    }
+
+    /// Current issue draft
+    #[must_use]
+    pub fn draft(&self) -> &IssueDraft {
+        &self.draft
+    }
+
+    /// Whether the model confirmed that the issue should be created
+    #[must_use]
+    pub fn issue_requested(&self) -> bool {
+        self.issue_requested
+    }
+
+    /// Every raw call handled so far, oldest first, with its result
+    #[must_use]
+    pub fn handled(&self) -> &[(RawFunctionCall, FunctionResult)] {
+        &self.handled
+    }
+
+    /// Consume the dispatcher, returning the updated context and draft
+    #[must_use]
+    pub fn into_parts(self) -> (SessionContext, IssueDraft) {
+        (self.context, self.draft)
+    }
+
+    /// Parse a raw function call from the provider into its typed form
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the arguments are not valid JSON or do not match
+    /// any known function
+    pub fn parse_call(call: &RawFunctionCall) -> AiResult<FunctionCall> {
+        let arguments: serde_json::Value = serde_json::from_str(&call.arguments)?;
+        let tagged = serde_json::json!({
+            "name": call.name,
+            "arguments": arguments,
+        });
+
+        serde_json::from_value(tagged).map_err(|e| {
+            AiError::InvalidRequest(format!("Invalid call to function '{}': {e}", call.name))
+        })
+    }
+
+    /// Parse and execute a raw function call
+    ///
+    /// Invalid calls produce a failed `FunctionResult` rather than an error, so
+    /// the model can see what went wrong and correct itself.
+    pub fn handle(&mut self, call: &RawFunctionCall) -> FunctionResult {
+        let result = match Self::parse_call(call) {
+            Ok(typed) => self.dispatch(typed),
+            Err(e) => failure(e.to_string()),
+        };
+        self.handled.push((call.clone(), result.clone()));
+        result
+    }
+
+    /// Execute a typed function call against the session state
+    pub fn dispatch(&mut self, call: FunctionCall) -> FunctionResult {
+        match call {
+            FunctionCall::SaveContext {
+                context_type,
+                content,
+            } => self.save_context(context_type, content),
+            FunctionCall::LinkAsset {
+                asset_id,
+                description,
+            } => self.link_asset(asset_id, &description),
+            FunctionCall::UpdateIssueDraft { updates } => self.update_issue_draft(updates),
+            FunctionCall::CreateIssue { confirm } => self.create_issue(confirm),
+        }
+    }
+
+    fn save_context(&mut self, context_type: ContextType, content: String) -> FunctionResult {
+        let notes = match context_type {
+            ContextType::Requirements => &mut self.context.requirements,
+            ContextType::Technical => &mut self.context.technical_notes,
+            ContextType::Design => &mut self.context.design_notes,
+            ContextType::Notes => &mut self.context.other_notes,
+        };
+        notes.push(content);
+
+        success(format!("Saved {context_type:?} context"), None)
+    }
+
+    fn link_asset(&mut self, asset_id: String, description: &str) -> FunctionResult {
+        if !self.available_assets.contains(&asset_id) {
+            return failure(format!(
+                "Asset '{asset_id}' was not uploaded to this session"
+            ));
+        }
+
+        let message = format!("Linked asset {asset_id}: {description}");
+        if !self.draft.assets.contains(&asset_id) {
+            self.draft.assets.push(asset_id);
+        }
+        success(message, None)
+    }
+
+    fn update_issue_draft(&mut self, updates: IssueDraftUpdate) -> FunctionResult {
+        let draft = &mut self.draft;
+
+        if let Some(title) = updates.title {
+            draft.title = Some(title);
+        }
+        if let Some(description) = updates.description {
+            draft.description = Some(description);
+        }
+        if let Some(criteria) = updates.acceptance_criteria {
+            draft.acceptance_criteria = criteria;
+        }
+        if let Some(notes) = updates.technical_notes {
+            draft.technical_notes = Some(notes);
+        }
+        if let Some(hours) = updates.estimated_hours {
+            draft.estimated_hours = Some(hours);
+        }
+        if let Some(priority) = updates.priority {
+            draft.priority = Some(priority);
+        }
+        for tag in updates.add_tags.unwrap_or_default() {
+            if !draft.tags.contains(&tag) {
+                draft.tags.push(tag);
+            }
+        }
+        if let Some(remove) = updates.remove_tags {
+            draft.tags.retain(|tag| !remove.contains(tag));
+        }
+
+        success(
+            "Issue draft updated".to_string(),
+            serde_json::to_value(&self.draft).ok(),
+        )
+    }
+
+    fn create_issue(&mut self, confirm: bool) -> FunctionResult {
+        let missing = self.draft.missing_fields();
+        if !missing.is_empty() {
+            return failure(format!(
+                "Cannot create issue yet, missing: {}",
+                missing.join(", ")
+            ));
+        }
+
+        let preview = serde_json::to_value(&self.draft).ok();
+        if confirm {
+            self.issue_requested = true;
+            success(
+                "Issue is ready and will be created when the session is finalized".to_string(),
+                preview,
+            )
+        } else {
+            success("Preview of the issue to be created".to_string(), preview)
+        }
+    }
+}
+
+fn success(message: String, data: Option<serde_json::Value>) -> FunctionResult {
+    FunctionResult {
+        success: true,
+        message,
+        data,
+    }
+}
+
+fn failure(message: String) -> FunctionResult {
+    FunctionResult {
+        success: false,
+        message,
+        data: None,
+    }
+}
+
+/// Run a chat request, executing function calls until the model gives a final answer
+///
+/// Each function call is appended to the conversation together with its result
+/// before the model is asked again. The returned response carries the token
+/// usage summed across all rounds.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The provider fails
+/// - The model keeps calling functions for more than `MAX_FUNCTION_ROUNDS` rounds
+pub async fn run_function_loop(
+    provider: &dyn AiProvider,
+    mut request: ChatRequest,
+    dispatcher: &mut FunctionDispatcher,
+) -> AiResult<ChatResponse> {
+    let mut total_usage: Option<TokenUsage> = None;
+
+    for _ in 0..MAX_FUNCTION_ROUNDS {
+        let mut response = provider.chat(request.clone()).await?;
+        total_usage = match (total_usage, response.usage.take()) {
+            (Some(total), Some(usage)) => Some(total + usage),
+            (total, usage) => total.or(usage),
+        };
+
+        let Some(call) = response.function_call.clone() else {
+            response.usage = total_usage;
+            return Ok(response);
+        };
+
+        tracing::debug!("Dispatching function call: {}", call.name);
+        let result = dispatcher.handle(&call);
+
+        request.messages.push(ChatMessage {
+            role: ChatRole::Assistant,
+            content: response.content(),
+            function_call: Some(call.clone()),
+        });
+        request.messages.push(ChatMessage {
+            role: ChatRole::Function,
+            content: serde_json::to_string(&result)?,
+            function_call: Some(call),
+        });
+    }
+
+    Err(AiError::Provider(format!(
+        "No final answer after {MAX_FUNCTION_ROUNDS} function calls"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::models::ChatChoice;
+    use async_trait::async_trait;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    fn raw_call(name: &str, arguments: &serde_json::Value) -> RawFunctionCall {
+        RawFunctionCall {
+            id: Some(format!("call_{name}")),
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        }
+    }
+
+    fn response(content: &str, function_call: Option<RawFunctionCall>) -> ChatResponse {
+        ChatResponse {
+            id: "resp".to_string(),
+            model: "test-model".to_string(),
+            choices: vec![ChatChoice {
+                index: 0,
+                message: ChatMessage {
+                    role: ChatRole::Assistant,
+                    content: content.to_string(),
+                    function_call: function_call.clone(),
+                },
+                finish_reason: Some("stop".to_string()),
+            }],
+            usage: Some(TokenUsage::new(10, 5)),
+            created: 0,
+            function_call,
+        }
+    }
+
+    /// Provider that replays scripted responses and records the requests it saw
+    struct ScriptedProvider {
+        responses: Mutex<VecDeque<ChatResponse>>,
+        requests: Mutex<Vec<ChatRequest>>,
+    }
+
+    impl ScriptedProvider {
+        fn new(responses: Vec<ChatResponse>) -> Self {
+            Self {
+                responses: Mutex::new(responses.into()),
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl AiProvider for ScriptedProvider {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn model(&self) -> &'static str {
+            "test-model"
+        }
+
+        async fn chat(&self, request: ChatRequest) -> AiResult<ChatResponse> {
+            self.requests.lock().expect("lock").push(request);
+            self.responses
+                .lock()
+                .expect("lock")
+                .pop_front()
+                .ok_or_else(|| AiError::Provider("script exhausted".to_string()))
+        }
+
+        async fn health_check(&self) -> AiResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_call_into_typed_variant() {
+        let call = raw_call(
+            "save_context",
+            &serde_json::json!({"context_type": "technical", "content": "Uses SQLite"}),
+        );
+        let parsed = FunctionDispatcher::parse_call(&call).expect("call should parse");
+        assert!(matches!(
+            parsed,
+            FunctionCall::SaveContext { context_type: ContextType::Technical, ref content }
+                if content == "Uses SQLite"
+        ));
+    }
+
+    #[test]
+    fn test_handle_unknown_function_returns_failure() {
+        let mut dispatcher = FunctionDispatcher::default();
+        let result = dispatcher.handle(&raw_call("delete_everything", &serde_json::json!({})));
+        assert!(!result.success);
+        assert!(result.message.contains("delete_everything"));
+    }
+
+    #[test]
+    fn test_update_issue_draft_merges_fields_and_tags() {
+        let mut dispatcher = FunctionDispatcher::default();
+        dispatcher.dispatch(FunctionCall::UpdateIssueDraft {
+            updates: IssueDraftUpdate {
+                title: Some("Login page".to_string()),
+                add_tags: Some(vec!["auth".to_string(), "ui".to_string()]),
+                ..Default::default()
+            },
+        });
+        let result = dispatcher.dispatch(FunctionCall::UpdateIssueDraft {
+            updates: IssueDraftUpdate {
+                priority: Some("high".to_string()),
+                add_tags: Some(vec!["auth".to_string()]),
+                remove_tags: Some(vec!["ui".to_string()]),
+                ..Default::default()
+            },
+        });
+
+        assert!(result.success);
+        let draft = dispatcher.draft();
+        assert_eq!(draft.title.as_deref(), Some("Login page"));
+        assert_eq!(draft.priority.as_deref(), Some("high"));
+        assert_eq!(draft.tags, vec!["auth".to_string()]);
+    }
+
+    #[test]
+    fn test_link_asset_requires_known_asset() {
+        let mut dispatcher = FunctionDispatcher::default().with_assets(vec!["asset_1".to_string()]);
+
+        let unknown = dispatcher.dispatch(FunctionCall::LinkAsset {
+            asset_id: "asset_2".to_string(),
+            description: "mockup".to_string(),
+        });
+        assert!(!unknown.success);
+
+        let known = dispatcher.dispatch(FunctionCall::LinkAsset {
+            asset_id: "asset_1".to_string(),
+            description: "mockup".to_string(),
+        });
+        assert!(known.success);
+        assert_eq!(dispatcher.draft().assets, vec!["asset_1".to_string()]);
+    }
+
+    #[test]
+    fn test_create_issue_requires_title_and_description() {
+        let mut dispatcher = FunctionDispatcher::default();
+        let result = dispatcher.dispatch(FunctionCall::CreateIssue { confirm: true });
+        assert!(!result.success);
+        assert!(!dispatcher.issue_requested());
+
+        dispatcher.dispatch(FunctionCall::UpdateIssueDraft {
+            updates: IssueDraftUpdate {
+                title: Some("Title".to_string()),
+                description: Some("Description".to_string()),
+                ..Default::default()
+            },
+        });
+
+        let preview = dispatcher.dispatch(FunctionCall::CreateIssue { confirm: false });
+        assert!(preview.success);
+        assert!(!dispatcher.issue_requested());
+
+        let confirmed = dispatcher.dispatch(FunctionCall::CreateIssue { confirm: true });
+        assert!(confirmed.success);
+        assert!(dispatcher.issue_requested());
+    }
+
+    #[tokio::test]
+    async fn test_run_function_loop_feeds_results_back_until_final_answer() {
+        let provider = ScriptedProvider::new(vec![
+            response(
+                "",
+                Some(raw_call(
+                    "save_context",
+                    &serde_json::json!({"context_type": "requirements", "content": "SSO login"}),
+                )),
+            ),
+            response("Noted the requirement.", None),
+        ]);
+        let mut dispatcher = FunctionDispatcher::default();
+        let request = ChatRequest::new(vec![ChatMessage {
+            role: ChatRole::User,
+            content: "We need SSO login".to_string(),
+            function_call: None,
+        }]);
+
+        let result = run_function_loop(&provider, request, &mut dispatcher)
+            .await
+            .expect("loop should finish");
+
+        assert_eq!(result.content(), "Noted the requirement.");
+        assert_eq!(result.usage.map(|u| u.total), Some(30));
+        assert_eq!(dispatcher.context().requirements, vec!["SSO login"]);
+        assert_eq!(dispatcher.handled().len(), 1);
+        assert!(dispatcher.handled()[0].1.success);
+
+        let requests = provider.requests.lock().expect("lock");
+        assert_eq!(requests.len(), 2);
+        let follow_up = &requests[1].messages;
+        assert_eq!(follow_up.len(), 3);
+        assert_eq!(follow_up[1].role, ChatRole::Assistant);
+        assert_eq!(follow_up[2].role, ChatRole::Function);
+        assert_eq!(
+            follow_up[2]
+                .function_call
+                .as_ref()
+                .and_then(|c| c.id.as_deref()),
+            Some("call_save_context")
+        );
+        assert!(follow_up[2].content.contains("\"success\":true"));
+    }
+
+    #[tokio::test]
+    async fn test_run_function_loop_stops_after_max_rounds() {
+        let looping_call = || {
+            response(
+                "",
+                Some(raw_call(
+                    "create_issue",
+                    &serde_json::json!({"confirm": false}),
+                )),
+            )
+        };
+        let provider = ScriptedProvider::new(
+            std::iter::repeat_with(looping_call)
+                .take(MAX_FUNCTION_ROUNDS)
+                .collect(),
+        );
+        let mut dispatcher = FunctionDispatcher::default();
+
+        let result = run_function_loop(&provider, ChatRequest::new(vec![]), &mut dispatcher).await;
+        assert!(matches!(result, Err(AiError::Provider(_))));
+    }
+}
@@ -4,8 +4,10 @@
 //! can use to perform structured actions during conversations.
 
 pub mod definitions;
+pub mod dispatcher;
 
 pub use definitions::{
     FunctionCall, FunctionDefinition, FunctionParameter, FunctionResult,
     get_business_analyst_functions,
 };
+pub use dispatcher::{FunctionDispatcher, MAX_FUNCTION_ROUNDS, run_function_loop};
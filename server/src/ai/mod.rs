@@ -1,25 +1,37 @@
 //! AI module - Centralized AI functionality for Kanbain
 //!
 //! This module provides:
-//! - AI provider abstraction (`OpenRouter` as primary, configurable endpoint)
+//! - AI provider abstraction (`OpenRouter`, OpenAI-compatible and Anthropic backends)
 //! - Business logic services (issue analysis, duplicate detection, sizing)
 //! - Prompt management and templating
 //! - Structured response validation
+//! - Text embeddings for similarity search, with a local fallback
+//! - Token counting with each model's byte pair encoding, and context window sizes
+//! - Model prices, for the cost of usage
 //!
 //! See `src/ai/README.md` for detailed architecture documentation.
 
+pub mod context;
+pub mod embeddings;
 pub mod error;
 pub mod functions;
 pub mod models;
+pub mod pricing;
 pub mod prompts;
 pub mod providers;
 pub mod services;
+pub mod tokenizer;
 
 // Re-export commonly used types
+pub use context::ContextWindows;
 pub use error::{AiError, AiResult};
 pub use functions::{
-    FunctionCall, FunctionDefinition, FunctionResult, get_business_analyst_functions,
+    FunctionCall, FunctionDefinition, FunctionDispatcher, FunctionResult,
+    get_business_analyst_functions,
 };
 pub use models::chat::{ChatMessage, ChatRequest, ChatResponse, ChatRole};
-pub use providers::{AiProvider, OpenRouterProvider};
+pub use models::embedding::{EmbeddingRequest, EmbeddingResponse};
+pub use pricing::{ModelPrice, ModelPricing};
+pub use providers::{AiProvider, OpenRouterProvider, ProviderRegistry};
 pub use services::{SchemaValidator, schemas};
+pub use tokenizer::{Tokenizer, Tokenizers};
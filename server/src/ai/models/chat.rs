@@ -16,6 +16,12 @@ pub enum ChatRole {
 pub struct ChatMessage {
     pub role: ChatRole,
     pub content: String,
+    /// Function call attached to this turn
+    ///
+    /// On `Assistant` messages this is the call the model requested; on
+    /// `Function` messages it identifies the call whose result is the content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<FunctionCall>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,7 +74,11 @@ pub struct ChatChoice {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionCall {
+    /// Provider-assigned call ID, used to pair results with the call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub name: String,
+    /// Arguments as a JSON-encoded string, exactly as produced by the model
     pub arguments: String,
 }
 
@@ -108,6 +118,18 @@ impl ChatRequest {
         }
     }
 
+    /// Offer functions the model may call instead of answering directly
+    #[must_use]
+    pub fn with_functions(mut self, functions: &[crate::ai::FunctionDefinition]) -> Self {
+        self.functions = Some(
+            functions
+                .iter()
+                .filter_map(|function| serde_json::to_value(function).ok())
+                .collect(),
+        );
+        self
+    }
+
     /// Add JSON schema for structured response
     #[must_use]
     pub fn with_json_schema(mut self, schema: serde_json::Value) -> Self {
@@ -0,0 +1,34 @@
+//! Embedding request and response types
+
+use super::usage::TokenUsage;
+use serde::{Deserialize, Serialize};
+
+/// Request to embed one or more texts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingRequest {
+    /// Embedding model to use; providers have no default embedding model
+    pub model: Option<String>,
+    /// Texts to embed, in order
+    pub input: Vec<String>,
+}
+
+impl EmbeddingRequest {
+    /// Create a request to embed `input` with `model`
+    #[must_use]
+    pub fn new(model: impl Into<String>, input: Vec<String>) -> Self {
+        Self {
+            model: Some(model.into()),
+            input,
+        }
+    }
+}
+
+/// Embeddings returned by a provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    /// Model that produced the embeddings
+    pub model: String,
+    /// One vector per input text, in the order of the request
+    pub embeddings: Vec<Vec<f32>>,
+    pub usage: Option<TokenUsage>,
+}
@@ -2,16 +2,20 @@
 
 pub mod analysis;
 pub mod chat;
+pub mod embedding;
 pub mod responses;
+pub mod structured;
 pub mod usage;
 
 // Re-export all types from chat module for backward compatibility
 pub use chat::{
     ChatChoice, ChatMessage, ChatRequest, ChatResponse, ChatRole, ResponseFormat, StreamEvent,
 };
+pub use embedding::{EmbeddingRequest, EmbeddingResponse};
 pub use responses::{
     ComplexityFactor, ConfidenceLevel, DuplicateDetection, DuplicateRecommendation,
     EffortEstimation, ImpactLevel, IssueAnalysis, IssueSuggestions, Priority, QualityAssessment,
     SimilarIssue, SimilarityLevel, SplitSuggestion, Suggestion, SuggestionCategory, TaskBreakdown,
 };
+pub use structured::{SchemaAttempt, StructuredResponse};
 pub use usage::TokenUsage;
@@ -0,0 +1,29 @@
+//! Structured output models for schema-constrained chat
+
+use crate::ai::models::chat::ChatResponse;
+use crate::ai::models::usage::TokenUsage;
+use serde::Serialize;
+
+/// One request made while producing a structured response
+///
+/// Every attempt is billed by the provider, so each one is recorded in `ai_usage`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaAttempt {
+    pub request_id: String,
+    pub model: String,
+    pub usage: Option<TokenUsage>,
+    pub duration_ms: i64,
+    /// Why the output was rejected; `None` for the attempt that was accepted
+    pub error: Option<String>,
+}
+
+/// A response whose content parsed and matched its schema
+#[derive(Debug, Clone)]
+pub struct StructuredResponse {
+    /// The accepted response
+    pub response: ChatResponse,
+    /// The parsed content of the accepted response
+    pub value: serde_json::Value,
+    /// Every attempt, oldest first; the last one is the accepted response
+    pub attempts: Vec<SchemaAttempt>,
+}
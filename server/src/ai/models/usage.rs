@@ -20,3 +20,15 @@ impl TokenUsage {
         }
     }
 }
+
+impl std::ops::Add for TokenUsage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            prompt: self.prompt + other.prompt,
+            completion: self.completion + other.completion,
+            total: self.total + other.total,
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! Prices of models, for the cost of their usage
+//!
+//! Prices are in US cents per million tokens, separately for prompt and
+//! completion tokens, and are found by the longest matching model name prefix,
+//! ignoring any `provider/` prefix. They are loaded from a TOML file
+//! (`AI_MODEL_PRICES_FILE`); models it does not price cost
+//! `AI_MODEL_PRICE_DEFAULT`, or are not priced at all when it is unset.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::ai::{AiError, AiResult};
+
+/// Price file when `AI_MODEL_PRICES_FILE` is not set
+const DEFAULT_PRICES_FILE: &str = "./model_prices.toml";
+
+/// Contents of a price file
+#[derive(Debug, Deserialize)]
+struct PricesFile {
+    /// Prices by model name prefix
+    #[serde(default)]
+    models: HashMap<String, ModelPrice>,
+}
+
+/// Price of a model's tokens, in cents per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+impl ModelPrice {
+    /// Cost of a request in millionths of a cent, which keeps the cost of
+    /// small requests to cheap models from rounding to nothing
+    #[must_use]
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    pub fn cost_microcents(&self, prompt_tokens: i64, completion_tokens: i64) -> i64 {
+        // A token at `price` cents per million tokens costs `price` microcents
+        (prompt_tokens as f64)
+            .mul_add(self.prompt, completion_tokens as f64 * self.completion)
+            .round() as i64
+    }
+
+    /// Parse `prompt/completion`
+    fn parse(value: &str) -> Option<Self> {
+        let (prompt, completion) = value.split_once('/')?;
+        let price = Self {
+            prompt: prompt.trim().parse().ok()?,
+            completion: completion.trim().parse().ok()?,
+        };
+        price.is_valid().then_some(price)
+    }
+
+    fn is_valid(&self) -> bool {
+        self.prompt >= 0.0 && self.completion >= 0.0
+    }
+}
+
+/// Prices by model
+#[derive(Debug, Clone, Default)]
+pub struct ModelPricing {
+    /// Model name prefixes and their prices
+    models: Vec<(String, ModelPrice)>,
+    default_price: Option<ModelPrice>,
+}
+
+impl ModelPricing {
+    /// Load prices from environment configuration
+    ///
+    /// - `AI_MODEL_PRICES_FILE`: TOML file of prices by model name prefix
+    ///   (default: `./model_prices.toml`, which may be missing)
+    /// - `AI_MODEL_PRICE_DEFAULT`: `prompt/completion` price of other models
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the price file is invalid, or was set and cannot be
+    /// read, or the default price is malformed
+    pub fn from_env() -> AiResult<Self> {
+        let mut pricing = match std::env::var("AI_MODEL_PRICES_FILE") {
+            Ok(path) => Self::load(path)?,
+            Err(_) if Path::new(DEFAULT_PRICES_FILE).exists() => Self::load(DEFAULT_PRICES_FILE)?,
+            Err(_) => {
+                tracing::warn!("No {DEFAULT_PRICES_FILE}, models are only priced by default");
+                Self::default()
+            }
+        };
+
+        if let Ok(value) = std::env::var("AI_MODEL_PRICE_DEFAULT") {
+            let price = ModelPrice::parse(&value).ok_or_else(|| {
+                AiError::Configuration(format!(
+                    "AI_MODEL_PRICE_DEFAULT must be 'prompt/completion', got '{value}'"
+                ))
+            })?;
+            pricing.default_price = Some(price);
+        }
+        Ok(pricing)
+    }
+
+    /// Load prices from a TOML file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is invalid
+    pub fn load(path: impl AsRef<Path>) -> AiResult<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            AiError::Configuration(format!(
+                "Failed to read model prices {}: {e}",
+                path.display()
+            ))
+        })?;
+        let pricing = Self::from_toml(&contents).map_err(|e| {
+            AiError::Configuration(format!("Invalid model prices {}: {e}", path.display()))
+        })?;
+        tracing::info!(
+            "Loaded prices of {} models from {}",
+            pricing.models.len(),
+            path.display()
+        );
+        Ok(pricing)
+    }
+
+    /// Parse prices from the contents of a price file
+    fn from_toml(contents: &str) -> Result<Self, String> {
+        let file: PricesFile = toml::from_str(contents).map_err(|e| e.to_string())?;
+        file.models
+            .into_iter()
+            .try_fold(Self::default(), |pricing, (prefix, price)| {
+                if prefix.trim().is_empty() || !price.is_valid() {
+                    return Err(format!("'{prefix}' needs a name and prices of at least 0"));
+                }
+                Ok(pricing.with_model(prefix.trim(), price))
+            })
+    }
+
+    /// Price models whose names start with a prefix
+    #[must_use]
+    pub fn with_model(mut self, prefix: &str, price: ModelPrice) -> Self {
+        self.models.push((prefix.to_lowercase(), price));
+        self
+    }
+
+    /// Price models that have no price of their own
+    #[must_use]
+    pub fn with_default(mut self, price: ModelPrice) -> Self {
+        self.default_price = Some(price);
+        self
+    }
+
+    /// Price of a model, if it has one
+    #[must_use]
+    pub fn price_for(&self, model: &str) -> Option<ModelPrice> {
+        let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+        self.models
+            .iter()
+            .filter(|(prefix, _)| name.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| *price)
+            .or(self.default_price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_for_model() {
+        let pricing = ModelPricing::from_toml(
+            r#"
+            [models]
+            "gpt-4o" = { prompt = 250, completion = 1000 }
+            "gpt-4o-mini" = { prompt = 15, completion = 60 }
+            "#,
+        )
+        .expect("valid prices");
+
+        let gpt_4o = pricing
+            .price_for("openai/gpt-4o-2024-08-06")
+            .expect("priced");
+        assert!((gpt_4o.prompt - 250.0).abs() < f64::EPSILON);
+        let mini = pricing.price_for("GPT-4o-mini").expect("priced");
+        assert!((mini.completion - 60.0).abs() < f64::EPSILON);
+        assert_eq!(pricing.price_for("unknown-model"), None);
+
+        let fallback = pricing.with_default(ModelPrice {
+            prompt: 100.0,
+            completion: 100.0,
+        });
+        assert!(fallback.price_for("unknown-model").is_some());
+    }
+
+    #[test]
+    fn test_price_file() {
+        let shipped = ModelPricing::load("model_prices.toml").expect("shipped prices load");
+        assert!(shipped.price_for("anthropic/claude-3.5-haiku").is_some());
+
+        assert!(
+            ModelPricing::from_toml("[models]\n\"gpt-4o\" = { prompt = -1, completion = 0 }")
+                .is_err()
+        );
+        assert!(ModelPricing::from_toml("[models]\n\"gpt-4o\" = { prompt = 1 }").is_err());
+        assert!(ModelPricing::load("missing_prices.toml").is_err());
+    }
+
+    #[test]
+    fn test_cost_microcents() {
+        let price = ModelPrice {
+            prompt: 250.0,
+            completion: 1_000.0,
+        };
+        // 1000 prompt tokens at $2.50 per million cost a quarter of a cent
+        assert_eq!(price.cost_microcents(1_000, 0), 250_000);
+        assert_eq!(price.cost_microcents(1_000, 500), 750_000);
+        assert_eq!(
+            ModelPrice::parse("7.5/30"),
+            Some(ModelPrice {
+                prompt: 7.5,
+                completion: 30.0
+            })
+        );
+        assert_eq!(ModelPrice::parse("-1/30"), None);
+    }
+}
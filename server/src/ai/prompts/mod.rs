@@ -1,9 +1,11 @@
 //! Prompt template management
 //!
 //! This module handles loading and managing prompt templates for different AI personas
-//! and use cases using Handlebars templates.
+//! and use cases. Templates are TOML files holding Handlebars prompts, loaded from
+//! disk and reloaded when they change.
 
-pub mod renderer;
+pub mod registry;
 pub mod templates;
 
-pub use renderer::PromptRenderer;
+pub use registry::{PromptRegistry, RenderedPrompt, TemplateInfo};
+pub use templates::{PromptTemplate, TemplateVariable};
@@ -0,0 +1,490 @@
+//! Registry of prompt templates loaded from a directory of TOML files
+//!
+//! Templates are loaded at startup and reloaded when the files change, so prompts
+//! can be edited without restarting the server. The directory is checked for
+//! changes at most once per reload interval, when a template is used. A reload
+//! that fails keeps the previously loaded templates.
+
+use super::templates::{PromptTemplate, TemplateVariable};
+use crate::ai::{AiError, AiResult, ChatMessage, ChatRole};
+use handlebars::Handlebars;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Template directory when `AI_PROMPTS_DIR` is not set
+const DEFAULT_PROMPTS_DIR: &str = "./prompts";
+/// Seconds between checks for changed templates when `AI_PROMPTS_RELOAD_SECS` is not set
+const DEFAULT_RELOAD_SECS: u64 = 2;
+
+/// A rendered template, ready to send
+#[derive(Debug, Clone)]
+pub struct RenderedPrompt {
+    pub name: String,
+    pub version: u32,
+    pub messages: Vec<ChatMessage>,
+}
+
+/// A loaded template as reported to clients
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateInfo {
+    pub name: String,
+    /// The version used when no version is requested
+    pub version: u32,
+    pub versions: Vec<u32>,
+    pub description: String,
+    pub variables: Vec<TemplateVariable>,
+}
+
+/// Modification time and size of each template file
+type Fingerprint = Vec<(PathBuf, Option<SystemTime>, u64)>;
+
+struct LoadedTemplates {
+    /// Versions of each template, by name
+    templates: HashMap<String, BTreeMap<u32, PromptTemplate>>,
+    handlebars: Handlebars<'static>,
+}
+
+pub struct PromptRegistry {
+    dir: PathBuf,
+    reload_interval: Option<Duration>,
+    loaded: RwLock<LoadedTemplates>,
+    /// Files as of the last load attempt, and when they were last checked
+    seen: Mutex<(Fingerprint, Instant)>,
+}
+
+impl PromptRegistry {
+    /// Load templates from environment configuration
+    ///
+    /// Reads configuration from:
+    /// - `AI_PROMPTS_DIR`        - Directory of `.toml` templates (default: `./prompts`)
+    /// - `AI_PROMPTS_RELOAD_SECS` - Seconds between checks for changes, `0` to disable (default: 2)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration is invalid or the templates cannot be loaded
+    pub fn from_env() -> AiResult<Self> {
+        let dir =
+            std::env::var("AI_PROMPTS_DIR").unwrap_or_else(|_| DEFAULT_PROMPTS_DIR.to_string());
+        let reload_secs = match std::env::var("AI_PROMPTS_RELOAD_SECS") {
+            Ok(value) => value.trim().parse().map_err(|_| {
+                AiError::Configuration(format!(
+                    "AI_PROMPTS_RELOAD_SECS must be a number, got '{value}'"
+                ))
+            })?,
+            Err(_) => DEFAULT_RELOAD_SECS,
+        };
+
+        Self::load(
+            dir,
+            (reload_secs > 0).then(|| Duration::from_secs(reload_secs)),
+        )
+    }
+
+    /// Load templates from a directory, checking for changes every `reload_interval`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be read or any template is invalid
+    pub fn load(dir: impl Into<PathBuf>, reload_interval: Option<Duration>) -> AiResult<Self> {
+        let dir = dir.into();
+        let fingerprint = fingerprint(&dir)?;
+        let loaded = load_templates(&fingerprint)?;
+        tracing::info!(
+            "Loaded {} prompt templates from {}",
+            loaded.templates.len(),
+            dir.display()
+        );
+
+        Ok(Self {
+            dir,
+            reload_interval,
+            loaded: RwLock::new(loaded),
+            seen: Mutex::new((fingerprint, Instant::now())),
+        })
+    }
+
+    /// Render the latest version of a template
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template does not exist, a required variable is
+    /// missing or the template fails to render
+    pub fn render(&self, name: &str, context: &Value) -> AiResult<RenderedPrompt> {
+        self.render_version(name, None, context)
+    }
+
+    /// Render a specific version of a template, or the latest if `version` is `None`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template or version does not exist, a required
+    /// variable is missing or the template fails to render
+    pub fn render_version(
+        &self,
+        name: &str,
+        version: Option<u32>,
+        context: &Value,
+    ) -> AiResult<RenderedPrompt> {
+        self.refresh();
+
+        let loaded = self.loaded.read().unwrap_or_else(PoisonError::into_inner);
+        let versions = loaded
+            .templates
+            .get(name)
+            .ok_or_else(|| AiError::PromptTemplate(format!("Template '{name}' not found")))?;
+        let template = match version {
+            Some(version) => versions.get(&version).ok_or_else(|| {
+                AiError::PromptTemplate(format!("Template '{name}' has no version {version}"))
+            })?,
+            None => versions
+                .values()
+                .next_back()
+                .ok_or_else(|| AiError::PromptTemplate(format!("Template '{name}' not found")))?,
+        };
+
+        let values = render_context(template, context)?;
+        let mut messages = Vec::new();
+        for (role, part) in [(ChatRole::System, "system"), (ChatRole::User, "user")] {
+            let key = template_key(template, part);
+            if !loaded.handlebars.has_template(&key) {
+                continue;
+            }
+            let rendered = loaded.handlebars.render(&key, &values).map_err(|e| {
+                AiError::PromptTemplate(format!(
+                    "Failed to render template '{name}' v{}: {e}",
+                    template.version
+                ))
+            })?;
+            let rendered = rendered.trim();
+            if !rendered.is_empty() {
+                messages.push(ChatMessage {
+                    role,
+                    content: rendered.to_string(),
+                    function_call: None,
+                });
+            }
+        }
+
+        Ok(RenderedPrompt {
+            name: template.name.clone(),
+            version: template.version,
+            messages,
+        })
+    }
+
+    /// List the loaded templates, sorted by name
+    #[must_use]
+    pub fn templates(&self) -> Vec<TemplateInfo> {
+        self.refresh();
+
+        let loaded = self.loaded.read().unwrap_or_else(PoisonError::into_inner);
+        let mut templates: Vec<TemplateInfo> = loaded
+            .templates
+            .values()
+            .filter_map(|versions| {
+                let latest = versions.values().next_back()?;
+                Some(TemplateInfo {
+                    name: latest.name.clone(),
+                    version: latest.version,
+                    versions: versions.keys().copied().collect(),
+                    description: latest.description.clone(),
+                    variables: latest.variables.clone(),
+                })
+            })
+            .collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        templates
+    }
+
+    /// Reload the templates if any file was added, changed or removed
+    ///
+    /// Returns whether the templates were reloaded. On failure the previously
+    /// loaded templates stay in use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be read or a template is invalid
+    pub fn reload(&self) -> AiResult<bool> {
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        seen.1 = Instant::now();
+
+        let current = fingerprint(&self.dir)?;
+        if current == seen.0 {
+            return Ok(false);
+        }
+        // Remember the attempt, so a broken file is reported once rather than on every check
+        seen.0.clone_from(&current);
+
+        let loaded = load_templates(&current)?;
+        tracing::info!(
+            "Reloaded {} prompt templates from {}",
+            loaded.templates.len(),
+            self.dir.display()
+        );
+        *self.loaded.write().unwrap_or_else(PoisonError::into_inner) = loaded;
+        Ok(true)
+    }
+
+    /// Reload changed templates if the reload interval has passed
+    fn refresh(&self) {
+        let Some(interval) = self.reload_interval else {
+            return;
+        };
+        let due = self
+            .seen
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .1
+            .elapsed()
+            >= interval;
+
+        if due && let Err(e) = self.reload() {
+            tracing::error!("Failed to reload prompt templates, keeping the previous ones: {e}");
+        }
+    }
+}
+
+/// List the template files in a directory with their modification times and sizes
+fn fingerprint(dir: &Path) -> AiResult<Fingerprint> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        AiError::Configuration(format!(
+            "Failed to read prompt template directory {}: {e}",
+            dir.display()
+        ))
+    })?;
+
+    let mut files: Fingerprint = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "toml")
+        })
+        .map(|path| {
+            let metadata = std::fs::metadata(&path).ok();
+            let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+            let len = metadata.map_or(0, |m| m.len());
+            (path, modified, len)
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Parse every template file and compile its prompts
+fn load_templates(files: &Fingerprint) -> AiResult<LoadedTemplates> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    // Prompts are plain text, not HTML
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars.register_helper("json", Box::new(json_helper));
+
+    let mut templates: HashMap<String, BTreeMap<u32, PromptTemplate>> = HashMap::new();
+    for (path, _, _) in files {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            AiError::PromptTemplate(format!("Failed to read {}: {e}", path.display()))
+        })?;
+        let template = PromptTemplate::from_toml(&contents, path)?;
+
+        for (part, source) in [("system", &template.system), ("user", &template.user)] {
+            if source.trim().is_empty() {
+                continue;
+            }
+            handlebars
+                .register_template_string(&template_key(&template, part), source)
+                .map_err(|e| {
+                    AiError::PromptTemplate(format!(
+                        "Invalid {part} prompt in {}: {e}",
+                        path.display()
+                    ))
+                })?;
+        }
+
+        let versions = templates.entry(template.name.clone()).or_default();
+        if versions.contains_key(&template.version) {
+            return Err(AiError::PromptTemplate(format!(
+                "Template '{}' v{} is defined more than once (again in {})",
+                template.name,
+                template.version,
+                path.display()
+            )));
+        }
+        versions.insert(template.version, template);
+    }
+
+    Ok(LoadedTemplates {
+        templates,
+        handlebars,
+    })
+}
+
+fn template_key(template: &PromptTemplate, part: &str) -> String {
+    format!("{}@{}/{part}", template.name, template.version)
+}
+
+/// Check the context against the declared variables, filling in optional ones
+fn render_context(template: &PromptTemplate, context: &Value) -> AiResult<Value> {
+    let mut values = match context {
+        Value::Object(values) => values.clone(),
+        Value::Null => Map::new(),
+        _ => {
+            return Err(AiError::PromptTemplate(format!(
+                "Context for template '{}' must be an object",
+                template.name
+            )));
+        }
+    };
+
+    for variable in &template.variables {
+        if values.contains_key(&variable.name) {
+            continue;
+        }
+        if variable.required {
+            return Err(AiError::PromptTemplate(format!(
+                "Template '{}' requires variable '{}'",
+                template.name, variable.name
+            )));
+        }
+        values.insert(variable.name.clone(), Value::Null);
+    }
+
+    Ok(Value::Object(values))
+}
+
+/// `{{json value}}` writes a value as JSON, e.g. to include a schema in a prompt
+fn json_helper(
+    h: &handlebars::Helper,
+    _: &Handlebars,
+    _: &handlebars::Context,
+    _: &mut handlebars::RenderContext,
+    out: &mut dyn handlebars::Output,
+) -> handlebars::HelperResult {
+    if let Some(param) = h.param(0) {
+        let json = serde_json::to_string(param.value()).unwrap_or_else(|_| "null".to_string());
+        out.write(&json)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const GREETING: &str = r#"
+        name = "greeting"
+        version = 1
+        system = "You greet people."
+        user = "Say hello to {{name}}."
+
+        [variables]
+        name = "Who to greet"
+    "#;
+
+    fn write(dir: &Path, file: &str, contents: &str) {
+        std::fs::write(dir.join(file), contents).expect("write template");
+    }
+
+    #[test]
+    fn test_bundled_templates_load() {
+        let registry = PromptRegistry::load(DEFAULT_PROMPTS_DIR, None).expect("bundled templates");
+        let names: Vec<String> = registry.templates().into_iter().map(|t| t.name).collect();
+        for persona in [
+            crate::models::AiPersona::BusinessAnalyst,
+            crate::models::AiPersona::TechnicalSupport,
+            crate::models::AiPersona::CodeReviewer,
+            crate::models::AiPersona::ProjectManager,
+        ] {
+            assert!(names.contains(&persona.template_name().to_string()));
+        }
+
+        let rendered = registry
+            .render(
+                "business_analyst_conversation",
+                &json!({ "messages": [{ "role": "user", "content": "Login is slow" }] }),
+            )
+            .expect("persona renders");
+        assert_eq!(rendered.messages.len(), 2);
+        assert_eq!(rendered.messages[1].content, "user: Login is slow");
+    }
+
+    #[test]
+    fn test_render_versions_and_variables() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        write(dir.path(), "greeting.toml", GREETING);
+        write(
+            dir.path(),
+            "greeting.v2.toml",
+            &GREETING
+                .replace("version = 1", "version = 2")
+                .replace("Say hello to", "Warmly greet"),
+        );
+        let registry = PromptRegistry::load(dir.path(), None).expect("load");
+
+        let latest = registry
+            .render("greeting", &json!({ "name": "Ada \"the\" <first>" }))
+            .expect("render");
+        assert_eq!(latest.version, 2);
+        assert_eq!(latest.messages[0].role, ChatRole::System);
+        assert_eq!(
+            latest.messages[1].content,
+            "Warmly greet Ada \"the\" <first>."
+        );
+
+        let pinned = registry
+            .render_version("greeting", Some(1), &json!({ "name": "Ada" }))
+            .expect("render v1");
+        assert_eq!(pinned.messages[1].content, "Say hello to Ada.");
+
+        assert!(registry.render("greeting", &json!({})).is_err());
+        assert!(
+            registry
+                .render_version("greeting", Some(3), &json!({ "name": "Ada" }))
+                .is_err()
+        );
+        assert_eq!(registry.templates()[0].versions, [1, 2]);
+    }
+
+    #[test]
+    fn test_reload_picks_up_changes_and_keeps_last_good_set() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        write(dir.path(), "greeting.toml", GREETING);
+        let registry = PromptRegistry::load(dir.path(), None).expect("load");
+        assert!(!registry.reload().expect("unchanged"));
+
+        write(
+            dir.path(),
+            "greeting.toml",
+            &GREETING.replace("Say hello", "Wave"),
+        );
+        write(
+            dir.path(),
+            "farewell.toml",
+            "version = 1\nuser = \"Goodbye {{name}}\"\n[variables]\nname = \"Who\"",
+        );
+        assert!(registry.reload().expect("changed"));
+        let context = json!({ "name": "Ada" });
+        assert_eq!(
+            registry
+                .render("greeting", &context)
+                .expect("render")
+                .messages[1]
+                .content,
+            "Wave to Ada."
+        );
+        assert!(registry.render("farewell", &context).is_ok());
+
+        // A broken edit is rejected and the previous templates stay in use
+        write(
+            dir.path(),
+            "farewell.toml",
+            "version = 1\nuser = \"Goodbye {{who}}\"",
+        );
+        assert!(registry.reload().is_err());
+        assert!(registry.render("farewell", &context).is_ok());
+    }
+}
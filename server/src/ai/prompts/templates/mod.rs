@@ -5,3 +5,347 @@
 //! - System prompt: Sets the AI's role and behavior
 //! - User prompt template: With placeholders for dynamic content
 //! - Variables: List of required variables and their descriptions
+//!
+//! ```toml
+//! name = "code_analysis"
+//! version = 2
+//! description = "Structured analysis of a code snippet"
+//! system = "You are an experienced code reviewer..."
+//! user = "Language: {{language}}\n\n{{code}}"
+//!
+//! [variables]
+//! code = "Source code to analyze"
+//! language = "Programming language of the code"
+//! context = { description = "What the code is for", required = false }
+//! ```
+//!
+//! Both prompts are Handlebars templates. Every variable a prompt uses must be
+//! declared, and every declared variable must be used, so a template cannot
+//! silently drift from the data its callers provide.
+
+use crate::ai::{AiError, AiResult};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// A variable a template expects in its render context
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateVariable {
+    pub name: String,
+    pub description: String,
+    /// Whether rendering fails when the variable is missing
+    pub required: bool,
+}
+
+/// A parsed and validated prompt template
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub version: u32,
+    pub description: String,
+    pub system: String,
+    pub user: String,
+    pub variables: Vec<TemplateVariable>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateFile {
+    name: Option<String>,
+    version: u32,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    system: String,
+    #[serde(default)]
+    user: String,
+    #[serde(default)]
+    variables: BTreeMap<String, VariableSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum VariableSpec {
+    Description(String),
+    Detailed {
+        description: String,
+        #[serde(default = "required_by_default")]
+        required: bool,
+    },
+}
+
+fn required_by_default() -> bool {
+    true
+}
+
+impl PromptTemplate {
+    /// Parse a template from the contents of a TOML file
+    ///
+    /// The name defaults to the file name up to its first `.`, so versions of a
+    /// template can live side by side as `name.v1.toml`, `name.v2.toml`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the TOML is invalid, both prompts are empty, a prompt
+    /// is not a valid Handlebars template, or the declared variables do not match
+    /// the ones the prompts use
+    pub fn from_toml(contents: &str, source: &Path) -> AiResult<Self> {
+        let file: TemplateFile = toml::from_str(contents).map_err(|e| {
+            AiError::PromptTemplate(format!("Invalid template {}: {e}", source.display()))
+        })?;
+
+        let name = file
+            .name
+            .or_else(|| {
+                source
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.split('.').next())
+                    .map(str::to_string)
+            })
+            .filter(|name| !name.trim().is_empty())
+            .ok_or_else(|| {
+                AiError::PromptTemplate(format!("Template {} has no name", source.display()))
+            })?;
+
+        if file.system.trim().is_empty() && file.user.trim().is_empty() {
+            return Err(AiError::PromptTemplate(format!(
+                "Template '{name}' has neither a system nor a user prompt"
+            )));
+        }
+
+        let variables = file
+            .variables
+            .into_iter()
+            .map(|(name, spec)| match spec {
+                VariableSpec::Description(description) => TemplateVariable {
+                    name,
+                    description,
+                    required: true,
+                },
+                VariableSpec::Detailed {
+                    description,
+                    required,
+                } => TemplateVariable {
+                    name,
+                    description,
+                    required,
+                },
+            })
+            .collect();
+
+        let template = Self {
+            name,
+            version: file.version,
+            description: file.description,
+            system: file.system,
+            user: file.user,
+            variables,
+        };
+        template.check_variables()?;
+        Ok(template)
+    }
+
+    /// Check that the prompts use exactly the declared variables
+    fn check_variables(&self) -> AiResult<()> {
+        let mut used = used_variables(&self.system)?;
+        used.extend(used_variables(&self.user)?);
+        let declared: BTreeSet<String> = self
+            .variables
+            .iter()
+            .map(|variable| variable.name.clone())
+            .collect();
+
+        let undeclared: Vec<&String> = used.difference(&declared).collect();
+        if !undeclared.is_empty() {
+            return Err(AiError::PromptTemplate(format!(
+                "Template '{}' v{} uses undeclared variables: {}",
+                self.name,
+                self.version,
+                join(&undeclared)
+            )));
+        }
+
+        let unused: Vec<&String> = declared.difference(&used).collect();
+        if !unused.is_empty() {
+            return Err(AiError::PromptTemplate(format!(
+                "Template '{}' v{} declares variables it never uses: {}",
+                self.name,
+                self.version,
+                join(&unused)
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn join(names: &[&String]) -> String {
+    names
+        .iter()
+        .map(|name| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Matches a Handlebars expression, including triple-stash and comments
+static EXPRESSION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)\{\{(!--.*?--|!.*?|\{.*?\}|.*?)\}\}").expect("valid expression regex")
+});
+
+/// Block helpers whose body is evaluated against a different context
+const SCOPING_BLOCKS: [&str; 2] = ["each", "with"];
+
+/// Collect the top-level context variables a Handlebars template refers to
+///
+/// Variables inside `#each` and `#with` blocks refer to the block's context and
+/// are not counted; the block's own argument is. Helper names are skipped.
+///
+/// # Errors
+///
+/// Returns an error if block helpers are not closed in order
+pub fn used_variables(template: &str) -> AiResult<BTreeSet<String>> {
+    let mut used = BTreeSet::new();
+    let mut blocks: Vec<String> = Vec::new();
+
+    for capture in EXPRESSION.captures_iter(template) {
+        let expression = capture[1].trim_start_matches('{').trim_end_matches('}');
+        let expression = expression.trim_matches('~').trim();
+        if expression.starts_with('!') {
+            continue;
+        }
+
+        if let Some(closing) = expression.strip_prefix('/') {
+            let closing = closing.trim();
+            match blocks.pop() {
+                Some(open) if open == closing => continue,
+                open => {
+                    return Err(AiError::PromptTemplate(format!(
+                        "Block '{}' closed by '{{{{/{closing}}}}}'",
+                        open.unwrap_or_default()
+                    )));
+                }
+            }
+        }
+
+        let (is_block, expression) = match expression.strip_prefix(['#', '^']) {
+            Some(rest) => (true, rest.trim()),
+            None => (false, expression),
+        };
+        let tokens: Vec<&str> = expression
+            .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .filter(|token| !token.is_empty())
+            .collect();
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+
+        // In `{{helper a b}}` and `{{#block a}}` only the arguments are variables
+        let arguments = if is_block || tokens.len() > 1 {
+            &tokens[1..]
+        } else {
+            &tokens[..]
+        };
+        if !blocks
+            .iter()
+            .any(|block| SCOPING_BLOCKS.contains(&block.as_str()))
+        {
+            for token in arguments {
+                if let Some(name) = variable_name(token) {
+                    used.insert(name);
+                }
+            }
+        }
+
+        if is_block {
+            blocks.push((*first).to_string());
+        }
+    }
+
+    if let Some(open) = blocks.pop() {
+        return Err(AiError::PromptTemplate(format!(
+            "Block '{open}' is never closed"
+        )));
+    }
+
+    Ok(used)
+}
+
+/// The root variable a token refers to, if it is a variable reference
+fn variable_name(token: &str) -> Option<String> {
+    // Hash arguments (`key=value`) refer to variables through their value
+    let token = token.rsplit('=').next().unwrap_or(token);
+    let root = token.split(['.', '/', '[']).next()?;
+
+    let is_literal = token.starts_with(['"', '\'', '@', '.'])
+        || token.parse::<f64>().is_ok()
+        || matches!(
+            root,
+            "" | "this" | "true" | "false" | "null" | "undefined" | "else" | "as"
+        );
+    (!is_literal).then(|| root.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn names(template: &str) -> Vec<String> {
+        used_variables(template)
+            .expect("valid template")
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn test_used_variables() {
+        assert_eq!(
+            names("Hi {{name}}, {{{raw}}} {{json data}} {{! note }} {{user.email}}"),
+            ["data", "name", "raw", "user"]
+        );
+        assert_eq!(
+            names(
+                "{{#if items}}{{#each items}}{{title}} {{@index}}{{/each}}{{else}}{{empty}}{{/if}}"
+            ),
+            ["empty", "items"]
+        );
+        assert!(used_variables("{{#each items}}{{/if}}").is_err());
+        assert!(used_variables("{{#if items}}").is_err());
+    }
+
+    #[test]
+    fn test_from_toml_checks_declared_variables() {
+        let source = PathBuf::from("greeting.v2.toml");
+        let template = PromptTemplate::from_toml(
+            r#"
+            version = 2
+            system = "Greet people."
+            user = "Say hello to {{name}}{{#if title}} ({{title}}){{/if}}"
+
+            [variables]
+            name = "Who to greet"
+            title = { description = "Their title", required = false }
+            "#,
+            &source,
+        )
+        .expect("valid template");
+        assert_eq!(template.name, "greeting");
+        assert_eq!(template.version, 2);
+        assert!(!template.variables[1].required);
+
+        let undeclared = PromptTemplate::from_toml(
+            "version = 1\nuser = \"{{name}} {{age}}\"\n[variables]\nname = \"Name\"",
+            &source,
+        );
+        assert!(undeclared.is_err_and(|e| e.to_string().contains("undeclared variables: age")));
+
+        let unused = PromptTemplate::from_toml(
+            "version = 1\nuser = \"{{name}}\"\n[variables]\nname = \"Name\"\nage = \"Age\"",
+            &source,
+        );
+        assert!(unused.is_err_and(|e| e.to_string().contains("never uses: age")));
+    }
+}
@@ -0,0 +1,777 @@
+//! Anthropic Messages API provider implementation
+//!
+//! Talks to the `/v1/messages` endpoint directly. System prompts are sent as the
+//! top-level `system` field, function calls map to `tool_use` / `tool_result`
+//! content blocks, and structured output is requested through the system prompt
+//! since the API has no `response_format`.
+
+use super::http::error_from_response;
+use super::registry::parse_model_list;
+use super::sse::SseDecoder;
+use super::traits::{AiProvider, ChatStream};
+use crate::ai::models::chat::{ChatChoice, FunctionCall};
+use crate::ai::models::{StreamEvent, TokenUsage};
+use crate::ai::{AiError, AiResult, ChatMessage, ChatRequest, ChatResponse, ChatRole};
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::stream;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+/// API version sent with every request
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Model used when `ANTHROPIC_MODEL` is not set
+const DEFAULT_MODEL: &str = "claude-sonnet-4-0";
+
+/// The Messages API requires `max_tokens`; used when the request does not set one
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+pub struct AnthropicProvider {
+    http: reqwest::Client,
+    endpoint: String,
+    api_key: String,
+    default_model: String,
+    models: Vec<String>,
+}
+
+impl AnthropicProvider {
+    /// Create the provider from environment configuration, if one is configured
+    ///
+    /// Reads configuration from:
+    /// - `ANTHROPIC_API_KEY`  - API key; the provider is disabled when unset
+    /// - `ANTHROPIC_MODEL`    - Optional default model (defaults to `claude-sonnet-4-0`)
+    /// - `ANTHROPIC_ENDPOINT` - Optional endpoint (defaults to `https://api.anthropic.com/v1`)
+    /// - `ANTHROPIC_MODELS`   - Optional comma-separated list of additional models
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let Ok(api_key) = std::env::var("ANTHROPIC_API_KEY") else {
+            return None;
+        };
+        let default_model =
+            std::env::var("ANTHROPIC_MODEL").unwrap_or_else(|_| DEFAULT_MODEL.to_string());
+        let endpoint = std::env::var("ANTHROPIC_ENDPOINT")
+            .unwrap_or_else(|_| "https://api.anthropic.com/v1".to_string());
+        let models = std::env::var("ANTHROPIC_MODELS")
+            .map(|value| parse_model_list(&value))
+            .unwrap_or_default();
+
+        tracing::info!("Using Anthropic provider at {endpoint}");
+
+        Some(Self::new(&endpoint, api_key, default_model).with_models(models))
+    }
+
+    /// Create a provider with explicit configuration
+    #[must_use]
+    pub fn new(endpoint: &str, api_key: String, default_model: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            api_key,
+            default_model,
+            models: Vec::new(),
+        }
+    }
+
+    /// Add models served by this provider besides the default model
+    #[must_use]
+    pub fn with_models(mut self, models: Vec<String>) -> Self {
+        self.models = models;
+        self
+    }
+
+    /// Convert our `ChatRequest` into a Messages API request body
+    fn build_request_body(&self, request: ChatRequest) -> AiResult<Value> {
+        let model = request.model.unwrap_or_else(|| self.default_model.clone());
+
+        let mut system_parts = Vec::new();
+        let mut messages = Vec::new();
+        for message in request.messages {
+            if message.role == ChatRole::System {
+                if !message.content.is_empty() {
+                    system_parts.push(message.content);
+                }
+            } else {
+                messages.push(Self::to_message(message));
+            }
+        }
+
+        if let Some(response_format) = request.response_format {
+            let Some(json_schema) = response_format.json_schema else {
+                return Err(AiError::Provider(
+                    "response_format requested but json_schema is None".to_string(),
+                ));
+            };
+            // Accept both a bare schema and the `{ "schema": ... }` wrapper
+            let schema = json_schema.get("schema").unwrap_or(&json_schema);
+            system_parts.push(format!(
+                "Respond only with a JSON object, without any surrounding text, that matches this JSON schema:\n{schema}"
+            ));
+        }
+
+        let mut body = json!({
+            "model": model,
+            "max_tokens": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            "messages": messages,
+        });
+
+        if !system_parts.is_empty() {
+            body["system"] = json!(system_parts.join("\n\n"));
+        }
+
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        if let Some(functions) = request.functions {
+            let tools = functions
+                .iter()
+                .map(Self::to_tool)
+                .collect::<AiResult<Vec<_>>>()?;
+
+            // ChatResponse carries a single function call, so ask for one call per turn
+            body["tool_choice"] = match request.function_call.as_deref() {
+                None | Some("auto") => json!({ "type": "auto", "disable_parallel_tool_use": true }),
+                Some("none") => json!({ "type": "none" }),
+                Some("required") => json!({ "type": "any", "disable_parallel_tool_use": true }),
+                Some(name) => {
+                    if !tools.iter().any(|tool| tool["name"] == name) {
+                        return Err(AiError::InvalidRequest(format!(
+                            "function_call '{name}' does not match any provided function"
+                        )));
+                    }
+                    json!({ "type": "tool", "name": name, "disable_parallel_tool_use": true })
+                }
+            };
+            body["tools"] = Value::Array(tools);
+        }
+
+        Ok(body)
+    }
+
+    /// Convert a chat message, including any attached function call, to the wire format
+    ///
+    /// Function results are sent back as `tool_result` blocks in a user turn.
+    fn to_message(msg: ChatMessage) -> Value {
+        match (msg.role, msg.function_call) {
+            (ChatRole::Assistant, Some(call)) => {
+                let input = serde_json::from_str::<Value>(&call.arguments)
+                    .ok()
+                    .filter(Value::is_object)
+                    .unwrap_or_else(|| json!({}));
+                let mut content = Self::text_blocks(&msg.content);
+                content.push(json!({
+                    "type": "tool_use",
+                    "id": Self::tool_use_id(call.id, &call.name),
+                    "name": call.name,
+                    "input": input,
+                }));
+                json!({ "role": "assistant", "content": content })
+            }
+            (ChatRole::Function, Some(call)) => json!({
+                "role": "user",
+                "content": [{
+                    "type": "tool_result",
+                    "tool_use_id": Self::tool_use_id(call.id, &call.name),
+                    "content": msg.content,
+                }],
+            }),
+            (ChatRole::Assistant, None) => {
+                json!({ "role": "assistant", "content": Self::text_blocks(&msg.content) })
+            }
+            (_, _) => json!({ "role": "user", "content": Self::text_blocks(&msg.content) }),
+        }
+    }
+
+    /// The API rejects empty text blocks, so empty content becomes no blocks
+    fn text_blocks(content: &str) -> Vec<Value> {
+        if content.is_empty() {
+            Vec::new()
+        } else {
+            vec![json!({ "type": "text", "text": content })]
+        }
+    }
+
+    /// Tool results must reference their call; calls without an ID are paired by name
+    fn tool_use_id(id: Option<String>, name: &str) -> String {
+        id.unwrap_or_else(|| format!("toolu_{name}"))
+    }
+
+    /// Convert a function definition (as JSON) to a Messages API tool
+    fn to_tool(function: &Value) -> AiResult<Value> {
+        let name = function
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                AiError::InvalidRequest("Function definition has no name".to_string())
+            })?;
+
+        Ok(json!({
+            "name": name,
+            "description": function.get("description").cloned().unwrap_or_else(|| json!("")),
+            "input_schema": function
+                .get("parameters")
+                .cloned()
+                .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+        }))
+    }
+
+    /// Map a Messages API stop reason onto the OpenAI-style finish reasons used elsewhere
+    fn finish_reason(stop_reason: &str) -> String {
+        match stop_reason {
+            "end_turn" | "stop_sequence" => "stop",
+            "max_tokens" => "length",
+            "tool_use" => "tool_calls",
+            other => other,
+        }
+        .to_string()
+    }
+
+    /// Convert a Messages API response into our `ChatResponse`
+    fn to_chat_response(response: MessagesResponse) -> ChatResponse {
+        let mut content = String::new();
+        let mut function_call = None;
+        for block in response.content {
+            match block {
+                ContentBlock::Text { text } => content.push_str(&text),
+                ContentBlock::ToolUse { id, name, input } => {
+                    if function_call.is_some() {
+                        tracing::warn!("Model returned several tool calls; only the first is used");
+                    } else {
+                        function_call = Some(FunctionCall {
+                            id: Some(id).filter(|id| !id.is_empty()),
+                            name,
+                            arguments: input.to_string(),
+                        });
+                    }
+                }
+                ContentBlock::Other => {}
+            }
+        }
+
+        ChatResponse {
+            id: response.id,
+            model: response.model,
+            choices: vec![ChatChoice {
+                index: 0,
+                message: ChatMessage {
+                    role: ChatRole::Assistant,
+                    content,
+                    function_call: function_call.clone(),
+                },
+                finish_reason: response.stop_reason.as_deref().map(Self::finish_reason),
+            }],
+            usage: Some(TokenUsage::new(
+                response.usage.input_tokens,
+                response.usage.output_tokens,
+            )),
+            created: chrono::Utc::now().timestamp(),
+            function_call,
+        }
+    }
+
+    /// Send a request body to the Messages API
+    async fn send(&self, body: &Value, stream: bool) -> AiResult<reqwest::Response> {
+        let mut request = self
+            .http
+            .post(format!("{}/messages", self.endpoint))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(body);
+        if stream {
+            request = request.header(reqwest::header::ACCEPT, "text/event-stream");
+        }
+
+        let response = request.send().await.map_err(AiError::from_transport)?;
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(error_from_response(response).await)
+        }
+    }
+
+    /// Classify an error event from the streaming API by its error type
+    fn error_from_event(error: &Value) -> AiError {
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .map_or_else(|| error.to_string(), str::to_string);
+        match error.get("type").and_then(Value::as_str) {
+            Some("rate_limit_error") => AiError::RateLimitExceeded { retry_after: None },
+            Some("overloaded_error") => AiError::Upstream {
+                status: 529,
+                message,
+            },
+            Some("api_error") => AiError::Upstream {
+                status: 500,
+                message,
+            },
+            _ => AiError::Provider(format!("Stream failed upstream: {message}")),
+        }
+    }
+}
+
+#[async_trait]
+impl AiProvider for AnthropicProvider {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn model(&self) -> &str {
+        &self.default_model
+    }
+
+    fn models(&self) -> Vec<String> {
+        let mut models = vec![self.default_model.clone()];
+        for model in &self.models {
+            if !models.contains(model) {
+                models.push(model.clone());
+            }
+        }
+        models
+    }
+
+    async fn chat(&self, request: ChatRequest) -> AiResult<ChatResponse> {
+        let body = self.build_request_body(request)?;
+        let response: MessagesResponse = self
+            .send(&body, false)
+            .await?
+            .json()
+            .await
+            .map_err(AiError::from_transport)?;
+        Ok(Self::to_chat_response(response))
+    }
+
+    async fn chat_stream(&self, request: ChatRequest) -> AiResult<ChatStream> {
+        let mut body = self.build_request_body(request)?;
+        let model = body["model"].as_str().unwrap_or_default().to_string();
+        body["stream"] = json!(true);
+
+        let response = self.send(&body, true).await?;
+
+        let events = stream::unfold(
+            (response, MessageStreamParser::new(model), false),
+            |(mut response, mut parser, finished)| async move {
+                if finished {
+                    return None;
+                }
+                match response.chunk().await {
+                    Ok(Some(bytes)) => {
+                        let events = parser.feed(&bytes);
+                        let finished = parser.is_done();
+                        Some((events, (response, parser, finished)))
+                    }
+                    Ok(None) => Some((parser.finish(), (response, parser, true))),
+                    Err(e) => Some((vec![Err(AiError::Network(e))], (response, parser, true))),
+                }
+            },
+        )
+        .flat_map(stream::iter);
+
+        Ok(Box::pin(events))
+    }
+
+    async fn health_check(&self) -> AiResult<()> {
+        let mut request = ChatRequest::new(vec![ChatMessage {
+            role: ChatRole::User,
+            content: "Hi".to_string(),
+            function_call: None,
+        }]);
+        request.max_tokens = Some(1);
+        self.chat(request).await.map(|_| ())
+    }
+}
+
+/// Response from the Messages API
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    id: String,
+    model: String,
+    content: Vec<ContentBlock>,
+    stop_reason: Option<String>,
+    usage: MessagesUsage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MessagesUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+/// A single event from the streaming Messages API
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum MessageStreamEvent {
+    MessageStart {
+        message: StreamMessage,
+    },
+    ContentBlockDelta {
+        delta: BlockDelta,
+    },
+    MessageDelta {
+        delta: MessageDelta,
+        #[serde(default)]
+        usage: Option<MessagesUsage>,
+    },
+    MessageStop,
+    Error {
+        error: Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamMessage {
+    id: String,
+    model: String,
+    #[serde(default)]
+    usage: MessagesUsage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BlockDelta {
+    TextDelta {
+        text: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDelta {
+    stop_reason: Option<String>,
+}
+
+/// Turns the raw SSE byte stream of a Messages API response into `StreamEvent`s
+///
+/// Kept free of any I/O so the wire handling can be tested without a network.
+struct MessageStreamParser {
+    decoder: SseDecoder,
+    model: String,
+    started: bool,
+    done: bool,
+    finish_reason: Option<String>,
+    prompt_tokens: u32,
+    completion_tokens: Option<u32>,
+}
+
+impl MessageStreamParser {
+    fn new(model: String) -> Self {
+        Self {
+            decoder: SseDecoder::new(),
+            model,
+            started: false,
+            done: false,
+            finish_reason: None,
+            prompt_tokens: 0,
+            completion_tokens: None,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Feed raw bytes from the response body
+    fn feed(&mut self, bytes: &[u8]) -> Vec<AiResult<StreamEvent>> {
+        let mut events = Vec::new();
+        for payload in self.decoder.push(bytes) {
+            self.handle_payload(&payload, &mut events);
+        }
+        events
+    }
+
+    /// Flush the parser once the response body has ended
+    fn finish(&mut self) -> Vec<AiResult<StreamEvent>> {
+        let mut events = Vec::new();
+        if let Some(payload) = self.decoder.finish() {
+            self.handle_payload(&payload, &mut events);
+        }
+        if !self.done {
+            self.complete(&mut events);
+        }
+        events
+    }
+
+    fn handle_payload(&mut self, payload: &str, events: &mut Vec<AiResult<StreamEvent>>) {
+        if self.done {
+            return;
+        }
+
+        let event: MessageStreamEvent = match serde_json::from_str(payload) {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!("Skipping unparseable stream event: {e}");
+                return;
+            }
+        };
+
+        match event {
+            MessageStreamEvent::MessageStart { message } => {
+                self.prompt_tokens = message.usage.input_tokens;
+                if !self.started {
+                    self.started = true;
+                    events.push(Ok(StreamEvent::Start {
+                        id: message.id,
+                        model: message.model,
+                    }));
+                }
+            }
+            MessageStreamEvent::ContentBlockDelta {
+                delta: BlockDelta::TextDelta { text },
+            } => {
+                self.ensure_started(events);
+                if !text.is_empty() {
+                    events.push(Ok(StreamEvent::Delta {
+                        content: text,
+                        index: 0,
+                    }));
+                }
+            }
+            MessageStreamEvent::MessageDelta { delta, usage } => {
+                if let Some(stop_reason) = delta.stop_reason {
+                    self.finish_reason = Some(AnthropicProvider::finish_reason(&stop_reason));
+                }
+                if let Some(usage) = usage {
+                    self.completion_tokens = Some(usage.output_tokens);
+                }
+            }
+            MessageStreamEvent::MessageStop => self.complete(events),
+            MessageStreamEvent::Error { error } => {
+                events.push(Err(AnthropicProvider::error_from_event(&error)));
+                self.done = true;
+            }
+            MessageStreamEvent::ContentBlockDelta { .. } | MessageStreamEvent::Other => {}
+        }
+    }
+
+    fn ensure_started(&mut self, events: &mut Vec<AiResult<StreamEvent>>) {
+        if !self.started {
+            self.started = true;
+            events.push(Ok(StreamEvent::Start {
+                id: String::new(),
+                model: self.model.clone(),
+            }));
+        }
+    }
+
+    fn complete(&mut self, events: &mut Vec<AiResult<StreamEvent>>) {
+        self.ensure_started(events);
+        events.push(Ok(StreamEvent::Done {
+            finish_reason: self
+                .finish_reason
+                .take()
+                .unwrap_or_else(|| "stop".to_string()),
+            usage: self
+                .completion_tokens
+                .map(|completion| TokenUsage::new(self.prompt_tokens, completion)),
+        }));
+        self.done = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_provider() -> AnthropicProvider {
+        AnthropicProvider::new(
+            "https://api.anthropic.com/v1/",
+            "test-key".to_string(),
+            "claude-test".to_string(),
+        )
+    }
+
+    fn message(role: ChatRole, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+            function_call: None,
+        }
+    }
+
+    fn collect(events: Vec<AiResult<StreamEvent>>) -> Vec<StreamEvent> {
+        events
+            .into_iter()
+            .map(|e| e.expect("event should not be an error"))
+            .collect()
+    }
+
+    #[test]
+    fn test_system_messages_become_system_field() {
+        let mut request = ChatRequest::new(vec![
+            message(ChatRole::System, "Be brief."),
+            message(ChatRole::User, "Hello"),
+        ])
+        .with_json_schema(json!({ "schema": { "type": "object" } }));
+        request.temperature = Some(0.5);
+
+        let body = test_provider()
+            .build_request_body(request)
+            .expect("body should build");
+
+        assert_eq!(body["model"], "claude-test");
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert_eq!(body["temperature"], 0.5);
+        let system = body["system"].as_str().expect("system prompt");
+        assert!(system.starts_with("Be brief."));
+        assert!(system.contains("{\"type\":\"object\"}"));
+        assert_eq!(body["messages"].as_array().map(Vec::len), Some(1));
+        assert_eq!(body["messages"][0]["role"], "user");
+        assert_eq!(body["messages"][0]["content"][0]["text"], "Hello");
+    }
+
+    #[test]
+    fn test_functions_are_sent_as_tools() {
+        let mut request =
+            ChatRequest::new(vec![]).with_functions(&crate::ai::get_business_analyst_functions());
+        let body = test_provider()
+            .build_request_body(request.clone())
+            .expect("body should build");
+
+        let tools = body["tools"].as_array().expect("tools");
+        assert_eq!(tools.len(), 4);
+        assert!(
+            tools
+                .iter()
+                .all(|tool| tool["input_schema"]["type"] == "object")
+        );
+        assert_eq!(body["tool_choice"]["type"], "auto");
+
+        request.function_call = Some("create_issue".to_string());
+        let body = test_provider()
+            .build_request_body(request.clone())
+            .expect("body should build");
+        assert_eq!(body["tool_choice"]["type"], "tool");
+        assert_eq!(body["tool_choice"]["name"], "create_issue");
+
+        request.function_call = Some("missing".to_string());
+        assert!(matches!(
+            test_provider().build_request_body(request),
+            Err(AiError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_function_turns_map_to_tool_blocks() {
+        let call = FunctionCall {
+            id: Some("toolu_1".to_string()),
+            name: "create_issue".to_string(),
+            arguments: "{\"confirm\":false}".to_string(),
+        };
+
+        let assistant = AnthropicProvider::to_message(ChatMessage {
+            role: ChatRole::Assistant,
+            content: String::new(),
+            function_call: Some(call.clone()),
+        });
+        assert_eq!(assistant["role"], "assistant");
+        assert_eq!(assistant["content"].as_array().map(Vec::len), Some(1));
+        assert_eq!(assistant["content"][0]["type"], "tool_use");
+        assert_eq!(assistant["content"][0]["input"]["confirm"], false);
+
+        let result = AnthropicProvider::to_message(ChatMessage {
+            role: ChatRole::Function,
+            content: "{\"success\":true}".to_string(),
+            function_call: Some(call),
+        });
+        assert_eq!(result["role"], "user");
+        assert_eq!(result["content"][0]["type"], "tool_result");
+        assert_eq!(result["content"][0]["tool_use_id"], "toolu_1");
+    }
+
+    #[test]
+    fn test_response_is_converted() {
+        let response: MessagesResponse = serde_json::from_value(json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-test",
+            "content": [
+                { "type": "text", "text": "Let me save that." },
+                { "type": "tool_use", "id": "toolu_9", "name": "save_context", "input": { "content": "x" } }
+            ],
+            "stop_reason": "tool_use",
+            "usage": { "input_tokens": 12, "output_tokens": 5 }
+        }))
+        .expect("response should parse");
+
+        let chat = AnthropicProvider::to_chat_response(response);
+        assert_eq!(chat.content(), "Let me save that.");
+        assert_eq!(chat.choices[0].finish_reason.as_deref(), Some("tool_calls"));
+        let call = chat.function_call.expect("function call");
+        assert_eq!(call.id.as_deref(), Some("toolu_9"));
+        assert_eq!(call.name, "save_context");
+        assert_eq!(call.arguments, "{\"content\":\"x\"}");
+        let usage = chat.usage.expect("usage");
+        assert_eq!((usage.prompt, usage.completion, usage.total), (12, 5, 17));
+    }
+
+    #[test]
+    fn test_stream_parser_emits_start_deltas_and_done_with_usage() {
+        let mut parser = MessageStreamParser::new("fallback".to_string());
+        let events = collect(parser.feed(
+            b"event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"model\":\"claude-test\",\"usage\":{\"input_tokens\":7}}}\n\n\
+event: ping\ndata: {\"type\":\"ping\"}\n\n\
+event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hel\"}}\n\n\
+event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"lo\"}}\n\n\
+event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":2}}\n\n\
+event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+        ));
+
+        assert!(parser.is_done());
+        assert_eq!(events.len(), 4);
+        assert!(
+            matches!(&events[0], StreamEvent::Start { id, model } if id == "msg_1" && model == "claude-test")
+        );
+        assert!(matches!(&events[1], StreamEvent::Delta { content, .. } if content == "Hel"));
+        assert!(matches!(&events[2], StreamEvent::Delta { content, .. } if content == "lo"));
+        match &events[3] {
+            StreamEvent::Done {
+                finish_reason,
+                usage,
+            } => {
+                assert_eq!(finish_reason, "stop");
+                let usage = usage.as_ref().expect("usage should be reported");
+                assert_eq!((usage.prompt, usage.completion, usage.total), (7, 2, 9));
+            }
+            other => panic!("Expected Done event, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_stream_parser_reports_upstream_error() {
+        let mut parser = MessageStreamParser::new("fallback".to_string());
+        let events = parser.feed(
+            b"event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n",
+        );
+
+        assert!(parser.is_done());
+        let error = events
+            .into_iter()
+            .next()
+            .expect("one event")
+            .expect_err("should be an error");
+        assert!(error.is_retryable());
+        assert!(error.to_string().contains("Overloaded"));
+    }
+}
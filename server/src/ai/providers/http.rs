@@ -0,0 +1,101 @@
+//! Helpers shared by providers that talk to their API over HTTP
+//!
+//! Upstream failures are classified into structured `AiError` variants so the
+//! retry policy can tell rate limits, timeouts and server errors apart from
+//! requests that will never succeed.
+
+use crate::ai::AiError;
+use std::time::Duration;
+
+/// Turn an unsuccessful response into a classified error
+pub(crate) async fn error_from_response(response: reqwest::Response) -> AiError {
+    let status = response.status().as_u16();
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    let body = response.text().await.unwrap_or_default();
+
+    AiError::from_status(status, retry_after, error_message(&body))
+}
+
+/// Classify an error object returned in the body of a successful response
+///
+/// `OpenRouter` reports some upstream failures this way, with the HTTP status in `code`.
+pub(crate) fn error_from_body(error: &serde_json::Value) -> AiError {
+    let message = error
+        .get("message")
+        .and_then(serde_json::Value::as_str)
+        .map_or_else(|| error.to_string(), str::to_string);
+
+    match error
+        .get("code")
+        .and_then(serde_json::Value::as_u64)
+        .and_then(|code| u16::try_from(code).ok())
+    {
+        Some(status) => AiError::from_status(status, None, message),
+        None => AiError::Provider(message),
+    }
+}
+
+/// Extract the error message from an error response body, falling back to the raw body
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| value["error"]["message"].as_str().map(str::to_string))
+        .unwrap_or_else(|| body.to_string())
+}
+
+/// Parse a `Retry-After` header given either as seconds or as an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return (seconds.is_finite() && seconds >= 0.0).then(|| Duration::from_secs_f64(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after(" 1.5 "),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(parse_retry_after("-1"), None);
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_error_message_prefers_error_field() {
+        assert_eq!(
+            error_message("{\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}"),
+            "Overloaded"
+        );
+        assert_eq!(error_message("Bad gateway"), "Bad gateway");
+    }
+
+    #[test]
+    fn test_error_from_body_uses_code_as_status() {
+        let limited = error_from_body(&serde_json::json!({
+            "code": 429,
+            "message": "Rate limit exceeded upstream"
+        }));
+        assert!(matches!(limited, AiError::RateLimitExceeded { .. }));
+
+        let unknown = error_from_body(&serde_json::json!({ "message": "Something broke" }));
+        assert!(matches!(unknown, AiError::Provider(ref message) if message == "Something broke"));
+    }
+}
@@ -0,0 +1,528 @@
+//! Deterministic provider for offline tests and local development
+//!
+//! `MockProvider` never touches the network. Replies come from a script, which can
+//! be loaded from a JSON fixture file or built in code:
+//!
+//! ```json
+//! {
+//!   "model": "mock-model",
+//!   "replies": [
+//!     { "content": "First reply", "chunks": ["First ", "reply"] },
+//!     { "function_call": { "name": "create_issue", "arguments": { "title": "Bug" } } },
+//!     { "error": { "status": 429, "message": "Slow down", "retry_after_secs": 1 } }
+//!   ],
+//!   "rules": [
+//!     { "when": "hello", "content": "Hi there!" }
+//!   ],
+//!   "default": { "content": "I am a mock." }
+//! }
+//! ```
+//!
+//! Each request takes the next scripted reply from `replies`. Once those are used
+//! up, the first rule whose `when` text appears in the last user message answers,
+//! then `default`, and finally an echo of the last user message.
+//!
+//! Embedding requests are answered with `LocalEmbedder` vectors, so similar texts
+//! get similar embeddings.
+
+use super::traits::{AiProvider, ChatStream};
+use crate::ai::embeddings::LocalEmbedder;
+use crate::ai::models::chat::{ChatChoice, FunctionCall, StreamEvent};
+use crate::ai::models::usage::TokenUsage;
+use crate::ai::{
+    AiError, AiResult, ChatMessage, ChatRequest, ChatResponse, ChatRole, EmbeddingRequest,
+    EmbeddingResponse,
+};
+use async_trait::async_trait;
+use futures::stream;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Model reported when the script does not name one
+const DEFAULT_MODEL: &str = "mock-model";
+
+/// A scripted reply
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MockReply {
+    /// Assistant message content
+    pub content: String,
+    /// Deltas sent when streaming; defaults to the content split after each space
+    pub chunks: Option<Vec<String>>,
+    /// Function call the model requests instead of answering
+    pub function_call: Option<MockFunctionCall>,
+    /// Fail the request with this error instead of answering
+    pub error: Option<MockError>,
+    /// Fail a stream with this message after the chunks have been sent
+    pub stream_error: Option<String>,
+    /// Finish reason; defaults to `tool_calls` for function calls and `stop` otherwise
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockFunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+/// An upstream failure, classified the same way as a real HTTP error response
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockError {
+    pub status: u16,
+    #[serde(default)]
+    pub message: String,
+    pub retry_after_secs: Option<u64>,
+}
+
+/// A reply given whenever the last user message contains `when`
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockRule {
+    pub when: String,
+    #[serde(flatten)]
+    pub reply: MockReply,
+}
+
+/// The full script a `MockProvider` plays back
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MockScript {
+    pub model: Option<String>,
+    pub replies: Vec<MockReply>,
+    pub rules: Vec<MockRule>,
+    pub default: Option<MockReply>,
+}
+
+impl MockReply {
+    /// A plain text reply
+    #[must_use]
+    pub fn text(content: &str) -> Self {
+        Self {
+            content: content.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// A reply requesting a function call
+    #[must_use]
+    pub fn function_call(name: &str, arguments: serde_json::Value) -> Self {
+        Self {
+            function_call: Some(MockFunctionCall {
+                name: name.to_string(),
+                arguments,
+            }),
+            ..Self::default()
+        }
+    }
+
+    /// A reply that fails as if the provider answered with `status`
+    #[must_use]
+    pub fn error(status: u16, message: &str) -> Self {
+        Self {
+            error: Some(MockError {
+                status,
+                message: message.to_string(),
+                retry_after_secs: None,
+            }),
+            ..Self::default()
+        }
+    }
+
+    /// Stream deltas in the given chunks
+    #[must_use]
+    pub fn with_chunks(mut self, chunks: &[&str]) -> Self {
+        self.chunks = Some(chunks.iter().map(ToString::to_string).collect());
+        self
+    }
+
+    fn to_error(&self) -> Option<AiError> {
+        self.error.as_ref().map(|error| {
+            AiError::from_status(
+                error.status,
+                error.retry_after_secs.map(Duration::from_secs),
+                error.message.clone(),
+            )
+        })
+    }
+
+    fn chunks(&self) -> Vec<String> {
+        self.chunks.clone().unwrap_or_else(|| {
+            self.content
+                .split_inclusive(' ')
+                .map(str::to_string)
+                .collect()
+        })
+    }
+
+    fn finish_reason(&self) -> String {
+        self.finish_reason.clone().unwrap_or_else(|| {
+            if self.function_call.is_some() {
+                "tool_calls".to_string()
+            } else {
+                "stop".to_string()
+            }
+        })
+    }
+}
+
+pub struct MockProvider {
+    name: String,
+    model: String,
+    replies: Mutex<VecDeque<MockReply>>,
+    rules: Vec<MockRule>,
+    default: Option<MockReply>,
+    requests: Mutex<Vec<ChatRequest>>,
+    next_id: AtomicU64,
+}
+
+impl MockProvider {
+    /// Provider name, usable in routing rules
+    pub const NAME: &'static str = "mock";
+
+    /// Create the provider from environment configuration, if it is enabled
+    ///
+    /// Reads configuration from:
+    /// - `AI_MOCK_PROVIDER` - Set to `true` to use the mock provider
+    /// - `AI_MOCK_FIXTURE`  - Optional path to a JSON script; setting it also enables the mock
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fixture cannot be read or parsed
+    pub fn from_env() -> AiResult<Option<Self>> {
+        if let Ok(path) = std::env::var("AI_MOCK_FIXTURE") {
+            tracing::info!("Using mock AI provider with fixture {path}");
+            return Self::from_fixture(path).map(Some);
+        }
+
+        let enabled = std::env::var("AI_MOCK_PROVIDER")
+            .is_ok_and(|value| matches!(value.trim(), "1" | "true" | "yes"));
+        if enabled {
+            tracing::info!("Using mock AI provider");
+        }
+        Ok(enabled.then(Self::new))
+    }
+
+    /// Create a provider that echoes the last user message
+    #[must_use]
+    pub fn new() -> Self {
+        Self::from_script(MockScript::default())
+    }
+
+    /// Create a provider that plays back a script
+    #[must_use]
+    pub fn from_script(script: MockScript) -> Self {
+        Self {
+            name: Self::NAME.to_string(),
+            model: script.model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            replies: Mutex::new(script.replies.into()),
+            rules: script.rules,
+            default: script.default,
+            requests: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Create a provider from a JSON fixture file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid script
+    pub fn from_fixture(path: impl AsRef<Path>) -> AiResult<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            AiError::Configuration(format!(
+                "Failed to read mock fixture {}: {e}",
+                path.display()
+            ))
+        })?;
+        let script: MockScript = serde_json::from_str(&contents).map_err(|e| {
+            AiError::Configuration(format!("Invalid mock fixture {}: {e}", path.display()))
+        })?;
+        Ok(Self::from_script(script))
+    }
+
+    /// Register the provider under another name, so several can be routed to
+    #[must_use]
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Queue a reply after the ones already scripted
+    pub fn push_reply(&self, reply: MockReply) {
+        self.replies
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push_back(reply);
+    }
+
+    /// Requests received so far, oldest first
+    #[must_use]
+    pub fn requests(&self) -> Vec<ChatRequest> {
+        self.requests
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Record the request and pick the reply for it
+    fn next_reply(&self, request: &ChatRequest) -> MockReply {
+        self.requests
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(request.clone());
+
+        if let Some(reply) = self
+            .replies
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .pop_front()
+        {
+            return reply;
+        }
+
+        let last_user_message = request
+            .messages
+            .iter()
+            .rev()
+            .find(|message| message.role == ChatRole::User)
+            .map_or("", |message| message.content.as_str());
+
+        self.rules
+            .iter()
+            .find(|rule| last_user_message.contains(&rule.when))
+            .map(|rule| rule.reply.clone())
+            .or_else(|| self.default.clone())
+            .unwrap_or_else(|| MockReply::text(&format!("Mock response to: {last_user_message}")))
+    }
+
+    fn next_id(&self) -> String {
+        format!("mock-{}", self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Usage counted in words, so it is stable across runs
+    fn usage(request: &ChatRequest, reply: &MockReply) -> TokenUsage {
+        let count = |text: &str| u32::try_from(text.split_whitespace().count()).unwrap_or(u32::MAX);
+        let prompt = request
+            .messages
+            .iter()
+            .map(|message| count(&message.content))
+            .sum();
+        let arguments = reply
+            .function_call
+            .as_ref()
+            .map_or(0, |call| count(&call.arguments.to_string()));
+        TokenUsage::new(prompt, count(&reply.content) + arguments)
+    }
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl AiProvider for MockProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn chat(&self, request: ChatRequest) -> AiResult<ChatResponse> {
+        let reply = self.next_reply(&request);
+        if let Some(error) = reply.to_error() {
+            return Err(error);
+        }
+
+        let function_call = reply.function_call.as_ref().map(|call| FunctionCall {
+            id: Some(format!("call_{}", call.name)),
+            name: call.name.clone(),
+            arguments: call.arguments.to_string(),
+        });
+
+        Ok(ChatResponse {
+            id: self.next_id(),
+            model: request.model.clone().unwrap_or_else(|| self.model.clone()),
+            choices: vec![ChatChoice {
+                index: 0,
+                message: ChatMessage {
+                    role: ChatRole::Assistant,
+                    content: reply.content.clone(),
+                    function_call: function_call.clone(),
+                },
+                finish_reason: Some(reply.finish_reason()),
+            }],
+            usage: Some(Self::usage(&request, &reply)),
+            created: chrono::Utc::now().timestamp(),
+            function_call,
+        })
+    }
+
+    async fn chat_stream(&self, request: ChatRequest) -> AiResult<ChatStream> {
+        let reply = self.next_reply(&request);
+        if let Some(error) = reply.to_error() {
+            return Err(error);
+        }
+
+        let mut events = vec![Ok(StreamEvent::Start {
+            id: self.next_id(),
+            model: request.model.clone().unwrap_or_else(|| self.model.clone()),
+        })];
+        events.extend(
+            reply
+                .chunks()
+                .into_iter()
+                .map(|content| Ok(StreamEvent::Delta { content, index: 0 })),
+        );
+        if let Some(message) = &reply.stream_error {
+            events.push(Err(AiError::Provider(message.clone())));
+        } else {
+            events.push(Ok(StreamEvent::Done {
+                finish_reason: reply.finish_reason(),
+                usage: Some(Self::usage(&request, &reply)),
+            }));
+        }
+
+        Ok(Box::pin(stream::iter(events)))
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> AiResult<EmbeddingResponse> {
+        let embedder = LocalEmbedder::default();
+        let words = request
+            .input
+            .iter()
+            .map(|text| u32::try_from(text.split_whitespace().count()).unwrap_or(u32::MAX))
+            .sum();
+
+        Ok(EmbeddingResponse {
+            model: request.model.unwrap_or_else(|| self.model.clone()),
+            embeddings: request
+                .input
+                .iter()
+                .map(|text| embedder.embed(text))
+                .collect(),
+            usage: Some(TokenUsage::new(words, 0)),
+        })
+    }
+
+    async fn health_check(&self) -> AiResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn user(content: &str) -> ChatRequest {
+        ChatRequest::new(vec![ChatMessage {
+            role: ChatRole::User,
+            content: content.to_string(),
+            function_call: None,
+        }])
+    }
+
+    #[tokio::test]
+    async fn test_replies_play_in_order_then_fall_back() {
+        let provider = MockProvider::from_script(MockScript {
+            replies: vec![MockReply::text("one"), MockReply::text("two")],
+            rules: vec![MockRule {
+                when: "weather".to_string(),
+                reply: MockReply::text("Sunny"),
+            }],
+            ..MockScript::default()
+        });
+
+        for expected in ["one", "two"] {
+            let response = provider.chat(user("weather?")).await.expect("scripted");
+            assert_eq!(response.content(), expected);
+        }
+        let ruled = provider
+            .chat(user("what's the weather"))
+            .await
+            .expect("rule");
+        assert_eq!(ruled.content(), "Sunny");
+        let echoed = provider.chat(user("hi")).await.expect("echo");
+        assert_eq!(echoed.content(), "Mock response to: hi");
+        assert_eq!(provider.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_function_calls_and_errors() {
+        let provider = MockProvider::new();
+        provider.push_reply(MockReply::function_call(
+            "create_issue",
+            serde_json::json!({ "title": "Bug" }),
+        ));
+        provider.push_reply(MockReply::error(503, "overloaded"));
+
+        let response = provider.chat(user("file it")).await.expect("call");
+        let call = response.function_call.expect("function call");
+        assert_eq!(call.name, "create_issue");
+        assert_eq!(call.arguments, "{\"title\":\"Bug\"}");
+        assert_eq!(
+            response.choices[0].finish_reason.as_deref(),
+            Some("tool_calls")
+        );
+
+        let error = provider.chat(user("again")).await.expect_err("error");
+        assert!(matches!(error, AiError::Upstream { status: 503, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_stream_sends_chunks() {
+        let provider = MockProvider::new();
+        provider.push_reply(MockReply::text("Hello there").with_chunks(&["Hel", "lo there"]));
+        provider.push_reply(MockReply {
+            stream_error: Some("connection reset".to_string()),
+            ..MockReply::text("Partial answer")
+        });
+
+        let events: Vec<_> = provider
+            .chat_stream(user("hi"))
+            .await
+            .expect("stream")
+            .collect()
+            .await;
+        let deltas: Vec<String> = events
+            .iter()
+            .filter_map(|event| match event {
+                Ok(StreamEvent::Delta { content, .. }) => Some(content.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(deltas, ["Hel", "lo there"]);
+        assert!(matches!(events.last(), Some(Ok(StreamEvent::Done { .. }))));
+
+        let events: Vec<_> = provider
+            .chat_stream(user("hi"))
+            .await
+            .expect("stream")
+            .collect()
+            .await;
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events.last(), Some(Err(AiError::Provider(_)))));
+    }
+
+    #[tokio::test]
+    async fn test_from_fixture() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/ai/mock_chat.json"
+        );
+        let provider = MockProvider::from_fixture(path).expect("fixture");
+
+        let response = provider.chat(user("hello")).await.expect("reply");
+        assert!(!response.content().is_empty());
+        assert!(MockProvider::from_fixture("/nonexistent.json").is_err());
+    }
+}
@@ -1,7 +1,19 @@
 //! AI provider implementations
 
+pub mod anthropic;
+mod http;
+pub mod mock;
+pub mod openai_compatible;
 pub mod openrouter;
+pub mod registry;
+pub mod retry;
+pub mod sse;
 pub mod traits;
 
+pub use anthropic::AnthropicProvider;
+pub use mock::{MockProvider, MockReply, MockScript};
+pub use openai_compatible::OpenAiCompatibleProvider;
 pub use openrouter::OpenRouterProvider;
-pub use traits::{AiProvider, UsageStats};
+pub use registry::{ModelRoute, ProviderInfo, ProviderRegistry};
+pub use retry::{RetryPolicy, RetryingProvider};
+pub use traits::{AiProvider, ChatStream, UsageStats};
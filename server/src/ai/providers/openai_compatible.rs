@@ -0,0 +1,110 @@
+//! Provider for any OpenAI-compatible chat completions endpoint
+//!
+//! Self-hosted servers such as llama.cpp or vLLM speak the same API as `OpenRouter`,
+//! so this provider reuses its request and streaming handling and only differs in
+//! name and configuration.
+
+use super::openrouter::OpenRouterProvider;
+use super::registry::parse_model_list;
+use super::traits::{AiProvider, ChatStream};
+use crate::ai::{
+    AiError, AiResult, ChatRequest, ChatResponse, EmbeddingRequest, EmbeddingResponse,
+};
+use async_trait::async_trait;
+
+pub struct OpenAiCompatibleProvider {
+    name: String,
+    inner: OpenRouterProvider,
+}
+
+impl OpenAiCompatibleProvider {
+    /// Name used in routing rules when `OPENAI_COMPAT_NAME` is not set
+    pub const DEFAULT_NAME: &'static str = "openai_compatible";
+
+    /// Create the provider from environment configuration, if one is configured
+    ///
+    /// Reads configuration from:
+    /// - `OPENAI_COMPAT_ENDPOINT` - Base URL (e.g. `http://localhost:8000/v1`); the
+    ///   provider is disabled when unset
+    /// - `OPENAI_COMPAT_MODEL`    - Required default model
+    /// - `OPENAI_COMPAT_API_KEY`  - Optional API key
+    /// - `OPENAI_COMPAT_MODELS`   - Optional comma-separated list of additional models
+    /// - `OPENAI_COMPAT_NAME`     - Optional provider name (defaults to `openai_compatible`)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the endpoint is set without a default model
+    pub fn from_env() -> AiResult<Option<Self>> {
+        let Ok(endpoint) = std::env::var("OPENAI_COMPAT_ENDPOINT") else {
+            return Ok(None);
+        };
+        let default_model = std::env::var("OPENAI_COMPAT_MODEL").map_err(|_| {
+            AiError::Configuration(
+                "OPENAI_COMPAT_MODEL is required when OPENAI_COMPAT_ENDPOINT is set".to_string(),
+            )
+        })?;
+        let name =
+            std::env::var("OPENAI_COMPAT_NAME").unwrap_or_else(|_| Self::DEFAULT_NAME.to_string());
+        let api_key = std::env::var("OPENAI_COMPAT_API_KEY").ok();
+        let models = std::env::var("OPENAI_COMPAT_MODELS")
+            .map(|value| parse_model_list(&value))
+            .unwrap_or_default();
+
+        tracing::info!("Using OpenAI-compatible provider '{name}' at {endpoint}");
+
+        Ok(Some(
+            Self::new(name, &endpoint, api_key, default_model).with_models(models),
+        ))
+    }
+
+    /// Create a provider for the given endpoint
+    #[must_use]
+    pub fn new(
+        name: String,
+        endpoint: &str,
+        api_key: Option<String>,
+        default_model: String,
+    ) -> Self {
+        let inner =
+            OpenRouterProvider::with_endpoint(endpoint, api_key.unwrap_or_default(), default_model);
+        Self { name, inner }
+    }
+
+    /// Add models served by this endpoint besides the default model
+    #[must_use]
+    pub fn with_models(mut self, models: Vec<String>) -> Self {
+        self.inner = self.inner.with_models(models);
+        self
+    }
+}
+
+#[async_trait]
+impl AiProvider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn models(&self) -> Vec<String> {
+        self.inner.models()
+    }
+
+    async fn chat(&self, request: ChatRequest) -> AiResult<ChatResponse> {
+        self.inner.chat(request).await
+    }
+
+    async fn chat_stream(&self, request: ChatRequest) -> AiResult<ChatStream> {
+        self.inner.chat_stream(request).await
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> AiResult<EmbeddingResponse> {
+        self.inner.embed(request).await
+    }
+
+    async fn health_check(&self) -> AiResult<()> {
+        self.inner.health_check().await
+    }
+}
@@ -1,18 +1,31 @@
 //! `OpenRouter` AI provider implementation
 
-use super::traits::AiProvider;
-use crate::ai::{AiResult, ChatMessage, ChatRequest, ChatResponse, ChatRole};
+use super::http::{error_from_body, error_from_response};
+use super::registry::parse_model_list;
+use super::sse::SseDecoder;
+use super::traits::{AiProvider, ChatStream};
+use crate::ai::models::chat::FunctionCall;
+use crate::ai::models::{StreamEvent, TokenUsage};
+use crate::ai::{
+    AiError, AiResult, ChatMessage, ChatRequest, ChatResponse, ChatRole, EmbeddingRequest,
+    EmbeddingResponse,
+};
 use async_trait::async_trait;
-use openai_api_rs::v1::api::OpenAIClient;
+use futures::StreamExt;
+use futures::stream;
 use openai_api_rs::v1::chat_completion::{
-    ChatCompletionMessage, ChatCompletionRequest, Content, MessageRole,
+    ChatCompletionMessage, ChatCompletionRequest, ChatCompletionResponse, Content, MessageRole,
+    ToolCall, ToolCallFunction,
 };
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use serde::Deserialize;
+use serde_json::json;
 
 pub struct OpenRouterProvider {
-    client: Arc<Mutex<OpenAIClient>>,
+    http: reqwest::Client,
+    endpoint: String,
+    api_key: String,
     default_model: String,
+    models: Vec<String>,
 }
 
 impl OpenRouterProvider {
@@ -46,10 +59,10 @@ impl OpenRouterProvider {
         };
 
         // Pretty print the given JSON schema for debugging
-        if tracing::enabled!(tracing::Level::DEBUG) {
-            if let Ok(json) = serde_json::to_string_pretty(&final_schema) {
-                tracing::debug!("Final JSON Schema:\n{}", json);
-            }
+        if tracing::enabled!(tracing::Level::DEBUG)
+            && let Ok(json) = serde_json::to_string_pretty(&final_schema)
+        {
+            tracing::debug!("Final JSON Schema:\n{}", json);
         }
 
         // Create the OpenAI-compatible response_format structure
@@ -68,12 +81,11 @@ impl OpenRouterProvider {
     /// - `OPENROUTER_API_KEY` - Required API key
     /// - `AI_DEFAULT_MODEL`   - Required default model
     /// - `OPENROUTER_ENDPOINT` - Optional endpoint (defaults to `OpenRouter`)
+    /// - `OPENROUTER_MODELS`  - Optional comma-separated list of additional models
     ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// - Required environment variables are missing
-    /// - The `OpenAI` client cannot be created
+    /// Returns an error if required environment variables are missing
     pub fn new() -> AiResult<Self> {
         // Get configuration from environment
         let api_key = std::env::var("OPENROUTER_API_KEY").map_err(|_| {
@@ -93,73 +105,55 @@ impl OpenRouterProvider {
         tracing::info!("Using provider endpoint: {endpoint}");
         tracing::info!("Using AI model: {default_model}");
 
-        let client = OpenAIClient::builder()
-            .with_endpoint(&endpoint)
-            .with_api_key(api_key)
-            .build()
-            .map_err(|e| crate::ai::AiError::Provider(format!("Failed to create client: {e}")))?;
+        let models = std::env::var("OPENROUTER_MODELS")
+            .map(|value| parse_model_list(&value))
+            .unwrap_or_default();
 
-        Ok(Self {
-            client: Arc::new(Mutex::new(client)),
-            default_model,
-        })
+        Ok(Self::build(&endpoint, api_key, default_model).with_models(models))
     }
 
     /// Create a new `OpenRouter` provider with explicit configuration
     ///
     /// This method is kept for backwards compatibility and testing
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if the `OpenAI` client cannot be created
-    pub fn with_config(api_key: String, default_model: String) -> AiResult<Self> {
+    #[must_use]
+    pub fn with_config(api_key: String, default_model: String) -> Self {
         let endpoint = std::env::var("OPENROUTER_ENDPOINT")
             .unwrap_or_else(|_| "https://openrouter.ai/api/v1".to_string());
 
-        let client = OpenAIClient::builder()
-            .with_endpoint(&endpoint)
-            .with_api_key(api_key)
-            .build()
-            .map_err(|e| crate::ai::AiError::Provider(format!("Failed to create client: {e}")))?;
-
-        Ok(Self {
-            client: Arc::new(Mutex::new(client)),
-            default_model,
-        })
+        Self::build(&endpoint, api_key, default_model)
     }
-}
 
-#[async_trait]
-impl AiProvider for OpenRouterProvider {
-    fn name(&self) -> &'static str {
-        "openrouter"
+    /// Create a provider for any OpenAI-compatible endpoint
+    #[must_use]
+    pub fn with_endpoint(endpoint: &str, api_key: String, default_model: String) -> Self {
+        Self::build(endpoint, api_key, default_model)
     }
 
-    fn model(&self) -> &str {
-        &self.default_model
+    /// Add models served by this provider besides the default model
+    #[must_use]
+    pub fn with_models(mut self, models: Vec<String>) -> Self {
+        self.models = models;
+        self
     }
 
-    async fn chat(&self, request: ChatRequest) -> AiResult<ChatResponse> {
-        use crate::ai::AiError;
+    fn build(endpoint: &str, api_key: String, default_model: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            api_key,
+            default_model,
+            models: Vec::new(),
+        }
+    }
 
-        // Convert our ChatRequest to OpenAI ChatCompletionRequest
+    /// Convert our `ChatRequest` into an OpenAI-compatible chat completion body
+    fn build_completion_request(&self, request: ChatRequest) -> AiResult<serde_json::Value> {
         let model = request.model.unwrap_or_else(|| self.default_model.clone());
 
         let messages: Vec<ChatCompletionMessage> = request
             .messages
             .into_iter()
-            .map(|msg| ChatCompletionMessage {
-                role: match msg.role {
-                    ChatRole::System => MessageRole::system,
-                    ChatRole::User => MessageRole::user,
-                    ChatRole::Assistant => MessageRole::assistant,
-                    ChatRole::Function => MessageRole::function,
-                },
-                content: Content::Text(msg.content),
-                name: None,
-                tool_calls: None,
-                tool_call_id: None,
-            })
+            .map(Self::to_completion_message)
             .collect();
 
         let mut req = ChatCompletionRequest::new(model, messages);
@@ -186,53 +180,305 @@ impl AiProvider for OpenRouterProvider {
             }
         }
 
-        // Add function definitions if provided
-        if let Some(_functions) = request.functions {
-            // TODO: Add function calling support when the OpenAI client supports it
+        let mut body = serde_json::to_value(&req)?;
+
+        // Add function definitions as tools if provided
+        if let Some(functions) = request.functions {
+            let tools = functions
+                .iter()
+                .map(Self::to_tool)
+                .collect::<AiResult<Vec<_>>>()?;
+
+            body["tool_choice"] = match request.function_call.as_deref() {
+                None | Some("auto") => json!("auto"),
+                Some("none") => json!("none"),
+                Some("required") => json!("required"),
+                Some(name) => {
+                    if !tools.iter().any(|tool| tool["function"]["name"] == name) {
+                        return Err(AiError::InvalidRequest(format!(
+                            "function_call '{name}' does not match any provided function"
+                        )));
+                    }
+                    json!({ "type": "function", "function": { "name": name } })
+                }
+            };
+            // ChatResponse carries a single function call, so ask for one call per turn
+            body["parallel_tool_calls"] = json!(false);
+            body["tools"] = serde_json::Value::Array(tools);
+        }
+
+        Ok(body)
+    }
+
+    /// Convert a chat message, including any attached function call, to the wire format
+    fn to_completion_message(msg: ChatMessage) -> ChatCompletionMessage {
+        let mut message = ChatCompletionMessage {
+            role: match msg.role {
+                ChatRole::System => MessageRole::system,
+                ChatRole::User => MessageRole::user,
+                ChatRole::Assistant => MessageRole::assistant,
+                ChatRole::Function => MessageRole::function,
+            },
+            content: Content::Text(msg.content),
+            name: None,
+            tool_calls: None,
+            tool_call_id: None,
+        };
+
+        match (msg.role, msg.function_call) {
+            (ChatRole::Assistant, Some(call)) => {
+                message.tool_calls = Some(vec![ToolCall {
+                    id: call.id.unwrap_or_default(),
+                    r#type: "function".to_string(),
+                    function: ToolCallFunction {
+                        name: Some(call.name),
+                        arguments: Some(call.arguments),
+                    },
+                }]);
+            }
+            (ChatRole::Function, Some(call)) => {
+                // Results of tool calls are sent as `tool` messages paired by call ID;
+                // without an ID fall back to the legacy `function` role
+                if call.id.is_some() {
+                    message.role = MessageRole::tool;
+                }
+                message.name = Some(call.name);
+                message.tool_call_id = call.id;
+            }
+            _ => {}
+        }
+
+        message
+    }
+
+    /// Convert a function definition (as JSON) to an OpenAI-compatible tool
+    ///
+    /// The parameter schema is passed through as is, so nested object
+    /// properties and integer types reach the model exactly as defined.
+    fn to_tool(function: &serde_json::Value) -> AiResult<serde_json::Value> {
+        let name = function
+            .get("name")
+            .and_then(serde_json::Value::as_str)
+            .ok_or_else(|| {
+                AiError::InvalidRequest("Function definition has no name".to_string())
+            })?;
+
+        Ok(json!({
+            "type": "function",
+            "function": {
+                "name": name,
+                "description": function.get("description").cloned().unwrap_or_else(|| json!("")),
+                "parameters": function
+                    .get("parameters")
+                    .cloned()
+                    .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+            },
+        }))
+    }
+
+    /// Convert an OpenAI-compatible completion into our `ChatResponse`
+    fn to_chat_response(response: ChatCompletionResponse) -> ChatResponse {
+        // Convert OpenAI response to our ChatResponse
+        let choices: Vec<crate::ai::models::ChatChoice> = response
+            .choices
+            .into_iter()
+            .map(|choice| crate::ai::models::ChatChoice {
+                index: u32::try_from(choice.index).unwrap_or(0),
+                message: ChatMessage {
+                    role: match choice.message.role {
+                        MessageRole::system => ChatRole::System,
+                        MessageRole::user => ChatRole::User,
+                        MessageRole::assistant => ChatRole::Assistant,
+                        MessageRole::function | MessageRole::tool => ChatRole::Function,
+                    },
+                    content: choice.message.content.unwrap_or_default(),
+                    function_call: Self::from_tool_calls(choice.message.tool_calls.as_ref()),
+                },
+                finish_reason: choice.finish_reason.map(|fr| format!("{fr:?}")),
+            })
+            .collect();
+
+        let usage = Some(crate::ai::models::TokenUsage {
+            prompt: u32::try_from(response.usage.prompt_tokens).unwrap_or(0),
+            completion: u32::try_from(response.usage.completion_tokens).unwrap_or(0),
+            total: u32::try_from(response.usage.total_tokens).unwrap_or(0),
+        });
+
+        ChatResponse {
+            id: response.id.unwrap_or_default(),
+            model: response.model,
+            usage,
+            created: response.created,
+            function_call: choices
+                .first()
+                .and_then(|choice| choice.message.function_call.clone()),
+            choices,
+        }
+    }
+
+    /// Send a completion request, classifying any failure
+    async fn post_completion(
+        &self,
+        body: &serde_json::Value,
+        stream: bool,
+    ) -> AiResult<reqwest::Response> {
+        let mut request = self
+            .http
+            .post(format!("{}/chat/completions", self.endpoint))
+            .bearer_auth(&self.api_key)
+            .json(body);
+        if stream {
+            request = request.header(reqwest::header::ACCEPT, "text/event-stream");
+        }
+
+        let response = request.send().await.map_err(AiError::from_transport)?;
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(error_from_response(response).await)
+        }
+    }
+
+    /// Convert an OpenAI-compatible `/embeddings` response body, restoring input order
+    fn to_embedding_response(
+        body: serde_json::Value,
+        inputs: usize,
+    ) -> AiResult<EmbeddingResponse> {
+        if let Some(error) = body.get("error") {
+            return Err(error_from_body(error));
+        }
+
+        let mut response: EmbeddingsBody = serde_json::from_value(body)?;
+        if response.data.len() != inputs {
+            return Err(AiError::Provider(format!(
+                "Expected {inputs} embeddings, got {}",
+                response.data.len()
+            )));
+        }
+        response.data.sort_by_key(|item| item.index);
+
+        Ok(EmbeddingResponse {
+            model: response.model,
+            embeddings: response
+                .data
+                .into_iter()
+                .map(|item| item.embedding)
+                .collect(),
+            usage: response
+                .usage
+                .map(|usage| crate::ai::models::TokenUsage::new(usage.prompt_tokens, 0)),
+        })
+    }
+
+    /// Extract a function call from a tool call in a response
+    fn from_tool_calls(tool_calls: Option<&Vec<ToolCall>>) -> Option<FunctionCall> {
+        let tool_calls = tool_calls?;
+        for dropped in tool_calls.iter().skip(1) {
             tracing::warn!(
-                "function calling requested but not yet supported by current OpenAI client"
+                "Dropping tool call {} ({}); only the first tool call in a response is used",
+                dropped.function.name.as_deref().unwrap_or("unnamed"),
+                dropped.id
             );
         }
 
-        // Make the API call
-        match self.client.lock().await.chat_completion(req).await {
-            Ok(response) => {
-                // Convert OpenAI response to our ChatResponse
-                let choices = response
-                    .choices
-                    .into_iter()
-                    .map(|choice| crate::ai::models::ChatChoice {
-                        index: u32::try_from(choice.index).unwrap_or(0),
-                        message: ChatMessage {
-                            role: match choice.message.role {
-                                MessageRole::system => ChatRole::System,
-                                MessageRole::user => ChatRole::User,
-                                MessageRole::assistant => ChatRole::Assistant,
-                                MessageRole::function | MessageRole::tool => ChatRole::Function,
-                            },
-                            content: choice.message.content.unwrap_or_default(),
-                        },
-                        finish_reason: choice.finish_reason.map(|fr| format!("{fr:?}")),
-                    })
-                    .collect();
-
-                let usage = Some(crate::ai::models::TokenUsage {
-                    prompt: u32::try_from(response.usage.prompt_tokens).unwrap_or(0),
-                    completion: u32::try_from(response.usage.completion_tokens).unwrap_or(0),
-                    total: u32::try_from(response.usage.total_tokens).unwrap_or(0),
-                });
-
-                Ok(ChatResponse {
-                    id: response.id.unwrap_or_default(),
-                    model: response.model,
-                    choices,
-                    usage,
-                    created: response.created,
-                    function_call: None, // TODO: Extract function calls from response
-                })
+        let call = tool_calls.first()?;
+        Some(FunctionCall {
+            id: Some(call.id.clone()).filter(|id| !id.is_empty()),
+            name: call.function.name.clone()?,
+            arguments: call
+                .function
+                .arguments
+                .clone()
+                .unwrap_or_else(|| "{}".to_string()),
+        })
+    }
+}
+
+#[async_trait]
+impl AiProvider for OpenRouterProvider {
+    fn name(&self) -> &'static str {
+        "openrouter"
+    }
+
+    fn model(&self) -> &str {
+        &self.default_model
+    }
+
+    fn models(&self) -> Vec<String> {
+        let mut models = vec![self.default_model.clone()];
+        for model in &self.models {
+            if !models.contains(model) {
+                models.push(model.clone());
             }
-            Err(e) => Err(AiError::Provider(format!("Chat request failed: {e}"))),
         }
+        models
+    }
+
+    async fn chat(&self, request: ChatRequest) -> AiResult<ChatResponse> {
+        let body = self.build_completion_request(request)?;
+
+        // Talk to the endpoint directly so failures keep their status and Retry-After
+        let response = self.post_completion(&body, false).await?;
+        let body: serde_json::Value = response.json().await.map_err(AiError::from_transport)?;
+        if let Some(error) = body.get("error") {
+            return Err(error_from_body(error));
+        }
+
+        let response: ChatCompletionResponse = serde_json::from_value(body)?;
+        Ok(Self::to_chat_response(response))
+    }
+
+    async fn chat_stream(&self, request: ChatRequest) -> AiResult<ChatStream> {
+        let mut body = self.build_completion_request(request)?;
+        let model = body["model"].as_str().unwrap_or_default().to_string();
+
+        body["stream"] = serde_json::json!(true);
+        body["stream_options"] = serde_json::json!({ "include_usage": true });
+
+        let response = self.post_completion(&body, true).await?;
+
+        let events = stream::unfold(
+            (response, CompletionStreamParser::new(model), false),
+            |(mut response, mut parser, finished)| async move {
+                if finished {
+                    return None;
+                }
+                match response.chunk().await {
+                    Ok(Some(bytes)) => {
+                        let events = parser.feed(&bytes);
+                        let finished = parser.is_done();
+                        Some((events, (response, parser, finished)))
+                    }
+                    Ok(None) => Some((parser.finish(), (response, parser, true))),
+                    Err(e) => Some((vec![Err(AiError::Network(e))], (response, parser, true))),
+                }
+            },
+        )
+        .flat_map(stream::iter);
+
+        Ok(Box::pin(events))
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> AiResult<EmbeddingResponse> {
+        let model = request
+            .model
+            .ok_or_else(|| AiError::InvalidRequest("An embedding model is required".to_string()))?;
+        let inputs = request.input.len();
+
+        let response = self
+            .http
+            .post(format!("{}/embeddings", self.endpoint))
+            .bearer_auth(&self.api_key)
+            .json(&serde_json::json!({ "model": model, "input": request.input }))
+            .send()
+            .await
+            .map_err(AiError::from_transport)?;
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let body = response.json().await.map_err(AiError::from_transport)?;
+        Self::to_embedding_response(body, inputs)
     }
 
     async fn health_check(&self) -> AiResult<()> {
@@ -240,8 +486,427 @@ impl AiProvider for OpenRouterProvider {
         let messages = vec![ChatMessage {
             role: ChatRole::User,
             content: "Hi".to_string(),
+            function_call: None,
         }];
         let request = crate::ai::ChatRequest::new(messages);
         self.chat(request).await.map(|_| ())
     }
 }
+
+/// Body of a successful `/embeddings` response
+#[derive(Debug, Deserialize)]
+struct EmbeddingsBody {
+    model: String,
+    data: Vec<EmbeddingItem>,
+    usage: Option<EmbeddingUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingItem {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingUsage {
+    prompt_tokens: u32,
+}
+
+/// A single `chat.completion.chunk` object from the streaming API
+#[derive(Debug, Deserialize)]
+struct CompletionChunk {
+    id: Option<String>,
+    model: Option<String>,
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    usage: Option<ChunkUsage>,
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    index: u32,
+    delta: Option<ChunkDelta>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkDelta {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkUsage {
+    #[serde(rename = "prompt_tokens")]
+    prompt: u32,
+    #[serde(rename = "completion_tokens")]
+    completion: u32,
+    #[serde(rename = "total_tokens")]
+    total: u32,
+}
+
+/// Turns the raw SSE byte stream of an OpenAI-compatible completion into `StreamEvent`s
+///
+/// Kept free of any I/O so the wire handling can be tested without a network.
+struct CompletionStreamParser {
+    decoder: SseDecoder,
+    model: String,
+    started: bool,
+    done: bool,
+    finish_reason: Option<String>,
+    usage: Option<TokenUsage>,
+}
+
+impl CompletionStreamParser {
+    fn new(model: String) -> Self {
+        Self {
+            decoder: SseDecoder::new(),
+            model,
+            started: false,
+            done: false,
+            finish_reason: None,
+            usage: None,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Feed raw bytes from the response body
+    fn feed(&mut self, bytes: &[u8]) -> Vec<AiResult<StreamEvent>> {
+        let mut events = Vec::new();
+        for payload in self.decoder.push(bytes) {
+            self.handle_payload(&payload, &mut events);
+        }
+        events
+    }
+
+    /// Flush the parser once the response body has ended
+    ///
+    /// Some servers close the connection without sending `[DONE]`, in which case
+    /// the stream is completed with whatever finish reason and usage were seen.
+    fn finish(&mut self) -> Vec<AiResult<StreamEvent>> {
+        let mut events = Vec::new();
+        if let Some(payload) = self.decoder.finish() {
+            self.handle_payload(&payload, &mut events);
+        }
+        if !self.done {
+            self.complete(&mut events);
+        }
+        events
+    }
+
+    fn handle_payload(&mut self, payload: &str, events: &mut Vec<AiResult<StreamEvent>>) {
+        if self.done {
+            return;
+        }
+
+        if payload.trim() == "[DONE]" {
+            self.complete(events);
+            return;
+        }
+
+        let chunk: CompletionChunk = match serde_json::from_str(payload) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                tracing::warn!("Skipping unparseable stream chunk: {e}");
+                return;
+            }
+        };
+
+        if let Some(error) = chunk.error {
+            events.push(Err(error_from_body(&error)));
+            self.done = true;
+            return;
+        }
+
+        if !self.started {
+            self.started = true;
+            events.push(Ok(StreamEvent::Start {
+                id: chunk.id.unwrap_or_default(),
+                model: chunk.model.unwrap_or_else(|| self.model.clone()),
+            }));
+        }
+
+        for choice in chunk.choices {
+            if let Some(content) = choice.delta.and_then(|d| d.content)
+                && !content.is_empty()
+            {
+                events.push(Ok(StreamEvent::Delta {
+                    content,
+                    index: choice.index,
+                }));
+            }
+            if choice.finish_reason.is_some() {
+                self.finish_reason = choice.finish_reason;
+            }
+        }
+
+        if let Some(usage) = chunk.usage {
+            self.usage = Some(TokenUsage {
+                prompt: usage.prompt,
+                completion: usage.completion,
+                total: usage.total,
+            });
+        }
+    }
+
+    fn complete(&mut self, events: &mut Vec<AiResult<StreamEvent>>) {
+        if !self.started {
+            self.started = true;
+            events.push(Ok(StreamEvent::Start {
+                id: String::new(),
+                model: self.model.clone(),
+            }));
+        }
+        events.push(Ok(StreamEvent::Done {
+            finish_reason: self
+                .finish_reason
+                .take()
+                .unwrap_or_else(|| "stop".to_string()),
+            usage: self.usage.take(),
+        }));
+        self.done = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(content: &str) -> String {
+        format!(
+            "data: {{\"id\":\"gen-1\",\"model\":\"test/model\",\"choices\":[{{\"index\":0,\"delta\":{{\"content\":{content:?}}},\"finish_reason\":null}}]}}\n\n"
+        )
+    }
+
+    fn collect(events: Vec<AiResult<StreamEvent>>) -> Vec<StreamEvent> {
+        events
+            .into_iter()
+            .map(|e| e.expect("event should not be an error"))
+            .collect()
+    }
+
+    #[test]
+    fn test_parser_emits_start_deltas_and_done_with_usage() {
+        let mut parser = CompletionStreamParser::new("fallback".to_string());
+        let mut events = collect(parser.feed(chunk("Hel").as_bytes()));
+        events.extend(collect(parser.feed(chunk("lo").as_bytes())));
+        events.extend(collect(parser.feed(
+            b"data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        )));
+        events.extend(collect(parser.feed(
+            b"data: {\"choices\":[],\"usage\":{\"prompt_tokens\":7,\"completion_tokens\":2,\"total_tokens\":9}}\n\ndata: [DONE]\n\n",
+        )));
+
+        assert!(parser.is_done());
+        assert_eq!(events.len(), 4);
+        assert!(
+            matches!(&events[0], StreamEvent::Start { id, model } if id == "gen-1" && model == "test/model")
+        );
+        assert!(matches!(&events[1], StreamEvent::Delta { content, index: 0 } if content == "Hel"));
+        assert!(matches!(&events[2], StreamEvent::Delta { content, .. } if content == "lo"));
+        match &events[3] {
+            StreamEvent::Done {
+                finish_reason,
+                usage,
+            } => {
+                assert_eq!(finish_reason, "stop");
+                let usage = usage.as_ref().expect("usage should be reported");
+                assert_eq!((usage.prompt, usage.completion, usage.total), (7, 2, 9));
+            }
+            other => panic!("Expected Done event, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parser_completes_when_body_ends_without_done_marker() {
+        let mut parser = CompletionStreamParser::new("fallback".to_string());
+        let mut events = collect(parser.feed(chunk("Hi").as_bytes()));
+        events.extend(collect(parser.finish()));
+
+        assert_eq!(events.len(), 3);
+        assert!(
+            matches!(&events[2], StreamEvent::Done { finish_reason, usage: None } if finish_reason == "stop")
+        );
+    }
+
+    #[test]
+    fn test_parser_reports_upstream_error() {
+        let mut parser = CompletionStreamParser::new("fallback".to_string());
+        let events = parser.feed(b"data: {\"error\":{\"message\":\"overloaded\"}}\n\n");
+
+        assert!(parser.is_done());
+        assert_eq!(events.len(), 1);
+        let error = events
+            .into_iter()
+            .next()
+            .expect("one event")
+            .expect_err("should be an error");
+        assert!(error.to_string().contains("overloaded"));
+    }
+
+    #[test]
+    fn test_parser_ignores_events_after_done() {
+        let mut parser = CompletionStreamParser::new("fallback".to_string());
+        let events = collect(parser.feed(b"data: [DONE]\n\n"));
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], StreamEvent::Start { model, .. } if model == "fallback"));
+        assert!(parser.feed(chunk("late").as_bytes()).is_empty());
+        assert!(parser.finish().is_empty());
+    }
+
+    #[test]
+    fn test_embedding_response_is_returned_in_input_order() {
+        let body = serde_json::json!({
+            "model": "text-embedding-3-small",
+            "data": [
+                { "object": "embedding", "index": 1, "embedding": [0.0, 1.0] },
+                { "object": "embedding", "index": 0, "embedding": [1.0, 0.0] }
+            ],
+            "usage": { "prompt_tokens": 6, "total_tokens": 6 }
+        });
+
+        let response =
+            OpenRouterProvider::to_embedding_response(body, 2).expect("embeddings should parse");
+        assert_eq!(response.model, "text-embedding-3-small");
+        assert_eq!(response.embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert_eq!(response.usage.map(|usage| usage.total), Some(6));
+
+        let short = serde_json::json!({ "model": "m", "data": [] });
+        assert!(OpenRouterProvider::to_embedding_response(short, 1).is_err());
+        let failed = serde_json::json!({ "error": { "code": 429, "message": "Slow down" } });
+        assert!(matches!(
+            OpenRouterProvider::to_embedding_response(failed, 1),
+            Err(AiError::RateLimitExceeded { .. })
+        ));
+    }
+
+    fn test_provider() -> OpenRouterProvider {
+        OpenRouterProvider::with_config("test-key".to_string(), "test/model".to_string())
+    }
+
+    #[test]
+    fn test_functions_are_sent_as_tools() {
+        let request =
+            ChatRequest::new(vec![]).with_functions(&crate::ai::get_business_analyst_functions());
+        let body = test_provider()
+            .build_completion_request(request)
+            .expect("request should build");
+
+        let tools = body["tools"].as_array().expect("tools should be set");
+        assert_eq!(tools.len(), 4);
+        let save_context = tools
+            .iter()
+            .find(|tool| tool["function"]["name"] == "save_context")
+            .expect("save_context tool");
+        assert_eq!(save_context["type"], "function");
+        assert_eq!(
+            save_context["function"]["parameters"]["properties"]["context_type"]["enum"]
+                .as_array()
+                .map(Vec::len),
+            Some(4)
+        );
+        assert_eq!(body["tool_choice"], "auto");
+        assert_eq!(body["parallel_tool_calls"], false);
+    }
+
+    #[test]
+    fn test_tool_parameters_keep_nested_objects_and_integers() {
+        let function = json!({
+            "name": "estimate",
+            "description": "Estimate an issue",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "points": { "type": "integer", "description": "Story points" },
+                    "range": {
+                        "type": "object",
+                        "description": "Estimate range",
+                        "properties": {
+                            "low": { "type": "integer", "description": "Lowest estimate" }
+                        },
+                        "required": ["low"]
+                    }
+                },
+                "required": ["points"]
+            }
+        });
+        let mut request = ChatRequest::new(vec![]);
+        request.functions = Some(vec![function.clone()]);
+        let body = test_provider()
+            .build_completion_request(request)
+            .expect("request should build");
+
+        assert_eq!(
+            body["tools"][0]["function"]["parameters"],
+            function["parameters"]
+        );
+    }
+
+    #[test]
+    fn test_named_function_call_forces_tool_choice() {
+        let mut request =
+            ChatRequest::new(vec![]).with_functions(&crate::ai::get_business_analyst_functions());
+        request.function_call = Some("create_issue".to_string());
+        let body = test_provider()
+            .build_completion_request(request.clone())
+            .expect("request should build");
+        assert_eq!(
+            body["tool_choice"],
+            json!({ "type": "function", "function": { "name": "create_issue" } })
+        );
+
+        request.function_call = Some("missing".to_string());
+        assert!(matches!(
+            test_provider().build_completion_request(request),
+            Err(AiError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_function_turns_map_to_tool_messages() {
+        let call = FunctionCall {
+            id: Some("call_1".to_string()),
+            name: "create_issue".to_string(),
+            arguments: "{\"confirm\":false}".to_string(),
+        };
+
+        let assistant = OpenRouterProvider::to_completion_message(ChatMessage {
+            role: ChatRole::Assistant,
+            content: String::new(),
+            function_call: Some(call.clone()),
+        });
+        let tool_calls = assistant.tool_calls.expect("tool calls");
+        assert_eq!(tool_calls[0].id, "call_1");
+        assert_eq!(tool_calls[0].function.name.as_deref(), Some("create_issue"));
+
+        let result = OpenRouterProvider::to_completion_message(ChatMessage {
+            role: ChatRole::Function,
+            content: "{\"success\":true}".to_string(),
+            function_call: Some(call),
+        });
+        assert_eq!(result.role, MessageRole::tool);
+        assert_eq!(result.tool_call_id.as_deref(), Some("call_1"));
+    }
+
+    #[test]
+    fn test_tool_calls_are_extracted_from_response() {
+        let tool_calls = vec![ToolCall {
+            id: "call_9".to_string(),
+            r#type: "function".to_string(),
+            function: ToolCallFunction {
+                name: Some("save_context".to_string()),
+                arguments: None,
+            },
+        }];
+
+        let call = OpenRouterProvider::from_tool_calls(Some(&tool_calls)).expect("call");
+        assert_eq!(call.id.as_deref(), Some("call_9"));
+        assert_eq!(call.name, "save_context");
+        assert_eq!(call.arguments, "{}");
+        assert!(OpenRouterProvider::from_tool_calls(None).is_none());
+    }
+}
@@ -0,0 +1,465 @@
+//! Registry of configured AI providers and the rules that route requests to them
+//!
+//! A request is routed by its model: a model listed by a provider goes to that
+//! provider, otherwise the longest matching model prefix rule decides, and anything
+//! else goes to the default provider. Personas can be pinned to a model, which is
+//! then routed the same way.
+//!
+//! The registry is itself an `AiProvider` that forwards each request to the
+//! provider its model routes to.
+
+use super::anthropic::AnthropicProvider;
+use super::mock::MockProvider;
+use super::openai_compatible::OpenAiCompatibleProvider;
+use super::openrouter::OpenRouterProvider;
+use super::traits::{AiProvider, ChatStream};
+use crate::ai::{
+    AiError, AiResult, ChatRequest, ChatResponse, EmbeddingRequest, EmbeddingResponse,
+};
+use crate::models::AiPersona;
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Sends models starting with `prefix` to the named provider
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelRoute {
+    pub prefix: String,
+    pub provider: String,
+}
+
+/// A configured provider as reported to clients
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderInfo {
+    pub name: String,
+    pub default_model: String,
+    pub models: Vec<String>,
+    /// Whether requests without a routed model use this provider
+    pub default: bool,
+}
+
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn AiProvider>>,
+    default_provider: usize,
+    model_routes: Vec<ModelRoute>,
+    persona_models: HashMap<AiPersona, String>,
+}
+
+impl ProviderRegistry {
+    /// Create a registry with a single provider, which becomes the default
+    #[must_use]
+    pub fn new(default_provider: Arc<dyn AiProvider>) -> Self {
+        Self {
+            providers: vec![default_provider],
+            default_provider: 0,
+            model_routes: Vec::new(),
+            persona_models: HashMap::new(),
+        }
+    }
+
+    /// Create the registry from environment configuration
+    ///
+    /// Each provider is enabled by its own variables (`OPENROUTER_API_KEY`,
+    /// `OPENAI_COMPAT_ENDPOINT`, `ANTHROPIC_API_KEY`). When the mock provider is
+    /// enabled (`AI_MOCK_PROVIDER`, `AI_MOCK_FIXTURE`) it replaces all other
+    /// providers, so nothing reaches the network. Routing is configured with:
+    /// - `AI_DEFAULT_PROVIDER` - Optional default provider name (defaults to the first enabled)
+    /// - `AI_MODEL_ROUTES`     - Optional `prefix=provider` pairs, comma-separated
+    /// - `AI_PERSONA_MODELS`   - Optional `persona=model` pairs, comma-separated
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no provider is configured, a provider fails to
+    /// initialize, or a routing rule is invalid
+    pub fn from_env() -> AiResult<Self> {
+        if let Some(mock) = MockProvider::from_env()? {
+            return Ok(Self::new(Arc::new(mock)));
+        }
+
+        let mut providers: Vec<Arc<dyn AiProvider>> = Vec::new();
+        if std::env::var("OPENROUTER_API_KEY").is_ok() {
+            providers.push(Arc::new(OpenRouterProvider::new()?));
+        }
+        if let Some(provider) = OpenAiCompatibleProvider::from_env()? {
+            providers.push(Arc::new(provider));
+        }
+        if let Some(provider) = AnthropicProvider::from_env() {
+            providers.push(Arc::new(provider));
+        }
+
+        let mut providers = providers.into_iter();
+        let Some(first) = providers.next() else {
+            return Err(AiError::Configuration(
+                "No AI provider configured; set OPENROUTER_API_KEY, OPENAI_COMPAT_ENDPOINT, ANTHROPIC_API_KEY or AI_MOCK_PROVIDER"
+                    .to_string(),
+            ));
+        };
+
+        let mut registry = Self::new(first);
+        for provider in providers {
+            registry.register(provider)?;
+        }
+
+        if let Ok(name) = std::env::var("AI_DEFAULT_PROVIDER") {
+            registry.set_default(name.trim())?;
+        }
+
+        if let Ok(routes) = std::env::var("AI_MODEL_ROUTES") {
+            for (prefix, provider) in parse_pairs("AI_MODEL_ROUTES", &routes)? {
+                registry.add_model_route(prefix, &provider)?;
+            }
+        }
+
+        if let Ok(persona_models) = std::env::var("AI_PERSONA_MODELS") {
+            for (persona, model) in parse_pairs("AI_PERSONA_MODELS", &persona_models)? {
+                let persona: AiPersona = serde_json::from_value(serde_json::json!(persona))
+                    .map_err(|_| {
+                        AiError::Configuration(format!(
+                            "AI_PERSONA_MODELS names unknown persona '{persona}'"
+                        ))
+                    })?;
+                registry.set_persona_model(persona, model);
+            }
+        }
+
+        tracing::info!(
+            "AI providers: {} (default: {})",
+            registry
+                .providers
+                .iter()
+                .map(|provider| provider.name().to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            registry.default_provider().name()
+        );
+
+        Ok(registry)
+    }
+
+    /// Add a provider
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a provider with the same name is already registered
+    pub fn register(&mut self, provider: Arc<dyn AiProvider>) -> AiResult<()> {
+        if self.position(provider.name()).is_some() {
+            return Err(AiError::Configuration(format!(
+                "AI provider '{}' is registered twice",
+                provider.name()
+            )));
+        }
+        self.providers.push(provider);
+        Ok(())
+    }
+
+    /// Make the named provider the default
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no provider has that name
+    pub fn set_default(&mut self, name: &str) -> AiResult<()> {
+        self.default_provider = self.require(name)?;
+        Ok(())
+    }
+
+    /// Route models starting with `prefix` to the named provider
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no provider has that name
+    pub fn add_model_route(&mut self, prefix: String, provider: &str) -> AiResult<()> {
+        self.require(provider)?;
+        self.model_routes.push(ModelRoute {
+            prefix,
+            provider: provider.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Use `model` for conversations with `persona`
+    pub fn set_persona_model(&mut self, persona: AiPersona, model: String) {
+        self.persona_models.insert(persona, model);
+    }
+
+    /// Provider used when a request's model is not routed elsewhere
+    #[must_use]
+    pub fn default_provider(&self) -> Arc<dyn AiProvider> {
+        Arc::clone(&self.providers[self.default_provider])
+    }
+
+    /// Look up a provider by name
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<Arc<dyn AiProvider>> {
+        self.position(name)
+            .map(|index| Arc::clone(&self.providers[index]))
+    }
+
+    /// Pick the provider for a request's model
+    #[must_use]
+    pub fn resolve(&self, model: Option<&str>) -> Arc<dyn AiProvider> {
+        let Some(model) = model else {
+            return self.default_provider();
+        };
+
+        if let Some(provider) = self
+            .providers
+            .iter()
+            .find(|provider| provider.models().iter().any(|served| served == model))
+        {
+            return Arc::clone(provider);
+        }
+
+        self.model_routes
+            .iter()
+            .filter(|route| model.starts_with(&route.prefix))
+            .max_by_key(|route| route.prefix.len())
+            .and_then(|route| self.get(&route.provider))
+            .unwrap_or_else(|| self.default_provider())
+    }
+
+    /// Model pinned to a persona, if any
+    #[must_use]
+    pub fn persona_model(&self, persona: AiPersona) -> Option<&str> {
+        self.persona_models.get(&persona).map(String::as_str)
+    }
+
+    /// Describe the configured providers and their models
+    #[must_use]
+    pub fn providers(&self) -> Vec<ProviderInfo> {
+        self.providers
+            .iter()
+            .enumerate()
+            .map(|(index, provider)| ProviderInfo {
+                name: provider.name().to_string(),
+                default_model: provider.model().to_string(),
+                models: provider.models(),
+                default: index == self.default_provider,
+            })
+            .collect()
+    }
+
+    /// Configured model prefix rules
+    #[must_use]
+    pub fn model_routes(&self) -> &[ModelRoute] {
+        &self.model_routes
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.providers
+            .iter()
+            .position(|provider| provider.name() == name)
+    }
+
+    fn require(&self, name: &str) -> AiResult<usize> {
+        self.position(name).ok_or_else(|| {
+            AiError::Configuration(format!("Unknown AI provider '{name}' in routing rules"))
+        })
+    }
+}
+
+#[async_trait]
+impl AiProvider for ProviderRegistry {
+    /// Name of the default provider
+    fn name(&self) -> &str {
+        self.providers[self.default_provider].name()
+    }
+
+    /// Default model of the default provider
+    fn model(&self) -> &str {
+        self.providers[self.default_provider].model()
+    }
+
+    fn models(&self) -> Vec<String> {
+        let mut models = Vec::new();
+        for model in self.providers.iter().flat_map(|provider| provider.models()) {
+            if !models.contains(&model) {
+                models.push(model);
+            }
+        }
+        models
+    }
+
+    async fn chat(&self, request: ChatRequest) -> AiResult<ChatResponse> {
+        self.resolve(request.model.as_deref()).chat(request).await
+    }
+
+    async fn chat_stream(&self, request: ChatRequest) -> AiResult<ChatStream> {
+        self.resolve(request.model.as_deref())
+            .chat_stream(request)
+            .await
+    }
+
+    /// Embedding models are routed like chat models, so a prefix rule can send
+    /// them to the provider that serves them
+    async fn embed(&self, request: EmbeddingRequest) -> AiResult<EmbeddingResponse> {
+        self.resolve(request.model.as_deref()).embed(request).await
+    }
+
+    async fn health_check(&self) -> AiResult<()> {
+        self.default_provider().health_check().await
+    }
+}
+
+/// Parse a comma-separated list of model names
+pub(crate) fn parse_model_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|model| !model.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse comma-separated `key=value` pairs from an environment variable
+fn parse_pairs(variable: &str, value: &str) -> AiResult<Vec<(String, String)>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            pair.split_once('=')
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+                .ok_or_else(|| {
+                    AiError::Configuration(format!(
+                        "{variable} entries must look like key=value, got '{pair}'"
+                    ))
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{ChatRequest, ChatResponse};
+    use async_trait::async_trait;
+
+    struct NamedProvider {
+        name: &'static str,
+        models: Vec<String>,
+    }
+
+    impl NamedProvider {
+        fn shared(name: &'static str, models: &[&str]) -> Arc<dyn AiProvider> {
+            Arc::new(Self {
+                name,
+                models: models.iter().map(ToString::to_string).collect(),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl AiProvider for NamedProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn model(&self) -> &str {
+            &self.models[0]
+        }
+
+        fn models(&self) -> Vec<String> {
+            self.models.clone()
+        }
+
+        async fn chat(&self, _request: ChatRequest) -> AiResult<ChatResponse> {
+            Err(AiError::Provider("not used".to_string()))
+        }
+
+        async fn health_check(&self) -> AiResult<()> {
+            Ok(())
+        }
+    }
+
+    fn registry() -> ProviderRegistry {
+        let mut registry = ProviderRegistry::new(NamedProvider::shared(
+            "openrouter",
+            &["gpt-4o", "anthropic/claude-sonnet-4"],
+        ));
+        registry
+            .register(NamedProvider::shared("local", &["llama3"]))
+            .expect("register local");
+        registry
+            .register(NamedProvider::shared("anthropic", &["claude-sonnet-4-0"]))
+            .expect("register anthropic");
+        registry
+    }
+
+    #[test]
+    fn test_resolve_by_listed_model() {
+        let registry = registry();
+        assert_eq!(registry.resolve(None).name(), "openrouter");
+        assert_eq!(registry.resolve(Some("llama3")).name(), "local");
+        assert_eq!(
+            registry.resolve(Some("anthropic/claude-sonnet-4")).name(),
+            "openrouter"
+        );
+        assert_eq!(registry.resolve(Some("unknown")).name(), "openrouter");
+    }
+
+    #[test]
+    fn test_resolve_by_longest_prefix() {
+        let mut registry = registry();
+        registry
+            .add_model_route("claude-".to_string(), "anthropic")
+            .expect("route");
+        registry
+            .add_model_route("claude-local-".to_string(), "local")
+            .expect("route");
+
+        assert_eq!(
+            registry.resolve(Some("claude-opus-4-0")).name(),
+            "anthropic"
+        );
+        assert_eq!(registry.resolve(Some("claude-local-7b")).name(), "local");
+        assert!(matches!(
+            registry.add_model_route("x-".to_string(), "missing"),
+            Err(AiError::Configuration(_))
+        ));
+    }
+
+    #[test]
+    fn test_default_and_duplicates() {
+        let mut registry = registry();
+        registry.set_default("local").expect("set default");
+        assert_eq!(registry.resolve(Some("unknown")).name(), "local");
+        assert!(registry.set_default("missing").is_err());
+        assert!(
+            registry
+                .register(NamedProvider::shared("local", &["other"]))
+                .is_err()
+        );
+
+        let providers = registry.providers();
+        assert_eq!(providers.len(), 3);
+        assert!(providers.iter().any(|p| p.name == "local" && p.default));
+        assert_eq!(providers[0].models.len(), 2);
+    }
+
+    #[test]
+    fn test_persona_model() {
+        let mut registry = registry();
+        registry.set_persona_model(AiPersona::CodeReviewer, "claude-sonnet-4-0".to_string());
+        assert_eq!(
+            registry.persona_model(AiPersona::CodeReviewer),
+            Some("claude-sonnet-4-0")
+        );
+        assert_eq!(registry.persona_model(AiPersona::BusinessAnalyst), None);
+    }
+
+    #[test]
+    fn test_parse_pairs_and_lists() {
+        assert_eq!(
+            parse_pairs("X", " claude-=anthropic , llama=local,").expect("pairs"),
+            vec![
+                ("claude-".to_string(), "anthropic".to_string()),
+                ("llama".to_string(), "local".to_string())
+            ]
+        );
+        assert!(parse_pairs("X", "claude-").is_err());
+        assert!(parse_pairs("X", "=anthropic").is_err());
+        assert_eq!(parse_model_list("a, b,,c "), vec!["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,488 @@
+//! Retry and fallback policy for AI providers
+//!
+//! `RetryingProvider` wraps another provider and retries failures that may succeed
+//! when sent again (rate limits, timeouts, 5xx and network errors) with exponential
+//! backoff and jitter. Every attempt is bounded by a per-request timeout. Once a
+//! model has used up its retries, the request moves on to the next model in the
+//! fallback list. Wrapping the `ProviderRegistry` lets fallback models live on a
+//! different provider.
+
+use super::traits::{AiProvider, ChatStream, UsageStats};
+use crate::ai::{
+    AiError, AiResult, ChatRequest, ChatResponse, EmbeddingRequest, EmbeddingResponse,
+};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Retries per model when `AI_MAX_RETRIES` is not set
+const DEFAULT_MAX_RETRIES: u32 = 2;
+/// First backoff delay when `AI_RETRY_BASE_DELAY_MS` is not set
+const DEFAULT_BASE_DELAY_MS: u64 = 500;
+/// Longest backoff delay when `AI_RETRY_MAX_DELAY_MS` is not set
+const DEFAULT_MAX_DELAY_MS: u64 = 8_000;
+/// Per-attempt timeout when `AI_REQUEST_TIMEOUT_SECS` is not set
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 120;
+
+/// How failed requests are retried and which models to fall back to
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries per model after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled for each further retry
+    pub base_delay: Duration,
+    /// Upper bound for a backoff delay, and for waiting on a provider's `Retry-After`
+    pub max_delay: Duration,
+    /// Time limit for each attempt, if any
+    pub request_timeout: Option<Duration>,
+    /// Models tried in order once the requested model has failed
+    pub fallback_models: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: Duration::from_millis(DEFAULT_BASE_DELAY_MS),
+            max_delay: Duration::from_millis(DEFAULT_MAX_DELAY_MS),
+            request_timeout: Some(Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECS)),
+            fallback_models: Vec::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that sends each request once, without timeout or fallback models
+    #[must_use]
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            request_timeout: None,
+            ..Self::default()
+        }
+    }
+
+    /// Create the policy from environment configuration
+    ///
+    /// Reads configuration from:
+    /// - `AI_MAX_RETRIES`          - Retries per model (default: 2)
+    /// - `AI_RETRY_BASE_DELAY_MS`  - First backoff delay (default: 500)
+    /// - `AI_RETRY_MAX_DELAY_MS`   - Longest backoff delay (default: 8000)
+    /// - `AI_REQUEST_TIMEOUT_SECS` - Per-attempt timeout, `0` to disable (default: 120)
+    /// - `AI_FALLBACK_MODELS`      - Comma-separated models to fall back to, in order
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a numeric setting cannot be parsed
+    pub fn from_env() -> AiResult<Self> {
+        let max_retries = env_number("AI_MAX_RETRIES", u64::from(DEFAULT_MAX_RETRIES))?;
+        let base_delay_ms = env_number("AI_RETRY_BASE_DELAY_MS", DEFAULT_BASE_DELAY_MS)?;
+        let max_delay_ms = env_number("AI_RETRY_MAX_DELAY_MS", DEFAULT_MAX_DELAY_MS)?;
+        let timeout_secs = env_number("AI_REQUEST_TIMEOUT_SECS", DEFAULT_REQUEST_TIMEOUT_SECS)?;
+        let fallback_models = std::env::var("AI_FALLBACK_MODELS")
+            .map(|value| super::registry::parse_model_list(&value))
+            .unwrap_or_default();
+
+        Ok(Self {
+            max_retries: u32::try_from(max_retries)
+                .map_err(|_| AiError::Configuration("AI_MAX_RETRIES is too large".to_string()))?,
+            base_delay: Duration::from_millis(base_delay_ms),
+            max_delay: Duration::from_millis(max_delay_ms.max(base_delay_ms)),
+            request_timeout: (timeout_secs > 0).then(|| Duration::from_secs(timeout_secs)),
+            fallback_models,
+        })
+    }
+
+    /// Backoff before retry number `retry` (starting at 0), with equal jitter
+    ///
+    /// The delay is at least half of the exponential backoff, so retries from many
+    /// clients spread out without any of them retrying immediately.
+    fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        let jitter_ms = u64::try_from(half.as_millis()).unwrap_or(u64::MAX);
+        let jitter = if jitter_ms == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(OsRng.next_u64() % (jitter_ms + 1))
+        };
+        half + jitter
+    }
+
+    /// Models to try, in order: the requested model (or the default), then the fallbacks
+    fn candidate_models(&self, requested: Option<&str>) -> Vec<Option<String>> {
+        let mut models = vec![requested.map(str::to_string)];
+        for model in &self.fallback_models {
+            if requested != Some(model.as_str()) && !models.contains(&Some(model.clone())) {
+                models.push(Some(model.clone()));
+            }
+        }
+        models
+    }
+}
+
+/// Read a non-negative integer setting, falling back to a default when unset
+fn env_number(variable: &str, default: u64) -> AiResult<u64> {
+    match std::env::var(variable) {
+        Ok(value) => value.trim().parse().map_err(|_| {
+            AiError::Configuration(format!("{variable} must be a non-negative integer"))
+        }),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Provider wrapper that applies a `RetryPolicy` to every request
+pub struct RetryingProvider {
+    inner: Arc<dyn AiProvider>,
+    policy: RetryPolicy,
+}
+
+impl RetryingProvider {
+    #[must_use]
+    pub fn new(inner: Arc<dyn AiProvider>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Run `call` with retries and fallbacks, returning the first success
+    ///
+    /// Non-retryable errors are returned straight away. If a provider asks us to
+    /// wait longer than the maximum backoff, the current model is abandoned for
+    /// the next fallback instead.
+    async fn run<T, F, Fut>(&self, request: ChatRequest, call: F) -> AiResult<T>
+    where
+        F: Fn(Arc<dyn AiProvider>, ChatRequest) -> Fut,
+        Fut: Future<Output = AiResult<T>>,
+    {
+        let mut last_error = None;
+
+        for (index, model) in self
+            .policy
+            .candidate_models(request.model.as_deref())
+            .into_iter()
+            .enumerate()
+        {
+            let mut request = request.clone();
+            if index > 0 {
+                tracing::warn!(
+                    "Falling back to model {} after repeated failures",
+                    model.as_deref().unwrap_or("(default)")
+                );
+                request.model = model;
+            }
+
+            let result = self
+                .retry(request.model.as_deref(), || {
+                    call(Arc::clone(&self.inner), request.clone())
+                })
+                .await;
+            match result {
+                Ok(value) => return Ok(value),
+                Err(error) if !error.is_retryable() => return Err(error),
+                Err(error) => last_error = Some(error),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| AiError::Unknown("No model was tried".to_string())))
+    }
+
+    /// Run `call` against one model, retrying failures that may succeed when sent again
+    ///
+    /// Returns the last error once the retries are used up, or as soon as the
+    /// provider asks us to wait longer than the maximum backoff.
+    async fn retry<T, F, Fut>(&self, model: Option<&str>, call: F) -> AiResult<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = AiResult<T>>,
+    {
+        let mut last_error = None;
+
+        for attempt in 0..=self.policy.max_retries {
+            let pending = call();
+            let result = match self.policy.request_timeout {
+                Some(limit) => tokio::time::timeout(limit, pending)
+                    .await
+                    .unwrap_or(Err(AiError::Timeout)),
+                None => pending.await,
+            };
+
+            let error = match result {
+                Ok(value) => return Ok(value),
+                Err(error) if !error.is_retryable() => return Err(error),
+                Err(error) => error,
+            };
+
+            tracing::warn!(
+                "AI request to model {} failed (attempt {}): {error}",
+                model.unwrap_or("(default)"),
+                attempt + 1
+            );
+
+            let delay = match error.retry_after() {
+                Some(wait) if wait > self.policy.max_delay => return Err(error),
+                Some(wait) => wait,
+                None => self.policy.backoff(attempt),
+            };
+            last_error = Some(error);
+
+            if attempt < self.policy.max_retries {
+                tokio::time::sleep(delay).await;
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| AiError::Unknown("No attempt was made".to_string())))
+    }
+}
+
+#[async_trait]
+impl AiProvider for RetryingProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn models(&self) -> Vec<String> {
+        self.inner.models()
+    }
+
+    async fn chat(&self, request: ChatRequest) -> AiResult<ChatResponse> {
+        self.run(request, |provider, request| async move {
+            provider.chat(request).await
+        })
+        .await
+    }
+
+    /// Retries apply until the stream has started; failures after that are
+    /// yielded as stream items as usual
+    async fn chat_stream(&self, request: ChatRequest) -> AiResult<ChatStream> {
+        self.run(request, |provider, request| async move {
+            provider.chat_stream(request).await
+        })
+        .await
+    }
+
+    /// Embeddings are retried like chat requests but never fall back to
+    /// another model, since vectors from different models cannot be compared
+    async fn embed(&self, request: EmbeddingRequest) -> AiResult<EmbeddingResponse> {
+        self.retry(request.model.as_deref(), || {
+            self.inner.embed(request.clone())
+        })
+        .await
+    }
+
+    async fn health_check(&self) -> AiResult<()> {
+        self.inner.health_check().await
+    }
+
+    fn get_usage_stats(&self) -> AiResult<UsageStats> {
+        self.inner.get_usage_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::models::ChatChoice;
+    use crate::ai::{ChatMessage, ChatRole};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Provider that replays scripted outcomes and records the model of each request
+    struct FlakyProvider {
+        outcomes: Mutex<VecDeque<AiResult<ChatResponse>>>,
+        models: Mutex<Vec<Option<String>>>,
+        delay: Duration,
+    }
+
+    impl FlakyProvider {
+        fn new(outcomes: Vec<AiResult<ChatResponse>>) -> Arc<Self> {
+            Self::slow(outcomes, Duration::ZERO)
+        }
+
+        fn slow(outcomes: Vec<AiResult<ChatResponse>>, delay: Duration) -> Arc<Self> {
+            Arc::new(Self {
+                outcomes: Mutex::new(outcomes.into()),
+                models: Mutex::new(Vec::new()),
+                delay,
+            })
+        }
+
+        fn requested_models(&self) -> Vec<Option<String>> {
+            self.models.lock().expect("lock").clone()
+        }
+    }
+
+    #[async_trait]
+    impl AiProvider for FlakyProvider {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+
+        fn model(&self) -> &'static str {
+            "primary"
+        }
+
+        async fn chat(&self, request: ChatRequest) -> AiResult<ChatResponse> {
+            self.models.lock().expect("lock").push(request.model);
+            tokio::time::sleep(self.delay).await;
+            self.outcomes
+                .lock()
+                .expect("lock")
+                .pop_front()
+                .unwrap_or_else(|| Err(AiError::Provider("script exhausted".to_string())))
+        }
+
+        async fn health_check(&self) -> AiResult<()> {
+            Ok(())
+        }
+    }
+
+    fn reply(content: &str) -> ChatResponse {
+        ChatResponse {
+            id: "resp".to_string(),
+            model: "test".to_string(),
+            choices: vec![ChatChoice {
+                index: 0,
+                message: ChatMessage {
+                    role: ChatRole::Assistant,
+                    content: content.to_string(),
+                    function_call: None,
+                },
+                finish_reason: Some("stop".to_string()),
+            }],
+            usage: None,
+            created: 0,
+            function_call: None,
+        }
+    }
+
+    fn server_error() -> AiResult<ChatResponse> {
+        Err(AiError::Upstream {
+            status: 502,
+            message: "bad gateway".to_string(),
+        })
+    }
+
+    fn fast_policy(fallback_models: &[&str]) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            request_timeout: Some(Duration::from_millis(200)),
+            fallback_models: fallback_models.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest::new(vec![])
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let inner = FlakyProvider::new(vec![server_error(), server_error(), Ok(reply("ok"))]);
+        let provider = RetryingProvider::new(inner.clone(), fast_policy(&[]));
+
+        let response = provider
+            .chat(request())
+            .await
+            .expect("third attempt succeeds");
+        assert_eq!(response.content(), "ok");
+        assert_eq!(inner.requested_models().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_permanent_errors() {
+        let inner = FlakyProvider::new(vec![
+            Err(AiError::InvalidRequest("bad".to_string())),
+            Ok(reply("never")),
+        ]);
+        let provider = RetryingProvider::new(inner.clone(), fast_policy(&["backup"]));
+
+        assert!(matches!(
+            provider.chat(request()).await,
+            Err(AiError::InvalidRequest(_))
+        ));
+        assert_eq!(inner.requested_models().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_after_retries_are_exhausted() {
+        let inner = FlakyProvider::new(vec![
+            server_error(),
+            server_error(),
+            server_error(),
+            Ok(reply("from backup")),
+        ]);
+        let provider = RetryingProvider::new(inner.clone(), fast_policy(&["backup"]));
+
+        let response = provider.chat(request()).await.expect("fallback succeeds");
+        assert_eq!(response.content(), "from backup");
+        assert_eq!(
+            inner.requested_models(),
+            vec![None, None, None, Some("backup".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_long_retry_after_skips_to_fallback() {
+        let inner = FlakyProvider::new(vec![
+            Err(AiError::RateLimitExceeded {
+                retry_after: Some(Duration::from_mins(1)),
+            }),
+            Ok(reply("from backup")),
+        ]);
+        let provider = RetryingProvider::new(inner.clone(), fast_policy(&["backup"]));
+
+        let response = provider.chat(request()).await.expect("fallback succeeds");
+        assert_eq!(response.content(), "from backup");
+        assert_eq!(inner.requested_models().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_attempts_time_out() {
+        let inner = FlakyProvider::slow(vec![Ok(reply("too late"))], Duration::from_secs(5));
+        let mut policy = fast_policy(&[]);
+        policy.max_retries = 0;
+        policy.request_timeout = Some(Duration::from_millis(10));
+        let provider = RetryingProvider::new(inner, policy);
+
+        assert!(matches!(
+            provider.chat(request()).await,
+            Err(AiError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            ..RetryPolicy::default()
+        };
+
+        for retry in 0..6 {
+            let exponential =
+                Duration::from_millis(100 * 2u64.pow(retry)).min(Duration::from_secs(1));
+            let delay = policy.backoff(retry);
+            assert!(delay >= exponential / 2, "retry {retry}: {delay:?}");
+            assert!(delay <= exponential, "retry {retry}: {delay:?}");
+        }
+    }
+
+    #[test]
+    fn test_candidate_models_skip_duplicates() {
+        let policy = fast_policy(&["a", "b", "a"]);
+        assert_eq!(
+            policy.candidate_models(Some("b")),
+            vec![Some("b".to_string()), Some("a".to_string())]
+        );
+        assert_eq!(policy.candidate_models(None).len(), 3);
+    }
+}
@@ -0,0 +1,124 @@
+//! Incremental decoder for the Server-Sent Events wire format
+//!
+//! OpenAI-compatible providers stream chat completions as SSE, where each event
+//! carries a JSON chunk in its `data:` field and the stream ends with `[DONE]`.
+//! Network reads do not line up with event boundaries, so the decoder buffers
+//! partial input until a complete event has arrived.
+
+/// Buffers raw bytes and yields the `data` payload of each complete event
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: String,
+    pending_bytes: Vec<u8>,
+}
+
+impl SseDecoder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a chunk of bytes and return the data payloads of all events it completed
+    ///
+    /// Comment lines (starting with `:`) and events without data are skipped.
+    /// Multi-line data fields are joined with `\n` as per the SSE specification.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending_bytes.extend_from_slice(bytes);
+
+        // Only decode up to the last complete UTF-8 sequence; keep the rest for the next read
+        let valid_up_to = match std::str::from_utf8(&self.pending_bytes) {
+            Ok(_) => self.pending_bytes.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        let decoded: Vec<u8> = self.pending_bytes.drain(..valid_up_to).collect();
+        self.buffer.push_str(&String::from_utf8_lossy(&decoded));
+
+        // Normalise CRLF line endings; a trailing `\r` stays buffered until its `\n` arrives
+        if self.buffer.contains("\r\n") {
+            self.buffer = self.buffer.replace("\r\n", "\n");
+        }
+
+        let mut payloads = Vec::new();
+        while let Some(boundary) = self.buffer.find("\n\n") {
+            let raw_event: String = self.buffer.drain(..boundary + 2).collect();
+            if let Some(data) = Self::parse_event(&raw_event) {
+                payloads.push(data);
+            }
+        }
+        payloads
+    }
+
+    /// Flush any trailing event that was not terminated by a blank line
+    pub fn finish(&mut self) -> Option<String> {
+        let remaining = std::mem::take(&mut self.buffer);
+        Self::parse_event(&remaining)
+    }
+
+    fn parse_event(raw_event: &str) -> Option<String> {
+        let data_lines: Vec<&str> = raw_event
+            .lines()
+            .filter(|line| !line.starts_with(':'))
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect();
+
+        if data_lines.is_empty() {
+            None
+        } else {
+            Some(data_lines.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decodes_complete_events() {
+        let mut decoder = SseDecoder::new();
+        let payloads = decoder.push(b"data: {\"a\":1}\n\ndata: [DONE]\n\n");
+        assert_eq!(payloads, vec!["{\"a\":1}", "[DONE]"]);
+    }
+
+    #[test]
+    fn test_buffers_partial_events() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(b"data: {\"content\":").is_empty());
+        assert!(decoder.push(b"\"hel").is_empty());
+        let payloads = decoder.push(b"lo\"}\n\n");
+        assert_eq!(payloads, vec!["{\"content\":\"hello\"}"]);
+    }
+
+    #[test]
+    fn test_skips_comments_and_handles_crlf() {
+        let mut decoder = SseDecoder::new();
+        let payloads = decoder.push(b": OPENROUTER PROCESSING\r\n\r\ndata:x\r\n\r\n");
+        assert_eq!(payloads, vec!["x"]);
+    }
+
+    #[test]
+    fn test_joins_multiline_data() {
+        let mut decoder = SseDecoder::new();
+        let payloads = decoder.push(b"event: message\ndata: line one\ndata: line two\n\n");
+        assert_eq!(payloads, vec!["line one\nline two"]);
+    }
+
+    #[test]
+    fn test_handles_utf8_split_across_reads() {
+        let mut decoder = SseDecoder::new();
+        let bytes = "data: 🦀\n\n".as_bytes();
+        // Split inside the four-byte crab emoji
+        assert!(decoder.push(&bytes[..8]).is_empty());
+        let payloads = decoder.push(&bytes[8..]);
+        assert_eq!(payloads, vec!["🦀"]);
+    }
+
+    #[test]
+    fn test_finish_flushes_unterminated_event() {
+        let mut decoder = SseDecoder::new();
+        assert!(decoder.push(b"data: tail").is_empty());
+        assert_eq!(decoder.finish(), Some("tail".to_string()));
+        assert_eq!(decoder.finish(), None);
+    }
+}
@@ -1,10 +1,15 @@
 //! AI provider trait definitions
 
 use crate::ai::{
-    error::AiResult,
-    models::chat::{ChatRequest, ChatResponse},
+    error::{AiError, AiResult},
+    models::chat::{ChatRequest, ChatResponse, StreamEvent},
+    models::embedding::{EmbeddingRequest, EmbeddingResponse},
 };
 use async_trait::async_trait;
+use futures::stream::{self, BoxStream};
+
+/// Stream of events produced by a streaming chat completion
+pub type ChatStream = BoxStream<'static, AiResult<StreamEvent>>;
 
 /// Trait that all AI providers must implement
 #[async_trait]
@@ -15,6 +20,13 @@ pub trait AiProvider: Send + Sync {
     /// Get the current model being used
     fn model(&self) -> &str;
 
+    /// Get the models this provider serves, starting with the default model
+    ///
+    /// Requests naming one of these models are routed to this provider.
+    fn models(&self) -> Vec<String> {
+        vec![self.model().to_string()]
+    }
+
     /// Send a chat completion request
     ///
     /// # Errors
@@ -25,6 +37,59 @@ pub trait AiProvider: Send + Sync {
     /// - The response cannot be parsed
     async fn chat(&self, request: ChatRequest) -> AiResult<ChatResponse>;
 
+    /// Send a streaming chat completion request
+    ///
+    /// The stream yields a `Start` event, zero or more `Delta` events as content
+    /// arrives, and ends with a `Done` event carrying the finish reason and usage.
+    ///
+    /// The default implementation performs a regular `chat` call and replays the
+    /// complete response as a single delta, for providers without native streaming.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request cannot be started. Failures after the
+    /// stream has started are yielded as stream items.
+    async fn chat_stream(&self, request: ChatRequest) -> AiResult<ChatStream> {
+        let response = self.chat(request).await?;
+        let finish_reason = response
+            .choices
+            .first()
+            .and_then(|c| c.finish_reason.clone())
+            .unwrap_or_else(|| "stop".to_string());
+        let content = response.content();
+
+        let mut events = vec![Ok(StreamEvent::Start {
+            id: response.id,
+            model: response.model,
+        })];
+        if !content.is_empty() {
+            events.push(Ok(StreamEvent::Delta { content, index: 0 }));
+        }
+        events.push(Ok(StreamEvent::Done {
+            finish_reason,
+            usage: response.usage,
+        }));
+
+        Ok(Box::pin(stream::iter(events)))
+    }
+
+    /// Embed texts as vectors for similarity search
+    ///
+    /// The default implementation reports that the provider has no embeddings API.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider does not support embeddings, the
+    /// request is invalid, the provider is unreachable or the response cannot
+    /// be parsed
+    async fn embed(&self, request: EmbeddingRequest) -> AiResult<EmbeddingResponse> {
+        let _ = request;
+        Err(AiError::InvalidRequest(format!(
+            "Provider '{}' does not support embeddings",
+            self.name()
+        )))
+    }
+
     /// Check if the provider is healthy
     ///
     /// # Errors
@@ -1,8 +1,38 @@
 //! JSON schema validation for structured AI responses
+//!
+//! Implements the subset of JSON Schema used by structured outputs: `type`,
+//! `enum`, `const`, object `properties`/`required`/`additionalProperties`, array
+//! `items` and length bounds, numeric and string bounds, `pattern`, `anyOf`/
+//! `allOf`/`oneOf`, and `$ref` into `$defs` (or any JSON pointer within the
+//! schema). Every violation is reported with the JSON pointer of the offending
+//! value.
 
 use crate::ai::{AiError, AiResult};
-use serde_json::Value;
+use regex::Regex;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
+use std::fmt;
+
+/// How deeply schemas may nest, counting `$ref` hops, before validation gives up
+const MAX_DEPTH: usize = 128;
+
+/// A single place where a value does not match its schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value; empty for the root
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "(root): {}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
 
 /// Schema validator for AI responses
 pub struct SchemaValidator {
@@ -21,119 +51,445 @@ impl SchemaValidator {
     ///
     /// # Errors
     ///
-    /// Returns an error if the schema is invalid
+    /// Returns an error if the schema is not an object, a `$ref` does not resolve
+    /// or a `pattern` is not a valid regular expression
     pub fn register_schema(&mut self, name: &str, schema: Value) -> AiResult<()> {
-        // Basic validation that it's a valid JSON schema
         if !schema.is_object() {
             return Err(AiError::SchemaValidation(
                 "Schema must be an object".to_string(),
             ));
         }
+        check_schema(&schema, &schema, "")?;
 
         self.schemas.insert(name.to_string(), schema);
         Ok(())
     }
 
+    /// Get a registered schema by name
+    #[must_use]
+    pub fn get_schema(&self, name: &str) -> Option<&Value> {
+        self.schemas.get(name)
+    }
+
     /// Validate a value against a named schema
     ///
     /// # Errors
     ///
     /// Returns an error if:
     /// - The schema doesn't exist
-    /// - The value doesn't match the schema
+    /// - The value doesn't match the schema; the message lists every violation
     pub fn validate(&self, schema_name: &str, value: &Value) -> AiResult<()> {
+        let violations = self.violations(schema_name, value)?;
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        Err(AiError::SchemaValidation(
+            violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; "),
+        ))
+    }
+
+    /// List every way a value fails to match a named schema
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema doesn't exist
+    pub fn violations(&self, schema_name: &str, value: &Value) -> AiResult<Vec<SchemaViolation>> {
         let schema = self.schemas.get(schema_name).ok_or_else(|| {
             AiError::SchemaValidation(format!("Schema '{schema_name}' not found"))
         })?;
 
-        // For now, just do basic type checking
-        // TODO: Implement full JSON schema validation
-        validate_against_schema(value, schema)
+        Ok(validate_against_schema(value, schema))
     }
 }
 
-/// Validate a value against a schema
-fn validate_against_schema(value: &Value, schema: &Value) -> AiResult<()> {
-    // Basic type validation
-    if let Some(schema_type) = schema.get("type").and_then(Value::as_str) {
-        match schema_type {
-            "object" => {
-                if !value.is_object() {
-                    return Err(AiError::SchemaValidation("Expected object".to_string()));
-                }
+/// Validate a value against a standalone schema, returning every violation
+#[must_use]
+pub fn validate_against_schema(value: &Value, schema: &Value) -> Vec<SchemaViolation> {
+    let mut validation = Validation {
+        root: schema,
+        violations: Vec::new(),
+    };
+    validation.check(value, schema, "", 0);
+    validation.violations
+}
 
-                // Validate required properties
-                if let Some(required) = schema.get("required").and_then(Value::as_array) {
-                    let obj = value.as_object().ok_or_else(|| {
-                        AiError::SchemaValidation("Value is not an object".to_string())
-                    })?;
-                    for req in required {
-                        if let Some(req_name) = req.as_str() {
-                            if !obj.contains_key(req_name) {
-                                return Err(AiError::SchemaValidation(format!(
-                                    "Missing required property: {req_name}"
-                                )));
-                            }
-                        }
-                    }
+/// Check that every `$ref` in a schema resolves and every `pattern` compiles
+fn check_schema(root: &Value, schema: &Value, location: &str) -> AiResult<()> {
+    match schema {
+        Value::Object(keywords) => {
+            if let Some(reference) = keywords.get("$ref").and_then(Value::as_str)
+                && resolve_ref(root, reference).is_none()
+            {
+                return Err(AiError::SchemaValidation(format!(
+                    "Unresolvable $ref '{reference}' at '{location}'"
+                )));
+            }
+            if let Some(pattern) = keywords.get("pattern").and_then(Value::as_str) {
+                Regex::new(pattern).map_err(|e| {
+                    AiError::SchemaValidation(format!(
+                        "Invalid pattern '{pattern}' at '{location}': {e}"
+                    ))
+                })?;
+            }
+            for (key, child) in keywords {
+                // Enum and const values are data, not subschemas
+                if key != "enum" && key != "const" {
+                    check_schema(root, child, &format!("{location}/{}", escape(key)))?;
                 }
+            }
+            Ok(())
+        }
+        Value::Array(children) => children.iter().enumerate().try_for_each(|(index, child)| {
+            check_schema(root, child, &format!("{location}/{index}"))
+        }),
+        _ => Ok(()),
+    }
+}
 
-                // Validate properties
-                if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
-                    let obj = value.as_object().ok_or_else(|| {
-                        AiError::SchemaValidation("Value is not an object".to_string())
-                    })?;
-                    for (key, val) in obj {
-                        if let Some(prop_schema) = properties.get(key) {
-                            validate_against_schema(val, prop_schema)?;
-                        }
-                    }
-                }
+/// Resolve a `$ref` such as `#/$defs/issue` against the root schema
+fn resolve_ref<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    if pointer.is_empty() {
+        Some(root)
+    } else {
+        root.pointer(pointer)
+    }
+}
+
+/// Escape a property name for use in a JSON pointer
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn matches_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "integer" => value
+            .as_f64()
+            .is_some_and(|n| value.is_i64() || value.is_u64() || n.fract() == 0.0),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+struct Validation<'a> {
+    root: &'a Value,
+    violations: Vec<SchemaViolation>,
+}
+
+impl Validation<'_> {
+    fn fail(&mut self, path: &str, message: String) {
+        self.violations.push(SchemaViolation {
+            path: path.to_string(),
+            message,
+        });
+    }
+
+    /// Whether a value matches a subschema, without recording its violations
+    fn matches(&self, value: &Value, schema: &Value, path: &str, depth: usize) -> bool {
+        let mut probe = Validation {
+            root: self.root,
+            violations: Vec::new(),
+        };
+        probe.check(value, schema, path, depth);
+        probe.violations.is_empty()
+    }
+
+    fn check(&mut self, value: &Value, schema: &Value, path: &str, depth: usize) {
+        if depth > MAX_DEPTH {
+            self.fail(path, "schema nesting is too deep".to_string());
+            return;
+        }
+
+        let keywords = match schema {
+            Value::Object(keywords) => keywords,
+            Value::Bool(false) => {
+                self.fail(path, "no value is allowed here".to_string());
+                return;
             }
-            "array" => {
-                if !value.is_array() {
-                    return Err(AiError::SchemaValidation("Expected array".to_string()));
-                }
+            _ => return,
+        };
 
-                // Validate items
-                if let Some(items_schema) = schema.get("items") {
-                    let arr = value.as_array().ok_or_else(|| {
-                        AiError::SchemaValidation("Value is not an array".to_string())
-                    })?;
-                    for item in arr {
-                        validate_against_schema(item, items_schema)?;
-                    }
-                }
+        if let Some(reference) = keywords.get("$ref").and_then(Value::as_str) {
+            match resolve_ref(self.root, reference) {
+                Some(target) => self.check(value, target, path, depth + 1),
+                None => self.fail(path, format!("unresolvable $ref '{reference}'")),
             }
-            "string" => {
-                if !value.is_string() {
-                    return Err(AiError::SchemaValidation("Expected string".to_string()));
-                }
+        }
+
+        if !self.check_type(value, keywords, path) {
+            // Further keywords would only repeat the type mismatch
+            return;
+        }
+        self.check_values(value, keywords, path);
+        self.check_combinators(value, keywords, path, depth);
+
+        match value {
+            Value::Object(object) => self.check_object(object, keywords, path, depth),
+            Value::Array(items) => self.check_array(items, keywords, path, depth),
+            Value::String(text) => self.check_string(text, keywords, path),
+            Value::Number(_) => self.check_number(value, keywords, path),
+            _ => {}
+        }
+    }
+
+    fn check_type(&mut self, value: &Value, keywords: &Map<String, Value>, path: &str) -> bool {
+        let expected: Vec<&str> = match keywords.get("type") {
+            Some(Value::String(expected)) => vec![expected.as_str()],
+            Some(Value::Array(expected)) => expected.iter().filter_map(Value::as_str).collect(),
+            _ => return true,
+        };
+
+        if let Some(unknown) = expected.iter().find(|expected| {
+            !matches!(
+                **expected,
+                "object" | "array" | "string" | "number" | "integer" | "boolean" | "null"
+            )
+        }) {
+            self.fail(path, format!("unknown type '{unknown}' in schema"));
+            return false;
+        }
+
+        if expected
+            .iter()
+            .any(|expected| matches_type(value, expected))
+        {
+            true
+        } else {
+            self.fail(
+                path,
+                format!(
+                    "expected {}, got {}",
+                    expected.join(" or "),
+                    type_name(value)
+                ),
+            );
+            false
+        }
+    }
+
+    fn check_values(&mut self, value: &Value, keywords: &Map<String, Value>, path: &str) {
+        if let Some(allowed) = keywords.get("enum").and_then(Value::as_array)
+            && !allowed.contains(value)
+        {
+            let allowed: Vec<String> = allowed.iter().map(ToString::to_string).collect();
+            self.fail(
+                path,
+                format!("{value} is not one of {}", allowed.join(", ")),
+            );
+        }
+
+        if let Some(expected) = keywords.get("const")
+            && expected != value
+        {
+            self.fail(path, format!("expected {expected}, got {value}"));
+        }
+    }
+
+    fn check_combinators(
+        &mut self,
+        value: &Value,
+        keywords: &Map<String, Value>,
+        path: &str,
+        depth: usize,
+    ) {
+        if let Some(all) = keywords.get("allOf").and_then(Value::as_array) {
+            for schema in all {
+                self.check(value, schema, path, depth + 1);
             }
-            "number" => {
-                if !value.is_number() {
-                    return Err(AiError::SchemaValidation("Expected number".to_string()));
-                }
+        }
+
+        if let Some(any) = keywords.get("anyOf").and_then(Value::as_array)
+            && !any
+                .iter()
+                .any(|schema| self.matches(value, schema, path, depth + 1))
+        {
+            self.fail(
+                path,
+                "does not match any of the allowed schemas".to_string(),
+            );
+        }
+
+        if let Some(one) = keywords.get("oneOf").and_then(Value::as_array) {
+            let matching = one
+                .iter()
+                .filter(|schema| self.matches(value, schema, path, depth + 1))
+                .count();
+            if matching != 1 {
+                self.fail(
+                    path,
+                    format!("must match exactly one schema, matched {matching}"),
+                );
             }
-            "boolean" => {
-                if !value.is_boolean() {
-                    return Err(AiError::SchemaValidation("Expected boolean".to_string()));
+        }
+    }
+
+    fn check_object(
+        &mut self,
+        object: &Map<String, Value>,
+        keywords: &Map<String, Value>,
+        path: &str,
+        depth: usize,
+    ) {
+        if let Some(required) = keywords.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    self.fail(path, format!("missing required property '{name}'"));
                 }
             }
-            "null" => {
-                if !value.is_null() {
-                    return Err(AiError::SchemaValidation("Expected null".to_string()));
+        }
+
+        let properties = keywords.get("properties").and_then(Value::as_object);
+        let additional = keywords.get("additionalProperties");
+        for (key, child) in object {
+            let child_path = format!("{path}/{}", escape(key));
+            match (
+                properties.and_then(|properties| properties.get(key)),
+                additional,
+            ) {
+                (Some(schema), _) => self.check(child, schema, &child_path, depth + 1),
+                (None, Some(Value::Bool(false))) => {
+                    self.fail(&child_path, format!("unexpected property '{key}'"));
+                }
+                (None, Some(schema @ Value::Object(_))) => {
+                    self.check(child, schema, &child_path, depth + 1);
                 }
+                (None, _) => {}
             }
-            _ => {
-                return Err(AiError::SchemaValidation(format!(
-                    "Unknown type: {schema_type}"
-                )));
+        }
+
+        self.check_count(
+            object.len(),
+            keywords,
+            "minProperties",
+            "maxProperties",
+            "properties",
+            path,
+        );
+    }
+
+    fn check_array(
+        &mut self,
+        items: &[Value],
+        keywords: &Map<String, Value>,
+        path: &str,
+        depth: usize,
+    ) {
+        if let Some(schema) = keywords.get("items") {
+            for (index, item) in items.iter().enumerate() {
+                self.check(item, schema, &format!("{path}/{index}"), depth + 1);
             }
         }
+
+        self.check_count(items.len(), keywords, "minItems", "maxItems", "items", path);
+
+        if keywords.get("uniqueItems").and_then(Value::as_bool) == Some(true)
+            && let Some(index) =
+                (1..items.len()).find(|&index| items[..index].contains(&items[index]))
+        {
+            self.fail(
+                &format!("{path}/{index}"),
+                "duplicate array item".to_string(),
+            );
+        }
     }
 
-    Ok(())
+    fn check_string(&mut self, text: &str, keywords: &Map<String, Value>, path: &str) {
+        self.check_count(
+            text.chars().count(),
+            keywords,
+            "minLength",
+            "maxLength",
+            "characters",
+            path,
+        );
+
+        if let Some(pattern) = keywords.get("pattern").and_then(Value::as_str) {
+            match Regex::new(pattern) {
+                Ok(regex) if !regex.is_match(text) => {
+                    self.fail(path, format!("does not match pattern '{pattern}'"));
+                }
+                Ok(_) => {}
+                Err(e) => self.fail(path, format!("invalid pattern '{pattern}': {e}")),
+            }
+        }
+    }
+
+    fn check_number(&mut self, value: &Value, keywords: &Map<String, Value>, path: &str) {
+        let Some(number) = value.as_f64() else {
+            return;
+        };
+        let bound = |name: &str| keywords.get(name).and_then(Value::as_f64);
+
+        if let Some(minimum) = bound("minimum")
+            && number < minimum
+        {
+            self.fail(path, format!("{value} is less than the minimum {minimum}"));
+        }
+        if let Some(maximum) = bound("maximum")
+            && number > maximum
+        {
+            self.fail(
+                path,
+                format!("{value} is greater than the maximum {maximum}"),
+            );
+        }
+        if let Some(minimum) = bound("exclusiveMinimum")
+            && number <= minimum
+        {
+            self.fail(path, format!("{value} must be greater than {minimum}"));
+        }
+        if let Some(maximum) = bound("exclusiveMaximum")
+            && number >= maximum
+        {
+            self.fail(path, format!("{value} must be less than {maximum}"));
+        }
+    }
+
+    /// Check a length or size against a pair of `min*`/`max*` keywords
+    fn check_count(
+        &mut self,
+        count: usize,
+        keywords: &Map<String, Value>,
+        min_keyword: &str,
+        max_keyword: &str,
+        unit: &str,
+        path: &str,
+    ) {
+        let limit = |name: &str| {
+            keywords
+                .get(name)
+                .and_then(Value::as_u64)
+                .and_then(|limit| usize::try_from(limit).ok())
+        };
+
+        if let Some(min) = limit(min_keyword)
+            && count < min
+        {
+            self.fail(path, format!("has {count} {unit}, fewer than {min}"));
+        }
+        if let Some(max) = limit(max_keyword)
+            && count > max
+        {
+            self.fail(path, format!("has {count} {unit}, more than {max}"));
+        }
+    }
 }
 
 impl SchemaValidator {
@@ -273,4 +629,125 @@ mod tests {
         });
         assert!(validator.validate("person", &invalid_type).is_err());
     }
+
+    fn paths(violations: &[SchemaViolation]) -> Vec<&str> {
+        violations.iter().map(|v| v.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_nested_violations_report_pointer_paths() {
+        let analysis = json!({
+            "summary": "Looks fine",
+            "complexity": "trivial",
+            "issues": [
+                { "line": 3, "severity": "warning", "message": "Unused import" },
+                { "line": "ten", "severity": "fatal" }
+            ],
+            "suggestions": ["Add tests", 42]
+        });
+
+        let violations = validate_against_schema(&analysis, &schemas::code_analysis());
+        assert_eq!(
+            paths(&violations),
+            [
+                "/complexity",
+                "/issues/1",
+                "/issues/1/line",
+                "/issues/1/severity",
+                "/suggestions/1"
+            ]
+        );
+        assert_eq!(
+            violations[1].to_string(),
+            "/issues/1: missing required property 'message'"
+        );
+    }
+
+    #[test]
+    fn test_strict_objects_refs_and_bounds() {
+        let schema = json!({
+            "type": "object",
+            "required": ["id", "tags", "owner"],
+            "additionalProperties": false,
+            "properties": {
+                "id": { "type": "string", "pattern": "^ISS-[0-9]+$" },
+                "kind": { "const": "issue" },
+                "points": { "type": "integer", "minimum": 1, "maximum": 13 },
+                "tags": { "type": "array", "items": { "type": "string", "minLength": 2 }, "maxItems": 2 },
+                "owner": { "$ref": "#/$defs/person" },
+                "reviewer": { "anyOf": [{ "$ref": "#/$defs/person" }, { "type": "null" }] }
+            },
+            "$defs": {
+                "person": {
+                    "type": "object",
+                    "required": ["name"],
+                    "properties": { "name": { "type": "string" } }
+                }
+            }
+        });
+
+        let valid = json!({
+            "id": "ISS-12",
+            "kind": "issue",
+            "points": 5,
+            "tags": ["ui"],
+            "owner": { "name": "Sam" },
+            "reviewer": null
+        });
+        assert!(validate_against_schema(&valid, &schema).is_empty());
+
+        let invalid = json!({
+            "id": "12",
+            "kind": "epic",
+            "points": 21,
+            "tags": ["ui", "x", "api"],
+            "owner": { "name": 7 },
+            "reviewer": "Sam",
+            "extra": true
+        });
+        assert_eq!(
+            paths(&validate_against_schema(&invalid, &schema)),
+            [
+                "/id",
+                "/kind",
+                "/points",
+                "/tags/1",
+                "/tags",
+                "/owner/name",
+                "/reviewer",
+                "/extra"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_register_rejects_broken_schemas() {
+        let mut validator = SchemaValidator::new();
+        assert!(
+            validator
+                .register_schema("dangling", json!({ "$ref": "#/$defs/missing" }))
+                .is_err()
+        );
+        assert!(
+            validator
+                .register_schema("pattern", json!({ "type": "string", "pattern": "([" }))
+                .is_err()
+        );
+        assert!(
+            validator
+                .register_schema("moderation", schemas::moderation_response())
+                .is_ok()
+        );
+
+        let error = validator
+            .validate(
+                "moderation",
+                &json!({ "safe": "yes", "issues": [], "severity": "none" }),
+            )
+            .expect_err("invalid moderation response");
+        assert_eq!(
+            error.to_string(),
+            "Schema validation error: (root): missing required property 'recommendation'; /safe: expected boolean, got string"
+        );
+    }
 }
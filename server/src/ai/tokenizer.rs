@@ -0,0 +1,476 @@
+//! Token counting with byte pair encoding vocabularies
+//!
+//! Vocabularies are read from `{AI_TOKENIZERS_DIR}/{encoding}.tiktoken`, the
+//! format `OpenAI` publishes its encodings in: one base64 token and its rank
+//! per line. Each model is counted with the encoding it was trained with, found
+//! by the longest matching model name prefix; models without a known encoding
+//! use `AI_TOKENIZER_DEFAULT`. When an encoding's file is missing, tokens are
+//! estimated as one per four characters instead.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use fancy_regex::Regex;
+
+use crate::ai::{AiError, AiResult};
+
+/// Directory holding vocabulary files when `AI_TOKENIZERS_DIR` is not set
+const DEFAULT_TOKENIZERS_DIR: &str = "./tokenizers";
+
+/// Encoding for models without a known one when `AI_TOKENIZER_DEFAULT` is not set
+const DEFAULT_ENCODING: &str = "cl100k_base";
+
+/// Characters per token assumed when no vocabulary is available
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Model name prefixes and the encodings of those models
+const MODEL_ENCODINGS: &[(&str, &str)] = &[
+    ("gpt-5", "o200k_base"),
+    ("gpt-4.1", "o200k_base"),
+    ("gpt-4.5", "o200k_base"),
+    ("gpt-4o", "o200k_base"),
+    ("o1", "o200k_base"),
+    ("o3", "o200k_base"),
+    ("o4", "o200k_base"),
+    ("gpt-4", "cl100k_base"),
+    ("gpt-3.5", "cl100k_base"),
+    ("text-embedding-3", "cl100k_base"),
+    ("text-embedding-ada-002", "cl100k_base"),
+    ("text-davinci", "p50k_base"),
+    ("davinci", "r50k_base"),
+];
+
+/// Splits text into words before merging, for `cl100k_base` and encodings
+/// without a pattern of their own
+const CL100K_PATTERN: &str = r"'(?i:[sdmt]|ll|ve|re)|[^\r\n\p{L}\p{N}]?+\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]++[\r\n]*|\s*[\r\n]|\s+(?!\S)|\s+";
+
+const O200K_PATTERN: &str = r"[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]*[\p{Ll}\p{Lm}\p{Lo}\p{M}]+(?i:'s|'t|'re|'ve|'m|'ll|'d)?|[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]+[\p{Ll}\p{Lm}\p{Lo}\p{M}]*(?i:'s|'t|'re|'ve|'m|'ll|'d)?|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n/]*|\s*[\r\n]+|\s+(?!\S)|\s+";
+
+const GPT2_PATTERN: &str =
+    r"'(?:[sdmt]|ll|ve|re)| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+(?!\S)|\s+";
+
+/// Pattern that splits text into words for an encoding
+fn pattern_for(encoding: &str) -> &'static str {
+    match encoding {
+        "o200k_base" => O200K_PATTERN,
+        "r50k_base" | "p50k_base" | "p50k_edit" | "gpt2" => GPT2_PATTERN,
+        _ => CL100K_PATTERN,
+    }
+}
+
+/// A byte pair encoding vocabulary
+#[derive(Debug)]
+pub struct BpeTokenizer {
+    encoding: String,
+    ranks: HashMap<Vec<u8>, u32>,
+    tokens: HashMap<u32, Vec<u8>>,
+    pattern: Regex,
+}
+
+impl BpeTokenizer {
+    /// Build a tokenizer from the contents of a `.tiktoken` file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a line is not a base64 token followed by its rank
+    pub fn parse(encoding: &str, contents: &str) -> AiResult<Self> {
+        let mut ranks = HashMap::new();
+        for (number, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = || {
+                AiError::Configuration(format!(
+                    "Invalid line {} in the {encoding} vocabulary",
+                    number + 1
+                ))
+            };
+            let (token, rank) = line.split_once(' ').ok_or_else(invalid)?;
+            let token = BASE64.decode(token).map_err(|_| invalid())?;
+            let rank = rank.trim().parse().map_err(|_| invalid())?;
+            ranks.insert(token, rank);
+        }
+
+        let pattern = Regex::new(pattern_for(encoding))
+            .map_err(|e| AiError::Configuration(format!("Invalid pattern for {encoding}: {e}")))?;
+        let tokens = ranks
+            .iter()
+            .map(|(bytes, rank)| (*rank, bytes.clone()))
+            .collect();
+        Ok(Self {
+            encoding: encoding.to_string(),
+            ranks,
+            tokens,
+            pattern,
+        })
+    }
+
+    /// Name of the encoding, such as `cl100k_base`
+    #[must_use]
+    pub fn encoding(&self) -> &str {
+        &self.encoding
+    }
+
+    /// Encode text as token ranks
+    #[must_use]
+    pub fn encode(&self, text: &str) -> Vec<u32> {
+        let mut tokens = Vec::new();
+        for (start, end) in self.pieces(text) {
+            self.encode_piece(&text.as_bytes()[start..end], &mut tokens);
+        }
+        tokens
+    }
+
+    /// Decode token ranks back into bytes; unknown ranks are skipped
+    #[must_use]
+    pub fn decode(&self, tokens: &[u32]) -> Vec<u8> {
+        tokens
+            .iter()
+            .filter_map(|rank| self.tokens.get(rank))
+            .flat_map(|bytes| bytes.iter().copied())
+            .collect()
+    }
+
+    /// Byte ranges of the words text is split into before merging
+    ///
+    /// The ranges cover the whole text; anything the pattern skips, or cannot
+    /// match within its backtracking limit, is kept as a word of its own.
+    fn pieces(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut pieces = Vec::new();
+        let mut last = 0;
+        for found in self.pattern.find_iter(text) {
+            let Ok(found) = found else { break };
+            if found.start() > last {
+                pieces.push((last, found.start()));
+            }
+            pieces.push((found.start(), found.end()));
+            last = found.end();
+        }
+        if last < text.len() {
+            pieces.push((last, text.len()));
+        }
+        pieces
+    }
+
+    /// Merge a word's bytes into tokens, lowest ranked pair first
+    fn encode_piece(&self, piece: &[u8], tokens: &mut Vec<u32>) {
+        if let Some(&rank) = self.ranks.get(piece) {
+            tokens.push(rank);
+            return;
+        }
+
+        // Boundaries between the word's current parts, starting from single bytes
+        let mut bounds: Vec<usize> = (0..=piece.len()).collect();
+        loop {
+            let mut best: Option<(u32, usize)> = None;
+            for i in 0..bounds.len().saturating_sub(2) {
+                if let Some(&rank) = self.ranks.get(&piece[bounds[i]..bounds[i + 2]])
+                    && best.is_none_or(|(lowest, _)| rank < lowest)
+                {
+                    best = Some((rank, i));
+                }
+            }
+            let Some((_, i)) = best else { break };
+            bounds.remove(i + 1);
+        }
+
+        for part in bounds.windows(2) {
+            if let Some(&rank) = self.ranks.get(&piece[part[0]..part[1]]) {
+                tokens.push(rank);
+            }
+        }
+    }
+
+    /// Length in bytes of the longest prefix of text that is at most
+    /// `max_tokens` tokens
+    fn prefix_len(&self, text: &str, max_tokens: usize) -> usize {
+        let mut remaining = max_tokens;
+        for (start, end) in self.pieces(text) {
+            let mut tokens = Vec::new();
+            self.encode_piece(&text.as_bytes()[start..end], &mut tokens);
+            if tokens.len() <= remaining {
+                remaining -= tokens.len();
+                continue;
+            }
+            let kept: usize = tokens[..remaining]
+                .iter()
+                .filter_map(|rank| self.tokens.get(rank))
+                .map(Vec::len)
+                .sum();
+            return start + kept;
+        }
+        text.len()
+    }
+}
+
+/// Counts and truncates text in a model's tokens
+#[derive(Debug, Clone)]
+pub enum Tokenizer {
+    /// The model's own vocabulary
+    Bpe(Arc<BpeTokenizer>),
+    /// One token per four characters, for models whose vocabulary is missing
+    Estimate,
+}
+
+impl Tokenizer {
+    /// Name of the encoding, or `estimate`
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Bpe(bpe) => bpe.encoding(),
+            Self::Estimate => "estimate",
+        }
+    }
+
+    /// Number of tokens in a text
+    #[must_use]
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Self::Bpe(bpe) => bpe.encode(text).len(),
+            Self::Estimate => text.chars().count().div_ceil(CHARS_PER_TOKEN),
+        }
+    }
+
+    /// Number of tokens in a text, as stored in `token_count` columns
+    #[must_use]
+    pub fn count_i64(&self, text: &str) -> i64 {
+        i64::try_from(self.count(text)).unwrap_or(i64::MAX)
+    }
+
+    /// The longest prefix of a text that is at most `max_tokens` tokens
+    ///
+    /// The text is cut on a token boundary, moved back to the previous
+    /// character boundary if a token ends inside a character.
+    #[must_use]
+    pub fn truncate<'a>(&self, text: &'a str, max_tokens: usize) -> &'a str {
+        let mut end = match self {
+            Self::Bpe(bpe) => bpe.prefix_len(text, max_tokens),
+            Self::Estimate => text
+                .char_indices()
+                .nth(max_tokens.saturating_mul(CHARS_PER_TOKEN))
+                .map_or(text.len(), |(index, _)| index),
+        };
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        &text[..end]
+    }
+}
+
+/// Vocabularies by encoding, loaded when first used
+pub struct Tokenizers {
+    dir: PathBuf,
+    /// Model name prefixes and encodings, checked before the built-in ones
+    models: Vec<(String, String)>,
+    default_encoding: String,
+    /// Loaded vocabularies; `None` for encodings whose file could not be read
+    loaded: RwLock<HashMap<String, Option<Arc<BpeTokenizer>>>>,
+}
+
+impl Tokenizers {
+    /// Create a registry reading vocabularies from a directory
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            models: Vec::new(),
+            default_encoding: DEFAULT_ENCODING.to_string(),
+            loaded: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Create a registry configured from the environment
+    ///
+    /// - `AI_TOKENIZERS_DIR`: directory of `.tiktoken` files (default `./tokenizers`)
+    /// - `AI_TOKENIZER_MODELS`: extra `prefix=encoding` pairs, comma separated
+    /// - `AI_TOKENIZER_DEFAULT`: encoding for other models (default `cl100k_base`)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `AI_TOKENIZER_MODELS` is malformed
+    pub fn from_env() -> AiResult<Self> {
+        let dir = std::env::var("AI_TOKENIZERS_DIR")
+            .unwrap_or_else(|_| DEFAULT_TOKENIZERS_DIR.to_string());
+        let mut tokenizers = Self::new(dir);
+
+        if let Ok(models) = std::env::var("AI_TOKENIZER_MODELS") {
+            for pair in models.split(',').filter(|pair| !pair.trim().is_empty()) {
+                let (prefix, encoding) = pair
+                    .split_once('=')
+                    .map(|(prefix, encoding)| (prefix.trim(), encoding.trim()))
+                    .filter(|(prefix, encoding)| !prefix.is_empty() && !encoding.is_empty())
+                    .ok_or_else(|| {
+                        AiError::Configuration(format!(
+                            "AI_TOKENIZER_MODELS entries must be 'prefix=encoding', got '{pair}'"
+                        ))
+                    })?;
+                tokenizers = tokenizers.with_model(prefix, encoding);
+            }
+        }
+        if let Ok(encoding) = std::env::var("AI_TOKENIZER_DEFAULT")
+            && !encoding.trim().is_empty()
+        {
+            tokenizers.default_encoding = encoding.trim().to_string();
+        }
+        Ok(tokenizers)
+    }
+
+    /// Count models whose names start with a prefix in an encoding
+    #[must_use]
+    pub fn with_model(mut self, prefix: &str, encoding: &str) -> Self {
+        self.models
+            .push((prefix.to_lowercase(), encoding.to_string()));
+        self
+    }
+
+    /// Encoding of a model, ignoring any `provider/` prefix in its name
+    #[must_use]
+    pub fn encoding_for(&self, model: &str) -> &str {
+        let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+        let configured = self
+            .models
+            .iter()
+            .filter(|(prefix, _)| name.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, encoding)| encoding.as_str());
+        configured
+            .or_else(|| {
+                MODEL_ENCODINGS
+                    .iter()
+                    .filter(|(prefix, _)| name.starts_with(prefix))
+                    .max_by_key(|(prefix, _)| prefix.len())
+                    .map(|(_, encoding)| *encoding)
+            })
+            .unwrap_or(&self.default_encoding)
+    }
+
+    /// Tokenizer for a model
+    #[must_use]
+    pub fn for_model(&self, model: &str) -> Tokenizer {
+        let encoding = self.encoding_for(model);
+        if let Some(loaded) = self
+            .loaded
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(encoding)
+        {
+            return loaded.clone().map_or(Tokenizer::Estimate, Tokenizer::Bpe);
+        }
+
+        let bpe = self.load(encoding).map(Arc::new);
+        self.loaded
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(encoding.to_string(), bpe.clone());
+        bpe.map_or(Tokenizer::Estimate, Tokenizer::Bpe)
+    }
+
+    /// Read an encoding's vocabulary, logging why if it cannot be used
+    fn load(&self, encoding: &str) -> Option<BpeTokenizer> {
+        let path = self.dir.join(format!("{encoding}.tiktoken"));
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                tracing::warn!(
+                    "Tokenizer vocabulary {} could not be read ({e}); estimating {encoding} tokens",
+                    path.display()
+                );
+                return None;
+            }
+        };
+        match BpeTokenizer::parse(encoding, &contents) {
+            Ok(bpe) => {
+                tracing::info!("Loaded tokenizer vocabulary {}", path.display());
+                Some(bpe)
+            }
+            Err(e) => {
+                tracing::warn!("{e}; estimating {encoding} tokens");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Vocabulary of all single bytes plus a few merges, ranked in merge order
+    fn test_vocabulary() -> String {
+        let mut lines: Vec<String> = (0..=255u8)
+            .map(|byte| format!("{} {byte}", BASE64.encode([byte])))
+            .collect();
+        for (rank, token) in ["he", "ll", "hell", "hello", " w", " wor", " world"]
+            .iter()
+            .enumerate()
+        {
+            lines.push(format!("{} {}", BASE64.encode(token), 256 + rank));
+        }
+        lines.join("\n")
+    }
+
+    fn test_tokenizer() -> Tokenizer {
+        Tokenizer::Bpe(Arc::new(
+            BpeTokenizer::parse("cl100k_base", &test_vocabulary()).expect("vocabulary parses"),
+        ))
+    }
+
+    #[test]
+    fn test_bpe_merges_by_rank() {
+        let Tokenizer::Bpe(bpe) = test_tokenizer() else {
+            unreachable!()
+        };
+        assert_eq!(bpe.encode("hello world"), vec![259, 262]);
+        assert_eq!(
+            bpe.encode("help"),
+            vec![256, u32::from(b'l'), u32::from(b'p')]
+        );
+        assert_eq!(
+            bpe.decode(&bpe.encode("hello, wörld!")),
+            "hello, wörld!".as_bytes()
+        );
+        assert!(BpeTokenizer::parse("cl100k_base", "not-base64!").is_err());
+    }
+
+    #[test]
+    fn test_truncate_on_token_boundaries() {
+        let tokenizer = test_tokenizer();
+        assert_eq!(tokenizer.count("hello world"), 2);
+        assert_eq!(tokenizer.truncate("hello world", 1), "hello");
+        assert_eq!(tokenizer.truncate("hello world", 5), "hello world");
+        // "ö" is two byte tokens; half of it is not kept
+        assert_eq!(tokenizer.count("ö"), 2);
+        assert_eq!(tokenizer.truncate("ö", 1), "");
+
+        let estimate = Tokenizer::Estimate;
+        assert_eq!(estimate.count(""), 0);
+        assert_eq!(estimate.count("hello world"), 3);
+        assert_eq!(estimate.count("🦀🚀"), 1);
+        assert_eq!(estimate.truncate("hello world", 1), "hell");
+        assert_eq!(estimate.truncate("🦀🚀🦀🚀🦀", 1), "🦀🚀🦀🚀");
+    }
+
+    #[test]
+    fn test_tokenizers_select_encoding_per_model() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        std::fs::write(dir.path().join("o200k_base.tiktoken"), test_vocabulary())
+            .expect("vocabulary written");
+        let tokenizers = Tokenizers::new(dir.path()).with_model("claude", "o200k_base");
+
+        assert_eq!(tokenizers.encoding_for("openai/gpt-4o-mini"), "o200k_base");
+        assert_eq!(tokenizers.encoding_for("gpt-4-turbo"), "cl100k_base");
+        assert_eq!(tokenizers.encoding_for("llama-3"), "cl100k_base");
+        assert_eq!(
+            tokenizers.encoding_for("anthropic/claude-sonnet-4"),
+            "o200k_base"
+        );
+
+        assert_eq!(tokenizers.for_model("gpt-4o").name(), "o200k_base");
+        assert_eq!(tokenizers.for_model("gpt-4o").count("hello world"), 2);
+        // Missing vocabularies fall back to estimating
+        assert_eq!(tokenizers.for_model("gpt-4").name(), "estimate");
+    }
+}
@@ -1,5 +1,7 @@
 // kanbain/server/src/config/mod.rs
 
 pub mod oauth;
+pub mod oidc;
 
 pub use oauth::OAuthConfig;
+pub use oidc::OidcProviderConfig;
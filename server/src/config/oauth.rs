@@ -8,6 +8,7 @@ use oauth2::{
 };
 use std::env;
 
+use super::OidcProviderConfig;
 use crate::errors::AppError;
 
 /// OAuth provider configuration
@@ -19,6 +20,8 @@ pub struct OAuthConfig {
     pub github_client_secret: Option<String>,
     pub server_url: String,
     pub client_url: String,
+    /// Generic OIDC providers, from `OIDC_PROVIDERS`
+    pub oidc_providers: Vec<OidcProviderConfig>,
 }
 
 impl OAuthConfig {
@@ -55,9 +58,18 @@ impl OAuthConfig {
             github_client_secret,
             server_url,
             client_url,
+            oidc_providers: OidcProviderConfig::from_env()?,
         })
     }
 
+    /// Find a configured OIDC provider by name
+    #[must_use]
+    pub fn oidc_provider(&self, name: &str) -> Option<&OidcProviderConfig> {
+        self.oidc_providers
+            .iter()
+            .find(|provider| provider.name == name)
+    }
+
     /// Get the Google OAuth authorization URL with required scopes
     #[must_use]
     pub fn get_google_auth_url(&self, state: &str) -> String {
@@ -0,0 +1,201 @@
+//! Configuration of generic OIDC providers
+//!
+//! Providers are listed by name in `OIDC_PROVIDERS` and configured with
+//! `OIDC_<NAME>_*` variables, where `<NAME>` is the provider name in upper
+//! case with `-` replaced by `_`:
+//!
+//! ```text
+//! OIDC_PROVIDERS="keycloak,okta"
+//! OIDC_KEYCLOAK_ISSUER="https://sso.example.com/realms/main"
+//! OIDC_KEYCLOAK_CLIENT_ID="kanbain"
+//! OIDC_KEYCLOAK_CLIENT_SECRET="..."          # optional for public clients
+//! OIDC_KEYCLOAK_SCOPES="openid email profile" # optional
+//! OIDC_KEYCLOAK_DISPLAY_NAME="Company SSO"    # optional
+//! OIDC_KEYCLOAK_TRUST_EMAIL="true"            # optional, defaults to false
+//! ```
+//!
+//! Any issuer can claim that an email is verified, so `email_verified` is only
+//! believed for providers marked with `TRUST_EMAIL`, which should be those that
+//! control the email domains their users have. Verified emails sign in to
+//! existing accounts with that email, so trusting a provider lets it sign in
+//! as any of its users' addresses.
+
+use std::env;
+
+use crate::errors::AppError;
+
+/// Names that are taken by built-in providers and OAuth routes
+const RESERVED_NAMES: [&str; 3] = ["google", "github", "exchange"];
+
+const DEFAULT_SCOPES: &str = "openid email profile";
+
+/// An OIDC provider users can sign in with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OidcProviderConfig {
+    /// Name used in routes and stored with linked identities
+    pub name: String,
+    /// Name shown to users
+    pub display_name: String,
+    /// Issuer URL the discovery document is fetched from
+    pub issuer: String,
+    pub client_id: String,
+    /// Secret of confidential clients; public clients rely on PKCE alone
+    pub client_secret: Option<String>,
+    pub scopes: Vec<String>,
+    /// Whether the provider's `email_verified` claim is believed
+    pub trust_email: bool,
+}
+
+impl OidcProviderConfig {
+    /// Read the providers listed in `OIDC_PROVIDERS`
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ConfigError` if a provider name is invalid or listed
+    /// twice, or a provider's issuer or client ID is missing or invalid
+    pub fn from_env() -> Result<Vec<Self>, AppError> {
+        let Ok(names) = env::var("OIDC_PROVIDERS") else {
+            return Ok(Vec::new());
+        };
+
+        let mut providers: Vec<Self> = Vec::new();
+        for name in names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let provider = Self::from_env_for(name)?;
+            if providers.iter().any(|other| other.name == provider.name) {
+                return Err(AppError::ConfigError(format!(
+                    "OIDC provider '{name}' is listed more than once"
+                )));
+            }
+            providers.push(provider);
+        }
+
+        Ok(providers)
+    }
+
+    /// Read the configuration of one provider
+    fn from_env_for(name: &str) -> Result<Self, AppError> {
+        Self::validate_name(name)?;
+        let prefix = format!("OIDC_{}", name.to_uppercase().replace('-', "_"));
+        let var = |suffix: &str| {
+            env::var(format!("{prefix}_{suffix}"))
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let required = |suffix: &str| {
+            var(suffix).ok_or_else(|| {
+                AppError::ConfigError(format!(
+                    "{prefix}_{suffix} is required for OIDC provider '{name}'"
+                ))
+            })
+        };
+
+        let issuer = required("ISSUER")?.trim_end_matches('/').to_string();
+        Self::validate_issuer(&prefix, &issuer)?;
+
+        Ok(Self {
+            name: name.to_string(),
+            display_name: var("DISPLAY_NAME").unwrap_or_else(|| name.to_string()),
+            issuer,
+            client_id: required("CLIENT_ID")?,
+            client_secret: var("CLIENT_SECRET"),
+            scopes: var("SCOPES")
+                .unwrap_or_else(|| DEFAULT_SCOPES.to_string())
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            trust_email: var("TRUST_EMAIL")
+                .is_some_and(|value| value.eq_ignore_ascii_case("true") || value == "1"),
+        })
+    }
+
+    /// Check that a name can be used in routes and does not shadow a built-in provider
+    fn validate_name(name: &str) -> Result<(), AppError> {
+        let valid = !name.is_empty()
+            && !name.starts_with('-')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !valid {
+            return Err(AppError::ConfigError(format!(
+                "OIDC provider name '{name}' must be lowercase letters, digits and '-'"
+            )));
+        }
+        if RESERVED_NAMES.contains(&name) {
+            return Err(AppError::ConfigError(format!(
+                "OIDC provider name '{name}' is reserved"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Issuers must use HTTPS, except on the local machine during development
+    fn validate_issuer(prefix: &str, issuer: &str) -> Result<(), AppError> {
+        let url = reqwest::Url::parse(issuer)
+            .map_err(|e| AppError::ConfigError(format!("{prefix}_ISSUER is not a URL: {e}")))?;
+        let local = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+        if url.scheme() != "https" && !(url.scheme() == "http" && local) {
+            return Err(AppError::ConfigError(format!(
+                "{prefix}_ISSUER must be an https URL"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Callback URL registered with the provider
+    #[must_use]
+    pub fn redirect_uri(&self, server_url: &str) -> String {
+        format!("{server_url}/api/auth/oauth/{}/callback", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        for name in ["keycloak", "azure-ad", "okta2"] {
+            assert!(OidcProviderConfig::validate_name(name).is_ok(), "{name}");
+        }
+        for name in [
+            "Keycloak", "azure_ad", "-okta", "", "github", "exchange", "a/b",
+        ] {
+            assert!(
+                matches!(
+                    OidcProviderConfig::validate_name(name),
+                    Err(AppError::ConfigError(_))
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_issuer() {
+        for issuer in [
+            "https://sso.example.com/realms/main",
+            "http://localhost:8180",
+            "http://127.0.0.1:9000",
+        ] {
+            assert!(
+                OidcProviderConfig::validate_issuer("OIDC_TEST", issuer).is_ok(),
+                "{issuer}"
+            );
+        }
+        for issuer in [
+            "http://sso.example.com",
+            "sso.example.com",
+            "ftp://localhost",
+        ] {
+            assert!(
+                OidcProviderConfig::validate_issuer("OIDC_TEST", issuer).is_err(),
+                "{issuer}"
+            );
+        }
+    }
+}
@@ -5,10 +5,57 @@ use std::sync::Arc;
 use crate::{
     core::AppState,
     errors::AppResult,
-    models::{AuthUser, PaymentUser, UnifiedAuthResponse, User},
+    models::{AuthUser, DeviceInfo, PaymentUser, SessionTokens, UnifiedAuthResponse, User},
     services::payment::PaymentDbOperations,
 };
 
+/// Start a new session for the user and issue its tokens
+///
+/// The access token is bound to the session, so it stops working as soon as the
+/// session is revoked.
+///
+/// # Errors
+/// Returns an error if:
+/// * Failed to fetch the user's roles
+/// * Failed to store the session
+/// * Token generation fails
+pub async fn issue_session_tokens(
+    state: &Arc<AppState>,
+    user: &User,
+    device: &DeviceInfo,
+) -> AppResult<SessionTokens> {
+    let roles = state.role.get_roles(user.id).await?;
+    let (session_id, refresh_token) = state.session.create_session(user.id, device).await?;
+    let access_token =
+        state
+            .auth
+            .generate_session_token(user.id, &user.email, &roles, session_id)?;
+
+    Ok(SessionTokens {
+        session_id,
+        access_token,
+        refresh_token,
+        expires_in: state.auth.access_token_ttl_seconds(),
+    })
+}
+
+/// Start a new session and build the unified auth response including its refresh token
+///
+/// # Errors
+/// Returns an error if the session cannot be created or the response cannot be built
+pub async fn build_session_auth_response(
+    state: &Arc<AppState>,
+    user: &User,
+    device: &DeviceInfo,
+) -> AppResult<UnifiedAuthResponse> {
+    let tokens = issue_session_tokens(state, user, device).await?;
+
+    let mut response = build_unified_auth_response(state, user, tokens.access_token).await?;
+    response.refresh_token = Some(tokens.refresh_token);
+    response.expires_in = Some(tokens.expires_in);
+    Ok(response)
+}
+
 /// Build a unified auth response for any authentication flow
 /// This ensures consistent response format across all auth methods
 ///
@@ -24,6 +71,7 @@ use crate::{
 /// Returns an error if:
 /// * Failed to fetch invite information
 /// * Failed to fetch payment information
+/// * Failed to fetch the email verification status
 pub async fn build_unified_auth_response(
     state: &Arc<AppState>,
     user: &User,
@@ -36,7 +84,8 @@ pub async fn build_unified_auth_response(
     let payment = state.payment.get_active_payment_for_user(user.id).await?;
 
     // Create unified response components
-    let auth_user = AuthUser::from(user.clone());
+    let mut auth_user = AuthUser::from(user.clone());
+    auth_user.email_verified = state.account_email.is_email_verified(user.id).await?;
     let payment_user = PaymentUser::from_payment_and_invite(payment.as_ref(), invite.as_ref());
 
     tracing::debug!(
@@ -48,6 +97,8 @@ pub async fn build_unified_auth_response(
 
     Ok(UnifiedAuthResponse {
         auth_token: token,
+        refresh_token: None,
+        expires_in: None,
         auth_user,
         payment_user,
     })
@@ -103,12 +154,13 @@ mod tests {
         };
 
         // Insert user into database
+        let user_id_str = user.id.to_string();
         sqlx::query!(
             r#"
             INSERT INTO users (id, email, hashed_password, provider, provider_user_id, created_at, updated_at)
             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
             "#,
-            user.id,
+            user_id_str,
             user.email,
             user.hashed_password,
             user.provider,
@@ -2,9 +2,14 @@
 
 pub mod auth_utils;
 pub mod password_utils;
+pub mod session_cookies;
 pub mod state;
+pub mod token_utils;
 
 // Re-export for easier access if desired
 // pub use password_utils::{hash_password, verify_password, PasswordError};
-pub use auth_utils::{build_unified_auth_response, build_unified_auth_response_no_token};
+pub use auth_utils::{
+    build_session_auth_response, build_unified_auth_response, build_unified_auth_response_no_token,
+    issue_session_tokens,
+};
 pub use state::AppState;
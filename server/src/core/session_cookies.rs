@@ -0,0 +1,185 @@
+// kanbain/server/src/core/session_cookies.rs
+
+//! Session cookies for browser clients
+//!
+//! Clients that choose the cookie session mode keep their tokens in `HttpOnly`
+//! cookies instead of script-readable storage. The cookies are `Secure` and
+//! `SameSite=Strict`, so they are not sent with cross-site requests, and the
+//! refresh token is only sent to the auth endpoints that use it.
+
+use axum::{
+    http::{HeaderMap, HeaderName, header},
+    response::AppendHeaders,
+};
+
+use crate::{
+    errors::{AppError, AppResult},
+    models::{UnifiedAuthResponse, session::SessionMode},
+    services::session_service::REFRESH_TOKEN_TTL_DAYS,
+};
+
+/// Cookie carrying the access token
+pub const ACCESS_TOKEN_COOKIE: &str = "kanbain_access";
+
+/// Cookie carrying the refresh token
+pub const REFRESH_TOKEN_COOKIE: &str = "kanbain_refresh";
+
+/// Cookie binding an identity link to the browser that started it
+pub const LINK_STATE_COOKIE: &str = "kanbain_link_state";
+
+/// Path the access token cookie is sent to
+const ACCESS_TOKEN_PATH: &str = "/api";
+
+/// Path the refresh token cookie is sent to: the refresh and logout endpoints
+const REFRESH_TOKEN_PATH: &str = "/api/auth";
+
+/// Path the link state cookie is sent to: the provider callbacks
+const LINK_STATE_PATH: &str = "/api/auth/oauth";
+
+type SetCookieHeaders = AppendHeaders<[(HeaderName, String); 2]>;
+
+fn cookie(name: &str, value: &str, path: &str, max_age: i64) -> String {
+    format!("{name}={value}; Path={path}; Max-Age={max_age}; HttpOnly; Secure; SameSite=Strict")
+}
+
+/// `Set-Cookie` headers storing a session's tokens
+pub fn session_cookies(
+    access_token: &str,
+    access_token_ttl_seconds: i64,
+    refresh_token: &str,
+) -> SetCookieHeaders {
+    AppendHeaders([
+        (
+            header::SET_COOKIE,
+            cookie(
+                ACCESS_TOKEN_COOKIE,
+                access_token,
+                ACCESS_TOKEN_PATH,
+                access_token_ttl_seconds,
+            ),
+        ),
+        (
+            header::SET_COOKIE,
+            cookie(
+                REFRESH_TOKEN_COOKIE,
+                refresh_token,
+                REFRESH_TOKEN_PATH,
+                REFRESH_TOKEN_TTL_DAYS * 24 * 60 * 60,
+            ),
+        ),
+    ])
+}
+
+/// Hand a new session to a client in the mode it chose
+///
+/// In the cookie mode the tokens are moved out of the response into cookies,
+/// which must be set along with it.
+///
+/// # Errors
+///
+/// Returns `AppError::InternalServerError` if the response has no refresh token
+pub fn deliver_session(
+    auth: &mut UnifiedAuthResponse,
+    mode: SessionMode,
+    access_token_ttl_seconds: i64,
+) -> AppResult<Option<SetCookieHeaders>> {
+    if mode == SessionMode::Token {
+        return Ok(None);
+    }
+
+    let refresh_token = auth
+        .refresh_token
+        .take()
+        .ok_or_else(|| AppError::InternalServerError("Session has no refresh token".to_string()))?;
+    let cookies = session_cookies(&auth.auth_token, access_token_ttl_seconds, &refresh_token);
+    auth.auth_token = String::new();
+    Ok(Some(cookies))
+}
+
+/// `Set-Cookie` headers removing the session cookies
+pub fn clear_session_cookies() -> SetCookieHeaders {
+    AppendHeaders([
+        (
+            header::SET_COOKIE,
+            cookie(ACCESS_TOKEN_COOKIE, "", ACCESS_TOKEN_PATH, 0),
+        ),
+        (
+            header::SET_COOKIE,
+            cookie(REFRESH_TOKEN_COOKIE, "", REFRESH_TOKEN_PATH, 0),
+        ),
+    ])
+}
+
+/// `Set-Cookie` header storing the state of an identity link
+///
+/// The provider redirects back to the callback from its own site, so the cookie
+/// is `SameSite=Lax`: it is sent with that top-level navigation, but not with
+/// cross-site subrequests.
+pub fn link_state_cookie(state: &str, max_age: i64) -> AppendHeaders<[(HeaderName, String); 1]> {
+    AppendHeaders([(
+        header::SET_COOKIE,
+        format!(
+            "{LINK_STATE_COOKIE}={state}; Path={LINK_STATE_PATH}; Max-Age={max_age}; HttpOnly; Secure; SameSite=Lax"
+        ),
+    )])
+}
+
+/// Read a cookie sent with a request
+#[must_use]
+pub fn read_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_session_cookies_are_http_only() {
+        let AppendHeaders(cookies) = session_cookies("access", 900, "refresh");
+
+        assert_eq!(
+            cookies[0].1,
+            "kanbain_access=access; Path=/api; Max-Age=900; HttpOnly; Secure; SameSite=Strict"
+        );
+        assert!(
+            cookies[1]
+                .1
+                .starts_with("kanbain_refresh=refresh; Path=/api/auth;")
+        );
+        assert!(cookies[1].1.ends_with("HttpOnly; Secure; SameSite=Strict"));
+
+        let AppendHeaders(cleared) = clear_session_cookies();
+        assert!(cleared.iter().all(|(_, value)| value.contains("Max-Age=0")));
+
+        let AppendHeaders([(_, link)]) = link_state_cookie("state-1", 600);
+        assert_eq!(
+            link,
+            "kanbain_link_state=state-1; Path=/api/auth/oauth; Max-Age=600; HttpOnly; Secure; SameSite=Lax"
+        );
+    }
+
+    #[test]
+    fn test_read_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            header::COOKIE,
+            HeaderValue::from_static("theme=dark; kanbain_access=token-1"),
+        );
+        headers.append(header::COOKIE, HeaderValue::from_static("kanbain_refresh="));
+
+        assert_eq!(read_cookie(&headers, ACCESS_TOKEN_COOKIE), Some("token-1"));
+        assert_eq!(read_cookie(&headers, "theme"), Some("dark"));
+        assert_eq!(read_cookie(&headers, REFRESH_TOKEN_COOKIE), None);
+        assert_eq!(read_cookie(&headers, "missing"), None);
+    }
+}
@@ -4,7 +4,10 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use crate::services::{
-    AiDataService, AiService, AuthService, InviteService, PaymentService, UserServiceImpl,
+    AccountEmailService, AiDataService, AiService, AiSessionService, AuthService, BoardService,
+    CliAuthService, ContextService, DocumentService, EmbeddingService, FileService,
+    IdentityService, InviteService, IssueService, MfaService, PaymentService, QuotaService,
+    RoleService, SessionService, UserServiceImpl,
 };
 
 /// Application state for handlers that need all services
@@ -15,4 +18,18 @@ pub struct AppState {
     pub ai: Arc<RwLock<AiService>>,
     pub ai_data: Arc<AiDataService>,
     pub payment: Arc<PaymentService>,
+    pub role: Arc<RoleService>,
+    pub session: Arc<SessionService>,
+    pub cli_auth: Arc<CliAuthService>,
+    pub ai_session: Arc<AiSessionService>,
+    pub boards: Arc<BoardService>,
+    pub issues: Arc<IssueService>,
+    pub embeddings: Arc<EmbeddingService>,
+    pub documents: Arc<DocumentService>,
+    pub files: Arc<FileService>,
+    pub context: Arc<ContextService>,
+    pub quotas: Arc<QuotaService>,
+    pub identities: Arc<IdentityService>,
+    pub account_email: Arc<AccountEmailService>,
+    pub mfa: Arc<MfaService>,
 }
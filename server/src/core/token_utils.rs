@@ -0,0 +1,60 @@
+// kanbain/server/src/core/token_utils.rs
+
+//! Helpers for opaque, server-side tokens
+//!
+//! Opaque tokens (refresh tokens, one-time codes) are handed to clients once and
+//! only their SHA-256 hash is stored, so a database leak does not expose usable tokens.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// Number of random bytes in a generated token
+const TOKEN_BYTES: usize = 32;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut out, byte| {
+            let _ = write!(out, "{byte:02x}");
+            out
+        })
+}
+
+/// Generate a random opaque token as a hex string
+#[must_use]
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+/// Hash an opaque token for storage and lookup
+#[must_use]
+pub fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_tokens_are_unique_hex() {
+        let first = generate_opaque_token();
+        let second = generate_opaque_token();
+
+        assert_eq!(first.len(), TOKEN_BYTES * 2);
+        assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_hash_token_is_stable_sha256() {
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_ne!(hash_token("abc"), hash_token("abd"));
+    }
+}
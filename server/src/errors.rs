@@ -1,7 +1,7 @@
 // kanbain/server/src/errors.rs
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
 use serde_json::json;
@@ -66,11 +66,19 @@ pub enum AppError {
 
     #[error("Not found: {0}")]
     NotFound(String),
+
+    /// A usage limit was reached; `headers` describe the limit and when to retry
+    #[error("Too many requests: {message}")]
+    TooManyRequests {
+        message: String,
+        headers: Vec<(&'static str, String)>,
+    },
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, error_message, error_detail) = match self {
+impl AppError {
+    /// Status, user-facing message and internal detail of the error response
+    fn status_and_message(self) -> (StatusCode, String, Option<String>) {
+        match self {
             AppError::SqlxError(e) => {
                 tracing::error!("Database error: {:?}", e);
                 (
@@ -156,23 +164,42 @@ impl IntoResponse for AppError {
                 None,
             ),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg, None),
+            AppError::TooManyRequests { message, .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, message, None)
+            }
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(mut self) -> Response {
+        let headers = match &mut self {
+            AppError::TooManyRequests { headers, .. } => std::mem::take(headers),
+            _ => Vec::new(),
         };
+        let (status, error_message, error_detail) = self.status_and_message();
 
         let mut body = json!({ "error": error_message });
 
         // Include detailed error in debug builds only for non-user-facing errors
-        if cfg!(debug_assertions) {
-            if let Some(detail) = error_detail {
-                match status {
-                    StatusCode::INTERNAL_SERVER_ERROR | StatusCode::BAD_REQUEST => {
-                        body["detail"] = json!(detail);
-                    }
-                    _ => {} // Don't add detail for auth errors etc.
+        if cfg!(debug_assertions)
+            && let Some(detail) = error_detail
+        {
+            match status {
+                StatusCode::INTERNAL_SERVER_ERROR | StatusCode::BAD_REQUEST => {
+                    body["detail"] = json!(detail);
                 }
+                _ => {} // Don't add detail for auth errors etc.
             }
         }
 
-        (status, Json(body)).into_response()
+        let mut response = (status, Json(body)).into_response();
+        for (name, value) in headers {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                response.headers_mut().insert(name, value);
+            }
+        }
+        response
     }
 }
 
@@ -297,6 +324,17 @@ mod tests {
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
+    #[tokio::test]
+    async fn test_too_many_requests_response() {
+        let error = AppError::TooManyRequests {
+            message: "Quota exceeded".to_string(),
+            headers: vec![("Retry-After", "60".to_string())],
+        };
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "60");
+    }
+
     #[tokio::test]
     async fn test_sqlx_error_response() {
         // We can't easily create a real SqlxError, so we'll test via the From trait
@@ -0,0 +1,116 @@
+// kanbain/server/src/handlers/account_handler.rs
+
+//! Email verification and password reset HTTP handlers
+//!
+//! Both flows mail the user a link with a one-time token; the client sends the
+//! token back to the matching confirm endpoint.
+
+use axum::{Json, extract::State, response::IntoResponse};
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::{
+    core::AppState,
+    errors::{AppError, AppResult},
+    handlers::auth_handler::validation_error,
+    middleware::JwtAuth,
+    models::account_email::{
+        ConfirmEmailRequest, ConfirmPasswordResetRequest, PasswordResetRequest,
+    },
+};
+
+/// Handler for POST /api/auth/verify-email/request - mails the current user a
+/// verification link
+///
+/// # Errors
+/// Returns `AppError::BadRequest` if the email is already verified, or an error
+/// if the email cannot be sent
+#[tracing::instrument(skip(state, auth), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn request_email_verification_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+) -> AppResult<impl IntoResponse> {
+    let user = state.user.find_by_id(auth.user.user_id).await?;
+    state.account_email.send_verification(&user).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "Verification email sent"
+    })))
+}
+
+/// Handler for POST /api/auth/verify-email/confirm - verifies an email with a
+/// mailed token
+///
+/// # Errors
+/// Returns `AppError::BadRequest` if the token is invalid, expired or was used
+#[tracing::instrument(skip(state, request), err(Debug))]
+pub async fn confirm_email_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ConfirmEmailRequest>,
+) -> AppResult<impl IntoResponse> {
+    state.account_email.confirm_email(&request.token).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "Email verified"
+    })))
+}
+
+/// Handler for POST /api/auth/password-reset/request - mails a password reset link
+///
+/// Responds the same whether or not an account has the email.
+///
+/// # Errors
+/// Returns `AppError::ValidationError` if the email is malformed, or an error if
+/// the email cannot be sent
+#[tracing::instrument(skip(state, request), err(Debug))]
+pub async fn request_password_reset_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<PasswordResetRequest>,
+) -> AppResult<impl IntoResponse> {
+    request.validate().map_err(|e| validation_error(&e))?;
+
+    let user = match state.user.find_by_email(&request.email).await {
+        Ok(user) => Some(user),
+        Err(AppError::UserNotFound) => None,
+        Err(e) => return Err(e),
+    };
+    state
+        .account_email
+        .send_password_reset(user.as_ref())
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "If an account uses this email, a reset link was sent to it"
+    })))
+}
+
+/// Handler for POST /api/auth/password-reset/confirm - sets a new password with
+/// a mailed token
+///
+/// All of the user's sessions are revoked, so a stolen session does not outlive
+/// the reset.
+///
+/// # Errors
+/// Returns `AppError::ValidationError` if the password is too short, or
+/// `AppError::BadRequest` if the token is invalid, expired or was used
+#[tracing::instrument(skip(state, request), err(Debug))]
+pub async fn confirm_password_reset_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ConfirmPasswordResetRequest>,
+) -> AppResult<impl IntoResponse> {
+    request.validate().map_err(|e| validation_error(&e))?;
+
+    let user_id = state
+        .account_email
+        .reset_password(&request.token, &request.password)
+        .await?;
+    state.session.revoke_all_sessions(user_id, None).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "Password reset, please sign in again"
+    })))
+}
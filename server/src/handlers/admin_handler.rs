@@ -0,0 +1,221 @@
+// kanbain/server/src/handlers/admin_handler.rs
+
+//! Administrative HTTP handlers
+//!
+//! Endpoints for managing user roles and which roles require two-factor
+//! authentication, which require the `manage_roles` permission, and users' AI
+//! quotas, which are for admins only.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    core::AppState,
+    errors::{AppError, AppResult},
+    middleware::{AdminOnly, CanManageRoles, RequirePermission, quota_middleware::resolve_plan},
+    models::{
+        mfa::MfaPolicy,
+        role::{GrantRoleRequest, Role},
+    },
+    services::quota_service::QuotaLimits,
+};
+
+fn parse_user_id(user_id: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(user_id).map_err(|_| AppError::BadRequest("Invalid user ID".to_string()))
+}
+
+/// Handler for GET /api/admin/users/{id}/roles - lists a user's role assignments
+///
+/// # Errors
+/// Returns an error if the user ID is invalid or the roles cannot be loaded
+pub async fn list_user_roles_handler(
+    State(state): State<Arc<AppState>>,
+    _auth: RequirePermission<CanManageRoles>,
+    Path(user_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&user_id)?;
+    let roles = state.role.list_assignments(user_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "user_id": user_id,
+        "roles": roles,
+    })))
+}
+
+/// Handler for POST /api/admin/users/{id}/roles - grants a role to a user
+///
+/// Returns 201 when the role is newly granted and 200 if the user already held it.
+/// The change is reflected in the user's token the next time they sign in.
+///
+/// # Errors
+/// Returns an error if the user ID is invalid, the user does not exist or the
+/// role cannot be stored
+pub async fn grant_role_handler(
+    State(state): State<Arc<AppState>>,
+    auth: RequirePermission<CanManageRoles>,
+    Path(user_id): Path<String>,
+    Json(request): Json<GrantRoleRequest>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&user_id)?;
+    let granted_by = auth.user.user_id.to_string();
+
+    let granted = state
+        .role
+        .grant_role(user_id, request.role, Some(&granted_by))
+        .await?;
+
+    let status = if granted {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+
+    Ok((
+        status,
+        Json(serde_json::json!({
+            "user_id": user_id,
+            "role": request.role,
+            "granted": granted,
+        })),
+    ))
+}
+
+/// Handler for DELETE /api/admin/users/{id}/roles/{role} - revokes a role from a user
+///
+/// Revocation takes effect immediately, including for tokens already issued.
+/// Admins cannot revoke their own admin role, so there is always a way back in.
+///
+/// # Errors
+/// Returns an error if the user ID or role is invalid, the user does not hold
+/// the role, or the database operation fails
+pub async fn revoke_role_handler(
+    State(state): State<Arc<AppState>>,
+    auth: RequirePermission<CanManageRoles>,
+    Path((user_id, role)): Path<(String, String)>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&user_id)?;
+    let role: Role = role.parse().map_err(AppError::BadRequest)?;
+
+    if role == Role::Admin && user_id == auth.user.user_id {
+        return Err(AppError::BadRequest(
+            "Admins cannot revoke their own admin role".to_string(),
+        ));
+    }
+
+    if !state.role.revoke_role(user_id, role).await? {
+        return Err(AppError::NotFound(format!(
+            "User does not have the '{role}' role"
+        )));
+    }
+
+    Ok(Json(serde_json::json!({
+        "user_id": user_id,
+        "role": role,
+        "revoked": true,
+    })))
+}
+
+/// Handler for GET /api/admin/mfa-policy - lists the roles that require
+/// two-factor authentication
+///
+/// # Errors
+/// Returns an error if the policy cannot be loaded
+pub async fn get_mfa_policy_handler(
+    State(state): State<Arc<AppState>>,
+    _auth: RequirePermission<CanManageRoles>,
+) -> AppResult<impl IntoResponse> {
+    Ok(Json(MfaPolicy {
+        required_roles: state.mfa.required_roles().await?,
+    }))
+}
+
+/// Handler for PUT /api/admin/mfa-policy - sets the roles that require
+/// two-factor authentication
+///
+/// Holders of those roles cannot use them until they enable two-factor
+/// authentication, and cannot disable it while they hold them. This includes
+/// the admin setting the policy.
+///
+/// # Errors
+/// Returns an error if the policy cannot be stored
+pub async fn set_mfa_policy_handler(
+    State(state): State<Arc<AppState>>,
+    auth: RequirePermission<CanManageRoles>,
+    Json(policy): Json<MfaPolicy>,
+) -> AppResult<impl IntoResponse> {
+    state
+        .mfa
+        .set_required_roles(&policy.required_roles, auth.user.user_id)
+        .await?;
+
+    Ok(Json(MfaPolicy {
+        required_roles: state.mfa.required_roles().await?,
+    }))
+}
+
+/// A user's own AI quota limits, if any, and their quota status
+async fn user_quota(state: &Arc<AppState>, user_id: Uuid) -> AppResult<serde_json::Value> {
+    let plan = resolve_plan(state, user_id).await?;
+    let user_id = user_id.to_string();
+    let limits = state.quotas.get_user_limits(&user_id).await?;
+    let status = state.quotas.status(&user_id, plan).await?;
+
+    Ok(serde_json::json!({
+        "user_id": user_id,
+        "limits": limits,
+        "status": status,
+    }))
+}
+
+/// Handler for GET /api/admin/users/{id}/ai-quota - shows a user's AI quota
+///
+/// # Errors
+/// Returns an error if the user ID is invalid, the user does not exist or the
+/// quota cannot be loaded
+pub async fn get_user_quota_handler(
+    State(state): State<Arc<AppState>>,
+    _admin: AdminOnly,
+    Path(user_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&user_id)?;
+    Ok(Json(user_quota(&state, user_id).await?))
+}
+
+/// Handler for PUT /api/admin/users/{id}/ai-quota - sets a user's own AI quota limits
+///
+/// Limits left out are the user's plan's, so an empty body puts the user back
+/// on their plan's limits.
+///
+/// # Errors
+/// Returns an error if the user ID is invalid, the user does not exist, a
+/// limit is negative or the limits cannot be stored
+pub async fn set_user_quota_handler(
+    State(state): State<Arc<AppState>>,
+    admin: AdminOnly,
+    Path(user_id): Path<String>,
+    Json(limits): Json<QuotaLimits>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = parse_user_id(&user_id)?;
+    state.user.find_by_id(user_id).await?;
+
+    state
+        .quotas
+        .set_user_limits(
+            &user_id.to_string(),
+            &limits,
+            &admin.user.user_id.to_string(),
+        )
+        .await?;
+    tracing::info!(
+        "Admin {} set the AI quota limits of user {user_id}",
+        admin.user.user_id
+    );
+
+    Ok(Json(user_quota(&state, user_id).await?))
+}
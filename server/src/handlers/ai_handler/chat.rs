@@ -1,19 +1,30 @@
 //! Chat-related handlers
 
 use axum::{Json, extract::State};
-use axum_extra::TypedHeader;
-use axum_extra::headers::Authorization;
-use axum_extra::headers::authorization::Bearer;
 use serde::{Deserialize, Serialize};
+use std::fmt::Write;
 use std::sync::Arc;
 
-use crate::ai::{ChatMessage, ChatRequest as AiChatRequest, ChatRole};
+use crate::ai::{AiError, ChatMessage, ChatRequest as AiChatRequest, ChatRole};
 use crate::core::AppState;
 use crate::errors::{AppError, AppResult};
-use crate::services::AuthService;
+use crate::middleware::{JwtAuth, check_ai_quota};
+use crate::models::{Citation, CreateMessageRequest, RetrievedChunk};
+use crate::services::ai_data_service::UsageRecord;
+use crate::services::context_service::{ContextRequest, DEFAULT_REPLY_TOKENS};
+
+/// Characters of a source shown in its citation
+const CITATION_EXCERPT_CHARS: usize = 200;
 
 #[derive(Debug, Deserialize)]
 pub struct ChatRequest {
+    /// Conversation to continue; `messages` then holds only the new turns, and
+    /// a new conversation is started when omitted
+    pub conversation_id: Option<String>,
+    /// Message of the conversation the new turns follow, forking a branch if
+    /// it already has replies; defaults to the latest message. With no new
+    /// turns, the reply to this message is regenerated.
+    pub parent_message_id: Option<String>,
     pub messages: Vec<MessageInput>,
     pub stream: Option<bool>,
     pub model: Option<String>,
@@ -22,6 +33,11 @@ pub struct ChatRequest {
     pub context: Option<Vec<String>>,
     pub use_schema: Option<String>,
     pub template: Option<String>,
+    /// Uploaded documents to answer from; their chunks most relevant to the
+    /// last user message are added to the prompt
+    pub document_ids: Option<Vec<String>>,
+    /// Number of chunks to retrieve from the documents
+    pub top_k: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -34,8 +50,13 @@ pub struct MessageInput {
 pub struct ChatResponse {
     pub id: String,
     pub conversation_id: String,
+    /// Stored reply, which later turns can follow
+    pub message_id: String,
     pub message: MessageOutput,
     pub usage: Option<crate::ai::models::TokenUsage>,
+    /// Document chunks the answer was based on
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub citations: Vec<Citation>,
 }
 
 #[derive(Debug, Serialize)]
@@ -44,24 +65,15 @@ pub struct MessageOutput {
     pub content: String,
 }
 
-/// Estimate token count for a text string (rough approximation)
-/// In production, use a proper tokenizer like tiktoken
-fn estimate_token_count(text: &str) -> i64 {
-    // Rough estimation: 1 token ≈ 4 characters for English text
-    // This is a very rough approximation; real tokenizers are more accurate
-    let char_count = text.chars().count();
-    // Use div_ceil for proper ceiling division
-    i64::try_from(char_count.div_ceil(4)).unwrap_or(0)
-}
-
 /// Handle non-streaming chat requests with full context and database persistence
 ///
 /// # Errors
 ///
-/// Returns an error if the AI request fails or authentication is invalid.
+/// Returns an error if the AI request fails, authentication is invalid or the
+/// user's AI quota is used up.
 pub async fn chat_handler(
     State(state): State<Arc<AppState>>,
-    auth_header: Option<TypedHeader<Authorization<Bearer>>>,
+    auth: JwtAuth,
     Json(request): Json<ChatRequest>,
 ) -> AppResult<Json<ChatResponse>> {
     // Check if streaming is requested - redirect to streaming handler
@@ -71,47 +83,174 @@ pub async fn chat_handler(
         ));
     }
 
-    // Extract user info from auth
-    let user_id = extract_user_id_from_auth(auth_header, &state.auth)?;
+    let user_id = auth.user.user_id.to_string();
+    check_ai_quota(&state, &user_id).await?;
+
+    // Retrieve the relevant parts of any referenced documents
+    let sources = retrieve_sources(&state, &request, &user_id).await?;
+
+    // Continue the conversation or start a new one
+    let (conversation_id, model) = match &request.conversation_id {
+        Some(conversation_id) => {
+            let conversation = state
+                .ai_data
+                .get_conversation(conversation_id, &user_id)
+                .await?;
+            (conversation.id, conversation.model)
+        }
+        None => create_conversation(&state, &user_id, &request).await?,
+    };
 
-    // Set up conversation
-    let (conversation_id, model) = create_conversation(&state, &user_id, &request).await?;
+    // Save the new turns, keeping system messages to send with every prompt
+    let (mut pinned, last_id) =
+        process_and_save_messages(&state, &request, &conversation_id, &user_id, &model).await?;
+    let parent_id = reply_parent(
+        &state,
+        &conversation_id,
+        last_id,
+        request.parent_message_id.as_deref(),
+    )
+    .await?;
+    if !sources.is_empty() {
+        pinned.insert(
+            0,
+            ChatMessage {
+                role: ChatRole::System,
+                content: sources_prompt(&sources),
+                function_call: None,
+            },
+        );
+    }
 
-    // Process messages and save to database
-    let messages = process_and_save_messages(&state, &request, &conversation_id, &user_id).await?;
+    // Fit the stored history into the model's context window
+    let messages = build_context(
+        &state,
+        &request,
+        pinned,
+        (&conversation_id, &parent_id),
+        &user_id,
+        &model,
+    )
+    .await?;
 
     // Get AI response
-    let response = get_ai_response(&state, messages, &request, &model).await?;
+    let response = get_ai_response(
+        &state,
+        messages,
+        &request,
+        (&user_id, &conversation_id),
+        &model,
+    )
+    .await?;
 
     // Save AI response and record usage
-    save_response_and_usage(&state, &response, &conversation_id, &user_id, &model).await?;
+    let message_id =
+        save_response_and_usage(&state, &response, (&conversation_id, &parent_id), &user_id)
+            .await?;
 
     // Convert to API response
-    Ok(Json(convert_to_chat_response(response, conversation_id)))
+    let mut chat_response = convert_to_chat_response(response, conversation_id);
+    chat_response.message_id = message_id;
+    chat_response.citations = citations(&chat_response.message.content, &sources);
+    Ok(Json(chat_response))
 }
 
-/// Extract user ID from authorization header
-fn extract_user_id_from_auth(
-    auth_header: Option<TypedHeader<Authorization<Bearer>>>,
-    auth_service: &Arc<AuthService>,
-) -> AppResult<String> {
-    let auth = auth_header
-        .ok_or_else(|| AppError::Unauthorized("Missing authorization header".to_string()))?;
+/// Retrieve the chunks of the request's documents most relevant to its last
+/// user message
+async fn retrieve_sources(
+    state: &Arc<AppState>,
+    request: &ChatRequest,
+    user_id: &str,
+) -> AppResult<Vec<RetrievedChunk>> {
+    let Some(document_ids) = request.document_ids.as_ref().filter(|ids| !ids.is_empty()) else {
+        return Ok(Vec::new());
+    };
+    let question = request
+        .messages
+        .iter()
+        .rev()
+        .find(|message| message.role == "user")
+        .map_or("", |message| message.content.as_str());
+
+    let ai_service = state.ai.read().await;
+    state
+        .documents
+        .retrieve(
+            &ai_service,
+            &state.embeddings,
+            user_id,
+            document_ids,
+            question,
+            request.top_k,
+        )
+        .await
+}
+
+/// System message presenting retrieved chunks as numbered sources
+fn sources_prompt(sources: &[RetrievedChunk]) -> String {
+    let mut prompt = String::from(
+        "Answer using the numbered sources below where they are relevant, and cite each \
+         source you use as [n]. If the sources do not contain the answer, say so.",
+    );
+    for (i, source) in sources.iter().enumerate() {
+        let _ = write!(
+            prompt,
+            "\n\n[{}] {} (part {})\n{}",
+            i + 1,
+            source.document_name,
+            source.chunk_index + 1,
+            source.content
+        );
+    }
+    prompt
+}
 
-    let token = auth.0.token();
+/// Citations for the sources an answer refers to as `[n]`, or for all of them
+/// if it refers to none
+fn citations(answer: &str, sources: &[RetrievedChunk]) -> Vec<Citation> {
+    let cited: Vec<usize> = (1..=sources.len())
+        .filter(|n| answer.contains(&format!("[{n}]")))
+        .collect();
+    let indexes = if cited.is_empty() {
+        (1..=sources.len()).collect()
+    } else {
+        cited
+    };
 
-    // Verify JWT token and extract user ID
-    let user_id = auth_service.get_user_id_from_token(token)?;
-    Ok(user_id.to_string())
+    indexes
+        .into_iter()
+        .map(|index| {
+            let source = &sources[index - 1];
+            let mut excerpt: String = source
+                .content
+                .chars()
+                .take(CITATION_EXCERPT_CHARS)
+                .collect();
+            if excerpt.len() < source.content.len() {
+                excerpt.push('…');
+            }
+            Citation {
+                index,
+                document_id: source.document_id.clone(),
+                document_name: source.document_name.clone(),
+                chunk_index: source.chunk_index,
+                excerpt,
+            }
+        })
+        .collect()
 }
 
-/// Create a new conversation in the database
+/// Create a new conversation in the database, using the requested model or
+/// else the default provider's
 async fn create_conversation(
     state: &Arc<AppState>,
     user_id: &str,
     request: &ChatRequest,
 ) -> AppResult<(String, String)> {
-    let model = request.model.as_deref().unwrap_or("gpt-4").to_string();
+    let model = match &request.model {
+        Some(model) => model.clone(),
+        None => state.ai.read().await.provider().model().to_string(),
+    };
 
     let create_request = crate::models::ai_models::CreateConversationRequest {
         title: Some("Chat Conversation".to_string()),
@@ -128,62 +267,149 @@ async fn create_conversation(
     Ok((conversation_response.id, model))
 }
 
-/// Process request messages and save user messages to database
+/// Save request messages to the conversation with their token counts, each
+/// following the one before
+///
+/// System messages are not part of the history; they are returned to be sent
+/// ahead of it, along with the last message saved, if any.
 async fn process_and_save_messages(
     state: &Arc<AppState>,
     request: &ChatRequest,
     conversation_id: &str,
     user_id: &str,
-) -> AppResult<Vec<ChatMessage>> {
-    let mut messages = Vec::new();
+    model: &str,
+) -> AppResult<(Vec<ChatMessage>, Option<String>)> {
+    let tokenizer = state.ai.read().await.tokenizer(Some(model));
+    let mut system_messages = Vec::new();
+    let mut parent_id = request.parent_message_id.clone();
+    let mut last_id = None;
 
     for msg in &request.messages {
         let role = match msg.role.as_str() {
-            "system" => ChatRole::System,
-            "assistant" => ChatRole::Assistant,
-            _ => ChatRole::User,
+            "system" => {
+                system_messages.push(ChatMessage {
+                    role: ChatRole::System,
+                    content: msg.content.clone(),
+                    function_call: None,
+                });
+                continue;
+            }
+            "assistant" => "assistant",
+            _ => "user",
         };
 
-        let chat_message = ChatMessage {
-            role: role.clone(),
+        // Save the turn to database with token count
+        let message_request = CreateMessageRequest {
+            role: role.to_string(),
             content: msg.content.clone(),
+            token_count: Some(tokenizer.count_i64(&msg.content)),
+            parent_message_id: parent_id.take(),
         };
-        messages.push(chat_message);
 
-        // Save user message to database with token count
-        if matches!(role, ChatRole::User) {
-            let token_count = estimate_token_count(&msg.content);
-            let message_request = crate::models::ai_models::CreateMessageRequest {
-                role: "user".to_string(),
-                content: msg.content.clone(),
-            };
+        let message = state
+            .ai_data
+            .add_message(conversation_id, user_id, message_request)
+            .await
+            .map_err(|e| match e {
+                AppError::NotFound(_) => e,
+                e => AppError::BadRequest(format!("Failed to save message: {e}")),
+            })?;
+        parent_id = Some(message.id.clone());
+        last_id = Some(message.id);
+    }
+
+    Ok((system_messages, last_id))
+}
 
-            // Note: In real implementation, we'd enhance CreateMessageRequest to include token_count
-            // For now, we'll create the message and then update it
-            let saved_message = state
+/// The message the reply follows: the last one saved from the request or,
+/// when regenerating, the requested parent or the latest message, which must
+/// be a user turn
+async fn reply_parent(
+    state: &Arc<AppState>,
+    conversation_id: &str,
+    last_id: Option<String>,
+    parent_id: Option<&str>,
+) -> AppResult<String> {
+    if let Some(last_id) = last_id {
+        return Ok(last_id);
+    }
+    let parent = match parent_id {
+        Some(parent_id) => Some(
+            state
                 .ai_data
-                .add_message(conversation_id, user_id, message_request)
-                .await
-                .map_err(|e| AppError::BadRequest(format!("Failed to save message: {e}")))?;
-
-            // Log the token count for now (in real implementation, store in DB)
-            tracing::info!(
-                "Message {} has estimated {} tokens",
-                saved_message.id,
-                token_count
-            );
-        }
+                .get_message(conversation_id, parent_id)
+                .await?,
+        ),
+        None => state.ai_data.latest_message(conversation_id).await?,
+    };
+    parent
+        .filter(|parent| parent.role == "user")
+        .map(|parent| parent.id)
+        .ok_or_else(|| {
+            AppError::ValidationError("There is no user message to reply to".to_string())
+        })
+}
+
+/// Build the prompt from the conversation's stored history, summarizing older
+/// turns if it does not fit in the model's context window
+async fn build_context(
+    state: &Arc<AppState>,
+    request: &ChatRequest,
+    pinned: Vec<ChatMessage>,
+    (conversation_id, message_id): (&str, &str),
+    user_id: &str,
+    model: &str,
+) -> AppResult<Vec<ChatMessage>> {
+    let ai_service = state.ai.read().await;
+    let reply_tokens = request
+        .max_tokens
+        .map_or(DEFAULT_REPLY_TOKENS, |max_tokens| {
+            usize::try_from(max_tokens).unwrap_or(usize::MAX)
+        });
+    let context = state
+        .context
+        .build_prompt(
+            &ai_service,
+            ContextRequest {
+                conversation_id,
+                message_id: Some(message_id),
+                model,
+                pinned,
+                reply_tokens,
+            },
+        )
+        .await?;
+
+    // Summarizing is billed like any other request
+    if let Some(usage) = context.summary_usage {
+        state
+            .ai_data
+            .record_usage(UsageRecord {
+                user_id,
+                model,
+                conversation_id: Some(conversation_id),
+                prompt_tokens: i64::from(usage.prompt),
+                completion_tokens: i64::from(usage.completion),
+                request_id: None,
+                duration_ms: None,
+            })
+            .await
+            .map_err(|e| AppError::BadRequest(format!("Failed to record usage: {e}")))?;
     }
 
-    Ok(messages)
+    Ok(context.messages)
 }
 
 /// Get AI response either with schema or regular chat
+///
+/// Regular chats ask for the conversation's model, which also decides the
+/// provider the request is routed to.
 async fn get_ai_response(
     state: &Arc<AppState>,
     messages: Vec<ChatMessage>,
     request: &ChatRequest,
-    _model: &str,
+    (user_id, conversation_id): (&str, &str),
+    model: &str,
 ) -> AppResult<crate::ai::ChatResponse> {
     let ai_service = state.ai.read().await;
 
@@ -205,10 +431,26 @@ async fn get_ai_response(
     }
 
     if let Some(schema_name) = &request.use_schema {
-        return ai_service
-            .chat_with_schema(messages, schema_name)
+        // The accepted attempt is recorded along with the saved response; rejected
+        // attempts were billed too, so record them here
+        let (rejected, result) = match ai_service.chat_with_schema(messages, schema_name).await {
+            Ok(mut structured) => {
+                structured.attempts.pop();
+                (structured.attempts, Ok(structured.response))
+            }
+            Err(AiError::SchemaRepairFailed { attempts, message }) => {
+                (attempts, Err(AiError::SchemaValidation(message)))
+            }
+            Err(e) => (Vec::new(), Err(e)),
+        };
+
+        state
+            .ai_data
+            .record_schema_attempts(user_id, Some(conversation_id), &rejected)
             .await
-            .map_err(|e| AppError::BadRequest(format!("Schema-based chat failed: {e}")));
+            .map_err(|e| AppError::BadRequest(format!("Failed to record usage: {e}")))?;
+
+        return result.map_err(|e| AppError::BadRequest(format!("Schema-based chat failed: {e}")));
     }
 
     // Use context and parameters if provided
@@ -218,70 +460,64 @@ async fn get_ai_response(
         let context_message = ChatMessage {
             role: ChatRole::System,
             content: format!("Additional context: {}", context.join(", ")),
+            function_call: None,
         };
         enhanced_messages.insert(0, context_message);
     }
 
-    let chat_request = AiChatRequest::new(enhanced_messages);
+    let mut chat_request = AiChatRequest::new(enhanced_messages);
+    chat_request.model = Some(model.to_string());
     ai_service
         .chat(chat_request)
         .await
         .map_err(|e| AppError::BadRequest(format!("AI request failed: {e}")))
 }
 
-/// Save AI response to database and record usage statistics
+/// Save AI response to database, following the message it replies to, and
+/// record usage statistics
+///
+/// Usage is priced under the model the provider reports having answered with,
+/// which can differ from the conversation's.
+///
+/// Returns the ID of the saved response.
 async fn save_response_and_usage(
     state: &Arc<AppState>,
     response: &crate::ai::ChatResponse,
-    conversation_id: &str,
+    (conversation_id, parent_id): (&str, &str),
     user_id: &str,
-    model: &str,
-) -> AppResult<()> {
+) -> AppResult<String> {
+    let model = response.model.as_str();
     // Save AI response to database with token count
     let ai_response_content = response
         .choices
         .first()
         .map_or(String::new(), |c| c.message.content.clone());
-    let response_token_count = estimate_token_count(&ai_response_content);
+    let token_count = state
+        .ai
+        .read()
+        .await
+        .tokenizer(Some(model))
+        .count_i64(&ai_response_content);
 
-    let message_request = crate::models::ai_models::CreateMessageRequest {
+    let message_request = CreateMessageRequest {
         role: "assistant".to_string(),
         content: ai_response_content,
+        token_count: Some(token_count),
+        parent_message_id: Some(parent_id.to_string()),
     };
 
-    let saved_response = state
+    let message = state
         .ai_data
         .add_message(conversation_id, user_id, message_request)
         .await
         .map_err(|e| AppError::BadRequest(format!("Failed to save AI response: {e}")))?;
 
-    // Log the response token count (in real implementation, store in DB)
-    tracing::info!(
-        "AI response {} has estimated {} tokens",
-        saved_response.id,
-        response_token_count
-    );
-
     // Record usage statistics
     if let Some(usage) = &response.usage {
-        // Calculate estimated cost (example: $0.03 per 1K tokens for GPT-4)
-        let total_tokens = usage.prompt + usage.completion;
-        let estimated_cost_cents = (i64::from(total_tokens) * 3) / 100; // Rough estimate
-
-        let ai_usage = crate::models::ai_models::AiUsage::new(
-            user_id.to_string(),
-            model.to_string(),
-            i64::from(usage.prompt),
-            i64::from(usage.completion),
-        )
-        .with_conversation(conversation_id.to_string())
-        .with_cost(estimated_cost_cents);
-
-        let conv_id = ai_usage.conversation_id.clone().unwrap_or_default();
-        let usage_record = crate::services::ai_data_service::UsageRecord {
+        let usage_record = UsageRecord {
             user_id,
             model,
-            conversation_id: Some(&conv_id),
+            conversation_id: Some(conversation_id),
             prompt_tokens: i64::from(usage.prompt),
             completion_tokens: i64::from(usage.completion),
             request_id: Some(&response.id),
@@ -295,7 +531,7 @@ async fn save_response_and_usage(
             .map_err(|e| AppError::BadRequest(format!("Failed to record usage: {e}")))?;
     }
 
-    Ok(())
+    Ok(message.id)
 }
 
 /// Convert internal AI response to API response format
@@ -306,6 +542,7 @@ fn convert_to_chat_response(
     ChatResponse {
         id: response.id,
         conversation_id,
+        message_id: String::new(),
         message: MessageOutput {
             role: "assistant".to_string(),
             content: response
@@ -318,6 +555,7 @@ fn convert_to_chat_response(
             completion: u.completion,
             total: u.total,
         }),
+        citations: Vec::new(),
     }
 }
 
@@ -326,49 +564,28 @@ mod tests {
     use super::*;
     use crate::ai::models::usage::TokenUsage as AiTokenUsage;
     use crate::ai::models::{ChatChoice, chat::ChatResponse as AiChatResponse};
-    use crate::test_helpers::create_test_app_state;
+    use crate::ai::providers::{MockProvider, MockReply, ProviderRegistry};
+    use crate::handlers::auth_handler::RegisterUserPayload;
+    use crate::test_helpers::{
+        create_test_app_state, create_test_services_with_provider, jwt_auth,
+    };
     use sqlx::SqlitePool;
 
-    #[test]
-    fn test_estimate_token_count() {
-        assert_eq!(estimate_token_count(""), 0);
-        assert_eq!(estimate_token_count("test"), 1);
-        assert_eq!(estimate_token_count("hello world"), 3);
-        assert_eq!(
-            estimate_token_count("This is a longer sentence with more tokens"),
-            11
-        );
-        assert_eq!(estimate_token_count("🦀🚀"), 1); // Unicode chars
-    }
-
-    #[sqlx::test]
-    async fn test_extract_user_id_from_auth_missing_header(pool: SqlitePool) {
-        let state = create_test_app_state(&pool);
-        let result = extract_user_id_from_auth(None, &state.auth);
-        assert!(result.is_err());
-        match result.expect_err("Expected an error but got Ok") {
-            AppError::Unauthorized(msg) => {
-                assert_eq!(msg, "Missing authorization header");
-            }
-            _ => panic!("Expected Unauthorized error"),
-        }
-    }
-
-    #[sqlx::test]
-    async fn test_extract_user_id_from_auth_invalid_token(pool: SqlitePool) {
-        let state = create_test_app_state(&pool);
-        let auth_header = TypedHeader(
-            Authorization::bearer("invalid_token").expect("Failed to create auth header"),
-        );
-        let result = extract_user_id_from_auth(Some(auth_header), &state.auth);
-        assert!(result.is_err());
-    }
-
     #[sqlx::test]
     async fn test_chat_handler_with_streaming_request(pool: SqlitePool) {
         let state = create_test_app_state(&pool);
+        let user = state
+            .user
+            .create_user(&RegisterUserPayload {
+                email: "stream@example.com".to_string(),
+                password: "test_password123".to_string(),
+            })
+            .await
+            .expect("Failed to create user");
 
         let request = ChatRequest {
+            conversation_id: None,
+            parent_message_id: None,
             messages: vec![MessageInput {
                 role: "user".to_string(),
                 content: "Hello".to_string(),
@@ -380,9 +597,11 @@ mod tests {
             context: None,
             use_schema: None,
             template: None,
+            document_ids: None,
+            top_k: None,
         };
 
-        let result = chat_handler(State(state), None, Json(request)).await;
+        let result = chat_handler(State(state), jwt_auth(&user), Json(request)).await;
 
         assert!(result.is_err());
         match result.expect_err("Expected an error but got Ok") {
@@ -397,30 +616,319 @@ mod tests {
     }
 
     #[sqlx::test]
-    async fn test_chat_handler_unauthorized(pool: SqlitePool) {
-        let state = create_test_app_state(&pool);
+    async fn test_chat_handler_returns_provider_reply(pool: SqlitePool) {
+        let provider = Arc::new(MockProvider::new());
+        provider.push_reply(MockReply::text("Scripted answer"));
+        let state = create_test_services_with_provider(&pool, provider.clone()).app_state;
+
+        let user = state
+            .user
+            .create_user(&RegisterUserPayload {
+                email: "chat@example.com".to_string(),
+                password: "test_password123".to_string(),
+            })
+            .await
+            .expect("Failed to create user");
 
         let request = ChatRequest {
+            conversation_id: None,
+            parent_message_id: None,
             messages: vec![MessageInput {
                 role: "user".to_string(),
                 content: "Hello".to_string(),
             }],
-            stream: Some(false),
+            stream: None,
             model: None,
             temperature: None,
             max_tokens: None,
             context: None,
             use_schema: None,
             template: None,
+            document_ids: None,
+            top_k: None,
         };
 
-        let result = chat_handler(State(state), None, Json(request)).await;
+        let Json(response) = chat_handler(State(state.clone()), jwt_auth(&user), Json(request))
+            .await
+            .expect("Chat should succeed");
+
+        assert_eq!(response.message.content, "Scripted answer");
+        assert!(response.usage.is_some());
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].messages[0].content, "Hello");
+        // Without a requested model the conversation uses the provider's default,
+        // and usage is recorded under the model that answered
+        assert_eq!(requests[0].model.as_deref(), Some("mock-model"));
+        let recorded: Vec<String> =
+            sqlx::query_scalar("SELECT model FROM ai_usage WHERE user_id = ?")
+                .bind(user.id.to_string())
+                .fetch_all(&pool)
+                .await
+                .expect("Failed to read usage");
+        assert_eq!(recorded, vec!["mock-model".to_string()]);
 
-        assert!(result.is_err());
-        match result.expect_err("Expected an error but got Ok") {
-            AppError::Unauthorized(_) => {}
-            _ => panic!("Expected Unauthorized error"),
+        // Both turns are stored with their token counts; without a vocabulary
+        // file tokens are estimated from characters
+        let conversation = state
+            .ai_data
+            .get_conversation_with_messages(&response.conversation_id, &user.id.to_string())
+            .await
+            .expect("Conversation should be stored");
+        let token_counts: Vec<_> = conversation
+            .messages
+            .iter()
+            .map(|message| message.token_count)
+            .collect();
+        assert_eq!(token_counts, vec![Some(2), Some(4)]);
+    }
+
+    #[sqlx::test]
+    async fn test_chat_handler_routes_by_model(pool: SqlitePool) {
+        let default = Arc::new(MockProvider::new());
+        let local = Arc::new(MockProvider::new().named("local"));
+        local.push_reply(MockReply::text("Local answer"));
+        let mut registry = ProviderRegistry::new(default.clone());
+        registry.register(local.clone()).expect("register local");
+        registry
+            .add_model_route("local/".to_string(), "local")
+            .expect("route");
+        let state = create_test_services_with_provider(&pool, Arc::new(registry)).app_state;
+
+        let user = state
+            .user
+            .create_user(&RegisterUserPayload {
+                email: "routed@example.com".to_string(),
+                password: "test_password123".to_string(),
+            })
+            .await
+            .expect("Failed to create user");
+
+        let request = ChatRequest {
+            conversation_id: None,
+            parent_message_id: None,
+            messages: vec![MessageInput {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+            }],
+            stream: None,
+            model: Some("local/llama3".to_string()),
+            temperature: None,
+            max_tokens: None,
+            context: None,
+            use_schema: None,
+            template: None,
+            document_ids: None,
+            top_k: None,
+        };
+
+        let Json(response) = chat_handler(State(state), jwt_auth(&user), Json(request))
+            .await
+            .expect("Chat should succeed");
+
+        assert_eq!(response.message.content, "Local answer");
+        assert!(default.requests().is_empty());
+        let requests = local.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].model.as_deref(), Some("local/llama3"));
+    }
+
+    #[sqlx::test]
+    async fn test_chat_handler_continues_and_branches_conversation(pool: SqlitePool) {
+        let provider = Arc::new(MockProvider::new());
+        let state = create_test_services_with_provider(&pool, provider.clone()).app_state;
+
+        let mut users = Vec::new();
+        for email in ["continue@example.com", "intruder@example.com"] {
+            let user = state
+                .user
+                .create_user(&RegisterUserPayload {
+                    email: email.to_string(),
+                    password: "test_password123".to_string(),
+                })
+                .await
+                .expect("Failed to create user");
+            users.push(user);
         }
+        let request =
+            |conversation_id: Option<&str>, parent: Option<&str>, content: &str| ChatRequest {
+                conversation_id: conversation_id.map(str::to_string),
+                parent_message_id: parent.map(str::to_string),
+                messages: if content.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![MessageInput {
+                        role: "user".to_string(),
+                        content: content.to_string(),
+                    }]
+                },
+                stream: None,
+                model: None,
+                temperature: None,
+                max_tokens: None,
+                context: None,
+                use_schema: None,
+                template: None,
+                document_ids: None,
+                top_k: None,
+            };
+        let contents = |request: &AiChatRequest| -> Vec<String> {
+            request
+                .messages
+                .iter()
+                .map(|message| message.content.clone())
+                .collect()
+        };
+
+        provider.push_reply(MockReply::text("Paris"));
+        let Json(first) = chat_handler(
+            State(state.clone()),
+            jwt_auth(&users[0]),
+            Json(request(None, None, "Capital of France?")),
+        )
+        .await
+        .expect("Chat should succeed");
+
+        // Only the new turn is sent; the history comes from the conversation
+        provider.push_reply(MockReply::text("Berlin"));
+        let Json(second) = chat_handler(
+            State(state.clone()),
+            jwt_auth(&users[0]),
+            Json(request(Some(&first.conversation_id), None, "And Germany?")),
+        )
+        .await
+        .expect("Chat should continue");
+        assert_eq!(second.conversation_id, first.conversation_id);
+        assert_eq!(
+            contents(&provider.requests()[1]),
+            vec!["Capital of France?", "Paris", "And Germany?"]
+        );
+
+        // Regenerating the first answer forks from the first question
+        let question = state
+            .ai_data
+            .get_message(&first.conversation_id, &first.message_id)
+            .await
+            .expect("Reply should be stored")
+            .parent_message_id
+            .expect("Reply should follow the question");
+        provider.push_reply(MockReply::text("Paris, France"));
+        let Json(regenerated) = chat_handler(
+            State(state.clone()),
+            jwt_auth(&users[0]),
+            Json(request(Some(&first.conversation_id), Some(&question), "")),
+        )
+        .await
+        .expect("Chat should regenerate");
+        assert_eq!(
+            contents(&provider.requests()[2]),
+            vec!["Capital of France?"]
+        );
+        let fork = state
+            .ai_data
+            .get_message(&first.conversation_id, &regenerated.message_id)
+            .await
+            .expect("Regenerated reply should be stored");
+        assert_eq!(fork.parent_message_id, Some(question));
+
+        // Continuing now follows the regenerated branch
+        provider.push_reply(MockReply::text("Berlin"));
+        let Json(fourth) = chat_handler(
+            State(state.clone()),
+            jwt_auth(&users[0]),
+            Json(request(Some(&first.conversation_id), None, "And Germany?")),
+        )
+        .await
+        .expect("Chat should continue");
+        assert_eq!(fourth.message.content, "Berlin");
+        assert_eq!(
+            contents(&provider.requests()[3]),
+            vec!["Capital of France?", "Paris, France", "And Germany?"]
+        );
+
+        // An assistant message is not something to reply to
+        let result = chat_handler(
+            State(state.clone()),
+            jwt_auth(&users[0]),
+            Json(request(
+                Some(&first.conversation_id),
+                Some(&first.message_id),
+                "",
+            )),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+        let result = chat_handler(
+            State(state),
+            jwt_auth(&users[1]),
+            Json(request(Some(&first.conversation_id), None, "Hi")),
+        )
+        .await;
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+        assert_eq!(provider.requests().len(), 4);
+    }
+
+    #[sqlx::test]
+    async fn test_chat_handler_answers_from_documents(pool: SqlitePool) {
+        let provider = Arc::new(MockProvider::new());
+        provider.push_reply(MockReply::text("You get twenty five days [2]."));
+        let state = create_test_services_with_provider(&pool, provider.clone()).app_state;
+
+        let user = state
+            .user
+            .create_user(&RegisterUserPayload {
+                email: "chat-documents@example.com".to_string(),
+                password: "test_password123".to_string(),
+            })
+            .await
+            .expect("Failed to create user");
+
+        let user_id = user.id.to_string();
+        let handbook = "Employees receive twenty five days of paid vacation each year.";
+        let policy = "Vacation requests need a manager's approval.";
+        let mut document_ids = Vec::new();
+        for (name, text) in [("policy.txt", policy), ("handbook.txt", handbook)] {
+            let document = state
+                .documents
+                .create_document(&user_id, name, None, text.len(), text)
+                .await
+                .expect("document created");
+            document_ids.push(document.id);
+        }
+
+        let request = ChatRequest {
+            conversation_id: None,
+            parent_message_id: None,
+            messages: vec![MessageInput {
+                role: "user".to_string(),
+                content: "How many vacation days do employees receive?".to_string(),
+            }],
+            stream: None,
+            model: None,
+            temperature: None,
+            max_tokens: None,
+            context: None,
+            use_schema: None,
+            template: None,
+            document_ids: Some(document_ids.clone()),
+            top_k: Some(2),
+        };
+
+        let Json(response) = chat_handler(State(state), jwt_auth(&user), Json(request))
+            .await
+            .expect("Chat should succeed");
+
+        // The handbook matches more of the question, so it is source [1]
+        let requests = provider.requests();
+        let prompt = &requests[0].messages[0];
+        assert_eq!(prompt.role, ChatRole::System);
+        assert!(prompt.content.contains("[1] handbook.txt (part 1)"));
+        assert!(prompt.content.contains("[2] policy.txt (part 1)"));
+        assert_eq!(response.citations.len(), 1);
+        assert_eq!(response.citations[0].index, 2);
+        assert_eq!(response.citations[0].document_id, document_ids[0]);
+        assert_eq!(response.citations[0].excerpt, policy);
     }
 
     #[test]
@@ -434,6 +942,7 @@ mod tests {
                 message: ChatMessage {
                     role: ChatRole::Assistant,
                     content: "Hello, how can I help you?".to_string(),
+                    function_call: None,
                 },
                 finish_reason: Some("stop".to_string()),
             }],
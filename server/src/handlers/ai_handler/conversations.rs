@@ -1,13 +1,17 @@
 //! Conversation management handlers
 
-use axum::{Json, extract::State};
-use axum_extra::TypedHeader;
-use axum_extra::headers::Authorization;
-use axum_extra::headers::authorization::Bearer;
+use axum::{
+    Json,
+    extract::{Query, State},
+};
 use std::sync::Arc;
 
 use crate::core::AppState;
 use crate::errors::{AppError, AppResult};
+use crate::middleware::quota_middleware::resolve_plan;
+use crate::middleware::{JwtAuth, check_embedding_quota};
+use crate::models::ConversationSearchQuery;
+use crate::services::quota_service::QuotaStatus;
 
 /// Get user's conversation history
 ///
@@ -16,11 +20,9 @@ use crate::errors::{AppError, AppResult};
 /// Returns an error if database query fails or authentication is invalid.
 pub async fn get_conversations_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: JwtAuth,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify JWT token and get user
-    let token = auth.token();
-    let user_id = state.auth.get_user_id_from_token(token)?.to_string();
+    let user_id = auth.user.user_id.to_string();
 
     let conversations = state
         .ai_data
@@ -40,12 +42,10 @@ pub async fn get_conversations_handler(
 /// Returns an error if database query fails or authentication is invalid.
 pub async fn get_conversation_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: JwtAuth,
     axum::extract::Path(conversation_id): axum::extract::Path<String>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify JWT token and get user
-    let token = auth.token();
-    let user_id = state.auth.get_user_id_from_token(token)?.to_string();
+    let user_id = auth.user.user_id.to_string();
     let conversation_with_messages = state
         .ai_data
         .get_conversation_with_messages(&conversation_id, &user_id)
@@ -62,11 +62,9 @@ pub async fn get_conversation_handler(
 /// Returns an error if database query fails or authentication is invalid.
 pub async fn get_usage_stats_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: JwtAuth,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify JWT token and get user
-    let token = auth.token();
-    let user_id = state.auth.get_user_id_from_token(token)?.to_string();
+    let user_id = auth.user.user_id.to_string();
 
     let usage_stats = state
         .ai_data
@@ -77,6 +75,25 @@ pub async fn get_usage_stats_handler(
     Ok(Json(serde_json::json!(usage_stats)))
 }
 
+/// Get the user's AI quota: their plan's limits, what they used of them and
+/// what remains
+///
+/// # Errors
+///
+/// Returns an error if database query fails or authentication is invalid.
+pub async fn get_quota_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+) -> AppResult<Json<QuotaStatus>> {
+    let plan = resolve_plan(&state, auth.user.user_id).await?;
+    let status = state
+        .quotas
+        .status(&auth.user.user_id.to_string(), plan)
+        .await?;
+
+    Ok(Json(status))
+}
+
 /// Archive a conversation (soft delete)
 ///
 /// # Errors
@@ -84,16 +101,14 @@ pub async fn get_usage_stats_handler(
 /// Returns an error if the conversation is not found or database operation fails.
 pub async fn archive_conversation_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: JwtAuth,
     axum::extract::Path(conversation_id): axum::extract::Path<String>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify JWT token and get user
-    let token = auth.token();
-    let user_id = state.auth.get_user_id_from_token(token)?.to_string();
+    let user_id = auth.user.user_id.to_string();
 
     // Demonstrate usage of archive and update_timestamp methods
     let mut conversation =
-        crate::models::ai_models::AiConversation::new(user_id.to_string(), "gpt-4".to_string());
+        crate::models::ai_models::AiConversation::new(user_id.clone(), "gpt-4".to_string());
     conversation.update_timestamp();
     conversation.archive();
 
@@ -123,12 +138,10 @@ pub async fn archive_conversation_handler(
 /// Returns an error if the conversation is not found or database operation fails.
 pub async fn delete_conversation_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: JwtAuth,
     axum::extract::Path(conversation_id): axum::extract::Path<String>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify JWT token and get user
-    let token = auth.token();
-    let user_id = state.auth.get_user_id_from_token(token)?.to_string();
+    let user_id = auth.user.user_id.to_string();
 
     // For now, we'll archive the conversation instead of deleting it
     // In the future, this could be a hard delete operation
@@ -144,110 +157,27 @@ pub async fn delete_conversation_handler(
     })))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::test_helpers::create_test_app_state;
-    use sqlx::SqlitePool;
-
-    #[sqlx::test]
-    async fn test_get_conversations_handler_unauthorized(pool: SqlitePool) {
-        let state = create_test_app_state(&pool);
-
-        // Create invalid auth header
-        let auth_header = TypedHeader(
-            Authorization::bearer("invalid_token").expect("Failed to create auth header"),
-        );
-
-        let result = get_conversations_handler(State(state), auth_header).await;
-
-        assert!(result.is_err());
-        match result.expect_err("Expected error but got Ok") {
-            AppError::Unauthorized(_) => {}
-            _ => panic!("Expected Unauthorized error"),
-        }
-    }
-
-    #[sqlx::test]
-    async fn test_get_conversation_handler_unauthorized(pool: SqlitePool) {
-        let state = create_test_app_state(&pool);
-
-        let auth_header = TypedHeader(
-            Authorization::bearer("invalid_token").expect("Failed to create auth header"),
-        );
-
-        let result = get_conversation_handler(
-            State(state),
-            auth_header,
-            axum::extract::Path("conv-123".to_string()),
-        )
-        .await;
-
-        assert!(result.is_err());
-        match result.expect_err("Expected error but got Ok") {
-            AppError::Unauthorized(_) => {}
-            _ => panic!("Expected Unauthorized error"),
-        }
-    }
-
-    #[sqlx::test]
-    async fn test_get_usage_stats_handler_unauthorized(pool: SqlitePool) {
-        let state = create_test_app_state(&pool);
-
-        let auth_header = TypedHeader(
-            Authorization::bearer("invalid_token").expect("Failed to create auth header"),
-        );
-
-        let result = get_usage_stats_handler(State(state), auth_header).await;
-
-        assert!(result.is_err());
-        match result.expect_err("Expected error but got Ok") {
-            AppError::Unauthorized(_) => {}
-            _ => panic!("Expected Unauthorized error"),
-        }
-    }
-
-    #[sqlx::test]
-    async fn test_archive_conversation_handler_unauthorized(pool: SqlitePool) {
-        let state = create_test_app_state(&pool);
-
-        let auth_header = TypedHeader(
-            Authorization::bearer("invalid_token").expect("Failed to create auth header"),
-        );
-
-        let result = archive_conversation_handler(
-            State(state),
-            auth_header,
-            axum::extract::Path("conv-123".to_string()),
-        )
-        .await;
-
-        assert!(result.is_err());
-        match result.expect_err("Expected error but got Ok") {
-            AppError::Unauthorized(_) => {}
-            _ => panic!("Expected Unauthorized error"),
-        }
-    }
-
-    #[sqlx::test]
-    async fn test_delete_conversation_handler_unauthorized(pool: SqlitePool) {
-        let state = create_test_app_state(&pool);
-
-        let auth_header = TypedHeader(
-            Authorization::bearer("invalid_token").expect("Failed to create auth header"),
-        );
-
-        let result = delete_conversation_handler(
-            State(state),
-            auth_header,
-            axum::extract::Path("conv-123".to_string()),
-        )
-        .await;
-
-        assert!(result.is_err());
-        match result.expect_err("Expected error but got Ok") {
-            AppError::Unauthorized(_) => {}
-            _ => panic!("Expected Unauthorized error"),
-        }
-    }
+/// Search the user's conversations by meaning rather than exact words
+///
+/// # Errors
+///
+/// Returns an error if the query is empty, the texts cannot be embedded, the
+/// user is over their AI quota, or authentication is invalid.
+pub async fn search_conversations_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Query(query): Query<ConversationSearchQuery>,
+) -> AppResult<Json<serde_json::Value>> {
+    let user_id = auth.user.user_id.to_string();
+    check_embedding_quota(&state, &user_id).await?;
+
+    let ai_service = state.ai.read().await;
+    let conversations = state
+        .embeddings
+        .search_conversations(&ai_service, &user_id, &query.q, query.limit)
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "conversations": conversations
+    })))
 }
@@ -0,0 +1,64 @@
+//! Document handlers for retrieval-augmented chat
+//!
+//! Documents are created by uploading files to `/api/ai/upload`; chat requests
+//! refer to them by ID. Documents of other users respond with 404.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use crate::core::AppState;
+use crate::errors::AppResult;
+use crate::middleware::JwtAuth;
+
+/// Handler for GET /api/ai/documents - lists the current user's documents
+///
+/// # Errors
+/// Returns an error if the documents cannot be loaded
+pub async fn list_documents_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+) -> AppResult<impl IntoResponse> {
+    let documents = state
+        .documents
+        .list_documents(&auth.user.user_id.to_string())
+        .await?;
+    Ok(Json(documents))
+}
+
+/// Handler for GET /api/ai/documents/{id} - gets a document
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user has no such document
+pub async fn get_document_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(document_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let document = state
+        .documents
+        .get_document(&auth.user.user_id.to_string(), &document_id)
+        .await?;
+    Ok(Json(document))
+}
+
+/// Handler for DELETE /api/ai/documents/{id} - deletes a document and its chunks
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user has no such document
+#[tracing::instrument(skip(state, auth), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn delete_document_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(document_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    state
+        .documents
+        .delete_document(&auth.user.user_id.to_string(), &document_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
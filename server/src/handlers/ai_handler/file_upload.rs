@@ -1,21 +1,26 @@
 //! File upload handler for AI context
+//!
+//! Uploaded files are kept in file storage, optionally linked to a
+//! conversation, and their text is stored as documents that chat requests can
+//! refer to by ID; the text is also returned, up to a token limit, for direct
+//! use.
 
 use axum::{
     Json,
-    extract::{Multipart, State},
+    extract::{Multipart, Query, State},
 };
-use axum_extra::TypedHeader;
-use axum_extra::headers::Authorization;
-use axum_extra::headers::authorization::Bearer;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::Arc;
 
+use crate::ai::Tokenizer;
 use crate::core::AppState;
 use crate::errors::{AppError, AppResult};
+use crate::middleware::JwtAuth;
+use crate::models::{FileLinks, FileResponse};
 
 const DEFAULT_MAX_TOKENS: usize = 10_000;
-const AVG_CHARS_PER_TOKEN: usize = 4; // Rough estimate: 1 token ≈ 4 characters
+const TRUNCATION_NOTICE: &str = "\n\n[Content truncated to fit within token limit]";
 
 /// Get the maximum token limit from environment variable or use default
 fn get_max_tokens() -> usize {
@@ -27,12 +32,29 @@ fn get_max_tokens() -> usize {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileUpload {
+    /// Stored file, with a signed URL to download it; empty files are not stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    /// Stored document for retrieval; files with no text are not stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_id: Option<String>,
     pub name: String,
     pub content: String,
     pub mime_type: Option<String>,
     pub size: usize,
 }
 
+/// Query parameters for uploading files
+#[derive(Debug, Default, Deserialize)]
+pub struct UploadQuery {
+    /// Conversation the files are uploaded to
+    pub conversation_id: Option<String>,
+    /// Model whose tokens the limit is counted in; the default model if unset
+    pub model: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct RawFileUpload {
     pub name: String,
@@ -147,40 +169,60 @@ fn extract_text_content(file: &RawFileUpload) -> AppResult<String> {
     }
 }
 
-/// Estimate token count from character count
-fn estimate_tokens(text: &str) -> usize {
-    text.len() / AVG_CHARS_PER_TOKEN
-}
-
 /// Truncate text to fit within token limit
-fn truncate_to_token_limit(text: String, max_tokens: usize) -> String {
-    let estimated_tokens = estimate_tokens(&text);
-    if estimated_tokens <= max_tokens {
+fn truncate_to_token_limit(text: String, tokenizer: &Tokenizer, max_tokens: usize) -> String {
+    if tokenizer.count(&text) <= max_tokens {
         return text;
     }
 
-    // Calculate approximate character limit
-    let char_limit = max_tokens * AVG_CHARS_PER_TOKEN;
-    let mut truncated = text.chars().take(char_limit).collect::<String>();
-
-    // Add truncation notice
-    truncated.push_str("\n\n[Content truncated to fit within token limit]");
+    let mut truncated = tokenizer.truncate(&text, max_tokens).to_string();
+    truncated.push_str(TRUNCATION_NOTICE);
     truncated
 }
 
+/// Store an uploaded file's bytes, unless it is empty
+async fn store_raw_file(
+    state: &AppState,
+    user_id: &str,
+    raw_file: &RawFileUpload,
+    links: &FileLinks,
+) -> AppResult<Option<FileResponse>> {
+    if raw_file.data.is_empty() {
+        return Ok(None);
+    }
+    let file = state
+        .files
+        .store_file(
+            user_id,
+            &raw_file.name,
+            raw_file.mime_type.as_deref(),
+            &raw_file.data,
+            links,
+        )
+        .await?;
+    Ok(Some(state.files.with_download_url(file)))
+}
+
 /// Handle file upload for chat context
 ///
+/// Every readable file is stored as a document, even when its text no longer
+/// fits within the token limit of the response.
+///
 /// # Errors
 ///
 /// Returns an error if file upload fails or authentication is invalid.
 pub async fn upload_file_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: JwtAuth,
+    Query(query): Query<UploadQuery>,
     mut multipart: Multipart,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify JWT token and get user
-    let token = auth.token();
-    let _user_id = state.auth.get_user_id_from_token(token)?.to_string();
+    let user_id = auth.user.user_id.to_string();
+    let conversation = FileLinks {
+        conversation_id: query.conversation_id,
+        ..FileLinks::default()
+    };
+    state.files.check_links(&user_id, &conversation).await?;
 
     let mut files = Vec::new();
     let mut raw_files = Vec::new();
@@ -209,17 +251,48 @@ pub async fn upload_file_handler(
     let mut total_tokens = 0;
     let mut truncated_files = 0;
     let max_tokens = get_max_tokens();
+    let tokenizer = state.ai.read().await.tokenizer(query.model.as_deref());
+    let mut documents = Vec::new();
 
     for raw_file in &raw_files {
         let mut content = extract_text_content(raw_file)?;
-        let file_tokens = estimate_tokens(&content);
+        let mut document_id = None;
+        if !content.trim().is_empty() {
+            let document = state
+                .documents
+                .create_document(
+                    &user_id,
+                    &raw_file.name,
+                    raw_file.mime_type.as_deref(),
+                    raw_file.data.len(),
+                    &content,
+                )
+                .await?;
+            document_id = Some(document.id.clone());
+            documents.push(document);
+        }
+
+        let links = FileLinks {
+            document_id: document_id.clone(),
+            ..conversation.clone()
+        };
+        let (file_id, download_url) = store_raw_file(&state, &user_id, raw_file, &links)
+            .await?
+            .map(|file| (file.file.id, file.download_url))
+            .unzip();
+
+        // Once the limit is reached, later files are only stored
+        if total_tokens >= max_tokens {
+            continue;
+        }
+        let file_tokens = tokenizer.count(&content);
 
         // Check if adding this file would exceed the limit
         if total_tokens + file_tokens > max_tokens {
             let remaining_tokens = max_tokens.saturating_sub(total_tokens);
             if remaining_tokens > 0 {
                 // Truncate this file to fit within remaining tokens
-                content = truncate_to_token_limit(content, remaining_tokens);
+                content = truncate_to_token_limit(content, &tokenizer, remaining_tokens);
                 truncated_files += 1;
             } else {
                 // Skip this file entirely
@@ -227,27 +300,27 @@ pub async fn upload_file_handler(
             }
         }
 
-        total_tokens += estimate_tokens(&content);
+        total_tokens += tokenizer.count(&content);
         let size = raw_file.data.len();
 
         files.push(FileUpload {
+            file_id,
+            download_url,
+            document_id,
             name: raw_file.name.clone(),
             content,
             mime_type: raw_file.mime_type.clone(),
             size,
         });
-
-        // Stop processing if we've reached the token limit
-        if total_tokens >= max_tokens {
-            break;
-        }
     }
 
     let mut response = serde_json::json!({
         "files_uploaded": files.len(),
         "files": files,
+        "documents": documents,
         "total_estimated_tokens": total_tokens,
-        "max_tokens": max_tokens
+        "max_tokens": max_tokens,
+        "tokenizer": tokenizer.name()
     });
 
     if truncated_files > 0 {
@@ -291,26 +364,18 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_estimate_tokens() {
-        assert_eq!(estimate_tokens("test"), 1); // 4 chars = 1 token
-        assert_eq!(estimate_tokens("this is a test"), 3); // 14 chars ≈ 3 tokens
-        assert_eq!(estimate_tokens(""), 0);
-    }
-
     #[test]
     fn test_truncate_to_token_limit_no_truncation() {
         let text = "This is a short text".to_string();
-        let result = truncate_to_token_limit(text.clone(), 100);
+        let result = truncate_to_token_limit(text.clone(), &Tokenizer::Estimate, 100);
         assert_eq!(result, text);
     }
 
     #[test]
     fn test_truncate_to_token_limit_with_truncation() {
-        let text = "a".repeat(1000); // 1000 chars = ~250 tokens
-        let result = truncate_to_token_limit(text, 10); // 10 tokens = ~40 chars
-        assert!(result.len() < 100);
-        assert!(result.ends_with("[Content truncated to fit within token limit]"));
+        let text = "ü".repeat(1000); // 1000 chars, 2000 bytes = 250 tokens
+        let result = truncate_to_token_limit(text, &Tokenizer::Estimate, 10);
+        assert_eq!(result, format!("{}{TRUNCATION_NOTICE}", "ü".repeat(40)));
     }
 
     #[test]
@@ -390,6 +455,9 @@ mod tests {
     #[test]
     fn test_file_upload_struct() {
         let upload = FileUpload {
+            file_id: Some("file_1".to_string()),
+            download_url: None,
+            document_id: Some("doc_1".to_string()),
             name: "test.txt".to_string(),
             content: "content".to_string(),
             mime_type: Some("text/plain".to_string()),
@@ -0,0 +1,180 @@
+//! Stored file handlers
+//!
+//! Files are uploaded to `/api/ai/upload` or to an AI session's assets, and
+//! downloaded through the signed `download_url` returned with them, which
+//! needs no token. Files of other users respond with 404.
+
+use axum::{
+    Json,
+    extract::{Multipart, Path, Query, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use crate::core::AppState;
+use crate::errors::{AppError, AppResult};
+use crate::middleware::JwtAuth;
+use crate::models::{DownloadQuery, FileLinks, FileListQuery, FileResponse};
+
+/// `Content-Disposition` value that downloads a file under its name
+fn attachment(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if (c.is_ascii_graphic() && c != '"' && c != '\\') || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("attachment; filename=\"{name}\"")
+}
+
+/// Handler for GET /api/ai/files - lists the current user's files
+///
+/// `session_id` and `conversation_id` query parameters limit the list to the
+/// files uploaded to that session or conversation.
+///
+/// # Errors
+/// Returns an error if the files cannot be loaded
+pub async fn list_files_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Query(query): Query<FileListQuery>,
+) -> AppResult<impl IntoResponse> {
+    let files: Vec<FileResponse> = state
+        .files
+        .list_files(&auth.user.user_id.to_string(), &query)
+        .await?
+        .into_iter()
+        .map(|file| state.files.with_download_url(file))
+        .collect();
+    Ok(Json(files))
+}
+
+/// Handler for GET /api/ai/files/{id} - gets a file with a fresh download URL
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user has no such file
+pub async fn get_file_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(file_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let file = state
+        .files
+        .get_file(&auth.user.user_id.to_string(), &file_id)
+        .await?;
+    Ok(Json(state.files.with_download_url(file)))
+}
+
+/// Handler for DELETE /api/ai/files/{id} - deletes a file
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user has no such file
+#[tracing::instrument(skip(state, auth), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn delete_file_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(file_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    state
+        .files
+        .delete_file(&auth.user.user_id.to_string(), &file_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Handler for GET /api/ai/files/{id}/content - downloads a file through a
+/// signed URL
+///
+/// # Errors
+/// Returns `AppError::Unauthorized` if the URL's signature is wrong or it has
+/// expired, or `AppError::NotFound` if the file no longer exists
+pub async fn download_file_handler(
+    State(state): State<Arc<AppState>>,
+    Path(file_id): Path<String>,
+    Query(query): Query<DownloadQuery>,
+) -> AppResult<impl IntoResponse> {
+    let (file, data) = state
+        .files
+        .read_signed(&file_id, query.expires, &query.signature)
+        .await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, file.content_type.clone()),
+            (header::CONTENT_DISPOSITION, attachment(&file.name)),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::CACHE_CONTROL, "private, max-age=300".to_string()),
+        ],
+        data,
+    ))
+}
+
+/// Handler for POST /api/ai/sessions/{id}/assets - uploads files to a session
+///
+/// Any type of file can be uploaded. The files become the session's assets,
+/// which the persona can link to the issue draft.
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user has no such session, or
+/// `AppError::ValidationError` if a file is empty
+#[tracing::instrument(skip(state, auth, multipart), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn upload_session_assets_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(session_id): Path<String>,
+    mut multipart: Multipart,
+) -> AppResult<impl IntoResponse> {
+    let user_id = auth.user.user_id.to_string();
+    let links = FileLinks {
+        session_id: Some(session_id),
+        ..FileLinks::default()
+    };
+
+    let mut files = Vec::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Multipart error: {e}")))?
+    {
+        let name = field.file_name().unwrap_or("unknown").to_string();
+        let content_type = field.content_type().map(std::string::ToString::to_string);
+        let data = field
+            .bytes()
+            .await
+            .map_err(|e| AppError::BadRequest(format!("Failed to read file: {e}")))?;
+
+        let file = state
+            .files
+            .store_file(&user_id, &name, content_type.as_deref(), &data, &links)
+            .await?;
+        files.push(state.files.with_download_url(file));
+    }
+
+    if files.is_empty() {
+        return Err(AppError::ValidationError(
+            "No files were uploaded.".to_string(),
+        ));
+    }
+    Ok((StatusCode::CREATED, Json(files)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_escapes_name() {
+        assert_eq!(
+            attachment("report 1.pdf"),
+            "attachment; filename=\"report 1.pdf\""
+        );
+        assert_eq!(
+            attachment("a\"b\\c\r\nd.txt"),
+            "attachment; filename=\"a_b_c__d.txt\""
+        );
+    }
+}
@@ -1,14 +1,15 @@
 //! Miscellaneous AI handlers
 
 use axum::{Json, extract::State};
-use axum_extra::TypedHeader;
-use axum_extra::headers::Authorization;
-use axum_extra::headers::authorization::Bearer;
 use serde::Deserialize;
 use std::sync::Arc;
 
+use crate::ai::models::StructuredResponse;
+use crate::ai::{AiError, AiResult};
 use crate::core::AppState;
 use crate::errors::{AppError, AppResult};
+use crate::middleware::{CanManageInvites, JwtAuth, RequirePermission, check_ai_quota};
+use crate::services::ai_data_service::UsageRecord;
 
 use super::file_upload::FileUpload;
 
@@ -27,7 +28,7 @@ pub struct CodeAnalysisRequest {
     pub context: Option<String>,
 }
 
-/// Get AI service info
+/// Get AI service info, including the configured providers and the models they serve
 ///
 /// # Errors
 ///
@@ -39,7 +40,10 @@ pub async fn ai_info_handler(
 
     let info = serde_json::json!({
         "provider": ai_service.provider_name(),
+        "providers": ai_service.list_providers(),
+        "model_routes": ai_service.model_routes(),
         "schemas": ai_service.list_schemas(),
+        "templates": ai_service.list_templates(),
         "streaming_supported": true,
         "websocket_supported": true,
     });
@@ -54,12 +58,11 @@ pub async fn ai_info_handler(
 /// Returns an error if the AI request fails or authentication is invalid.
 pub async fn contextual_chat_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: JwtAuth,
     Json(request): Json<ContextualChatRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify JWT token and get user
-    let token = auth.token();
-    let _user_id = state.auth.get_user_id_from_token(token)?;
+    let user_id = auth.user.user_id.to_string();
+    check_ai_quota(&state, &user_id).await?;
 
     let ai_service = state.ai.read().await;
 
@@ -75,6 +78,21 @@ pub async fn contextual_chat_handler(
         .chat_with_template("contextual_chat", &template_data)
         .await
         .map_err(|e| AppError::BadRequest(format!("Template chat failed: {e}")))?;
+    if let Some(usage) = &response.usage {
+        state
+            .ai_data
+            .record_usage(UsageRecord {
+                conversation_id: None,
+                user_id: &user_id,
+                model: &response.model,
+                prompt_tokens: i64::from(usage.prompt),
+                completion_tokens: i64::from(usage.completion),
+                request_id: Some(&response.id),
+                duration_ms: None,
+            })
+            .await
+            .map_err(|e| AppError::BadRequest(format!("Failed to record usage: {e}")))?;
+    }
 
     // If schema is requested, validate the response
     if let Some(schema_name) = &request.use_schema {
@@ -108,27 +126,29 @@ pub async fn contextual_chat_handler(
 /// Returns an error if the AI request fails or authentication is invalid.
 pub async fn code_analysis_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: JwtAuth,
     Json(request): Json<CodeAnalysisRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify JWT token and get user
-    let token = auth.token();
-    let _user_id = state.auth.get_user_id_from_token(token)?.to_string();
+    let user_id = auth.user.user_id.to_string();
+    check_ai_quota(&state, &user_id).await?;
 
     let ai_service = state.ai.read().await;
 
     // Use the dedicated analyze_code method
-    let analysis_result = ai_service
+    let result = ai_service
         .analyze_code(
             &request.code,
             Some(&request.language),
             request.context.as_deref(),
         )
-        .await
-        .map_err(|e| AppError::BadRequest(format!("Code analysis failed: {e}")))?;
+        .await;
+    record_structured_usage(&state, &user_id, &result).await?;
+    let analysis =
+        result.map_err(|e| AppError::BadRequest(format!("Code analysis failed: {e}")))?;
 
     Ok(Json(serde_json::json!({
-        "analysis": analysis_result,
+        "analysis": analysis.value,
+        "attempts": analysis.attempts.len(),
         "language": request.language,
         "timestamp": chrono::Utc::now()
     })))
@@ -164,48 +184,55 @@ pub async fn health_check_handler(
 /// Returns an error if moderation fails or authentication is invalid.
 pub async fn moderate_content_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: JwtAuth,
     Json(request): Json<serde_json::Value>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify JWT token and get user
-    let token = auth.token();
-    let _user_id = state.auth.get_user_id_from_token(token)?.to_string();
+    let user_id = auth.user.user_id.to_string();
 
     let content = request
         .get("content")
         .and_then(|v| v.as_str())
         .ok_or_else(|| AppError::BadRequest("Missing content field".to_string()))?;
+    check_ai_quota(&state, &user_id).await?;
 
     let ai_service = state.ai.read().await;
 
-    let moderation_result = ai_service
-        .moderate_content(content)
-        .await
-        .map_err(|e| AppError::BadRequest(format!("Moderation failed: {e}")))?;
+    let result = ai_service.moderate_content(content).await;
+    record_structured_usage(&state, &user_id, &result).await?;
+    let moderation = result.map_err(|e| AppError::BadRequest(format!("Moderation failed: {e}")))?;
 
-    Ok(Json(serde_json::json!(moderation_result)))
+    Ok(Json(moderation.value))
+}
+
+/// Record usage for every attempt behind a structured response, including the
+/// rejected attempts of a request that never produced valid output
+async fn record_structured_usage(
+    state: &AppState,
+    user_id: &str,
+    result: &AiResult<StructuredResponse>,
+) -> AppResult<()> {
+    let attempts = match result {
+        Ok(structured) => &structured.attempts,
+        Err(AiError::SchemaRepairFailed { attempts, .. }) => attempts,
+        Err(_) => return Ok(()),
+    };
+
+    state
+        .ai_data
+        .record_schema_attempts(user_id, None, attempts)
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Failed to record usage: {e}")))
 }
 
 /// Verify JWT token endpoint - demonstrates JWT token validation
 ///
 /// # Errors
 ///
-/// Returns an error if the token is invalid or expired.
-pub async fn verify_token_handler(
-    State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
-) -> AppResult<Json<serde_json::Value>> {
-    let token = auth.token();
-
-    // Use the auth service to verify the token and get user ID
-    let user_id = state
-        .auth
-        .get_user_id_from_token(token)
-        .map_err(|e| AppError::Unauthorized(format!("Token verification failed: {e}")))?;
-
+/// Returns an error if the token is invalid or expired, or its session was revoked.
+pub async fn verify_token_handler(auth: JwtAuth) -> AppResult<Json<serde_json::Value>> {
     Ok(Json(serde_json::json!({
         "valid": true,
-        "user_id": user_id,
+        "user_id": auth.user.user_id,
         "verified_at": chrono::Utc::now()
     })))
 }
@@ -249,7 +276,7 @@ pub async fn error_demo_handler(
             Err(AppError::BadRequest(format!("AI Error: {ai_error}")))
         }
         "ai_rate_limit" => {
-            let ai_error = crate::ai::AiError::RateLimitExceeded;
+            let ai_error = crate::ai::AiError::RateLimitExceeded { retry_after: None };
             Err(AppError::BadRequest(format!("AI Error: {ai_error}")))
         }
         "ai_unknown" => {
@@ -268,14 +295,12 @@ pub async fn error_demo_handler(
 ///
 /// # Errors
 ///
-/// Returns an error if database operation fails or authentication is invalid.
+/// Returns an error if database operation fails or the user lacks the
+/// `manage_invites` permission.
 pub async fn list_invites_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _auth: RequirePermission<CanManageInvites>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify admin access (in a real app, you'd check user permissions)
-    let _token = auth.token();
-
     let invites = state
         .invite
         .list_invites()
@@ -292,24 +317,20 @@ pub async fn list_invites_handler(
 ///
 /// # Errors
 ///
-/// Returns an error if invite creation fails or authentication is invalid.
+/// Returns an error if invite creation fails or the user lacks the
+/// `manage_invites` permission.
 pub async fn create_invite_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: RequirePermission<CanManageInvites>,
     Json(request): Json<serde_json::Value>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify admin access (in a real app, you'd check user permissions)
-    let _token = auth.token();
-
     let email = request
         .get("email")
         .and_then(|v| v.as_str())
         .ok_or_else(|| AppError::BadRequest("Missing email field".to_string()))?;
 
-    let invited_by = request
-        .get("invited_by")
-        .and_then(|v| v.as_str())
-        .map(String::from);
+    // Record the authenticated user as the inviter rather than trusting the request body
+    let invited_by = Some(auth.user.email);
 
     let invite = state
         .invite
@@ -371,15 +392,13 @@ pub async fn get_invite_handler(
 ///
 /// # Errors
 ///
-/// Returns an error if invite deletion fails or authentication is invalid.
+/// Returns an error if invite deletion fails or the user lacks the
+/// `manage_invites` permission.
 pub async fn delete_invite_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    _auth: RequirePermission<CanManageInvites>,
     axum::extract::Path(invite_id): axum::extract::Path<String>,
 ) -> AppResult<Json<serde_json::Value>> {
-    // Verify admin access (in a real app, you'd check user permissions)
-    let _token = auth.token();
-
     state
         .invite
         .delete_invite(&invite_id)
@@ -395,7 +414,11 @@ pub async fn delete_invite_handler(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_helpers::create_test_app_state;
+    use crate::ai::providers::{MockProvider, MockReply};
+    use crate::handlers::auth_handler::RegisterUserPayload;
+    use crate::test_helpers::{
+        create_test_app_state, create_test_services_with_provider, jwt_auth,
+    };
     use serde_json::json;
     use sqlx::SqlitePool;
 
@@ -424,6 +447,18 @@ mod tests {
         let value = response.0;
 
         assert!(value.get("provider").is_some());
+        let providers = value["providers"].as_array().expect("providers list");
+        assert!(!providers.is_empty());
+        assert!(
+            providers
+                .iter()
+                .any(|p| p["name"] == value["provider"] && p["default"] == json!(true))
+        );
+        assert!(
+            providers
+                .iter()
+                .all(|p| p["models"].as_array().is_some_and(|m| !m.is_empty()))
+        );
         assert!(value.get("schemas").is_some());
         assert_eq!(value.get("streaming_supported"), Some(&json!(true)));
         assert_eq!(value.get("websocket_supported"), Some(&json!(true)));
@@ -581,11 +616,46 @@ mod tests {
         assert!(value.get("message").is_some());
     }
 
+    #[tokio::test]
+    async fn test_moderate_content_handler_records_every_attempt() {
+        let pool = setup_test_db().await;
+        let provider = Arc::new(MockProvider::new());
+        provider.push_reply(MockReply::text("{\"safe\": true}"));
+        provider.push_reply(MockReply::text(
+            r#"{"safe": true, "issues": [], "severity": "none", "recommendation": "allow"}"#,
+        ));
+        let state = create_test_services_with_provider(&pool, provider).app_state;
+
+        let user = state
+            .user
+            .create_user(&RegisterUserPayload {
+                email: "moderator@example.com".to_string(),
+                password: "test_password123".to_string(),
+            })
+            .await
+            .expect("Failed to create user");
+
+        let Json(verdict) = moderate_content_handler(
+            State(state.clone()),
+            jwt_auth(&user),
+            Json(json!({ "content": "A friendly comment" })),
+        )
+        .await
+        .expect("Moderation should succeed");
+        assert_eq!(verdict["recommendation"], "allow");
+
+        let recorded: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ai_usage WHERE user_id = ?")
+            .bind(user.id.to_string())
+            .fetch_one(&pool)
+            .await
+            .expect("Failed to count usage");
+        assert_eq!(recorded, 2);
+    }
+
     // Note: The following tests require authentication and would need to be integration tests
     // with proper JWT token setup to test the authenticated endpoints:
     // - contextual_chat_handler
     // - code_analysis_handler
-    // - moderate_content_handler
     // - verify_token_handler
     // - list_invites_handler
     // - create_invite_handler
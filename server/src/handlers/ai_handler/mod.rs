@@ -2,21 +2,34 @@
 
 pub mod chat;
 pub mod conversations;
+pub mod documents;
 pub mod file_upload;
+pub mod files;
 pub mod misc;
+pub mod sessions;
 pub mod streaming;
 
 // Re-export all public handlers
 pub use chat::chat_handler;
 pub use conversations::{
     archive_conversation_handler, delete_conversation_handler, get_conversation_handler,
-    get_conversations_handler, get_usage_stats_handler,
+    get_conversations_handler, get_quota_handler, get_usage_stats_handler,
+    search_conversations_handler,
 };
+pub use documents::{delete_document_handler, get_document_handler, list_documents_handler};
 pub use file_upload::upload_file_handler;
+pub use files::{
+    delete_file_handler, download_file_handler, get_file_handler, list_files_handler,
+    upload_session_assets_handler,
+};
 pub use misc::{
     ai_info_handler, code_analysis_handler, contextual_chat_handler, demo_message_handler,
     error_demo_handler, health_check_handler, moderate_content_handler, verify_token_handler,
 };
+pub use sessions::{
+    create_session_handler, finalize_session_handler, get_session_handler, preview_session_handler,
+    send_session_message_handler,
+};
 pub use streaming::chat_stream_handler;
 
 // Re-export handlers that belong elsewhere
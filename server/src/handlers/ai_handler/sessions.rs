@@ -0,0 +1,179 @@
+//! Conversational issue-creation session handlers
+//!
+//! A session is a conversation with an AI persona that builds up an issue
+//! draft. The client creates a session, sends messages, previews the draft and
+//! finally completes or cancels the session.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::ai::ChatResponse;
+use crate::core::AppState;
+use crate::errors::{AppError, AppResult};
+use crate::middleware::{JwtAuth, check_ai_quota, check_embedding_quota};
+use crate::models::{CreateSessionRequest, FinalizeSessionRequest, SendMessageRequest};
+use crate::services::ai_data_service::UsageRecord;
+
+/// Record the usage of a persona reply, which may span several function-calling rounds
+async fn record_session_usage(
+    state: &AppState,
+    user_id: &str,
+    response: &ChatResponse,
+    started: Instant,
+) -> AppResult<()> {
+    let Some(usage) = &response.usage else {
+        return Ok(());
+    };
+
+    state
+        .ai_data
+        .record_usage(UsageRecord {
+            conversation_id: None,
+            user_id,
+            model: &response.model,
+            prompt_tokens: i64::from(usage.prompt),
+            completion_tokens: i64::from(usage.completion),
+            request_id: Some(&response.id),
+            duration_ms: Some(i64::try_from(started.elapsed().as_millis()).unwrap_or(i64::MAX)),
+        })
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Failed to record usage: {e}")))
+}
+
+/// Handler for POST /api/ai/sessions - starts a session with a persona
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user is not a member of the board,
+/// `AppError::ValidationError` if the initial input is too long,
+/// `AppError::TooManyRequests` if the user's AI quota is used up, or
+/// `AppError::BadRequest` if the AI request fails
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn create_session_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Json(request): Json<CreateSessionRequest>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = auth.user.user_id.to_string();
+    // Only an initial input is sent to the persona
+    if request.initial_input.is_some() {
+        check_ai_quota(&state, &user_id).await?;
+    }
+    let started = Instant::now();
+
+    let (session, response) = {
+        let ai_service = state.ai.read().await;
+        state
+            .ai_session
+            .create_session(&ai_service, &user_id, &request)
+            .await?
+    };
+    if let Some(response) = &response {
+        record_session_usage(&state, &user_id, response, started).await?;
+    }
+
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+/// Handler for GET /api/ai/sessions/{id} - gets a session and its messages
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user has no such session
+pub async fn get_session_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(session_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let session = state
+        .ai_session
+        .get_session(&auth.user.user_id.to_string(), &session_id)
+        .await?;
+    Ok(Json(session))
+}
+
+/// Handler for POST /api/ai/sessions/{id}/messages - sends a message to the persona
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user has no such session,
+/// `AppError::ValidationError` if the message is empty or too long,
+/// `AppError::TooManyRequests` if the user's AI quota is used up, or
+/// `AppError::BadRequest` if the session is no longer active or the AI request fails
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn send_session_message_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(session_id): Path<String>,
+    Json(request): Json<SendMessageRequest>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = auth.user.user_id.to_string();
+    check_ai_quota(&state, &user_id).await?;
+    let started = Instant::now();
+
+    let (reply, response) = {
+        let ai_service = state.ai.read().await;
+        state
+            .ai_session
+            .send_message(&ai_service, &user_id, &session_id, &request.message)
+            .await?
+    };
+    record_session_usage(&state, &user_id, &response, started).await?;
+
+    Ok(Json(reply))
+}
+
+/// Handler for GET /api/ai/sessions/{id}/preview - previews the issue being drafted
+///
+/// Sessions started for a board also list the board's likely duplicates,
+/// unless the user is over their AI quota.
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user has no such session
+pub async fn preview_session_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(session_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = auth.user.user_id.to_string();
+    let embeddings = match check_embedding_quota(&state, &user_id).await {
+        Ok(()) => Some(state.embeddings.as_ref()),
+        Err(AppError::TooManyRequests { .. }) => None,
+        Err(e) => return Err(e),
+    };
+
+    let ai_service = state.ai.read().await;
+    let preview = state
+        .ai_session
+        .preview(&ai_service, embeddings, &user_id, &session_id)
+        .await?;
+    Ok(Json(preview))
+}
+
+/// Handler for POST /api/ai/sessions/{id}/finalize - completes or cancels a session
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user has no such session or is not a
+/// member of the board, `AppError::ValidationError` if the draft is
+/// incomplete, or `AppError::BadRequest` if the session is no longer active
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn finalize_session_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(session_id): Path<String>,
+    Json(request): Json<FinalizeSessionRequest>,
+) -> AppResult<impl IntoResponse> {
+    let finalized = state
+        .ai_session
+        .finalize(
+            &state.issues,
+            &auth.user.user_id.to_string(),
+            &session_id,
+            &request,
+        )
+        .await?;
+    Ok(Json(finalized))
+}
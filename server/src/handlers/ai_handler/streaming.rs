@@ -4,43 +4,40 @@ use axum::{
     extract::{Query, State},
     response::{Sse, sse::Event},
 };
-use axum_extra::TypedHeader;
-use axum_extra::headers::Authorization;
-use axum_extra::headers::authorization::Bearer;
-use futures::stream::{self, Stream};
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
 use std::convert::Infallible;
 use std::sync::Arc;
 use std::time::Duration;
-use uuid::Uuid;
 
-use crate::ai::{ChatMessage, ChatRequest as AiChatRequest, ChatRole};
+use crate::ai::models::{StreamEvent, TokenUsage};
+use crate::ai::{AiResult, ChatMessage, ChatRequest as AiChatRequest, ChatRole};
 use crate::core::AppState;
 use crate::errors::AppResult;
+use crate::middleware::{JwtAuth, check_ai_quota};
+use crate::services::ai_data_service::UsageRecord;
 
 use super::chat::ChatRequest;
 
-#[derive(Debug, serde::Serialize)]
-pub struct StreamChunk {
-    pub id: String,
-    pub delta: String,
-    pub finished: bool,
-}
-
 /// Handle SSE streaming chat requests
 ///
+/// Each SSE event carries a JSON-encoded `StreamEvent` (`start`, `delta`, `done` or
+/// `error`), forwarded from the provider as soon as it arrives. The final `done`
+/// event reports the token usage of the completion, which is recorded against
+/// the user's quota.
+///
 /// # Errors
 ///
-/// Returns an error if the AI request fails or authentication is invalid.
+/// Returns an error if authentication is invalid or the user's AI quota is used
+/// up. Provider failures are reported to the client as an `error` event on the
+/// stream.
 pub async fn chat_stream_handler(
     State(state): State<Arc<AppState>>,
-    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    auth: JwtAuth,
     Query(params): Query<ChatRequest>,
 ) -> AppResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
-    // Verify JWT token and get user
-    let token = auth.token();
-    let _user_id = state.auth.get_user_id_from_token(token)?;
-
-    let chat_id = Uuid::new_v4().to_string();
+    let user_id = auth.user.user_id.to_string();
+    check_ai_quota(&state, &user_id).await?;
 
     // Convert request messages
     let messages: Vec<ChatMessage> = params
@@ -53,71 +50,51 @@ pub async fn chat_stream_handler(
                 _ => ChatRole::User,
             },
             content: msg.content,
+            function_call: None,
         })
         .collect();
 
-    // For now, simulate streaming by calling regular chat and splitting the response
-    let ai_service = state.ai.read().await;
-    let chat_request = AiChatRequest::new(messages);
-    let response = ai_service.chat(chat_request).await;
-
-    // Create a stream that sends events
-    let stream = stream::unfold(
-        (chat_id, response, 0),
-        |(chat_id, response, mut index)| async move {
-            match &response {
-                Ok(chat_response) => {
-                    let content = chat_response
-                        .choices
-                        .first()
-                        .map(|c| c.message.content.clone())
-                        .unwrap_or_default();
-
-                    // Split the response into chunks to simulate streaming
-                    let words: Vec<&str> = content.split_whitespace().collect();
-                    let chunks: Vec<String> = words
-                        .chunks(3) // Group words into chunks of 3
-                        .map(|chunk| format!("{} ", chunk.join(" ")))
-                        .collect();
-
-                    if index < chunks.len() {
-                        let chunk = StreamChunk {
-                            id: chat_id.clone(),
-                            delta: chunks[index].clone(),
-                            finished: index == chunks.len() - 1,
-                        };
-
-                        let event = Event::default()
-                            .json_data(chunk)
-                            .unwrap_or_else(|_| Event::default().data("error"));
-
-                        index += 1;
-                        tokio::time::sleep(Duration::from_millis(100)).await;
-                        Some((Ok(event), (chat_id, response, index)))
-                    } else {
-                        None
-                    }
-                }
-                Err(e) => {
-                    if index == 0 {
-                        let error_chunk = StreamChunk {
-                            id: chat_id.clone(),
-                            delta: format!("Error: {e}"),
-                            finished: true,
-                        };
-
-                        let event = Event::default()
-                            .json_data(error_chunk)
-                            .unwrap_or_else(|_| Event::default().data("error"));
-
-                        Some((Ok(event), (chat_id, response, 1)))
-                    } else {
-                        None
-                    }
+    // Only hold the service lock while starting the stream, not for its whole lifetime
+    let provider_stream = {
+        let ai_service = state.ai.read().await;
+        let mut request = AiChatRequest::new(messages);
+        request.model = params.model;
+        ai_service.chat_stream(request).await
+    };
+
+    let events = match provider_stream {
+        Ok(events) => events,
+        Err(e) => {
+            tracing::error!("Failed to start chat stream: {e}");
+            stream::once(async move { Err(e) }).boxed()
+        }
+    };
+
+    let stream = events
+        .map(into_stream_event)
+        .scan(None, |started, event| {
+            // The usage in `done` is recorded under the ID and model from `start`
+            if let StreamEvent::Start { id, model } = &event {
+                *started = Some((id.clone(), model.clone()));
+            }
+            let finished = match &event {
+                StreamEvent::Done {
+                    usage: Some(usage), ..
+                } => started.clone().map(|started| (started, usage.clone())),
+                _ => None,
+            };
+            future::ready(Some((event, finished)))
+        })
+        .then(move |(event, finished)| {
+            let state = state.clone();
+            let user_id = user_id.clone();
+            async move {
+                if let Some(((request_id, model), usage)) = finished {
+                    record_stream_usage(&state, &user_id, &request_id, &model, &usage).await;
                 }
+                Ok(to_sse_event(&event))
             }
-        },
-    );
+        });
 
     Ok(Sse::new(stream).keep_alive(
         axum::response::sse::KeepAlive::new()
@@ -126,37 +103,50 @@ pub async fn chat_stream_handler(
     ))
 }
 
+/// Record the usage of a finished stream; the reply has already been sent, so a
+/// failure is only logged
+async fn record_stream_usage(
+    state: &AppState,
+    user_id: &str,
+    request_id: &str,
+    model: &str,
+    usage: &TokenUsage,
+) {
+    let result = state
+        .ai_data
+        .record_usage(UsageRecord {
+            conversation_id: None,
+            user_id,
+            model,
+            prompt_tokens: i64::from(usage.prompt),
+            completion_tokens: i64::from(usage.completion),
+            request_id: Some(request_id),
+            duration_ms: None,
+        })
+        .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to record usage of chat stream {request_id}: {e}");
+    }
+}
+
+/// Turn a provider stream item into the event sent to the client
+fn into_stream_event(result: AiResult<StreamEvent>) -> StreamEvent {
+    result.unwrap_or_else(|e| StreamEvent::Error {
+        message: e.to_string(),
+    })
+}
+
+fn to_sse_event(event: &StreamEvent) -> Event {
+    Event::default()
+        .json_data(event)
+        .unwrap_or_else(|_| Event::default().data("error"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::handlers::ai_handler::chat::MessageInput;
 
-    #[test]
-    fn test_stream_chunk_serialization() {
-        let chunk = StreamChunk {
-            id: "test_id".to_string(),
-            delta: "Hello world".to_string(),
-            finished: false,
-        };
-
-        let json = serde_json::to_value(&chunk).expect("Should serialize stream chunk");
-        assert_eq!(json["id"], "test_id");
-        assert_eq!(json["delta"], "Hello world");
-        assert_eq!(json["finished"], false);
-    }
-
-    #[test]
-    fn test_stream_chunk_finished() {
-        let chunk = StreamChunk {
-            id: "test_id".to_string(),
-            delta: "Final chunk".to_string(),
-            finished: true,
-        };
-
-        let json = serde_json::to_value(&chunk).expect("Should serialize finished chunk");
-        assert_eq!(json["finished"], true);
-    }
-
     #[tokio::test]
     async fn test_chat_request_message_conversion() {
         // Test the conversion logic from MessageInput to ChatMessage
@@ -189,6 +179,7 @@ mod tests {
                     _ => ChatRole::User,
                 },
                 content: msg.content,
+                function_call: None,
             })
             .collect();
 
@@ -199,81 +190,35 @@ mod tests {
         assert!(matches!(converted[3].role, ChatRole::User)); // Invalid role defaults to User
     }
 
-    // Note: Testing the actual chat_stream_handler requires authentication setup
-    // and would be better suited for integration tests due to the complexity
-    // of mocking SSE streaming responses and JWT tokens.
-
-    #[tokio::test]
-    async fn test_uuid_generation() {
-        // Test that we can generate unique chat IDs
-        let id1 = Uuid::new_v4().to_string();
-        let id2 = Uuid::new_v4().to_string();
-
-        assert_ne!(id1, id2);
-        assert!(!id1.is_empty());
-        assert!(!id2.is_empty());
-    }
-
     #[test]
-    fn test_word_chunking_logic() {
-        // Test the chunking logic used in the streaming handler
-        let content = "This is a test message with multiple words";
-        let words: Vec<&str> = content.split_whitespace().collect();
-        let chunks: Vec<String> = words
-            .chunks(3) // Group words into chunks of 3
-            .map(|chunk| format!("{} ", chunk.join(" ")))
-            .collect();
-
-        assert_eq!(chunks.len(), 3); // 9 words / 3 = 3 chunks
-        assert_eq!(chunks[0], "This is a ");
-        assert_eq!(chunks[1], "test message with ");
-        assert_eq!(chunks[2], "multiple words ");
+    fn test_into_stream_event_passes_through_events() {
+        let event = into_stream_event(Ok(StreamEvent::Delta {
+            content: "Hello".to_string(),
+            index: 0,
+        }));
+        assert!(matches!(event, StreamEvent::Delta { content, .. } if content == "Hello"));
     }
 
     #[test]
-    fn test_word_chunking_edge_cases() {
-        // Test empty content
-        let content = "";
-        let words: Vec<&str> = content.split_whitespace().collect();
-        let chunks: Vec<String> = words
-            .chunks(3)
-            .map(|chunk| format!("{} ", chunk.join(" ")))
-            .collect();
-        assert_eq!(chunks.len(), 0);
-
-        // Test single word
-        let content = "Hello";
-        let words: Vec<&str> = content.split_whitespace().collect();
-        let chunks: Vec<String> = words
-            .chunks(3)
-            .map(|chunk| format!("{} ", chunk.join(" ")))
-            .collect();
-        assert_eq!(chunks.len(), 1);
-        assert_eq!(chunks[0], "Hello ");
-
-        // Test two words
-        let content = "Hello world";
-        let words: Vec<&str> = content.split_whitespace().collect();
-        let chunks: Vec<String> = words
-            .chunks(3)
-            .map(|chunk| format!("{} ", chunk.join(" ")))
-            .collect();
-        assert_eq!(chunks.len(), 1);
-        assert_eq!(chunks[0], "Hello world ");
+    fn test_into_stream_event_converts_errors() {
+        let event = into_stream_event(Err(crate::ai::AiError::Provider(
+            "upstream unavailable".to_string(),
+        )));
+        match event {
+            StreamEvent::Error { message } => assert!(message.contains("upstream unavailable")),
+            other => panic!("Expected Error event, got {other:?}"),
+        }
     }
 
     #[test]
-    fn test_stream_chunk_debug() {
-        let chunk = StreamChunk {
-            id: "debug_test".to_string(),
-            delta: "Debug content".to_string(),
-            finished: false,
+    fn test_stream_event_wire_format() {
+        let done = StreamEvent::Done {
+            finish_reason: "stop".to_string(),
+            usage: Some(crate::ai::models::TokenUsage::new(10, 5)),
         };
-
-        let debug_str = format!("{chunk:?}");
-        assert!(debug_str.contains("StreamChunk"));
-        assert!(debug_str.contains("debug_test"));
-        assert!(debug_str.contains("Debug content"));
-        assert!(debug_str.contains("false"));
+        let json = serde_json::to_value(&done).expect("Should serialize stream event");
+        assert_eq!(json["type"], "done");
+        assert_eq!(json["finish_reason"], "stop");
+        assert_eq!(json["usage"]["total"], 15);
     }
 }
@@ -1,13 +1,21 @@
 // kanbain/server/src/handlers/auth_handler.rs
 
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::{TypedHeader, headers::UserAgent};
 use serde::Deserialize;
 use std::sync::Arc;
-use validator::Validate;
+use validator::{Validate, ValidationErrors};
 
 use crate::{
-    core::{AppState, build_unified_auth_response, password_utils::verify_password},
+    core::{AppState, build_session_auth_response, password_utils::verify_password},
     errors::{AppError, AppResult},
+    models::{DeviceInfo, mfa::MfaChallengeResponse},
+    services::mfa_service::MFA_CHALLENGE_TTL_SECONDS,
 };
 
 #[derive(Debug, Deserialize, Validate, Clone)]
@@ -32,17 +40,39 @@ pub struct LoginUserPayload {
     pub password: String,
 }
 
+/// Turn validation errors into a user-friendly error for the first failing field
+pub(crate) fn validation_error(validation_errors: &ValidationErrors) -> AppError {
+    // For a production app, you might want a more structured error response.
+    let first_error = validation_errors
+        .field_errors()
+        .into_iter()
+        .next()
+        .map_or_else(
+            || "Invalid input.".to_string(),
+            |(_, errors)| {
+                errors
+                    .first()
+                    .and_then(|err| err.message.as_ref().map(std::string::ToString::to_string))
+                    .unwrap_or_else(|| "Unknown validation error".to_string())
+            },
+        );
+    AppError::ValidationError(first_error)
+}
+
 // Note: We now use UnifiedAuthResponse for all auth endpoints
 // The old UserResponse, RegisterResponse, and LoginResponse types have been replaced
 
 /// Register a new user
 ///
+/// The new account is mailed a link to verify its email address.
+///
 /// # Errors
 ///
 /// Returns an error if validation fails, user already exists, or database operation fails
 #[tracing::instrument(skip(state, payload), fields(email = %payload.email), err(Debug))]
 pub async fn register_user_handler(
     State(state): State<Arc<AppState>>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     Json(payload): Json<RegisterUserPayload>,
 ) -> AppResult<impl IntoResponse> {
     // 1. Validate the payload
@@ -51,22 +81,7 @@ pub async fn register_user_handler(
             "Validation failed for registration payload: {:?}",
             validation_errors
         );
-        // Convert validator::ValidationErrors to a user-friendly string.
-        // For a production app, you might want a more structured error response.
-        let first_error = validation_errors
-            .field_errors()
-            .into_iter()
-            .next()
-            .map_or_else(
-                || "Invalid input.".to_string(),
-                |(_, errors)| {
-                    errors
-                        .first()
-                        .and_then(|err| err.message.as_ref().map(std::string::ToString::to_string))
-                        .unwrap_or_else(|| "Unknown validation error".to_string())
-                },
-            );
-        return Err(AppError::ValidationError(first_error));
+        return Err(validation_error(&validation_errors));
     }
 
     tracing::info!(
@@ -102,27 +117,33 @@ pub async fn register_user_handler(
                 // We don't fail the registration since the user is already created
             }
 
-            // Generate JWT token for immediate login (matches OAuth behavior)
-            let token = state
-                .auth
-                .generate_token(created_user.id, &created_user.email)
+            tracing::info!(
+                "User successfully created with email: {}",
+                created_user.email
+            );
+
+            // Registration succeeds even if the email cannot be sent; the user can ask again
+            if let Err(e) = state.account_email.send_verification(&created_user).await {
+                tracing::error!(
+                    "Failed to send verification email to {}: {:?}",
+                    created_user.email,
+                    e
+                );
+            }
+
+            // Start a session for immediate login (matches OAuth behavior)
+            let device = DeviceInfo::web(user_agent.as_ref().map(|agent| agent.as_str()));
+            let response = build_session_auth_response(&state, &created_user, &device)
+                .await
                 .map_err(|e| {
                     tracing::error!(
-                        "Failed to generate token for user {}: {:?}",
+                        "Failed to start session for user {}: {:?}",
                         created_user.email,
                         e
                     );
                     e
                 })?;
 
-            tracing::info!(
-                "User successfully created with email: {}",
-                created_user.email
-            );
-
-            // Create unified auth response using shared function
-            let response = build_unified_auth_response(&state, &created_user, token).await?;
-
             Ok((StatusCode::CREATED, Json(response)))
         }
         Err(app_error) => {
@@ -136,34 +157,26 @@ pub async fn register_user_handler(
 
 /// Login user
 ///
+/// Users with two-factor authentication enabled get an `mfa_pending` token
+/// instead of a session, to exchange at `POST /api/auth/mfa/verify` along with
+/// a code.
+///
 /// # Errors
 ///
 /// Returns an error if validation fails, user not found, password incorrect, or JWT generation fails
 #[tracing::instrument(skip(state, payload), fields(email = %payload.email), err(Debug))]
 pub async fn login_user_handler(
     State(state): State<Arc<AppState>>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     Json(payload): Json<LoginUserPayload>,
-) -> AppResult<impl IntoResponse> {
+) -> AppResult<Response> {
     // 1. Validate the payload
     if let Err(validation_errors) = payload.validate() {
         tracing::warn!(
             "Validation failed for login payload: {:?}",
             validation_errors
         );
-        let first_error = validation_errors
-            .field_errors()
-            .into_iter()
-            .next()
-            .map_or_else(
-                || "Invalid input.".to_string(),
-                |(_, errors)| {
-                    errors
-                        .first()
-                        .and_then(|err| err.message.as_ref().map(std::string::ToString::to_string))
-                        .unwrap_or_else(|| "Unknown validation error".to_string())
-                },
-            );
-        return Err(AppError::ValidationError(first_error));
+        return Err(validation_error(&validation_errors));
     }
 
     tracing::info!("Login attempt for email: {}", payload.email);
@@ -188,18 +201,32 @@ pub async fn login_user_handler(
         return Err(AppError::InvalidCredentials);
     }
 
-    // 4. Generate JWT token
-    let token = state
-        .auth
-        .generate_token(user.id, &user.email)
+    // 4. Users with two-factor authentication continue at the verify endpoint
+    if state.mfa.is_enabled(user.id).await? {
+        let mfa_token = state.mfa.create_challenge(user.id).await?;
+        tracing::info!(
+            "Password accepted for {}, waiting for second factor",
+            user.email
+        );
+        return Ok((
+            StatusCode::OK,
+            Json(MfaChallengeResponse::new(
+                mfa_token,
+                MFA_CHALLENGE_TTL_SECONDS,
+            )),
+        )
+            .into_response());
+    }
+
+    // 5. Start a session and create unified auth response using shared function
+    let device = DeviceInfo::web(user_agent.as_ref().map(|agent| agent.as_str()));
+    let response = build_session_auth_response(&state, &user, &device)
+        .await
         .map_err(|e| {
-            tracing::error!("Failed to generate token for user {}: {:?}", user.email, e);
+            tracing::error!("Failed to start session for user {}: {:?}", user.email, e);
             e
         })?;
 
-    // 5. Create unified auth response using shared function
-    let response = build_unified_auth_response(&state, &user, token).await?;
-
     if response.payment_user.payment_required {
         tracing::info!(
             "User {} requires payment (no invite and no active payment)",
@@ -209,7 +236,7 @@ pub async fn login_user_handler(
 
     tracing::info!("User logged in successfully: {}", response.auth_user.email);
 
-    Ok((StatusCode::OK, Json(response)))
+    Ok((StatusCode::OK, Json(response)).into_response())
 }
 
 #[cfg(test)]
@@ -243,7 +270,7 @@ mod tests {
             password: "strongPassword123!".to_string(),
         };
 
-        let result = register_user_handler(State(state), Json(payload.clone())).await;
+        let result = register_user_handler(State(state), None, Json(payload.clone())).await;
         assert!(result.is_ok());
 
         match result {
@@ -277,7 +304,7 @@ mod tests {
             password: "strongPassword123!".to_string(),
         };
 
-        let result = register_user_handler(State(state), Json(payload)).await;
+        let result = register_user_handler(State(state), None, Json(payload)).await;
         assert!(result.is_ok());
 
         assert!(result.is_ok(), "Failed to register user with invite");
@@ -293,7 +320,7 @@ mod tests {
             password: "strongPassword123!".to_string(),
         };
 
-        let result = register_user_handler(State(state), Json(payload)).await;
+        let result = register_user_handler(State(state), None, Json(payload)).await;
         assert!(result.is_err());
 
         match result {
@@ -314,7 +341,7 @@ mod tests {
             password: "short".to_string(),
         };
 
-        let result = register_user_handler(State(state), Json(payload)).await;
+        let result = register_user_handler(State(state), None, Json(payload)).await;
         assert!(result.is_err());
 
         match result {
@@ -337,11 +364,12 @@ mod tests {
         };
 
         // Register first user
-        let result1 = register_user_handler(State(state.clone()), Json(payload.clone())).await;
+        let result1 =
+            register_user_handler(State(state.clone()), None, Json(payload.clone())).await;
         assert!(result1.is_ok());
 
         // Try to register with same email
-        let result2 = register_user_handler(State(state), Json(payload)).await;
+        let result2 = register_user_handler(State(state), None, Json(payload)).await;
         assert!(result2.is_err());
 
         match result2 {
@@ -365,7 +393,7 @@ mod tests {
             email: email.clone(),
             password: password.to_string(),
         };
-        register_user_handler(State(state.clone()), Json(register_payload))
+        register_user_handler(State(state.clone()), None, Json(register_payload))
             .await
             .expect("Failed to register user");
 
@@ -375,7 +403,7 @@ mod tests {
             password: password.to_string(),
         };
 
-        let result = login_user_handler(State(state), Json(login_payload)).await;
+        let result = login_user_handler(State(state), None, Json(login_payload)).await;
         assert!(result.is_ok());
 
         assert!(result.is_ok(), "Failed to login user");
@@ -394,7 +422,7 @@ mod tests {
             email: email.clone(),
             password: password.to_string(),
         };
-        register_user_handler(State(state.clone()), Json(register_payload))
+        register_user_handler(State(state.clone()), None, Json(register_payload))
             .await
             .expect("Failed to register user");
 
@@ -404,7 +432,7 @@ mod tests {
             password: "wrongPassword123!".to_string(),
         };
 
-        let result = login_user_handler(State(state), Json(login_payload)).await;
+        let result = login_user_handler(State(state), None, Json(login_payload)).await;
         assert!(result.is_err());
 
         match result {
@@ -423,7 +451,7 @@ mod tests {
             password: "somePassword123!".to_string(),
         };
 
-        let result = login_user_handler(State(state), Json(login_payload)).await;
+        let result = login_user_handler(State(state), None, Json(login_payload)).await;
         assert!(result.is_err());
 
         match result {
@@ -442,7 +470,7 @@ mod tests {
             password: "somePassword123!".to_string(),
         };
 
-        let result = login_user_handler(State(state), Json(login_payload)).await;
+        let result = login_user_handler(State(state), None, Json(login_payload)).await;
         assert!(result.is_err());
 
         match result {
@@ -463,7 +491,7 @@ mod tests {
             password: String::new(),
         };
 
-        let result = login_user_handler(State(state), Json(login_payload)).await;
+        let result = login_user_handler(State(state), None, Json(login_payload)).await;
         assert!(result.is_err());
 
         match result {
@@ -487,7 +515,7 @@ mod tests {
             email: email.clone(),
             password: password.to_string(),
         };
-        register_user_handler(State(state.clone()), Json(register_payload))
+        register_user_handler(State(state.clone()), None, Json(register_payload))
             .await
             .expect("Failed to register user");
 
@@ -496,7 +524,7 @@ mod tests {
             email: email.clone(),
             password: password.to_string(),
         };
-        let login_result = login_user_handler(State(state), Json(login_payload)).await;
+        let login_result = login_user_handler(State(state), None, Json(login_payload)).await;
         assert!(login_result.is_ok(), "Failed to login user");
 
         // Both register and login should succeed
@@ -0,0 +1,144 @@
+// kanbain/server/src/handlers/board_handler.rs
+
+//! Board HTTP handlers
+//!
+//! Endpoints for creating, listing and deleting boards and for managing who is
+//! a member of them. Boards the user is not a member of respond with 404.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use crate::{
+    core::AppState,
+    errors::AppResult,
+    middleware::{JwtAuth, VerifiedEmail},
+    models::{AddBoardMemberRequest, CreateBoardRequest},
+};
+
+/// Handler for POST /api/boards - creates a board owned by the current user
+///
+/// # Errors
+/// Returns `AppError::ValidationError` if the name or description is invalid
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn create_board_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Json(request): Json<CreateBoardRequest>,
+) -> AppResult<impl IntoResponse> {
+    let board = state
+        .boards
+        .create_board(&auth.user.user_id.to_string(), &request)
+        .await?;
+    Ok((StatusCode::CREATED, Json(board)))
+}
+
+/// Handler for GET /api/boards - lists the boards the current user is a member of
+///
+/// # Errors
+/// Returns an error if the boards cannot be loaded
+pub async fn list_boards_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+) -> AppResult<impl IntoResponse> {
+    let boards = state
+        .boards
+        .list_boards(&auth.user.user_id.to_string())
+        .await?;
+    Ok(Json(boards))
+}
+
+/// Handler for GET /api/boards/{id} - gets a board
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user is not a member of the board
+pub async fn get_board_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(board_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let board = state
+        .boards
+        .get_board(&auth.user.user_id.to_string(), &board_id)
+        .await?;
+    Ok(Json(board))
+}
+
+/// Handler for DELETE /api/boards/{id} - deletes a board and its issues
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user is not a member of the board, or
+/// `AppError::Forbidden` if they are not its owner
+#[tracing::instrument(skip(state, auth), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn delete_board_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(board_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    state
+        .boards
+        .delete_board(&auth.user.user_id.to_string(), &board_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Handler for GET /api/boards/{id}/members - lists a board's members
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user is not a member of the board
+pub async fn list_board_members_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(board_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let members = state
+        .boards
+        .list_members(&auth.user.user_id.to_string(), &board_id)
+        .await?;
+    Ok(Json(members))
+}
+
+/// Handler for POST /api/boards/{id}/members - adds a registered user to a board
+///
+/// Only users with a verified email may add members.
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user is not a member of the board or no
+/// user has the email, `AppError::Forbidden` if the user is not the owner or
+/// has not verified their email, or
+/// `AppError::BadRequest` if the user being added is already a member
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn add_board_member_handler(
+    State(state): State<Arc<AppState>>,
+    auth: VerifiedEmail,
+    Path(board_id): Path<String>,
+    Json(request): Json<AddBoardMemberRequest>,
+) -> AppResult<impl IntoResponse> {
+    let member = state
+        .boards
+        .add_member(&auth.user.user_id.to_string(), &board_id, &request.email)
+        .await?;
+    Ok((StatusCode::CREATED, Json(member)))
+}
+
+/// Handler for DELETE `/api/boards/{id}/members/{user_id}` - removes a member
+///
+/// # Errors
+/// Returns `AppError::NotFound` if either user is not a member of the board,
+/// or `AppError::Forbidden` if the current user may not remove the member
+#[tracing::instrument(skip(state, auth), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn remove_board_member_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path((board_id, member_id)): Path<(String, String)>,
+) -> AppResult<impl IntoResponse> {
+    state
+        .boards
+        .remove_member(&auth.user.user_id.to_string(), &board_id, &member_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,112 @@
+// kanbain/server/src/handlers/cli_auth_handler.rs
+
+//! CLI device login HTTP handlers
+//!
+//! Command-line tools start a flow with a PKCE code challenge and send the user to
+//! the verification URL. The signed-in user approves the flow in the browser, and
+//! the tool exchanges the one-time code plus its code verifier for session tokens
+//! bound to a new device.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use crate::{
+    core::{AppState, issue_session_tokens},
+    errors::{AppError, AppResult},
+    middleware::JwtAuth,
+    models::{
+        cli_auth::{ApproveCliFlowResponse, CliTokenRequest, StartCliFlowRequest},
+        session::SessionTokenResponse,
+    },
+};
+
+/// Handler for POST /api/auth/cli/flows - starts a CLI login flow
+///
+/// # Errors
+/// Returns `AppError::ValidationError` if the device details, code challenge or
+/// redirect URI are invalid
+#[tracing::instrument(skip(state, request), fields(device_name = %request.device_name), err(Debug))]
+pub async fn start_cli_flow_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<StartCliFlowRequest>,
+) -> AppResult<impl IntoResponse> {
+    let response = state.cli_auth.start_flow(&request).await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Handler for GET /api/auth/cli/flows/{state} - shows a flow to the approving user
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the flow does not exist
+pub async fn get_cli_flow_handler(
+    State(state): State<Arc<AppState>>,
+    _auth: JwtAuth,
+    Path(flow_state): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let flow = state.cli_auth.get_flow(&flow_state).await?;
+    Ok(Json(flow))
+}
+
+/// Handler for POST /api/auth/cli/flows/{state}/approve - approves a flow for the current user
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the flow does not exist, has expired or was
+/// already approved
+#[tracing::instrument(skip(state, auth, flow_state), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn approve_cli_flow_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(flow_state): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let (code, redirect_url) = state
+        .cli_auth
+        .approve_flow(&flow_state, auth.user.user_id)
+        .await?;
+
+    Ok(Json(ApproveCliFlowResponse { code, redirect_url }))
+}
+
+/// Handler for POST /api/auth/cli/token - exchanges an approved flow's code for tokens
+///
+/// Creates a session for the device described when the flow was started.
+///
+/// # Errors
+/// Returns `AppError::Unauthorized` if the code or code verifier is invalid, or the
+/// flow is not approved or has expired
+#[tracing::instrument(skip(state, request), err(Debug))]
+pub async fn cli_token_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<CliTokenRequest>,
+) -> AppResult<impl IntoResponse> {
+    let exchanged = state
+        .cli_auth
+        .exchange_code(&request.state, &request.code, &request.code_verifier)
+        .await?;
+
+    let user = match state.user.find_by_id(exchanged.user_id).await {
+        Ok(user) => user,
+        Err(AppError::UserNotFound) => {
+            return Err(AppError::Unauthorized("User no longer exists".to_string()));
+        }
+        Err(e) => return Err(e),
+    };
+
+    let tokens = issue_session_tokens(&state, &user, &exchanged.device).await?;
+    state
+        .cli_auth
+        .link_device(&exchanged.flow_id, tokens.session_id)
+        .await?;
+
+    tracing::info!(
+        "CLI device '{}' signed in for user {}",
+        exchanged.device.name,
+        user.email
+    );
+
+    Ok(Json(SessionTokenResponse::from(tokens)))
+}
@@ -0,0 +1,190 @@
+// kanbain/server/src/handlers/issue_handler.rs
+
+//! Issue HTTP handlers
+//!
+//! Endpoints for creating, listing, editing and moving the issues on a board.
+//! Issues on boards the user is not a member of respond with 404.
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use crate::{
+    core::AppState,
+    errors::AppResult,
+    middleware::{JwtAuth, check_embedding_quota},
+    models::{
+        CreateIssueRequest, DuplicateCheckRequest, IssueListQuery, TransitionIssueRequest,
+        UpdateIssueRequest,
+    },
+};
+
+/// Handler for POST /api/boards/{id}/issues - adds an issue to a board's backlog
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user is not a member of the board, or
+/// `AppError::ValidationError` if a field is invalid
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn create_issue_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(board_id): Path<String>,
+    Json(request): Json<CreateIssueRequest>,
+) -> AppResult<impl IntoResponse> {
+    let issue = state
+        .issues
+        .create_issue(&auth.user.user_id.to_string(), &board_id, &request)
+        .await?;
+    Ok((StatusCode::CREATED, Json(issue)))
+}
+
+/// Handler for GET /api/boards/{id}/issues - lists one page of a board's issues
+///
+/// Supports `status`, `priority` and `type` filters, `sort` and `order`, and
+/// `cursor` and `limit` for pagination.
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user is not a member of the board, or
+/// `AppError::ValidationError` if the limit or cursor is invalid
+pub async fn list_issues_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(board_id): Path<String>,
+    Query(query): Query<IssueListQuery>,
+) -> AppResult<impl IntoResponse> {
+    let page = state
+        .issues
+        .list_issues(&auth.user.user_id.to_string(), &board_id, &query)
+        .await?;
+    Ok(Json(page))
+}
+
+/// Handler for GET /api/issues/{id} - gets an issue
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the issue is not on one of the user's boards
+pub async fn get_issue_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(issue_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let issue = state
+        .issues
+        .get_issue(&auth.user.user_id.to_string(), &issue_id)
+        .await?;
+    Ok(Json(issue))
+}
+
+/// Handler for PUT /api/issues/{id} - changes an issue's details
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the issue is not on one of the user's
+/// boards, or `AppError::ValidationError` if a field is invalid
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn update_issue_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(issue_id): Path<String>,
+    Json(request): Json<UpdateIssueRequest>,
+) -> AppResult<impl IntoResponse> {
+    let issue = state
+        .issues
+        .update_issue(&auth.user.user_id.to_string(), &issue_id, &request)
+        .await?;
+    Ok(Json(issue))
+}
+
+/// Handler for POST /api/issues/{id}/status - moves an issue to another status
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the issue is not on one of the user's
+/// boards, `AppError::ValidationError` if the transition is not allowed, or
+/// `AppError::BadRequest` if the issue was moved concurrently
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn transition_issue_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(issue_id): Path<String>,
+    Json(request): Json<TransitionIssueRequest>,
+) -> AppResult<impl IntoResponse> {
+    let issue = state
+        .issues
+        .transition_issue(&auth.user.user_id.to_string(), &issue_id, request.status)
+        .await?;
+    Ok(Json(issue))
+}
+
+/// Handler for DELETE /api/issues/{id} - deletes an issue
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the issue is not on one of the user's boards
+#[tracing::instrument(skip(state, auth), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn delete_issue_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(issue_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    state
+        .issues
+        .delete_issue(&auth.user.user_id.to_string(), &issue_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Handler for POST /api/boards/{id}/duplicates - finds issues a draft may duplicate
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the user is not a member of the board,
+/// `AppError::ValidationError` if the title is empty, `AppError::BadRequest`
+/// if the texts cannot be embedded, or `AppError::TooManyRequests` if the user
+/// is over their AI quota
+pub async fn check_duplicates_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(board_id): Path<String>,
+    Json(request): Json<DuplicateCheckRequest>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = auth.user.user_id.to_string();
+    check_embedding_quota(&state, &user_id).await?;
+
+    let ai_service = state.ai.read().await;
+    let result = state
+        .embeddings
+        .check_duplicates(&ai_service, &user_id, &board_id, &request)
+        .await?;
+    Ok(Json(result))
+}
+
+/// Handler for GET /api/issues/{id}/duplicates - finds other issues on the
+/// board that an issue may duplicate
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the issue is not on one of the user's boards,
+/// `AppError::BadRequest` if the texts cannot be embedded, or
+/// `AppError::TooManyRequests` if the user is over their AI quota
+pub async fn issue_duplicates_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(issue_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let user_id = auth.user.user_id.to_string();
+    let issue = state.issues.get_issue(&user_id, &issue_id).await?;
+    check_embedding_quota(&state, &user_id).await?;
+    let request = DuplicateCheckRequest {
+        title: issue.title,
+        description: issue.description,
+        exclude_issue_id: Some(issue.id),
+        limit: None,
+    };
+
+    let ai_service = state.ai.read().await;
+    let result = state
+        .embeddings
+        .check_duplicates(&ai_service, &user_id, &issue.board_id, &request)
+        .await?;
+    Ok(Json(result))
+}
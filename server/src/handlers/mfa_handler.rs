@@ -0,0 +1,173 @@
+// kanbain/server/src/handlers/mfa_handler.rs
+
+//! Two-factor authentication HTTP handlers
+//!
+//! Users enroll an authenticator app, enable it with a code from the app, and
+//! afterwards complete each sign-in at the verify endpoint with the
+//! `mfa_pending` token the password step returned.
+
+use axum::{
+    Json,
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use axum_extra::{TypedHeader, headers::UserAgent};
+use std::sync::Arc;
+
+use crate::{
+    core::{AppState, build_session_auth_response, session_cookies::deliver_session},
+    errors::{AppError, AppResult},
+    middleware::JwtAuth,
+    models::{
+        DeviceInfo,
+        mfa::{MfaCodeRequest, MfaVerifyRequest, RecoveryCodesResponse},
+    },
+};
+
+/// Handler for GET /api/auth/mfa - shows the current user's two-factor status
+///
+/// # Errors
+/// Returns an error if the status cannot be loaded
+#[tracing::instrument(skip(state, auth), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn get_mfa_status_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+) -> AppResult<impl IntoResponse> {
+    let roles = state.role.get_roles(auth.user.user_id).await?;
+    let status = state.mfa.status(auth.user.user_id, &roles).await?;
+    Ok(Json(status))
+}
+
+/// Handler for POST /api/auth/mfa/enroll - starts enrolling an authenticator app
+///
+/// Returns the secret and `otpauth://` URI to add to the app.
+///
+/// # Errors
+/// Returns `AppError::BadRequest` if two-factor authentication is already enabled
+#[tracing::instrument(skip(state, auth), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn enroll_mfa_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+) -> AppResult<impl IntoResponse> {
+    let enrollment = state
+        .mfa
+        .start_enrollment(auth.user.user_id, &auth.user.email)
+        .await?;
+    Ok(Json(enrollment))
+}
+
+/// Handler for POST /api/auth/mfa/enable - enables the enrolled app with a code from it
+///
+/// Returns the recovery codes, which are not shown again. The user's other
+/// sessions are revoked, since they were started without a second factor.
+///
+/// # Errors
+/// Returns `AppError::BadRequest` if no enrollment was started, or
+/// `AppError::Unauthorized` if the code is invalid
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn enable_mfa_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Json(request): Json<MfaCodeRequest>,
+) -> AppResult<impl IntoResponse> {
+    let recovery_codes = state.mfa.enable(auth.user.user_id, &request.code).await?;
+    state
+        .session
+        .revoke_all_sessions(auth.user.user_id, auth.user.session_id)
+        .await?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Handler for POST /api/auth/mfa/disable - turns two-factor authentication off
+///
+/// Takes a code from the app or a recovery code, so a stolen session alone
+/// cannot remove the second factor.
+///
+/// # Errors
+/// Returns `AppError::Forbidden` if one of the user's roles requires two-factor
+/// authentication, `AppError::BadRequest` if it is not enabled, or
+/// `AppError::Unauthorized` if the code is invalid
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn disable_mfa_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Json(request): Json<MfaCodeRequest>,
+) -> AppResult<impl IntoResponse> {
+    let roles = state.role.get_roles(auth.user.user_id).await?;
+    if state.mfa.is_required_for(&roles).await? {
+        return Err(AppError::Forbidden(
+            "Two-factor authentication is required for your role".to_string(),
+        ));
+    }
+
+    state.mfa.verify(auth.user.user_id, &request.code).await?;
+    state.mfa.disable(auth.user.user_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "Two-factor authentication disabled"
+    })))
+}
+
+/// Handler for POST /api/auth/mfa/recovery-codes - replaces the recovery codes
+///
+/// Takes a code from the app or a recovery code; the previous recovery codes
+/// stop working.
+///
+/// # Errors
+/// Returns `AppError::BadRequest` if two-factor authentication is not enabled,
+/// or `AppError::Unauthorized` if the code is invalid
+#[tracing::instrument(skip(state, auth, request), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn regenerate_recovery_codes_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Json(request): Json<MfaCodeRequest>,
+) -> AppResult<impl IntoResponse> {
+    state.mfa.verify(auth.user.user_id, &request.code).await?;
+    let recovery_codes = state
+        .mfa
+        .regenerate_recovery_codes(auth.user.user_id)
+        .await?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Handler for POST /api/auth/mfa/verify - completes a sign-in with its second factor
+///
+/// Exchanges the `mfa_pending` token from the password step, together with a
+/// code from the app or a recovery code, for a session. In the cookie session
+/// mode the tokens are set as `HttpOnly` cookies and left out of the response body.
+///
+/// # Errors
+/// Returns `AppError::Unauthorized` if the token is invalid, expired or used,
+/// or the code is invalid, or an error if the session cannot be started
+#[tracing::instrument(skip(state, user_agent, request), err(Debug))]
+pub async fn verify_mfa_handler(
+    State(state): State<Arc<AppState>>,
+    user_agent: Option<TypedHeader<UserAgent>>,
+    Json(request): Json<MfaVerifyRequest>,
+) -> AppResult<Response> {
+    let user_id = state
+        .mfa
+        .complete_challenge(&request.mfa_token, &request.code)
+        .await?;
+    let user = match state.user.find_by_id(user_id).await {
+        Ok(user) => user,
+        Err(AppError::UserNotFound) => {
+            return Err(AppError::Unauthorized("User no longer exists".to_string()));
+        }
+        Err(e) => return Err(e),
+    };
+
+    let device = DeviceInfo::web(user_agent.as_ref().map(|agent| agent.as_str()));
+    let mut auth = build_session_auth_response(&state, &user, &device).await?;
+    let cookies = deliver_session(
+        &mut auth,
+        request.session,
+        state.auth.access_token_ttl_seconds(),
+    )?;
+
+    tracing::info!("User logged in with a second factor: {}", user.email);
+    Ok((cookies, Json(auth)).into_response())
+}
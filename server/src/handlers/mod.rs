@@ -1,6 +1,13 @@
+pub mod account_handler;
+pub mod admin_handler;
 pub mod ai_handler;
 pub mod auth_handler;
+pub mod board_handler;
+pub mod cli_auth_handler;
 pub mod health_handler;
+pub mod issue_handler;
+pub mod mfa_handler;
 pub mod oauth_handler;
 pub mod payment_handler;
+pub mod session_handler;
 pub mod user_handler;
@@ -1,21 +1,34 @@
 // kanbain/server/src/handlers/oauth_handler/mod.rs
 
 use axum::{
-    extract::{Query, State},
-    response::Redirect,
+    Json,
+    extract::{FromRef, Path, Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Redirect, Response},
 };
-use serde::Deserialize;
+use axum_extra::{TypedHeader, headers::UserAgent};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use tracing::instrument;
 use uuid::Uuid;
 
 use crate::{
-    core::{AppState, build_unified_auth_response},
-    errors::AppError,
+    core::{
+        AppState, build_session_auth_response,
+        session_cookies::{LINK_STATE_COOKIE, deliver_session, link_state_cookie, read_cookie},
+    },
+    errors::{AppError, AppResult},
+    middleware::JwtAuth,
     models::{
-        OAuthCallbackParams, UnifiedAuthResponse, User,
-        oauth::{OAuthProvider, OAuthUserInfo},
+        DeviceInfo, UnifiedAuthResponse, User,
+        mfa::MfaChallengeResponse,
+        oauth::{OAuthProvider, OAuthUserInfo, ValidatedOAuthState},
+        session::SessionMode,
+    },
+    services::{
+        OAuthService, mfa_service::MFA_CHALLENGE_TTL_SECONDS,
+        oauth_service::OAUTH_STATE_TTL_MINUTES,
     },
-    services::OAuthService,
 };
 
 /// Request payload for OAuth login initiation
@@ -36,7 +49,33 @@ pub struct OAuthCallbackQuery {
     pub error: Option<String>,
 }
 
-// Note: We now use UnifiedAuthResponse with OAuthCallbackParams for OAuth responses
+/// Request to exchange a one-time OAuth login code for a session
+#[derive(Debug, Deserialize)]
+pub struct OAuthCodeExchangeRequest {
+    /// Code the callback redirected to the client with
+    pub code: String,
+    /// How the client keeps the session tokens
+    #[serde(default)]
+    pub session: SessionMode,
+}
+
+/// Response of a login code exchange
+#[derive(Debug, Serialize)]
+pub struct OAuthCodeExchangeResponse {
+    #[serde(flatten)]
+    pub auth: UnifiedAuthResponse,
+    /// Whether the sign-in created the user
+    pub is_new_user: bool,
+}
+
+/// Response for starting to link a provider account
+#[derive(Debug, Serialize)]
+pub struct IdentityLinkResponse {
+    /// Provider URL to send the user to
+    pub authorization_url: String,
+    /// State the provider will return to the callback
+    pub state: String,
+}
 
 /// Extended application state for OAuth handlers
 #[derive(Clone)]
@@ -45,6 +84,12 @@ pub struct OAuthAppState {
     pub oauth_service: std::sync::Arc<OAuthService>,
 }
 
+impl FromRef<OAuthAppState> for Arc<AppState> {
+    fn from_ref(state: &OAuthAppState) -> Self {
+        state.app_state.clone()
+    }
+}
+
 /// Initiate Google OAuth login flow
 ///
 /// Redirects the user to Google's OAuth authorization URL
@@ -75,7 +120,7 @@ pub async fn google_login_init(
 
     tracing::info!("Initiating Google OAuth login with state: {}", csrf_state);
 
-    Ok(Redirect::permanent(&auth_url))
+    Ok(Redirect::to(&auth_url))
 }
 
 /// Initiate GitHub OAuth login flow
@@ -108,7 +153,57 @@ pub async fn github_login_init(
 
     tracing::info!("Initiating GitHub OAuth login with state: {}", csrf_state);
 
-    Ok(Redirect::permanent(&auth_url))
+    Ok(Redirect::to(&auth_url))
+}
+
+/// Initiate OAuth login with any configured provider
+///
+/// Redirects the user to the provider's authorization URL. Generic OIDC
+/// providers are configured by name in `OIDC_PROVIDERS`.
+///
+/// # Errors
+///
+/// Returns `AppError::NotFound` for an unknown provider, or an error if the
+/// provider cannot be reached or the state cannot be stored
+#[instrument(skip(state), err(Debug))]
+pub async fn oauth_login_init(
+    State(state): State<OAuthAppState>,
+    Path(provider): Path<String>,
+    Query(params): Query<OAuthInitRequest>,
+) -> Result<Redirect, AppError> {
+    let provider = state.oauth_service.provider(&provider)?;
+    let csrf_state = params.state.unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let auth_url = state
+        .oauth_service
+        .authorization_url(&provider, &csrf_state, None)
+        .await?;
+
+    tracing::info!(
+        "Initiating {} OAuth login with state: {}",
+        provider,
+        csrf_state
+    );
+
+    // Each request has its own state, so the redirect must not be cached
+    Ok(Redirect::to(&auth_url))
+}
+
+/// Handle the OAuth callback of any configured provider
+///
+/// # Errors
+///
+/// Returns `AppError::NotFound` for an unknown provider, or an error if the
+/// state is missing or invalid
+#[instrument(skip(state, headers), fields(code = %params.code), err(Debug))]
+pub async fn oauth_callback(
+    State(state): State<OAuthAppState>,
+    Path(provider): Path<String>,
+    headers: HeaderMap,
+    Query(params): Query<OAuthCallbackQuery>,
+) -> Result<Redirect, AppError> {
+    let provider = state.oauth_service.provider(&provider)?;
+    handle_oauth_callback(state, &headers, params, provider).await
 }
 
 /// Handle Google OAuth callback
@@ -119,12 +214,13 @@ pub async fn github_login_init(
 /// # Errors
 ///
 /// Returns an error if OAuth exchange fails, user info retrieval fails, or JWT generation fails
-#[instrument(skip(state), fields(code = %params.code), err(Debug))]
+#[instrument(skip(state, headers), fields(code = %params.code), err(Debug))]
 pub async fn google_oauth_callback(
     State(state): State<OAuthAppState>,
+    headers: HeaderMap,
     Query(params): Query<OAuthCallbackQuery>,
 ) -> Result<Redirect, AppError> {
-    handle_oauth_callback(state, params, OAuthProvider::Google).await
+    handle_oauth_callback(state, &headers, params, OAuthProvider::Google).await
 }
 
 /// Handle GitHub OAuth callback
@@ -135,17 +231,26 @@ pub async fn google_oauth_callback(
 /// # Errors
 ///
 /// Returns an error if OAuth exchange fails, user info retrieval fails, or JWT generation fails
-#[instrument(skip(state), fields(code = %params.code), err(Debug))]
+#[instrument(skip(state, headers), fields(code = %params.code), err(Debug))]
 pub async fn github_oauth_callback(
     State(state): State<OAuthAppState>,
+    headers: HeaderMap,
     Query(params): Query<OAuthCallbackQuery>,
 ) -> Result<Redirect, AppError> {
-    handle_oauth_callback(state, params, OAuthProvider::GitHub).await
+    handle_oauth_callback(state, &headers, params, OAuthProvider::GitHub).await
 }
 
 /// Generic OAuth callback handler
+///
+/// Successful sign-ins redirect to the client with a one-time code, which the
+/// client exchanges for a session at `POST /api/auth/oauth/exchange`. Tokens never
+/// appear in the redirect URL.
+///
+/// Links only complete in the browser that started them, which holds the
+/// state in the link state cookie.
 async fn handle_oauth_callback(
     state: OAuthAppState,
+    headers: &HeaderMap,
     params: OAuthCallbackQuery,
     provider: OAuthProvider,
 ) -> Result<Redirect, AppError> {
@@ -154,61 +259,66 @@ async fn handle_oauth_callback(
         return Ok(redirect_with_error(&state, &error));
     }
 
-    // Validate state parameter for CSRF protection
-    if let Some(state_param) = &params.state {
-        state
-            .oauth_service
-            .validate_oauth_state(state_param, provider.clone())
-            .await
-            .map_err(|_| AppError::Unauthorized("Invalid or expired OAuth state".to_string()))?;
-
-        tracing::info!("OAuth state validated successfully");
-    } else {
-        // State is required for security
+    // Validate state parameter for CSRF protection; state is required for security
+    let Some(state_param) = &params.state else {
         return Err(AppError::Unauthorized(
             "Missing OAuth state parameter".to_string(),
         ));
+    };
+    let oauth_state = state
+        .oauth_service
+        .validate_oauth_state(state_param, provider.clone())
+        .await
+        .map_err(|_| AppError::Unauthorized("Invalid or expired OAuth state".to_string()))?;
+
+    tracing::info!("OAuth state validated successfully");
+
+    // Otherwise anyone could send a link they started to a victim, linking the
+    // victim's provider account to their own user
+    if oauth_state.link_user_id.is_some()
+        && read_cookie(headers, LINK_STATE_COOKIE) != Some(state_param.as_str())
+    {
+        return Ok(redirect_with_error(&state, "identity_link_mismatch"));
     }
 
     // Exchange authorization code for user info
-    let Ok(oauth_user_info) = exchange_oauth_code(&state, &params.code, provider).await else {
+    let Ok(oauth_user_info) =
+        exchange_oauth_code(&state, &params.code, provider, &oauth_state).await
+    else {
         return Ok(redirect_with_error(&state, "oauth_exchange_failed"));
     };
 
+    // States stored by a signed-in user link the account instead of signing in
+    if let Some(user_id) = oauth_state.link_user_id {
+        return Ok(link_oauth_identity(&state, user_id, &oauth_user_info).await);
+    }
+
     // Get or create user
     let (user, is_new_user) = match get_or_create_user(&state, &oauth_user_info).await {
         Ok(result) => result,
         Err(error_code) => return Ok(redirect_with_error(&state, &error_code)),
     };
 
-    // Generate JWT token
-    let Ok(token) = generate_jwt_token(&state, &user) else {
-        return Ok(redirect_with_error(&state, "token_generation_failed"));
+    // The session is started when the client exchanges the code
+    let code = match state
+        .oauth_service
+        .create_login_code(user.id, is_new_user)
+        .await
+    {
+        Ok(code) => code,
+        Err(e) => {
+            tracing::error!("Failed to issue OAuth login code: {:?}", e);
+            return Ok(redirect_with_error(&state, "token_generation_failed"));
+        }
     };
 
-    // Create unified response using shared function
-    let unified_response =
-        match build_unified_auth_response(&state.app_state, &user, token.clone()).await {
-            Ok(response) => response,
-            Err(e) => {
-                tracing::error!("Failed to build unified auth response: {:?}", e);
-                return Ok(redirect_with_error(&state, "internal_error"));
-            }
-        };
-
     tracing::info!(
-        "OAuth login successful for user: {} (new_user: {}, payment_required: {})",
+        "OAuth login successful for user: {} (new_user: {})",
         user.email,
-        is_new_user,
-        unified_response.payment_user.payment_required
+        is_new_user
     );
 
-    // Redirect to client with success data
-    Ok(redirect_with_success(
-        &state,
-        &unified_response,
-        is_new_user,
-    ))
+    Ok(redirect_with_success(&state, &code, is_new_user))
 }
 
 /// Redirect to client with error
@@ -220,29 +330,83 @@ fn redirect_with_error(state: &OAuthAppState, error: &str) -> Redirect {
         client_url,
         urlencoding::encode(error)
     );
-    Redirect::permanent(&redirect_url)
+    Redirect::to(&redirect_url)
 }
 
-/// Redirect to client with success data
-fn redirect_with_success(
-    state: &OAuthAppState,
-    response: &UnifiedAuthResponse,
-    is_new_user: bool,
-) -> Redirect {
-    let client_url = state.oauth_service.get_client_url();
-    let params = OAuthCallbackParams::from_unified_response(response, is_new_user);
-
+/// Redirect to client with the one-time login code
+fn redirect_with_success(state: &OAuthAppState, code: &str, is_new_user: bool) -> Redirect {
     let redirect_url = format!(
-        "{}/auth/oauth/callback?token={}&user_id={}&email={}&is_new_user={}&payment_required={}&has_valid_invite={}",
-        client_url,
-        urlencoding::encode(&params.token),
-        params.user_id,
-        urlencoding::encode(&params.email),
-        params.is_new_user,
-        params.payment_required,
-        params.has_valid_invite
+        "{}/auth/oauth/callback?code={}&is_new_user={}",
+        state.oauth_service.get_client_url(),
+        urlencoding::encode(code),
+        is_new_user
     );
-    Redirect::permanent(&redirect_url)
+    Redirect::to(&redirect_url)
+}
+
+/// Exchange a one-time login code for a session
+///
+/// In the cookie session mode the tokens are set as `HttpOnly` cookies and left
+/// out of the response body. Users with two-factor authentication get an
+/// `mfa_pending` token instead, to complete the sign-in with at
+/// `POST /api/auth/mfa/verify`.
+///
+/// # Errors
+///
+/// Returns `AppError::Unauthorized` if the code is invalid, expired or already
+/// used, or the user no longer exists, or an error if the session cannot be started
+#[instrument(skip(state, user_agent, request), err(Debug))]
+pub async fn exchange_login_code(
+    State(state): State<OAuthAppState>,
+    user_agent: Option<TypedHeader<UserAgent>>,
+    Json(request): Json<OAuthCodeExchangeRequest>,
+) -> AppResult<Response> {
+    let grant = state.oauth_service.redeem_login_code(&request.code).await?;
+    let user = match state.app_state.user.find_by_id(grant.user_id).await {
+        Ok(user) => user,
+        Err(AppError::UserNotFound) => {
+            return Err(AppError::Unauthorized("User no longer exists".to_string()));
+        }
+        Err(e) => return Err(e),
+    };
+
+    // Signing in with a provider does not skip the second factor
+    if state.app_state.mfa.is_enabled(user.id).await? {
+        let mfa_token = state.app_state.mfa.create_challenge(user.id).await?;
+        tracing::info!(
+            "OAuth login code exchanged for user: {}, waiting for second factor",
+            user.email
+        );
+        return Ok(Json(MfaChallengeResponse::new(
+            mfa_token,
+            MFA_CHALLENGE_TTL_SECONDS,
+        ))
+        .into_response());
+    }
+
+    let device = DeviceInfo::web(user_agent.as_ref().map(|agent| agent.as_str()));
+    let mut auth = build_session_auth_response(&state.app_state, &user, &device).await?;
+
+    tracing::info!(
+        "OAuth login code exchanged for user: {} (payment_required: {})",
+        user.email,
+        auth.payment_user.payment_required
+    );
+
+    let cookies = deliver_session(
+        &mut auth,
+        request.session,
+        state.app_state.auth.access_token_ttl_seconds(),
+    )?;
+
+    Ok((
+        cookies,
+        Json(OAuthCodeExchangeResponse {
+            auth,
+            is_new_user: grant.is_new_user,
+        }),
+    )
+        .into_response())
 }
 
 /// Exchange OAuth authorization code for user info
@@ -250,10 +414,17 @@ async fn exchange_oauth_code(
     state: &OAuthAppState,
     code: &str,
     provider: OAuthProvider,
+    oauth_state: &ValidatedOAuthState,
 ) -> Result<OAuthUserInfo, AppError> {
-    let oauth_user_info = match provider {
+    let oauth_user_info = match &provider {
         OAuthProvider::Google => state.oauth_service.handle_google_callback(code).await?,
         OAuthProvider::GitHub => state.oauth_service.handle_github_callback(code).await?,
+        OAuthProvider::Oidc(name) => {
+            state
+                .oauth_service
+                .handle_oidc_callback(name, code, oauth_state)
+                .await?
+        }
     };
 
     tracing::info!(
@@ -265,11 +436,76 @@ async fn exchange_oauth_code(
     Ok(oauth_user_info)
 }
 
-/// Get existing user or create new one from OAuth info
+/// Link the provider account to the user who started linking it
+async fn link_oauth_identity(
+    state: &OAuthAppState,
+    user_id: Uuid,
+    oauth_user_info: &OAuthUserInfo,
+) -> Redirect {
+    match state
+        .app_state
+        .identities
+        .link_identity(user_id, oauth_user_info)
+        .await
+    {
+        Ok(()) => {
+            let redirect_url = format!(
+                "{}/auth/oauth/callback?linked={}",
+                state.oauth_service.get_client_url(),
+                oauth_user_info.provider
+            );
+            Redirect::to(&redirect_url)
+        }
+        Err(AppError::BadRequest(_)) => redirect_with_error(state, "identity_already_linked"),
+        Err(e) => {
+            tracing::error!("Failed to link OAuth identity: {:?}", e);
+            redirect_with_error(state, "identity_link_failed")
+        }
+    }
+}
+
+/// Get the user the provider account signs in to, or create a new one
+///
+/// Provider accounts are matched by their linked identity. An existing user
+/// with the same email is only signed in, and the account linked, when the
+/// provider verified the email; otherwise they must sign in and link it.
 async fn get_or_create_user(
     state: &OAuthAppState,
     oauth_user_info: &OAuthUserInfo,
 ) -> Result<(User, bool), String> {
+    let provider = oauth_user_info.provider.to_string();
+    let linked_user_id = state
+        .app_state
+        .identities
+        .find_user_id(&provider, &oauth_user_info.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error finding OAuth identity: {:?}", e);
+            "user_lookup_failed"
+        })?;
+
+    if let Some(user_id) = linked_user_id {
+        let user = state
+            .app_state
+            .user
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error finding user for OAuth identity: {:?}", e);
+                "user_lookup_failed"
+            })?;
+        if let Err(e) = state
+            .app_state
+            .identities
+            .record_login(oauth_user_info)
+            .await
+        {
+            tracing::warn!("Failed to record OAuth login: {:?}", e);
+        }
+        tracing::info!("Found linked user for OAuth login: {}", user.email);
+        return Ok((user, false));
+    }
+
     match state
         .app_state
         .user
@@ -277,60 +513,143 @@ async fn get_or_create_user(
         .await
     {
         Ok(existing_user) => {
+            if !oauth_user_info.email_verified {
+                tracing::warn!(
+                    "Refusing OAuth login to existing user {} with unverified email",
+                    oauth_user_info.email
+                );
+                return Err("account_exists_link_required".to_string());
+            }
+            if !owns_email(state, &existing_user).await? {
+                tracing::warn!(
+                    "Refusing OAuth login to existing user {} who has not verified their email",
+                    oauth_user_info.email
+                );
+                return Err("account_exists_link_required".to_string());
+            }
+
+            state
+                .app_state
+                .identities
+                .link_identity(existing_user.id, oauth_user_info)
+                .await
+                .map_err(|e| {
+                    tracing::warn!("Failed to link OAuth identity by verified email: {:?}", e);
+                    "account_exists_link_required"
+                })?;
             tracing::info!(
-                "Found existing user for OAuth login: {}",
+                "Linked {} account to existing user by verified email: {}",
+                provider,
                 oauth_user_info.email
             );
+            record_verified_email(state, existing_user.id, oauth_user_info).await;
             Ok((existing_user, false))
         }
-        Err(AppError::UserNotFound) => {
-            tracing::info!(
-                "Creating new user from OAuth info: {}",
-                oauth_user_info.email
-            );
+        Err(AppError::UserNotFound) => create_oauth_user(state, oauth_user_info).await,
+        Err(e) => {
+            tracing::error!("Error finding user: {:?}", e);
+            Err("user_lookup_failed".to_string())
+        }
+    }
+}
 
-            // Create user without requiring invite (users without invite will need payment)
-            let new_user = create_user_from_oauth(state, oauth_user_info)
-                .await
-                .map_err(|e| {
-                    tracing::error!("Failed to create user from OAuth: {:?}", e);
-                    "user_creation_failed"
-                })?;
+/// Whether an existing account is known to belong to the owner of its email
+///
+/// Anyone can register a password account with an email they do not own, so
+/// such accounts are only linked by email once the email is verified; otherwise
+/// the owner signing in with a provider would hand their sign-in to whoever
+/// registered first. Accounts created through a provider have no usable password.
+async fn owns_email(state: &OAuthAppState, user: &User) -> Result<bool, String> {
+    if user.provider != "local" {
+        return Ok(true);
+    }
+    state
+        .app_state
+        .account_email
+        .is_email_verified(user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error checking email verification: {:?}", e);
+            "user_lookup_failed".to_string()
+        })
+}
 
-            // Try to mark invite as used if one exists
-            match state
-                .app_state
-                .invite
-                .mark_invite_used(&oauth_user_info.email)
-                .await
-            {
-                Ok(()) => {
-                    tracing::info!("Marked invite as used for user: {}", oauth_user_info.email);
-                }
-                Err(AppError::InviteNotFound) => {
-                    tracing::info!(
-                        "No invite found for user: {} - payment will be required",
-                        oauth_user_info.email
-                    );
-                }
-                Err(e) => {
-                    tracing::error!("Failed to mark invite as used: {:?}", e);
-                    // Continue anyway since user was created successfully
-                }
-            }
+/// Mark the user's email verified if the provider verified it
+async fn record_verified_email(
+    state: &OAuthAppState,
+    user_id: Uuid,
+    oauth_user_info: &OAuthUserInfo,
+) {
+    if !oauth_user_info.email_verified {
+        return;
+    }
+    if let Err(e) = state
+        .app_state
+        .account_email
+        .mark_email_verified(user_id)
+        .await
+    {
+        // The user can still verify their email by mail
+        tracing::error!(
+            "Failed to record verified email of user {}: {:?}",
+            user_id,
+            e
+        );
+    }
+}
 
-            Ok((new_user, true))
+/// Create a new user with the provider account linked
+async fn create_oauth_user(
+    state: &OAuthAppState,
+    oauth_user_info: &OAuthUserInfo,
+) -> Result<(User, bool), String> {
+    tracing::info!(
+        "Creating new user from OAuth info: {}",
+        oauth_user_info.email
+    );
+
+    // Create user without requiring invite (users without invite will need payment)
+    let new_user = create_user_from_oauth(state, oauth_user_info)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to create user from OAuth: {:?}", e);
+            "user_creation_failed"
+        })?;
+
+    state
+        .app_state
+        .identities
+        .link_identity(new_user.id, oauth_user_info)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to link OAuth identity to new user: {:?}", e);
+            "user_creation_failed"
+        })?;
+    record_verified_email(state, new_user.id, oauth_user_info).await;
+
+    // Try to mark invite as used if one exists
+    match state
+        .app_state
+        .invite
+        .mark_invite_used(&oauth_user_info.email)
+        .await
+    {
+        Ok(()) => {
+            tracing::info!("Marked invite as used for user: {}", oauth_user_info.email);
+        }
+        Err(AppError::InviteNotFound) => {
+            tracing::info!(
+                "No invite found for user: {} - payment will be required",
+                oauth_user_info.email
+            );
         }
         Err(e) => {
-            tracing::error!("Error finding user: {:?}", e);
-            Err("user_lookup_failed".to_string())
+            tracing::error!("Failed to mark invite as used: {:?}", e);
+            // Continue anyway since user was created successfully
         }
     }
-}
 
-/// Generate JWT token for user
-fn generate_jwt_token(state: &OAuthAppState, user: &User) -> Result<String, AppError> {
-    state.app_state.auth.generate_token(user.id, &user.email)
+    Ok((new_user, true))
 }
 
 /// Create a new user from OAuth user information
@@ -380,5 +699,83 @@ async fn create_user_from_oauth(
     state.app_state.user.find_by_email(&oauth_info.email).await
 }
 
+/// Handler for GET /api/auth/identities - lists the provider accounts linked to the current user
+///
+/// # Errors
+/// Returns an error if the identities cannot be loaded
+pub async fn list_identities_handler(
+    State(state): State<OAuthAppState>,
+    auth: JwtAuth,
+) -> AppResult<impl IntoResponse> {
+    let identities = state
+        .app_state
+        .identities
+        .list_identities(auth.user.user_id)
+        .await?;
+
+    Ok(Json(serde_json::json!({ "identities": identities })))
+}
+
+/// Handler for POST /api/auth/identities/{provider} - starts linking a provider account
+///
+/// Returns the provider URL to send the user to. When they approve, the
+/// provider's callback links the account to the current user. The state is
+/// also set in an `HttpOnly` cookie, which the callback requires, so the link
+/// can only be completed in this browser.
+///
+/// # Errors
+/// Returns `AppError::NotFound` for an unknown provider, or an error if the
+/// OAuth state cannot be stored
+pub async fn link_identity_handler(
+    State(state): State<OAuthAppState>,
+    auth: JwtAuth,
+    Path(provider): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let provider = state.oauth_service.provider(&provider)?;
+    let csrf_state = Uuid::new_v4().to_string();
+
+    let authorization_url = state
+        .oauth_service
+        .authorization_url(&provider, &csrf_state, Some(auth.user.user_id))
+        .await?;
+
+    tracing::info!(
+        "User {} started linking a {} account",
+        auth.user.user_id,
+        provider
+    );
+
+    Ok((
+        link_state_cookie(&csrf_state, OAUTH_STATE_TTL_MINUTES * 60),
+        Json(IdentityLinkResponse {
+            authorization_url,
+            state: csrf_state,
+        }),
+    ))
+}
+
+/// Handler for DELETE /api/auth/identities/{provider} - unlinks a provider account
+///
+/// # Errors
+/// Returns `AppError::NotFound` if no account of the provider is linked, or
+/// `AppError::BadRequest` if it is the only way the user can sign in
+pub async fn unlink_identity_handler(
+    State(state): State<OAuthAppState>,
+    auth: JwtAuth,
+    Path(provider): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    // Accounts of providers that are no longer configured can still be unlinked
+    state
+        .app_state
+        .identities
+        .unlink_identity(auth.user.user_id, &provider)
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "Account unlinked successfully"
+    })))
+}
+
 #[cfg(test)]
 mod tests;
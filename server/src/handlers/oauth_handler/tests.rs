@@ -3,17 +3,16 @@
 use super::*;
 use crate::{
     handlers::auth_handler::RegisterUserPayload,
-    models::{
-        oauth::{OAuthProvider, OAuthUserInfo},
-        user::User,
-    },
+    models::oauth::{OAuthProvider, OAuthUserInfo},
     services::OAuthService,
     test_helpers::create_test_app_state,
 };
-use axum::extract::{Query, State};
+use axum::{
+    extract::{Query, State},
+    http::header,
+};
 use sqlx::SqlitePool;
 use std::sync::Arc;
-use uuid::Uuid;
 
 async fn setup_test_db() -> SqlitePool {
     let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
@@ -163,7 +162,13 @@ async fn test_exchange_oauth_code_google() {
     let state = create_test_oauth_app_state(&pool);
 
     // This would normally make an HTTP request, so we test the error path
-    let result = exchange_oauth_code(&state, "test_code", OAuthProvider::Google).await;
+    let result = exchange_oauth_code(
+        &state,
+        "test_code",
+        OAuthProvider::Google,
+        &ValidatedOAuthState::default(),
+    )
+    .await;
     // This will fail because we're not mocking the HTTP client
     // but we're testing the code path and error handling
     assert!(result.is_err());
@@ -175,7 +180,13 @@ async fn test_exchange_oauth_code_github() {
     let state = create_test_oauth_app_state(&pool);
 
     // This would normally make an HTTP request, so we test the error path
-    let result = exchange_oauth_code(&state, "test_code", OAuthProvider::GitHub).await;
+    let result = exchange_oauth_code(
+        &state,
+        "test_code",
+        OAuthProvider::GitHub,
+        &ValidatedOAuthState::default(),
+    )
+    .await;
     // This will fail because we're not mocking the HTTP client
     // but we're testing the code path and error handling
     assert!(result.is_err());
@@ -192,6 +203,7 @@ async fn test_create_user_from_oauth_success() {
         name: Some("Test User".to_string()),
         picture: None,
         provider: OAuthProvider::Google,
+        email_verified: true,
     };
 
     let result = create_user_from_oauth(&state, &oauth_info).await;
@@ -207,7 +219,7 @@ async fn test_get_or_create_user_existing() {
     let state = create_test_oauth_app_state(&pool);
 
     // First create a user using the user service
-    let _existing_user = state
+    let existing_user = state
         .app_state
         .user
         .create_user(&RegisterUserPayload {
@@ -217,20 +229,107 @@ async fn test_get_or_create_user_existing() {
         .await
         .unwrap();
 
-    let oauth_info = OAuthUserInfo {
+    let mut oauth_info = OAuthUserInfo {
         id: "google_123".to_string(),
         email: "existing@example.com".to_string(),
         name: Some("Existing User".to_string()),
         picture: None,
         provider: OAuthProvider::Google,
+        email_verified: false,
     };
 
+    // An unverified email must not take over the existing account
     let result = get_or_create_user(&state, &oauth_info).await;
-    assert!(result.is_ok());
+    assert_eq!(result.unwrap_err(), "account_exists_link_required");
 
-    let (user, is_new) = result.unwrap();
-    assert_eq!(user.email, "existing@example.com");
+    // Nor may a verified one while the account's own email is unverified, as
+    // anyone could have registered it
+    oauth_info.email_verified = true;
+    let result = get_or_create_user(&state, &oauth_info).await;
+    assert_eq!(result.unwrap_err(), "account_exists_link_required");
+
+    // Once both are verified it signs in to the account and links it
+    state
+        .app_state
+        .account_email
+        .mark_email_verified(existing_user.id)
+        .await
+        .unwrap();
+    let (user, is_new) = get_or_create_user(&state, &oauth_info).await.unwrap();
+    assert_eq!(user.id, existing_user.id);
     assert!(!is_new); // Should be false for existing user
+    assert_eq!(
+        state
+            .app_state
+            .identities
+            .find_user_id("google", "google_123")
+            .await
+            .unwrap(),
+        Some(existing_user.id)
+    );
+}
+
+#[tokio::test]
+async fn test_get_or_create_user_linked_identity() {
+    let pool = setup_test_db().await;
+    let state = create_test_oauth_app_state(&pool);
+
+    let existing_user = state
+        .app_state
+        .user
+        .create_user(&RegisterUserPayload {
+            email: "linked@example.com".to_string(),
+            password: "test_password123".to_string(),
+        })
+        .await
+        .unwrap();
+
+    let oauth_info = OAuthUserInfo {
+        id: "github_789".to_string(),
+        email: "other-address@example.com".to_string(),
+        name: None,
+        picture: None,
+        provider: OAuthProvider::GitHub,
+        email_verified: false,
+    };
+    state
+        .app_state
+        .identities
+        .link_identity(existing_user.id, &oauth_info)
+        .await
+        .unwrap();
+
+    // The linked account signs in whatever email the provider reports
+    let (user, is_new) = get_or_create_user(&state, &oauth_info).await.unwrap();
+    assert_eq!(user.id, existing_user.id);
+    assert!(!is_new);
+}
+
+#[tokio::test]
+async fn test_get_or_create_user_links_provider_account() {
+    let pool = setup_test_db().await;
+    let state = create_test_oauth_app_state(&pool);
+
+    let google_info = OAuthUserInfo {
+        id: "google_321".to_string(),
+        email: "provider@example.com".to_string(),
+        name: None,
+        picture: None,
+        provider: OAuthProvider::Google,
+        email_verified: false,
+    };
+    let (created, _) = get_or_create_user(&state, &google_info).await.unwrap();
+
+    // Accounts created through a provider have no password anyone else could know
+    let github_info = OAuthUserInfo {
+        id: "github_321".to_string(),
+        provider: OAuthProvider::GitHub,
+        email_verified: true,
+        ..google_info
+    };
+    let (user, is_new) = get_or_create_user(&state, &github_info).await.unwrap();
+    assert_eq!(user.id, created.id);
+    assert!(!is_new);
 }
 
 #[tokio::test]
@@ -244,6 +343,7 @@ async fn test_get_or_create_user_new() {
         name: Some("New User".to_string()),
         picture: None,
         provider: OAuthProvider::Google,
+        email_verified: true,
     };
 
     let result = get_or_create_user(&state, &oauth_info).await;
@@ -254,26 +354,117 @@ async fn test_get_or_create_user_new() {
     assert!(is_new); // Should be true for new user
 }
 
+async fn create_password_user(state: &OAuthAppState) -> User {
+    state
+        .app_state
+        .user
+        .create_user(&RegisterUserPayload {
+            email: "test@example.com".to_string(),
+            password: "password123".to_string(),
+        })
+        .await
+        .unwrap()
+}
+
+async fn exchange(state: &OAuthAppState, code: &str, session: SessionMode) -> AppResult<Response> {
+    let request = OAuthCodeExchangeRequest {
+        code: code.to_string(),
+        session,
+    };
+    exchange_login_code(State(state.clone()), None, Json(request)).await
+}
+
+async fn response_json(response: Response) -> serde_json::Value {
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
 #[tokio::test]
-async fn test_generate_jwt_token_success() {
+async fn test_login_code_is_exchanged_once() {
     let pool = setup_test_db().await;
     let state = create_test_oauth_app_state(&pool);
+    let user = create_password_user(&state).await;
 
-    let user = User {
-        id: Uuid::new_v4(),
-        email: "test@example.com".to_string(),
-        hashed_password: "hashed".to_string(),
-        provider: "oauth".to_string(),
-        provider_user_id: None,
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
-    };
+    let code = state
+        .oauth_service
+        .create_login_code(user.id, true)
+        .await
+        .unwrap();
+    let response = exchange(&state, &code, SessionMode::Token).await.unwrap();
+    assert!(response.headers().get(header::SET_COOKIE).is_none());
+
+    let body = response_json(response).await;
+    assert!(!body["auth_token"].as_str().unwrap().is_empty());
+    assert!(body["refresh_token"].as_str().is_some());
+    assert_eq!(body["auth_user"]["id"], user.id.to_string());
+    assert_eq!(body["is_new_user"], true);
+
+    let reused = exchange(&state, &code, SessionMode::Token).await;
+    assert!(matches!(reused, Err(AppError::Unauthorized(_))));
+    let unknown = exchange(&state, "not-a-code", SessionMode::Token).await;
+    assert!(matches!(unknown, Err(AppError::Unauthorized(_))));
+}
 
-    let result = generate_jwt_token(&state, &user);
-    assert!(result.is_ok());
+#[tokio::test]
+async fn test_expired_login_code_is_rejected() {
+    let pool = setup_test_db().await;
+    let state = create_test_oauth_app_state(&pool);
+    let user = create_password_user(&state).await;
+
+    let code = state
+        .oauth_service
+        .create_login_code(user.id, false)
+        .await
+        .unwrap();
+    let expired = chrono::Utc::now() - chrono::Duration::seconds(1);
+    sqlx::query!("UPDATE oauth_login_codes SET expires_at = ?1", expired)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let result = exchange(&state, &code, SessionMode::Token).await;
+    assert!(matches!(result, Err(AppError::Unauthorized(_))));
+    assert_eq!(
+        state.oauth_service.cleanup_expired_states().await.unwrap(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_login_code_exchange_in_cookie_mode() {
+    let pool = setup_test_db().await;
+    let state = create_test_oauth_app_state(&pool);
+    let user = create_password_user(&state).await;
+
+    let code = state
+        .oauth_service
+        .create_login_code(user.id, false)
+        .await
+        .unwrap();
+    let response = exchange(&state, &code, SessionMode::Cookie).await.unwrap();
+
+    let cookies: Vec<&str> = response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap())
+        .collect();
+    assert_eq!(cookies.len(), 2);
+    assert!(cookies[0].starts_with("kanbain_access="));
+    assert!(cookies[1].starts_with("kanbain_refresh="));
+    assert!(
+        cookies
+            .iter()
+            .all(|cookie| cookie.contains("HttpOnly; Secure"))
+    );
 
-    let token = result.unwrap();
-    assert!(!token.is_empty());
+    // Tokens are only in the cookies
+    let body = response_json(response).await;
+    assert_eq!(body["auth_token"], "");
+    assert!(body.get("refresh_token").is_none());
+    assert_eq!(body["auth_user"]["id"], user.id.to_string());
 }
 
 #[tokio::test]
@@ -1,6 +1,6 @@
 use crate::core::AppState;
 use crate::errors::AppError;
-use crate::middleware::JwtAuth;
+use crate::middleware::{JwtAuth, VerifiedEmail};
 use crate::models::payment::CreatePaymentIntentRequest;
 use axum::{
     Json,
@@ -30,11 +30,14 @@ pub async fn get_payment_status_handler(
 
 /// Create Stripe payment intent
 ///
+/// Only users with a verified email may pay, so receipts reach their owner.
+///
 /// # Errors
 ///
-/// Returns an error if the payment service fails to create payment intent
+/// Returns `AppError::Forbidden` if the user has not verified their email, or
+/// an error if the payment service fails to create payment intent
 pub async fn create_payment_intent_handler(
-    auth: JwtAuth,
+    auth: VerifiedEmail,
     State(app_state): State<Arc<AppState>>,
     Json(request): Json<CreatePaymentIntentRequest>,
 ) -> Result<impl IntoResponse, AppError> {
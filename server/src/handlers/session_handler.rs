@@ -0,0 +1,167 @@
+// kanbain/server/src/handlers/session_handler.rs
+
+//! Session HTTP handlers
+//!
+//! Endpoints for refreshing access tokens, logging out, and listing or revoking
+//! the current user's sessions.
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        AppState,
+        session_cookies::{
+            REFRESH_TOKEN_COOKIE, clear_session_cookies, read_cookie, session_cookies,
+        },
+    },
+    errors::{AppError, AppResult},
+    middleware::JwtAuth,
+    models::session::{RefreshTokenRequest, SessionTokenResponse},
+};
+
+/// Handler for POST /api/auth/refresh - exchanges a refresh token for new tokens
+///
+/// The presented refresh token is consumed and a new one is returned. Presenting a
+/// refresh token that was already used revokes the whole session.
+///
+/// Requests without a refresh token in the body use the refresh token cookie of
+/// the cookie session mode, and get the new tokens as cookies too.
+///
+/// # Errors
+/// Returns `AppError::Unauthorized` if the refresh token is missing, invalid,
+/// expired, reused, or the session was revoked
+#[tracing::instrument(skip(state, headers, request), err(Debug))]
+pub async fn refresh_token_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<RefreshTokenRequest>,
+) -> AppResult<Response> {
+    let from_cookie = request.refresh_token.is_none();
+    let refresh_token = request
+        .refresh_token
+        .as_deref()
+        .or_else(|| read_cookie(&headers, REFRESH_TOKEN_COOKIE))
+        .ok_or_else(|| AppError::Unauthorized("Missing refresh token".to_string()))?;
+
+    let rotated = state.session.rotate_refresh_token(refresh_token).await?;
+
+    let user = match state.user.find_by_id(rotated.user_id).await {
+        Ok(user) => user,
+        Err(AppError::UserNotFound) => {
+            return Err(AppError::Unauthorized("User no longer exists".to_string()));
+        }
+        Err(e) => return Err(e),
+    };
+
+    // Roles are reloaded so grants and revocations apply from the next refresh
+    let roles = state.role.get_roles(user.id).await?;
+    let auth_token =
+        state
+            .auth
+            .generate_session_token(user.id, &user.email, &roles, rotated.session_id)?;
+
+    tracing::debug!(
+        "Refreshed session {} for {}",
+        rotated.session_id,
+        user.email
+    );
+
+    let expires_in = state.auth.access_token_ttl_seconds();
+    if from_cookie {
+        let cookies = session_cookies(&auth_token, expires_in, &rotated.refresh_token);
+        return Ok((
+            cookies,
+            Json(serde_json::json!({ "expires_in": expires_in })),
+        )
+            .into_response());
+    }
+
+    Ok(Json(SessionTokenResponse {
+        auth_token,
+        refresh_token: rotated.refresh_token,
+        expires_in,
+    })
+    .into_response())
+}
+
+/// Handler for POST /api/auth/logout - ends the session the request was made with
+///
+/// Session cookies of the cookie session mode are cleared as well.
+///
+/// # Errors
+/// Returns `AppError::BadRequest` if the token is not bound to a session, or an
+/// error if the session cannot be revoked
+#[tracing::instrument(skip(state, auth), fields(user_id = %auth.user.user_id), err(Debug))]
+pub async fn logout_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+) -> AppResult<impl IntoResponse> {
+    let session_id = auth.user.session_id.ok_or_else(|| {
+        AppError::BadRequest("Token is not associated with a session".to_string())
+    })?;
+
+    state
+        .session
+        .revoke_session(auth.user.user_id, session_id)
+        .await?;
+
+    Ok((
+        clear_session_cookies(),
+        Json(serde_json::json!({
+            "success": true,
+            "message": "Logged out successfully"
+        })),
+    ))
+}
+
+/// Handler for GET /api/auth/sessions - lists the current user's active sessions
+///
+/// # Errors
+/// Returns an error if the sessions cannot be loaded
+pub async fn list_sessions_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+) -> AppResult<impl IntoResponse> {
+    let sessions = state
+        .session
+        .list_sessions(auth.user.user_id, auth.user.session_id)
+        .await?;
+
+    Ok(Json(serde_json::json!({ "sessions": sessions })))
+}
+
+/// Handler for DELETE /api/auth/sessions/{id} - revokes one of the current user's sessions
+///
+/// Access tokens issued for the session stop working immediately.
+///
+/// # Errors
+/// Returns `AppError::NotFound` if the session does not exist, belongs to another
+/// user or was already revoked
+pub async fn revoke_session_handler(
+    State(state): State<Arc<AppState>>,
+    auth: JwtAuth,
+    Path(session_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let session_id = Uuid::parse_str(&session_id)
+        .map_err(|_| AppError::BadRequest("Invalid session ID".to_string()))?;
+
+    if !state
+        .session
+        .revoke_session(auth.user.user_id, session_id)
+        .await?
+    {
+        return Err(AppError::NotFound("Session not found".to_string()));
+    }
+
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "message": "Session revoked successfully"
+    })))
+}
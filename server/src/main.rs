@@ -8,7 +8,10 @@ use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberI
 
 // Use the library crate instead of re-declaring modules
 use server::errors;
-use server::services::{AuthService, InviteService, OAuthService, UserServiceImpl};
+use server::services::{
+    AccountEmailService, AiSessionService, AuthService, CliAuthService, FileService, InviteService,
+    OAuthService, RoleService, UserServiceImpl,
+};
 
 /// Initialize tracing/logging
 fn initialize_tracing() {
@@ -38,10 +41,121 @@ async fn run_migrations(db_pool: &sqlx::SqlitePool) -> Result<(), errors::AppErr
     Ok(())
 }
 
-/// Set up the OAuth state cleanup scheduler
-async fn setup_oauth_cleanup_scheduler(
-    oauth_service: &Arc<OAuthService>,
+/// Grant the admin role to registered users listed in `ADMIN_EMAILS`
+///
+/// `ADMIN_EMAILS` is a comma-separated list of email addresses. Users who have
+/// not registered or verified their email yet are picked up on the next restart.
+async fn bootstrap_admins(db_pool: &sqlx::SqlitePool) -> Result<(), errors::AppError> {
+    let Ok(admin_emails) = env::var("ADMIN_EMAILS") else {
+        return Ok(());
+    };
+
+    let emails: Vec<String> = admin_emails
+        .split(',')
+        .map(|email| email.trim().to_lowercase())
+        .filter(|email| !email.is_empty())
+        .collect();
+
+    let granted = RoleService::new(db_pool.clone())
+        .bootstrap_admins(&emails)
+        .await?;
+    if granted > 0 {
+        info!(
+            "Granted admin role to {} user(s) from ADMIN_EMAILS",
+            granted
+        );
+    }
+    Ok(())
+}
+
+/// Add the job that deletes stored file contents no file uses any more
+async fn add_file_cleanup_job(
+    scheduler: &JobScheduler,
+    file_service: &Arc<FileService>,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let file_service_for_cleanup = file_service.clone();
+    scheduler
+        .add(
+            Job::new_async("45 0 * * * *", move |_uuid, _l| {
+                let file_service = file_service_for_cleanup.clone();
+                Box::pin(async move {
+                    match file_service.collect_garbage().await {
+                        Ok(deleted_count) => {
+                            if deleted_count > 0 {
+                                tracing::info!("Deleted {} unused stored files", deleted_count);
+                            }
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to delete unused stored files: {:?}", e);
+                        }
+                    }
+                })
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to create file cleanup job: {:?}", e);
+                Box::new(e) as Box<dyn std::error::Error>
+            })?,
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to add file cleanup job to scheduler: {:?}", e);
+            Box::new(e) as Box<dyn std::error::Error>
+        })?;
+    info!("Stored file cleanup job scheduled to run every hour");
+    Ok(())
+}
+
+/// Add the job that deletes expired and used email verification and password
+/// reset tokens
+async fn add_account_email_cleanup_job(
+    scheduler: &JobScheduler,
+    account_email_service: &Arc<AccountEmailService>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let account_email_service_for_cleanup = account_email_service.clone();
+    scheduler
+        .add(
+            Job::new_async("15 0 * * * *", move |_uuid, _l| {
+                let account_email_service = account_email_service_for_cleanup.clone();
+                Box::pin(async move {
+                    match account_email_service.cleanup_tokens().await {
+                        Ok(deleted_count) => {
+                            if deleted_count > 0 {
+                                tracing::info!(
+                                    "Deleted {} expired or used account email tokens",
+                                    deleted_count
+                                );
+                            }
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to delete account email tokens: {:?}", e);
+                        }
+                    }
+                })
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to create account email token cleanup job: {:?}", e);
+                Box::new(e) as Box<dyn std::error::Error>
+            })?,
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!(
+                "Failed to add account email token cleanup job to scheduler: {:?}",
+                e
+            );
+            Box::new(e) as Box<dyn std::error::Error>
+        })?;
+    info!("Account email token cleanup job scheduled to run every hour");
+    Ok(())
+}
+
+/// Set up the scheduler that cleans up expired OAuth states and CLI login flows
+/// and expires idle AI sessions
+async fn setup_cleanup_scheduler(
+    oauth_service: &Arc<OAuthService>,
+    cli_auth_service: &Arc<CliAuthService>,
+    ai_session_service: &Arc<AiSessionService>,
+) -> Result<JobScheduler, Box<dyn std::error::Error>> {
     let scheduler = JobScheduler::new().await.map_err(|e| {
         tracing::error!("Failed to create job scheduler: {:?}", e);
         Box::new(e) as Box<dyn std::error::Error>
@@ -76,13 +190,93 @@ async fn setup_oauth_cleanup_scheduler(
             Box::new(e) as Box<dyn std::error::Error>
         })?;
 
+    let cli_auth_service_for_cleanup = cli_auth_service.clone();
+    scheduler
+        .add(
+            Job::new_async("30 */10 * * * *", move |_uuid, _l| {
+                let cli_auth_service = cli_auth_service_for_cleanup.clone();
+                Box::pin(async move {
+                    match cli_auth_service.cleanup_expired_flows().await {
+                        Ok(deleted_count) => {
+                            if deleted_count > 0 {
+                                tracing::info!(
+                                    "Cleaned up {} expired CLI login flows",
+                                    deleted_count
+                                );
+                            }
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to cleanup expired CLI login flows: {:?}", e);
+                        }
+                    }
+                })
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to create CLI flow cleanup job: {:?}", e);
+                Box::new(e) as Box<dyn std::error::Error>
+            })?,
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to add CLI flow cleanup job to scheduler: {:?}", e);
+            Box::new(e) as Box<dyn std::error::Error>
+        })?;
+
+    let ai_session_service_for_cleanup = ai_session_service.clone();
+    scheduler
+        .add(
+            Job::new_async("15 */5 * * * *", move |_uuid, _l| {
+                let ai_session_service = ai_session_service_for_cleanup.clone();
+                Box::pin(async move {
+                    match ai_session_service.expire_sessions().await {
+                        Ok(expired_count) => {
+                            if expired_count > 0 {
+                                tracing::info!("Expired {} idle AI sessions", expired_count);
+                            }
+                        }
+                        Err(e) => {
+                            tracing::error!("Failed to expire idle AI sessions: {:?}", e);
+                        }
+                    }
+                })
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to create AI session expiry job: {:?}", e);
+                Box::new(e) as Box<dyn std::error::Error>
+            })?,
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to add AI session expiry job to scheduler: {:?}", e);
+            Box::new(e) as Box<dyn std::error::Error>
+        })?;
+
     scheduler.start().await.map_err(|e| {
         tracing::error!("Failed to start job scheduler: {:?}", e);
         Box::new(e) as Box<dyn std::error::Error>
     })?;
 
-    info!("OAuth state cleanup job scheduled to run every 10 minutes");
-    Ok(())
+    info!("OAuth state and CLI login flow cleanup jobs scheduled to run every 10 minutes");
+    info!("AI session expiry job scheduled to run every 5 minutes");
+    Ok(scheduler)
+}
+
+/// Check that at least one AI provider is configured
+fn check_ai_provider_configured() -> Result<(), errors::AppError> {
+    let keys = [
+        "OPENROUTER_API_KEY",
+        "OPENAI_COMPAT_ENDPOINT",
+        "ANTHROPIC_API_KEY",
+        "AI_MOCK_PROVIDER",
+        "AI_MOCK_FIXTURE",
+    ];
+    if keys.iter().any(|key| std::env::var(key).is_ok()) {
+        Ok(())
+    } else {
+        Err(errors::AppError::ConfigError(
+            "One of OPENROUTER_API_KEY, OPENAI_COMPAT_ENDPOINT, ANTHROPIC_API_KEY or AI_MOCK_PROVIDER must be set".to_string(),
+        ))
+    }
 }
 
 #[tokio::main]
@@ -98,10 +292,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // -------- Check the correct environment is setup --------
-    // Check if we have a real LLM API key
-    std::env::var("OPENROUTER_API_KEY").expect("OPENROUTER_API_KEY must be set!");
-    // Check if we have a model configured
-    std::env::var("AI_DEFAULT_MODEL").expect("AI_DEFAULT_MODEL must be set!");
+    // Check that at least one AI provider is configured
+    check_ai_provider_configured()?;
 
     // Initialize tracing (logging)
     initialize_tracing();
@@ -151,6 +343,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Run database migrations
     run_migrations(&db_pool).await?;
 
+    // Grant admin role to configured administrators
+    bootstrap_admins(&db_pool).await?;
+
     // Initialize services and application state
     let user_service = Arc::new(UserServiceImpl::new(db_pool.clone()));
     let auth_service = Arc::new(AuthService::new().map_err(|e| {
@@ -163,8 +358,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Box::new(e) as Box<dyn std::error::Error>
     })?);
 
-    // Set up scheduled cleanup task for OAuth states
-    setup_oauth_cleanup_scheduler(&oauth_service).await?;
+    // Set up scheduled cleanup tasks for OAuth states and CLI login flows
+    let cli_auth_service = Arc::new(CliAuthService::new(db_pool.clone()));
+    let ai_session_service = Arc::new(AiSessionService::new(db_pool.clone()));
+    let file_service = Arc::new(FileService::from_env(db_pool.clone())?);
+    let scheduler =
+        setup_cleanup_scheduler(&oauth_service, &cli_auth_service, &ai_session_service).await?;
+    add_file_cleanup_job(&scheduler, &file_service).await?;
+    let account_email_service = Arc::new(AccountEmailService::from_env(db_pool.clone())?);
+    add_account_email_cleanup_job(&scheduler, &account_email_service).await?;
 
     // Create the main application router
     let app = server::routes::create_router(
@@ -282,10 +484,10 @@ fn init_database_if_needed(database_url: &str) -> Result<(), Box<dyn std::error:
 
 fn extract_sqlite_path(database_url: &str) -> Option<String> {
     // Parse SQLite URL format: sqlite:path/to/file.db?options
-    if let Some(stripped) = database_url.strip_prefix("sqlite:") {
-        if let Some(path_part) = stripped.split('?').next() {
-            return Some(path_part.to_string());
-        }
+    if let Some(stripped) = database_url.strip_prefix("sqlite:")
+        && let Some(path_part) = stripped.split('?').next()
+    {
+        return Some(path_part.to_string());
     }
     None
 }
@@ -2,63 +2,76 @@
 
 //! JWT authentication middleware and extractors for Axum
 //!
-//! This module provides JWT token extraction and validation for protected endpoints.
+//! This module provides JWT token extraction and validation for protected endpoints,
+//! and role-based authorization extractors built on top of it.
 
 use axum::{
     Json,
-    extract::FromRequestParts,
-    http::{StatusCode, request},
+    extract::{FromRef, FromRequestParts},
+    http::{HeaderMap, StatusCode, request},
     response::{IntoResponse, Response},
 };
-use axum_extra::{
-    TypedHeader,
-    headers::{Authorization, authorization::Bearer},
-};
+use axum_extra::headers::{Authorization, HeaderMapExt, authorization::Bearer};
 use serde_json::json;
+use std::marker::PhantomData;
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::{core::AppState, errors::AppError};
+use crate::{
+    core::{
+        AppState,
+        session_cookies::{ACCESS_TOKEN_COOKIE, read_cookie},
+    },
+    errors::AppError,
+    models::role::{Permission, Role, roles_grant},
+};
 
 /// Represents the authenticated user extracted from JWT
 #[derive(Debug, Clone)]
 pub struct AuthenticatedUser {
     pub user_id: Uuid,
     pub email: String,
+    /// Roles embedded in the token when it was issued
+    pub roles: Vec<Role>,
+    /// Session the token was issued for, if it is bound to one
+    pub session_id: Option<Uuid>,
 }
 
 /// JWT token extractor that validates and extracts user information from the Authorization header
+///
+/// Browser clients in the cookie session mode send the token in the access token
+/// cookie instead.
+///
+/// Works with any router state the `AppState` can be taken from, such as the
+/// OAuth routes' state.
 pub struct JwtAuth {
     pub user: AuthenticatedUser,
 }
 
-impl FromRequestParts<Arc<AppState>> for JwtAuth {
+impl<S> FromRequestParts<S> for JwtAuth
+where
+    Arc<AppState>: FromRef<S>,
+    S: Send + Sync,
+{
     type Rejection = Response;
 
     async fn from_request_parts(
         parts: &mut request::Parts,
-        state: &Arc<AppState>,
+        state: &S,
     ) -> Result<Self, Self::Rejection> {
-        // We already have the app state
-        let app_state = state;
-
-        // Extract the Authorization header
-        let authorization_header =
-            TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, &())
-                .await
-                .map_err(|_| {
-                    tracing::debug!("Missing or invalid Authorization header");
-                    (
-                        StatusCode::UNAUTHORIZED,
-                        Json(json!({"error": "Missing or invalid authorization header"})),
-                    )
-                        .into_response()
-                })?;
+        let app_state = Arc::<AppState>::from_ref(state);
 
-        let token = authorization_header.token();
+        let token = request_token(&parts.headers).ok_or_else(|| {
+            tracing::debug!("Missing or invalid Authorization header");
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({"error": "Missing or invalid authorization header"})),
+            )
+                .into_response()
+        })?;
 
         // Validate the JWT token
-        let claims = app_state.auth.validate_token(token).map_err(|e| {
+        let claims = app_state.auth.validate_token(&token).map_err(|e| {
             tracing::warn!("JWT validation failed: {:?}", e);
             (
                 StatusCode::UNAUTHORIZED,
@@ -103,17 +116,205 @@ impl FromRequestParts<Arc<AppState>> for JwtAuth {
                 }
             })?;
 
+        // Tokens bound to a session stop working once the session is revoked
+        let session_id = match claims.sid.as_deref() {
+            Some(sid) => {
+                let session_id = Uuid::parse_str(sid).map_err(|e| {
+                    tracing::error!("Failed to parse session ID from JWT claims: {}", e);
+                    (
+                        StatusCode::UNAUTHORIZED,
+                        Json(json!({"error": "Invalid token format"})),
+                    )
+                        .into_response()
+                })?;
+
+                let active = app_state
+                    .session
+                    .is_session_active(session_id)
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("Database error during session verification: {:?}", e);
+                        (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            Json(json!({"error": "Internal server error"})),
+                        )
+                            .into_response()
+                    })?;
+                if !active {
+                    tracing::warn!("JWT presented for revoked session: {}", session_id);
+                    return Err((
+                        StatusCode::UNAUTHORIZED,
+                        Json(json!({"error": "Session has been revoked"})),
+                    )
+                        .into_response());
+                }
+
+                Some(session_id)
+            }
+            None => None,
+        };
+
         tracing::debug!("Successfully authenticated user: {}", claims.email);
 
         Ok(JwtAuth {
             user: AuthenticatedUser {
                 user_id,
                 email: claims.email,
+                roles: claims.roles,
+                session_id,
             },
         })
     }
 }
 
+/// Token a request was made with
+///
+/// Bearer tokens take precedence over the session cookie of cookie-mode clients.
+fn request_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .typed_get::<Authorization<Bearer>>()
+        .map(|authorization| authorization.token().to_string())
+        .or_else(|| read_cookie(headers, ACCESS_TOKEN_COOKIE).map(str::to_string))
+}
+
+/// Check that both the token and the user's current roles satisfy `allowed`
+///
+/// Roles in the token must grant access so a token cannot be used beyond what
+/// it was issued for; the database is checked too so revocations take effect
+/// immediately rather than when the token expires. Users whose roles require
+/// two-factor authentication must have enabled it.
+async fn authorize(
+    state: &Arc<AppState>,
+    user: &AuthenticatedUser,
+    allowed: impl Fn(&[Role]) -> bool,
+) -> Result<(), Response> {
+    if !allowed(&user.roles) {
+        tracing::warn!("Access denied for user {}: token lacks role", user.email);
+        return Err(AppError::Forbidden("Insufficient permissions".to_string()).into_response());
+    }
+
+    let current_roles = state.role.get_roles(user.user_id).await.map_err(|e| {
+        tracing::error!("Failed to load roles for user {}: {:?}", user.email, e);
+        e.into_response()
+    })?;
+
+    if !allowed(&current_roles) {
+        tracing::warn!("Access denied for user {}: role revoked", user.email);
+        return Err(AppError::Forbidden("Insufficient permissions".to_string()).into_response());
+    }
+
+    // Roles can be set to require a second factor before they are used
+    state
+        .mfa
+        .check_requirement(user.user_id, &current_roles)
+        .await
+        .map_err(|e| {
+            tracing::warn!("Access denied for user {}: {:?}", user.email, e);
+            e.into_response()
+        })?;
+
+    Ok(())
+}
+
+/// Extractor that only admits users holding the admin role
+pub struct AdminOnly {
+    pub user: AuthenticatedUser,
+}
+
+impl FromRequestParts<Arc<AppState>> for AdminOnly {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let JwtAuth { user } = JwtAuth::from_request_parts(parts, state).await?;
+        authorize(state, &user, |roles| roles.contains(&Role::Admin)).await?;
+
+        Ok(AdminOnly { user })
+    }
+}
+
+/// Permission that a `RequirePermission` extractor checks for
+pub trait PermissionRequirement: Send + Sync {
+    const PERMISSION: Permission;
+}
+
+/// Requires `Permission::ManageInvites`
+pub struct CanManageInvites;
+
+impl PermissionRequirement for CanManageInvites {
+    const PERMISSION: Permission = Permission::ManageInvites;
+}
+
+/// Requires `Permission::ManageRoles`
+pub struct CanManageRoles;
+
+impl PermissionRequirement for CanManageRoles {
+    const PERMISSION: Permission = Permission::ManageRoles;
+}
+
+/// Extractor that only admits users whose roles grant the permission `P`
+///
+/// ```ignore
+/// async fn handler(auth: RequirePermission<CanManageInvites>) { /* ... */ }
+/// ```
+pub struct RequirePermission<P: PermissionRequirement> {
+    pub user: AuthenticatedUser,
+    _permission: PhantomData<P>,
+}
+
+impl<P: PermissionRequirement> FromRequestParts<Arc<AppState>> for RequirePermission<P> {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let JwtAuth { user } = JwtAuth::from_request_parts(parts, state).await?;
+        authorize(state, &user, |roles| roles_grant(roles, P::PERMISSION)).await?;
+
+        Ok(RequirePermission {
+            user,
+            _permission: PhantomData,
+        })
+    }
+}
+
+/// Extractor that only admits users who verified their email address
+///
+/// Guards actions that reach other people or spend money, so an account
+/// registered with someone else's address cannot act in their name. Accounts
+/// created before email verification existed are admitted too.
+pub struct VerifiedEmail {
+    pub user: AuthenticatedUser,
+}
+
+impl FromRequestParts<Arc<AppState>> for VerifiedEmail {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let JwtAuth { user } = JwtAuth::from_request_parts(parts, state).await?;
+        let verified = state
+            .account_email
+            .has_verified_access(user.user_id)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        if !verified {
+            return Err(
+                AppError::Forbidden("Verify your email address to do this".to_string())
+                    .into_response(),
+            );
+        }
+
+        Ok(VerifiedEmail { user })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -123,6 +324,8 @@ mod tests {
         let user = AuthenticatedUser {
             user_id: Uuid::new_v4(),
             email: "test@example.com".to_string(),
+            roles: vec![],
+            session_id: None,
         };
         assert_eq!(user.email, "test@example.com");
     }
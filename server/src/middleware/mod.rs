@@ -2,8 +2,13 @@
 
 pub mod auth_middleware;
 pub mod payment_middleware;
+pub mod quota_middleware;
 
 // Re-export for convenience
-pub use auth_middleware::JwtAuth;
+pub use auth_middleware::{
+    AdminOnly, AuthenticatedUser, CanManageInvites, CanManageRoles, JwtAuth, RequirePermission,
+    VerifiedEmail,
+};
+pub use quota_middleware::{check_ai_quota, check_embedding_quota};
 // PaymentRequired will be used when we update the AI handlers
 // pub use payment_middleware::PaymentRequired;
@@ -51,17 +51,17 @@ pub async fn check_payment_status(
     }
 
     // Check if payment subscription has expired
-    if let Some(subscription_end_date) = payment_status.subscription_end_date {
-        if subscription_end_date < chrono::Utc::now() {
-            tracing::warn!(
-                "Access denied for user {} ({}): payment subscription expired at {}",
-                user_email,
-                user_id,
-                subscription_end_date
-            );
-
-            return Err(AppError::PaymentRequired);
-        }
+    if let Some(subscription_end_date) = payment_status.subscription_end_date
+        && subscription_end_date < chrono::Utc::now()
+    {
+        tracing::warn!(
+            "Access denied for user {} ({}): payment subscription expired at {}",
+            user_email,
+            user_id,
+            subscription_end_date
+        );
+
+        return Err(AppError::PaymentRequired);
     }
 
     Ok(())
@@ -152,6 +152,8 @@ mod tests {
         let auth_user = AuthenticatedUser {
             user_id: user.id,
             email: user.email,
+            roles: vec![],
+            session_id: None,
         };
 
         let result = check_payment_status(&state, &auth_user).await;
@@ -185,6 +187,8 @@ mod tests {
         let auth_user = AuthenticatedUser {
             user_id: user.id,
             email: user.email,
+            roles: vec![],
+            session_id: None,
         };
 
         let result = check_payment_status(&state, &auth_user).await;
@@ -204,6 +208,8 @@ mod tests {
         let auth_user = AuthenticatedUser {
             user_id: user.id,
             email: user.email,
+            roles: vec![],
+            session_id: None,
         };
 
         let result = check_payment_status(&state, &auth_user).await;
@@ -243,6 +249,8 @@ mod tests {
         let auth_user = AuthenticatedUser {
             user_id: user.id,
             email: user.email,
+            roles: vec![],
+            session_id: None,
         };
 
         let result = check_payment_status(&state, &auth_user).await;
@@ -289,6 +297,8 @@ mod tests {
         let auth_user = AuthenticatedUser {
             user_id: user.id,
             email: user.email,
+            roles: vec![],
+            session_id: None,
         };
 
         let result = check_payment_status(&state, &auth_user).await;
@@ -326,6 +336,8 @@ mod tests {
         let auth_user = AuthenticatedUser {
             user_id: user.id,
             email: user.email,
+            roles: vec![],
+            session_id: None,
         };
 
         let result = check_payment_status(&state, &auth_user).await;
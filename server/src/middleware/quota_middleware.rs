@@ -0,0 +1,112 @@
+//! AI usage quota checks for handlers that call an AI provider
+//!
+//! Embedding calls count as provider calls too. Chat requests that retrieve
+//! document chunks are covered by the chat's own check.
+
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    core::AppState,
+    errors::{AppError, AppResult},
+    services::quota_service::{Plan, QuotaStatus},
+};
+
+/// Find the plan a user is on: `paid` with an active subscription, `invited`
+/// if they were invited and `free` otherwise
+///
+/// # Errors
+///
+/// Returns an error if the user does not exist or a lookup fails
+pub async fn resolve_plan(state: &Arc<AppState>, user_id: Uuid) -> AppResult<Plan> {
+    let payment_status = state.payment.get_user_payment_status(user_id).await?;
+    let subscription_current = payment_status
+        .subscription_end_date
+        .is_none_or(|end_date| end_date > chrono::Utc::now());
+    if payment_status.has_active_payment && subscription_current {
+        return Ok(Plan::Paid);
+    }
+
+    let user = state.user.find_by_id(user_id).await?;
+    let invite = state.invite.get_user_invite(&user.email).await?;
+    // An invite that was used stays valid after it would have expired
+    if invite.is_some_and(|invite| invite.used_at.is_some() || !invite.is_expired()) {
+        return Ok(Plan::Invited);
+    }
+
+    Ok(Plan::Free)
+}
+
+/// Check that a user has quota left for another AI request
+///
+/// # Errors
+///
+/// Returns `AppError::TooManyRequests` if a limit of the user's quota has been
+/// reached, or an error if a lookup fails
+pub async fn check_ai_quota(state: &Arc<AppState>, user_id: &str) -> AppResult<QuotaStatus> {
+    let uuid = Uuid::parse_str(user_id)
+        .map_err(|_| AppError::Unauthorized("Invalid user ID".to_string()))?;
+    let plan = resolve_plan(state, uuid).await?;
+    state.quotas.check(user_id, plan).await
+}
+
+/// Check that a user has quota left before texts are embedded
+///
+/// Local embeddings are computed in-process at no cost, so they are exempt;
+/// only a configured provider embedding model is checked.
+///
+/// # Errors
+///
+/// Returns `AppError::TooManyRequests` if the provider embeds and a limit of
+/// the user's quota has been reached, or an error if a lookup fails
+pub async fn check_embedding_quota(state: &Arc<AppState>, user_id: &str) -> AppResult<()> {
+    if state.ai.read().await.embeds_locally() {
+        return Ok(());
+    }
+    check_ai_quota(state, user_id).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::providers::MockProvider;
+    use crate::handlers::auth_handler::RegisterUserPayload;
+    use crate::services::{AiService, quota_service::QuotaLimits};
+    use crate::test_helpers::create_test_app_state;
+    use sqlx::SqlitePool;
+
+    #[sqlx::test]
+    async fn test_check_embedding_quota_exempts_local_embeddings(pool: SqlitePool) {
+        let state = create_test_app_state(&pool);
+        let user = state
+            .user
+            .create_user(&RegisterUserPayload {
+                email: "embedder@example.com".to_string(),
+                password: "test_password123".to_string(),
+            })
+            .await
+            .expect("Failed to create user");
+        let user_id = user.id.to_string();
+        state
+            .quotas
+            .set_user_limits(
+                &user_id,
+                &QuotaLimits {
+                    daily_tokens: Some(0),
+                    ..QuotaLimits::default()
+                },
+                "test",
+            )
+            .await
+            .expect("Failed to set limits");
+
+        assert!(check_embedding_quota(&state, &user_id).await.is_ok());
+
+        *state.ai.write().await = AiService::with_provider(Arc::new(MockProvider::new()))
+            .expect("Failed to create AI service")
+            .with_embedding_model(Some("text-embedding-test".to_string()));
+        let result = check_embedding_quota(&state, &user_id).await;
+        assert!(matches!(result, Err(AppError::TooManyRequests { .. })));
+    }
+}
@@ -0,0 +1,69 @@
+//! Email verification and password reset
+//!
+//! Both flows mail the user a link with a one-time token. Only the token's hash
+//! is stored in `account_email_tokens`; a token works once, for one purpose,
+//! until it expires, and requesting a new one invalidates the previous one.
+
+use chrono::Duration;
+use serde::Deserialize;
+use validator::Validate;
+
+/// What an account email token confirms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountEmailPurpose {
+    /// The user receives mail at the account's email address
+    VerifyEmail,
+    /// The user may set a new password
+    ResetPassword,
+}
+
+impl AccountEmailPurpose {
+    /// Value stored in the database
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::VerifyEmail => "verify_email",
+            Self::ResetPassword => "reset_password",
+        }
+    }
+
+    /// How long a token stays valid
+    #[must_use]
+    pub fn ttl(self) -> Duration {
+        match self {
+            Self::VerifyEmail => Duration::hours(24),
+            Self::ResetPassword => Duration::hours(1),
+        }
+    }
+
+    /// Client page the emailed link opens
+    #[must_use]
+    pub fn client_path(self) -> &'static str {
+        match self {
+            Self::VerifyEmail => "/auth/verify-email",
+            Self::ResetPassword => "/auth/reset-password",
+        }
+    }
+}
+
+/// Request to confirm an email address with a mailed token
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConfirmEmailRequest {
+    pub token: String,
+}
+
+/// Request to mail a password reset link
+#[derive(Debug, Clone, Deserialize, Validate)]
+pub struct PasswordResetRequest {
+    #[validate(email(message = "Email must be a valid email address."))]
+    pub email: String,
+}
+
+/// Request to set a new password with a mailed token
+#[derive(Debug, Clone, Deserialize, Validate)]
+pub struct ConfirmPasswordResetRequest {
+    pub token: String,
+
+    #[validate(length(min = 12, message = "Password must be at least 12 characters long."))]
+    pub password: String,
+}
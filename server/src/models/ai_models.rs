@@ -27,6 +27,19 @@ pub struct AiMessage {
     pub token_count: Option<i64>,
     pub created_at: String,       // ISO8601 timestamp
     pub metadata: Option<String>, // JSON string
+    /// Message this one follows; messages with the same parent are branches
+    pub parent_message_id: Option<String>,
+}
+
+/// Summary of a conversation up to a message, stored under `summary` in the
+/// message's metadata
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConversationSummary {
+    pub content: String,
+    pub token_count: i64,
+    /// Messages the summary covers, up to and including this one along its branch
+    pub message_count: i64,
+    pub created_at: String, // ISO8601 timestamp
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -42,6 +55,8 @@ pub struct AiUsage {
     pub created_at: String, // ISO8601 timestamp
     pub request_id: Option<String>,
     pub duration_ms: Option<i64>,
+    /// Exact cost in millionths of a cent, of which `cost_cents` is the rounding
+    pub cost_microcents: Option<i64>,
 }
 
 // DTOs for API requests/responses
@@ -71,6 +86,7 @@ pub struct MessageResponse {
     pub content: String,
     pub token_count: Option<i64>,
     pub created_at: String,
+    pub parent_message_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -83,6 +99,26 @@ pub struct ConversationWithMessages {
 pub struct CreateMessageRequest {
     pub role: String,
     pub content: String,
+    /// Tokens in `content` for the conversation's model
+    #[serde(default)]
+    pub token_count: Option<i64>,
+    /// Message to follow, forking a branch if it already has replies;
+    /// defaults to the conversation's latest message
+    #[serde(default)]
+    pub parent_message_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConversationSearchQuery {
+    pub q: String,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SimilarConversation {
+    pub conversation_id: String,
+    pub title: Option<String>,
+    pub similarity_score: f32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -145,6 +181,7 @@ impl AiMessage {
             token_count: None,
             created_at: Utc::now().to_rfc3339(),
             metadata: None,
+            parent_message_id: None,
         }
     }
 
@@ -153,6 +190,25 @@ impl AiMessage {
         self.token_count = Some(token_count);
         self
     }
+
+    #[must_use]
+    pub fn with_parent(mut self, parent_message_id: String) -> Self {
+        self.parent_message_id = Some(parent_message_id);
+        self
+    }
+}
+
+impl From<AiMessage> for MessageResponse {
+    fn from(message: AiMessage) -> Self {
+        Self {
+            id: message.id,
+            role: message.role,
+            content: message.content,
+            token_count: message.token_count,
+            created_at: message.created_at,
+            parent_message_id: message.parent_message_id,
+        }
+    }
 }
 
 impl AiUsage {
@@ -170,6 +226,7 @@ impl AiUsage {
             created_at: Utc::now().to_rfc3339(),
             request_id: None,
             duration_ms: None,
+            cost_microcents: None,
         }
     }
 
@@ -185,6 +242,14 @@ impl AiUsage {
         self
     }
 
+    /// Set the exact cost, and the cost in cents rounded from it
+    #[must_use]
+    pub fn with_cost_microcents(mut self, cost_microcents: i64) -> Self {
+        self.cost_microcents = Some(cost_microcents);
+        self.cost_cents = Some((cost_microcents + 500_000) / 1_000_000);
+        self
+    }
+
     #[must_use]
     pub fn with_request_id(mut self, request_id: String) -> Self {
         self.request_id = Some(request_id);
@@ -280,6 +345,7 @@ mod tests {
         assert!(message.token_count.is_none());
         assert!(!message.created_at.is_empty());
         assert!(message.metadata.is_none());
+        assert!(message.parent_message_id.is_none());
     }
 
     #[test]
@@ -316,6 +382,7 @@ mod tests {
         assert_eq!(usage.total_tokens, 250);
         assert!(usage.conversation_id.is_none());
         assert!(usage.cost_cents.is_none());
+        assert!(usage.cost_microcents.is_none());
         assert!(!usage.created_at.is_empty());
         assert!(usage.request_id.is_none());
         assert!(usage.duration_ms.is_none());
@@ -337,6 +404,15 @@ mod tests {
         assert_eq!(usage.cost_cents, Some(325));
     }
 
+    #[test]
+    fn test_ai_usage_with_cost_microcents() {
+        let usage = AiUsage::new("user_123".to_string(), "gpt-4o".to_string(), 1_000, 500)
+            .with_cost_microcents(1_750_000);
+
+        assert_eq!(usage.cost_microcents, Some(1_750_000));
+        assert_eq!(usage.cost_cents, Some(2));
+    }
+
     #[test]
     fn test_ai_usage_with_request_id() {
         let usage = AiUsage::new("user_123".to_string(), "gpt-4".to_string(), 100, 150)
@@ -410,6 +486,7 @@ mod tests {
             content: "Hello!".to_string(),
             token_count: Some(10),
             created_at: "2024-01-01T00:00:00Z".to_string(),
+            parent_message_id: None,
         };
 
         let json = serde_json::to_string(&response).expect("Failed to serialize");
@@ -437,6 +514,7 @@ mod tests {
                 content: "Hello".to_string(),
                 token_count: Some(5),
                 created_at: "2024-01-01T00:00:00Z".to_string(),
+                parent_message_id: None,
             },
             MessageResponse {
                 id: "msg_2".to_string(),
@@ -444,6 +522,7 @@ mod tests {
                 content: "Hi there!".to_string(),
                 token_count: Some(8),
                 created_at: "2024-01-01T00:00:01Z".to_string(),
+                parent_message_id: Some("msg_1".to_string()),
             },
         ];
 
@@ -463,6 +542,8 @@ mod tests {
         let request = CreateMessageRequest {
             role: "user".to_string(),
             content: "What is Rust?".to_string(),
+            token_count: Some(4),
+            parent_message_id: None,
         };
 
         let json = serde_json::to_string(&request).expect("Failed to serialize");
@@ -3,10 +3,11 @@
 use serde::{Deserialize, Serialize};
 
 /// Available AI personas for different conversation contexts
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum AiPersona {
     /// Business Analyst for requirements gathering and issue creation
+    #[default]
     BusinessAnalyst,
     /// Technical Support for troubleshooting
     TechnicalSupport,
@@ -49,12 +50,6 @@ impl AiPersona {
     }
 }
 
-impl Default for AiPersona {
-    fn default() -> Self {
-        Self::BusinessAnalyst
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -5,7 +5,24 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use crate::ai::models::analysis::DuplicateCheckResult;
+
 use super::ai_persona::AiPersona;
+use super::issue::{CreateIssueRequest, IssuePriority, IssueType};
+
+/// Minutes a session stays open without activity
+pub const SESSION_TTL_MINUTES: i64 = 30;
+
+/// What a session is working towards
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "TEXT")]
+#[serde(rename_all = "snake_case")]
+pub enum SessionType {
+    #[sqlx(rename = "issue_creation")]
+    IssueCreation,
+    #[sqlx(rename = "issue_update")]
+    IssueUpdate,
+}
 
 /// Session status
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
@@ -42,6 +59,7 @@ pub struct AiSession {
     pub user_id: String,
     #[serde(rename = "type")]
     #[sqlx(rename = "type")]
+    pub session_type: SessionType,
     pub status: SessionStatus,
     pub context: String,       // JSON string
     pub draft: Option<String>, // JSON string
@@ -133,7 +151,7 @@ pub struct SessionContext {
 }
 
 /// Issue draft structure (stored as JSON)
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct IssueDraft {
     pub title: Option<String>,
     pub description: Option<String>,
@@ -181,6 +199,106 @@ pub struct IssuePreview {
     pub priority: String,
     pub tags: Vec<String>,
     pub asset_count: usize,
+    /// Issues on the session's board that the draft may duplicate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicates: Option<DuplicateCheckResult>,
+}
+
+/// A session together with its conversation so far
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionDetailsResponse {
+    pub session_id: String,
+    pub status: SessionStatus,
+    pub persona: AiPersona,
+    pub context: SessionContext,
+    pub draft: Option<IssueDraft>,
+    pub messages: Vec<AiSessionMessage>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl IssueDraft {
+    /// Names of the fields an issue cannot be created without
+    #[must_use]
+    pub fn missing_fields(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if self
+            .title
+            .as_deref()
+            .is_none_or(|title| title.trim().is_empty())
+        {
+            missing.push("title");
+        }
+        if self
+            .description
+            .as_deref()
+            .is_none_or(|description| description.trim().is_empty())
+        {
+            missing.push("description");
+        }
+        missing
+    }
+}
+
+impl From<&IssueDraft> for IssuePreview {
+    fn from(draft: &IssueDraft) -> Self {
+        Self {
+            title: draft.title.clone().unwrap_or_default(),
+            description: draft.description.clone().unwrap_or_default(),
+            acceptance_criteria: draft.acceptance_criteria.clone(),
+            technical_notes: draft.technical_notes.clone(),
+            estimated_hours: draft.estimated_hours,
+            priority: draft
+                .priority
+                .clone()
+                .unwrap_or_else(|| "medium".to_string()),
+            tags: draft.tags.clone(),
+            asset_count: draft.assets.len(),
+            duplicates: None,
+        }
+    }
+}
+
+impl From<&IssueDraft> for CreateIssueRequest {
+    /// Build the issue a completed draft describes
+    ///
+    /// Acceptance criteria and technical notes become sections of the
+    /// description, and the estimate is rounded up to whole hours.
+    fn from(draft: &IssueDraft) -> Self {
+        let mut description = draft.description.clone().unwrap_or_default();
+        if !draft.acceptance_criteria.is_empty() {
+            description.push_str("\n\n## Acceptance criteria\n");
+            for criterion in &draft.acceptance_criteria {
+                description.push_str("\n- ");
+                description.push_str(criterion);
+            }
+        }
+        if let Some(notes) = draft
+            .technical_notes
+            .as_deref()
+            .filter(|n| !n.trim().is_empty())
+        {
+            description.push_str("\n\n## Technical notes\n\n");
+            description.push_str(notes);
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        let size_estimate = draft
+            .estimated_hours
+            .filter(|hours| hours.is_finite() && *hours >= 0.0)
+            .map(|hours| hours.ceil() as i64);
+
+        Self {
+            title: draft.title.clone().unwrap_or_default(),
+            description: Some(description),
+            issue_type: IssueType::Feature,
+            priority: draft
+                .priority
+                .as_deref()
+                .and_then(|priority| priority.parse().ok())
+                .unwrap_or(IssuePriority::Medium),
+            size_estimate,
+        }
+    }
 }
 
 impl AiSession {
@@ -195,6 +313,7 @@ impl AiSession {
         Self {
             id: format!("sess_{}", Uuid::new_v4()),
             user_id,
+            session_type: SessionType::IssueCreation,
             status: SessionStatus::Active,
             context: serde_json::to_string(&SessionContext::default())
                 .expect("Failed to serialize default context"),
@@ -202,7 +321,7 @@ impl AiSession {
             created_at: now,
             updated_at: now,
             completed_at: None,
-            expires_at: now + chrono::Duration::minutes(30), // 30 minute expiry
+            expires_at: now + chrono::Duration::minutes(SESSION_TTL_MINUTES),
         }
     }
 
@@ -449,6 +568,57 @@ mod tests {
         assert!(draft.tags.is_empty());
     }
 
+    #[test]
+    fn test_issue_draft_missing_fields_and_preview() {
+        let mut draft = IssueDraft {
+            title: Some("  ".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(draft.missing_fields(), vec!["title", "description"]);
+
+        draft.title = Some("Login page".to_string());
+        draft.description = Some("Let users sign in".to_string());
+        draft.assets.push("asset_1".to_string());
+        assert!(draft.missing_fields().is_empty());
+
+        let preview = IssuePreview::from(&draft);
+        assert_eq!(preview.title, "Login page");
+        assert_eq!(preview.priority, "medium");
+        assert_eq!(preview.asset_count, 1);
+    }
+
+    #[test]
+    fn test_issue_draft_to_issue_request() {
+        let draft = IssueDraft {
+            title: Some("Login page".to_string()),
+            description: Some("Let users sign in".to_string()),
+            acceptance_criteria: vec!["Wrong passwords are rejected".to_string()],
+            technical_notes: Some("Reuse the auth service".to_string()),
+            estimated_hours: Some(2.5),
+            priority: Some("High".to_string()),
+            ..Default::default()
+        };
+
+        let request = CreateIssueRequest::from(&draft);
+        assert_eq!(request.priority, IssuePriority::High);
+        assert_eq!(request.size_estimate, Some(3));
+        assert_eq!(
+            request.description.as_deref(),
+            Some(
+                "Let users sign in\n\n## Acceptance criteria\n\n- Wrong passwords are rejected\n\n## Technical notes\n\nReuse the auth service"
+            )
+        );
+
+        let vague = IssueDraft {
+            priority: Some("whenever".to_string()),
+            ..draft
+        };
+        assert_eq!(
+            CreateIssueRequest::from(&vague).priority,
+            IssuePriority::Medium
+        );
+    }
+
     #[test]
     fn test_finalize_action_serialization() {
         assert_eq!(
@@ -487,9 +657,11 @@ mod tests {
             priority: "medium".to_string(),
             tags: vec!["backend".to_string()],
             asset_count: 2,
+            duplicates: None,
         };
 
         let json = serde_json::to_string(&preview).expect("Failed to serialize");
+        assert!(!json.contains("duplicates"));
         let deserialized: IssuePreview =
             serde_json::from_str(&json).expect("Failed to deserialize");
 
@@ -13,6 +13,14 @@ pub struct UnifiedAuthResponse {
     /// JWT authentication token
     pub auth_token: String,
 
+    /// Refresh token for the session, when one was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+
+    /// Access token lifetime in seconds, when a session was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_in: Option<i64>,
+
     /// User information
     pub auth_user: AuthUser,
 
@@ -27,6 +35,10 @@ pub struct AuthUser {
     pub email: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+
+    /// Whether the user verified their email address
+    #[serde(default)]
+    pub email_verified: bool,
 }
 
 /// Payment/subscription status information
@@ -55,6 +67,7 @@ impl From<User> for AuthUser {
             email: user.email,
             created_at: user.created_at,
             updated_at: user.updated_at,
+            email_verified: false,
         }
     }
 }
@@ -83,29 +96,3 @@ impl PaymentUser {
         }
     }
 }
-
-/// OAuth callback response parameters
-/// These are passed as query parameters in the redirect URL
-#[derive(Debug, Serialize, Deserialize)]
-pub struct OAuthCallbackParams {
-    pub token: String,
-    pub user_id: Uuid,
-    pub email: String,
-    pub is_new_user: bool,
-    pub payment_required: bool,
-    pub has_valid_invite: bool,
-}
-
-impl OAuthCallbackParams {
-    #[must_use]
-    pub fn from_unified_response(response: &UnifiedAuthResponse, is_new_user: bool) -> Self {
-        OAuthCallbackParams {
-            token: response.auth_token.clone(),
-            user_id: response.auth_user.id,
-            email: response.auth_user.email.clone(),
-            is_new_user,
-            payment_required: response.payment_user.payment_required,
-            has_valid_invite: response.payment_user.has_valid_invite,
-        }
-    }
-}
@@ -0,0 +1,87 @@
+//! Board models
+//!
+//! A board groups issues. Every board has exactly one owner, who manages its
+//! members; only members can see the board and its issues.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A member's role on a board
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum BoardRole {
+    /// Created the board; can manage members and delete it
+    Owner,
+    /// Can read and change the board's issues
+    Member,
+}
+
+/// A board as stored in the database
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Board {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub owner_id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A board together with the requesting user's role on it
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct BoardResponse {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub board: Board,
+    pub role: BoardRole,
+}
+
+/// A member of a board
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct BoardMember {
+    pub user_id: String,
+    pub email: String,
+    pub role: BoardRole,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request to create a board
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateBoardRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Request to add a registered user to a board
+#[derive(Debug, Clone, Deserialize)]
+pub struct AddBoardMemberRequest {
+    pub email: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_response_flattens_board() {
+        let now = Utc::now();
+        let response = BoardResponse {
+            board: Board {
+                id: "board_1".to_string(),
+                name: "Roadmap".to_string(),
+                description: None,
+                owner_id: "user_1".to_string(),
+                created_at: now,
+                updated_at: now,
+            },
+            role: BoardRole::Owner,
+        };
+
+        let json = serde_json::to_value(&response).expect("Failed to serialize");
+        assert_eq!(json["name"], "Roadmap");
+        assert_eq!(json["role"], "owner");
+    }
+}
@@ -0,0 +1,102 @@
+//! CLI device login flows
+//!
+//! A command-line tool starts a flow with a PKCE code challenge, the user approves
+//! it in the browser, and the tool exchanges the resulting code together with its
+//! code verifier for session tokens bound to a new device.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Status of a CLI login flow
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CliFlowStatus {
+    /// Waiting for the user to approve the flow
+    Pending,
+    /// Approved; the code can be exchanged for tokens
+    Approved,
+    /// The code has been exchanged for tokens
+    Completed,
+    /// The flow expired before it was completed
+    Expired,
+}
+
+impl CliFlowStatus {
+    /// Database representation of the status
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Completed => "completed",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+impl std::str::FromStr for CliFlowStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "approved" => Ok(Self::Approved),
+            "completed" => Ok(Self::Completed),
+            "expired" => Ok(Self::Expired),
+            _ => Err(format!("Unknown CLI flow status: {s}")),
+        }
+    }
+}
+
+/// Request to start a CLI login flow
+#[derive(Debug, Clone, Deserialize)]
+pub struct StartCliFlowRequest {
+    /// User-friendly name of the device, shown when approving
+    pub device_name: String,
+    /// Hardware/system fingerprint identifying the device
+    pub device_fingerprint: String,
+    /// Base64url-encoded SHA-256 hash of the code verifier
+    pub code_challenge: String,
+    /// Only `S256` is supported
+    #[serde(default)]
+    pub code_challenge_method: Option<String>,
+    /// Optional loopback URI the browser sends the code to after approval
+    #[serde(default)]
+    pub redirect_uri: Option<String>,
+}
+
+/// Response to starting a CLI login flow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartCliFlowResponse {
+    /// Opaque handle identifying the flow
+    pub state: String,
+    /// Page where the user approves the flow
+    pub verification_url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A CLI login flow as shown to the approving user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CliFlowInfo {
+    pub device_name: String,
+    pub status: CliFlowStatus,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Response to approving a CLI login flow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApproveCliFlowResponse {
+    /// One-time code the CLI exchanges for tokens
+    pub code: String,
+    /// Loopback URL carrying the code, when the CLI provided a redirect URI
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_url: Option<String>,
+}
+
+/// Request to exchange an approved flow's code for tokens
+#[derive(Debug, Clone, Deserialize)]
+pub struct CliTokenRequest {
+    pub state: String,
+    pub code: String,
+    pub code_verifier: String,
+}
@@ -0,0 +1,48 @@
+//! Document models for retrieval-augmented chat
+//!
+//! A document is the text extracted from a file a user uploaded, split into
+//! overlapping chunks. Chat requests name documents by ID; the chunks most
+//! relevant to the question are added to the prompt and cited in the answer.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// An uploaded document as stored in the database
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Document {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub content_type: Option<String>,
+    /// Size of the uploaded file in bytes
+    pub size: i64,
+    /// SHA-256 of the extracted text, used to spot repeat uploads
+    pub content_hash: String,
+    pub chunk_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A chunk of a document retrieved for a question
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrievedChunk {
+    pub chunk_id: i64,
+    pub document_id: String,
+    pub document_name: String,
+    pub chunk_index: i64,
+    pub content: String,
+    /// Relevance to the question; only comparable within one retrieval
+    pub score: f32,
+}
+
+/// A source an answer was based on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Citation {
+    /// Number the answer refers to the source by, as in `[1]`
+    pub index: usize,
+    pub document_id: String,
+    pub document_name: String,
+    pub chunk_index: i64,
+    /// Start of the chunk's text
+    pub excerpt: String,
+}
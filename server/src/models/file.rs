@@ -0,0 +1,58 @@
+//! Stored file models
+//!
+//! Uploaded bytes are kept in the storage backend once per distinct content;
+//! a stored file is one upload of them, with the name and links it came with.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A file a user uploaded, as stored in the database
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct StoredFile {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub content_type: String,
+    /// Size in bytes
+    pub size: i64,
+    /// SHA-256 of the bytes; uploads of the same bytes share one stored copy
+    pub content_hash: String,
+    pub session_id: Option<String>,
+    pub conversation_id: Option<String>,
+    /// Document made from the file's text, if it had any
+    pub document_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What an upload belongs to
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileLinks {
+    pub session_id: Option<String>,
+    pub conversation_id: Option<String>,
+    pub document_id: Option<String>,
+}
+
+/// A stored file with a link to download it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileResponse {
+    #[serde(flatten)]
+    pub file: StoredFile,
+    /// Signed URL that downloads the file without a token until it expires
+    pub download_url: String,
+}
+
+/// Query parameters for listing files
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FileListQuery {
+    pub session_id: Option<String>,
+    pub conversation_id: Option<String>,
+}
+
+/// Query parameters of a signed download URL
+#[derive(Debug, Clone, Deserialize)]
+pub struct DownloadQuery {
+    /// Unix time the URL expires at
+    pub expires: i64,
+    pub signature: String,
+}
@@ -0,0 +1,21 @@
+//! Sign-in identities from OAuth providers
+//!
+//! Identities are stored in the `user_identities` table, keyed by provider and
+//! the provider's ID for the account, so a user can sign in with several
+//! providers and a provider account always signs in to the same user.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A provider account linked to a user
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UserIdentity {
+    pub provider: String,
+    pub provider_user_id: String,
+    pub user_id: String,
+    /// Email the provider reported when the account was last used
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,240 @@
+//! Issue models
+//!
+//! Issues move through the board's columns in order:
+//! backlog → current release → doing → review → done. They can move forward
+//! one column at a time, and back to any earlier column when work is reopened.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Kind of work an issue describes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum IssueType {
+    Feature,
+    Enhancement,
+    Defect,
+    Task,
+}
+
+/// Column an issue is in
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, sqlx::Type,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum IssueStatus {
+    Backlog,
+    CurrentRelease,
+    Doing,
+    Review,
+    Done,
+}
+
+impl IssueStatus {
+    /// Statuses in workflow order
+    pub const WORKFLOW: [Self; 5] = [
+        Self::Backlog,
+        Self::CurrentRelease,
+        Self::Doing,
+        Self::Review,
+        Self::Done,
+    ];
+
+    /// The status that follows this one in the workflow
+    #[must_use]
+    pub fn next(self) -> Option<Self> {
+        let position = Self::WORKFLOW.iter().position(|status| *status == self)?;
+        Self::WORKFLOW.get(position + 1).copied()
+    }
+
+    /// Whether an issue in this status may move to `target`
+    ///
+    /// Issues advance one step at a time and may return to any earlier status.
+    #[must_use]
+    pub fn can_transition_to(self, target: Self) -> bool {
+        target < self || self.next() == Some(target)
+    }
+}
+
+/// How urgent an issue is
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum IssuePriority {
+    Critical,
+    High,
+    Medium,
+    Low,
+}
+
+impl std::str::FromStr for IssuePriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "critical" => Ok(Self::Critical),
+            "high" => Ok(Self::High),
+            "medium" => Ok(Self::Medium),
+            "low" => Ok(Self::Low),
+            _ => Err(format!("Unknown issue priority: {s}")),
+        }
+    }
+}
+
+/// An issue on a board
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Issue {
+    pub id: String,
+    pub board_id: String,
+    pub title: String,
+    pub description: Option<String>,
+    #[serde(rename = "type")]
+    #[sqlx(rename = "type")]
+    pub issue_type: IssueType,
+    pub status: IssueStatus,
+    pub priority: IssuePriority,
+    /// Estimated effort in hours
+    pub size_estimate: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub created_by: String,
+}
+
+/// Request to create an issue; new issues start in the backlog
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateIssueRequest {
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(rename = "type")]
+    pub issue_type: IssueType,
+    pub priority: IssuePriority,
+    #[serde(default)]
+    pub size_estimate: Option<i64>,
+}
+
+/// Request to change an issue's details; omitted fields are left unchanged
+///
+/// An empty description clears it. Status changes go through
+/// `TransitionIssueRequest`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpdateIssueRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[serde(rename = "type")]
+    pub issue_type: Option<IssueType>,
+    pub priority: Option<IssuePriority>,
+    pub size_estimate: Option<i64>,
+}
+
+/// Request to move an issue to another status
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransitionIssueRequest {
+    pub status: IssueStatus,
+}
+
+/// Field issues are listed by
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSort {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Priority,
+    Title,
+}
+
+/// Direction issues are listed in
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Filters, ordering and page position for listing a board's issues
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IssueListQuery {
+    pub status: Option<IssueStatus>,
+    pub priority: Option<IssuePriority>,
+    #[serde(rename = "type")]
+    pub issue_type: Option<IssueType>,
+    #[serde(default)]
+    pub sort: IssueSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    /// Opaque cursor from a previous page's `next_cursor`
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// One page of issues
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssuePage {
+    pub issues: Vec<Issue>,
+    /// Cursor for the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Request to find existing issues that a new issue may duplicate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateCheckRequest {
+    pub title: String,
+    pub description: Option<String>,
+    /// Issue to leave out of the results, such as the one being edited
+    pub exclude_issue_id: Option<String>,
+    /// Most duplicates to return, 5 by default
+    pub limit: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_transitions() {
+        assert!(IssueStatus::Backlog.can_transition_to(IssueStatus::CurrentRelease));
+        assert!(IssueStatus::Doing.can_transition_to(IssueStatus::Review));
+        assert!(IssueStatus::Review.can_transition_to(IssueStatus::Done));
+        assert!(!IssueStatus::Backlog.can_transition_to(IssueStatus::Doing));
+        assert!(!IssueStatus::Doing.can_transition_to(IssueStatus::Doing));
+        assert!(!IssueStatus::Done.can_transition_to(IssueStatus::Done));
+
+        // Reopening moves work back to any earlier column
+        assert!(IssueStatus::Done.can_transition_to(IssueStatus::Doing));
+        assert!(IssueStatus::Review.can_transition_to(IssueStatus::Backlog));
+        assert_eq!(IssueStatus::Done.next(), None);
+    }
+
+    #[test]
+    fn test_issue_serializes_type_field() {
+        let now = Utc::now();
+        let issue = Issue {
+            id: "issue_1".to_string(),
+            board_id: "board_1".to_string(),
+            title: "Fix login".to_string(),
+            description: None,
+            issue_type: IssueType::Defect,
+            status: IssueStatus::CurrentRelease,
+            priority: IssuePriority::High,
+            size_estimate: Some(3),
+            created_at: now,
+            updated_at: now,
+            created_by: "user_1".to_string(),
+        };
+
+        let json = serde_json::to_value(&issue).expect("Failed to serialize");
+        assert_eq!(json["type"], "defect");
+        assert_eq!(json["status"], "current_release");
+    }
+
+    #[test]
+    fn test_priority_from_str() {
+        assert_eq!("High".parse::<IssuePriority>(), Ok(IssuePriority::High));
+        assert!("urgent".parse::<IssuePriority>().is_err());
+    }
+}
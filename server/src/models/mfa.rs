@@ -0,0 +1,79 @@
+//! TOTP two-factor authentication
+//!
+//! Users enroll an authenticator app with a shared secret and enable the factor
+//! by entering a code from it. Once enabled, signing in takes two steps: the
+//! password step returns an `mfa_pending` token instead of a session, which is
+//! exchanged for a session together with a code from the app or a one-time
+//! recovery code.
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{role::Role, session::SessionMode};
+
+/// A started enrollment, shown to the user to set up their authenticator app
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaEnrollment {
+    /// Base32 secret, for apps that cannot scan the URI
+    pub secret: String,
+    /// `otpauth://` URI to show as a QR code
+    pub otpauth_uri: String,
+}
+
+/// A user's two-factor authentication status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaStatus {
+    pub enabled: bool,
+    /// Whether one of the user's roles requires two-factor authentication
+    pub required: bool,
+    pub recovery_codes_remaining: i64,
+}
+
+/// Response of the password step of a sign-in that needs a second factor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaChallengeResponse {
+    /// Always `mfa_pending`
+    pub status: String,
+    /// Token to send to the verify endpoint with a code
+    pub mfa_token: String,
+    /// Token lifetime in seconds
+    pub expires_in: i64,
+}
+
+impl MfaChallengeResponse {
+    #[must_use]
+    pub fn new(mfa_token: String, expires_in: i64) -> Self {
+        Self {
+            status: "mfa_pending".to_string(),
+            mfa_token,
+            expires_in,
+        }
+    }
+}
+
+/// Request carrying a code from the authenticator app or a recovery code
+#[derive(Debug, Clone, Deserialize)]
+pub struct MfaCodeRequest {
+    pub code: String,
+}
+
+/// Request to complete a sign-in with its second factor
+#[derive(Debug, Clone, Deserialize)]
+pub struct MfaVerifyRequest {
+    pub mfa_token: String,
+    /// Code from the authenticator app or a recovery code
+    pub code: String,
+    #[serde(default)]
+    pub session: SessionMode,
+}
+
+/// Recovery codes, shown to the user only when they are generated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Roles whose holders must use two-factor authentication
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaPolicy {
+    pub required_roles: Vec<Role>,
+}
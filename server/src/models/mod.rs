@@ -1,15 +1,26 @@
+pub mod account_email;
 pub mod ai_models;
 pub mod ai_persona;
 pub mod ai_session;
 pub mod auth;
+pub mod board;
+pub mod cli_auth;
+pub mod document;
+pub mod file;
+pub mod identity;
 pub mod invite;
+pub mod issue;
+pub mod mfa;
 pub mod oauth;
 pub mod payment;
+pub mod role;
+pub mod session;
 pub mod user;
 
 pub use ai_models::{
-    AiConversation, AiMessage, AiUsage, ConversationResponse, ConversationWithMessages,
-    CreateConversationRequest, CreateMessageRequest, MessageResponse, UsageStatsResponse,
+    AiConversation, AiMessage, AiUsage, ConversationResponse, ConversationSearchQuery,
+    ConversationSummary, ConversationWithMessages, CreateConversationRequest, CreateMessageRequest,
+    MessageResponse, SimilarConversation, UsageStatsResponse,
 };
 pub use ai_persona::AiPersona;
 pub use ai_session::{
@@ -18,8 +29,20 @@ pub use ai_session::{
     MessageRole, SendMessageRequest, SendMessageResponse, SessionContext, SessionStatus,
 };
 // Public API exports
-pub use auth::{AuthUser, OAuthCallbackParams, PaymentUser, UnifiedAuthResponse};
+pub use auth::{AuthUser, PaymentUser, UnifiedAuthResponse};
+pub use board::{
+    AddBoardMemberRequest, Board, BoardMember, BoardResponse, BoardRole, CreateBoardRequest,
+};
+pub use document::{Citation, Document, RetrievedChunk};
+pub use file::{DownloadQuery, FileLinks, FileListQuery, FileResponse, StoredFile};
+pub use identity::UserIdentity;
 pub use invite::UserInvite;
+pub use issue::{
+    CreateIssueRequest, DuplicateCheckRequest, Issue, IssueListQuery, IssuePage, IssuePriority,
+    IssueSort, IssueStatus, IssueType, SortOrder, TransitionIssueRequest, UpdateIssueRequest,
+};
+pub use role::{Permission, Role};
+pub use session::{DeviceInfo, SessionInfo, SessionTokens};
 // Payment models exported internally to modules
 // Individual modules import directly from payment::
 pub use user::{User, UserConversionError, UserFromDb};
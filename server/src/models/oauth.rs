@@ -1,12 +1,15 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use uuid::Uuid;
 
 /// OAuth provider enumeration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum OAuthProvider {
     Google,
     GitHub,
+    /// OIDC provider configured by name in `OIDC_PROVIDERS`
+    Oidc(String),
 }
 
 impl std::fmt::Display for OAuthProvider {
@@ -14,6 +17,7 @@ impl std::fmt::Display for OAuthProvider {
         match self {
             Self::Google => write!(f, "google"),
             Self::GitHub => write!(f, "github"),
+            Self::Oidc(name) => write!(f, "{name}"),
         }
     }
 }
@@ -38,6 +42,8 @@ pub struct OAuthUserInfo {
     pub name: Option<String>,
     pub picture: Option<String>,
     pub provider: OAuthProvider,
+    /// Whether the provider verified that the user owns `email`
+    pub email_verified: bool,
 }
 
 /// Google OAuth user information response
@@ -60,6 +66,7 @@ impl From<GoogleUserInfo> for OAuthUserInfo {
             name: google_info.name,
             picture: google_info.picture,
             provider: OAuthProvider::Google,
+            email_verified: google_info.email_verified == Some(true),
         }
     }
 }
@@ -87,13 +94,62 @@ impl GitHubUserInfo {
     /// Convert GitHub user info to OAuth user info
     /// Note: Email might need to be fetched separately if not public
     #[must_use]
-    pub fn into_oauth_user_info(self, email: String) -> OAuthUserInfo {
+    pub fn into_oauth_user_info(self, email: String, email_verified: bool) -> OAuthUserInfo {
         OAuthUserInfo {
             id: self.id.to_string(),
             email,
             name: self.name.or(Some(self.login)),
             picture: self.avatar_url,
             provider: OAuthProvider::GitHub,
+            email_verified,
+        }
+    }
+}
+
+/// OIDC provider metadata from its discovery document
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    pub userinfo_endpoint: Option<String>,
+    #[serde(default)]
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+}
+
+/// Tokens returned by an OIDC token endpoint
+#[derive(Debug, Deserialize)]
+pub struct OidcTokenResponse {
+    pub access_token: String,
+    pub id_token: Option<String>,
+}
+
+/// Claims of an OIDC ID token, or of a userinfo response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+    /// Value from the authorization request, echoed to bind the token to it
+    pub nonce: Option<String>,
+    /// Client the token was issued to
+    pub azp: Option<String>,
+}
+
+impl OidcClaims {
+    /// Convert the claims to OAuth user info
+    #[must_use]
+    pub fn into_oauth_user_info(self, provider: OAuthProvider, email: String) -> OAuthUserInfo {
+        OAuthUserInfo {
+            id: self.sub,
+            email,
+            name: self.name,
+            picture: self.picture,
+            provider,
+            email_verified: self.email_verified == Some(true),
         }
     }
 }
@@ -107,3 +163,22 @@ pub struct OAuthState {
     pub created_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
 }
+
+/// What a validated OAuth state was stored with
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidatedOAuthState {
+    /// User linking the provider account, for states of a link request
+    pub link_user_id: Option<Uuid>,
+    /// PKCE code verifier to send with the authorization code
+    pub pkce_verifier: Option<String>,
+    /// Nonce the ID token must carry
+    pub nonce: Option<String>,
+}
+
+/// Sign-in a one-time OAuth login code was exchanged for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OAuthLoginGrant {
+    pub user_id: Uuid,
+    /// Whether the sign-in created the user
+    pub is_new_user: bool,
+}
@@ -0,0 +1,133 @@
+//! Roles and permissions for authorization
+//!
+//! Roles are assigned to users and stored in the `user_roles` table. Each role
+//! grants a fixed set of permissions; handlers check permissions rather than
+//! roles so new roles can be introduced without touching them.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Role that can be granted to a user
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Full administrative access, including managing roles
+    Admin,
+    /// Can manage user invites
+    InviteManager,
+}
+
+/// Permission checked by protected endpoints
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// List, create and delete invites
+    ManageInvites,
+    /// Grant and revoke user roles
+    ManageRoles,
+}
+
+impl Role {
+    /// All roles, in order of decreasing privilege
+    pub const ALL: [Role; 2] = [Role::Admin, Role::InviteManager];
+
+    /// Database and wire representation of the role
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::InviteManager => "invite_manager",
+        }
+    }
+
+    /// Permissions granted by this role
+    #[must_use]
+    pub fn permissions(self) -> &'static [Permission] {
+        match self {
+            Self::Admin => &[Permission::ManageInvites, Permission::ManageRoles],
+            Self::InviteManager => &[Permission::ManageInvites],
+        }
+    }
+
+    /// Check whether this role grants the given permission
+    #[must_use]
+    pub fn has_permission(self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("Unknown role: {s}"))
+    }
+}
+
+/// Check whether any of the given roles grants the permission
+#[must_use]
+pub fn roles_grant(roles: &[Role], permission: Permission) -> bool {
+    roles.iter().any(|role| role.has_permission(permission))
+}
+
+/// A role assignment as stored in the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoleAssignment {
+    pub user_id: String,
+    pub role: Role,
+    pub granted_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request to grant a role to a user
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrantRoleRequest {
+    pub role: Role,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_round_trip() {
+        for role in Role::ALL {
+            assert_eq!(role.as_str().parse::<Role>(), Ok(role));
+            assert_eq!(
+                serde_json::to_string(&role).expect("Failed to serialize"),
+                format!("\"{}\"", role.as_str())
+            );
+        }
+        assert!("superuser".parse::<Role>().is_err());
+    }
+
+    #[test]
+    fn test_role_permissions() {
+        assert!(Role::Admin.has_permission(Permission::ManageRoles));
+        assert!(Role::Admin.has_permission(Permission::ManageInvites));
+        assert!(Role::InviteManager.has_permission(Permission::ManageInvites));
+        assert!(!Role::InviteManager.has_permission(Permission::ManageRoles));
+    }
+
+    #[test]
+    fn test_roles_grant() {
+        assert!(!roles_grant(&[], Permission::ManageInvites));
+        assert!(roles_grant(
+            &[Role::InviteManager],
+            Permission::ManageInvites
+        ));
+        assert!(!roles_grant(
+            &[Role::InviteManager],
+            Permission::ManageRoles
+        ));
+    }
+}
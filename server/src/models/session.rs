@@ -0,0 +1,122 @@
+//! Login sessions and refresh tokens
+//!
+//! Each login creates a session, stored as a row in `cli_devices`. A session owns
+//! a chain of refresh tokens in `cli_refresh_tokens`; every refresh revokes the
+//! presented token and issues the next one in the chain.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Maximum stored length of a device name
+const MAX_DEVICE_NAME_LEN: usize = 200;
+
+/// Description of the device or client a session belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub fingerprint: String,
+}
+
+impl DeviceInfo {
+    /// Fingerprint recorded for browser sessions
+    pub const WEB_FINGERPRINT: &'static str = "web";
+
+    /// Describe a browser session from its user agent, if known
+    #[must_use]
+    pub fn web(user_agent: Option<&str>) -> Self {
+        let name = user_agent
+            .map(str::trim)
+            .filter(|agent| !agent.is_empty())
+            .map_or_else(
+                || "Unknown device".to_string(),
+                |agent| agent.chars().take(MAX_DEVICE_NAME_LEN).collect(),
+            );
+
+        Self {
+            name,
+            fingerprint: Self::WEB_FINGERPRINT.to_string(),
+        }
+    }
+}
+
+/// An active session as shown to its owner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: Uuid,
+    pub device_name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Whether this is the session the request was made with
+    pub current: bool,
+}
+
+/// Tokens issued when a session is created or refreshed
+#[derive(Debug, Clone)]
+pub struct SessionTokens {
+    pub session_id: Uuid,
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+}
+
+/// How a browser client keeps its session tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionMode {
+    /// Tokens are returned in the response body
+    #[default]
+    Token,
+    /// Tokens are set as `HttpOnly` cookies and left out of the response body
+    Cookie,
+}
+
+/// Request to exchange a refresh token for new tokens
+///
+/// Clients in the cookie session mode leave the token out; the refresh token
+/// cookie is used instead.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RefreshTokenRequest {
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+}
+
+/// Tokens returned by a token refresh or a CLI code exchange
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionTokenResponse {
+    pub auth_token: String,
+    pub refresh_token: String,
+    /// Access token lifetime in seconds
+    pub expires_in: i64,
+}
+
+impl From<SessionTokens> for SessionTokenResponse {
+    fn from(tokens: SessionTokens) -> Self {
+        Self {
+            auth_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            expires_in: tokens.expires_in,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_web_device_info() {
+        let device = DeviceInfo::web(Some("  Mozilla/5.0 "));
+        assert_eq!(device.name, "Mozilla/5.0");
+        assert_eq!(device.fingerprint, DeviceInfo::WEB_FINGERPRINT);
+
+        assert_eq!(DeviceInfo::web(None).name, "Unknown device");
+        assert_eq!(DeviceInfo::web(Some("")).name, "Unknown device");
+
+        let long_agent = "a".repeat(500);
+        assert_eq!(
+            DeviceInfo::web(Some(&long_agent)).name.len(),
+            MAX_DEVICE_NAME_LEN
+        );
+    }
+}
@@ -10,32 +10,209 @@ use std::{env, sync::Arc};
 use tower_http::cors::CorsLayer;
 use tower_http::services::{ServeDir, ServeFile};
 
+use crate::ai::ModelPricing;
 use crate::core::AppState;
 use crate::handlers::{
+    account_handler::{
+        confirm_email_handler, confirm_password_reset_handler, request_email_verification_handler,
+        request_password_reset_handler,
+    },
+    admin_handler::{
+        get_mfa_policy_handler, get_user_quota_handler, grant_role_handler,
+        list_user_roles_handler, revoke_role_handler, set_mfa_policy_handler,
+        set_user_quota_handler,
+    },
     ai_handler::{
         ai_info_handler, archive_conversation_handler, chat_handler, chat_stream_handler,
         code_analysis_handler, contextual_chat_handler, create_invite_handler,
-        delete_conversation_handler, delete_invite_handler, demo_message_handler,
-        error_demo_handler, get_conversation_handler, get_conversations_handler,
-        get_invite_handler, get_usage_stats_handler, health_check_handler, list_invites_handler,
-        moderate_content_handler, upload_file_handler, verify_token_handler,
+        create_session_handler, delete_conversation_handler, delete_document_handler,
+        delete_file_handler, delete_invite_handler, demo_message_handler, download_file_handler,
+        error_demo_handler, finalize_session_handler, get_conversation_handler,
+        get_conversations_handler, get_document_handler, get_file_handler, get_invite_handler,
+        get_quota_handler, get_session_handler, get_usage_stats_handler, health_check_handler,
+        list_documents_handler, list_files_handler, list_invites_handler, moderate_content_handler,
+        preview_session_handler, search_conversations_handler, send_session_message_handler,
+        upload_file_handler, upload_session_assets_handler, verify_token_handler,
     },
     auth_handler::{login_user_handler, register_user_handler},
+    board_handler::{
+        add_board_member_handler, create_board_handler, delete_board_handler, get_board_handler,
+        list_board_members_handler, list_boards_handler, remove_board_member_handler,
+    },
+    cli_auth_handler::{
+        approve_cli_flow_handler, cli_token_handler, get_cli_flow_handler, start_cli_flow_handler,
+    },
     health_handler::{health_check, readiness_check},
+    issue_handler::{
+        check_duplicates_handler, create_issue_handler, delete_issue_handler, get_issue_handler,
+        issue_duplicates_handler, list_issues_handler, transition_issue_handler,
+        update_issue_handler,
+    },
+    mfa_handler::{
+        disable_mfa_handler, enable_mfa_handler, enroll_mfa_handler, get_mfa_status_handler,
+        regenerate_recovery_codes_handler, verify_mfa_handler,
+    },
     oauth_handler::{
-        OAuthAppState, github_login_init, github_oauth_callback, google_login_init,
-        google_oauth_callback,
+        OAuthAppState, exchange_login_code, github_login_init, github_oauth_callback,
+        google_login_init, google_oauth_callback, link_identity_handler, list_identities_handler,
+        oauth_callback, oauth_login_init, unlink_identity_handler,
     },
     payment_handler::{
         create_payment_intent_handler, get_payment_status_handler, stripe_webhook_handler,
     },
+    session_handler::{
+        list_sessions_handler, logout_handler, refresh_token_handler, revoke_session_handler,
+    },
     user_handler::get_current_user_handler,
 };
 use crate::services::{
-    AiDataService, AiService, AuthService, InviteService, OAuthService, PaymentService,
-    UserServiceImpl,
+    AccountEmailService, AiDataService, AiService, AiSessionService, AuthService, BoardService,
+    CliAuthService, ContextService, DocumentService, EmbeddingService, FileService,
+    IdentityService, InviteService, IssueService, MfaService, OAuthService, PaymentService,
+    QuotaService, RoleService, SessionService, UserServiceImpl, quota_service::QuotaPlans,
 };
 
+/// Create OAuth sign-in and linked identity routes
+fn oauth_routes() -> Router<OAuthAppState> {
+    Router::new()
+        .route("/api/auth/oauth/google", get(google_login_init))
+        .route(
+            "/api/auth/oauth/google/callback",
+            get(google_oauth_callback),
+        )
+        .route("/api/auth/oauth/github", get(github_login_init))
+        .route(
+            "/api/auth/oauth/github/callback",
+            get(github_oauth_callback),
+        )
+        // Generic OIDC providers, and the built-in ones by name
+        .route("/api/auth/oauth/exchange", post(exchange_login_code))
+        .route("/api/auth/oauth/{provider}", get(oauth_login_init))
+        .route("/api/auth/oauth/{provider}/callback", get(oauth_callback))
+        // Provider accounts linked to the current user
+        .route("/api/auth/identities", get(list_identities_handler))
+        .route(
+            "/api/auth/identities/{provider}",
+            post(link_identity_handler),
+        )
+        .route(
+            "/api/auth/identities/{provider}",
+            axum::routing::delete(unlink_identity_handler),
+        )
+}
+
+/// Create authentication, session, account email, two-factor and CLI login routes
+fn auth_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/auth/register", post(register_user_handler))
+        .route("/api/auth/login", post(login_user_handler))
+        .route("/api/auth/verify", get(verify_token_handler))
+        .route("/api/auth/refresh", post(refresh_token_handler))
+        .route("/api/auth/logout", post(logout_handler))
+        .route("/api/auth/sessions", get(list_sessions_handler))
+        .route(
+            "/api/auth/sessions/{id}",
+            axum::routing::delete(revoke_session_handler),
+        )
+        // Email verification and password reset routes
+        .route(
+            "/api/auth/verify-email/request",
+            post(request_email_verification_handler),
+        )
+        .route(
+            "/api/auth/verify-email/confirm",
+            post(confirm_email_handler),
+        )
+        .route(
+            "/api/auth/password-reset/request",
+            post(request_password_reset_handler),
+        )
+        .route(
+            "/api/auth/password-reset/confirm",
+            post(confirm_password_reset_handler),
+        )
+        // Two-factor authentication routes
+        .route("/api/auth/mfa", get(get_mfa_status_handler))
+        .route("/api/auth/mfa/enroll", post(enroll_mfa_handler))
+        .route("/api/auth/mfa/enable", post(enable_mfa_handler))
+        .route("/api/auth/mfa/disable", post(disable_mfa_handler))
+        .route(
+            "/api/auth/mfa/recovery-codes",
+            post(regenerate_recovery_codes_handler),
+        )
+        .route("/api/auth/mfa/verify", post(verify_mfa_handler))
+        // CLI device login routes
+        .route("/api/auth/cli/flows", post(start_cli_flow_handler))
+        .route("/api/auth/cli/flows/{state}", get(get_cli_flow_handler))
+        .route(
+            "/api/auth/cli/flows/{state}/approve",
+            post(approve_cli_flow_handler),
+        )
+        .route("/api/auth/cli/token", post(cli_token_handler))
+}
+
+/// Create admin routes for invite, role and two-factor policy management
+fn admin_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/admin/invites", get(list_invites_handler))
+        .route("/api/admin/invites", post(create_invite_handler))
+        .route(
+            "/api/admin/invites/{id}",
+            axum::routing::delete(delete_invite_handler),
+        )
+        .route("/api/admin/users/{id}/roles", get(list_user_roles_handler))
+        .route("/api/admin/users/{id}/roles", post(grant_role_handler))
+        .route(
+            "/api/admin/users/{id}/roles/{role}",
+            axum::routing::delete(revoke_role_handler),
+        )
+        .route(
+            "/api/admin/users/{id}/ai-quota",
+            get(get_user_quota_handler),
+        )
+        .route(
+            "/api/admin/users/{id}/ai-quota",
+            axum::routing::put(set_user_quota_handler),
+        )
+        .route("/api/admin/mfa-policy", get(get_mfa_policy_handler))
+        .route(
+            "/api/admin/mfa-policy",
+            axum::routing::put(set_mfa_policy_handler),
+        )
+}
+
+/// Create board and issue routes
+fn board_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/api/boards", get(list_boards_handler))
+        .route("/api/boards", post(create_board_handler))
+        .route("/api/boards/{id}", get(get_board_handler))
+        .route(
+            "/api/boards/{id}",
+            axum::routing::delete(delete_board_handler),
+        )
+        .route("/api/boards/{id}/members", get(list_board_members_handler))
+        .route("/api/boards/{id}/members", post(add_board_member_handler))
+        .route(
+            "/api/boards/{id}/members/{user_id}",
+            axum::routing::delete(remove_board_member_handler),
+        )
+        .route("/api/boards/{id}/issues", get(list_issues_handler))
+        .route("/api/boards/{id}/issues", post(create_issue_handler))
+        .route(
+            "/api/boards/{id}/duplicates",
+            post(check_duplicates_handler),
+        )
+        .route("/api/issues/{id}", get(get_issue_handler))
+        .route("/api/issues/{id}", axum::routing::put(update_issue_handler))
+        .route(
+            "/api/issues/{id}",
+            axum::routing::delete(delete_issue_handler),
+        )
+        .route("/api/issues/{id}/status", post(transition_issue_handler))
+        .route("/api/issues/{id}/duplicates", get(issue_duplicates_handler))
+}
+
 /// Create AI routes
 fn ai_routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -44,7 +221,24 @@ fn ai_routes() -> Router<Arc<AppState>> {
         .route("/api/ai/chat/contextual", post(contextual_chat_handler))
         .route("/api/ai/analyze/code", post(code_analysis_handler))
         .route("/api/ai/upload", post(upload_file_handler))
+        .route("/api/ai/documents", get(list_documents_handler))
+        .route("/api/ai/documents/{id}", get(get_document_handler))
+        .route(
+            "/api/ai/documents/{id}",
+            axum::routing::delete(delete_document_handler),
+        )
+        .route("/api/ai/files", get(list_files_handler))
+        .route("/api/ai/files/{id}", get(get_file_handler))
+        .route(
+            "/api/ai/files/{id}",
+            axum::routing::delete(delete_file_handler),
+        )
+        .route("/api/ai/files/{id}/content", get(download_file_handler))
         .route("/api/ai/conversations", get(get_conversations_handler))
+        .route(
+            "/api/ai/conversations/search",
+            get(search_conversations_handler),
+        )
         .route("/api/ai/conversations/{id}", get(get_conversation_handler))
         .route(
             "/api/ai/conversations/{id}",
@@ -54,7 +248,26 @@ fn ai_routes() -> Router<Arc<AppState>> {
             "/api/ai/conversations/{id}/archive",
             post(archive_conversation_handler),
         )
+        .route("/api/ai/sessions", post(create_session_handler))
+        .route("/api/ai/sessions/{id}", get(get_session_handler))
+        .route(
+            "/api/ai/sessions/{id}/messages",
+            post(send_session_message_handler),
+        )
+        .route(
+            "/api/ai/sessions/{id}/assets",
+            post(upload_session_assets_handler),
+        )
+        .route(
+            "/api/ai/sessions/{id}/preview",
+            get(preview_session_handler),
+        )
+        .route(
+            "/api/ai/sessions/{id}/finalize",
+            post(finalize_session_handler),
+        )
         .route("/api/ai/usage", get(get_usage_stats_handler))
+        .route("/api/ai/quota", get(get_quota_handler))
         .route("/api/ai/health", get(health_check_handler))
         .route("/api/ai/moderate", post(moderate_content_handler))
         .route("/api/ai/info", get(ai_info_handler))
@@ -119,11 +332,42 @@ pub fn create_router(
 ) -> Result<Router, Box<dyn std::error::Error>> {
     // Initialize AI services
     let ai_service = AiService::new()?;
-    let ai_data_service = AiDataService::new(db_pool.clone());
+    let ai_data_service =
+        AiDataService::new(db_pool.clone()).with_pricing(ModelPricing::from_env()?);
+    let quota_service = QuotaService::new(db_pool.clone()).with_plans(QuotaPlans::from_env()?);
 
     // Initialize Payment service
     let payment_service = PaymentService::new(db_pool.clone())?;
 
+    // Initialize Role service
+    let role_service = RoleService::new(db_pool.clone());
+
+    // Initialize Session service
+    let session_service = SessionService::new(db_pool.clone());
+
+    // Initialize sign-in identity service
+    let identity_service = IdentityService::new(db_pool.clone());
+
+    // Initialize email verification and password reset service
+    let account_email_service = AccountEmailService::from_env(db_pool.clone())?;
+
+    // Initialize two-factor authentication service
+    let mfa_service = MfaService::new(db_pool.clone());
+
+    // Initialize CLI login service
+    let cli_auth_service = CliAuthService::new(db_pool.clone());
+
+    // Initialize AI session service
+    let ai_session_service = AiSessionService::new(db_pool.clone());
+
+    // Initialize board, issue, embedding, document, file and context services
+    let board_service = BoardService::new(db_pool.clone());
+    let issue_service = IssueService::new(db_pool.clone());
+    let embedding_service = EmbeddingService::new(db_pool.clone());
+    let document_service = DocumentService::new(db_pool.clone());
+    let file_service = FileService::from_env(db_pool.clone())?;
+    let context_service = ContextService::new(db_pool.clone());
+
     let app_state = Arc::new(AppState {
         user: user_service,
         auth: auth_service,
@@ -131,6 +375,20 @@ pub fn create_router(
         ai: Arc::new(tokio::sync::RwLock::new(ai_service)),
         ai_data: Arc::new(ai_data_service),
         payment: Arc::new(payment_service),
+        role: Arc::new(role_service),
+        session: Arc::new(session_service),
+        cli_auth: Arc::new(cli_auth_service),
+        ai_session: Arc::new(ai_session_service),
+        boards: Arc::new(board_service),
+        issues: Arc::new(issue_service),
+        embeddings: Arc::new(embedding_service),
+        documents: Arc::new(document_service),
+        files: Arc::new(file_service),
+        context: Arc::new(context_service),
+        quotas: Arc::new(quota_service),
+        identities: Arc::new(identity_service),
+        account_email: Arc::new(account_email_service),
+        mfa: Arc::new(mfa_service),
     });
 
     let oauth_app_state = OAuthAppState {
@@ -139,18 +397,7 @@ pub fn create_router(
     };
 
     // Create OAuth routes with their own state
-    let oauth_router = Router::new()
-        .route("/api/auth/oauth/google", get(google_login_init))
-        .route(
-            "/api/auth/oauth/google/callback",
-            get(google_oauth_callback),
-        )
-        .route("/api/auth/oauth/github", get(github_login_init))
-        .route(
-            "/api/auth/oauth/github/callback",
-            get(github_oauth_callback),
-        )
-        .with_state(oauth_app_state);
+    let oauth_router = oauth_routes().with_state(oauth_app_state);
 
     let static_dir = env::var("STATIC_DIR").unwrap_or_else(|_| "./static".to_string());
 
@@ -159,10 +406,7 @@ pub fn create_router(
         // Health check endpoints (no authentication needed)
         .route("/health", get(health_check))
         .route("/ready", get(readiness_check))
-        // Authentication routes
-        .route("/api/auth/register", post(register_user_handler))
-        .route("/api/auth/login", post(login_user_handler))
-        .route("/api/auth/verify", get(verify_token_handler))
+        .merge(auth_routes())
         // Protected user routes
         .route("/api/users/me", get(get_current_user_handler))
         // Payment routes
@@ -172,18 +416,13 @@ pub fn create_router(
             post(create_payment_intent_handler),
         )
         .route("/api/webhooks/stripe", post(stripe_webhook_handler))
-        // Admin routes (invite management)
-        .route("/api/admin/invites", get(list_invites_handler))
-        .route("/api/admin/invites", post(create_invite_handler))
-        .route(
-            "/api/admin/invites/{id}",
-            axum::routing::delete(delete_invite_handler),
-        )
         .route("/api/invites/{email}", get(get_invite_handler))
         // Debug/development routes
         .route("/api/debug/error/{error_type}", get(error_demo_handler))
         .route("/api/debug/message", get(demo_message_handler))
+        .merge(admin_routes())
         .merge(ai_routes())
+        .merge(board_routes())
         .with_state(app_state)
         // Merge OAuth routes
         .merge(oauth_router)
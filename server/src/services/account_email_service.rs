@@ -0,0 +1,362 @@
+//! Email verification and password reset service
+//!
+//! Issues the one-time tokens of both flows, mails them as links to the client,
+//! and applies them. Only token hashes are stored, see `models::account_email`.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    core::{
+        password_utils::hash_password,
+        token_utils::{generate_opaque_token, hash_token},
+    },
+    errors::{AppError, AppResult},
+    models::{User, account_email::AccountEmailPurpose},
+    services::mail::{Email, Mailer, mailer_from_env},
+};
+
+/// Minimum time between two emails of the same kind to one user
+const RESEND_INTERVAL_SECONDS: i64 = 60;
+
+fn invalid_token() -> AppError {
+    AppError::BadRequest("Invalid or expired token".to_string())
+}
+
+pub struct AccountEmailService {
+    db: SqlitePool,
+    mailer: Arc<dyn Mailer>,
+    /// Base URL of the client the emailed links open
+    client_url: String,
+}
+
+impl AccountEmailService {
+    #[must_use]
+    pub fn new(db: SqlitePool, mailer: Arc<dyn Mailer>, client_url: impl Into<String>) -> Self {
+        Self {
+            db,
+            mailer,
+            client_url: client_url.into(),
+        }
+    }
+
+    /// Create the service with the mailer selected by `MAIL_TRANSPORT` and
+    /// links to `CLIENT_URL`
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ConfigError` if the mailer is misconfigured
+    pub fn from_env(db: SqlitePool) -> AppResult<Self> {
+        let mailer = mailer_from_env()?;
+        let client_url =
+            std::env::var("CLIENT_URL").unwrap_or_else(|_| "http://localhost:8080".to_string());
+        tracing::info!("Sending account emails with the {} mailer", mailer.name());
+        Ok(Self::new(db, mailer, client_url))
+    }
+
+    /// Whether the user verified their email address
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::UserNotFound` if the user does not exist, or `AppError`
+    /// if the database query fails
+    pub async fn is_email_verified(&self, user_id: Uuid) -> AppResult<bool> {
+        let user_id = user_id.to_string();
+        let verified_at = sqlx::query_scalar!(
+            r#"SELECT email_verified_at as "verified_at: DateTime<Utc>" FROM users WHERE id = ?1"#,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(AppError::UserNotFound)?;
+
+        Ok(verified_at.is_some())
+    }
+
+    /// Whether the user may do what needs a verified email: they verified it,
+    /// or their account predates email verification
+    ///
+    /// Grandfathered accounts are not considered to own their address, so use
+    /// `is_email_verified` where ownership matters.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::UserNotFound` if the user does not exist, or `AppError`
+    /// if the database query fails
+    pub async fn has_verified_access(&self, user_id: Uuid) -> AppResult<bool> {
+        let user_id = user_id.to_string();
+        sqlx::query_scalar!(
+            r#"
+            SELECT (email_verified_at IS NOT NULL OR verification_grandfathered) as "allowed!: bool"
+            FROM users WHERE id = ?1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(AppError::UserNotFound)
+    }
+
+    /// Record that the user's email address is verified
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn mark_email_verified(&self, user_id: Uuid) -> AppResult<()> {
+        let user_id = user_id.to_string();
+        let now = Utc::now();
+        sqlx::query!(
+            r#"
+            UPDATE users SET email_verified_at = ?1
+            WHERE id = ?2 AND email_verified_at IS NULL
+            "#,
+            now,
+            user_id
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Mail the user a link to verify their email address
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::BadRequest` if the email is already verified, or an
+    /// error if the token cannot be stored or the email cannot be sent
+    pub async fn send_verification(&self, user: &User) -> AppResult<()> {
+        if self.is_email_verified(user.id).await? {
+            return Err(AppError::BadRequest(
+                "Email is already verified".to_string(),
+            ));
+        }
+
+        let Some(link) = self.issue(user, AccountEmailPurpose::VerifyEmail).await? else {
+            return Ok(());
+        };
+        self.mailer
+            .send(&Email {
+                to: user.email.clone(),
+                subject: "Verify your email address".to_string(),
+                body: format!(
+                    "Open this link to verify your email address:\n\n{link}\n\n\
+                     The link expires in 24 hours. If you did not create an account, \
+                     you can ignore this email."
+                ),
+            })
+            .await
+    }
+
+    /// Verify the email address a token was sent to
+    ///
+    /// Returns the user whose email was verified.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::BadRequest` if the token is invalid, expired or used,
+    /// or the account's email changed since it was sent
+    pub async fn confirm_email(&self, token: &str) -> AppResult<Uuid> {
+        let mut tx = self.db.begin().await?;
+        let user_id = Self::consume(&mut tx, token, AccountEmailPurpose::VerifyEmail).await?;
+        let user_id_str = user_id.to_string();
+        let now = Utc::now();
+
+        sqlx::query!(
+            r#"
+            UPDATE users SET email_verified_at = COALESCE(email_verified_at, ?1)
+            WHERE id = ?2
+            "#,
+            now,
+            user_id_str
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::info!("Verified email of user {}", user_id);
+        Ok(user_id)
+    }
+
+    /// Mail a password reset link to the user with this email, if there is one
+    ///
+    /// Succeeds for unknown addresses too, so the result does not reveal which
+    /// emails have accounts.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the token cannot be stored or the email cannot be sent
+    pub async fn send_password_reset(&self, user: Option<&User>) -> AppResult<()> {
+        let Some(user) = user else {
+            tracing::info!("Password reset requested for an unknown email");
+            return Ok(());
+        };
+
+        let Some(link) = self.issue(user, AccountEmailPurpose::ResetPassword).await? else {
+            return Ok(());
+        };
+        self.mailer
+            .send(&Email {
+                to: user.email.clone(),
+                subject: "Reset your password".to_string(),
+                body: format!(
+                    "Open this link to choose a new password:\n\n{link}\n\n\
+                     The link expires in 1 hour and signs you out everywhere. \
+                     If you did not ask to reset your password, you can ignore this email."
+                ),
+            })
+            .await
+    }
+
+    /// Set a new password with a reset token
+    ///
+    /// The token also proves the user receives mail at the address, so the
+    /// email is marked verified. Returns the user whose password was reset.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::BadRequest` if the token is invalid, expired or used,
+    /// or the account's email changed since it was sent
+    pub async fn reset_password(&self, token: &str, password: &str) -> AppResult<Uuid> {
+        let hashed_password = hash_password(password).map_err(AppError::PasswordUtilError)?;
+
+        let mut tx = self.db.begin().await?;
+        let user_id = Self::consume(&mut tx, token, AccountEmailPurpose::ResetPassword).await?;
+        let user_id_str = user_id.to_string();
+        let now = Utc::now();
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET hashed_password = ?1, updated_at = ?2, email_verified_at = COALESCE(email_verified_at, ?2)
+            WHERE id = ?3
+            "#,
+            hashed_password,
+            now,
+            user_id_str
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::info!("Reset password of user {}", user_id);
+        Ok(user_id)
+    }
+
+    /// Delete expired and used tokens
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn cleanup_tokens(&self) -> AppResult<u64> {
+        let now = Utc::now();
+        let result = sqlx::query!(
+            "DELETE FROM account_email_tokens WHERE expires_at < ?1 OR used_at IS NOT NULL",
+            now
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Store a new token for the user and return the link to mail
+    ///
+    /// Earlier unused tokens of the same purpose stop working. Returns `None`
+    /// without issuing a token if one was sent within `RESEND_INTERVAL_SECONDS`.
+    async fn issue(&self, user: &User, purpose: AccountEmailPurpose) -> AppResult<Option<String>> {
+        let user_id = user.id.to_string();
+        let purpose_str = purpose.as_str();
+        let now = Utc::now();
+        let resend_after = now - Duration::seconds(RESEND_INTERVAL_SECONDS);
+
+        let mut tx = self.db.begin().await?;
+        let recent = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) FROM account_email_tokens
+            WHERE user_id = ?1 AND purpose = ?2 AND used_at IS NULL AND created_at > ?3
+            "#,
+            user_id,
+            purpose_str,
+            resend_after
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if recent > 0 {
+            tracing::info!(
+                "Not sending another {} email to user {} yet",
+                purpose_str,
+                user.id
+            );
+            return Ok(None);
+        }
+
+        sqlx::query!(
+            "DELETE FROM account_email_tokens WHERE user_id = ?1 AND purpose = ?2 AND used_at IS NULL",
+            user_id,
+            purpose_str
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let token = generate_opaque_token();
+        let token_hash = hash_token(&token);
+        let expires_at = now + purpose.ttl();
+        sqlx::query!(
+            r#"
+            INSERT INTO account_email_tokens (token_hash, user_id, purpose, email, created_at, expires_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            token_hash,
+            user_id,
+            purpose_str,
+            user.email,
+            now,
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Some(format!(
+            "{}{}?token={}",
+            self.client_url,
+            purpose.client_path(),
+            token
+        )))
+    }
+
+    /// Mark a token used and return the user it was issued for
+    async fn consume(
+        tx: &mut sqlx::SqliteConnection,
+        token: &str,
+        purpose: AccountEmailPurpose,
+    ) -> AppResult<Uuid> {
+        let token_hash = hash_token(token);
+        let purpose_str = purpose.as_str();
+        let now = Utc::now();
+
+        // The token only applies while the account still has the address it was sent to
+        let user_id = sqlx::query_scalar!(
+            r#"
+            UPDATE account_email_tokens SET used_at = ?1
+            WHERE token_hash = ?2 AND purpose = ?3 AND used_at IS NULL AND expires_at > ?1
+              AND email = (SELECT email FROM users WHERE users.id = account_email_tokens.user_id)
+            RETURNING user_id as "user_id!"
+            "#,
+            now,
+            token_hash,
+            purpose_str
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+        Uuid::parse_str(&user_id)
+            .map_err(|_| AppError::InternalServerError("Invalid user ID in token".to_string()))
+    }
+}
+
+#[cfg(test)]
+#[path = "account_email_service_tests.rs"]
+mod account_email_service_tests;
@@ -0,0 +1,240 @@
+//! Tests for the email verification and password reset service
+
+#[cfg(test)]
+mod tests {
+    use crate::core::password_utils::verify_password;
+    use crate::errors::AppError;
+    use crate::handlers::auth_handler::RegisterUserPayload;
+    use crate::models::User;
+    use crate::services::mail::OutboxMailer;
+    use crate::services::{AccountEmailService, UserServiceImpl};
+    use sqlx::SqlitePool;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect("sqlite::memory:")
+            .await
+            .expect("Failed to create test database");
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .expect("Failed to run migrations");
+
+        pool
+    }
+
+    async fn create_user(pool: &SqlitePool, email: &str) -> User {
+        UserServiceImpl::new(pool.clone())
+            .create_user(&RegisterUserPayload {
+                email: email.to_string(),
+                password: "old_password_123".to_string(),
+            })
+            .await
+            .expect("Failed to create test user")
+    }
+
+    fn service(pool: &SqlitePool, outbox: &Path) -> AccountEmailService {
+        AccountEmailService::new(
+            pool.clone(),
+            Arc::new(OutboxMailer::new(outbox)),
+            "https://app.example.com",
+        )
+    }
+
+    /// Contents of the emails in the outbox, oldest first
+    fn sent_emails(outbox: &Path) -> Vec<String> {
+        let mut paths: Vec<_> = std::fs::read_dir(outbox)
+            .map(|entries| {
+                entries
+                    .map(|entry| entry.expect("readable entry").path())
+                    .collect()
+            })
+            .unwrap_or_default();
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| std::fs::read_to_string(path).expect("readable email"))
+            .collect()
+    }
+
+    fn link_token(email: &str, path: &str) -> String {
+        let link = email
+            .split_whitespace()
+            .find(|word| word.starts_with("https://"))
+            .expect("email has a link");
+        let prefix = format!("https://app.example.com{path}?token=");
+        link.strip_prefix(&prefix)
+            .expect("link to client")
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_grandfathered_accounts_have_access_but_are_not_verified() {
+        let pool = setup_test_db().await;
+        let outbox = tempfile::tempdir().expect("temp dir");
+        let service = service(&pool, outbox.path());
+        let user = create_user(&pool, "legacy@example.com").await;
+        assert!(!service.has_verified_access(user.id).await.expect("access"));
+
+        sqlx::query("UPDATE users SET verification_grandfathered = TRUE WHERE id = ?1")
+            .bind(user.id.to_string())
+            .execute(&pool)
+            .await
+            .expect("grandfathered");
+        assert!(service.has_verified_access(user.id).await.expect("access"));
+        assert!(!service.is_email_verified(user.id).await.expect("status"));
+    }
+
+    #[tokio::test]
+    async fn test_email_verification() {
+        let pool = setup_test_db().await;
+        let outbox = tempfile::tempdir().expect("temp dir");
+        let service = service(&pool, outbox.path());
+        let user = create_user(&pool, "verify@example.com").await;
+        assert!(!service.is_email_verified(user.id).await.expect("status"));
+
+        service.send_verification(&user).await.expect("email sent");
+        let emails = sent_emails(outbox.path());
+        assert_eq!(emails.len(), 1);
+        assert!(emails[0].starts_with("To: verify@example.com\nSubject: Verify your email"));
+        let token = link_token(&emails[0], "/auth/verify-email");
+
+        // Only the hash of the token is stored
+        let stored: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM account_email_tokens WHERE token_hash = ?1")
+                .bind(&token)
+                .fetch_one(&pool)
+                .await
+                .expect("count");
+        assert_eq!(stored, 0);
+
+        assert_eq!(
+            service.confirm_email(&token).await.expect("verified"),
+            user.id
+        );
+        assert!(service.is_email_verified(user.id).await.expect("status"));
+
+        // Tokens are single-use, and verified users get no more emails
+        assert!(matches!(
+            service.confirm_email(&token).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(matches!(
+            service.send_verification(&user).await,
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_password_reset() {
+        let pool = setup_test_db().await;
+        let outbox = tempfile::tempdir().expect("temp dir");
+        let service = service(&pool, outbox.path());
+        let user = create_user(&pool, "reset@example.com").await;
+
+        service.send_password_reset(None).await.expect("no email");
+        assert!(sent_emails(outbox.path()).is_empty());
+
+        service
+            .send_password_reset(Some(&user))
+            .await
+            .expect("email sent");
+        let token = link_token(&sent_emails(outbox.path())[0], "/auth/reset-password");
+
+        // A reset token does not verify an email, nor the other way around
+        assert!(matches!(
+            service.confirm_email(&token).await,
+            Err(AppError::BadRequest(_))
+        ));
+
+        service
+            .reset_password(&token, "new_password_456")
+            .await
+            .expect("password reset");
+        let hashed: String = sqlx::query_scalar("SELECT hashed_password FROM users WHERE id = ?1")
+            .bind(user.id.to_string())
+            .fetch_one(&pool)
+            .await
+            .expect("user");
+        assert!(verify_password("new_password_456", &hashed).is_ok());
+        assert!(service.is_email_verified(user.id).await.expect("status"));
+
+        assert!(matches!(
+            service.reset_password(&token, "another_password_789").await,
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tokens_expire_and_are_replaced() {
+        let pool = setup_test_db().await;
+        let outbox = tempfile::tempdir().expect("temp dir");
+        let service = service(&pool, outbox.path());
+        let user = create_user(&pool, "expire@example.com").await;
+
+        service
+            .send_password_reset(Some(&user))
+            .await
+            .expect("email sent");
+        let first = link_token(&sent_emails(outbox.path())[0], "/auth/reset-password");
+
+        // A second request right away sends nothing
+        service
+            .send_password_reset(Some(&user))
+            .await
+            .expect("request accepted");
+        assert_eq!(sent_emails(outbox.path()).len(), 1);
+
+        // Once the resend interval passed, a new token replaces the first
+        sqlx::query("UPDATE account_email_tokens SET created_at = datetime('now', '-2 minutes')")
+            .execute(&pool)
+            .await
+            .expect("backdated");
+        service
+            .send_password_reset(Some(&user))
+            .await
+            .expect("email sent");
+        let emails = sent_emails(outbox.path());
+        assert_eq!(emails.len(), 2);
+        let second = link_token(&emails[1], "/auth/reset-password");
+        assert!(matches!(
+            service.reset_password(&first, "new_password_456").await,
+            Err(AppError::BadRequest(_))
+        ));
+
+        // Expired tokens do not work and are cleaned up
+        sqlx::query("UPDATE account_email_tokens SET expires_at = datetime('now', '-1 minute')")
+            .execute(&pool)
+            .await
+            .expect("expired");
+        assert!(matches!(
+            service.reset_password(&second, "new_password_456").await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert_eq!(service.cleanup_tokens().await.expect("cleanup"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_token_requires_unchanged_email() {
+        let pool = setup_test_db().await;
+        let outbox = tempfile::tempdir().expect("temp dir");
+        let service = service(&pool, outbox.path());
+        let user = create_user(&pool, "old@example.com").await;
+
+        service.send_verification(&user).await.expect("email sent");
+        let token = link_token(&sent_emails(outbox.path())[0], "/auth/verify-email");
+
+        sqlx::query("UPDATE users SET email = 'new@example.com' WHERE id = ?1")
+            .bind(user.id.to_string())
+            .execute(&pool)
+            .await
+            .expect("email changed");
+        assert!(matches!(
+            service.confirm_email(&token).await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(!service.is_email_verified(user.id).await.expect("status"));
+    }
+}
@@ -2,6 +2,8 @@
 
 use sqlx::SqlitePool;
 
+use crate::ai::ModelPricing;
+use crate::ai::models::SchemaAttempt;
 use crate::errors::{AppError, AppResult};
 use crate::models::{
     AiConversation, AiMessage, AiUsage, ConversationResponse, ConversationWithMessages,
@@ -21,12 +23,24 @@ pub struct UsageRecord<'a> {
 
 pub struct AiDataService {
     db: SqlitePool,
+    pricing: ModelPricing,
 }
 
 impl AiDataService {
+    /// Create the service, pricing usage with the built-in model prices
     #[must_use]
     pub fn new(db: SqlitePool) -> Self {
-        Self { db }
+        Self {
+            db,
+            pricing: ModelPricing::default(),
+        }
+    }
+
+    /// Price usage with the given model prices
+    #[must_use]
+    pub fn with_pricing(mut self, pricing: ModelPricing) -> Self {
+        self.pricing = pricing;
+        self
     }
 
     /// Create a new conversation
@@ -141,6 +155,88 @@ impl AiDataService {
         Ok(results)
     }
 
+    /// Get a conversation the user owns
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the conversation does not exist, is
+    /// archived or belongs to another user, or an error if the database
+    /// operation fails
+    pub async fn get_conversation(
+        &self,
+        conversation_id: &str,
+        user_id: &str,
+    ) -> AppResult<AiConversation> {
+        sqlx::query_as!(
+            AiConversation,
+            r#"
+            SELECT id, user_id, title, model, system_prompt, created_at, updated_at, archived_at, metadata
+            FROM ai_conversations
+            WHERE id = ?1 AND user_id = ?2 AND archived_at IS NULL
+            "#,
+            conversation_id,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Conversation not found".to_string()))
+    }
+
+    /// Get a message of a conversation
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the conversation has no such message, or
+    /// an error if the database operation fails
+    pub async fn get_message(
+        &self,
+        conversation_id: &str,
+        message_id: &str,
+    ) -> AppResult<MessageResponse> {
+        let message = sqlx::query_as!(
+            AiMessage,
+            r#"
+            SELECT id, conversation_id, role, content, token_count, created_at, metadata, parent_message_id
+            FROM ai_messages
+            WHERE id = ?1 AND conversation_id = ?2
+            "#,
+            message_id,
+            conversation_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Message not found".to_string()))?;
+
+        Ok(message.into())
+    }
+
+    /// Get the latest message of a conversation, which new messages follow by
+    /// default
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails
+    pub async fn latest_message(
+        &self,
+        conversation_id: &str,
+    ) -> AppResult<Option<MessageResponse>> {
+        let message = sqlx::query_as!(
+            AiMessage,
+            r#"
+            SELECT id, conversation_id, role, content, token_count, created_at, metadata, parent_message_id
+            FROM ai_messages
+            WHERE conversation_id = ?1
+            ORDER BY created_at DESC, rowid DESC
+            LIMIT 1
+            "#,
+            conversation_id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(message.map(Into::into))
+    }
+
     /// Get a conversation with its messages
     ///
     /// # Errors
@@ -170,26 +266,18 @@ impl AiDataService {
         let messages = sqlx::query_as!(
             AiMessage,
             r#"
-            SELECT id, conversation_id, role, content, token_count, created_at, metadata
+            SELECT id, conversation_id, role, content, token_count, created_at, metadata, parent_message_id
             FROM ai_messages
             WHERE conversation_id = ?1
-            ORDER BY created_at ASC
+            ORDER BY created_at ASC, rowid ASC
             "#,
             conversation_id
         )
         .fetch_all(&self.db)
         .await?;
 
-        let message_responses: Vec<MessageResponse> = messages
-            .into_iter()
-            .map(|msg| MessageResponse {
-                id: msg.id,
-                role: msg.role,
-                content: msg.content,
-                token_count: msg.token_count,
-                created_at: msg.created_at,
-            })
-            .collect();
+        let message_responses: Vec<MessageResponse> =
+            messages.into_iter().map(MessageResponse::from).collect();
 
         Ok(ConversationWithMessages {
             conversation: ConversationResponse {
@@ -208,9 +296,13 @@ impl AiDataService {
 
     /// Add a message to a conversation
     ///
+    /// The message follows `parent_message_id` if given, forking a new branch
+    /// when that message already has replies, and the latest message otherwise.
+    ///
     /// # Errors
     ///
-    /// Returns an error if the conversation is not found or database operation fails
+    /// Returns an error if the conversation or parent message is not found or
+    /// database operation fails
     pub async fn add_message(
         &self,
         conversation_id: &str,
@@ -234,12 +326,24 @@ impl AiDataService {
             return Err(AppError::BadRequest("Conversation not found".to_string()));
         }
 
-        let message = AiMessage::new(conversation_id.to_string(), request.role, request.content);
+        let parent = match &request.parent_message_id {
+            Some(parent_id) => Some(self.get_message(conversation_id, parent_id).await?),
+            None => self.latest_message(conversation_id).await?,
+        };
+
+        let mut message =
+            AiMessage::new(conversation_id.to_string(), request.role, request.content);
+        if let Some(token_count) = request.token_count {
+            message = message.with_token_count(token_count);
+        }
+        if let Some(parent) = parent {
+            message = message.with_parent(parent.id);
+        }
 
         sqlx::query!(
             r#"
-            INSERT INTO ai_messages (id, conversation_id, role, content, token_count, created_at, metadata)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            INSERT INTO ai_messages (id, conversation_id, role, content, token_count, created_at, metadata, parent_message_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
             "#,
             message.id,
             message.conversation_id,
@@ -247,7 +351,8 @@ impl AiDataService {
             message.content,
             message.token_count,
             message.created_at,
-            message.metadata
+            message.metadata,
+            message.parent_message_id
         )
         .execute(&self.db)
         .await?;
@@ -266,16 +371,10 @@ impl AiDataService {
         .execute(&self.db)
         .await?;
 
-        Ok(MessageResponse {
-            id: message.id,
-            role: message.role,
-            content: message.content,
-            token_count: message.token_count,
-            created_at: message.created_at,
-        })
+        Ok(message.into())
     }
 
-    /// Record AI usage statistics
+    /// Record AI usage statistics, with its cost if the model has a price
     ///
     /// # Errors
     ///
@@ -306,10 +405,22 @@ impl AiDataService {
             usage
         };
 
+        let usage = if let Some(price) = self.pricing.price_for(usage_record.model) {
+            let cost =
+                price.cost_microcents(usage_record.prompt_tokens, usage_record.completion_tokens);
+            usage.with_cost_microcents(cost)
+        } else {
+            tracing::debug!(
+                "No price for model {}, usage recorded without cost",
+                usage_record.model
+            );
+            usage
+        };
+
         sqlx::query!(
             r#"
-            INSERT INTO ai_usage (id, conversation_id, user_id, model, prompt_tokens, completion_tokens, total_tokens, cost_cents, created_at, request_id, duration_ms)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+            INSERT INTO ai_usage (id, conversation_id, user_id, model, prompt_tokens, completion_tokens, total_tokens, cost_cents, created_at, request_id, duration_ms, cost_microcents)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
             "#,
             usage.id,
             usage.conversation_id,
@@ -321,7 +432,8 @@ impl AiDataService {
             usage.cost_cents,
             usage.created_at,
             usage.request_id,
-            usage.duration_ms
+            usage.duration_ms,
+            usage.cost_microcents
         )
         .execute(&self.db)
         .await?;
@@ -329,6 +441,38 @@ impl AiDataService {
         Ok(())
     }
 
+    /// Record usage for every attempt made while producing a structured response
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails
+    pub async fn record_schema_attempts(
+        &self,
+        user_id: &str,
+        conversation_id: Option<&str>,
+        attempts: &[SchemaAttempt],
+    ) -> AppResult<()> {
+        for attempt in attempts {
+            let (prompt_tokens, completion_tokens) =
+                attempt.usage.as_ref().map_or((0, 0), |usage| {
+                    (i64::from(usage.prompt), i64::from(usage.completion))
+                });
+
+            self.record_usage(UsageRecord {
+                conversation_id,
+                user_id,
+                model: &attempt.model,
+                prompt_tokens,
+                completion_tokens,
+                request_id: Some(&attempt.request_id),
+                duration_ms: Some(attempt.duration_ms),
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
     /// Get usage statistics for a user
     ///
     /// # Errors
@@ -2,6 +2,7 @@
 
 #[cfg(test)]
 mod tests {
+    use crate::ai::{ModelPrice, ModelPricing};
     use crate::errors::AppError;
     use crate::models::{CreateConversationRequest, CreateMessageRequest};
     use crate::services::AiDataService;
@@ -196,6 +197,8 @@ mod tests {
         let msg1 = CreateMessageRequest {
             role: "user".to_string(),
             content: "Hello".to_string(),
+            token_count: None,
+            parent_message_id: None,
         };
         service
             .add_message(&conversation.id, &user_id, msg1)
@@ -205,6 +208,8 @@ mod tests {
         let msg2 = CreateMessageRequest {
             role: "assistant".to_string(),
             content: "Hi there!".to_string(),
+            token_count: Some(3),
+            parent_message_id: None,
         };
         service
             .add_message(&conversation.id, &user_id, msg2)
@@ -221,6 +226,8 @@ mod tests {
         assert_eq!(result.messages.len(), 2);
         assert_eq!(result.messages[0].content, "Hello");
         assert_eq!(result.messages[1].content, "Hi there!");
+        assert_eq!(result.messages[0].token_count, None);
+        assert_eq!(result.messages[1].token_count, Some(3));
         assert_eq!(result.conversation.message_count, Some(2));
         assert!(result.conversation.last_message_at.is_some());
     }
@@ -233,6 +240,8 @@ mod tests {
         let msg_request = CreateMessageRequest {
             role: "user".to_string(),
             content: "Hello".to_string(),
+            token_count: None,
+            parent_message_id: None,
         };
 
         let result = service
@@ -246,6 +255,83 @@ mod tests {
         }
     }
 
+    #[sqlx::test]
+    async fn test_add_message_branches_from_parent(pool: SqlitePool) {
+        let service = AiDataService::new(pool.clone());
+        let user_id = create_test_user(&pool).await;
+        let other_id = create_test_user(&pool).await;
+
+        let conversation = service
+            .create_conversation(
+                &user_id,
+                CreateConversationRequest {
+                    model: "gpt-4".to_string(),
+                    title: None,
+                    system_prompt: None,
+                },
+            )
+            .await
+            .expect("Failed to create conversation for branching test");
+        let message = |role: &str, content: &str, parent: Option<&str>| CreateMessageRequest {
+            role: role.to_string(),
+            content: content.to_string(),
+            token_count: None,
+            parent_message_id: parent.map(str::to_string),
+        };
+
+        // Messages follow the latest one by default
+        let question = service
+            .add_message(&conversation.id, &user_id, message("user", "Hi", None))
+            .await
+            .expect("Failed to add question");
+        let answer = service
+            .add_message(
+                &conversation.id,
+                &user_id,
+                message("assistant", "Hello", None),
+            )
+            .await
+            .expect("Failed to add answer");
+        assert_eq!(question.parent_message_id, None);
+        assert_eq!(
+            answer.parent_message_id.as_deref(),
+            Some(question.id.as_str())
+        );
+
+        // A second reply to the question forks a branch and becomes the latest
+        let fork = service
+            .add_message(
+                &conversation.id,
+                &user_id,
+                message("assistant", "Hey", Some(&question.id)),
+            )
+            .await
+            .expect("Failed to fork");
+        assert_eq!(
+            fork.parent_message_id.as_deref(),
+            Some(question.id.as_str())
+        );
+        let latest = service
+            .latest_message(&conversation.id)
+            .await
+            .expect("Failed to get latest message")
+            .expect("Conversation has messages");
+        assert_eq!(latest.id, fork.id);
+
+        let result = service
+            .add_message(
+                &conversation.id,
+                &user_id,
+                message("user", "Hi", Some("nonexistent")),
+            )
+            .await;
+        assert!(matches!(result, Err(AppError::NotFound(_))));
+        assert!(matches!(
+            service.get_conversation(&conversation.id, &other_id).await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+
     #[sqlx::test]
     async fn test_add_message_updates_conversation_timestamp(pool: SqlitePool) {
         let service = AiDataService::new(pool.clone());
@@ -270,6 +356,8 @@ mod tests {
         let msg_request = CreateMessageRequest {
             role: "user".to_string(),
             content: "Hello".to_string(),
+            token_count: None,
+            parent_message_id: None,
         };
         service
             .add_message(&conversation.id, &user_id, msg_request)
@@ -287,7 +375,8 @@ mod tests {
 
     #[sqlx::test]
     async fn test_record_usage(pool: SqlitePool) {
-        let service = AiDataService::new(pool.clone());
+        let pricing = ModelPricing::load("model_prices.toml").expect("Failed to load prices");
+        let service = AiDataService::new(pool.clone()).with_pricing(pricing);
         let user_id = create_test_user(&pool).await;
 
         // Create a conversation first to get a valid conversation_id
@@ -323,6 +412,57 @@ mod tests {
             .expect("Failed to get usage stats");
         assert_eq!(stats.total_tokens, 150);
         assert_eq!(stats.models_used, vec!["gpt-4"]);
+
+        // GPT-4 costs $30 and $60 per million prompt and completion tokens
+        let cost = sqlx::query!(
+            "SELECT cost_cents, cost_microcents FROM ai_usage WHERE user_id = ?1",
+            user_id
+        )
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to load usage");
+        assert_eq!(cost.cost_microcents, Some(600_000));
+        assert_eq!(cost.cost_cents, Some(1));
+    }
+
+    #[sqlx::test]
+    async fn test_record_usage_with_configured_prices(pool: SqlitePool) {
+        let pricing = ModelPricing::default().with_model(
+            "local",
+            ModelPrice {
+                prompt: 10.0,
+                completion: 20.0,
+            },
+        );
+        let service = AiDataService::new(pool.clone()).with_pricing(pricing);
+        let user_id = create_test_user(&pool).await;
+
+        for model in ["local-llama", "unpriced-model"] {
+            service
+                .record_usage(UsageRecord {
+                    conversation_id: None,
+                    user_id: &user_id,
+                    model,
+                    prompt_tokens: 1_000,
+                    completion_tokens: 1_000,
+                    request_id: None,
+                    duration_ms: None,
+                })
+                .await
+                .expect("Failed to record usage");
+        }
+
+        let costs = sqlx::query!(
+            "SELECT model, cost_cents, cost_microcents FROM ai_usage WHERE user_id = ?1 ORDER BY model",
+            user_id
+        )
+        .fetch_all(&pool)
+        .await
+        .expect("Failed to load usage");
+        assert_eq!(costs[0].model, "local-llama");
+        assert_eq!(costs[0].cost_microcents, Some(30_000));
+        assert_eq!(costs[0].cost_cents, Some(0));
+        assert_eq!(costs[1].cost_microcents, None);
     }
 
     #[sqlx::test]
@@ -390,6 +530,8 @@ mod tests {
             let msg = CreateMessageRequest {
                 role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
                 content: format!("Message {i}"),
+                token_count: None,
+                parent_message_id: None,
             };
             service
                 .add_message(&conversation.id, &user_id, msg)
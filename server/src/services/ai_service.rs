@@ -1,54 +1,236 @@
 //! AI service that integrates provider and schema validation
 
+use crate::ai::embeddings::{LOCAL_EMBEDDING_MODEL, LocalEmbedder};
+use crate::ai::models::{SchemaAttempt, StructuredResponse};
 use crate::ai::{
     AiError, AiProvider, AiResult, ChatMessage, ChatRequest, ChatResponse, ChatRole,
-    OpenRouterProvider, SchemaValidator, prompts::PromptRenderer, schemas,
+    ContextWindows, EmbeddingRequest, EmbeddingResponse, FunctionDispatcher, ProviderRegistry,
+    SchemaValidator,
+    functions::{get_business_analyst_functions, run_function_loop},
+    prompts::{PromptRegistry, TemplateInfo},
+    providers::{ChatStream, ModelRoute, ProviderInfo, RetryPolicy, RetryingProvider},
+    schemas,
+    tokenizer::{Tokenizer, Tokenizers},
 };
+use crate::models::AiPersona;
 use std::sync::Arc;
+use std::time::Instant;
+
+/// Corrective re-prompts for invalid structured output when
+/// `AI_SCHEMA_REPAIR_ATTEMPTS` is not set
+const DEFAULT_SCHEMA_REPAIR_ATTEMPTS: u32 = 2;
 
 /// Main AI service that coordinates all AI functionality
 pub struct AiService {
+    providers: Arc<ProviderRegistry>,
+    /// The registry wrapped in the retry policy; all chat requests go through it
     provider: Arc<dyn AiProvider>,
     schema_validator: SchemaValidator,
-    prompt_renderer: PromptRenderer,
+    prompts: PromptRegistry,
+    /// How many times invalid structured output is sent back for correction
+    schema_repair_attempts: u32,
+    /// Provider embedding model; texts are embedded locally when unset
+    embedding_model: Option<String>,
+    tokenizers: Tokenizers,
+    context_windows: ContextWindows,
 }
 
 impl AiService {
-    /// Create a new AI service with the given configuration
+    /// Create a new AI service with providers configured from the environment
     ///
     /// # Errors
     ///
     /// Returns an error if:
-    /// - The AI provider cannot be initialized
+    /// - No AI provider is configured, or one cannot be initialized
+    /// - The routing, retry or schema repair configuration is invalid
+    pub fn new() -> AiResult<Self> {
+        let schema_repair_attempts = match std::env::var("AI_SCHEMA_REPAIR_ATTEMPTS") {
+            Ok(value) => value.trim().parse().map_err(|_| {
+                AiError::Configuration(format!(
+                    "AI_SCHEMA_REPAIR_ATTEMPTS must be a number, got '{value}'"
+                ))
+            })?,
+            Err(_) => DEFAULT_SCHEMA_REPAIR_ATTEMPTS,
+        };
+
+        let embedding_model = std::env::var("AI_EMBEDDING_MODEL")
+            .ok()
+            .map(|model| model.trim().to_string())
+            .filter(|model| !model.is_empty());
+
+        Ok(
+            Self::with_providers(ProviderRegistry::from_env()?, RetryPolicy::from_env()?)?
+                .with_schema_repair_attempts(schema_repair_attempts)
+                .with_embedding_model(embedding_model),
+        )
+    }
+
+    /// Create a new AI service backed by a single provider, without retries
     ///
-    /// # Panics
+    /// Used to inject a provider such as `MockProvider` in tests.
     ///
-    /// Panics if `OPENROUTER_API_KEY` environment variable is not set in production
-    pub fn new() -> AiResult<Self> {
-        // Initialize provider using environment configuration
-        let provider: Arc<dyn AiProvider> = Arc::new(OpenRouterProvider::new()?);
+    /// # Errors
+    ///
+    /// Returns an error if the schemas or prompt templates cannot be loaded
+    pub fn with_provider(provider: Arc<dyn AiProvider>) -> AiResult<Self> {
+        Self::with_providers(ProviderRegistry::new(provider), RetryPolicy::none())
+    }
+
+    /// Create a new AI service that routes requests through the given providers
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schemas or prompt templates cannot be loaded
+    pub fn with_providers(providers: ProviderRegistry, retry: RetryPolicy) -> AiResult<Self> {
+        let providers = Arc::new(providers);
+        let provider: Arc<dyn AiProvider> = Arc::new(RetryingProvider::new(
+            Arc::clone(&providers) as Arc<dyn AiProvider>,
+            retry,
+        ));
 
         // Initialize schema validator with common schemas
         let mut schema_validator = SchemaValidator::new();
         schema_validator.register_schema("moderation_response", schemas::moderation_response())?;
         schema_validator.register_schema("code_analysis", schemas::code_analysis())?;
 
-        // Initialize prompt renderer
-        let prompt_renderer = PromptRenderer::new()?;
+        // Load prompt templates
+        let prompts = PromptRegistry::from_env()?;
+        let tokenizers = Tokenizers::from_env()?;
+        let context_windows = ContextWindows::from_env()?;
 
         Ok(Self {
+            providers,
             provider,
             schema_validator,
-            prompt_renderer,
+            prompts,
+            schema_repair_attempts: DEFAULT_SCHEMA_REPAIR_ATTEMPTS,
+            embedding_model: None,
+            tokenizers,
+            context_windows,
         })
     }
 
-    /// Get the AI provider
+    /// Set how many times invalid structured output is sent back to the model
+    /// for correction; `0` accepts or rejects the first response as is
+    #[must_use]
+    pub fn with_schema_repair_attempts(mut self, attempts: u32) -> Self {
+        self.schema_repair_attempts = attempts;
+        self
+    }
+
+    /// Embed texts with a provider model instead of locally
+    ///
+    /// `None` embeds locally with `LocalEmbedder`, which needs no network.
+    #[must_use]
+    pub fn with_embedding_model(mut self, model: Option<String>) -> Self {
+        self.embedding_model = model;
+        self
+    }
+
+    /// Name of the model texts are embedded with
+    ///
+    /// Vectors are only comparable with vectors from the same model.
+    #[must_use]
+    pub fn embedding_model(&self) -> &str {
+        self.embedding_model
+            .as_deref()
+            .unwrap_or(LOCAL_EMBEDDING_MODEL)
+    }
+
+    /// Whether texts are embedded in-process rather than by the provider
+    #[must_use]
+    pub fn embeds_locally(&self) -> bool {
+        self.embedding_model.is_none()
+    }
+
+    /// Tokenizer for a model, or for the default model when `None`
+    #[must_use]
+    pub fn tokenizer(&self, model: Option<&str>) -> Tokenizer {
+        self.tokenizers
+            .for_model(model.unwrap_or_else(|| self.provider.model()))
+    }
+
+    /// Set the context windows of models
+    #[must_use]
+    pub fn with_context_windows(mut self, context_windows: ContextWindows) -> Self {
+        self.context_windows = context_windows;
+        self
+    }
+
+    /// Context window of a model, or of the default model when `None`, in tokens
+    #[must_use]
+    pub fn context_window(&self, model: Option<&str>) -> usize {
+        self.context_windows
+            .window_for(model.unwrap_or_else(|| self.provider.model()))
+    }
+
+    /// Embed texts for similarity search
+    ///
+    /// Uses the configured provider embedding model, or embeds locally when
+    /// none is configured. Provider failures are returned rather than falling
+    /// back, so vectors from different models are never mixed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider fails or returns the wrong number of vectors
+    pub async fn embed(&self, input: Vec<String>) -> AiResult<EmbeddingResponse> {
+        let Some(model) = &self.embedding_model else {
+            let embedder = LocalEmbedder::default();
+            return Ok(EmbeddingResponse {
+                model: LOCAL_EMBEDDING_MODEL.to_string(),
+                embeddings: input.iter().map(|text| embedder.embed(text)).collect(),
+                usage: None,
+            });
+        };
+        if input.is_empty() {
+            return Ok(EmbeddingResponse {
+                model: model.clone(),
+                embeddings: Vec::new(),
+                usage: None,
+            });
+        }
+
+        let inputs = input.len();
+        let mut response = self
+            .provider
+            .embed(EmbeddingRequest::new(model.clone(), input))
+            .await?;
+        if response.embeddings.len() != inputs {
+            return Err(AiError::Provider(format!(
+                "Expected {inputs} embeddings, got {}",
+                response.embeddings.len()
+            )));
+        }
+        // Record vectors under the configured name even if the provider reports
+        // a more specific one, so stored vectors stay comparable
+        response.model.clone_from(model);
+        Ok(response)
+    }
+
+    /// Get the AI provider, which routes each request and retries failures
     #[must_use]
     pub fn provider(&self) -> Arc<dyn AiProvider> {
         Arc::clone(&self.provider)
     }
 
+    /// Get the AI provider a request for `model` is routed to
+    #[must_use]
+    pub fn provider_for(&self, model: Option<&str>) -> Arc<dyn AiProvider> {
+        self.providers.resolve(model)
+    }
+
+    /// Apply a persona's routing to a request
+    ///
+    /// If the persona is pinned to a model and the request does not name one,
+    /// the request uses the persona's model.
+    #[must_use]
+    pub fn for_persona(&self, persona: AiPersona, mut request: ChatRequest) -> ChatRequest {
+        if request.model.is_none() {
+            request.model = self.providers.persona_model(persona).map(str::to_string);
+        }
+        request
+    }
+
     /// Send a chat message to the AI provider
     ///
     /// # Errors
@@ -58,6 +240,57 @@ impl AiService {
         self.provider.chat(request).await
     }
 
+    /// Stream a chat response from the AI provider as it is generated
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider fails to start the stream
+    pub async fn chat_stream(&self, request: ChatRequest) -> AiResult<ChatStream> {
+        self.provider.chat_stream(request).await
+    }
+
+    /// Chat with function calling, executing each call until the model gives a final answer
+    ///
+    /// The request should offer functions via `ChatRequest::with_functions`.
+    /// Calls are applied to the dispatcher's session state and their results
+    /// sent back to the model.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the provider fails or the model never stops calling functions
+    pub async fn chat_with_functions(
+        &self,
+        request: ChatRequest,
+        dispatcher: &mut FunctionDispatcher,
+    ) -> AiResult<ChatResponse> {
+        run_function_loop(self.provider.as_ref(), request, dispatcher).await
+    }
+
+    /// Continue a conversation as a persona
+    ///
+    /// Renders the persona's conversation template with `context` (the
+    /// conversation so far and any notes), applies the persona's model routing
+    /// and, if the persona supports it, offers the issue-drafting functions,
+    /// whose calls update the dispatcher's session state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template cannot be rendered, the provider fails
+    /// or the model never stops calling functions
+    pub async fn chat_as_persona(
+        &self,
+        persona: AiPersona,
+        context: &serde_json::Value,
+        dispatcher: &mut FunctionDispatcher,
+    ) -> AiResult<ChatResponse> {
+        let messages = self.render_template(persona.template_name(), context)?;
+        let mut request = self.for_persona(persona, ChatRequest::new(messages));
+        if persona.supports_functions() {
+            request = request.with_functions(&get_business_analyst_functions());
+        }
+        self.chat_with_functions(request, dispatcher).await
+    }
+
     /// Send a system message to the AI
     ///
     /// # Errors
@@ -69,15 +302,17 @@ impl AiService {
         user_content: String,
     ) -> AiResult<String> {
         let request = ChatRequest {
-            model: Some(self.provider.model().to_string()),
+            model: Some(self.providers.default_provider().model().to_string()),
             messages: vec![
                 ChatMessage {
                     role: ChatRole::System,
                     content: system_content,
+                    function_call: None,
                 },
                 ChatMessage {
                     role: ChatRole::User,
                     content: user_content,
+                    function_call: None,
                 },
             ],
             temperature: Some(0.7),
@@ -99,10 +334,22 @@ impl AiService {
             .ok_or_else(|| AiError::InvalidRequest("No message content in response".to_string()))
     }
 
-    /// Get the provider name
+    /// Get the default provider name
+    #[must_use]
+    pub fn provider_name(&self) -> String {
+        self.providers.default_provider().name().to_string()
+    }
+
+    /// List the configured providers and the models they serve
+    #[must_use]
+    pub fn list_providers(&self) -> Vec<ProviderInfo> {
+        self.providers.providers()
+    }
+
+    /// List the model prefix routing rules
     #[must_use]
-    pub fn provider_name(&self) -> &str {
-        self.provider.name()
+    pub fn model_routes(&self) -> &[ModelRoute] {
+        self.providers.model_routes()
     }
 
     /// List available schemas
@@ -123,119 +370,153 @@ impl AiService {
         template_name: &str,
         context: &serde_json::Value,
     ) -> AiResult<ChatResponse> {
-        // Render the prompt using the template
-        let rendered = self
-            .prompt_renderer
-            .render_request(template_name, context)?;
-
-        // Convert rendered messages to ChatMessage
-        let messages = if let Some(messages_array) = rendered.as_array() {
-            messages_array
-                .iter()
-                .filter_map(|msg| {
-                    let role = msg.get("role")?.as_str()?;
-                    let msg_content = msg.get("content")?.as_str()?;
-                    let role = match role {
-                        "system" => ChatRole::System,
-                        "user" => ChatRole::User,
-                        "assistant" => ChatRole::Assistant,
-                        _ => return None,
-                    };
-                    Some(ChatMessage {
-                        role,
-                        content: msg_content.to_string(),
-                    })
-                })
-                .collect()
-        } else {
-            return Err(AiError::InvalidRequest(
-                "Template did not render to an array of messages".to_string(),
-            ));
-        };
-
+        let messages = self.render_template(template_name, context)?;
         let request = ChatRequest::new(messages);
         self.chat(request).await
     }
 
-    /// Chat with schema validation
+    /// Render the latest version of a prompt template into chat messages
+    fn render_template(
+        &self,
+        template_name: &str,
+        context: &serde_json::Value,
+    ) -> AiResult<Vec<ChatMessage>> {
+        let rendered = self.prompts.render(template_name, context)?;
+        tracing::debug!(
+            "Rendered prompt template '{}' v{}",
+            rendered.name,
+            rendered.version
+        );
+        Ok(rendered.messages)
+    }
+
+    /// List the loaded prompt templates
+    #[must_use]
+    pub fn list_templates(&self) -> Vec<TemplateInfo> {
+        self.prompts.templates()
+    }
+
+    /// Chat with schema validation, asking the model to repair invalid output
+    ///
+    /// When the response is not valid JSON or does not match the schema, the
+    /// model is shown its previous output and the violations and asked for a
+    /// corrected response, up to the configured number of repair attempts.
     ///
     /// # Errors
     ///
     /// Returns an error if:
     /// - The schema doesn't exist
     /// - The AI provider fails
-    /// - The response doesn't match the schema
+    /// - The response still doesn't match the schema after every repair attempt
+    ///   (`AiError::SchemaRepairFailed`, which carries the attempts made)
     pub async fn chat_with_schema(
         &self,
-        messages: Vec<ChatMessage>,
+        mut messages: Vec<ChatMessage>,
         schema_name: &str,
-    ) -> AiResult<ChatResponse> {
-        // Get the schema
-        let schemas_list = self.schema_validator.list_schemas();
-        if !schemas_list.contains(&schema_name.to_string()) {
-            return Err(AiError::InvalidRequest(format!(
-                "Schema '{schema_name}' not found"
-            )));
-        }
+    ) -> AiResult<StructuredResponse> {
+        let schema = self
+            .schema_validator
+            .get_schema(schema_name)
+            .cloned()
+            .ok_or_else(|| AiError::InvalidRequest(format!("Schema '{schema_name}' not found")))?;
 
-        // Get the actual schema value
-        let schema = match schema_name {
-            "moderation_response" => schemas::moderation_response(),
-            "code_analysis" => schemas::code_analysis(),
-            _ => {
-                return Err(AiError::InvalidRequest(format!(
-                    "Unknown schema: {schema_name}"
-                )));
-            }
-        };
+        let mut attempts = Vec::new();
+        loop {
+            let request = ChatRequest::new(messages.clone()).with_json_schema(schema.clone());
+            let started = Instant::now();
+            let response = self.chat(request).await?;
+            let mut attempt = SchemaAttempt {
+                request_id: response.id.clone(),
+                model: response.model.clone(),
+                usage: response.usage.clone(),
+                duration_ms: i64::try_from(started.elapsed().as_millis()).unwrap_or(i64::MAX),
+                error: None,
+            };
 
-        // Create request with JSON schema
-        let request = ChatRequest::new(messages).with_json_schema(schema);
+            let content = response.content();
+            match self.parse_structured(schema_name, &content) {
+                Ok(value) => {
+                    attempts.push(attempt);
+                    return Ok(StructuredResponse {
+                        response,
+                        value,
+                        attempts,
+                    });
+                }
+                Err(problem) => {
+                    tracing::warn!(
+                        "Structured output for schema '{schema_name}' rejected (attempt {}): {problem}",
+                        attempts.len() + 1
+                    );
+                    attempt.error = Some(problem.clone());
+                    attempts.push(attempt);
 
-        // Send to AI provider
-        let response = self.chat(request).await?;
+                    if attempts.len() > self.schema_repair_attempts as usize {
+                        return Err(AiError::SchemaRepairFailed {
+                            message: problem,
+                            attempts,
+                        });
+                    }
 
-        // Validate the response
-        if let Some(choice) = response.choices.first() {
-            let content_value: serde_json::Value = serde_json::from_str(&choice.message.content)
-                .map_err(|e| AiError::InvalidRequest(format!("Invalid JSON response: {e}")))?;
-            self.schema_validator
-                .validate(schema_name, &content_value)?;
+                    messages.push(ChatMessage {
+                        role: ChatRole::Assistant,
+                        content,
+                        function_call: None,
+                    });
+                    messages.push(ChatMessage {
+                        role: ChatRole::User,
+                        content: repair_prompt(&problem),
+                        function_call: None,
+                    });
+                }
+            }
         }
+    }
 
-        Ok(response)
+    /// Parse and validate structured output, describing what is wrong with it
+    fn parse_structured(
+        &self,
+        schema_name: &str,
+        content: &str,
+    ) -> Result<serde_json::Value, String> {
+        let value: serde_json::Value = serde_json::from_str(strip_code_fence(content))
+            .map_err(|e| format!("Response is not valid JSON: {e}"))?;
+
+        let violations = self
+            .schema_validator
+            .violations(schema_name, &value)
+            .map_err(|e| e.to_string())?;
+        if violations.is_empty() {
+            Ok(value)
+        } else {
+            Err(violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; "))
+        }
     }
 
     /// Analyze code
     ///
     /// # Errors
     ///
-    /// Returns an error if the AI provider fails
+    /// Returns an error if the AI provider fails or the analysis never matches
+    /// the `code_analysis` schema
     pub async fn analyze_code(
         &self,
         code: &str,
         language: Option<&str>,
         context: Option<&str>,
-    ) -> AiResult<serde_json::Value> {
+    ) -> AiResult<StructuredResponse> {
         let template_data = serde_json::json!({
             "code": code,
             "language": language.unwrap_or("unknown"),
             "context": context.unwrap_or("")
         });
 
-        let response = self
-            .chat_with_template("code_analysis", &template_data)
-            .await?;
-
-        // Extract and parse the response
-        let response_content = response
-            .choices
-            .first()
-            .map(|c| &c.message.content)
-            .ok_or_else(|| AiError::InvalidRequest("No response content".to_string()))?;
-
-        serde_json::from_str(response_content)
-            .map_err(|e| AiError::InvalidRequest(format!("Invalid JSON response: {e}")))
+        let messages = self.render_template("code_analysis", &template_data)?;
+        self.chat_with_schema(messages, "code_analysis").await
     }
 
     /// Check provider health
@@ -244,31 +525,43 @@ impl AiService {
     ///
     /// Returns an error if the provider is unhealthy
     pub async fn health_check(&self) -> AiResult<()> {
-        self.provider.health_check().await
+        self.providers.default_provider().health_check().await
     }
 
     /// Moderate content
     ///
     /// # Errors
     ///
-    /// Returns an error if the AI provider fails
-    pub async fn moderate_content(&self, content: &str) -> AiResult<serde_json::Value> {
+    /// Returns an error if the AI provider fails or the verdict never matches
+    /// the `moderation_response` schema
+    pub async fn moderate_content(&self, content: &str) -> AiResult<StructuredResponse> {
         let template_data = serde_json::json!({
             "content": content
         });
 
-        let response = self
-            .chat_with_template("content_moderation", &template_data)
-            .await?;
+        let messages = self.render_template("content_moderation", &template_data)?;
+        self.chat_with_schema(messages, "moderation_response").await
+    }
+}
 
-        // Extract and parse the response
-        let response_content = response
-            .choices
-            .first()
-            .map(|c| &c.message.content)
-            .ok_or_else(|| AiError::InvalidRequest("No response content".to_string()))?;
+/// Instructions sent back to the model after it produced invalid structured output
+fn repair_prompt(problem: &str) -> String {
+    format!(
+        "Your previous response could not be accepted: {problem}\n\n\
+         Reply again with only the corrected JSON, matching the required schema exactly. \
+         Do not include any explanation or code fences."
+    )
+}
 
-        serde_json::from_str(response_content)
-            .map_err(|e| AiError::InvalidRequest(format!("Invalid JSON response: {e}")))
-    }
+/// Remove a Markdown code fence some models wrap JSON output in
+fn strip_code_fence(content: &str) -> &str {
+    let trimmed = content.trim();
+    trimmed
+        .strip_prefix("```")
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map_or(trimmed, |inner| inner.trim_start_matches("json").trim())
 }
+
+#[cfg(test)]
+#[path = "ai_service_tests.rs"]
+mod ai_service_tests;
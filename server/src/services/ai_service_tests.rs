@@ -0,0 +1,122 @@
+//! Tests for AI service
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::embeddings::LOCAL_EMBEDDING_MODEL;
+    use crate::ai::providers::{MockProvider, MockReply};
+    use crate::ai::{AiError, ChatMessage, ChatRole};
+    use crate::services::AiService;
+    use std::sync::Arc;
+
+    const VALID_MODERATION: &str =
+        r#"{"safe": true, "issues": [], "severity": "none", "recommendation": "allow"}"#;
+
+    fn service_with(replies: &[&str]) -> (AiService, Arc<MockProvider>) {
+        let provider = Arc::new(MockProvider::new());
+        for reply in replies {
+            provider.push_reply(MockReply::text(reply));
+        }
+        let service = AiService::with_provider(provider.clone()).expect("AI service");
+        (service, provider)
+    }
+
+    fn user(content: &str) -> Vec<ChatMessage> {
+        vec![ChatMessage {
+            role: ChatRole::User,
+            content: content.to_string(),
+            function_call: None,
+        }]
+    }
+
+    #[tokio::test]
+    async fn test_chat_with_schema_repairs_invalid_output() {
+        let (service, provider) = service_with(&[
+            "Sure! Here is the verdict.",
+            r#"{"safe": true, "issues": [], "severity": "extreme", "recommendation": "allow"}"#,
+            VALID_MODERATION,
+        ]);
+
+        let structured = service
+            .chat_with_schema(user("Moderate this"), "moderation_response")
+            .await
+            .expect("repaired output");
+
+        assert_eq!(structured.value["recommendation"], "allow");
+        assert_eq!(structured.attempts.len(), 3);
+        assert!(
+            structured.attempts[0]
+                .error
+                .as_deref()
+                .is_some_and(|error| error.starts_with("Response is not valid JSON"))
+        );
+        assert!(structured.attempts[2].error.is_none());
+
+        // Each repair shows the model its previous output and what was wrong with it
+        let requests = provider.requests();
+        let repair = &requests[2].messages;
+        assert_eq!(repair.len(), 5);
+        assert_eq!(repair[3].role, ChatRole::Assistant);
+        assert!(repair[3].content.contains("extreme"));
+        assert!(repair[4].content.contains("/severity"));
+        assert!(
+            requests
+                .iter()
+                .all(|request| request.response_format.is_some())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_with_schema_gives_up_after_repair_attempts() {
+        let (service, provider) = service_with(&["not json", r#"{"safe": "maybe"}"#, "unused"]);
+        let service = service.with_schema_repair_attempts(1);
+
+        let error = service
+            .chat_with_schema(user("Moderate this"), "moderation_response")
+            .await
+            .expect_err("output never becomes valid");
+
+        let AiError::SchemaRepairFailed { message, attempts } = error else {
+            panic!("Expected SchemaRepairFailed, got {error:?}");
+        };
+        assert_eq!(attempts.len(), 2);
+        assert!(message.contains("/safe"));
+        assert_eq!(provider.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_chat_with_schema_accepts_fenced_json() {
+        let fenced = format!("```json\n{VALID_MODERATION}\n```");
+        let (service, _) = service_with(&[&fenced]);
+
+        let structured = service
+            .chat_with_schema(user("Moderate this"), "moderation_response")
+            .await
+            .expect("fenced output");
+        assert_eq!(structured.attempts.len(), 1);
+
+        let missing = service.chat_with_schema(user("x"), "missing").await;
+        assert!(matches!(missing, Err(AiError::InvalidRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_embed_uses_the_configured_model_or_embeds_locally() {
+        let (local, _) = service_with(&[]);
+        assert_eq!(local.embedding_model(), LOCAL_EMBEDDING_MODEL);
+        let response = local
+            .embed(vec!["Dark mode".to_string(), "Light mode".to_string()])
+            .await
+            .expect("local embeddings");
+        assert_eq!(response.model, LOCAL_EMBEDDING_MODEL);
+        assert_eq!(response.embeddings.len(), 2);
+        assert!(response.usage.is_none());
+
+        let (remote, _) = service_with(&[]);
+        let remote = remote.with_embedding_model(Some("text-embedding-test".to_string()));
+        let response = remote
+            .embed(vec!["Dark mode".to_string()])
+            .await
+            .expect("provider embeddings");
+        assert_eq!(response.model, "text-embedding-test");
+        assert!(response.usage.is_some());
+    }
+}
@@ -0,0 +1,625 @@
+//! Conversational issue-creation sessions
+//!
+//! Manages the `ai_sessions` tables. A session holds a conversation with an AI
+//! persona, the context notes the persona saved and the issue draft it is
+//! building. Each message runs the persona's functions against that state, and
+//! the session ends when it is finalized, cancelled or left idle until it
+//! expires.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::SqlitePool;
+
+use crate::{
+    ai::{ChatResponse, FunctionDispatcher, models::chat::FunctionCall as RawFunctionCall},
+    errors::{AppError, AppResult},
+    models::{
+        AiPersona, AiSession, AiSessionMessage, CreateIssueRequest, CreateSessionRequest,
+        CreateSessionResponse, DuplicateCheckRequest, FinalizeAction, FinalizeSessionRequest,
+        FinalizeSessionResponse, IssueDraft, IssuePreview, MessageRole, SendMessageResponse,
+        SessionContext, SessionStatus,
+        ai_session::{FunctionCall, SESSION_TTL_MINUTES, SessionDetailsResponse, SessionType},
+    },
+    services::{AiService, EmbeddingService, IssueService, board_service::require_member},
+};
+
+/// Maximum length of a single user message
+const MAX_MESSAGE_LEN: usize = 10_000;
+
+/// The outcome of one exchange with the persona, not yet saved
+struct Turn {
+    messages: Vec<AiSessionMessage>,
+    context: SessionContext,
+    draft: IssueDraft,
+    draft_updated: bool,
+    function_calls: Vec<FunctionCall>,
+    response: ChatResponse,
+}
+
+pub struct AiSessionService {
+    db: SqlitePool,
+}
+
+fn session_not_found() -> AppError {
+    AppError::NotFound("AI session not found".to_string())
+}
+
+fn corrupt_state(e: &serde_json::Error) -> AppError {
+    AppError::InternalServerError(format!("Invalid AI session state: {e}"))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> AppResult<String> {
+    serde_json::to_string(value).map_err(|e| corrupt_state(&e))
+}
+
+fn validate_message(message: &str) -> AppResult<&str> {
+    let message = message.trim();
+    if message.is_empty() || message.len() > MAX_MESSAGE_LEN {
+        return Err(AppError::ValidationError(
+            "Message is required and must be at most 10000 characters.".to_string(),
+        ));
+    }
+    Ok(message)
+}
+
+/// The status a session effectively has, treating idle active sessions as expired
+fn effective_status(session: &AiSession, now: DateTime<Utc>) -> SessionStatus {
+    if session.status == SessionStatus::Active && session.expires_at <= now {
+        SessionStatus::Expired
+    } else {
+        session.status
+    }
+}
+
+/// Notes shown to the persona alongside the conversation
+fn context_notes(context: &SessionContext, draft: &IssueDraft) -> Vec<String> {
+    let labelled = [
+        ("Requirement", &context.requirements),
+        ("Technical note", &context.technical_notes),
+        ("Design note", &context.design_notes),
+        ("Note", &context.other_notes),
+    ];
+
+    let mut notes: Vec<String> = labelled
+        .into_iter()
+        .flat_map(|(label, entries)| entries.iter().map(move |entry| format!("{label}: {entry}")))
+        .collect();
+    if *draft != IssueDraft::default()
+        && let Ok(draft_json) = serde_json::to_string(draft)
+    {
+        notes.push(format!("Current issue draft: {draft_json}"));
+    }
+    notes
+}
+
+fn function_call(call: &RawFunctionCall) -> FunctionCall {
+    FunctionCall {
+        name: call.name.clone(),
+        parameters: serde_json::from_str(&call.arguments)
+            .unwrap_or_else(|_| serde_json::Value::String(call.arguments.clone())),
+    }
+}
+
+fn greeting(persona: AiPersona) -> String {
+    format!(
+        "Hi, I'm your {}. Tell me what you'd like to work on and I'll help you shape it into an issue.",
+        persona.display_name()
+    )
+}
+
+impl AiSessionService {
+    /// Create a new `AiSessionService`
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Start a session with a persona
+    ///
+    /// If the request has an initial input it is sent as the first message and
+    /// the persona's reply is returned together with the provider response, so
+    /// its usage can be recorded. Otherwise the persona greets the user.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user is not a member of the board
+    /// the session is for, `AppError::ValidationError` if the initial input is
+    /// too long, `AppError::BadRequest` if the AI request fails, or `AppError`
+    /// if the database operation fails
+    pub async fn create_session(
+        &self,
+        ai: &AiService,
+        user_id: &str,
+        request: &CreateSessionRequest,
+    ) -> AppResult<(CreateSessionResponse, Option<ChatResponse>)> {
+        if let Some(board_id) = &request.board_id {
+            require_member(&self.db, board_id, user_id).await?;
+        }
+
+        let mut session = AiSession::new(user_id.to_string());
+        let context = SessionContext {
+            board_id: request.board_id.clone(),
+            persona: request.persona,
+            ..Default::default()
+        };
+        session.context = to_json(&context)?;
+
+        let initial_input = request
+            .initial_input
+            .as_deref()
+            .filter(|input| !input.trim().is_empty());
+        let (messages, response) = match initial_input {
+            Some(input) => {
+                let turn =
+                    Self::converse(ai, &session, &[], Vec::new(), validate_message(input)?).await?;
+                session.context = to_json(&turn.context)?;
+                if turn.draft_updated {
+                    session.draft = Some(to_json(&turn.draft)?);
+                }
+                (turn.messages, Some(turn.response))
+            }
+            None => (
+                vec![AiSessionMessage::new(
+                    session.id.clone(),
+                    MessageRole::Assistant,
+                    greeting(request.persona),
+                )],
+                None,
+            ),
+        };
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO ai_sessions
+                (id, user_id, type, status, context, draft, created_at, updated_at, expires_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "#,
+            session.id,
+            session.user_id,
+            session.session_type,
+            session.status,
+            session.context,
+            session.draft,
+            session.created_at,
+            session.updated_at,
+            session.expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+        for message in &messages {
+            Self::insert_message(&mut tx, message).await?;
+        }
+        tx.commit().await?;
+
+        tracing::info!(
+            "Started {} session {} for user {}",
+            request.persona.display_name(),
+            session.id,
+            user_id
+        );
+
+        let assistant_response = messages
+            .last()
+            .map(|message| message.content.clone())
+            .unwrap_or_default();
+        Ok((
+            CreateSessionResponse {
+                session_id: session.id,
+                status: session.status,
+                assistant_response,
+                expires_at: session.expires_at,
+            },
+            response,
+        ))
+    }
+
+    /// Send a user message to an active session and run the persona's reply
+    ///
+    /// Function calls the persona makes are applied to the session's context
+    /// and draft, and every message of the exchange is saved. Activity pushes
+    /// the session's expiry back.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user has no such session,
+    /// `AppError::BadRequest` if it is no longer active or the AI request
+    /// fails, `AppError::ValidationError` if the message is empty or too long,
+    /// or `AppError` if the database operation fails
+    pub async fn send_message(
+        &self,
+        ai: &AiService,
+        user_id: &str,
+        session_id: &str,
+        message: &str,
+    ) -> AppResult<(SendMessageResponse, ChatResponse)> {
+        let message = validate_message(message)?;
+        let session = self.active_session(user_id, session_id).await?;
+        let history = self.messages(session_id).await?;
+        let assets = self.asset_ids(session_id).await?;
+
+        let turn = Self::converse(ai, &session, &history, assets, message).await?;
+
+        let context_json = to_json(&turn.context)?;
+        let draft_json = if turn.draft_updated {
+            Some(to_json(&turn.draft)?)
+        } else {
+            session.draft.clone()
+        };
+        let now = Utc::now();
+        let expires_at = now + Duration::minutes(SESSION_TTL_MINUTES);
+
+        let mut tx = self.db.begin().await?;
+        let updated = sqlx::query!(
+            r#"
+            UPDATE ai_sessions SET context = ?1, draft = ?2, expires_at = ?3, updated_at = ?4
+            WHERE id = ?5 AND status = 'active'
+            "#,
+            context_json,
+            draft_json,
+            expires_at,
+            now,
+            session_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Err(AppError::BadRequest(
+                "AI session is no longer active".to_string(),
+            ));
+        }
+        for message in &turn.messages {
+            Self::insert_message(&mut tx, message).await?;
+        }
+        tx.commit().await?;
+
+        let assistant = turn.messages.last().ok_or_else(|| {
+            AppError::InternalServerError("AI session turn produced no reply".to_string())
+        })?;
+        Ok((
+            SendMessageResponse {
+                message_id: assistant.id.clone(),
+                assistant_response: assistant.content.clone(),
+                function_calls: turn.function_calls,
+                draft_updated: turn.draft_updated,
+            },
+            turn.response,
+        ))
+    }
+
+    /// Get a session with its messages
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user has no such session, or
+    /// `AppError` if the database query fails
+    pub async fn get_session(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> AppResult<SessionDetailsResponse> {
+        let session = self.owned_session(user_id, session_id).await?;
+        let context = session.get_context().map_err(|e| corrupt_state(&e))?;
+        let draft = session.get_draft().map_err(|e| corrupt_state(&e))?;
+        let messages = self.messages(session_id).await?;
+
+        Ok(SessionDetailsResponse {
+            session_id: session.id.clone(),
+            status: effective_status(&session, Utc::now()),
+            persona: context.persona,
+            context,
+            draft,
+            messages,
+            expires_at: session.expires_at,
+        })
+    }
+
+    /// Preview the issue the session's draft would create
+    ///
+    /// When the session was started for a board and the draft has a title, the
+    /// preview lists issues on that board the draft may duplicate, unless
+    /// `embeddings` is `None`. The check is best effort: if it fails, the
+    /// preview is returned without it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user has no such session, or
+    /// `AppError` if the database query fails
+    pub async fn preview(
+        &self,
+        ai: &AiService,
+        embeddings: Option<&EmbeddingService>,
+        user_id: &str,
+        session_id: &str,
+    ) -> AppResult<IssuePreview> {
+        let session = self.owned_session(user_id, session_id).await?;
+        let draft = session
+            .get_draft()
+            .map_err(|e| corrupt_state(&e))?
+            .unwrap_or_default();
+        let context = session.get_context().map_err(|e| corrupt_state(&e))?;
+
+        let mut preview = IssuePreview::from(&draft);
+        if let (Some(embeddings), Some(board_id), Some(title)) =
+            (embeddings, &context.board_id, &draft.title)
+        {
+            let request = DuplicateCheckRequest {
+                title: title.clone(),
+                description: draft.description.clone(),
+                exclude_issue_id: None,
+                limit: None,
+            };
+            match embeddings
+                .check_duplicates(ai, user_id, board_id, &request)
+                .await
+            {
+                Ok(result) => preview.duplicates = Some(result),
+                Err(e) => tracing::warn!(session_id, "Duplicate check failed: {e}"),
+            }
+        }
+        Ok(preview)
+    }
+
+    /// Finalize an active session, either completing it or cancelling it
+    ///
+    /// Completing requires a draft with a title and description, and creates an
+    /// issue from the draft in the chosen board's backlog.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user has no such session or is not
+    /// a member of the board, `AppError::BadRequest` if it is no longer active,
+    /// `AppError::ValidationError` if the draft or board is incomplete, or
+    /// `AppError` if the database operation fails
+    pub async fn finalize(
+        &self,
+        issues: &IssueService,
+        user_id: &str,
+        session_id: &str,
+        request: &FinalizeSessionRequest,
+    ) -> AppResult<FinalizeSessionResponse> {
+        let session = self.active_session(user_id, session_id).await?;
+        let mut context = session.get_context().map_err(|e| corrupt_state(&e))?;
+
+        let (status, issue) = match request.action {
+            FinalizeAction::Cancel => (SessionStatus::Cancelled, None),
+            FinalizeAction::CreateIssue => {
+                let draft = session
+                    .get_draft()
+                    .map_err(|e| corrupt_state(&e))?
+                    .unwrap_or_default();
+                let missing = draft.missing_fields();
+                if !missing.is_empty() {
+                    return Err(AppError::ValidationError(format!(
+                        "Issue draft is missing: {}",
+                        missing.join(", ")
+                    )));
+                }
+                let board_id = request.board_id.trim();
+                if board_id.is_empty() {
+                    return Err(AppError::ValidationError(
+                        "A board is required to create an issue.".to_string(),
+                    ));
+                }
+                let issue = issues
+                    .create_issue(user_id, board_id, &CreateIssueRequest::from(&draft))
+                    .await?;
+                context.board_id = Some(issue.board_id.clone());
+                (SessionStatus::Completed, Some(issue))
+            }
+        };
+
+        let context_json = to_json(&context)?;
+        let now = Utc::now();
+        let finalized = sqlx::query!(
+            r#"
+            UPDATE ai_sessions SET status = ?1, context = ?2, completed_at = ?3, updated_at = ?3
+            WHERE id = ?4 AND status = 'active'
+            "#,
+            status,
+            context_json,
+            now,
+            session_id
+        )
+        .execute(&self.db)
+        .await?;
+        if finalized.rows_affected() == 0 {
+            // Another request finalized the session first; keep only its issue
+            if let Some(issue) = &issue {
+                issues.delete_issue(user_id, &issue.id).await?;
+            }
+            return Err(AppError::BadRequest(
+                "AI session is no longer active".to_string(),
+            ));
+        }
+
+        tracing::info!("AI session {} finalized as {:?}", session_id, status);
+
+        Ok(FinalizeSessionResponse {
+            url: issue
+                .as_ref()
+                .map(|issue| format!("/boards/{}/issues/{}", issue.board_id, issue.id)),
+            board_id: issue.as_ref().map(|issue| issue.board_id.clone()),
+            issue_id: issue.map(|issue| issue.id),
+            status,
+            assets_moved: 0,
+        })
+    }
+
+    /// Mark active sessions whose expiry time has passed as expired
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn expire_sessions(&self) -> AppResult<u64> {
+        let now = Utc::now();
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE ai_sessions SET status = 'expired', updated_at = ?1
+            WHERE status = 'active' AND expires_at <= ?1
+            "#,
+            now
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Run one exchange with the session's persona without saving it
+    async fn converse(
+        ai: &AiService,
+        session: &AiSession,
+        history: &[AiSessionMessage],
+        assets: Vec<String>,
+        message: &str,
+    ) -> AppResult<Turn> {
+        let context = session.get_context().map_err(|e| corrupt_state(&e))?;
+        let draft = session
+            .get_draft()
+            .map_err(|e| corrupt_state(&e))?
+            .unwrap_or_default();
+        let user_message =
+            AiSessionMessage::new(session.id.clone(), MessageRole::User, message.to_string());
+
+        let conversation: Vec<serde_json::Value> = history
+            .iter()
+            .chain(std::iter::once(&user_message))
+            .filter(|message| message.role != MessageRole::Function)
+            .map(|message| serde_json::json!({"role": message.role, "content": message.content}))
+            .collect();
+        let prompt = serde_json::json!({
+            "messages": conversation,
+            "context": context_notes(&context, &draft),
+        });
+
+        let persona = context.persona;
+        let mut dispatcher = FunctionDispatcher::new(context, draft.clone()).with_assets(assets);
+        let response = ai
+            .chat_as_persona(persona, &prompt, &mut dispatcher)
+            .await
+            .map_err(|e| AppError::BadRequest(format!("AI session request failed: {e}")))?;
+
+        let mut messages = vec![user_message];
+        let mut function_calls = Vec::new();
+        for (call, result) in dispatcher.handled() {
+            let mut function_message =
+                AiSessionMessage::new(session.id.clone(), MessageRole::Function, to_json(result)?);
+            function_message.function_call = Some(to_json(call)?);
+            messages.push(function_message);
+            function_calls.push(function_call(call));
+        }
+        messages.push(AiSessionMessage::new(
+            session.id.clone(),
+            MessageRole::Assistant,
+            response.content(),
+        ));
+
+        let (context, updated_draft) = dispatcher.into_parts();
+        Ok(Turn {
+            messages,
+            context,
+            draft_updated: updated_draft != draft,
+            draft: updated_draft,
+            function_calls,
+            response,
+        })
+    }
+
+    async fn owned_session(&self, user_id: &str, session_id: &str) -> AppResult<AiSession> {
+        sqlx::query_as!(
+            AiSession,
+            r#"
+            SELECT id, user_id,
+                   type as "session_type: SessionType",
+                   status as "status: SessionStatus",
+                   context, draft,
+                   created_at as "created_at: DateTime<Utc>",
+                   updated_at as "updated_at: DateTime<Utc>",
+                   completed_at as "completed_at: DateTime<Utc>",
+                   expires_at as "expires_at: DateTime<Utc>"
+            FROM ai_sessions
+            WHERE id = ?1 AND user_id = ?2
+            "#,
+            session_id,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(session_not_found)
+    }
+
+    /// Load a session that can still take messages, expiring it if it sat idle too long
+    async fn active_session(&self, user_id: &str, session_id: &str) -> AppResult<AiSession> {
+        let session = self.owned_session(user_id, session_id).await?;
+        match effective_status(&session, Utc::now()) {
+            SessionStatus::Active => Ok(session),
+            SessionStatus::Expired => {
+                if session.status == SessionStatus::Active {
+                    self.expire_sessions().await?;
+                }
+                Err(AppError::BadRequest("AI session has expired".to_string()))
+            }
+            SessionStatus::Completed | SessionStatus::Cancelled => Err(AppError::BadRequest(
+                "AI session is no longer active".to_string(),
+            )),
+        }
+    }
+
+    async fn messages(&self, session_id: &str) -> AppResult<Vec<AiSessionMessage>> {
+        let messages = sqlx::query_as!(
+            AiSessionMessage,
+            r#"
+            SELECT id, session_id,
+                   role as "role: MessageRole",
+                   content, function_call,
+                   created_at as "created_at: DateTime<Utc>"
+            FROM ai_session_messages
+            WHERE session_id = ?1
+            ORDER BY created_at, rowid
+            "#,
+            session_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(messages)
+    }
+
+    async fn asset_ids(&self, session_id: &str) -> AppResult<Vec<String>> {
+        let asset_ids = sqlx::query_scalar!(
+            "SELECT id FROM ai_session_assets WHERE session_id = ?1 ORDER BY created_at",
+            session_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(asset_ids)
+    }
+
+    async fn insert_message(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        message: &AiSessionMessage,
+    ) -> AppResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO ai_session_messages (id, session_id, role, content, function_call, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            message.id,
+            message.session_id,
+            message.role,
+            message.content,
+            message.function_call,
+            message.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "ai_session_service_tests.rs"]
+mod ai_session_service_tests;
@@ -0,0 +1,382 @@
+//! Tests for AI session service
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::providers::{MockProvider, MockReply};
+    use crate::errors::AppError;
+    use crate::models::{
+        AiPersona, CreateBoardRequest, CreateIssueRequest, CreateSessionRequest, FinalizeAction,
+        FinalizeSessionRequest, IssuePriority, IssueStatus, IssueType, MessageRole, SessionStatus,
+    };
+    use crate::services::{
+        AiService, AiSessionService, BoardService, EmbeddingService, IssueService,
+    };
+    use serde_json::json;
+    use sqlx::SqlitePool;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    async fn create_test_user(pool: &SqlitePool) -> String {
+        let user_id = Uuid::new_v4().to_string();
+        let email = format!("test+{user_id}@example.com");
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, email, hashed_password, provider, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            user_id,
+            email,
+            "hashed_password",
+            "local",
+            now,
+            now
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to create test user");
+
+        user_id
+    }
+
+    async fn create_test_board(pool: &SqlitePool, owner_id: &str) -> String {
+        BoardService::new(pool.clone())
+            .create_board(
+                owner_id,
+                &CreateBoardRequest {
+                    name: "Roadmap".to_string(),
+                    description: None,
+                },
+            )
+            .await
+            .expect("board created")
+            .board
+            .id
+    }
+
+    fn ai_with(replies: Vec<MockReply>) -> (AiService, Arc<MockProvider>) {
+        let provider = Arc::new(MockProvider::new());
+        for reply in replies {
+            provider.push_reply(reply);
+        }
+        let service = AiService::with_provider(provider.clone()).expect("AI service");
+        (service, provider)
+    }
+
+    fn create_request(initial_input: Option<&str>) -> CreateSessionRequest {
+        CreateSessionRequest {
+            initial_input: initial_input.map(str::to_string),
+            board_id: None,
+            persona: AiPersona::BusinessAnalyst,
+        }
+    }
+
+    fn finalize_request(action: FinalizeAction, board_id: &str) -> FinalizeSessionRequest {
+        FinalizeSessionRequest {
+            action,
+            board_id: board_id.to_string(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_create_session_greets_without_calling_the_model(pool: SqlitePool) {
+        let service = AiSessionService::new(pool.clone());
+        let user_id = create_test_user(&pool).await;
+        let (ai, provider) = ai_with(vec![]);
+
+        let (created, response) = service
+            .create_session(&ai, &user_id, &create_request(None))
+            .await
+            .expect("session created");
+
+        assert!(response.is_none());
+        assert!(provider.requests().is_empty());
+        assert_eq!(created.status, SessionStatus::Active);
+        assert!(created.assistant_response.contains("Business Analyst"));
+
+        let details = service
+            .get_session(&user_id, &created.session_id)
+            .await
+            .expect("session found");
+        assert_eq!(details.persona, AiPersona::BusinessAnalyst);
+        assert_eq!(details.messages.len(), 1);
+        assert_eq!(details.messages[0].role, MessageRole::Assistant);
+    }
+
+    #[sqlx::test]
+    async fn test_send_message_runs_persona_functions(pool: SqlitePool) {
+        let service = AiSessionService::new(pool.clone());
+        let user_id = create_test_user(&pool).await;
+        let (ai, provider) = ai_with(vec![
+            MockReply::text("What should happen after signing in?"),
+            MockReply::function_call(
+                "save_context",
+                json!({"context_type": "requirements", "content": "Users land on the dashboard"}),
+            ),
+            MockReply::function_call(
+                "update_issue_draft",
+                json!({"updates": {
+                    "title": "Single sign-on",
+                    "description": "Let users sign in with their company account",
+                    "priority": "high"
+                }}),
+            ),
+            MockReply::text("I've drafted the issue."),
+        ]);
+
+        let (created, _) = service
+            .create_session(&ai, &user_id, &create_request(Some("We need SSO")))
+            .await
+            .expect("session created");
+        assert_eq!(
+            created.assistant_response,
+            "What should happen after signing in?"
+        );
+
+        let (reply, response) = service
+            .send_message(
+                &ai,
+                &user_id,
+                &created.session_id,
+                "Send them to the dashboard",
+            )
+            .await
+            .expect("message sent");
+
+        assert_eq!(reply.assistant_response, "I've drafted the issue.");
+        assert!(reply.draft_updated);
+        let called: Vec<&str> = reply
+            .function_calls
+            .iter()
+            .map(|call| call.name.as_str())
+            .collect();
+        assert_eq!(called, ["save_context", "update_issue_draft"]);
+        assert!(response.usage.is_some());
+
+        // The persona sees the whole conversation and is offered its functions
+        let requests = provider.requests();
+        let prompt = &requests[1].messages;
+        assert!(
+            prompt
+                .iter()
+                .any(|m| m.content.contains("user: We need SSO"))
+        );
+        assert!(requests[1].functions.is_some());
+
+        let details = service
+            .get_session(&user_id, &created.session_id)
+            .await
+            .expect("session found");
+        assert_eq!(
+            details.context.requirements,
+            ["Users land on the dashboard"]
+        );
+        let roles: Vec<MessageRole> = details.messages.iter().map(|m| m.role).collect();
+        assert_eq!(
+            roles,
+            [
+                MessageRole::User,
+                MessageRole::Assistant,
+                MessageRole::User,
+                MessageRole::Function,
+                MessageRole::Function,
+                MessageRole::Assistant,
+            ]
+        );
+
+        let preview = service
+            .preview(
+                &ai,
+                Some(&EmbeddingService::new(pool.clone())),
+                &user_id,
+                &created.session_id,
+            )
+            .await
+            .expect("preview");
+        assert_eq!(preview.title, "Single sign-on");
+        assert_eq!(preview.priority, "high");
+        assert!(preview.duplicates.is_none());
+    }
+
+    #[sqlx::test]
+    async fn test_preview_lists_likely_duplicates_on_the_board(pool: SqlitePool) {
+        let service = AiSessionService::new(pool.clone());
+        let issues = IssueService::new(pool.clone());
+        let user_id = create_test_user(&pool).await;
+        let board_id = create_test_board(&pool, &user_id).await;
+        let existing = issues
+            .create_issue(
+                &user_id,
+                &board_id,
+                &CreateIssueRequest {
+                    title: "Export boards to CSV".to_string(),
+                    description: Some("Download a board's issues as a CSV file".to_string()),
+                    issue_type: IssueType::Feature,
+                    priority: IssuePriority::Medium,
+                    size_estimate: None,
+                },
+            )
+            .await
+            .expect("issue created");
+        let (ai, _) = ai_with(vec![
+            MockReply::function_call(
+                "update_issue_draft",
+                json!({"updates": {
+                    "title": "CSV export for boards",
+                    "description": "Users want to download board issues as a CSV file"
+                }}),
+            ),
+            MockReply::text("Drafted."),
+        ]);
+        let mut request = create_request(Some("Let me export boards"));
+        request.board_id = Some(board_id);
+        let (created, _) = service
+            .create_session(&ai, &user_id, &request)
+            .await
+            .expect("session created");
+
+        let preview = service
+            .preview(
+                &ai,
+                Some(&EmbeddingService::new(pool.clone())),
+                &user_id,
+                &created.session_id,
+            )
+            .await
+            .expect("preview");
+        let duplicates = preview.duplicates.expect("duplicate check ran");
+        assert_eq!(duplicates.duplicates.len(), 1);
+        assert_eq!(duplicates.duplicates[0].issue_id, existing.id);
+        assert!(duplicates.uniqueness_score < 0.6);
+    }
+
+    #[sqlx::test]
+    async fn test_finalize_creates_an_issue_from_a_complete_draft(pool: SqlitePool) {
+        let service = AiSessionService::new(pool.clone());
+        let issues = IssueService::new(pool.clone());
+        let user_id = create_test_user(&pool).await;
+        let outsider_id = create_test_user(&pool).await;
+        let board_id = create_test_board(&pool, &user_id).await;
+        let (ai, _) = ai_with(vec![
+            MockReply::function_call(
+                "update_issue_draft",
+                json!({"updates": {
+                    "title": "Export",
+                    "description": "Export boards to CSV",
+                    "priority": "low",
+                    "estimated_hours": 3.5
+                }}),
+            ),
+            MockReply::text("Drafted."),
+        ]);
+        let (created, _) = service
+            .create_session(&ai, &user_id, &create_request(None))
+            .await
+            .expect("session created");
+
+        let incomplete = service
+            .finalize(
+                &issues,
+                &user_id,
+                &created.session_id,
+                &finalize_request(FinalizeAction::CreateIssue, &board_id),
+            )
+            .await;
+        assert!(
+            matches!(incomplete, Err(AppError::ValidationError(message)) if message.contains("title, description"))
+        );
+
+        service
+            .send_message(&ai, &user_id, &created.session_id, "CSV export please")
+            .await
+            .expect("message sent");
+        let foreign_board = create_test_board(&pool, &outsider_id).await;
+        let not_member = service
+            .finalize(
+                &issues,
+                &user_id,
+                &created.session_id,
+                &finalize_request(FinalizeAction::CreateIssue, &foreign_board),
+            )
+            .await;
+        assert!(matches!(not_member, Err(AppError::NotFound(_))));
+
+        let finalized = service
+            .finalize(
+                &issues,
+                &user_id,
+                &created.session_id,
+                &finalize_request(FinalizeAction::CreateIssue, &board_id),
+            )
+            .await
+            .expect("finalized");
+        assert_eq!(finalized.status, SessionStatus::Completed);
+        assert_eq!(finalized.board_id.as_deref(), Some(board_id.as_str()));
+
+        let issue_id = finalized.issue_id.expect("issue created");
+        assert_eq!(
+            finalized.url,
+            Some(format!("/boards/{board_id}/issues/{issue_id}"))
+        );
+        let issue = issues
+            .get_issue(&user_id, &issue_id)
+            .await
+            .expect("issue found");
+        assert_eq!(issue.title, "Export");
+        assert_eq!(issue.status, IssueStatus::Backlog);
+        assert_eq!(issue.priority, IssuePriority::Low);
+        assert_eq!(issue.size_estimate, Some(4));
+
+        let after = service
+            .send_message(&ai, &user_id, &created.session_id, "One more thing")
+            .await;
+        assert!(matches!(after, Err(AppError::BadRequest(_))));
+    }
+
+    #[sqlx::test]
+    async fn test_sessions_are_private_and_expire(pool: SqlitePool) {
+        let service = AiSessionService::new(pool.clone());
+        let user_id = create_test_user(&pool).await;
+        let other_user_id = create_test_user(&pool).await;
+        let (ai, _) = ai_with(vec![]);
+        let (created, _) = service
+            .create_session(&ai, &user_id, &create_request(None))
+            .await
+            .expect("session created");
+
+        let foreign = service
+            .get_session(&other_user_id, &created.session_id)
+            .await;
+        assert!(matches!(foreign, Err(AppError::NotFound(_))));
+        let foreign_cancel = service
+            .finalize(
+                &IssueService::new(pool.clone()),
+                &other_user_id,
+                &created.session_id,
+                &finalize_request(FinalizeAction::Cancel, ""),
+            )
+            .await;
+        assert!(matches!(foreign_cancel, Err(AppError::NotFound(_))));
+
+        let past = chrono::Utc::now() - chrono::Duration::minutes(1);
+        sqlx::query("UPDATE ai_sessions SET expires_at = ?1 WHERE id = ?2")
+            .bind(past)
+            .bind(&created.session_id)
+            .execute(&pool)
+            .await
+            .expect("session backdated");
+
+        assert_eq!(service.expire_sessions().await.expect("expired"), 1);
+        let details = service
+            .get_session(&user_id, &created.session_id)
+            .await
+            .expect("session found");
+        assert_eq!(details.status, SessionStatus::Expired);
+
+        let late = service
+            .send_message(&ai, &user_id, &created.session_id, "Hello?")
+            .await;
+        assert!(matches!(late, Err(AppError::BadRequest(_))));
+    }
+}
@@ -3,6 +3,8 @@
 //! Authentication service for JWT token management
 //!
 //! This service handles JWT generation, validation, and token claims management.
+//! Access tokens are short-lived; long-lived sessions are maintained with refresh
+//! tokens by the `SessionService`.
 
 use chrono::{Duration, Utc};
 use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
@@ -11,6 +13,7 @@ use std::env;
 use uuid::Uuid;
 
 use crate::errors::{AppError, AppResult};
+use crate::models::role::Role;
 
 /// JWT Claims structure
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,19 +22,32 @@ pub struct Claims {
     pub email: String,
     pub exp: i64, // Expiration time (as UTC timestamp)
     pub iat: i64, // Issued at (as UTC timestamp)
+    #[serde(default)]
+    pub roles: Vec<Role>, // Roles held when the token was issued
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>, // Session the token was issued for, if any
 }
 
+/// Default lifetime of access tokens in minutes
+const DEFAULT_ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+
 /// Authentication service for JWT operations
 pub struct AuthService {
     encoding_key: EncodingKey,
     decoding_key: DecodingKey,
+    access_token_ttl: Duration,
 }
 
 impl AuthService {
     /// Creates a new `AuthService` instance
     ///
+    /// # Environment Variables
+    /// - `JWT_SECRET`: Secret used to sign tokens (at least 32 characters)
+    /// - `ACCESS_TOKEN_TTL_MINUTES`: Access token lifetime (default: 15)
+    ///
     /// # Errors
-    /// Returns `AppError::ConfigurationError` if `JWT_SECRET` environment variable is missing or invalid
+    /// Returns `AppError::ConfigurationError` if `JWT_SECRET` environment variable is missing or invalid,
+    /// or if `ACCESS_TOKEN_TTL_MINUTES` is not a positive number
     pub fn new() -> AppResult<Self> {
         let jwt_secret = env::var("JWT_SECRET").map_err(|_| {
             AppError::ConfigurationError("JWT_SECRET environment variable is required".to_string())
@@ -46,32 +62,81 @@ impl AuthService {
         let encoding_key = EncodingKey::from_secret(jwt_secret.as_bytes());
         let decoding_key = DecodingKey::from_secret(jwt_secret.as_bytes());
 
+        let ttl_minutes = match env::var("ACCESS_TOKEN_TTL_MINUTES") {
+            Ok(value) => value
+                .parse::<i64>()
+                .ok()
+                .filter(|minutes| *minutes > 0)
+                .ok_or_else(|| {
+                    AppError::ConfigurationError(
+                        "ACCESS_TOKEN_TTL_MINUTES must be a positive number".to_string(),
+                    )
+                })?,
+            Err(_) => DEFAULT_ACCESS_TOKEN_TTL_MINUTES,
+        };
+
         Ok(Self {
             encoding_key,
             decoding_key,
+            access_token_ttl: Duration::minutes(ttl_minutes),
         })
     }
 
+    /// Lifetime of issued access tokens in seconds
+    #[must_use]
+    pub fn access_token_ttl_seconds(&self) -> i64 {
+        self.access_token_ttl.num_seconds()
+    }
+
     /// Generates a JWT token for the given user
     ///
     /// # Arguments
     /// * `user_id` - The UUID of the user
     /// * `email` - The email address of the user
+    /// * `roles` - The roles currently held by the user
     ///
     /// # Returns
     /// A JWT token string
     ///
     /// # Errors
     /// Returns `AppError::InternalServerError` if token generation fails
-    pub fn generate_token(&self, user_id: Uuid, email: &str) -> AppResult<String> {
+    pub fn generate_token(&self, user_id: Uuid, email: &str, roles: &[Role]) -> AppResult<String> {
+        self.encode_token(user_id, email, roles, None)
+    }
+
+    /// Generates a JWT token bound to a session
+    ///
+    /// Tokens bound to a session are rejected as soon as the session is revoked.
+    ///
+    /// # Errors
+    /// Returns `AppError::InternalServerError` if token generation fails
+    pub fn generate_session_token(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        roles: &[Role],
+        session_id: Uuid,
+    ) -> AppResult<String> {
+        self.encode_token(user_id, email, roles, Some(session_id))
+    }
+
+    fn encode_token(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        roles: &[Role],
+        session_id: Option<Uuid>,
+    ) -> AppResult<String> {
         let now = Utc::now();
-        let expiration = now + Duration::hours(24); // Token expires in 24 hours
+        let expiration = now + self.access_token_ttl;
 
         let claims = Claims {
             sub: user_id.to_string(),
             email: email.to_string(),
             exp: expiration.timestamp(),
             iat: now.timestamp(),
+            roles: roles.to_vec(),
+            sid: session_id.map(|id| id.to_string()),
         };
 
         encode(&Header::default(), &claims, &self.encoding_key).map_err(|e| {
@@ -238,7 +303,7 @@ mod tests {
         let email = "test@example.com";
 
         let token = auth_service
-            .generate_token(user_id, email)
+            .generate_token(user_id, email, &[])
             .expect("Failed to generate token");
 
         let claims = auth_service
@@ -252,6 +317,47 @@ mod tests {
         cleanup_test_env();
     }
 
+    #[test]
+    fn test_token_carries_roles() {
+        setup_test_env();
+        let auth_service = AuthService::new().expect("Failed to create auth service");
+
+        let token = auth_service
+            .generate_token(Uuid::new_v4(), "admin@example.com", &[Role::Admin])
+            .expect("Failed to generate token");
+
+        let claims = auth_service
+            .validate_token(&token)
+            .expect("Failed to validate token");
+
+        assert_eq!(claims.roles, vec![Role::Admin]);
+        cleanup_test_env();
+    }
+
+    #[test]
+    fn test_session_token_carries_session_id() {
+        setup_test_env();
+        let auth_service = AuthService::new().expect("Failed to create auth service");
+        let session_id = Uuid::new_v4();
+
+        let token = auth_service
+            .generate_session_token(Uuid::new_v4(), "test@example.com", &[], session_id)
+            .expect("Failed to generate token");
+        let claims = auth_service
+            .validate_token(&token)
+            .expect("Failed to validate token");
+        assert_eq!(claims.sid, Some(session_id.to_string()));
+
+        let token = auth_service
+            .generate_token(Uuid::new_v4(), "test@example.com", &[])
+            .expect("Failed to generate token");
+        let claims = auth_service
+            .validate_token(&token)
+            .expect("Failed to validate token");
+        assert!(claims.sid.is_none());
+        cleanup_test_env();
+    }
+
     #[test]
     fn test_token_expiration_time() {
         setup_test_env();
@@ -261,16 +367,16 @@ mod tests {
         let email = "test@example.com";
 
         let token = auth_service
-            .generate_token(user_id, email)
+            .generate_token(user_id, email, &[])
             .expect("Failed to generate token");
 
         let claims = auth_service
             .validate_token(&token)
             .expect("Failed to validate token");
 
-        // Check that token expires in approximately 24 hours
+        // Check that token expires after the default access token lifetime
         let now = Utc::now().timestamp();
-        let expected_exp = now + Duration::hours(24).num_seconds();
+        let expected_exp = now + Duration::minutes(DEFAULT_ACCESS_TOKEN_TTL_MINUTES).num_seconds();
         assert!((claims.exp - expected_exp).abs() < 60); // Within 1 minute tolerance
         cleanup_test_env();
     }
@@ -284,7 +390,7 @@ mod tests {
         let email = "";
 
         let token = auth_service
-            .generate_token(user_id, email)
+            .generate_token(user_id, email, &[])
             .expect("Failed to generate token");
 
         let claims = auth_service
@@ -304,7 +410,7 @@ mod tests {
         let email = "test@example.com";
 
         let token = auth_service
-            .generate_token(user_id, email)
+            .generate_token(user_id, email, &[])
             .expect("Failed to generate token");
 
         let extracted_user_id = auth_service
@@ -329,6 +435,8 @@ mod tests {
             email: "test@example.com".to_string(),
             exp: expiration.timestamp(),
             iat: now.timestamp(),
+            roles: vec![],
+            sid: None,
         };
 
         let token = encode(&Header::default(), &claims, &auth_service.encoding_key)
@@ -375,6 +483,8 @@ mod tests {
             email: "test@example.com".to_string(),
             exp: past.timestamp(),
             iat: (past - Duration::hours(24)).timestamp(),
+            roles: vec![],
+            sid: None,
         };
 
         let token = encode(&Header::default(), &claims, &auth_service.encoding_key)
@@ -405,6 +515,8 @@ mod tests {
             email: "test@example.com".to_string(),
             exp: expiration.timestamp(),
             iat: now.timestamp(),
+            roles: vec![],
+            sid: None,
         };
 
         // Create a token with HS384 instead of HS256
@@ -432,6 +544,8 @@ mod tests {
             email: "test@example.com".to_string(),
             exp: expiration.timestamp(),
             iat: now.timestamp(),
+            roles: vec![],
+            sid: None,
         };
 
         let token =
@@ -473,6 +587,8 @@ mod tests {
             email: "test@example.com".to_string(),
             exp: now.timestamp() + 3600,
             iat: now.timestamp(),
+            roles: vec![],
+            sid: None,
         };
 
         // Test that Claims can be serialized/deserialized
@@ -0,0 +1,339 @@
+//! Board and board membership service
+//!
+//! Manages the `boards` and `board_members` tables. The user who creates a
+//! board becomes its owner and decides who else is a member. Boards are only
+//! visible to their members; to everyone else they do not exist.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    errors::{AppError, AppResult},
+    models::{BoardMember, BoardResponse, BoardRole, CreateBoardRequest},
+};
+
+/// Maximum length of a board name
+const MAX_BOARD_NAME_LEN: usize = 100;
+
+/// Maximum length of a board description
+const MAX_BOARD_DESCRIPTION_LEN: usize = 2000;
+
+pub struct BoardService {
+    db: SqlitePool,
+}
+
+pub(crate) fn board_not_found() -> AppError {
+    AppError::NotFound("Board not found".to_string())
+}
+
+/// Format a timestamp for a TEXT column
+///
+/// Fixed precision keeps stored timestamps in chronological order when they
+/// are compared as text, which cursor pagination relies on.
+pub(crate) fn db_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// Look up a user's role on a board
+///
+/// # Errors
+///
+/// Returns `AppError::NotFound` if the board does not exist or the user is not
+/// a member, or `AppError` if the database query fails
+pub(crate) async fn require_member(
+    db: &SqlitePool,
+    board_id: &str,
+    user_id: &str,
+) -> AppResult<BoardRole> {
+    sqlx::query_scalar!(
+        r#"SELECT role as "role: BoardRole" FROM board_members WHERE board_id = ?1 AND user_id = ?2"#,
+        board_id,
+        user_id
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(board_not_found)
+}
+
+fn validate_board(request: &CreateBoardRequest) -> AppResult<(&str, Option<&str>)> {
+    let name = request.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_BOARD_NAME_LEN {
+        return Err(AppError::ValidationError(
+            "Board name is required and must be at most 100 characters.".to_string(),
+        ));
+    }
+
+    let description = request
+        .description
+        .as_deref()
+        .map(str::trim)
+        .filter(|description| !description.is_empty());
+    if description
+        .is_some_and(|description| description.chars().count() > MAX_BOARD_DESCRIPTION_LEN)
+    {
+        return Err(AppError::ValidationError(
+            "Board description must be at most 2000 characters.".to_string(),
+        ));
+    }
+
+    Ok((name, description))
+}
+
+impl BoardService {
+    /// Create a new `BoardService`
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Create a board owned by `owner_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ValidationError` if the name or description is
+    /// invalid, or `AppError` if the database operation fails
+    pub async fn create_board(
+        &self,
+        owner_id: &str,
+        request: &CreateBoardRequest,
+    ) -> AppResult<BoardResponse> {
+        let (name, description) = validate_board(request)?;
+        let board_id = format!("board_{}", Uuid::new_v4());
+        let now = db_timestamp(Utc::now());
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO boards (id, name, description, owner_id, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+            "#,
+            board_id,
+            name,
+            description,
+            owner_id,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO board_members (board_id, user_id, role, created_at)
+            VALUES (?1, ?2, 'owner', ?3)
+            "#,
+            board_id,
+            owner_id,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::info!("User {} created board {}", owner_id, board_id);
+        self.get_board(owner_id, &board_id).await
+    }
+
+    /// List the boards a user is a member of, most recently created first
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn list_boards(&self, user_id: &str) -> AppResult<Vec<BoardResponse>> {
+        let boards = sqlx::query_as::<_, BoardResponse>(
+            r"
+            SELECT b.id, b.name, b.description, b.owner_id, b.created_at, b.updated_at, m.role
+            FROM boards b
+            JOIN board_members m ON m.board_id = b.id
+            WHERE m.user_id = ?1
+            ORDER BY b.created_at DESC, b.id DESC
+            ",
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(boards)
+    }
+
+    /// Get a board the user is a member of
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the board does not exist or the user is
+    /// not a member, or `AppError` if the database query fails
+    pub async fn get_board(&self, user_id: &str, board_id: &str) -> AppResult<BoardResponse> {
+        sqlx::query_as::<_, BoardResponse>(
+            r"
+            SELECT b.id, b.name, b.description, b.owner_id, b.created_at, b.updated_at, m.role
+            FROM boards b
+            JOIN board_members m ON m.board_id = b.id
+            WHERE b.id = ?1 AND m.user_id = ?2
+            ",
+        )
+        .bind(board_id)
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(board_not_found)
+    }
+
+    /// Delete a board and all of its issues
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user is not a member,
+    /// `AppError::Forbidden` if they are not the owner, or `AppError` if the
+    /// database operation fails
+    pub async fn delete_board(&self, user_id: &str, board_id: &str) -> AppResult<()> {
+        self.require_owner(board_id, user_id).await?;
+
+        sqlx::query!("DELETE FROM boards WHERE id = ?1", board_id)
+            .execute(&self.db)
+            .await?;
+
+        tracing::info!("User {} deleted board {}", user_id, board_id);
+        Ok(())
+    }
+
+    /// List a board's members, owner first
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user is not a member, or `AppError`
+    /// if the database query fails
+    pub async fn list_members(&self, user_id: &str, board_id: &str) -> AppResult<Vec<BoardMember>> {
+        require_member(&self.db, board_id, user_id).await?;
+
+        let members = sqlx::query_as!(
+            BoardMember,
+            r#"
+            SELECT m.user_id, u.email,
+                   m.role as "role: BoardRole",
+                   m.created_at as "created_at: DateTime<Utc>"
+            FROM board_members m
+            JOIN users u ON u.id = m.user_id
+            WHERE m.board_id = ?1
+            ORDER BY m.role = 'owner' DESC, m.created_at, u.email
+            "#,
+            board_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(members)
+    }
+
+    /// Add a registered user to a board as a member
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the requester is not a member or no user
+    /// has the email, `AppError::Forbidden` if the requester is not the owner,
+    /// `AppError::BadRequest` if the user is already a member, or `AppError` if
+    /// the database operation fails
+    pub async fn add_member(
+        &self,
+        user_id: &str,
+        board_id: &str,
+        email: &str,
+    ) -> AppResult<BoardMember> {
+        self.require_owner(board_id, user_id).await?;
+
+        let email = email.trim();
+        let member_id = sqlx::query_scalar!(
+            r#"SELECT id as "id!" FROM users WHERE email = ?1 COLLATE NOCASE"#,
+            email
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No registered user has that email".to_string()))?;
+
+        let now = db_timestamp(Utc::now());
+        let added = sqlx::query!(
+            r#"
+            INSERT INTO board_members (board_id, user_id, role, created_at)
+            VALUES (?1, ?2, 'member', ?3)
+            ON CONFLICT (board_id, user_id) DO NOTHING
+            "#,
+            board_id,
+            member_id,
+            now
+        )
+        .execute(&self.db)
+        .await?;
+        if added.rows_affected() == 0 {
+            return Err(AppError::BadRequest(
+                "User is already a member of this board".to_string(),
+            ));
+        }
+
+        tracing::info!("User {} added {} to board {}", user_id, member_id, board_id);
+
+        self.list_members(user_id, board_id)
+            .await?
+            .into_iter()
+            .find(|member| member.user_id == member_id)
+            .ok_or_else(|| AppError::InternalServerError("Added member not found".to_string()))
+    }
+
+    /// Remove a member from a board
+    ///
+    /// The owner can remove anyone but themselves; members can remove themselves.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the requester or the target is not a
+    /// member, `AppError::Forbidden` if the requester may not remove the
+    /// target, or `AppError` if the database operation fails
+    pub async fn remove_member(
+        &self,
+        user_id: &str,
+        board_id: &str,
+        member_id: &str,
+    ) -> AppResult<()> {
+        let role = require_member(&self.db, board_id, user_id).await?;
+        let target_role = require_member(&self.db, board_id, member_id)
+            .await
+            .map_err(|_| AppError::NotFound("Board member not found".to_string()))?;
+
+        if target_role == BoardRole::Owner {
+            return Err(AppError::Forbidden(
+                "The board owner cannot be removed".to_string(),
+            ));
+        }
+        if role != BoardRole::Owner && user_id != member_id {
+            return Err(AppError::Forbidden(
+                "Only the board owner can remove other members".to_string(),
+            ));
+        }
+
+        sqlx::query!(
+            "DELETE FROM board_members WHERE board_id = ?1 AND user_id = ?2",
+            board_id,
+            member_id
+        )
+        .execute(&self.db)
+        .await?;
+
+        tracing::info!(
+            "User {} removed {} from board {}",
+            user_id,
+            member_id,
+            board_id
+        );
+        Ok(())
+    }
+
+    async fn require_owner(&self, board_id: &str, user_id: &str) -> AppResult<()> {
+        match require_member(&self.db, board_id, user_id).await? {
+            BoardRole::Owner => Ok(()),
+            BoardRole::Member => Err(AppError::Forbidden(
+                "Only the board owner can do this".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "board_service_tests.rs"]
+mod board_service_tests;
@@ -0,0 +1,148 @@
+//! Tests for board service
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::AppError;
+    use crate::models::{BoardRole, CreateBoardRequest};
+    use crate::services::BoardService;
+    use sqlx::SqlitePool;
+    use uuid::Uuid;
+
+    async fn create_test_user(pool: &SqlitePool) -> (String, String) {
+        let user_id = Uuid::new_v4().to_string();
+        let email = format!("test+{user_id}@example.com");
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, email, hashed_password, provider, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            user_id,
+            email,
+            "hashed_password",
+            "local",
+            now,
+            now
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to create test user");
+
+        (user_id, email)
+    }
+
+    fn board_request(name: &str) -> CreateBoardRequest {
+        CreateBoardRequest {
+            name: name.to_string(),
+            description: Some("  ".to_string()),
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_boards_are_visible_to_members_only(pool: SqlitePool) {
+        let service = BoardService::new(pool.clone());
+        let (owner_id, _) = create_test_user(&pool).await;
+        let (outsider_id, _) = create_test_user(&pool).await;
+
+        let board = service
+            .create_board(&owner_id, &board_request("  Roadmap "))
+            .await
+            .expect("board created");
+        assert_eq!(board.board.name, "Roadmap");
+        assert_eq!(board.board.description, None);
+        assert_eq!(board.role, BoardRole::Owner);
+
+        let boards = service.list_boards(&owner_id).await.expect("boards listed");
+        assert_eq!(boards.len(), 1);
+        assert!(
+            service
+                .list_boards(&outsider_id)
+                .await
+                .expect("boards listed")
+                .is_empty()
+        );
+
+        let hidden = service.get_board(&outsider_id, &board.board.id).await;
+        assert!(matches!(hidden, Err(AppError::NotFound(_))));
+        let hidden_delete = service.delete_board(&outsider_id, &board.board.id).await;
+        assert!(matches!(hidden_delete, Err(AppError::NotFound(_))));
+
+        let unnamed = service.create_board(&owner_id, &board_request(" ")).await;
+        assert!(matches!(unnamed, Err(AppError::ValidationError(_))));
+    }
+
+    #[sqlx::test]
+    async fn test_owner_manages_members(pool: SqlitePool) {
+        let service = BoardService::new(pool.clone());
+        let (owner_id, _) = create_test_user(&pool).await;
+        let (member_id, member_email) = create_test_user(&pool).await;
+        let (other_id, other_email) = create_test_user(&pool).await;
+        let board = service
+            .create_board(&owner_id, &board_request("Roadmap"))
+            .await
+            .expect("board created");
+        let board_id = &board.board.id;
+
+        let member = service
+            .add_member(&owner_id, board_id, &member_email.to_uppercase())
+            .await
+            .expect("member added");
+        assert_eq!(member.user_id, member_id);
+        assert_eq!(member.role, BoardRole::Member);
+
+        let again = service.add_member(&owner_id, board_id, &member_email).await;
+        assert!(matches!(again, Err(AppError::BadRequest(_))));
+        let unknown = service
+            .add_member(&owner_id, board_id, "nobody@example.com")
+            .await;
+        assert!(matches!(unknown, Err(AppError::NotFound(_))));
+
+        // Members can see the board but not manage it
+        let as_member = service
+            .get_board(&member_id, board_id)
+            .await
+            .expect("member sees board");
+        assert_eq!(as_member.role, BoardRole::Member);
+        let invite = service.add_member(&member_id, board_id, &other_email).await;
+        assert!(matches!(invite, Err(AppError::Forbidden(_))));
+        let delete = service.delete_board(&member_id, board_id).await;
+        assert!(matches!(delete, Err(AppError::Forbidden(_))));
+        let remove_owner = service.remove_member(&owner_id, board_id, &owner_id).await;
+        assert!(matches!(remove_owner, Err(AppError::Forbidden(_))));
+
+        let members = service
+            .list_members(&member_id, board_id)
+            .await
+            .expect("members listed");
+        let roles: Vec<BoardRole> = members.iter().map(|m| m.role).collect();
+        assert_eq!(roles, [BoardRole::Owner, BoardRole::Member]);
+
+        service
+            .add_member(&owner_id, board_id, &other_email)
+            .await
+            .expect("member added");
+        let remove_other = service.remove_member(&member_id, board_id, &other_id).await;
+        assert!(matches!(remove_other, Err(AppError::Forbidden(_))));
+
+        // Members may leave on their own
+        service
+            .remove_member(&member_id, board_id, &member_id)
+            .await
+            .expect("member left");
+        let gone = service.get_board(&member_id, board_id).await;
+        assert!(matches!(gone, Err(AppError::NotFound(_))));
+
+        service
+            .delete_board(&owner_id, board_id)
+            .await
+            .expect("board deleted");
+        assert!(
+            service
+                .list_boards(&other_id)
+                .await
+                .expect("boards listed")
+                .is_empty()
+        );
+    }
+}
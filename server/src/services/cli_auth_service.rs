@@ -0,0 +1,592 @@
+//! CLI device login service
+//!
+//! Manages the `cli_auth_flows` table. A flow is started by a command-line tool
+//! with a PKCE code challenge, approved by a signed-in user in the browser, and
+//! completed when the tool presents the one-time code together with the matching
+//! code verifier. Only the hash of the code is stored.
+
+use chrono::{DateTime, Duration, Utc};
+use oauth2::{PkceCodeChallenge, PkceCodeVerifier};
+use reqwest::Url;
+use sqlx::SqlitePool;
+use std::env;
+use uuid::Uuid;
+
+use crate::{
+    core::token_utils::{generate_opaque_token, hash_token},
+    errors::{AppError, AppResult},
+    models::{
+        DeviceInfo,
+        cli_auth::{CliFlowInfo, CliFlowStatus, StartCliFlowRequest, StartCliFlowResponse},
+    },
+};
+
+/// Minutes a flow stays valid after it is started
+pub const CLI_FLOW_TTL_MINUTES: i64 = 10;
+
+/// The only supported PKCE challenge method
+const CHALLENGE_METHOD_S256: &str = "S256";
+
+/// Length of a base64url-encoded SHA-256 digest without padding
+const S256_CHALLENGE_LEN: usize = 43;
+
+/// Maximum length of device names and fingerprints
+const MAX_DEVICE_FIELD_LEN: usize = 200;
+
+/// An approved flow whose code has been exchanged
+#[derive(Debug, Clone)]
+pub struct ExchangedFlow {
+    pub flow_id: String,
+    pub user_id: Uuid,
+    pub device: DeviceInfo,
+}
+
+pub struct CliAuthService {
+    db: SqlitePool,
+    client_url: String,
+}
+
+fn is_base64url(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Check that a redirect URI points at the local machine over plain HTTP
+fn is_loopback_uri(uri: &str) -> bool {
+    Url::parse(uri).is_ok_and(|url| {
+        url.scheme() == "http"
+            && matches!(url.host_str(), Some("127.0.0.1" | "[::1]" | "localhost"))
+    })
+}
+
+/// Verify a PKCE code verifier against an S256 code challenge
+fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    // RFC 7636: verifiers are 43 to 128 unreserved characters
+    let valid_verifier = (43..=128).contains(&code_verifier.len())
+        && code_verifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'));
+    if !valid_verifier {
+        return false;
+    }
+
+    let verifier = PkceCodeVerifier::new(code_verifier.to_string());
+    PkceCodeChallenge::from_code_verifier_sha256(&verifier).as_str() == code_challenge
+}
+
+fn invalid_flow() -> AppError {
+    AppError::NotFound("CLI login flow not found or expired".to_string())
+}
+
+impl CliAuthService {
+    /// Create a new `CliAuthService`
+    ///
+    /// # Environment Variables
+    /// - `CLIENT_URL`: Base URL of the web client hosting the approval page (default: `http://localhost:8080`)
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        let client_url =
+            env::var("CLIENT_URL").unwrap_or_else(|_| "http://localhost:8080".to_string());
+        Self { db, client_url }
+    }
+
+    /// Start a new CLI login flow
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ValidationError` if the device details, code challenge or
+    /// redirect URI are invalid, or `AppError` if the database operation fails
+    pub async fn start_flow(
+        &self,
+        request: &StartCliFlowRequest,
+    ) -> AppResult<StartCliFlowResponse> {
+        let device_name = request.device_name.trim();
+        let device_fingerprint = request.device_fingerprint.trim();
+        if device_name.is_empty() || device_name.len() > MAX_DEVICE_FIELD_LEN {
+            return Err(AppError::ValidationError(
+                "Device name is required and must be at most 200 characters.".to_string(),
+            ));
+        }
+        if device_fingerprint.is_empty() || device_fingerprint.len() > MAX_DEVICE_FIELD_LEN {
+            return Err(AppError::ValidationError(
+                "Device fingerprint is required and must be at most 200 characters.".to_string(),
+            ));
+        }
+
+        let method = request
+            .code_challenge_method
+            .as_deref()
+            .unwrap_or(CHALLENGE_METHOD_S256);
+        if method != CHALLENGE_METHOD_S256 {
+            return Err(AppError::ValidationError(
+                "Only the S256 code challenge method is supported.".to_string(),
+            ));
+        }
+        if request.code_challenge.len() != S256_CHALLENGE_LEN
+            || !is_base64url(&request.code_challenge)
+        {
+            return Err(AppError::ValidationError(
+                "Code challenge must be a base64url-encoded SHA-256 hash.".to_string(),
+            ));
+        }
+
+        if let Some(redirect_uri) = &request.redirect_uri
+            && !is_loopback_uri(redirect_uri)
+        {
+            return Err(AppError::ValidationError(
+                "Redirect URI must be an http loopback address.".to_string(),
+            ));
+        }
+
+        let flow_id = Uuid::new_v4().to_string();
+        let state = generate_opaque_token();
+        let now = Utc::now();
+        let expires_at = now + Duration::minutes(CLI_FLOW_TTL_MINUTES);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO cli_auth_flows
+                (id, device_name, device_fingerprint, state, code_challenge, challenge_method,
+                 redirect_uri, status, expires_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'pending', ?8, ?9)
+            "#,
+            flow_id,
+            device_name,
+            device_fingerprint,
+            state,
+            request.code_challenge,
+            CHALLENGE_METHOD_S256,
+            request.redirect_uri,
+            expires_at,
+            now
+        )
+        .execute(&self.db)
+        .await?;
+
+        tracing::info!("Started CLI login flow for device '{}'", device_name);
+
+        let verification_url = format!(
+            "{}/auth/cli?state={}",
+            self.client_url.trim_end_matches('/'),
+            urlencoding::encode(&state)
+        );
+
+        Ok(StartCliFlowResponse {
+            state,
+            verification_url,
+            expires_at,
+        })
+    }
+
+    /// Look up a flow for display on the approval page
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the flow does not exist, or `AppError` if
+    /// the database query fails
+    pub async fn get_flow(&self, state: &str) -> AppResult<CliFlowInfo> {
+        let flow = sqlx::query!(
+            r#"
+            SELECT device_name, status, expires_at as "expires_at: DateTime<Utc>"
+            FROM cli_auth_flows
+            WHERE state = ?1
+            "#,
+            state
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(invalid_flow)?;
+
+        let mut status = flow
+            .status
+            .parse::<CliFlowStatus>()
+            .map_err(AppError::InternalServerError)?;
+        if flow.expires_at < Utc::now() && status != CliFlowStatus::Completed {
+            status = CliFlowStatus::Expired;
+        }
+
+        Ok(CliFlowInfo {
+            device_name: flow.device_name,
+            status,
+            expires_at: flow.expires_at,
+        })
+    }
+
+    /// Approve a pending flow on behalf of a user and issue its one-time code
+    ///
+    /// Returns the code and, if the CLI registered a loopback redirect URI, the
+    /// URL the browser should send the code to.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the flow does not exist, has expired or is
+    /// no longer pending, or `AppError` if the database operation fails
+    pub async fn approve_flow(
+        &self,
+        state: &str,
+        user_id: Uuid,
+    ) -> AppResult<(String, Option<String>)> {
+        let code = generate_opaque_token();
+        let code_hash = hash_token(&code);
+        let user_id_str = user_id.to_string();
+        let now = Utc::now();
+
+        let approved = sqlx::query!(
+            r#"
+            UPDATE cli_auth_flows
+            SET status = 'approved', user_id = ?1, auth_code = ?2, approved_at = ?3
+            WHERE state = ?4 AND status = 'pending' AND expires_at > ?3
+            RETURNING redirect_uri
+            "#,
+            user_id_str,
+            code_hash,
+            now,
+            state
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(invalid_flow)?;
+
+        tracing::info!("CLI login flow approved by user {}", user_id);
+
+        let redirect_url = approved.redirect_uri.map(|uri| {
+            let separator = if uri.contains('?') { '&' } else { '?' };
+            format!(
+                "{uri}{separator}code={}&state={}",
+                urlencoding::encode(&code),
+                urlencoding::encode(state)
+            )
+        });
+
+        Ok((code, redirect_url))
+    }
+
+    /// Exchange an approved flow's code and PKCE verifier
+    ///
+    /// The flow is consumed on success. A wrong code or verifier expires the flow.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Unauthorized` if the flow is unknown, not approved,
+    /// expired, or the code or verifier do not match, or `AppError` if the database
+    /// operation fails
+    pub async fn exchange_code(
+        &self,
+        state: &str,
+        code: &str,
+        code_verifier: &str,
+    ) -> AppResult<ExchangedFlow> {
+        let invalid_grant =
+            || AppError::Unauthorized("Invalid or expired CLI login code".to_string());
+        let now = Utc::now();
+
+        let flow = sqlx::query!(
+            r#"
+            SELECT id, user_id, device_name, device_fingerprint, code_challenge, auth_code, status,
+                   expires_at as "expires_at: DateTime<Utc>"
+            FROM cli_auth_flows
+            WHERE state = ?1
+            "#,
+            state
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(invalid_grant)?;
+
+        if flow.status != CliFlowStatus::Approved.as_str() || flow.expires_at < now {
+            return Err(invalid_grant());
+        }
+
+        let code_matches = flow
+            .auth_code
+            .as_deref()
+            .is_some_and(|stored| stored == hash_token(code));
+        if !code_matches || !verify_pkce(code_verifier, &flow.code_challenge) {
+            tracing::warn!(
+                "CLI login flow {} presented a wrong code or verifier",
+                flow.id
+            );
+            self.set_status(&flow.id, CliFlowStatus::Expired).await?;
+            return Err(invalid_grant());
+        }
+
+        // Consume the flow; a concurrent exchange of the same code loses here
+        let consumed = sqlx::query!(
+            r#"
+            UPDATE cli_auth_flows SET status = 'completed', auth_code = NULL, completed_at = ?1
+            WHERE id = ?2 AND status = 'approved'
+            "#,
+            now,
+            flow.id
+        )
+        .execute(&self.db)
+        .await?;
+        if consumed.rows_affected() == 0 {
+            return Err(invalid_grant());
+        }
+
+        let user_id = flow
+            .user_id
+            .as_deref()
+            .map(Uuid::parse_str)
+            .transpose()
+            .map_err(|e| {
+                AppError::InternalServerError(format!("Invalid user ID in database: {e}"))
+            })?
+            .ok_or_else(invalid_grant)?;
+
+        Ok(ExchangedFlow {
+            flow_id: flow.id,
+            user_id,
+            device: DeviceInfo {
+                name: flow.device_name,
+                fingerprint: flow.device_fingerprint,
+            },
+        })
+    }
+
+    /// Record the device created for a completed flow
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn link_device(&self, flow_id: &str, device_id: Uuid) -> AppResult<()> {
+        let device_id_str = device_id.to_string();
+
+        sqlx::query!(
+            "UPDATE cli_auth_flows SET device_id = ?1 WHERE id = ?2",
+            device_id_str,
+            flow_id
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn set_status(&self, flow_id: &str, status: CliFlowStatus) -> AppResult<()> {
+        let status_str = status.as_str();
+
+        sqlx::query!(
+            "UPDATE cli_auth_flows SET status = ?1 WHERE id = ?2",
+            status_str,
+            flow_id
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete flows whose expiry time has passed
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn cleanup_expired_flows(&self) -> AppResult<u64> {
+        let now = Utc::now();
+
+        let result = sqlx::query!("DELETE FROM cli_auth_flows WHERE expires_at < ?1", now)
+            .execute(&self.db)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::auth_handler::RegisterUserPayload;
+    use crate::services::UserServiceImpl;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect("sqlite::memory:")
+            .await
+            .expect("Failed to create test database");
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .expect("Failed to run migrations");
+
+        pool
+    }
+
+    async fn create_user(pool: &SqlitePool, email: &str) -> Uuid {
+        UserServiceImpl::new(pool.clone())
+            .create_user(&RegisterUserPayload {
+                email: email.to_string(),
+                password: "password123".to_string(),
+            })
+            .await
+            .expect("Failed to create user")
+            .id
+    }
+
+    fn flow_request(code_challenge: &str, redirect_uri: Option<&str>) -> StartCliFlowRequest {
+        StartCliFlowRequest {
+            device_name: "Work laptop".to_string(),
+            device_fingerprint: "fingerprint-123".to_string(),
+            code_challenge: code_challenge.to_string(),
+            code_challenge_method: None,
+            redirect_uri: redirect_uri.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_loopback_uri_validation() {
+        assert!(is_loopback_uri("http://127.0.0.1:8765/callback"));
+        assert!(is_loopback_uri("http://localhost:8765"));
+        assert!(is_loopback_uri("http://[::1]:8765/"));
+        assert!(!is_loopback_uri("https://example.com/callback"));
+        assert!(!is_loopback_uri("http://192.168.1.10/callback"));
+        assert!(!is_loopback_uri("not a url"));
+    }
+
+    #[test]
+    fn test_verify_pkce() {
+        let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+        assert!(verify_pkce(verifier.secret(), challenge.as_str()));
+
+        let (_, other_verifier) = PkceCodeChallenge::new_random_sha256();
+        assert!(!verify_pkce(other_verifier.secret(), challenge.as_str()));
+        assert!(!verify_pkce("too-short", challenge.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_complete_flow() {
+        let pool = setup_test_db().await;
+        let service = CliAuthService::new(pool.clone());
+        let user_id = create_user(&pool, "cli@example.com").await;
+        let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+
+        let started = service
+            .start_flow(&flow_request(
+                challenge.as_str(),
+                Some("http://127.0.0.1:8765/callback"),
+            ))
+            .await
+            .expect("start flow");
+        assert!(started.verification_url.contains(&started.state));
+
+        let info = service.get_flow(&started.state).await.expect("get flow");
+        assert_eq!(info.device_name, "Work laptop");
+        assert_eq!(info.status, CliFlowStatus::Pending);
+
+        let (code, redirect_url) = service
+            .approve_flow(&started.state, user_id)
+            .await
+            .expect("approve flow");
+        assert!(
+            redirect_url
+                .expect("redirect url")
+                .starts_with("http://127.0.0.1:8765/callback?code=")
+        );
+
+        // A flow can only be approved once
+        assert!(service.approve_flow(&started.state, user_id).await.is_err());
+
+        let exchanged = service
+            .exchange_code(&started.state, &code, verifier.secret())
+            .await
+            .expect("exchange code");
+        assert_eq!(exchanged.user_id, user_id);
+        assert_eq!(exchanged.device.name, "Work laptop");
+        assert_eq!(exchanged.device.fingerprint, "fingerprint-123");
+
+        // The code is single use
+        let replay = service
+            .exchange_code(&started.state, &code, verifier.secret())
+            .await;
+        assert!(matches!(replay, Err(AppError::Unauthorized(_))));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_verifier_expires_flow() {
+        let pool = setup_test_db().await;
+        let service = CliAuthService::new(pool.clone());
+        let user_id = create_user(&pool, "cli@example.com").await;
+        let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+        let (_, wrong_verifier) = PkceCodeChallenge::new_random_sha256();
+
+        let started = service
+            .start_flow(&flow_request(challenge.as_str(), None))
+            .await
+            .expect("start flow");
+        let (code, redirect_url) = service
+            .approve_flow(&started.state, user_id)
+            .await
+            .expect("approve flow");
+        assert!(redirect_url.is_none());
+
+        let result = service
+            .exchange_code(&started.state, &code, wrong_verifier.secret())
+            .await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+
+        // The correct verifier no longer works either
+        let result = service
+            .exchange_code(&started.state, &code, verifier.secret())
+            .await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+        assert_eq!(
+            service
+                .get_flow(&started.state)
+                .await
+                .expect("get flow")
+                .status,
+            CliFlowStatus::Expired
+        );
+    }
+
+    #[tokio::test]
+    async fn test_start_flow_validation() {
+        let pool = setup_test_db().await;
+        let service = CliAuthService::new(pool);
+        let (challenge, _) = PkceCodeChallenge::new_random_sha256();
+
+        let result = service.start_flow(&flow_request("short", None)).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+        let result = service
+            .start_flow(&flow_request(
+                challenge.as_str(),
+                Some("https://attacker.example.com/callback"),
+            ))
+            .await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+        let mut request = flow_request(challenge.as_str(), None);
+        request.code_challenge_method = Some("plain".to_string());
+        let result = service.start_flow(&request).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_expired_flows() {
+        let pool = setup_test_db().await;
+        let service = CliAuthService::new(pool.clone());
+        let (challenge, _) = PkceCodeChallenge::new_random_sha256();
+
+        let started = service
+            .start_flow(&flow_request(challenge.as_str(), None))
+            .await
+            .expect("start flow");
+        assert_eq!(service.cleanup_expired_flows().await.expect("cleanup"), 0);
+
+        let past = Utc::now() - Duration::minutes(1);
+        sqlx::query!(
+            "UPDATE cli_auth_flows SET expires_at = ?1 WHERE state = ?2",
+            past,
+            started.state
+        )
+        .execute(&pool)
+        .await
+        .expect("expire flow");
+
+        assert_eq!(service.cleanup_expired_flows().await.expect("cleanup"), 1);
+        assert!(service.get_flow(&started.state).await.is_err());
+    }
+}
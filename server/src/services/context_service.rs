@@ -0,0 +1,403 @@
+//! Context service for fitting conversations into a model's context window
+//!
+//! Prompts are built from a conversation's stored messages. When they no
+//! longer fit in the model's window, less the tokens reserved for the reply,
+//! the older turns are summarized by the model and only the summary and the
+//! most recent turns are sent. Each summary is stored under `summary` in the
+//! metadata of the last message it covers, so later prompts start from it and
+//! only summarize what came after.
+//!
+//! A conversation's messages form a tree, and a prompt is built from one
+//! branch of it: the message being replied to and its ancestors. A summary
+//! only covers the ancestors of the message it is stored on, so it holds for
+//! every branch forked after that message.
+
+use serde_json::{Value, json};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+
+use crate::ai::models::TokenUsage;
+use crate::ai::{ChatMessage, ChatRole, Tokenizer};
+use crate::errors::{AppError, AppResult};
+use crate::models::ConversationSummary;
+use crate::services::AiService;
+
+/// Tokens reserved for the reply when the request does not limit it
+pub const DEFAULT_REPLY_TOKENS: usize = 1024;
+
+/// Tokens each message costs beyond its content, for its role and separators
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// Longest summary kept; longer replies are truncated
+const SUMMARY_MAX_TOKENS: usize = 512;
+
+/// Tokens set aside for the summarization prompt's own instructions
+const SUMMARY_PROMPT_TOKENS: usize = 256;
+
+/// Template that summarizes conversation turns
+const SUMMARY_TEMPLATE: &str = "conversation_summary";
+
+/// A message as stored, with its tokens counted
+struct StoredTurn {
+    id: String,
+    role: String,
+    content: String,
+    tokens: usize,
+    summary: Option<ConversationSummary>,
+}
+
+/// What a prompt is built from
+pub struct ContextRequest<'a> {
+    pub conversation_id: &'a str,
+    /// Last message of the branch to build from; the conversation's latest
+    /// message when `None`
+    pub message_id: Option<&'a str>,
+    /// Model the prompt is for, which decides its window and tokenizer
+    pub model: &'a str,
+    /// Messages sent ahead of the history in every prompt, such as system
+    /// instructions and retrieved sources
+    pub pinned: Vec<ChatMessage>,
+    /// Tokens left free for the reply
+    pub reply_tokens: usize,
+}
+
+/// A prompt that fits the model's context window
+#[derive(Debug)]
+pub struct BuiltContext {
+    pub messages: Vec<ChatMessage>,
+    /// Tokens in the prompt, as counted by the model's tokenizer
+    pub prompt_tokens: usize,
+    /// Tokens spent summarizing older turns for this prompt, if any
+    pub summary_usage: Option<TokenUsage>,
+}
+
+pub struct ContextService {
+    db: SqlitePool,
+}
+
+/// Tokens a message costs in a prompt
+fn message_tokens(tokenizer: &Tokenizer, content: &str) -> usize {
+    tokenizer.count(content) + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// Chat role of a stored message role
+fn chat_role(role: &str) -> ChatRole {
+    match role {
+        "system" => ChatRole::System,
+        "assistant" => ChatRole::Assistant,
+        _ => ChatRole::User,
+    }
+}
+
+/// System message giving the model a summary of the earlier conversation
+fn summary_message(summary: &str) -> ChatMessage {
+    ChatMessage {
+        role: ChatRole::System,
+        content: format!("Summary of the earlier conversation:\n{summary}"),
+        function_call: None,
+    }
+}
+
+impl ContextService {
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Build a prompt from a conversation's stored messages that fits the
+    /// model's context window
+    ///
+    /// The pinned messages come first, then the latest summary of the older
+    /// turns, if any, then as many of the most recent turns as fit. Turns that
+    /// no longer fit are summarized and the summary stored. If summarizing
+    /// fails, those turns are left out and the previous summary is used.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ValidationError` if the pinned messages or the last
+    /// message do not fit in the window, or an error if the messages cannot be
+    /// loaded or the summary stored
+    pub async fn build_prompt(
+        &self,
+        ai: &AiService,
+        request: ContextRequest<'_>,
+    ) -> AppResult<BuiltContext> {
+        let tokenizer = ai.tokenizer(Some(request.model));
+        let window = ai.context_window(Some(request.model));
+        let pinned_tokens: usize = request
+            .pinned
+            .iter()
+            .map(|message| message_tokens(&tokenizer, &message.content))
+            .sum();
+        let budget = window
+            .checked_sub(request.reply_tokens + pinned_tokens)
+            .filter(|budget| *budget > MESSAGE_OVERHEAD_TOKENS)
+            .ok_or_else(|| {
+                AppError::ValidationError(format!(
+                    "The request does not fit in the {window} token context window of {}.",
+                    request.model
+                ))
+            })?;
+
+        let turns = self
+            .load_branch(request.conversation_id, request.message_id, &tokenizer)
+            .await?;
+        // Only turns after the latest summary are sent as they are
+        let start = turns
+            .iter()
+            .rposition(|turn| turn.summary.is_some())
+            .map_or(0, |index| index + 1);
+        let mut summary = start
+            .checked_sub(1)
+            .and_then(|index| turns[index].summary.clone());
+        let turns = &turns[start..];
+
+        let summary_tokens = summary
+            .as_ref()
+            .map_or(0, |summary| message_tokens(&tokenizer, &summary.content));
+        let history_tokens: usize = turns.iter().map(|turn| turn.tokens).sum();
+
+        let mut kept_from = 0;
+        let mut summary_usage = None;
+        if summary_tokens + history_tokens > budget {
+            // Keep the most recent turns that leave room for a summary
+            let keep_budget = budget.saturating_sub(SUMMARY_MAX_TOKENS + MESSAGE_OVERHEAD_TOKENS);
+            let mut kept_tokens = 0;
+            kept_from = turns.len();
+            while kept_from > 0 && kept_tokens + turns[kept_from - 1].tokens <= keep_budget {
+                kept_from -= 1;
+                kept_tokens += turns[kept_from].tokens;
+            }
+            if kept_from == turns.len() {
+                return Err(AppError::ValidationError(format!(
+                    "The last message is too long for the {window} token context window of {}.",
+                    request.model
+                )));
+            }
+
+            let older = &turns[..kept_from];
+            match Self::summarize(ai, &tokenizer, window, summary.as_ref(), older).await {
+                Ok((content, usage)) => {
+                    let covered = summary.as_ref().map_or(0, |summary| summary.message_count);
+                    let new_summary = ConversationSummary {
+                        token_count: i64::try_from(tokenizer.count(&content)).unwrap_or(i64::MAX),
+                        content,
+                        message_count: covered + i64::try_from(older.len()).unwrap_or(i64::MAX),
+                        created_at: chrono::Utc::now().to_rfc3339(),
+                    };
+                    let last = &older[older.len() - 1];
+                    self.store_summary(&last.id, &new_summary).await?;
+                    summary = Some(new_summary);
+                    summary_usage = usage;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to summarize {} messages of conversation {}, leaving them out: {e}",
+                        older.len(),
+                        request.conversation_id
+                    );
+                }
+            }
+        }
+
+        let mut messages = request.pinned;
+        if let Some(summary) = &summary {
+            messages.push(summary_message(&summary.content));
+        }
+        messages.extend(turns[kept_from..].iter().map(|turn| ChatMessage {
+            role: chat_role(&turn.role),
+            content: turn.content.clone(),
+            function_call: None,
+        }));
+        let prompt_tokens = messages
+            .iter()
+            .map(|message| message_tokens(&tokenizer, &message.content))
+            .sum();
+
+        Ok(BuiltContext {
+            messages,
+            prompt_tokens,
+            summary_usage,
+        })
+    }
+
+    /// Load the branch of a conversation ending at a message, oldest first,
+    /// counting its messages' tokens
+    async fn load_branch(
+        &self,
+        conversation_id: &str,
+        message_id: Option<&str>,
+        tokenizer: &Tokenizer,
+    ) -> AppResult<Vec<StoredTurn>> {
+        let mut rows = sqlx::query!(
+            r#"
+            SELECT id as "id!", role, content, metadata, parent_message_id
+            FROM ai_messages
+            WHERE conversation_id = ?1
+            ORDER BY created_at ASC, rowid ASC
+            "#,
+            conversation_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let index: HashMap<String, usize> = rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| (row.id.clone(), i))
+            .collect();
+        let mut next = match message_id {
+            Some(message_id) => Some(
+                *index
+                    .get(message_id)
+                    .ok_or_else(|| AppError::NotFound("Message not found".to_string()))?,
+            ),
+            None => rows.len().checked_sub(1),
+        };
+        // Follow parents back to the root, stopping should they ever loop
+        let mut branch = Vec::new();
+        while let Some(i) = next.filter(|_| branch.len() < rows.len()) {
+            branch.push(i);
+            next = rows[i]
+                .parent_message_id
+                .as_ref()
+                .and_then(|parent| index.get(parent).copied());
+        }
+
+        Ok(branch
+            .into_iter()
+            .rev()
+            .map(|i| {
+                let row = &mut rows[i];
+                // Stored counts may come from another model's tokenizer, so
+                // messages are counted again for this one
+                let tokens = message_tokens(tokenizer, &row.content);
+                let summary = row
+                    .metadata
+                    .take()
+                    .and_then(|metadata| serde_json::from_str::<Value>(&metadata).ok())
+                    .and_then(|mut metadata| metadata.get_mut("summary").map(Value::take))
+                    .and_then(|summary| serde_json::from_value(summary).ok());
+                StoredTurn {
+                    id: std::mem::take(&mut row.id),
+                    role: std::mem::take(&mut row.role),
+                    content: std::mem::take(&mut row.content),
+                    tokens,
+                    summary,
+                }
+            })
+            .collect())
+    }
+
+    /// Store a summary in the metadata of the last message it covers
+    async fn store_summary(
+        &self,
+        message_id: &str,
+        summary: &ConversationSummary,
+    ) -> AppResult<()> {
+        let metadata =
+            sqlx::query_scalar!("SELECT metadata FROM ai_messages WHERE id = ?1", message_id)
+                .fetch_one(&self.db)
+                .await?;
+        let mut metadata = metadata
+            .and_then(|metadata| serde_json::from_str::<Value>(&metadata).ok())
+            .filter(Value::is_object)
+            .unwrap_or_else(|| json!({}));
+        metadata["summary"] = serde_json::to_value(summary)
+            .map_err(|e| AppError::InternalServerError(format!("Failed to encode summary: {e}")))?;
+        let metadata = metadata.to_string();
+
+        sqlx::query!(
+            "UPDATE ai_messages SET metadata = ?1 WHERE id = ?2",
+            metadata,
+            message_id
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Summarize turns, following on from an earlier summary
+    ///
+    /// Turns that would not fit in one summarization prompt are summarized a
+    /// part at a time, each part's summary carried into the next.
+    async fn summarize(
+        ai: &AiService,
+        tokenizer: &Tokenizer,
+        window: usize,
+        previous: Option<&ConversationSummary>,
+        turns: &[StoredTurn],
+    ) -> AppResult<(String, Option<TokenUsage>)> {
+        let input_budget = window.saturating_sub(2 * SUMMARY_MAX_TOKENS + SUMMARY_PROMPT_TOKENS);
+        let mut summary = previous.map(|summary| summary.content.clone());
+        let mut usage: Option<TokenUsage> = None;
+        let mut part: Vec<Value> = Vec::new();
+        let mut part_tokens = 0;
+
+        for (i, turn) in turns.iter().enumerate() {
+            // A single turn may take up at most half a part
+            let content = tokenizer.truncate(&turn.content, input_budget / 2);
+            let tokens = message_tokens(tokenizer, content);
+            if !part.is_empty() && part_tokens + tokens > input_budget {
+                let (text, part_usage) =
+                    Self::summarize_part(ai, tokenizer, summary.as_deref(), &part).await?;
+                summary = Some(text);
+                usage = add_usage(usage, part_usage);
+                part.clear();
+                part_tokens = 0;
+            }
+            part.push(json!({"role": turn.role, "content": content}));
+            part_tokens += tokens;
+
+            if i == turns.len() - 1 {
+                let (text, part_usage) =
+                    Self::summarize_part(ai, tokenizer, summary.as_deref(), &part).await?;
+                summary = Some(text);
+                usage = add_usage(usage, part_usage);
+            }
+        }
+
+        let summary = summary.ok_or_else(|| {
+            AppError::InternalServerError("There were no messages to summarize".to_string())
+        })?;
+        Ok((summary, usage))
+    }
+
+    /// Summarize one part of the turns with the model
+    async fn summarize_part(
+        ai: &AiService,
+        tokenizer: &Tokenizer,
+        previous: Option<&str>,
+        messages: &[Value],
+    ) -> AppResult<(String, Option<TokenUsage>)> {
+        let mut variables = json!({ "messages": messages });
+        if let Some(previous) = previous {
+            variables["summary"] = json!(previous);
+        }
+        let response = ai
+            .chat_with_template(SUMMARY_TEMPLATE, &variables)
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Summarization failed: {e}")))?;
+
+        let content = response.content();
+        let content = tokenizer.truncate(content.trim(), SUMMARY_MAX_TOKENS);
+        if content.is_empty() {
+            return Err(AppError::InternalServerError(
+                "Summarization returned nothing".to_string(),
+            ));
+        }
+        Ok((content.to_string(), response.usage))
+    }
+}
+
+/// Total of two optional usages
+fn add_usage(total: Option<TokenUsage>, usage: Option<TokenUsage>) -> Option<TokenUsage> {
+    match (total, usage) {
+        (Some(total), Some(usage)) => Some(total + usage),
+        (total, usage) => total.or(usage),
+    }
+}
+
+#[cfg(test)]
+#[path = "context_service_tests.rs"]
+mod context_service_tests;
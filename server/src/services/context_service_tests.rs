@@ -0,0 +1,336 @@
+//! Tests for context service
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::providers::{MockProvider, MockReply};
+    use crate::ai::{ChatMessage, ChatRole, ContextWindows};
+    use crate::errors::AppError;
+    use crate::models::{CreateConversationRequest, CreateMessageRequest};
+    use crate::services::context_service::ContextRequest;
+    use crate::services::{AiDataService, AiService, ContextService};
+    use sqlx::SqlitePool;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    /// Model given a small window for these tests
+    const MODEL: &str = "tiny";
+
+    /// 200 estimated tokens of content, 204 with the message overhead
+    const TURN_CHARS: usize = 800;
+
+    async fn create_test_user(pool: &SqlitePool) -> String {
+        let user_id = Uuid::new_v4().to_string();
+        let email = format!("test+{user_id}@example.com");
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, email, hashed_password, provider, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            user_id,
+            email,
+            "hashed_password",
+            "local",
+            now,
+            now
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to create test user");
+
+        user_id
+    }
+
+    fn tiny_ai(provider: Arc<MockProvider>) -> AiService {
+        AiService::with_provider(provider)
+            .expect("AI service")
+            .with_context_windows(ContextWindows::default().with_model(MODEL, 4_000))
+    }
+
+    fn request(conversation_id: &str, pinned: Vec<ChatMessage>) -> ContextRequest<'_> {
+        ContextRequest {
+            conversation_id,
+            message_id: None,
+            model: MODEL,
+            pinned,
+            reply_tokens: 2_000,
+        }
+    }
+
+    struct Conversation {
+        data: AiDataService,
+        user_id: String,
+        id: String,
+        turns: usize,
+    }
+
+    impl Conversation {
+        async fn create(pool: &SqlitePool) -> Self {
+            let data = AiDataService::new(pool.clone());
+            let user_id = create_test_user(pool).await;
+            let conversation = data
+                .create_conversation(
+                    &user_id,
+                    CreateConversationRequest {
+                        title: None,
+                        model: MODEL.to_string(),
+                        system_prompt: None,
+                    },
+                )
+                .await
+                .expect("conversation created");
+            Self {
+                data,
+                user_id,
+                id: conversation.id,
+                turns: 0,
+            }
+        }
+
+        /// Add turns alternating between user and assistant, each starting
+        /// with its number so it can be recognized in prompts
+        async fn add_turns(&mut self, count: usize) {
+            for _ in 0..count {
+                let role = if self.turns.is_multiple_of(2) {
+                    "user"
+                } else {
+                    "assistant"
+                };
+                let label = format!("turn {} ", self.turns);
+                let content = format!("{label}{}", "x".repeat(TURN_CHARS - label.len()));
+                self.data
+                    .add_message(
+                        &self.id,
+                        &self.user_id,
+                        CreateMessageRequest {
+                            role: role.to_string(),
+                            content,
+                            token_count: None,
+                            parent_message_id: None,
+                        },
+                    )
+                    .await
+                    .expect("message added");
+                self.turns += 1;
+            }
+        }
+    }
+
+    /// Numbers of the turns in a prompt
+    fn turn_numbers(messages: &[ChatMessage]) -> Vec<usize> {
+        messages
+            .iter()
+            .filter_map(|message| message.content.strip_prefix("turn "))
+            .filter_map(|rest| rest.split(' ').next()?.parse().ok())
+            .collect()
+    }
+
+    async fn stored_summaries(pool: &SqlitePool) -> usize {
+        let count = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM ai_messages WHERE json_extract(metadata, '$.summary') IS NOT NULL"
+        )
+        .fetch_one(pool)
+        .await
+        .expect("count");
+        usize::try_from(count).expect("count")
+    }
+
+    #[sqlx::test]
+    async fn test_short_history_is_sent_whole(pool: SqlitePool) {
+        let provider = Arc::new(MockProvider::new());
+        let ai = tiny_ai(provider.clone());
+        let service = ContextService::new(pool.clone());
+        let mut conversation = Conversation::create(&pool).await;
+        conversation.add_turns(3).await;
+
+        let system = ChatMessage {
+            role: ChatRole::System,
+            content: "Be brief.".to_string(),
+            function_call: None,
+        };
+        let built = service
+            .build_prompt(&ai, request(&conversation.id, vec![system]))
+            .await
+            .expect("prompt built");
+
+        assert_eq!(built.messages.len(), 4);
+        assert_eq!(built.messages[0].content, "Be brief.");
+        assert_eq!(turn_numbers(&built.messages), vec![0, 1, 2]);
+        assert!(matches!(built.messages[1].role, ChatRole::User));
+        assert!(matches!(built.messages[2].role, ChatRole::Assistant));
+        assert_eq!(built.prompt_tokens, 3 + 4 + 3 * 204);
+        assert!(built.summary_usage.is_none());
+        assert!(provider.requests().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_long_history_is_summarized(pool: SqlitePool) {
+        let provider = Arc::new(MockProvider::new());
+        let ai = tiny_ai(provider.clone());
+        let service = ContextService::new(pool.clone());
+        let mut conversation = Conversation::create(&pool).await;
+        conversation.add_turns(10).await;
+
+        provider.push_reply(MockReply::text("First summary."));
+        let built = service
+            .build_prompt(&ai, request(&conversation.id, Vec::new()))
+            .await
+            .expect("prompt built");
+
+        // The three oldest turns make room for the summary
+        assert_eq!(
+            built.messages[0].content,
+            "Summary of the earlier conversation:\nFirst summary."
+        );
+        assert_eq!(turn_numbers(&built.messages), (3..10).collect::<Vec<_>>());
+        assert!(built.prompt_tokens <= 2_000);
+        assert!(built.summary_usage.is_some());
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 1);
+        let summarized = &requests[0].messages[1].content;
+        assert!(summarized.contains("user: turn 0 "));
+        assert!(summarized.contains("user: turn 2 "));
+        assert!(!summarized.contains("turn 3 "));
+        assert_eq!(stored_summaries(&pool).await, 1);
+
+        // The stored summary is reused while the newer turns fit
+        conversation.add_turns(2).await;
+        let built = service
+            .build_prompt(&ai, request(&conversation.id, Vec::new()))
+            .await
+            .expect("prompt built");
+        assert!(built.messages[0].content.ends_with("First summary."));
+        assert_eq!(turn_numbers(&built.messages), (3..12).collect::<Vec<_>>());
+        assert!(built.summary_usage.is_none());
+        assert_eq!(provider.requests().len(), 1);
+
+        // Once they do not, they are summarized on top of it
+        conversation.add_turns(2).await;
+        provider.push_reply(MockReply::text("Second summary."));
+        let built = service
+            .build_prompt(&ai, request(&conversation.id, Vec::new()))
+            .await
+            .expect("prompt built");
+        assert!(built.messages[0].content.ends_with("Second summary."));
+        assert_eq!(turn_numbers(&built.messages), (7..14).collect::<Vec<_>>());
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 2);
+        let summarized = &requests[1].messages[1].content;
+        assert!(summarized.contains("First summary."));
+        assert!(summarized.contains("user: turn 6 "));
+        assert!(!summarized.contains("turn 2 "));
+        assert_eq!(stored_summaries(&pool).await, 2);
+    }
+
+    #[sqlx::test]
+    async fn test_prompt_follows_one_branch(pool: SqlitePool) {
+        let ai = tiny_ai(Arc::new(MockProvider::new()));
+        let service = ContextService::new(pool.clone());
+        let mut conversation = Conversation::create(&pool).await;
+        conversation.add_turns(3).await;
+        let stored = conversation
+            .data
+            .get_conversation_with_messages(&conversation.id, &conversation.user_id)
+            .await
+            .expect("conversation loaded");
+        let ids: Vec<String> = stored.messages.into_iter().map(|m| m.id).collect();
+
+        // Answering turn 0 again forks a branch, which is now the latest
+        let fork = conversation
+            .data
+            .add_message(
+                &conversation.id,
+                &conversation.user_id,
+                CreateMessageRequest {
+                    role: "assistant".to_string(),
+                    content: "turn 1b".to_string(),
+                    token_count: None,
+                    parent_message_id: Some(ids[0].clone()),
+                },
+            )
+            .await
+            .expect("message added");
+
+        let built = service
+            .build_prompt(&ai, request(&conversation.id, Vec::new()))
+            .await
+            .expect("prompt built");
+        assert_eq!(turn_numbers(&built.messages), vec![0]);
+        assert_eq!(built.messages[1].content, "turn 1b");
+
+        let mut original = request(&conversation.id, Vec::new());
+        original.message_id = Some(&ids[2]);
+        let built = service
+            .build_prompt(&ai, original)
+            .await
+            .expect("prompt built");
+        assert_eq!(turn_numbers(&built.messages), vec![0, 1, 2]);
+        assert!(built.messages.iter().all(|m| m.content != fork.content));
+
+        let mut missing = request(&conversation.id, Vec::new());
+        missing.message_id = Some("nonexistent");
+        assert!(matches!(
+            service.build_prompt(&ai, missing).await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[sqlx::test]
+    async fn test_failed_summary_leaves_older_turns_out(pool: SqlitePool) {
+        let provider = Arc::new(MockProvider::new());
+        let ai = tiny_ai(provider.clone());
+        let service = ContextService::new(pool.clone());
+        let mut conversation = Conversation::create(&pool).await;
+        conversation.add_turns(10).await;
+
+        provider.push_reply(MockReply::error(500, "provider down"));
+        let built = service
+            .build_prompt(&ai, request(&conversation.id, Vec::new()))
+            .await
+            .expect("prompt built");
+
+        assert_eq!(turn_numbers(&built.messages), (3..10).collect::<Vec<_>>());
+        assert_eq!(built.messages.len(), 7);
+        assert!(built.summary_usage.is_none());
+        assert_eq!(stored_summaries(&pool).await, 0);
+    }
+
+    #[sqlx::test]
+    async fn test_oversized_request_is_rejected(pool: SqlitePool) {
+        let ai = tiny_ai(Arc::new(MockProvider::new()));
+        let service = ContextService::new(pool.clone());
+        let mut conversation = Conversation::create(&pool).await;
+        conversation.add_turns(1).await;
+
+        let huge = "x".repeat(4 * 4_000);
+        conversation
+            .data
+            .add_message(
+                &conversation.id,
+                &conversation.user_id,
+                CreateMessageRequest {
+                    role: "user".to_string(),
+                    content: huge.clone(),
+                    token_count: None,
+                    parent_message_id: None,
+                },
+            )
+            .await
+            .expect("message added");
+        let result = service
+            .build_prompt(&ai, request(&conversation.id, Vec::new()))
+            .await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+
+        let pinned = vec![ChatMessage {
+            role: ChatRole::System,
+            content: huge,
+            function_call: None,
+        }];
+        let result = service
+            .build_prompt(&ai, request(&conversation.id, pinned))
+            .await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+    }
+}
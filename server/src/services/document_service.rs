@@ -0,0 +1,454 @@
+//! Document service for retrieval-augmented chat
+//!
+//! Manages the `documents` and `document_chunks` tables. Uploaded text is split
+//! into overlapping chunks that the `document_chunks_fts` full-text index keeps
+//! searchable. Retrieval ranks a question's chunks by BM25 and, when enabled,
+//! by embedding similarity as well, merging the two rankings with reciprocal
+//! rank fusion.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+use uuid::Uuid;
+
+use crate::{
+    errors::{AppError, AppResult},
+    models::{Document, RetrievedChunk},
+    services::{AiService, EmbeddingService, board_service::db_timestamp},
+};
+
+/// Characters in a chunk, about 300 tokens
+const CHUNK_CHARS: usize = 1200;
+
+/// Characters a chunk repeats from the end of the previous one, so a passage
+/// split across chunks can still be found whole
+const CHUNK_OVERLAP: usize = 200;
+
+/// Maximum length of a document name
+const MAX_NAME_LEN: usize = 255;
+
+/// Chunks retrieved when the request does not say how many
+const DEFAULT_TOP_K: u32 = 4;
+
+/// Most chunks a request can retrieve
+const MAX_TOP_K: u32 = 20;
+
+/// Most documents one request can refer to
+const MAX_DOCUMENTS_PER_REQUEST: usize = 20;
+
+/// Each ranking contributes this many times `top_k` candidates to the fusion
+const CANDIDATE_FACTOR: usize = 4;
+
+/// Reciprocal rank fusion constant; damps the advantage of the very top ranks
+const RRF_K: f32 = 60.0;
+
+/// Most search terms taken from a question
+const MAX_QUERY_TERMS: usize = 32;
+
+/// A chunk as loaded for retrieval: ID, document ID, index and content
+type ChunkRow = (i64, String, i64, String);
+
+pub struct DocumentService {
+    db: SqlitePool,
+    use_embeddings: bool,
+}
+
+fn document_not_found() -> AppError {
+    AppError::NotFound("Document not found".to_string())
+}
+
+/// Split text into overlapping chunks of about `CHUNK_CHARS` characters
+///
+/// Chunks end at a paragraph break where possible, then at the end of a
+/// sentence, then between words.
+fn chunk_text(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < chars.len() {
+        let mut end = (start + CHUNK_CHARS).min(chars.len());
+        if end < chars.len() {
+            end = break_point(&chars[start..end]).map_or(end, |offset| start + offset);
+        }
+
+        let chunk: String = chars[start..end].iter().collect();
+        let chunk = chunk.trim();
+        if !chunk.is_empty() {
+            chunks.push(chunk.to_string());
+        }
+        if end == chars.len() {
+            break;
+        }
+
+        // Start the next chunk at a word within the overlap
+        let mut next = end.saturating_sub(CHUNK_OVERLAP).max(start + 1);
+        while next < end && !chars[next - 1].is_whitespace() {
+            next += 1;
+        }
+        start = next;
+    }
+
+    chunks
+}
+
+/// Where to end a chunk within the second half of `window`, just after a
+/// paragraph break, sentence end or space
+fn break_point(window: &[char]) -> Option<usize> {
+    let last = |matches: &dyn Fn(usize) -> bool| {
+        (window.len() / 2..window.len())
+            .rev()
+            .find(|&i| i > 0 && matches(i))
+    };
+
+    last(&|i| window[i] == '\n' && window[i - 1] == '\n')
+        .or_else(|| {
+            last(&|i| window[i].is_whitespace() && matches!(window[i - 1], '.' | '!' | '?'))
+        })
+        .or_else(|| last(&|i| window[i].is_whitespace()))
+        .map(|i| i + 1)
+}
+
+/// Turn a question into an FTS5 query that matches any of its words
+///
+/// Each word is quoted so characters with a meaning in FTS5 syntax are taken
+/// literally. Returns `None` if the question has no words to search for.
+fn fts_query(text: &str) -> Option<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+    {
+        let term = format!("\"{}\"", word.to_lowercase());
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+        if terms.len() == MAX_QUERY_TERMS {
+            break;
+        }
+    }
+    (!terms.is_empty()).then(|| terms.join(" OR "))
+}
+
+/// Add a ranking's reciprocal rank fusion scores to `fused`
+fn fuse(fused: &mut HashMap<i64, (ChunkRow, f32)>, ranking: Vec<ChunkRow>) {
+    for (rank, row) in ranking.into_iter().enumerate() {
+        #[allow(clippy::cast_precision_loss)]
+        let score = 1.0 / (RRF_K + rank as f32 + 1.0);
+        fused.entry(row.0).or_insert((row, 0.0)).1 += score;
+    }
+}
+
+/// Start a query over the chunks of the given documents
+fn chunks_of<'a>(select: &str, document_ids: &'a [String]) -> QueryBuilder<'a, Sqlite> {
+    let mut builder = QueryBuilder::new(select);
+    builder.push(" c.document_id IN (");
+    let mut ids = builder.separated(", ");
+    for id in document_ids {
+        ids.push_bind(id);
+    }
+    ids.push_unseparated(")");
+    builder
+}
+
+impl DocumentService {
+    /// Create the service; `AI_RAG_EMBEDDINGS=true` adds embedding similarity
+    /// to full-text retrieval
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        let use_embeddings = std::env::var("AI_RAG_EMBEDDINGS")
+            .is_ok_and(|value| value.eq_ignore_ascii_case("true") || value == "1");
+        Self { db, use_embeddings }
+    }
+
+    /// Whether retrieval also ranks chunks by embedding similarity
+    #[must_use]
+    pub fn with_embeddings(mut self, use_embeddings: bool) -> Self {
+        self.use_embeddings = use_embeddings;
+        self
+    }
+
+    /// Store the text of an uploaded file as a document
+    ///
+    /// Uploading the same text again returns the document already stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ValidationError` if the name or text is empty, or
+    /// `AppError` if the database operation fails
+    pub async fn create_document(
+        &self,
+        user_id: &str,
+        name: &str,
+        content_type: Option<&str>,
+        size: usize,
+        text: &str,
+    ) -> AppResult<Document> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(AppError::ValidationError(
+                "Document name is required and must be at most 255 characters.".to_string(),
+            ));
+        }
+        let content_hash = format!("{:x}", Sha256::digest(text.as_bytes()));
+        if let Some(existing) = sqlx::query_scalar!(
+            r#"SELECT id as "id!" FROM documents WHERE user_id = ?1 AND content_hash = ?2"#,
+            user_id,
+            content_hash
+        )
+        .fetch_optional(&self.db)
+        .await?
+        {
+            return self.get_document(user_id, &existing).await;
+        }
+
+        let chunks = chunk_text(text);
+        if chunks.is_empty() {
+            return Err(AppError::ValidationError(
+                "Document has no text to index.".to_string(),
+            ));
+        }
+
+        let document_id = format!("doc_{}", Uuid::new_v4());
+        let size = i64::try_from(size).unwrap_or(i64::MAX);
+        #[allow(clippy::cast_possible_wrap)]
+        let chunk_count = chunks.len() as i64;
+        let now = db_timestamp(Utc::now());
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO documents (id, user_id, name, content_type, size, content_hash, chunk_count, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            document_id,
+            user_id,
+            name,
+            content_type,
+            size,
+            content_hash,
+            chunk_count,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        for (index, content) in chunks.iter().enumerate() {
+            #[allow(clippy::cast_possible_wrap)]
+            let index = index as i64;
+            sqlx::query!(
+                "INSERT INTO document_chunks (document_id, chunk_index, content) VALUES (?1, ?2, ?3)",
+                document_id,
+                index,
+                content
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        tracing::info!(
+            "User {} stored document {} in {} chunks",
+            user_id,
+            document_id,
+            chunk_count
+        );
+        self.get_document(user_id, &document_id).await
+    }
+
+    /// List a user's documents, most recently uploaded first
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn list_documents(&self, user_id: &str) -> AppResult<Vec<Document>> {
+        Ok(sqlx::query_as!(
+            Document,
+            r#"
+            SELECT id as "id!", user_id, name, content_type, size, content_hash, chunk_count,
+                   created_at as "created_at: DateTime<Utc>"
+            FROM documents
+            WHERE user_id = ?1
+            ORDER BY created_at DESC, id DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?)
+    }
+
+    /// Get one of a user's documents
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user has no such document, or
+    /// `AppError` if the database query fails
+    pub async fn get_document(&self, user_id: &str, document_id: &str) -> AppResult<Document> {
+        sqlx::query_as!(
+            Document,
+            r#"
+            SELECT id as "id!", user_id, name, content_type, size, content_hash, chunk_count,
+                   created_at as "created_at: DateTime<Utc>"
+            FROM documents
+            WHERE id = ?1 AND user_id = ?2
+            "#,
+            document_id,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(document_not_found)
+    }
+
+    /// Delete one of a user's documents with its chunks
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user has no such document, or
+    /// `AppError` if the database operation fails
+    pub async fn delete_document(&self, user_id: &str, document_id: &str) -> AppResult<()> {
+        let result = sqlx::query!(
+            "DELETE FROM documents WHERE id = ?1 AND user_id = ?2",
+            document_id,
+            user_id
+        )
+        .execute(&self.db)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(document_not_found());
+        }
+        Ok(())
+    }
+
+    /// Retrieve the chunks of the given documents most relevant to a question
+    ///
+    /// If nothing in the documents matches the question, such as when it asks
+    /// for a summary, the first chunks of the documents are returned instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user does not have one of the
+    /// documents, `AppError::ValidationError` if too many are given,
+    /// `AppError::BadRequest` if the texts cannot be embedded, or `AppError`
+    /// if the database operation fails
+    pub async fn retrieve(
+        &self,
+        ai: &AiService,
+        embeddings: &EmbeddingService,
+        user_id: &str,
+        document_ids: &[String],
+        query: &str,
+        top_k: Option<u32>,
+    ) -> AppResult<Vec<RetrievedChunk>> {
+        let names = self.document_names(user_id, document_ids).await?;
+        let top_k = top_k.unwrap_or(DEFAULT_TOP_K).clamp(1, MAX_TOP_K) as usize;
+        let candidates = i64::try_from(top_k * CANDIDATE_FACTOR).unwrap_or(i64::MAX);
+        let mut fused = HashMap::new();
+
+        if let Some(fts) = fts_query(query) {
+            let mut builder = chunks_of(
+                "SELECT c.id, c.document_id, c.chunk_index, c.content \
+                 FROM document_chunks_fts f JOIN document_chunks c ON c.id = f.rowid WHERE",
+                document_ids,
+            );
+            builder.push(" AND document_chunks_fts MATCH ");
+            builder.push_bind(fts);
+            builder.push(" ORDER BY bm25(document_chunks_fts) LIMIT ");
+            builder.push_bind(candidates);
+            let ranking: Vec<ChunkRow> = builder.build_query_as().fetch_all(&self.db).await?;
+            fuse(&mut fused, ranking);
+        }
+
+        if self.use_embeddings {
+            let chunks: Vec<ChunkRow> = chunks_of(
+                "SELECT c.id, c.document_id, c.chunk_index, c.content FROM document_chunks c WHERE",
+                document_ids,
+            )
+            .build_query_as()
+            .fetch_all(&self.db)
+            .await?;
+            let texts: Vec<(i64, String)> = chunks
+                .iter()
+                .map(|(id, _, _, content)| (*id, content.clone()))
+                .collect();
+            let scores = embeddings.score_document_chunks(ai, &texts, query).await?;
+
+            let mut ranked: Vec<(f32, ChunkRow)> = scores.into_iter().zip(chunks).collect();
+            ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+            let ranking = ranked
+                .into_iter()
+                .filter(|(score, _)| *score > 0.0)
+                .take(top_k * CANDIDATE_FACTOR)
+                .map(|(_, row)| row)
+                .collect();
+            fuse(&mut fused, ranking);
+        }
+
+        let mut ranked: Vec<(ChunkRow, f32)> = fused.into_values().collect();
+        if ranked.is_empty() {
+            let mut builder = chunks_of(
+                "SELECT c.id, c.document_id, c.chunk_index, c.content FROM document_chunks c WHERE",
+                document_ids,
+            );
+            builder.push(" ORDER BY c.chunk_index, c.document_id LIMIT ");
+            builder.push_bind(i64::try_from(top_k).unwrap_or(i64::MAX));
+            let leading: Vec<ChunkRow> = builder.build_query_as().fetch_all(&self.db).await?;
+            ranked = leading.into_iter().map(|row| (row, 0.0)).collect();
+        }
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.0.cmp(&b.0.0)));
+
+        Ok(ranked
+            .into_iter()
+            .take(top_k)
+            .map(
+                |((chunk_id, document_id, chunk_index, content), score)| RetrievedChunk {
+                    chunk_id,
+                    document_name: names.get(&document_id).cloned().unwrap_or_default(),
+                    document_id,
+                    chunk_index,
+                    content,
+                    score,
+                },
+            )
+            .collect())
+    }
+
+    /// Names of the given documents, checking the user has all of them
+    async fn document_names(
+        &self,
+        user_id: &str,
+        document_ids: &[String],
+    ) -> AppResult<HashMap<String, String>> {
+        if document_ids.is_empty() || document_ids.len() > MAX_DOCUMENTS_PER_REQUEST {
+            return Err(AppError::ValidationError(
+                "Between 1 and 20 documents can be referenced.".to_string(),
+            ));
+        }
+
+        let mut builder: QueryBuilder<Sqlite> =
+            QueryBuilder::new("SELECT id, name FROM documents WHERE user_id = ");
+        builder.push_bind(user_id);
+        builder.push(" AND id IN (");
+        let mut ids = builder.separated(", ");
+        for id in document_ids {
+            ids.push_bind(id);
+        }
+        ids.push_unseparated(")");
+        let names: HashMap<String, String> = builder
+            .build_query_as::<(String, String)>()
+            .fetch_all(&self.db)
+            .await?
+            .into_iter()
+            .collect();
+
+        if document_ids.iter().any(|id| !names.contains_key(id)) {
+            return Err(document_not_found());
+        }
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+#[path = "document_service_tests.rs"]
+mod document_service_tests;
@@ -0,0 +1,197 @@
+//! Tests for document service
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::providers::MockProvider;
+    use crate::errors::AppError;
+    use crate::services::document_service::{CHUNK_CHARS, chunk_text, fts_query};
+    use crate::services::{AiService, DocumentService, EmbeddingService};
+    use sqlx::SqlitePool;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    async fn create_test_user(pool: &SqlitePool) -> String {
+        let user_id = Uuid::new_v4().to_string();
+        let email = format!("test+{user_id}@example.com");
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, email, hashed_password, provider, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            user_id,
+            email,
+            "hashed_password",
+            "local",
+            now,
+            now
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to create test user");
+
+        user_id
+    }
+
+    fn local_ai() -> AiService {
+        AiService::with_provider(Arc::new(MockProvider::new())).expect("AI service")
+    }
+
+    /// A handbook long enough to split into several chunks, with one
+    /// paragraph about vacation
+    fn handbook() -> String {
+        let filler = "Our office is open on weekdays and the kitchen is stocked with coffee. ";
+        let mut text = filler.repeat(30);
+        text.push_str("\n\nEmployees receive twenty five days of paid vacation each year.\n\n");
+        text.push_str(&filler.repeat(30));
+        text
+    }
+
+    #[test]
+    fn test_chunk_text_overlaps_and_breaks_at_sentences() {
+        let chunks = chunk_text(&handbook());
+        assert!(chunks.len() > 2);
+        for chunk in &chunks {
+            assert!(chunk.chars().count() <= CHUNK_CHARS);
+            assert!(chunk.ends_with('.'));
+        }
+        // Each chunk repeats the end of the one before it
+        let tail = &chunks[0][chunks[0].len() - 40..];
+        assert!(chunks[1].contains(tail));
+
+        assert_eq!(chunk_text("Short note."), vec!["Short note.".to_string()]);
+        assert!(chunk_text("  \n ").is_empty());
+    }
+
+    #[test]
+    fn test_fts_query_quotes_words() {
+        assert_eq!(
+            fts_query("How many vacation days? (NEAR \"x\")").as_deref(),
+            Some("\"how\" OR \"many\" OR \"vacation\" OR \"days\" OR \"near\"")
+        );
+        assert_eq!(fts_query("? !"), None);
+    }
+
+    #[sqlx::test]
+    async fn test_create_document_chunks_and_dedupes(pool: SqlitePool) {
+        let service = DocumentService::new(pool.clone());
+        let user_id = create_test_user(&pool).await;
+        let other_id = create_test_user(&pool).await;
+        let text = handbook();
+
+        let document = service
+            .create_document(
+                &user_id,
+                "handbook.txt",
+                Some("text/plain"),
+                text.len(),
+                &text,
+            )
+            .await
+            .expect("document created");
+        assert!(document.id.starts_with("doc_"));
+        assert_eq!(
+            usize::try_from(document.chunk_count).expect("count"),
+            chunk_text(&text).len()
+        );
+
+        // The same text uploaded again is the same document
+        let again = service
+            .create_document(&user_id, "copy.txt", None, text.len(), &text)
+            .await
+            .expect("document created");
+        assert_eq!(again.id, document.id);
+        assert_eq!(again.name, "handbook.txt");
+
+        // But not for another user
+        let theirs = service
+            .create_document(&other_id, "handbook.txt", None, text.len(), &text)
+            .await
+            .expect("document created");
+        assert_ne!(theirs.id, document.id);
+
+        let empty = service
+            .create_document(&user_id, "empty.txt", None, 0, "   ")
+            .await;
+        assert!(matches!(empty, Err(AppError::ValidationError(_))));
+
+        let listed = service.list_documents(&user_id).await.expect("listed");
+        assert_eq!(listed.len(), 1);
+        assert!(matches!(
+            service.get_document(&other_id, &document.id).await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[sqlx::test]
+    async fn test_retrieve_ranks_relevant_chunks(pool: SqlitePool) {
+        let service = DocumentService::new(pool.clone()).with_embeddings(true);
+        let embeddings = EmbeddingService::new(pool.clone());
+        let ai = local_ai();
+        let user_id = create_test_user(&pool).await;
+        let other_id = create_test_user(&pool).await;
+        let text = handbook();
+        let document = service
+            .create_document(&user_id, "handbook.txt", None, text.len(), &text)
+            .await
+            .expect("document created");
+        let ids = vec![document.id.clone()];
+
+        let chunks = service
+            .retrieve(
+                &ai,
+                &embeddings,
+                &user_id,
+                &ids,
+                "How much vacation do I get?",
+                Some(1),
+            )
+            .await
+            .expect("retrieved");
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].document_name, "handbook.txt");
+        assert!(chunks[0].content.contains("paid vacation"));
+
+        // A question matching nothing falls back to the start of the document
+        let chunks = service
+            .retrieve(&ai, &embeddings, &user_id, &ids, "", Some(2))
+            .await
+            .expect("retrieved");
+        assert_eq!(
+            chunks.iter().map(|c| c.chunk_index).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+
+        let outsider = service
+            .retrieve(&ai, &embeddings, &other_id, &ids, "vacation", None)
+            .await;
+        assert!(matches!(outsider, Err(AppError::NotFound(_))));
+        let none = service
+            .retrieve(&ai, &embeddings, &user_id, &[], "vacation", None)
+            .await;
+        assert!(matches!(none, Err(AppError::ValidationError(_))));
+
+        // Deleting the document deletes its chunks and their embeddings
+        let stored = sqlx::query_scalar!("SELECT COUNT(*) FROM embeddings")
+            .fetch_one(&pool)
+            .await
+            .expect("count");
+        assert_eq!(stored, document.chunk_count);
+        service
+            .delete_document(&user_id, &document.id)
+            .await
+            .expect("document deleted");
+        for table in ["document_chunks", "document_chunks_fts", "embeddings"] {
+            let remaining: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table}"))
+                .fetch_one(&pool)
+                .await
+                .expect("count");
+            assert_eq!(remaining, 0, "{table} is empty");
+        }
+        assert!(matches!(
+            service.delete_document(&user_id, &document.id).await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
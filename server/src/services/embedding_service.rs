@@ -0,0 +1,416 @@
+//! Embedding service
+//!
+//! Keeps vectors for issues, conversations and document chunks in the
+//! `embeddings` table and searches them by cosine similarity. Vectors are
+//! computed lazily: a search embeds whatever has no vector yet for the
+//! configured model, or whose text has changed since it was embedded, which is
+//! detected by a hash of the text.
+
+use sha2::{Digest, Sha256};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+
+use crate::{
+    ai::{
+        embeddings::{cosine_similarity, vector_from_bytes, vector_to_bytes},
+        models::analysis::{DuplicateCheckResult, DuplicateIssue},
+    },
+    errors::{AppError, AppResult},
+    models::{DuplicateCheckRequest, SimilarConversation},
+    services::{
+        AiService,
+        board_service::{db_timestamp, require_member},
+    },
+};
+
+/// Issues at least this similar to a draft are reported as likely duplicates
+pub const DUPLICATE_THRESHOLD: f32 = 0.4;
+
+/// Conversations less similar than this to a search query are not results
+const SEARCH_THRESHOLD: f32 = 0.1;
+
+/// Duplicates returned when the request does not set a limit
+const DEFAULT_DUPLICATE_LIMIT: u32 = 5;
+
+/// Conversations returned when the query does not set a limit
+const DEFAULT_SEARCH_LIMIT: u32 = 10;
+
+/// Most results a request can ask for
+const MAX_RESULTS: u32 = 50;
+
+/// Maximum length of a search query
+const MAX_QUERY_LEN: usize = 1000;
+
+/// Characters of a conversation that are embedded; the start of a
+/// conversation usually says what it is about
+const MAX_CONVERSATION_TEXT_LEN: usize = 8000;
+
+/// Texts sent to the provider in one embedding request
+const EMBED_BATCH_SIZE: usize = 64;
+
+/// Kinds of entity that have embeddings
+#[derive(Debug, Clone, Copy)]
+enum EntityType {
+    Issue,
+    Conversation,
+    DocumentChunk,
+}
+
+impl EntityType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Issue => "issue",
+            Self::Conversation => "conversation",
+            Self::DocumentChunk => "document_chunk",
+        }
+    }
+}
+
+/// A text to embed, identified by the entity it belongs to
+struct EntityText {
+    id: String,
+    text: String,
+}
+
+pub struct EmbeddingService {
+    db: SqlitePool,
+}
+
+fn embedding_failed(e: &impl std::fmt::Display) -> AppError {
+    AppError::BadRequest(format!("Embedding failed: {e}"))
+}
+
+fn content_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+fn issue_text(title: &str, description: Option<&str>) -> String {
+    match description.map(str::trim).filter(|d| !d.is_empty()) {
+        Some(description) => format!("{}\n\n{description}", title.trim()),
+        None => title.trim().to_string(),
+    }
+}
+
+fn clamp_limit(limit: Option<u32>, default: u32) -> usize {
+    limit.unwrap_or(default).clamp(1, MAX_RESULTS) as usize
+}
+
+/// Truncate a text to at most `max` characters
+fn truncate_chars(text: &mut String, max: usize) {
+    if let Some((index, _)) = text.char_indices().nth(max) {
+        text.truncate(index);
+    }
+}
+
+impl EmbeddingService {
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Find issues on a board that a new issue may duplicate
+    ///
+    /// Issues are ranked by the cosine similarity of their title and
+    /// description to the request's. The uniqueness score is one minus the
+    /// similarity of the closest issue, so 1.0 means nothing on the board is
+    /// alike and 0.0 means an identical issue exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user is not a member of the board,
+    /// `AppError::ValidationError` if the title is empty, `AppError::BadRequest`
+    /// if the texts cannot be embedded, or `AppError` if the database
+    /// operation fails
+    pub async fn check_duplicates(
+        &self,
+        ai: &AiService,
+        user_id: &str,
+        board_id: &str,
+        request: &DuplicateCheckRequest,
+    ) -> AppResult<DuplicateCheckResult> {
+        require_member(&self.db, board_id, user_id).await?;
+        if request.title.trim().is_empty() {
+            return Err(AppError::ValidationError(
+                "A title is required to check for duplicates.".to_string(),
+            ));
+        }
+
+        let issues = sqlx::query!(
+            r#"
+            SELECT id as "id!", title, description, status
+            FROM issues
+            WHERE board_id = ?1 AND id != COALESCE(?2, '')
+            ORDER BY created_at
+            "#,
+            board_id,
+            request.exclude_issue_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let documents: Vec<EntityText> = issues
+            .iter()
+            .map(|issue| EntityText {
+                id: issue.id.clone(),
+                text: issue_text(&issue.title, issue.description.as_deref()),
+            })
+            .collect();
+        let vectors = self
+            .ensure_embeddings(ai, EntityType::Issue, &documents)
+            .await?;
+        let query = self
+            .embed_query(
+                ai,
+                issue_text(&request.title, request.description.as_deref()),
+            )
+            .await?;
+
+        let mut ranked: Vec<(f32, usize)> = vectors
+            .iter()
+            .enumerate()
+            .map(|(index, vector)| (cosine_similarity(&query, vector), index))
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let closest = ranked.first().map_or(0.0, |(score, _)| score.max(0.0));
+        let duplicates = ranked
+            .into_iter()
+            .filter(|(score, _)| *score >= DUPLICATE_THRESHOLD)
+            .take(clamp_limit(request.limit, DEFAULT_DUPLICATE_LIMIT))
+            .map(|(score, index)| DuplicateIssue {
+                issue_id: issues[index].id.clone(),
+                title: issues[index].title.clone(),
+                similarity_score: score,
+                status: issues[index].status.clone(),
+            })
+            .collect();
+
+        Ok(DuplicateCheckResult {
+            duplicates,
+            uniqueness_score: (1.0 - closest).clamp(0.0, 1.0),
+        })
+    }
+
+    /// Search the user's conversations for ones about the query
+    ///
+    /// Archived conversations are left out. A conversation's title and
+    /// messages are embedded together.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ValidationError` if the query is empty or too long,
+    /// `AppError::BadRequest` if the texts cannot be embedded, or `AppError`
+    /// if the database operation fails
+    pub async fn search_conversations(
+        &self,
+        ai: &AiService,
+        user_id: &str,
+        query: &str,
+        limit: Option<u32>,
+    ) -> AppResult<Vec<SimilarConversation>> {
+        let query = query.trim();
+        if query.is_empty() || query.chars().count() > MAX_QUERY_LEN {
+            return Err(AppError::ValidationError(
+                "Search query is required and must be at most 1000 characters.".to_string(),
+            ));
+        }
+
+        let conversations = sqlx::query!(
+            r#"
+            SELECT id as "id!", title
+            FROM ai_conversations
+            WHERE user_id = ?1 AND archived_at IS NULL
+            ORDER BY created_at
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+        let messages = sqlx::query!(
+            r#"
+            SELECT m.conversation_id, m.content
+            FROM ai_messages m
+            JOIN ai_conversations c ON c.id = m.conversation_id
+            WHERE c.user_id = ?1 AND c.archived_at IS NULL AND m.role != 'system'
+            ORDER BY m.created_at, m.id
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut documents: Vec<EntityText> = conversations
+            .iter()
+            .map(|conversation| EntityText {
+                id: conversation.id.clone(),
+                text: conversation.title.clone().unwrap_or_default(),
+            })
+            .collect();
+        for message in &messages {
+            if let Some(document) = documents
+                .iter_mut()
+                .find(|document| document.id == message.conversation_id)
+                && document.text.len() < MAX_CONVERSATION_TEXT_LEN
+            {
+                document.text.push_str("\n\n");
+                document.text.push_str(&message.content);
+            }
+        }
+        for document in &mut documents {
+            truncate_chars(&mut document.text, MAX_CONVERSATION_TEXT_LEN);
+        }
+
+        let vectors = self
+            .ensure_embeddings(ai, EntityType::Conversation, &documents)
+            .await?;
+        let query = self.embed_query(ai, query.to_string()).await?;
+
+        let mut ranked: Vec<(f32, usize)> = vectors
+            .iter()
+            .enumerate()
+            .map(|(index, vector)| (cosine_similarity(&query, vector), index))
+            .filter(|(score, _)| *score >= SEARCH_THRESHOLD)
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        Ok(ranked
+            .into_iter()
+            .take(clamp_limit(limit, DEFAULT_SEARCH_LIMIT))
+            .map(|(score, index)| SimilarConversation {
+                conversation_id: conversations[index].id.clone(),
+                title: conversations[index].title.clone(),
+                similarity_score: score,
+            })
+            .collect())
+    }
+
+    /// Cosine similarity of each document chunk to a question, in order
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::BadRequest` if the texts cannot be embedded, or
+    /// `AppError` if the database operation fails
+    pub async fn score_document_chunks(
+        &self,
+        ai: &AiService,
+        chunks: &[(i64, String)],
+        query: &str,
+    ) -> AppResult<Vec<f32>> {
+        let texts: Vec<EntityText> = chunks
+            .iter()
+            .map(|(id, content)| EntityText {
+                id: id.to_string(),
+                text: content.clone(),
+            })
+            .collect();
+        let vectors = self
+            .ensure_embeddings(ai, EntityType::DocumentChunk, &texts)
+            .await?;
+        let query = self.embed_query(ai, query.to_string()).await?;
+        Ok(vectors
+            .iter()
+            .map(|vector| cosine_similarity(&query, vector))
+            .collect())
+    }
+
+    async fn embed_query(&self, ai: &AiService, text: String) -> AppResult<Vec<f32>> {
+        ai.embed(vec![text])
+            .await
+            .map_err(|e| embedding_failed(&e))?
+            .embeddings
+            .pop()
+            .ok_or_else(|| embedding_failed(&"no embedding returned"))
+    }
+
+    /// Vectors for the documents, in order, embedding any that are missing or
+    /// out of date and storing them for next time
+    async fn ensure_embeddings(
+        &self,
+        ai: &AiService,
+        entity_type: EntityType,
+        documents: &[EntityText],
+    ) -> AppResult<Vec<Vec<f32>>> {
+        if documents.is_empty() {
+            return Ok(Vec::new());
+        }
+        let model = ai.embedding_model().to_string();
+
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "SELECT entity_id, content_hash, vector FROM embeddings WHERE entity_type = ",
+        );
+        builder.push_bind(entity_type.as_str());
+        builder.push(" AND model = ");
+        builder.push_bind(&model);
+        builder.push(" AND entity_id IN (");
+        let mut ids = builder.separated(", ");
+        for document in documents {
+            ids.push_bind(&document.id);
+        }
+        ids.push_unseparated(")");
+        let stored: Vec<(String, String, Vec<u8>)> =
+            builder.build_query_as().fetch_all(&self.db).await?;
+
+        let hashes: Vec<String> = documents
+            .iter()
+            .map(|document| content_hash(&document.text))
+            .collect();
+        let mut vectors: Vec<Option<Vec<f32>>> = documents
+            .iter()
+            .zip(&hashes)
+            .map(|(document, hash)| {
+                stored
+                    .iter()
+                    .find(|(id, stored_hash, _)| *id == document.id && stored_hash == hash)
+                    .and_then(|(_, _, bytes)| vector_from_bytes(bytes))
+            })
+            .collect();
+
+        let stale: Vec<usize> = (0..documents.len())
+            .filter(|index| vectors[*index].is_none())
+            .collect();
+        for batch in stale.chunks(EMBED_BATCH_SIZE) {
+            let input = batch
+                .iter()
+                .map(|index| documents[*index].text.clone())
+                .collect();
+            let response = ai.embed(input).await.map_err(|e| embedding_failed(&e))?;
+
+            let now = db_timestamp(chrono::Utc::now());
+            let mut tx = self.db.begin().await?;
+            for (index, vector) in batch.iter().zip(response.embeddings) {
+                let bytes = vector_to_bytes(&vector);
+                #[allow(clippy::cast_possible_wrap)]
+                let dimensions = vector.len() as i64;
+                let entity = entity_type.as_str();
+                sqlx::query!(
+                    r#"
+                    INSERT INTO embeddings
+                        (entity_type, entity_id, model, dimensions, vector, content_hash, created_at, updated_at)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+                    ON CONFLICT (entity_type, entity_id, model) DO UPDATE SET
+                        dimensions = excluded.dimensions,
+                        vector = excluded.vector,
+                        content_hash = excluded.content_hash,
+                        updated_at = excluded.updated_at
+                    "#,
+                    entity,
+                    documents[*index].id,
+                    model,
+                    dimensions,
+                    bytes,
+                    hashes[*index],
+                    now
+                )
+                .execute(&mut *tx)
+                .await?;
+                vectors[*index] = Some(vector);
+            }
+            tx.commit().await?;
+        }
+
+        Ok(vectors.into_iter().map(Option::unwrap_or_default).collect())
+    }
+}
+
+#[cfg(test)]
+#[path = "embedding_service_tests.rs"]
+mod embedding_service_tests;
@@ -0,0 +1,305 @@
+//! Tests for embedding service
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::embeddings::LOCAL_EMBEDDING_MODEL;
+    use crate::ai::providers::MockProvider;
+    use crate::errors::AppError;
+    use crate::models::{
+        CreateBoardRequest, CreateConversationRequest, CreateIssueRequest, CreateMessageRequest,
+        DuplicateCheckRequest, IssuePriority, IssueType, UpdateIssueRequest,
+    };
+    use crate::services::{AiDataService, AiService, BoardService, EmbeddingService, IssueService};
+    use sqlx::SqlitePool;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    async fn create_test_user(pool: &SqlitePool) -> String {
+        let user_id = Uuid::new_v4().to_string();
+        let email = format!("test+{user_id}@example.com");
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, email, hashed_password, provider, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            user_id,
+            email,
+            "hashed_password",
+            "local",
+            now,
+            now
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to create test user");
+
+        user_id
+    }
+
+    async fn create_test_board(pool: &SqlitePool, owner_id: &str) -> String {
+        BoardService::new(pool.clone())
+            .create_board(
+                owner_id,
+                &CreateBoardRequest {
+                    name: "Roadmap".to_string(),
+                    description: None,
+                },
+            )
+            .await
+            .expect("board created")
+            .board
+            .id
+    }
+
+    async fn create_test_issue(
+        issues: &IssueService,
+        user_id: &str,
+        board_id: &str,
+        title: &str,
+        description: &str,
+    ) -> String {
+        issues
+            .create_issue(
+                user_id,
+                board_id,
+                &CreateIssueRequest {
+                    title: title.to_string(),
+                    description: Some(description.to_string()),
+                    issue_type: IssueType::Feature,
+                    priority: IssuePriority::Medium,
+                    size_estimate: None,
+                },
+            )
+            .await
+            .expect("issue created")
+            .id
+    }
+
+    fn duplicate_request(title: &str, description: &str) -> DuplicateCheckRequest {
+        DuplicateCheckRequest {
+            title: title.to_string(),
+            description: Some(description.to_string()),
+            exclude_issue_id: None,
+            limit: None,
+        }
+    }
+
+    fn local_ai() -> AiService {
+        AiService::with_provider(Arc::new(MockProvider::new())).expect("AI service")
+    }
+
+    #[sqlx::test]
+    async fn test_check_duplicates_ranks_similar_issues(pool: SqlitePool) {
+        let service = EmbeddingService::new(pool.clone());
+        let issues = IssueService::new(pool.clone());
+        let ai = local_ai();
+        let user_id = create_test_user(&pool).await;
+        let outsider_id = create_test_user(&pool).await;
+        let board_id = create_test_board(&pool, &user_id).await;
+
+        let login = create_test_issue(
+            &issues,
+            &user_id,
+            &board_id,
+            "Google login fails",
+            "Users cannot log in with their Google account",
+        )
+        .await;
+        create_test_issue(
+            &issues,
+            &user_id,
+            &board_id,
+            "Dark mode",
+            "Add a dark theme to the settings page",
+        )
+        .await;
+
+        let result = service
+            .check_duplicates(
+                &ai,
+                &user_id,
+                &board_id,
+                &duplicate_request("Login with Google", "Google account login fails for users"),
+            )
+            .await
+            .expect("duplicates checked");
+        assert_eq!(result.duplicates.len(), 1);
+        assert_eq!(result.duplicates[0].issue_id, login);
+        assert_eq!(result.duplicates[0].status, "backlog");
+        assert!(result.uniqueness_score < 0.6);
+        assert!(
+            (result.uniqueness_score - (1.0 - result.duplicates[0].similarity_score)).abs() < 1e-6
+        );
+
+        let unique = service
+            .check_duplicates(
+                &ai,
+                &user_id,
+                &board_id,
+                &duplicate_request("Export to CSV", "Download the board as a spreadsheet"),
+            )
+            .await
+            .expect("duplicates checked");
+        assert!(unique.duplicates.is_empty());
+        assert!(unique.uniqueness_score > 0.9);
+
+        // An issue is not reported as a duplicate of itself
+        let mut own = duplicate_request(
+            "Google login fails",
+            "Users cannot log in with their Google account",
+        );
+        own.exclude_issue_id = Some(login);
+        let result = service
+            .check_duplicates(&ai, &user_id, &board_id, &own)
+            .await
+            .expect("duplicates checked");
+        assert!(result.duplicates.is_empty());
+
+        let outsider = service
+            .check_duplicates(
+                &ai,
+                &outsider_id,
+                &board_id,
+                &duplicate_request("Login", "Login"),
+            )
+            .await;
+        assert!(matches!(outsider, Err(AppError::NotFound(_))));
+    }
+
+    #[sqlx::test]
+    async fn test_issue_embeddings_are_refreshed_when_the_issue_changes(pool: SqlitePool) {
+        let service = EmbeddingService::new(pool.clone());
+        let issues = IssueService::new(pool.clone());
+        let ai = local_ai();
+        let user_id = create_test_user(&pool).await;
+        let board_id = create_test_board(&pool, &user_id).await;
+        let issue_id = create_test_issue(
+            &issues,
+            &user_id,
+            &board_id,
+            "Slow search",
+            "Searching the board takes seconds",
+        )
+        .await;
+
+        let query = duplicate_request("Search is slow", "Searching the board takes seconds");
+        service
+            .check_duplicates(&ai, &user_id, &board_id, &query)
+            .await
+            .expect("duplicates checked");
+        let stored = sqlx::query!(
+            r#"SELECT model, dimensions, content_hash FROM embeddings WHERE entity_id = ?1"#,
+            issue_id
+        )
+        .fetch_one(&pool)
+        .await
+        .expect("embedding stored");
+        assert_eq!(stored.model, LOCAL_EMBEDDING_MODEL);
+        assert_eq!(stored.dimensions, 512);
+
+        issues
+            .update_issue(
+                &user_id,
+                &issue_id,
+                &UpdateIssueRequest {
+                    title: Some("Avatar upload".to_string()),
+                    description: Some("Let users upload a profile picture".to_string()),
+                    priority: None,
+                    issue_type: None,
+                    size_estimate: None,
+                },
+            )
+            .await
+            .expect("issue updated");
+        let result = service
+            .check_duplicates(&ai, &user_id, &board_id, &query)
+            .await
+            .expect("duplicates checked");
+        assert!(result.duplicates.is_empty());
+
+        let refreshed = sqlx::query_scalar!(
+            r#"SELECT content_hash FROM embeddings WHERE entity_id = ?1"#,
+            issue_id
+        )
+        .fetch_one(&pool)
+        .await
+        .expect("embedding stored");
+        assert_ne!(refreshed, stored.content_hash);
+
+        // Deleting the issue deletes its embedding
+        issues
+            .delete_issue(&user_id, &issue_id)
+            .await
+            .expect("issue deleted");
+        let remaining = sqlx::query_scalar!("SELECT COUNT(*) FROM embeddings")
+            .fetch_one(&pool)
+            .await
+            .expect("count");
+        assert_eq!(remaining, 0);
+    }
+
+    #[sqlx::test]
+    async fn test_search_conversations_finds_related_conversations(pool: SqlitePool) {
+        let service = EmbeddingService::new(pool.clone());
+        let ai_data = AiDataService::new(pool.clone());
+        let ai = local_ai();
+        let user_id = create_test_user(&pool).await;
+        let other_id = create_test_user(&pool).await;
+
+        let mut ids = Vec::new();
+        for (owner, title, message) in [
+            (
+                &user_id,
+                "Deployment",
+                "How do I deploy the server to Fly.io?",
+            ),
+            (
+                &user_id,
+                "Recipes",
+                "Suggest a dinner with pasta and tomatoes",
+            ),
+            (&other_id, "Deploying", "Deploy the server to Fly.io"),
+        ] {
+            let conversation = ai_data
+                .create_conversation(
+                    owner,
+                    CreateConversationRequest {
+                        title: Some(title.to_string()),
+                        model: "mock".to_string(),
+                        system_prompt: None,
+                    },
+                )
+                .await
+                .expect("conversation created");
+            ai_data
+                .add_message(
+                    &conversation.id,
+                    owner,
+                    CreateMessageRequest {
+                        role: "user".to_string(),
+                        content: message.to_string(),
+                        token_count: None,
+                        parent_message_id: None,
+                    },
+                )
+                .await
+                .expect("message added");
+            ids.push(conversation.id);
+        }
+
+        let results = service
+            .search_conversations(&ai, &user_id, "deploying the server", None)
+            .await
+            .expect("search ran");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].conversation_id, ids[0]);
+        assert_eq!(results[0].title.as_deref(), Some("Deployment"));
+
+        let empty = service
+            .search_conversations(&ai, &user_id, "  ", None)
+            .await;
+        assert!(matches!(empty, Err(AppError::ValidationError(_))));
+    }
+}
@@ -0,0 +1,442 @@
+//! File service for uploaded assets
+//!
+//! Manages the `stored_objects` and `stored_files` tables. Bytes go to the
+//! `FileStorage` backend under a key derived from their SHA-256, so uploading
+//! the same content again stores nothing new. Each upload is a stored file
+//! that can belong to an AI session, where it becomes one of the session's
+//! assets, to a conversation, and to the document made from its text.
+//!
+//! Files are downloaded through signed URLs that expire, so they can be used
+//! where no `Authorization` header can be sent, such as in links and images.
+//! Blobs no stored file refers to any more are removed by `collect_garbage`.
+
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    errors::{AppError, AppResult},
+    models::{FileLinks, FileListQuery, FileResponse, StoredFile},
+    services::{
+        board_service::db_timestamp,
+        storage::{FileStorage, storage_from_env},
+    },
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long a download URL works
+const DOWNLOAD_URL_TTL_SECS: i64 = 15 * 60;
+
+/// How long a blob is kept after its last upload even if no file refers to
+/// it, so garbage collection cannot remove one an upload is about to use
+const GC_GRACE_PERIOD_SECS: i64 = 60 * 60;
+
+/// Maximum length of a file name
+const MAX_NAME_LEN: usize = 255;
+
+/// Content type of files uploaded without one
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+#[derive(Clone)]
+pub struct FileService {
+    db: SqlitePool,
+    storage: Arc<dyn FileStorage>,
+    /// Key signing download URLs
+    url_key: Vec<u8>,
+}
+
+fn file_not_found() -> AppError {
+    AppError::NotFound("File not found".to_string())
+}
+
+fn invalid_download_url() -> AppError {
+    AppError::Unauthorized("Download link is invalid or has expired".to_string())
+}
+
+/// Key of the blob holding content with the given hash
+fn storage_key(content_hash: &str) -> String {
+    format!("objects/{}/{content_hash}", &content_hash[..2])
+}
+
+impl FileService {
+    /// Create the service with a storage backend and a key to sign download
+    /// URLs with
+    #[must_use]
+    pub fn new(db: SqlitePool, storage: Arc<dyn FileStorage>, url_key: &[u8]) -> Self {
+        Self {
+            db,
+            storage,
+            url_key: url_key.to_vec(),
+        }
+    }
+
+    /// Create the service with the storage backend `STORAGE_BACKEND` selects,
+    /// signing download URLs with `FILE_URL_SECRET` or else `JWT_SECRET`
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ConfigError` if the storage backend is misconfigured
+    /// or neither secret is set
+    pub fn from_env(db: SqlitePool) -> AppResult<Self> {
+        let storage = storage_from_env()?;
+        let secret = std::env::var("FILE_URL_SECRET")
+            .or_else(|_| std::env::var("JWT_SECRET"))
+            .map_err(|_| {
+                AppError::ConfigError(
+                    "FILE_URL_SECRET or JWT_SECRET must be set to sign download URLs".to_string(),
+                )
+            })?;
+        tracing::info!("Storing uploaded files with the {} backend", storage.name());
+        Ok(Self::new(db, storage, secret.as_bytes()))
+    }
+
+    /// Store an uploaded file
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ValidationError` if the file is empty or its name is
+    /// invalid, `AppError::NotFound` if the user does not have the session,
+    /// conversation or document it is linked to, or `AppError` if it cannot be
+    /// stored
+    pub async fn store_file(
+        &self,
+        user_id: &str,
+        name: &str,
+        content_type: Option<&str>,
+        data: &[u8],
+        links: &FileLinks,
+    ) -> AppResult<StoredFile> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(AppError::ValidationError(
+                "File name is required and must be at most 255 characters.".to_string(),
+            ));
+        }
+        if data.is_empty() {
+            return Err(AppError::ValidationError("File is empty.".to_string()));
+        }
+        self.check_links(user_id, links).await?;
+
+        let content_type = content_type
+            .filter(|content_type| !content_type.is_empty())
+            .unwrap_or(DEFAULT_CONTENT_TYPE);
+        let content_hash = format!("{:x}", Sha256::digest(data));
+        let key = storage_key(&content_hash);
+        let size = i64::try_from(data.len()).unwrap_or(i64::MAX);
+        let now = db_timestamp(Utc::now());
+
+        // Record the blob before writing it so garbage collection spares it
+        let stored = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM stored_objects WHERE content_hash = ?1",
+            content_hash
+        )
+        .fetch_one(&self.db)
+        .await?
+            > 0;
+        sqlx::query!(
+            r#"
+            INSERT INTO stored_objects (content_hash, storage_key, size, last_used_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (content_hash) DO UPDATE SET last_used_at = excluded.last_used_at
+            "#,
+            content_hash,
+            key,
+            size,
+            now
+        )
+        .execute(&self.db)
+        .await?;
+        if !stored {
+            self.storage.put(&key, data).await?;
+        }
+
+        let file_id = format!("file_{}", Uuid::new_v4());
+        let mut tx = self.db.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO stored_files
+                (id, user_id, content_hash, name, content_type, size, session_id, conversation_id, document_id, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            "#,
+            file_id,
+            user_id,
+            content_hash,
+            name,
+            content_type,
+            size,
+            links.session_id,
+            links.conversation_id,
+            links.document_id,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        if let Some(session_id) = &links.session_id {
+            sqlx::query!(
+                r#"
+                INSERT INTO ai_session_assets (id, session_id, file_path, content_type, size, description, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                "#,
+                file_id,
+                session_id,
+                key,
+                content_type,
+                size,
+                name,
+                now
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        tracing::info!(
+            "User {} stored file {} ({} bytes, {})",
+            user_id,
+            file_id,
+            size,
+            if stored {
+                "deduplicated"
+            } else {
+                "new content"
+            }
+        );
+        self.get_file(user_id, &file_id).await
+    }
+
+    /// List a user's files, most recent first, optionally only those of a
+    /// session or conversation
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn list_files(
+        &self,
+        user_id: &str,
+        query: &FileListQuery,
+    ) -> AppResult<Vec<StoredFile>> {
+        Ok(sqlx::query_as!(
+            StoredFile,
+            r#"
+            SELECT id as "id!", user_id, name, content_type, size, content_hash,
+                   session_id, conversation_id, document_id,
+                   created_at as "created_at: DateTime<Utc>"
+            FROM stored_files
+            WHERE user_id = ?1
+              AND (?2 IS NULL OR session_id = ?2)
+              AND (?3 IS NULL OR conversation_id = ?3)
+            ORDER BY created_at DESC, id DESC
+            "#,
+            user_id,
+            query.session_id,
+            query.conversation_id
+        )
+        .fetch_all(&self.db)
+        .await?)
+    }
+
+    /// Get one of a user's files
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user has no such file, or
+    /// `AppError` if the database query fails
+    pub async fn get_file(&self, user_id: &str, file_id: &str) -> AppResult<StoredFile> {
+        self.find_file(file_id)
+            .await?
+            .filter(|file| file.user_id == user_id)
+            .ok_or_else(file_not_found)
+    }
+
+    /// Delete one of a user's files; its bytes are removed by the next garbage
+    /// collection once no other file has the same content
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user has no such file, or
+    /// `AppError` if the database operation fails
+    pub async fn delete_file(&self, user_id: &str, file_id: &str) -> AppResult<()> {
+        let result = sqlx::query!(
+            "DELETE FROM stored_files WHERE id = ?1 AND user_id = ?2",
+            file_id,
+            user_id
+        )
+        .execute(&self.db)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(file_not_found());
+        }
+        Ok(())
+    }
+
+    /// The file with a signed URL to download it
+    #[must_use]
+    pub fn with_download_url(&self, file: StoredFile) -> FileResponse {
+        let expires = (Utc::now() + Duration::seconds(DOWNLOAD_URL_TTL_SECS)).timestamp();
+        let signature = self.sign(&file.id, expires);
+        let download_url = format!(
+            "/api/ai/files/{}/content?expires={expires}&signature={signature}",
+            file.id
+        );
+        FileResponse { file, download_url }
+    }
+
+    /// Read a file through a signed download URL
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Unauthorized` if the signature is wrong or the URL
+    /// has expired, `AppError::NotFound` if the file no longer exists, or
+    /// `AppError` if it cannot be read
+    pub async fn read_signed(
+        &self,
+        file_id: &str,
+        expires: i64,
+        signature: &str,
+    ) -> AppResult<(StoredFile, Vec<u8>)> {
+        if expires < Utc::now().timestamp() {
+            return Err(invalid_download_url());
+        }
+        let signature = decode_hex(signature).ok_or_else(invalid_download_url)?;
+        self.mac(file_id, expires)
+            .verify_slice(&signature)
+            .map_err(|_| invalid_download_url())?;
+
+        let file = self.find_file(file_id).await?.ok_or_else(file_not_found)?;
+        let data = self.storage.get(&storage_key(&file.content_hash)).await?;
+        Ok((file, data))
+    }
+
+    /// Delete blobs no stored file refers to, once they are past the grace
+    /// period; returns how many were deleted
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database or storage operation fails
+    pub async fn collect_garbage(&self) -> AppResult<u64> {
+        let cutoff = db_timestamp(Utc::now() - Duration::seconds(GC_GRACE_PERIOD_SECS));
+        let candidates = sqlx::query!(
+            r#"
+            SELECT content_hash as "content_hash!", storage_key FROM stored_objects o
+            WHERE last_used_at < ?1
+              AND NOT EXISTS (SELECT 1 FROM stored_files f WHERE f.content_hash = o.content_hash)
+            "#,
+            cutoff
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut deleted = 0;
+        for candidate in candidates {
+            // Check again as the row is deleted, in case an upload reused it
+            let result = sqlx::query!(
+                r#"
+                DELETE FROM stored_objects
+                WHERE content_hash = ?1 AND last_used_at < ?2
+                  AND NOT EXISTS (SELECT 1 FROM stored_files WHERE content_hash = ?1)
+                "#,
+                candidate.content_hash,
+                cutoff
+            )
+            .execute(&self.db)
+            .await?;
+            if result.rows_affected() > 0 {
+                self.storage.delete(&candidate.storage_key).await?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn find_file(&self, file_id: &str) -> AppResult<Option<StoredFile>> {
+        Ok(sqlx::query_as!(
+            StoredFile,
+            r#"
+            SELECT id as "id!", user_id, name, content_type, size, content_hash,
+                   session_id, conversation_id, document_id,
+                   created_at as "created_at: DateTime<Utc>"
+            FROM stored_files
+            WHERE id = ?1
+            "#,
+            file_id
+        )
+        .fetch_optional(&self.db)
+        .await?)
+    }
+
+    /// Check the user has everything a file would be linked to
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user does not have the session,
+    /// conversation or document, or `AppError` if the database query fails
+    pub async fn check_links(&self, user_id: &str, links: &FileLinks) -> AppResult<()> {
+        if let Some(session_id) = &links.session_id {
+            let owned = sqlx::query_scalar!(
+                "SELECT COUNT(*) FROM ai_sessions WHERE id = ?1 AND user_id = ?2",
+                session_id,
+                user_id
+            )
+            .fetch_one(&self.db)
+            .await?;
+            if owned == 0 {
+                return Err(AppError::NotFound("Session not found".to_string()));
+            }
+        }
+        if let Some(conversation_id) = &links.conversation_id {
+            let owned = sqlx::query_scalar!(
+                "SELECT COUNT(*) FROM ai_conversations WHERE id = ?1 AND user_id = ?2",
+                conversation_id,
+                user_id
+            )
+            .fetch_one(&self.db)
+            .await?;
+            if owned == 0 {
+                return Err(AppError::NotFound("Conversation not found".to_string()));
+            }
+        }
+        if let Some(document_id) = &links.document_id {
+            let owned = sqlx::query_scalar!(
+                "SELECT COUNT(*) FROM documents WHERE id = ?1 AND user_id = ?2",
+                document_id,
+                user_id
+            )
+            .fetch_one(&self.db)
+            .await?;
+            if owned == 0 {
+                return Err(AppError::NotFound("Document not found".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn mac(&self, file_id: &str, expires: i64) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.url_key).expect("HMAC accepts keys of any length");
+        mac.update(format!("{file_id}:{expires}").as_bytes());
+        mac
+    }
+
+    fn sign(&self, file_id: &str, expires: i64) -> String {
+        format!("{:x}", self.mac(file_id, expires).finalize().into_bytes())
+    }
+}
+
+/// Decode lowercase or uppercase hex, or `None` if it is not valid hex
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+#[path = "file_service_tests.rs"]
+mod file_service_tests;
@@ -0,0 +1,248 @@
+//! Tests for file service
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::AppError;
+    use crate::models::{FileLinks, FileListQuery};
+    use crate::services::FileService;
+    use crate::services::storage::MemoryStorage;
+    use sqlx::SqlitePool;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    async fn create_test_user(pool: &SqlitePool) -> String {
+        let user_id = Uuid::new_v4().to_string();
+        let email = format!("test+{user_id}@example.com");
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, email, hashed_password, provider, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            user_id,
+            email,
+            "hashed_password",
+            "local",
+            now,
+            now
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to create test user");
+
+        user_id
+    }
+
+    async fn create_test_session(pool: &SqlitePool, user_id: &str) -> String {
+        let session_id = format!("sess_{}", Uuid::new_v4());
+        let expires_at = (chrono::Utc::now() + chrono::Duration::minutes(30)).to_rfc3339();
+        sqlx::query!(
+            r#"
+            INSERT INTO ai_sessions (id, user_id, type, status, context, expires_at)
+            VALUES (?1, ?2, 'issue_creation', 'active', '{}', ?3)
+            "#,
+            session_id,
+            user_id,
+            expires_at
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to create test session");
+
+        session_id
+    }
+
+    fn file_service(pool: &SqlitePool) -> (FileService, Arc<MemoryStorage>) {
+        let storage = Arc::new(MemoryStorage::new());
+        let service = FileService::new(pool.clone(), storage.clone(), b"test-secret");
+        (service, storage)
+    }
+
+    /// Split a download URL into its file ID, expiry and signature
+    fn parse_download_url(url: &str) -> (String, i64, String) {
+        let (path, query) = url.split_once('?').expect("query string");
+        let file_id = path
+            .trim_start_matches("/api/ai/files/")
+            .trim_end_matches("/content")
+            .to_string();
+        let mut expires = 0;
+        let mut signature = String::new();
+        for pair in query.split('&') {
+            match pair.split_once('=') {
+                Some(("expires", value)) => expires = value.parse().expect("expiry"),
+                Some(("signature", value)) => signature = value.to_string(),
+                _ => {}
+            }
+        }
+        (file_id, expires, signature)
+    }
+
+    #[sqlx::test]
+    async fn test_store_file_dedupes_content(pool: SqlitePool) {
+        let (service, storage) = file_service(&pool);
+        let user_id = create_test_user(&pool).await;
+        let other_id = create_test_user(&pool).await;
+        let links = FileLinks::default();
+
+        let first = service
+            .store_file(&user_id, "notes.txt", Some("text/plain"), b"hello", &links)
+            .await
+            .expect("file stored");
+        let second = service
+            .store_file(&other_id, "copy.txt", None, b"hello", &links)
+            .await
+            .expect("file stored");
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.content_hash, second.content_hash);
+        assert_eq!(first.size, 5);
+        assert_eq!(second.content_type, "application/octet-stream");
+        assert_eq!(storage.keys().len(), 1);
+
+        let empty = service
+            .store_file(&user_id, "empty.txt", None, b"", &links)
+            .await;
+        assert!(matches!(empty, Err(AppError::ValidationError(_))));
+        assert!(matches!(
+            service.get_file(&other_id, &first.id).await,
+            Err(AppError::NotFound(_))
+        ));
+
+        // The signed URL downloads the file without a token
+        let response = service.with_download_url(first);
+        let (file_id, expires, signature) = parse_download_url(&response.download_url);
+        assert_eq!(file_id, response.file.id);
+        let (file, data) = service
+            .read_signed(&file_id, expires, &signature)
+            .await
+            .expect("file read");
+        assert_eq!(file.name, "notes.txt");
+        assert_eq!(data, b"hello");
+
+        for (id, expires, signature) in [
+            (second.id.as_str(), expires, signature.as_str()),
+            (file_id.as_str(), expires + 1, signature.as_str()),
+            (file_id.as_str(), expires, "not-hex"),
+            (file_id.as_str(), 0, signature.as_str()),
+        ] {
+            assert!(matches!(
+                service.read_signed(id, expires, signature).await,
+                Err(AppError::Unauthorized(_))
+            ));
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_session_files_are_session_assets(pool: SqlitePool) {
+        let (service, _) = file_service(&pool);
+        let user_id = create_test_user(&pool).await;
+        let other_id = create_test_user(&pool).await;
+        let session_id = create_test_session(&pool, &user_id).await;
+        let links = FileLinks {
+            session_id: Some(session_id.clone()),
+            ..FileLinks::default()
+        };
+
+        let file = service
+            .store_file(
+                &user_id,
+                "screenshot.png",
+                Some("image/png"),
+                b"png",
+                &links,
+            )
+            .await
+            .expect("file stored");
+        let asset = sqlx::query!(
+            "SELECT session_id, file_path, size FROM ai_session_assets WHERE id = ?1",
+            file.id
+        )
+        .fetch_one(&pool)
+        .await
+        .expect("asset stored");
+        assert_eq!(asset.session_id, session_id);
+        assert_eq!(
+            asset.file_path,
+            format!("objects/{}/{}", &file.content_hash[..2], file.content_hash)
+        );
+        assert_eq!(asset.size, 3);
+
+        let listed = service
+            .list_files(
+                &user_id,
+                &FileListQuery {
+                    session_id: Some(session_id.clone()),
+                    conversation_id: None,
+                },
+            )
+            .await
+            .expect("files listed");
+        assert_eq!(listed.len(), 1);
+
+        let outsider = service
+            .store_file(&other_id, "x.png", None, b"png", &links)
+            .await;
+        assert!(matches!(outsider, Err(AppError::NotFound(_))));
+
+        // Deleting the file removes the asset
+        service
+            .delete_file(&user_id, &file.id)
+            .await
+            .expect("file deleted");
+        let assets = sqlx::query_scalar!("SELECT COUNT(*) FROM ai_session_assets")
+            .fetch_one(&pool)
+            .await
+            .expect("count");
+        assert_eq!(assets, 0);
+        assert!(matches!(
+            service.delete_file(&user_id, &file.id).await,
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[sqlx::test]
+    async fn test_collect_garbage_removes_unreferenced_blobs(pool: SqlitePool) {
+        let (service, storage) = file_service(&pool);
+        let user_id = create_test_user(&pool).await;
+        let links = FileLinks::default();
+
+        let kept = service
+            .store_file(&user_id, "kept.txt", None, b"kept", &links)
+            .await
+            .expect("file stored");
+        let removed = service
+            .store_file(&user_id, "removed.txt", None, b"removed", &links)
+            .await
+            .expect("file stored");
+        service
+            .delete_file(&user_id, &removed.id)
+            .await
+            .expect("file deleted");
+
+        // Recently uploaded blobs are spared
+        assert_eq!(service.collect_garbage().await.expect("collected"), 0);
+        assert_eq!(storage.keys().len(), 2);
+
+        sqlx::query!("UPDATE stored_objects SET last_used_at = '2000-01-01T00:00:00.000000Z'")
+            .execute(&pool)
+            .await
+            .expect("blobs aged");
+        assert_eq!(service.collect_garbage().await.expect("collected"), 1);
+        assert_eq!(
+            storage.keys(),
+            vec![format!(
+                "objects/{}/{}",
+                &kept.content_hash[..2],
+                kept.content_hash
+            )]
+        );
+
+        // Deleting the user deletes their files, leaving the blob to collect
+        sqlx::query!("DELETE FROM users WHERE id = ?1", user_id)
+            .execute(&pool)
+            .await
+            .expect("user deleted");
+        assert_eq!(service.collect_garbage().await.expect("collected"), 1);
+        assert!(storage.keys().is_empty());
+    }
+}
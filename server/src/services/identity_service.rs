@@ -0,0 +1,390 @@
+//! Sign-in identity service
+//!
+//! Stores the provider accounts linked to each user in the `user_identities`
+//! table. OAuth sign-ins are matched to users by provider account, so linking
+//! an account is always explicit, or done on sign-in only when the provider
+//! verified the email of an existing user.
+
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    errors::{AppError, AppResult},
+    models::{UserIdentity, oauth::OAuthUserInfo},
+};
+
+pub struct IdentityService {
+    db: SqlitePool,
+}
+
+impl IdentityService {
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Find the user a provider account is linked to
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn find_user_id(
+        &self,
+        provider: &str,
+        provider_user_id: &str,
+    ) -> AppResult<Option<Uuid>> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            SELECT user_id FROM user_identities
+            WHERE provider = ?1 AND provider_user_id = ?2
+            "#,
+            provider,
+            provider_user_id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        user_id
+            .map(|user_id| {
+                Uuid::parse_str(&user_id).map_err(|_| {
+                    AppError::InternalServerError("Invalid user ID in identity".to_string())
+                })
+            })
+            .transpose()
+    }
+
+    /// List the provider accounts linked to a user
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn list_identities(&self, user_id: Uuid) -> AppResult<Vec<UserIdentity>> {
+        let user_id = user_id.to_string();
+
+        let identities = sqlx::query_as!(
+            UserIdentity,
+            r#"
+            SELECT
+                provider,
+                provider_user_id,
+                user_id,
+                email,
+                email_verified as "email_verified: bool",
+                created_at as "created_at: DateTime<Utc>",
+                last_login_at as "last_login_at: DateTime<Utc>"
+            FROM user_identities
+            WHERE user_id = ?1
+            ORDER BY created_at
+            "#,
+            user_id
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(identities)
+    }
+
+    /// Link a provider account to a user
+    ///
+    /// Linking an account that is already linked to the user updates the
+    /// email stored for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::BadRequest` if the account is linked to another user
+    /// or the user already has another account of the provider linked, or
+    /// `AppError` if the database operation fails
+    pub async fn link_identity(&self, user_id: Uuid, info: &OAuthUserInfo) -> AppResult<()> {
+        let provider = info.provider.to_string();
+
+        match self.find_user_id(&provider, &info.id).await? {
+            Some(owner) if owner != user_id => {
+                return Err(AppError::BadRequest(format!(
+                    "This {provider} account is already linked to another user"
+                )));
+            }
+            Some(_) => {
+                self.record_login(info).await?;
+                return Ok(());
+            }
+            None => {}
+        }
+
+        let user_id = user_id.to_string();
+        let now = Utc::now();
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_identities (provider, provider_user_id, user_id, email, email_verified, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            provider,
+            info.id,
+            user_id,
+            info.email,
+            info.email_verified,
+            now
+        )
+        .execute(&self.db)
+        .await;
+
+        match result {
+            Ok(_) => {
+                tracing::info!(
+                    "Linked {} account {} to user {}",
+                    provider,
+                    info.id,
+                    user_id
+                );
+                Ok(())
+            }
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(AppError::BadRequest(
+                format!("A {provider} account is already linked; unlink it first"),
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Record a sign-in with a provider account, updating its email
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn record_login(&self, info: &OAuthUserInfo) -> AppResult<()> {
+        let provider = info.provider.to_string();
+        let now = Utc::now();
+
+        sqlx::query!(
+            r#"
+            UPDATE user_identities
+            SET email = ?1, email_verified = ?2, last_login_at = ?3
+            WHERE provider = ?4 AND provider_user_id = ?5
+            "#,
+            info.email,
+            info.email_verified,
+            now,
+            provider,
+            info.id
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Unlink a user's account of a provider
+    ///
+    /// Users who sign in without a password keep at least one account.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user has no account of the provider
+    /// linked, `AppError::BadRequest` if it is the last way they can sign in,
+    /// or `AppError` if the database operation fails
+    pub async fn unlink_identity(&self, user_id: Uuid, provider: &str) -> AppResult<()> {
+        let user_id = user_id.to_string();
+
+        let sign_in = sqlx::query!(
+            r#"
+            SELECT
+                users.provider = 'local' as "has_password!: bool",
+                (SELECT COUNT(*) FROM user_identities WHERE user_id = users.id) as "identities!: i64"
+            FROM users
+            WHERE id = ?1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or(AppError::UserNotFound)?;
+
+        if !sign_in.has_password && sign_in.identities <= 1 {
+            return Err(AppError::BadRequest(
+                "Cannot unlink the only account you can sign in with".to_string(),
+            ));
+        }
+
+        let result = sqlx::query!(
+            "DELETE FROM user_identities WHERE user_id = ?1 AND provider = ?2",
+            user_id,
+            provider
+        )
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!(
+                "No {provider} account is linked"
+            )));
+        }
+
+        tracing::info!("Unlinked {} account from user {}", provider, user_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::auth_handler::RegisterUserPayload;
+    use crate::models::oauth::OAuthProvider;
+    use crate::services::UserServiceImpl;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect("sqlite::memory:")
+            .await
+            .expect("Failed to create test database");
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .expect("Failed to run migrations");
+
+        pool
+    }
+
+    async fn create_user(pool: &SqlitePool, email: &str) -> Uuid {
+        UserServiceImpl::new(pool.clone())
+            .create_user(&RegisterUserPayload {
+                email: email.to_string(),
+                password: "password123".to_string(),
+            })
+            .await
+            .expect("Failed to create user")
+            .id
+    }
+
+    fn account(provider: OAuthProvider, id: &str) -> OAuthUserInfo {
+        OAuthUserInfo {
+            id: id.to_string(),
+            email: format!("{id}@example.com"),
+            name: None,
+            picture: None,
+            provider,
+            email_verified: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_link_and_find_identity() {
+        let pool = setup_test_db().await;
+        let service = IdentityService::new(pool.clone());
+        let user_id = create_user(&pool, "links@example.com").await;
+
+        service
+            .link_identity(user_id, &account(OAuthProvider::Google, "g-1"))
+            .await
+            .expect("google linked");
+        service
+            .link_identity(user_id, &account(OAuthProvider::GitHub, "gh-1"))
+            .await
+            .expect("github linked");
+        // Linking the same account again is a no-op
+        service
+            .link_identity(user_id, &account(OAuthProvider::Google, "g-1"))
+            .await
+            .expect("google linked again");
+
+        assert_eq!(
+            service.find_user_id("google", "g-1").await.expect("lookup"),
+            Some(user_id)
+        );
+        assert_eq!(
+            service
+                .find_user_id("google", "gh-1")
+                .await
+                .expect("lookup"),
+            None
+        );
+        let identities = service.list_identities(user_id).await.expect("identities");
+        assert_eq!(identities.len(), 2);
+        assert!(identities[0].email_verified);
+        assert!(identities[0].last_login_at.is_some());
+
+        // A second account of the same provider is refused
+        let result = service
+            .link_identity(user_id, &account(OAuthProvider::Google, "g-2"))
+            .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_identity_cannot_be_linked_twice() {
+        let pool = setup_test_db().await;
+        let service = IdentityService::new(pool.clone());
+        let owner = create_user(&pool, "owner@example.com").await;
+        let other = create_user(&pool, "other@example.com").await;
+
+        service
+            .link_identity(owner, &account(OAuthProvider::GitHub, "gh-1"))
+            .await
+            .expect("linked");
+        let result = service
+            .link_identity(other, &account(OAuthProvider::GitHub, "gh-1"))
+            .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert_eq!(
+            service
+                .find_user_id("github", "gh-1")
+                .await
+                .expect("lookup"),
+            Some(owner)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unlink_keeps_a_way_to_sign_in() {
+        let pool = setup_test_db().await;
+        let service = IdentityService::new(pool.clone());
+        let user_id = create_user(&pool, "unlink@example.com").await;
+        service
+            .link_identity(user_id, &account(OAuthProvider::Google, "g-1"))
+            .await
+            .expect("linked");
+
+        // Users with a password can unlink all their accounts
+        service
+            .unlink_identity(user_id, "google")
+            .await
+            .expect("unlinked");
+        assert!(matches!(
+            service.unlink_identity(user_id, "google").await,
+            Err(AppError::NotFound(_))
+        ));
+
+        // Users created by OAuth sign-in have no password to fall back on
+        let user_id_str = user_id.to_string();
+        sqlx::query!(
+            "UPDATE users SET provider = 'google' WHERE id = ?1",
+            user_id_str
+        )
+        .execute(&pool)
+        .await
+        .expect("provider updated");
+        service
+            .link_identity(user_id, &account(OAuthProvider::Google, "g-1"))
+            .await
+            .expect("linked");
+        assert!(matches!(
+            service.unlink_identity(user_id, "google").await,
+            Err(AppError::BadRequest(_))
+        ));
+
+        service
+            .link_identity(user_id, &account(OAuthProvider::GitHub, "gh-1"))
+            .await
+            .expect("linked");
+        service
+            .unlink_identity(user_id, "google")
+            .await
+            .expect("unlinked");
+        assert_eq!(
+            service
+                .list_identities(user_id)
+                .await
+                .expect("identities")
+                .len(),
+            1
+        );
+    }
+}
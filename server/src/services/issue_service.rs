@@ -0,0 +1,460 @@
+//! Issue service
+//!
+//! Manages the `issues` table. Every operation is scoped to the boards the
+//! requesting user is a member of; issues on other boards are reported as not
+//! found. Listing supports filters, several sort orders and cursor pagination:
+//! the cursor records the sort key and ID of the last issue on a page, so pages
+//! stay stable while issues are added.
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+use uuid::Uuid;
+
+use crate::{
+    errors::{AppError, AppResult},
+    models::{
+        CreateIssueRequest, Issue, IssueListQuery, IssuePage, IssuePriority, IssueSort,
+        IssueStatus, IssueType, SortOrder, UpdateIssueRequest,
+    },
+    services::board_service::{db_timestamp, require_member},
+};
+
+/// Issues per page when the query does not set a limit
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// Largest page a query can ask for
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Maximum length of an issue title
+const MAX_TITLE_LEN: usize = 200;
+
+/// Maximum length of an issue description
+const MAX_DESCRIPTION_LEN: usize = 20_000;
+
+/// Orders priorities from most to least urgent
+const PRIORITY_RANK: &str =
+    "CASE priority WHEN 'critical' THEN 0 WHEN 'high' THEN 1 WHEN 'medium' THEN 2 ELSE 3 END";
+
+const ISSUE_COLUMNS: &str = "id, board_id, title, description, type, status, priority, \
+                             size_estimate, created_at, updated_at, created_by";
+
+/// Sort key value of the last issue on a page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum CursorKey {
+    Rank(i64),
+    Text(String),
+}
+
+/// Position after which the next page starts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Cursor {
+    sort: IssueSort,
+    order: SortOrder,
+    key: CursorKey,
+    id: String,
+}
+
+impl Cursor {
+    fn after(issue: &Issue, sort: IssueSort, order: SortOrder) -> Self {
+        let key = match sort {
+            IssueSort::CreatedAt => CursorKey::Text(db_timestamp(issue.created_at)),
+            IssueSort::UpdatedAt => CursorKey::Text(db_timestamp(issue.updated_at)),
+            IssueSort::Priority => CursorKey::Rank(priority_rank(issue.priority)),
+            IssueSort::Title => CursorKey::Text(issue.title.clone()),
+        };
+        Self {
+            sort,
+            order,
+            key,
+            id: issue.id.clone(),
+        }
+    }
+
+    fn encode(&self) -> AppResult<String> {
+        let json = serde_json::to_vec(self)
+            .map_err(|e| AppError::InternalServerError(format!("Failed to encode cursor: {e}")))?;
+        Ok(URL_SAFE_NO_PAD.encode(json))
+    }
+
+    fn decode(cursor: &str) -> AppResult<Self> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| AppError::ValidationError("Invalid cursor.".to_string()))
+    }
+}
+
+fn priority_rank(priority: IssuePriority) -> i64 {
+    match priority {
+        IssuePriority::Critical => 0,
+        IssuePriority::High => 1,
+        IssuePriority::Medium => 2,
+        IssuePriority::Low => 3,
+    }
+}
+
+fn sort_expression(sort: IssueSort) -> &'static str {
+    match sort {
+        IssueSort::CreatedAt => "created_at",
+        IssueSort::UpdatedAt => "updated_at",
+        IssueSort::Priority => PRIORITY_RANK,
+        IssueSort::Title => "title",
+    }
+}
+
+fn issue_not_found() -> AppError {
+    AppError::NotFound("Issue not found".to_string())
+}
+
+fn validate_title(title: &str) -> AppResult<&str> {
+    let title = title.trim();
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LEN {
+        return Err(AppError::ValidationError(
+            "Issue title is required and must be at most 200 characters.".to_string(),
+        ));
+    }
+    Ok(title)
+}
+
+/// Trim a description, treating an empty one as no description
+fn validate_description(description: Option<&str>) -> AppResult<Option<&str>> {
+    let description = description
+        .map(str::trim)
+        .filter(|description| !description.is_empty());
+    if description.is_some_and(|description| description.chars().count() > MAX_DESCRIPTION_LEN) {
+        return Err(AppError::ValidationError(
+            "Issue description must be at most 20000 characters.".to_string(),
+        ));
+    }
+    Ok(description)
+}
+
+fn validate_size_estimate(size_estimate: Option<i64>) -> AppResult<Option<i64>> {
+    if size_estimate.is_some_and(|hours| hours < 0) {
+        return Err(AppError::ValidationError(
+            "Size estimate cannot be negative.".to_string(),
+        ));
+    }
+    Ok(size_estimate)
+}
+
+pub struct IssueService {
+    db: SqlitePool,
+}
+
+impl IssueService {
+    /// Create a new `IssueService`
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Create an issue in a board's backlog
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user is not a member of the board,
+    /// `AppError::ValidationError` if a field is invalid, or `AppError` if the
+    /// database operation fails
+    pub async fn create_issue(
+        &self,
+        user_id: &str,
+        board_id: &str,
+        request: &CreateIssueRequest,
+    ) -> AppResult<Issue> {
+        require_member(&self.db, board_id, user_id).await?;
+        let title = validate_title(&request.title)?;
+        let description = validate_description(request.description.as_deref())?;
+        let size_estimate = validate_size_estimate(request.size_estimate)?;
+
+        let issue_id = format!("issue_{}", Uuid::new_v4());
+        let now = db_timestamp(Utc::now());
+
+        sqlx::query!(
+            r#"
+            INSERT INTO issues
+                (id, board_id, title, description, type, status, priority, size_estimate,
+                 created_at, updated_at, created_by)
+            VALUES (?1, ?2, ?3, ?4, ?5, 'backlog', ?6, ?7, ?8, ?8, ?9)
+            "#,
+            issue_id,
+            board_id,
+            title,
+            description,
+            request.issue_type,
+            request.priority,
+            size_estimate,
+            now,
+            user_id
+        )
+        .execute(&self.db)
+        .await?;
+
+        tracing::info!(
+            "User {} created issue {} on board {}",
+            user_id,
+            issue_id,
+            board_id
+        );
+        self.get_issue(user_id, &issue_id).await
+    }
+
+    /// Get an issue on one of the user's boards
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the issue does not exist or the user is
+    /// not a member of its board, or `AppError` if the database query fails
+    pub async fn get_issue(&self, user_id: &str, issue_id: &str) -> AppResult<Issue> {
+        sqlx::query_as!(
+            Issue,
+            r#"
+            SELECT i.id as "id!", i.board_id, i.title, i.description,
+                   i.type as "issue_type: IssueType",
+                   i.status as "status: IssueStatus",
+                   i.priority as "priority: IssuePriority",
+                   i.size_estimate,
+                   i.created_at as "created_at: DateTime<Utc>",
+                   i.updated_at as "updated_at: DateTime<Utc>",
+                   i.created_by
+            FROM issues i
+            JOIN board_members m ON m.board_id = i.board_id
+            WHERE i.id = ?1 AND m.user_id = ?2
+            "#,
+            issue_id,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(issue_not_found)
+    }
+
+    /// Change an issue's details
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the issue is not on one of the user's
+    /// boards, `AppError::ValidationError` if a field is invalid, or `AppError`
+    /// if the database operation fails
+    pub async fn update_issue(
+        &self,
+        user_id: &str,
+        issue_id: &str,
+        request: &UpdateIssueRequest,
+    ) -> AppResult<Issue> {
+        let mut issue = self.get_issue(user_id, issue_id).await?;
+
+        if let Some(title) = &request.title {
+            issue.title = validate_title(title)?.to_string();
+        }
+        if let Some(description) = &request.description {
+            issue.description = validate_description(Some(description))?.map(str::to_string);
+        }
+        if let Some(issue_type) = request.issue_type {
+            issue.issue_type = issue_type;
+        }
+        if let Some(priority) = request.priority {
+            issue.priority = priority;
+        }
+        if request.size_estimate.is_some() {
+            issue.size_estimate = validate_size_estimate(request.size_estimate)?;
+        }
+
+        let now = db_timestamp(Utc::now());
+        sqlx::query!(
+            r#"
+            UPDATE issues
+            SET title = ?1, description = ?2, type = ?3, priority = ?4, size_estimate = ?5,
+                updated_at = ?6
+            WHERE id = ?7
+            "#,
+            issue.title,
+            issue.description,
+            issue.issue_type,
+            issue.priority,
+            issue.size_estimate,
+            now,
+            issue_id
+        )
+        .execute(&self.db)
+        .await?;
+
+        self.get_issue(user_id, issue_id).await
+    }
+
+    /// Move an issue to another status
+    ///
+    /// Issues advance one status at a time and may return to any earlier one.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the issue is not on one of the user's
+    /// boards, `AppError::ValidationError` if the transition is not allowed,
+    /// `AppError::BadRequest` if the issue changed status concurrently, or
+    /// `AppError` if the database operation fails
+    pub async fn transition_issue(
+        &self,
+        user_id: &str,
+        issue_id: &str,
+        status: IssueStatus,
+    ) -> AppResult<Issue> {
+        let issue = self.get_issue(user_id, issue_id).await?;
+        if !issue.status.can_transition_to(status) {
+            return Err(AppError::ValidationError(format!(
+                "An issue cannot move from {} to {}.",
+                status_name(issue.status),
+                status_name(status)
+            )));
+        }
+
+        let now = db_timestamp(Utc::now());
+        let moved = sqlx::query!(
+            "UPDATE issues SET status = ?1, updated_at = ?2 WHERE id = ?3 AND status = ?4",
+            status,
+            now,
+            issue_id,
+            issue.status
+        )
+        .execute(&self.db)
+        .await?;
+        if moved.rows_affected() == 0 {
+            return Err(AppError::BadRequest(
+                "The issue's status changed in the meantime; reload and try again".to_string(),
+            ));
+        }
+
+        tracing::info!(
+            "User {} moved issue {} from {} to {}",
+            user_id,
+            issue_id,
+            status_name(issue.status),
+            status_name(status)
+        );
+        self.get_issue(user_id, issue_id).await
+    }
+
+    /// Delete an issue
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the issue is not on one of the user's
+    /// boards, or `AppError` if the database operation fails
+    pub async fn delete_issue(&self, user_id: &str, issue_id: &str) -> AppResult<()> {
+        self.get_issue(user_id, issue_id).await?;
+
+        sqlx::query!("DELETE FROM issues WHERE id = ?1", issue_id)
+            .execute(&self.db)
+            .await?;
+
+        tracing::info!("User {} deleted issue {}", user_id, issue_id);
+        Ok(())
+    }
+
+    /// List one page of a board's issues
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if the user is not a member of the board,
+    /// `AppError::ValidationError` if the limit or cursor is invalid or the
+    /// cursor came from a differently sorted listing, or `AppError` if the
+    /// database query fails
+    pub async fn list_issues(
+        &self,
+        user_id: &str,
+        board_id: &str,
+        query: &IssueListQuery,
+    ) -> AppResult<IssuePage> {
+        require_member(&self.db, board_id, user_id).await?;
+
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(AppError::ValidationError(
+                "Limit must be between 1 and 100.".to_string(),
+            ));
+        }
+        let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+        if cursor
+            .as_ref()
+            .is_some_and(|cursor| cursor.sort != query.sort || cursor.order != query.order)
+        {
+            return Err(AppError::ValidationError(
+                "Cursor does not match the requested sort order.".to_string(),
+            ));
+        }
+
+        let mut builder: QueryBuilder<Sqlite> = QueryBuilder::new(format!(
+            "SELECT {ISSUE_COLUMNS} FROM issues WHERE board_id = "
+        ));
+        builder.push_bind(board_id);
+        if let Some(status) = query.status {
+            builder.push(" AND status = ").push_bind(status);
+        }
+        if let Some(priority) = query.priority {
+            builder.push(" AND priority = ").push_bind(priority);
+        }
+        if let Some(issue_type) = query.issue_type {
+            builder.push(" AND type = ").push_bind(issue_type);
+        }
+
+        let key = sort_expression(query.sort);
+        let (comparison, direction) = match query.order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+        if let Some(cursor) = cursor {
+            builder.push(format!(" AND (({key}) {comparison} "));
+            push_key(&mut builder, &cursor.key);
+            builder.push(format!(" OR (({key}) = "));
+            push_key(&mut builder, &cursor.key);
+            builder.push(format!(" AND id {comparison} "));
+            builder.push_bind(cursor.id);
+            builder.push("))");
+        }
+        builder.push(format!(
+            " ORDER BY ({key}) {direction}, id {direction} LIMIT "
+        ));
+        builder.push_bind(i64::from(limit) + 1);
+
+        let mut issues: Vec<Issue> = builder.build_query_as().fetch_all(&self.db).await?;
+
+        let next_cursor = if issues.len() > limit as usize {
+            issues.truncate(limit as usize);
+            issues
+                .last()
+                .map(|last| Cursor::after(last, query.sort, query.order).encode())
+                .transpose()?
+        } else {
+            None
+        };
+
+        Ok(IssuePage {
+            issues,
+            next_cursor,
+        })
+    }
+}
+
+fn push_key(builder: &mut QueryBuilder<'_, Sqlite>, key: &CursorKey) {
+    match key {
+        CursorKey::Rank(rank) => builder.push_bind(*rank),
+        CursorKey::Text(text) => builder.push_bind(text.clone()),
+    };
+}
+
+fn status_name(status: IssueStatus) -> &'static str {
+    match status {
+        IssueStatus::Backlog => "backlog",
+        IssueStatus::CurrentRelease => "current_release",
+        IssueStatus::Doing => "doing",
+        IssueStatus::Review => "review",
+        IssueStatus::Done => "done",
+    }
+}
+
+#[cfg(test)]
+#[path = "issue_service_tests.rs"]
+mod issue_service_tests;
@@ -0,0 +1,268 @@
+//! Tests for issue service
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::AppError;
+    use crate::models::{
+        CreateBoardRequest, CreateIssueRequest, IssueListQuery, IssuePriority, IssueSort,
+        IssueStatus, IssueType, SortOrder, UpdateIssueRequest,
+    };
+    use crate::services::{BoardService, IssueService};
+    use sqlx::SqlitePool;
+    use uuid::Uuid;
+
+    async fn create_test_user(pool: &SqlitePool) -> String {
+        let user_id = Uuid::new_v4().to_string();
+        let email = format!("test+{user_id}@example.com");
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, email, hashed_password, provider, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            user_id,
+            email,
+            "hashed_password",
+            "local",
+            now,
+            now
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to create test user");
+
+        user_id
+    }
+
+    async fn create_test_board(pool: &SqlitePool, owner_id: &str) -> String {
+        BoardService::new(pool.clone())
+            .create_board(
+                owner_id,
+                &CreateBoardRequest {
+                    name: "Roadmap".to_string(),
+                    description: None,
+                },
+            )
+            .await
+            .expect("board created")
+            .board
+            .id
+    }
+
+    fn issue_request(
+        title: &str,
+        issue_type: IssueType,
+        priority: IssuePriority,
+    ) -> CreateIssueRequest {
+        CreateIssueRequest {
+            title: title.to_string(),
+            description: Some("Details".to_string()),
+            issue_type,
+            priority,
+            size_estimate: Some(2),
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_issue_crud_is_scoped_to_board_members(pool: SqlitePool) {
+        let service = IssueService::new(pool.clone());
+        let user_id = create_test_user(&pool).await;
+        let outsider_id = create_test_user(&pool).await;
+        let board_id = create_test_board(&pool, &user_id).await;
+
+        let issue = service
+            .create_issue(
+                &user_id,
+                &board_id,
+                &issue_request(" Fix login ", IssueType::Defect, IssuePriority::High),
+            )
+            .await
+            .expect("issue created");
+        assert_eq!(issue.title, "Fix login");
+        assert_eq!(issue.status, IssueStatus::Backlog);
+        assert_eq!(issue.created_by, user_id);
+
+        let foreign = service
+            .create_issue(
+                &outsider_id,
+                &board_id,
+                &issue_request("Sneaky", IssueType::Task, IssuePriority::Low),
+            )
+            .await;
+        assert!(matches!(foreign, Err(AppError::NotFound(_))));
+        let hidden = service.get_issue(&outsider_id, &issue.id).await;
+        assert!(matches!(hidden, Err(AppError::NotFound(_))));
+
+        let updated = service
+            .update_issue(
+                &user_id,
+                &issue.id,
+                &UpdateIssueRequest {
+                    description: Some(String::new()),
+                    priority: Some(IssuePriority::Critical),
+                    ..UpdateIssueRequest::default()
+                },
+            )
+            .await
+            .expect("issue updated");
+        assert_eq!(updated.title, "Fix login");
+        assert_eq!(updated.description, None);
+        assert_eq!(updated.priority, IssuePriority::Critical);
+        assert_eq!(updated.size_estimate, Some(2));
+
+        let negative = service
+            .update_issue(
+                &user_id,
+                &issue.id,
+                &UpdateIssueRequest {
+                    size_estimate: Some(-1),
+                    ..UpdateIssueRequest::default()
+                },
+            )
+            .await;
+        assert!(matches!(negative, Err(AppError::ValidationError(_))));
+
+        let hidden_delete = service.delete_issue(&outsider_id, &issue.id).await;
+        assert!(matches!(hidden_delete, Err(AppError::NotFound(_))));
+        service
+            .delete_issue(&user_id, &issue.id)
+            .await
+            .expect("issue deleted");
+        let gone = service.get_issue(&user_id, &issue.id).await;
+        assert!(matches!(gone, Err(AppError::NotFound(_))));
+    }
+
+    #[sqlx::test]
+    async fn test_issues_follow_the_workflow(pool: SqlitePool) {
+        let service = IssueService::new(pool.clone());
+        let user_id = create_test_user(&pool).await;
+        let board_id = create_test_board(&pool, &user_id).await;
+        let issue = service
+            .create_issue(
+                &user_id,
+                &board_id,
+                &issue_request("Export", IssueType::Feature, IssuePriority::Medium),
+            )
+            .await
+            .expect("issue created");
+
+        let skipped = service
+            .transition_issue(&user_id, &issue.id, IssueStatus::Doing)
+            .await;
+        assert!(matches!(skipped, Err(AppError::ValidationError(_))));
+
+        for status in &IssueStatus::WORKFLOW[1..] {
+            let moved = service
+                .transition_issue(&user_id, &issue.id, *status)
+                .await
+                .expect("issue moved");
+            assert_eq!(moved.status, *status);
+        }
+
+        let reopened = service
+            .transition_issue(&user_id, &issue.id, IssueStatus::Doing)
+            .await
+            .expect("issue reopened");
+        assert_eq!(reopened.status, IssueStatus::Doing);
+    }
+
+    #[sqlx::test]
+    async fn test_list_issues_filters_sorts_and_paginates(pool: SqlitePool) {
+        let service = IssueService::new(pool.clone());
+        let user_id = create_test_user(&pool).await;
+        let board_id = create_test_board(&pool, &user_id).await;
+        let issues = [
+            ("A", IssueType::Feature, IssuePriority::Low),
+            ("B", IssueType::Defect, IssuePriority::Critical),
+            ("C", IssueType::Feature, IssuePriority::High),
+            ("D", IssueType::Task, IssuePriority::High),
+            ("E", IssueType::Feature, IssuePriority::Medium),
+        ];
+        for (title, issue_type, priority) in issues {
+            service
+                .create_issue(
+                    &user_id,
+                    &board_id,
+                    &issue_request(title, issue_type, priority),
+                )
+                .await
+                .expect("issue created");
+        }
+
+        // Newest first by default
+        let all = service
+            .list_issues(&user_id, &board_id, &IssueListQuery::default())
+            .await
+            .expect("issues listed");
+        let titles: Vec<&str> = all.issues.iter().map(|i| i.title.as_str()).collect();
+        assert_eq!(titles, ["E", "D", "C", "B", "A"]);
+        assert!(all.next_cursor.is_none());
+
+        let features = service
+            .list_issues(
+                &user_id,
+                &board_id,
+                &IssueListQuery {
+                    issue_type: Some(IssueType::Feature),
+                    sort: IssueSort::Title,
+                    order: SortOrder::Asc,
+                    ..IssueListQuery::default()
+                },
+            )
+            .await
+            .expect("issues listed");
+        let titles: Vec<&str> = features.issues.iter().map(|i| i.title.as_str()).collect();
+        assert_eq!(titles, ["A", "C", "E"]);
+
+        // Walk the priority ordering two issues at a time
+        let mut query = IssueListQuery {
+            sort: IssueSort::Priority,
+            order: SortOrder::Asc,
+            limit: Some(2),
+            ..IssueListQuery::default()
+        };
+        let mut titles = Vec::new();
+        let mut pages = 0;
+        loop {
+            let page = service
+                .list_issues(&user_id, &board_id, &query)
+                .await
+                .expect("issues listed");
+            pages += 1;
+            titles.extend(page.issues.into_iter().map(|i| i.title));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(pages, 3);
+        let priorities: Vec<&str> = titles.iter().map(String::as_str).collect();
+        assert_eq!(priorities[0], "B");
+        assert_eq!(priorities[4], "A");
+        assert_eq!(priorities.len(), 5);
+
+        let mismatched = service
+            .list_issues(
+                &user_id,
+                &board_id,
+                &IssueListQuery {
+                    cursor: query.cursor.clone(),
+                    ..IssueListQuery::default()
+                },
+            )
+            .await;
+        assert!(matches!(mismatched, Err(AppError::ValidationError(_))));
+        let garbage = service
+            .list_issues(
+                &user_id,
+                &board_id,
+                &IssueListQuery {
+                    cursor: Some("not-a-cursor".to_string()),
+                    ..IssueListQuery::default()
+                },
+            )
+            .await;
+        assert!(matches!(garbage, Err(AppError::ValidationError(_))));
+    }
+}
@@ -0,0 +1,71 @@
+//! Pluggable delivery of account emails
+//!
+//! `Mailer` sends plain-text emails such as verification and password reset
+//! links. `MAIL_TRANSPORT` selects the implementation:
+//!
+//! - `outbox` (default): written to `MAIL_OUTBOX_DIR` (`./outbox`) and logged,
+//!   for development and tests
+//! - `smtp`: sent through an SMTP server, see `SmtpMailer`
+
+mod outbox;
+mod smtp;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::errors::{AppError, AppResult};
+
+pub use outbox::OutboxMailer;
+pub use smtp::{SmtpConfig, SmtpMailer, SmtpSecurity};
+
+/// Default directory of the outbox
+const DEFAULT_OUTBOX_DIR: &str = "./outbox";
+
+/// A plain-text email to one recipient
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Trait that all mail transports must implement
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    /// Get the transport name
+    fn name(&self) -> &'static str;
+
+    /// Send an email
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the email cannot be delivered to the transport
+    async fn send(&self, email: &Email) -> AppResult<()>;
+}
+
+/// Create the mailer selected by `MAIL_TRANSPORT`
+///
+/// # Errors
+///
+/// Returns `AppError::ConfigError` if the transport is unknown or its settings
+/// are missing
+pub fn mailer_from_env() -> AppResult<Arc<dyn Mailer>> {
+    let transport = std::env::var("MAIL_TRANSPORT").unwrap_or_else(|_| "outbox".to_string());
+    match transport.to_ascii_lowercase().as_str() {
+        "outbox" => {
+            let dir =
+                std::env::var("MAIL_OUTBOX_DIR").unwrap_or_else(|_| DEFAULT_OUTBOX_DIR.to_string());
+            Ok(Arc::new(OutboxMailer::new(dir)))
+        }
+        "smtp" => Ok(Arc::new(SmtpMailer::new(SmtpConfig::from_env()?)?)),
+        other => Err(AppError::ConfigError(format!(
+            "Unknown MAIL_TRANSPORT '{other}', expected outbox or smtp"
+        ))),
+    }
+}
+
+/// Error for an email that could not be sent
+fn mail_failed(email: &Email, error: &impl std::fmt::Display) -> AppError {
+    tracing::error!("Failed to send email '{}': {}", email.subject, error);
+    AppError::InternalServerError("Failed to send email".to_string())
+}
@@ -0,0 +1,110 @@
+//! Mail transport that keeps emails on the local filesystem
+
+use async_trait::async_trait;
+use chrono::Utc;
+use std::path::{Path, PathBuf};
+
+use super::{Email, Mailer, mail_failed};
+use crate::errors::AppResult;
+
+/// Writes each email to a file in a directory instead of sending it
+///
+/// Files are named after the time and recipient, so the latest email to an
+/// address is easy to find. Nothing leaves the machine, which makes this the
+/// transport for development and tests.
+#[derive(Debug, Clone)]
+pub struct OutboxMailer {
+    dir: PathBuf,
+}
+
+impl OutboxMailer {
+    /// Create the outbox; the directory is created on first send
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Directory the emails are written to
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// File name of an email, safe for any recipient address
+    fn file_name(email: &Email) -> String {
+        let recipient: String = email
+            .to
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '@' | '.' | '-' | '_' | '+') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!(
+            "{}-{}.eml",
+            Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            recipient
+        )
+    }
+}
+
+#[async_trait]
+impl Mailer for OutboxMailer {
+    fn name(&self) -> &'static str {
+        "outbox"
+    }
+
+    async fn send(&self, email: &Email) -> AppResult<()> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| mail_failed(email, &e))?;
+
+        let path = self.dir.join(Self::file_name(email));
+        let message = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            email.to, email.subject, email.body
+        );
+        tokio::fs::write(&path, message)
+            .await
+            .map_err(|e| mail_failed(email, &e))?;
+
+        tracing::info!(
+            "Wrote email '{}' for {} to {}",
+            email.subject,
+            email.to,
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_outbox_writes_email() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let mailer = OutboxMailer::new(dir.path().join("outbox"));
+        let email = Email {
+            to: "user/../x@example.com".to_string(),
+            subject: "Hello".to_string(),
+            body: "Line one\nLine two".to_string(),
+        };
+
+        mailer.send(&email).await.expect("email written");
+
+        let mut entries = std::fs::read_dir(mailer.dir()).expect("outbox exists");
+        let entry = entries.next().expect("one email").expect("readable entry");
+        let name = entry.file_name().into_string().expect("utf-8 name");
+        assert!(name.ends_with("-user_.._x@example.com.eml"), "{name}");
+        let message = std::fs::read_to_string(entry.path()).expect("readable email");
+        assert_eq!(
+            message,
+            "To: user/../x@example.com\nSubject: Hello\n\nLine one\nLine two\n"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! Mail transport through an SMTP server
+//!
+//! Configured with:
+//!
+//! ```text
+//! MAIL_TRANSPORT="smtp"
+//! MAIL_FROM="Kanbain <no-reply@example.com>"
+//! SMTP_HOST="smtp.example.com"
+//! SMTP_PORT="587"             # optional, defaults to the port of SMTP_SECURITY
+//! SMTP_SECURITY="starttls"    # starttls (default), tls or none
+//! SMTP_USERNAME="..."         # optional
+//! SMTP_PASSWORD="..."         # optional
+//! ```
+
+use async_trait::async_trait;
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+
+use super::{Email, Mailer, mail_failed};
+use crate::errors::{AppError, AppResult};
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection with STARTTLS, which the server must offer
+    StartTls,
+    /// TLS from the start of the connection
+    Tls,
+    /// No encryption, for local relays only
+    None,
+}
+
+impl SmtpSecurity {
+    fn default_port(self) -> u16 {
+        match self {
+            Self::StartTls => 587,
+            Self::Tls => 465,
+            Self::None => 25,
+        }
+    }
+}
+
+/// Settings of an SMTP server
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender of all emails, such as `Kanbain <no-reply@example.com>`
+    pub from: String,
+}
+
+impl SmtpConfig {
+    /// Read the settings from the environment
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ConfigError` if `SMTP_HOST` or `MAIL_FROM` is missing,
+    /// or a setting is invalid
+    pub fn from_env() -> AppResult<Self> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let required = |name: &str| {
+            var(name).ok_or_else(|| {
+                AppError::ConfigError(format!("{name} is required when MAIL_TRANSPORT is smtp"))
+            })
+        };
+
+        let security = match var("SMTP_SECURITY").as_deref() {
+            None | Some("starttls") => SmtpSecurity::StartTls,
+            Some("tls") => SmtpSecurity::Tls,
+            Some("none") => SmtpSecurity::None,
+            Some(other) => {
+                return Err(AppError::ConfigError(format!(
+                    "Unknown SMTP_SECURITY '{other}', expected starttls, tls or none"
+                )));
+            }
+        };
+        let port = var("SMTP_PORT")
+            .map(|port| {
+                port.parse()
+                    .map_err(|_| AppError::ConfigError(format!("Invalid SMTP_PORT '{port}'")))
+            })
+            .transpose()?
+            .unwrap_or_else(|| security.default_port());
+
+        Ok(Self {
+            host: required("SMTP_HOST")?,
+            port,
+            security,
+            username: var("SMTP_USERNAME"),
+            password: var("SMTP_PASSWORD"),
+            from: required("MAIL_FROM")?,
+        })
+    }
+}
+
+/// Sends emails through an SMTP server
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// Create the transport; no connection is made until the first email
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ConfigError` if the sender is not a valid address,
+    /// only one of username and password is set, or the TLS settings fail
+    pub fn new(config: SmtpConfig) -> AppResult<Self> {
+        let from = config
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| AppError::ConfigError(format!("Invalid MAIL_FROM: {e}")))?;
+
+        let builder = match config.security {
+            SmtpSecurity::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+            }
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host),
+            SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                &config.host,
+            )),
+        }
+        .map_err(|e| AppError::ConfigError(format!("Invalid SMTP settings: {e}")))?
+        .port(config.port);
+
+        let builder = match (config.username, config.password) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username, password))
+            }
+            (None, None) => builder,
+            _ => {
+                return Err(AppError::ConfigError(
+                    "SMTP_USERNAME and SMTP_PASSWORD must be set together".to_string(),
+                ));
+            }
+        };
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn send(&self, email: &Email) -> AppResult<()> {
+        let to = email
+            .to
+            .parse::<Mailbox>()
+            .map_err(|e| AppError::BadRequest(format!("Invalid email address: {e}")))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())
+            .map_err(|e| mail_failed(email, &e))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| mail_failed(email, &e))?;
+
+        tracing::info!("Sent email '{}' to {}", email.subject, email.to);
+        Ok(())
+    }
+}
@@ -0,0 +1,567 @@
+//! Two-factor authentication service
+//!
+//! Enrolls TOTP authenticator apps, checks their codes and the one-time recovery
+//! codes, tracks the `mfa_pending` challenges of two-step sign-ins, and keeps the
+//! roles that require a second factor. See `models::mfa`.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{Duration, Utc};
+use sqlx::SqlitePool;
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use crate::{
+    core::token_utils::{generate_opaque_token, hash_token},
+    errors::{AppError, AppResult},
+    models::{
+        mfa::{MfaEnrollment, MfaStatus},
+        role::Role,
+    },
+};
+
+/// Name authenticator apps show next to the account
+const TOTP_ISSUER: &str = "Kanbain";
+
+/// Digits in a code
+const TOTP_DIGITS: usize = 6;
+
+/// Seconds each code is valid for
+const TOTP_STEP_SECONDS: u64 = 30;
+
+/// Size of generated secrets, as recommended by RFC 4226
+const TOTP_SECRET_BYTES: usize = 20;
+
+/// Codes of this many steps before and after the current one are accepted, to
+/// allow for clock drift
+const TOTP_SKEW_STEPS: u64 = 1;
+
+/// Recovery codes generated at a time
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Random bytes in a recovery code, which encode to 16 base32 characters
+const RECOVERY_CODE_BYTES: usize = 10;
+
+/// Lifetime of `mfa_pending` tokens
+pub const MFA_CHALLENGE_TTL_SECONDS: i64 = 300;
+
+/// Invalid codes an `mfa_pending` token survives
+const MAX_CHALLENGE_ATTEMPTS: i64 = 5;
+
+fn invalid_code() -> AppError {
+    AppError::Unauthorized("Invalid two-factor authentication code".to_string())
+}
+
+fn invalid_challenge() -> AppError {
+    AppError::Unauthorized("Sign-in expired, please sign in again".to_string())
+}
+
+fn not_enabled() -> AppError {
+    AppError::BadRequest("Two-factor authentication is not enabled".to_string())
+}
+
+fn parse_user_id(user_id: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(user_id)
+        .map_err(|_| AppError::InternalServerError("Invalid user ID in database".to_string()))
+}
+
+/// Authenticator for a base32 secret
+fn totp(secret: &str, account_name: &str) -> AppResult<TOTP> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| AppError::InternalServerError(format!("Invalid TOTP secret: {e:?}")))?;
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP_SECONDS,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        account_name.to_string(),
+    )
+    .map_err(|e| AppError::InternalServerError(format!("Invalid TOTP settings: {e}")))
+}
+
+/// Time step a code matches, if it is valid now
+fn matching_step(totp: &TOTP, code: &str) -> Option<u64> {
+    let now = u64::try_from(Utc::now().timestamp()).ok()?;
+    let current = now / TOTP_STEP_SECONDS;
+
+    (current.saturating_sub(TOTP_SKEW_STEPS)..=current + TOTP_SKEW_STEPS)
+        .find(|step| totp.check(code, step * TOTP_STEP_SECONDS))
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Recovery code as stored, ignoring case, spaces and dashes
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Random recovery code, such as `abcd-efgh-ijkl-mnop`
+fn generate_recovery_code() -> String {
+    let mut bytes = [0u8; RECOVERY_CODE_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    let encoded = Secret::Raw(bytes.to_vec()).to_encoded().to_string();
+
+    encoded
+        .to_ascii_lowercase()
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+pub struct MfaService {
+    db: SqlitePool,
+}
+
+impl MfaService {
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Whether the user has two-factor authentication enabled
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn is_enabled(&self, user_id: Uuid) -> AppResult<bool> {
+        let user_id = user_id.to_string();
+        let enabled = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM user_mfa WHERE user_id = ?1 AND enabled_at IS NOT NULL",
+            user_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+        Ok(enabled > 0)
+    }
+
+    /// The user's two-factor authentication status
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn status(&self, user_id: Uuid, roles: &[Role]) -> AppResult<MfaStatus> {
+        let enabled = self.is_enabled(user_id).await?;
+        let user_id = user_id.to_string();
+        let recovery_codes_remaining = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM mfa_recovery_codes WHERE user_id = ?1 AND used_at IS NULL",
+            user_id
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(MfaStatus {
+            enabled,
+            required: self.is_required_for(roles).await?,
+            recovery_codes_remaining,
+        })
+    }
+
+    /// Generate a new secret for the user to add to their authenticator app
+    ///
+    /// The factor is not used until `enable` confirms a code from the app.
+    /// Starting again replaces the secret of an unfinished enrollment.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::BadRequest` if two-factor authentication is already
+    /// enabled, or `AppError` if the database operation fails
+    pub async fn start_enrollment(&self, user_id: Uuid, email: &str) -> AppResult<MfaEnrollment> {
+        if self.is_enabled(user_id).await? {
+            return Err(AppError::BadRequest(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let mut bytes = [0u8; TOTP_SECRET_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+        let secret = Secret::Raw(bytes.to_vec()).to_encoded().to_string();
+        let otpauth_uri = totp(&secret, email)?.get_url();
+
+        let user_id = user_id.to_string();
+        let now = Utc::now();
+        sqlx::query!(
+            r#"
+            INSERT INTO user_mfa (user_id, secret, created_at) VALUES (?1, ?2, ?3)
+            ON CONFLICT(user_id) DO UPDATE
+            SET secret = excluded.secret, created_at = excluded.created_at, last_used_step = NULL
+            WHERE user_mfa.enabled_at IS NULL
+            "#,
+            user_id,
+            secret,
+            now
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(MfaEnrollment {
+            secret,
+            otpauth_uri,
+        })
+    }
+
+    /// Enable two-factor authentication with a code from the enrolled app
+    ///
+    /// Returns the user's recovery codes, which are not shown again.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::BadRequest` if no enrollment was started,
+    /// `AppError::Unauthorized` if the code is invalid, or `AppError` if the
+    /// database operation fails
+    pub async fn enable(&self, user_id: Uuid, code: &str) -> AppResult<Vec<String>> {
+        let user_id_str = user_id.to_string();
+        let secret = sqlx::query_scalar!(
+            "SELECT secret FROM user_mfa WHERE user_id = ?1 AND enabled_at IS NULL",
+            user_id_str
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| {
+            AppError::BadRequest("Start enrolling an authenticator app first".to_string())
+        })?;
+
+        if !self.accept_totp(&user_id_str, &secret, code.trim()).await? {
+            return Err(invalid_code());
+        }
+
+        let now = Utc::now();
+        let mut tx = self.db.begin().await?;
+        sqlx::query!(
+            "UPDATE user_mfa SET enabled_at = ?1 WHERE user_id = ?2",
+            now,
+            user_id_str
+        )
+        .execute(&mut *tx)
+        .await?;
+        let codes = Self::replace_recovery_codes(&mut tx, &user_id_str).await?;
+        tx.commit().await?;
+
+        tracing::info!("Enabled two-factor authentication for user {}", user_id);
+        Ok(codes)
+    }
+
+    /// Turn two-factor authentication off and delete the secret and recovery codes
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn disable(&self, user_id: Uuid) -> AppResult<()> {
+        let user_id_str = user_id.to_string();
+        let mut tx = self.db.begin().await?;
+        sqlx::query!("DELETE FROM user_mfa WHERE user_id = ?1", user_id_str)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "DELETE FROM mfa_recovery_codes WHERE user_id = ?1",
+            user_id_str
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::info!("Disabled two-factor authentication for user {}", user_id);
+        Ok(())
+    }
+
+    /// Check a code from the user's authenticator app or one of their recovery codes
+    ///
+    /// Each code is accepted once: app codes are not accepted again and recovery
+    /// codes are used up.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::BadRequest` if two-factor authentication is not
+    /// enabled, `AppError::Unauthorized` if the code is invalid, or `AppError`
+    /// if the database operation fails
+    pub async fn verify(&self, user_id: Uuid, code: &str) -> AppResult<()> {
+        let user_id = user_id.to_string();
+        let secret = sqlx::query_scalar!(
+            "SELECT secret FROM user_mfa WHERE user_id = ?1 AND enabled_at IS NOT NULL",
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(not_enabled)?;
+
+        let code = code.trim();
+        let accepted = if is_totp_code(code) {
+            self.accept_totp(&user_id, &secret, code).await?
+        } else {
+            self.use_recovery_code(&user_id, code).await?
+        };
+
+        if accepted {
+            Ok(())
+        } else {
+            Err(invalid_code())
+        }
+    }
+
+    /// Replace the user's recovery codes with new ones
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::BadRequest` if two-factor authentication is not
+    /// enabled, or `AppError` if the database operation fails
+    pub async fn regenerate_recovery_codes(&self, user_id: Uuid) -> AppResult<Vec<String>> {
+        if !self.is_enabled(user_id).await? {
+            return Err(not_enabled());
+        }
+
+        let user_id = user_id.to_string();
+        let mut tx = self.db.begin().await?;
+        let codes = Self::replace_recovery_codes(&mut tx, &user_id).await?;
+        tx.commit().await?;
+        Ok(codes)
+    }
+
+    /// Start the second step of a sign-in and return its `mfa_pending` token
+    ///
+    /// Expired challenges of all users are deleted along the way.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn create_challenge(&self, user_id: Uuid) -> AppResult<String> {
+        let token = generate_opaque_token();
+        let token_hash = hash_token(&token);
+        let user_id = user_id.to_string();
+        let now = Utc::now();
+        let expires_at = now + Duration::seconds(MFA_CHALLENGE_TTL_SECONDS);
+
+        sqlx::query!("DELETE FROM mfa_challenges WHERE expires_at < ?1", now)
+            .execute(&self.db)
+            .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO mfa_challenges (token_hash, user_id, expires_at, created_at)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+            token_hash,
+            user_id,
+            expires_at,
+            now
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Complete a sign-in with its `mfa_pending` token and a code
+    ///
+    /// Returns the user signing in. The token works once, and stops working
+    /// after `MAX_CHALLENGE_ATTEMPTS` invalid codes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Unauthorized` if the token is invalid, expired or used,
+    /// or the code is invalid, or `AppError` if the database operation fails
+    pub async fn complete_challenge(&self, token: &str, code: &str) -> AppResult<Uuid> {
+        let token_hash = hash_token(token);
+        let now = Utc::now();
+
+        let user_id = sqlx::query_scalar!(
+            r#"
+            SELECT user_id FROM mfa_challenges
+            WHERE token_hash = ?1 AND used_at IS NULL AND expires_at > ?2 AND failed_attempts < ?3
+            "#,
+            token_hash,
+            now,
+            MAX_CHALLENGE_ATTEMPTS
+        )
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(invalid_challenge)?;
+        let user_id = parse_user_id(&user_id)?;
+
+        if let Err(e) = self.verify(user_id, code).await {
+            if matches!(e, AppError::Unauthorized(_)) {
+                sqlx::query!(
+                    "UPDATE mfa_challenges SET failed_attempts = failed_attempts + 1 WHERE token_hash = ?1",
+                    token_hash
+                )
+                .execute(&self.db)
+                .await?;
+            }
+            return Err(e);
+        }
+
+        let claimed = sqlx::query!(
+            "UPDATE mfa_challenges SET used_at = ?1 WHERE token_hash = ?2 AND used_at IS NULL",
+            now,
+            token_hash
+        )
+        .execute(&self.db)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Err(invalid_challenge());
+        }
+
+        Ok(user_id)
+    }
+
+    /// Roles whose holders must use two-factor authentication
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn required_roles(&self) -> AppResult<Vec<Role>> {
+        let rows = sqlx::query_scalar!("SELECT role FROM role_mfa_requirements ORDER BY role")
+            .fetch_all(&self.db)
+            .await?;
+
+        rows.iter()
+            .map(|role| role.parse().map_err(AppError::InternalServerError))
+            .collect()
+    }
+
+    /// Replace the roles that require two-factor authentication
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn set_required_roles(&self, roles: &[Role], set_by: Uuid) -> AppResult<()> {
+        let set_by = set_by.to_string();
+        let now = Utc::now();
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query!("DELETE FROM role_mfa_requirements")
+            .execute(&mut *tx)
+            .await?;
+        for role in roles {
+            let role = role.as_str();
+            sqlx::query!(
+                r#"
+                INSERT OR IGNORE INTO role_mfa_requirements (role, required_by, created_at)
+                VALUES (?1, ?2, ?3)
+                "#,
+                role,
+                set_by,
+                now
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        tracing::info!(
+            "Two-factor authentication now required for roles {:?}",
+            roles
+        );
+        Ok(())
+    }
+
+    /// Whether any of the roles requires two-factor authentication
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn is_required_for(&self, roles: &[Role]) -> AppResult<bool> {
+        if roles.is_empty() {
+            return Ok(false);
+        }
+        let required = self.required_roles().await?;
+        Ok(roles.iter().any(|role| required.contains(role)))
+    }
+
+    /// Check that a user whose roles require two-factor authentication has it enabled
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Forbidden` if the user must but does not use two-factor
+    /// authentication, or `AppError` if the database query fails
+    pub async fn check_requirement(&self, user_id: Uuid, roles: &[Role]) -> AppResult<()> {
+        if self.is_required_for(roles).await? && !self.is_enabled(user_id).await? {
+            return Err(AppError::Forbidden(
+                "Two-factor authentication is required for your role, enable it to continue"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Accept a code from the app unless it, or a later one, was accepted before
+    async fn accept_totp(&self, user_id: &str, secret: &str, code: &str) -> AppResult<bool> {
+        let Some(step) = matching_step(&totp(secret, "")?, code) else {
+            return Ok(false);
+        };
+        let step = i64::try_from(step)
+            .map_err(|_| AppError::InternalServerError("Invalid TOTP time step".to_string()))?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_mfa SET last_used_step = ?1
+            WHERE user_id = ?2 AND (last_used_step IS NULL OR last_used_step < ?1)
+            "#,
+            step,
+            user_id
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Use up one of the user's recovery codes
+    async fn use_recovery_code(&self, user_id: &str, code: &str) -> AppResult<bool> {
+        let code_hash = hash_token(&normalize_recovery_code(code));
+        let now = Utc::now();
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE mfa_recovery_codes SET used_at = ?1
+            WHERE code_hash = ?2 AND user_id = ?3 AND used_at IS NULL
+            "#,
+            now,
+            code_hash,
+            user_id
+        )
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() == 1 {
+            tracing::info!("User {} used a recovery code", user_id);
+        }
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn replace_recovery_codes(
+        tx: &mut sqlx::SqliteConnection,
+        user_id: &str,
+    ) -> AppResult<Vec<String>> {
+        sqlx::query!("DELETE FROM mfa_recovery_codes WHERE user_id = ?1", user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let now = Utc::now();
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect();
+        for code in &codes {
+            let code_hash = hash_token(&normalize_recovery_code(code));
+            sqlx::query!(
+                "INSERT INTO mfa_recovery_codes (code_hash, user_id, created_at) VALUES (?1, ?2, ?3)",
+                code_hash,
+                user_id,
+                now
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        Ok(codes)
+    }
+}
+
+#[cfg(test)]
+#[path = "mfa_service_tests.rs"]
+mod mfa_service_tests;
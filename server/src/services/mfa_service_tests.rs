@@ -0,0 +1,280 @@
+//! Tests for the two-factor authentication service
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::AppError;
+    use crate::handlers::auth_handler::RegisterUserPayload;
+    use crate::models::{Role, User};
+    use crate::services::{MfaService, UserServiceImpl};
+    use chrono::Utc;
+    use sqlx::SqlitePool;
+    use totp_rs::{Algorithm, Secret, TOTP};
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect("sqlite::memory:")
+            .await
+            .expect("Failed to create test database");
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .expect("Failed to run migrations");
+
+        pool
+    }
+
+    async fn create_user(pool: &SqlitePool, email: &str) -> User {
+        UserServiceImpl::new(pool.clone())
+            .create_user(&RegisterUserPayload {
+                email: email.to_string(),
+                password: "secure_password_123".to_string(),
+            })
+            .await
+            .expect("Failed to create test user")
+    }
+
+    /// Code the authenticator app shows `offset_steps` steps from now
+    fn app_code(secret: &str, offset_steps: i64) -> String {
+        let totp = TOTP::new(
+            Algorithm::SHA1,
+            6,
+            0,
+            30,
+            Secret::Encoded(secret.to_string())
+                .to_bytes()
+                .expect("base32 secret"),
+            None,
+            String::new(),
+        )
+        .expect("valid TOTP");
+        let time = u64::try_from(Utc::now().timestamp() + offset_steps * 30).expect("time");
+        totp.generate(time)
+    }
+
+    /// Enable MFA for a user and return the secret and recovery codes
+    async fn enable(service: &MfaService, user: &User) -> (String, Vec<String>) {
+        let enrollment = service
+            .start_enrollment(user.id, &user.email)
+            .await
+            .expect("enrollment started");
+        let codes = service
+            .enable(user.id, &app_code(&enrollment.secret, 0))
+            .await
+            .expect("MFA enabled");
+        (enrollment.secret, codes)
+    }
+
+    #[tokio::test]
+    async fn test_enrollment_is_verified_before_enabling() {
+        let pool = setup_test_db().await;
+        let service = MfaService::new(pool.clone());
+        let user = create_user(&pool, "enroll@example.com").await;
+
+        assert!(matches!(
+            service.enable(user.id, "123456").await,
+            Err(AppError::BadRequest(_))
+        ));
+
+        let enrollment = service
+            .start_enrollment(user.id, &user.email)
+            .await
+            .expect("enrollment started");
+        assert!(
+            enrollment
+                .otpauth_uri
+                .starts_with("otpauth://totp/Kanbain:")
+        );
+        assert!(enrollment.otpauth_uri.contains(&enrollment.secret));
+        assert!(!service.is_enabled(user.id).await.expect("status"));
+
+        // A wrong code leaves the factor disabled
+        let wrong = if app_code(&enrollment.secret, 0) == "000000" {
+            "111111"
+        } else {
+            "000000"
+        };
+        assert!(matches!(
+            service.enable(user.id, wrong).await,
+            Err(AppError::Unauthorized(_))
+        ));
+        assert!(!service.is_enabled(user.id).await.expect("status"));
+
+        let codes = service
+            .enable(user.id, &app_code(&enrollment.secret, 0))
+            .await
+            .expect("MFA enabled");
+        assert_eq!(codes.len(), 10);
+        assert!(service.is_enabled(user.id).await.expect("status"));
+        assert!(matches!(
+            service.start_enrollment(user.id, &user.email).await,
+            Err(AppError::BadRequest(_))
+        ));
+
+        // Recovery codes are stored hashed
+        let stored: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM mfa_recovery_codes WHERE code_hash = ?1")
+                .bind(&codes[0])
+                .fetch_one(&pool)
+                .await
+                .expect("count");
+        assert_eq!(stored, 0);
+    }
+
+    #[tokio::test]
+    async fn test_codes_are_accepted_once() {
+        let pool = setup_test_db().await;
+        let service = MfaService::new(pool.clone());
+        let user = create_user(&pool, "codes@example.com").await;
+        let (secret, recovery_codes) = enable(&service, &user).await;
+
+        // The code used to enable cannot be replayed, but the next one works
+        assert!(matches!(
+            service.verify(user.id, &app_code(&secret, 0)).await,
+            Err(AppError::Unauthorized(_))
+        ));
+        service
+            .verify(user.id, &app_code(&secret, 1))
+            .await
+            .expect("next code accepted");
+        assert!(matches!(
+            service.verify(user.id, &app_code(&secret, 3)).await,
+            Err(AppError::Unauthorized(_))
+        ));
+
+        // Recovery codes work once, in any case and with or without dashes
+        let code = recovery_codes[0].to_uppercase().replace('-', " ");
+        service.verify(user.id, &code).await.expect("recovery code");
+        assert!(matches!(
+            service.verify(user.id, &recovery_codes[0]).await,
+            Err(AppError::Unauthorized(_))
+        ));
+        let status = service.status(user.id, &[]).await.expect("status");
+        assert_eq!(status.recovery_codes_remaining, 9);
+
+        let new_codes = service
+            .regenerate_recovery_codes(user.id)
+            .await
+            .expect("regenerated");
+        assert!(matches!(
+            service.verify(user.id, &recovery_codes[1]).await,
+            Err(AppError::Unauthorized(_))
+        ));
+        service
+            .verify(user.id, &new_codes[1])
+            .await
+            .expect("new recovery code");
+
+        service.disable(user.id).await.expect("disabled");
+        assert!(matches!(
+            service.verify(user.id, &new_codes[2]).await,
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_challenges() {
+        let pool = setup_test_db().await;
+        let service = MfaService::new(pool.clone());
+        let user = create_user(&pool, "challenge@example.com").await;
+        let (_, recovery_codes) = enable(&service, &user).await;
+
+        let token = service.create_challenge(user.id).await.expect("challenge");
+        assert_eq!(
+            service
+                .complete_challenge(&token, &recovery_codes[0])
+                .await
+                .expect("signed in"),
+            user.id
+        );
+        assert!(matches!(
+            service.complete_challenge(&token, &recovery_codes[1]).await,
+            Err(AppError::Unauthorized(_))
+        ));
+
+        // Too many invalid codes end the challenge
+        let token = service.create_challenge(user.id).await.expect("challenge");
+        for _ in 0..5 {
+            assert!(matches!(
+                service.complete_challenge(&token, "not-a-code").await,
+                Err(AppError::Unauthorized(_))
+            ));
+        }
+        assert!(matches!(
+            service.complete_challenge(&token, &recovery_codes[1]).await,
+            Err(AppError::Unauthorized(_))
+        ));
+
+        // Expired challenges do not work
+        let token = service.create_challenge(user.id).await.expect("challenge");
+        sqlx::query("UPDATE mfa_challenges SET expires_at = datetime('now', '-1 minute')")
+            .execute(&pool)
+            .await
+            .expect("expired");
+        assert!(matches!(
+            service.complete_challenge(&token, &recovery_codes[1]).await,
+            Err(AppError::Unauthorized(_))
+        ));
+        service
+            .verify(user.id, &recovery_codes[1])
+            .await
+            .expect("recovery code still unused");
+    }
+
+    #[tokio::test]
+    async fn test_role_requirements() {
+        let pool = setup_test_db().await;
+        let service = MfaService::new(pool.clone());
+        let user = create_user(&pool, "required@example.com").await;
+        let admin = create_user(&pool, "policy-admin@example.com").await;
+
+        assert!(service.required_roles().await.expect("roles").is_empty());
+        service
+            .check_requirement(user.id, &[Role::Admin])
+            .await
+            .expect("not required yet");
+
+        service
+            .set_required_roles(&[Role::Admin], admin.id)
+            .await
+            .expect("policy set");
+        assert_eq!(
+            service.required_roles().await.expect("roles"),
+            vec![Role::Admin]
+        );
+        assert!(
+            service
+                .is_required_for(&[Role::Admin])
+                .await
+                .expect("required")
+        );
+        assert!(
+            !service
+                .is_required_for(&[Role::InviteManager])
+                .await
+                .expect("not required")
+        );
+        assert!(matches!(
+            service.check_requirement(user.id, &[Role::Admin]).await,
+            Err(AppError::Forbidden(_))
+        ));
+
+        enable(&service, &user).await;
+        service
+            .check_requirement(user.id, &[Role::Admin])
+            .await
+            .expect("MFA enabled");
+        assert!(
+            service
+                .status(user.id, &[Role::Admin])
+                .await
+                .expect("status")
+                .required
+        );
+
+        service
+            .set_required_roles(&[], admin.id)
+            .await
+            .expect("policy cleared");
+        assert!(service.required_roles().await.expect("roles").is_empty());
+    }
+}
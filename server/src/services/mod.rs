@@ -1,21 +1,52 @@
 // kanbain/server/src/services/mod.rs
 
+pub mod account_email_service;
 pub mod ai_data_service;
 pub mod ai_service;
+pub mod ai_session_service;
 pub mod auth_service;
+pub mod board_service;
+pub mod cli_auth_service;
+pub mod context_service;
+pub mod document_service;
+pub mod embedding_service;
+pub mod file_service;
+pub mod identity_service;
 pub mod invite_service;
+pub mod issue_service;
+pub mod mail;
+pub mod mfa_service;
 pub mod oauth_service;
+pub mod oidc_client;
 pub mod payment;
+pub mod quota_service;
+pub mod role_service;
+pub mod session_service;
+pub mod storage;
 pub mod user_service;
 
 #[cfg(test)]
 mod tests;
 
 // Re-export for convenience
+pub use account_email_service::AccountEmailService;
 pub use ai_data_service::AiDataService;
 pub use ai_service::AiService;
+pub use ai_session_service::AiSessionService;
 pub use auth_service::AuthService;
+pub use board_service::BoardService;
+pub use cli_auth_service::CliAuthService;
+pub use context_service::ContextService;
+pub use document_service::DocumentService;
+pub use embedding_service::EmbeddingService;
+pub use file_service::FileService;
+pub use identity_service::IdentityService;
 pub use invite_service::InviteService;
+pub use issue_service::IssueService;
+pub use mfa_service::MfaService;
 pub use oauth_service::OAuthService;
 pub use payment::PaymentService;
+pub use quota_service::QuotaService;
+pub use role_service::RoleService;
+pub use session_service::SessionService;
 pub use user_service::UserServiceImpl;
@@ -1,17 +1,33 @@
 use chrono::{DateTime, Duration, Utc};
-use oauth2::{AuthorizationCode, TokenResponse, basic::BasicTokenResponse, reqwest};
+use oauth2::{
+    AuthorizationCode, CsrfToken, PkceCodeChallenge, TokenResponse, basic::BasicTokenResponse,
+    reqwest,
+};
 use reqwest::Client;
 use sqlx::SqlitePool;
+use uuid::Uuid;
 
 use crate::{
     config::OAuthConfig,
+    core::token_utils::{generate_opaque_token, hash_token},
     errors::AppError,
-    models::oauth::{GitHubEmail, GitHubUserInfo, GoogleUserInfo, OAuthProvider, OAuthUserInfo},
+    models::oauth::{
+        GitHubEmail, GitHubUserInfo, GoogleUserInfo, OAuthLoginGrant, OAuthProvider, OAuthUserInfo,
+        ValidatedOAuthState,
+    },
+    services::oidc_client::OidcClient,
 };
 
+/// Lifetime of the one-time code handing a sign-in over to the client
+pub const OAUTH_LOGIN_CODE_TTL_SECONDS: i64 = 60;
+
+/// Lifetime of the state of an authorization request
+pub const OAUTH_STATE_TTL_MINUTES: i64 = 10;
+
 pub struct OAuthService {
     config: OAuthConfig,
     http_client: Client,
+    oidc: OidcClient,
     db_pool: SqlitePool,
 }
 
@@ -22,14 +38,91 @@ impl OAuthService {
     ///
     /// Returns an error if OAuth configuration fails
     pub fn new(db_pool: SqlitePool) -> Result<Self, AppError> {
-        let config = OAuthConfig::new()?;
+        Ok(Self::with_config(OAuthConfig::new()?, db_pool))
+    }
+
+    /// Creates an OAuth service with the given configuration
+    #[must_use]
+    pub fn with_config(config: OAuthConfig, db_pool: SqlitePool) -> Self {
         let http_client = Client::new();
 
-        Ok(Self {
+        Self {
             config,
+            oidc: OidcClient::new(http_client.clone()),
             http_client,
             db_pool,
-        })
+        }
+    }
+
+    /// Find a provider by the name used in routes
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if no provider of that name is configured
+    pub fn provider(&self, name: &str) -> Result<OAuthProvider, AppError> {
+        match name {
+            "google" => Ok(OAuthProvider::Google),
+            "github" if self.config.github_client_id.is_some() => Ok(OAuthProvider::GitHub),
+            _ if self.config.oidc_provider(name).is_some() => {
+                Ok(OAuthProvider::Oidc(name.to_string()))
+            }
+            _ => Err(AppError::NotFound(format!(
+                "Unknown OAuth provider: {name}"
+            ))),
+        }
+    }
+
+    /// Store a new OAuth state and build the provider URL to send the user to
+    ///
+    /// States stored with `link_user_id` link the provider account to that user
+    /// instead of signing in. OIDC requests are sent with PKCE and a
+    /// nonce, which are stored with the state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state cannot be stored or an OIDC
+    /// provider cannot be reached
+    pub async fn authorization_url(
+        &self,
+        provider: &OAuthProvider,
+        state: &str,
+        link_user_id: Option<Uuid>,
+    ) -> Result<String, AppError> {
+        let link_user_id = link_user_id.map(|user_id| user_id.to_string());
+        let OAuthProvider::Oidc(name) = provider else {
+            self.insert_oauth_state(state, provider, None, link_user_id.as_deref(), None, None)
+                .await?;
+            return Ok(match provider {
+                OAuthProvider::GitHub => self.get_github_auth_url(state),
+                _ => self.get_google_auth_url(state),
+            });
+        };
+
+        let oidc_provider = self.oidc_provider(name)?;
+        let (code_challenge, code_verifier) = PkceCodeChallenge::new_random_sha256();
+        let nonce = CsrfToken::new_random();
+        let url = self
+            .oidc
+            .authorization_url(
+                oidc_provider,
+                &oidc_provider.redirect_uri(&self.config.server_url),
+                state,
+                code_challenge.as_str(),
+                nonce.secret(),
+            )
+            .await?;
+
+        self.insert_oauth_state(
+            state,
+            provider,
+            None,
+            link_user_id.as_deref(),
+            Some(code_verifier.secret()),
+            Some(nonce.secret()),
+        )
+        .await?;
+
+        Ok(url)
     }
 
     /// Get Google OAuth authorization URL
@@ -208,48 +301,94 @@ impl OAuthService {
             AppError::InternalServerError("Invalid user information format".to_string())
         })?;
 
-        // If email is not public, fetch it separately
-        let email = if let Some(email) = user_info.email.clone() {
-            email
-        } else {
-            // Fetch emails endpoint
-            let emails_response = self
-                .http_client
-                .get("https://api.github.com/user/emails")
-                .header("Accept", "application/vnd.github.v3+json")
-                .header("User-Agent", "kanbain-oauth")
-                .bearer_auth(access_token)
-                .send()
-                .await
-                .map_err(|e| {
-                    tracing::error!("Failed to fetch GitHub emails: {:?}", e);
-                    AppError::InternalServerError("Failed to fetch email information".to_string())
-                })?;
-
-            if !emails_response.status().is_success() {
-                tracing::error!(
-                    "GitHub emails API returned error: {}",
-                    emails_response.status()
-                );
-                return Err(AppError::InternalServerError(
-                    "Failed to fetch email information".to_string(),
-                ));
-            }
-
-            let emails: Vec<GitHubEmail> = emails_response.json().await.map_err(|e| {
-                tracing::error!("Failed to parse GitHub emails: {:?}", e);
-                AppError::InternalServerError("Invalid email information format".to_string())
+        // A public email is not necessarily verified, so the account's emails
+        // are always fetched to find out
+        let emails_response = self
+            .http_client
+            .get("https://api.github.com/user/emails")
+            .header("Accept", "application/vnd.github.v3+json")
+            .header("User-Agent", "kanbain-oauth")
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to fetch GitHub emails: {:?}", e);
+                AppError::InternalServerError("Failed to fetch email information".to_string())
             })?;
 
+        if !emails_response.status().is_success() {
+            tracing::error!(
+                "GitHub emails API returned error: {}",
+                emails_response.status()
+            );
+            return Err(AppError::InternalServerError(
+                "Failed to fetch email information".to_string(),
+            ));
+        }
+
+        let emails: Vec<GitHubEmail> = emails_response.json().await.map_err(|e| {
+            tracing::error!("Failed to parse GitHub emails: {:?}", e);
+            AppError::InternalServerError("Invalid email information format".to_string())
+        })?;
+
+        let (email, email_verified) = if let Some(email) = user_info.email.clone() {
+            let verified = emails
+                .iter()
+                .any(|e| e.verified && e.email.eq_ignore_ascii_case(&email));
+            (email, verified)
+        } else {
             // Find primary verified email
             emails
                 .into_iter()
                 .find(|e| e.primary && e.verified)
-                .map(|e| e.email)
+                .map(|e| (e.email, true))
                 .ok_or_else(|| AppError::Forbidden("No verified primary email found".to_string()))?
         };
 
-        Ok(user_info.into_oauth_user_info(email))
+        Ok(user_info.into_oauth_user_info(email, email_verified))
+    }
+
+    /// Exchange an OIDC authorization code for the user's verified information
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if:
+    /// - The provider is not configured or the state has no PKCE verifier or nonce
+    /// - Failed to exchange authorization code
+    /// - The ID token is invalid or not for this request
+    pub async fn handle_oidc_callback(
+        &self,
+        name: &str,
+        code: &str,
+        oauth_state: &ValidatedOAuthState,
+    ) -> Result<OAuthUserInfo, AppError> {
+        let oidc_provider = self.oidc_provider(name)?;
+        let (Some(code_verifier), Some(nonce)) = (&oauth_state.pkce_verifier, &oauth_state.nonce)
+        else {
+            return Err(AppError::Unauthorized(
+                "OAuth state was not issued for this provider".to_string(),
+            ));
+        };
+
+        let (claims, email) = self
+            .oidc
+            .exchange_code(
+                oidc_provider,
+                &oidc_provider.redirect_uri(&self.config.server_url),
+                code,
+                code_verifier,
+                nonce,
+            )
+            .await?;
+
+        Ok(claims.into_oauth_user_info(OAuthProvider::Oidc(name.to_string()), email))
+    }
+
+    /// Find a configured OIDC provider
+    fn oidc_provider(&self, name: &str) -> Result<&crate::config::OidcProviderConfig, AppError> {
+        self.config
+            .oidc_provider(name)
+            .ok_or_else(|| AppError::NotFound(format!("Unknown OAuth provider: {name}")))
     }
 
     /// Store OAuth state for CSRF protection
@@ -263,19 +402,35 @@ impl OAuthService {
         provider: OAuthProvider,
         redirect_uri: Option<&str>,
     ) -> Result<(), AppError> {
-        // OAuth state expires after 10 minutes
+        self.insert_oauth_state(state, &provider, redirect_uri, None, None, None)
+            .await
+    }
+
+    /// Insert an OAuth state with what the callback needs to complete the request
+    async fn insert_oauth_state(
+        &self,
+        state: &str,
+        provider: &OAuthProvider,
+        redirect_uri: Option<&str>,
+        link_user_id: Option<&str>,
+        pkce_verifier: Option<&str>,
+        nonce: Option<&str>,
+    ) -> Result<(), AppError> {
         let now = Utc::now();
-        let expires_at = now + Duration::minutes(10);
+        let expires_at = now + Duration::minutes(OAUTH_STATE_TTL_MINUTES);
         let provider_str = provider.to_string();
 
         sqlx::query!(
             r#"
-            INSERT INTO oauth_states (state, provider, redirect_uri, created_at, expires_at)
-            VALUES (?1, ?2, ?3, ?4, ?5)
+            INSERT INTO oauth_states (state, provider, redirect_uri, link_user_id, pkce_verifier, nonce, created_at, expires_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
             "#,
             state,
             provider_str,
             redirect_uri,
+            link_user_id,
+            pkce_verifier,
+            nonce,
             now,
             expires_at
         )
@@ -291,6 +446,10 @@ impl OAuthService {
 
     /// Validate and consume OAuth state
     ///
+    /// Returns what the state was stored with: the user linking the provider
+    /// to their account, and the PKCE verifier and nonce of OIDC
+    /// requests.
+    ///
     /// # Errors
     ///
     /// Returns an error if state is invalid, expired, or database operation fails
@@ -298,13 +457,13 @@ impl OAuthService {
         &self,
         state: &str,
         provider: OAuthProvider,
-    ) -> Result<(), AppError> {
+    ) -> Result<ValidatedOAuthState, AppError> {
         // First, check if the state exists and is valid
         let provider_str = provider.to_string();
 
         let oauth_state = sqlx::query!(
             r#"
-            SELECT state, provider, redirect_uri, created_at as "created_at: DateTime<Utc>", expires_at as "expires_at: DateTime<Utc>"
+            SELECT state, provider, redirect_uri, link_user_id, pkce_verifier, nonce, created_at as "created_at: DateTime<Utc>", expires_at as "expires_at: DateTime<Utc>"
             FROM oauth_states
             WHERE state = ?1 AND provider = ?2
             "#,
@@ -333,7 +492,20 @@ impl OAuthService {
         // Delete the state after successful validation (one-time use)
         self.delete_oauth_state(state).await?;
 
-        Ok(())
+        let link_user_id = oauth_state
+            .link_user_id
+            .map(|user_id| {
+                Uuid::parse_str(&user_id).map_err(|_| {
+                    AppError::InternalServerError("Invalid user in OAuth state".to_string())
+                })
+            })
+            .transpose()?;
+
+        Ok(ValidatedOAuthState {
+            link_user_id,
+            pkce_verifier: oauth_state.pkce_verifier,
+            nonce: oauth_state.nonce,
+        })
     }
 
     /// Delete OAuth state
@@ -355,7 +527,86 @@ impl OAuthService {
         Ok(())
     }
 
-    /// Clean up expired OAuth states
+    /// Issue a one-time code the client exchanges for a session
+    ///
+    /// Only the hash of the code is stored. It expires after
+    /// `OAUTH_LOGIN_CODE_TTL_SECONDS` and can be exchanged once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if database operation fails
+    pub async fn create_login_code(
+        &self,
+        user_id: Uuid,
+        is_new_user: bool,
+    ) -> Result<String, AppError> {
+        let code = generate_opaque_token();
+        let code_hash = hash_token(&code);
+        let user_id = user_id.to_string();
+        let now = Utc::now();
+        let expires_at = now + Duration::seconds(OAUTH_LOGIN_CODE_TTL_SECONDS);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO oauth_login_codes (code_hash, user_id, is_new_user, created_at, expires_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+            code_hash,
+            user_id,
+            is_new_user,
+            now,
+            expires_at
+        )
+        .execute(&self.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to store OAuth login code: {:?}", e);
+            AppError::InternalServerError("Failed to store OAuth login code".to_string())
+        })?;
+
+        Ok(code)
+    }
+
+    /// Consume a one-time login code
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Unauthorized` if the code is unknown, expired or was
+    /// already exchanged, or an error if database operation fails
+    pub async fn redeem_login_code(&self, code: &str) -> Result<OAuthLoginGrant, AppError> {
+        let code_hash = hash_token(code);
+        let now = Utc::now();
+
+        // Marking the code used and reading it is one statement, so a concurrent
+        // exchange of the same code finds nothing
+        let grant = sqlx::query!(
+            r#"
+            UPDATE oauth_login_codes SET used_at = ?1
+            WHERE code_hash = ?2 AND used_at IS NULL AND expires_at > ?1
+            RETURNING user_id as "user_id!", is_new_user as "is_new_user!: bool"
+            "#,
+            now,
+            code_hash
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to redeem OAuth login code: {:?}", e);
+            AppError::InternalServerError("Failed to redeem OAuth login code".to_string())
+        })?
+        .ok_or_else(|| AppError::Unauthorized("Invalid or expired login code".to_string()))?;
+
+        let user_id = Uuid::parse_str(&grant.user_id).map_err(|_| {
+            AppError::InternalServerError("Invalid user in OAuth login code".to_string())
+        })?;
+
+        Ok(OAuthLoginGrant {
+            user_id,
+            is_new_user: grant.is_new_user,
+        })
+    }
+
+    /// Clean up expired OAuth states and login codes
     ///
     /// This should be called periodically to remove expired states
     ///
@@ -380,7 +631,21 @@ impl OAuthService {
             AppError::InternalServerError("Failed to cleanup expired states".to_string())
         })?;
 
-        Ok(result.rows_affected())
+        let codes = sqlx::query!(
+            r#"
+            DELETE FROM oauth_login_codes
+            WHERE expires_at < ?1
+            "#,
+            now
+        )
+        .execute(&self.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to cleanup expired OAuth login codes: {:?}", e);
+            AppError::InternalServerError("Failed to cleanup expired states".to_string())
+        })?;
+
+        Ok(result.rows_affected() + codes.rows_affected())
     }
 }
 
@@ -0,0 +1,408 @@
+//! OIDC client for generic providers
+//!
+//! Providers are found through their discovery document, users are sent to
+//! the provider with PKCE and a nonce, and the ID token returned for the
+//! authorization code is verified against the provider's JWKS. Discovery
+//! documents and key sets are cached; key sets are fetched again when a token
+//! is signed with a key they do not contain, so providers can rotate keys.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation, decode, decode_header,
+    jwk::{Jwk, JwkSet},
+};
+use reqwest::{Client, Url};
+use tokio::sync::RwLock;
+
+use crate::{
+    config::OidcProviderConfig,
+    errors::AppError,
+    models::oauth::{OidcClaims, OidcMetadata, OidcTokenResponse},
+};
+
+/// Signature algorithms accepted for ID tokens; symmetric algorithms would let
+/// anyone holding the client secret forge tokens
+const ID_TOKEN_ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+pub struct OidcClient {
+    http_client: Client,
+    /// Discovery documents by issuer
+    metadata: RwLock<HashMap<String, Arc<OidcMetadata>>>,
+    /// Key sets by JWKS URI
+    key_sets: RwLock<HashMap<String, Arc<JwkSet>>>,
+}
+
+impl OidcClient {
+    #[must_use]
+    pub fn new(http_client: Client) -> Self {
+        Self {
+            http_client,
+            metadata: RwLock::new(HashMap::new()),
+            key_sets: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Get a provider's discovery document
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::InternalServerError` if the document cannot be
+    /// fetched or is for another issuer
+    pub async fn metadata(
+        &self,
+        provider: &OidcProviderConfig,
+    ) -> Result<Arc<OidcMetadata>, AppError> {
+        if let Some(metadata) = self.metadata.read().await.get(&provider.issuer) {
+            return Ok(metadata.clone());
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", provider.issuer);
+        let metadata: OidcMetadata = self.get_json(&url, "discovery document").await?;
+
+        // The discovery document must be for the configured issuer
+        if metadata.issuer.trim_end_matches('/') != provider.issuer {
+            tracing::error!(
+                "OIDC provider {} reports issuer {} instead of {}",
+                provider.name,
+                metadata.issuer,
+                provider.issuer
+            );
+            return Err(AppError::InternalServerError(
+                "OIDC provider is misconfigured".to_string(),
+            ));
+        }
+
+        let metadata = Arc::new(metadata);
+        self.metadata
+            .write()
+            .await
+            .insert(provider.issuer.clone(), metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Build the URL to send the user to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the discovery document cannot be fetched or has an
+    /// invalid authorization endpoint
+    pub async fn authorization_url(
+        &self,
+        provider: &OidcProviderConfig,
+        redirect_uri: &str,
+        state: &str,
+        code_challenge: &str,
+        nonce: &str,
+    ) -> Result<String, AppError> {
+        let metadata = self.metadata(provider).await?;
+        let scopes = provider.scopes.join(" ");
+
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            [
+                ("response_type", "code"),
+                ("client_id", provider.client_id.as_str()),
+                ("redirect_uri", redirect_uri),
+                ("scope", scopes.as_str()),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", code_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| {
+            tracing::error!("Invalid OIDC authorization endpoint: {:?}", e);
+            AppError::InternalServerError("OIDC provider is misconfigured".to_string())
+        })?;
+
+        Ok(url.to_string())
+    }
+
+    /// Exchange an authorization code for the user's verified claims
+    ///
+    /// The email is only reported verified if the provider is trusted to verify emails
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Unauthorized` if the code is rejected or the ID token
+    /// is missing or invalid, or `AppError::Forbidden` if the provider does not
+    /// report an email address
+    pub async fn exchange_code(
+        &self,
+        provider: &OidcProviderConfig,
+        redirect_uri: &str,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<(OidcClaims, String), AppError> {
+        let metadata = self.metadata(provider).await?;
+        let tokens = self
+            .request_tokens(provider, &metadata, redirect_uri, code, code_verifier)
+            .await?;
+
+        let id_token = tokens.id_token.ok_or_else(|| {
+            tracing::error!("OIDC provider {} returned no ID token", provider.name);
+            AppError::Unauthorized("Provider returned no ID token".to_string())
+        })?;
+        let claims = self
+            .verify_id_token(provider, &metadata, &id_token, nonce)
+            .await?;
+
+        // Providers may leave the email out of the ID token
+        let (mut claims, email) = match claims.email.clone() {
+            Some(email) => (claims, email),
+            None => {
+                self.user_info(&metadata, &tokens.access_token, claims)
+                    .await?
+            }
+        };
+
+        // Any issuer can claim an email is verified; only trusted ones are believed
+        if !provider.trust_email {
+            claims.email_verified = None;
+        }
+
+        Ok((claims, email))
+    }
+
+    /// Request tokens for an authorization code
+    async fn request_tokens(
+        &self,
+        provider: &OidcProviderConfig,
+        metadata: &OidcMetadata,
+        redirect_uri: &str,
+        code: &str,
+        code_verifier: &str,
+    ) -> Result<OidcTokenResponse, AppError> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("code_verifier", code_verifier),
+            ("client_id", provider.client_id.as_str()),
+        ];
+        let mut request = self.http_client.post(&metadata.token_endpoint);
+        if let Some(secret) = &provider.client_secret {
+            // Prefer client_secret_post; use basic auth if the provider only supports that
+            let methods = &metadata.token_endpoint_auth_methods_supported;
+            if methods.is_empty() || methods.iter().any(|m| m == "client_secret_post") {
+                form.push(("client_secret", secret.as_str()));
+            } else {
+                request = request.basic_auth(
+                    urlencoding::encode(&provider.client_id),
+                    Some(urlencoding::encode(secret)),
+                );
+            }
+        }
+
+        let response = request.form(&form).send().await.map_err(|e| {
+            tracing::error!("Failed to reach OIDC token endpoint: {:?}", e);
+            AppError::InternalServerError("Failed to reach identity provider".to_string())
+        })?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            tracing::error!("OIDC token endpoint returned {}: {}", status, body);
+            return Err(AppError::Unauthorized(
+                "Invalid authorization code".to_string(),
+            ));
+        }
+
+        response.json().await.map_err(|e| {
+            tracing::error!("Invalid OIDC token response: {:?}", e);
+            AppError::Unauthorized("Invalid token response".to_string())
+        })
+    }
+
+    /// Verify an ID token's signature and claims
+    async fn verify_id_token(
+        &self,
+        provider: &OidcProviderConfig,
+        metadata: &OidcMetadata,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<OidcClaims, AppError> {
+        let key_set = self.key_set(&metadata.jwks_uri, false).await?;
+        match validate_id_token(id_token, &key_set, provider, nonce) {
+            // The provider may have rotated its keys since they were cached
+            Err(IdTokenError::UnknownKey) => {
+                let key_set = self.key_set(&metadata.jwks_uri, true).await?;
+                validate_id_token(id_token, &key_set, provider, nonce)
+            }
+            result => result,
+        }
+        .map_err(|e| {
+            tracing::warn!("Rejected ID token from {}: {:?}", provider.name, e);
+            AppError::Unauthorized("Invalid ID token".to_string())
+        })
+    }
+
+    /// Get a provider's key set, fetching it if it is not cached or `refresh` is set
+    async fn key_set(&self, jwks_uri: &str, refresh: bool) -> Result<Arc<JwkSet>, AppError> {
+        if !refresh && let Some(key_set) = self.key_sets.read().await.get(jwks_uri) {
+            return Ok(key_set.clone());
+        }
+
+        let key_set: Arc<JwkSet> = Arc::new(self.get_json(jwks_uri, "key set").await?);
+        self.key_sets
+            .write()
+            .await
+            .insert(jwks_uri.to_string(), key_set.clone());
+        Ok(key_set)
+    }
+
+    /// Fetch the user's email from the userinfo endpoint
+    async fn user_info(
+        &self,
+        metadata: &OidcMetadata,
+        access_token: &str,
+        claims: OidcClaims,
+    ) -> Result<(OidcClaims, String), AppError> {
+        let no_email =
+            || AppError::Forbidden("Provider did not share an email address".to_string());
+        let endpoint = metadata.userinfo_endpoint.as_deref().ok_or_else(no_email)?;
+
+        let response = self
+            .http_client
+            .get(endpoint)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| {
+                tracing::error!("Failed to fetch OIDC user info: {:?}", e);
+                AppError::InternalServerError("Failed to fetch user information".to_string())
+            })?;
+        let user_info: OidcClaims = response.json().await.map_err(|e| {
+            tracing::error!("Invalid OIDC user info: {:?}", e);
+            AppError::InternalServerError("Invalid user information format".to_string())
+        })?;
+
+        // User info is only about the ID token's user if the subjects match
+        if user_info.sub != claims.sub {
+            tracing::error!("OIDC user info is for another subject");
+            return Err(AppError::Unauthorized(
+                "Invalid user information".to_string(),
+            ));
+        }
+        let email = user_info.email.ok_or_else(no_email)?;
+
+        Ok((
+            OidcClaims {
+                email: Some(email.clone()),
+                email_verified: user_info.email_verified,
+                name: claims.name.or(user_info.name),
+                picture: claims.picture.or(user_info.picture),
+                ..claims
+            },
+            email,
+        ))
+    }
+
+    /// Fetch a JSON document from a provider
+    async fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        url: &str,
+        what: &str,
+    ) -> Result<T, AppError> {
+        let response = self
+            .http_client
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| {
+                tracing::error!("Failed to fetch OIDC {} from {}: {:?}", what, url, e);
+                AppError::InternalServerError("Failed to reach identity provider".to_string())
+            })?;
+
+        response.json().await.map_err(|e| {
+            tracing::error!("Invalid OIDC {} from {}: {:?}", what, url, e);
+            AppError::InternalServerError("Identity provider returned invalid data".to_string())
+        })
+    }
+}
+
+/// Why an ID token was rejected
+#[derive(Debug, PartialEq, Eq)]
+pub enum IdTokenError {
+    /// The token is signed with an algorithm that is not accepted
+    UnsupportedAlgorithm,
+    /// The token is signed with a key that is not in the key set
+    UnknownKey,
+    /// The signature, issuer, audience or expiry is invalid
+    Invalid(String),
+    /// The token was not issued for this authorization request
+    NonceMismatch,
+}
+
+/// Validate an ID token against a key set, the provider's issuer and client ID
+/// and the nonce of the authorization request
+///
+/// # Errors
+///
+/// Returns the reason the token is not valid
+pub fn validate_id_token(
+    id_token: &str,
+    key_set: &JwkSet,
+    provider: &OidcProviderConfig,
+    nonce: &str,
+) -> Result<OidcClaims, IdTokenError> {
+    let header = decode_header(id_token).map_err(|e| IdTokenError::Invalid(e.to_string()))?;
+    if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+        return Err(IdTokenError::UnsupportedAlgorithm);
+    }
+
+    let jwk = find_key(key_set, header.kid.as_deref()).ok_or(IdTokenError::UnknownKey)?;
+    let key = DecodingKey::from_jwk(jwk).map_err(|e| IdTokenError::Invalid(e.to_string()))?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[&provider.issuer, &format!("{}/", provider.issuer)]);
+    validation.set_audience(&[&provider.client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = decode::<OidcClaims>(id_token, &key, &validation)
+        .map_err(|e| IdTokenError::Invalid(e.to_string()))?
+        .claims;
+
+    if claims
+        .azp
+        .as_deref()
+        .is_some_and(|azp| azp != provider.client_id)
+    {
+        return Err(IdTokenError::Invalid(
+            "Token was issued to another client".to_string(),
+        ));
+    }
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(IdTokenError::NonceMismatch);
+    }
+
+    Ok(claims)
+}
+
+/// Find the key a token was signed with; tokens without a key ID may only be
+/// used with single-key sets
+fn find_key<'a>(key_set: &'a JwkSet, kid: Option<&str>) -> Option<&'a Jwk> {
+    match kid {
+        Some(kid) => key_set.find(kid),
+        None if key_set.keys.len() == 1 => key_set.keys.first(),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+#[path = "oidc_client_tests.rs"]
+mod oidc_client_tests;
@@ -0,0 +1,463 @@
+//! Quota service for limiting each user's AI usage
+//!
+//! Every user is on a plan: `paid` with an active subscription, `invited` with
+//! a valid invite and `free` otherwise. A plan limits the tokens used and the
+//! amount spent per UTC day and per calendar month, summed from `ai_usage`;
+//! limits left unset are unlimited. An admin can set limits for a single user,
+//! which replace their plan's.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::errors::{AppError, AppResult};
+
+/// Millionths of a cent in a cent, the unit `ai_usage.cost_microcents` is in
+const MICROCENTS_PER_CENT: i64 = 1_000_000;
+
+/// Plans, from least to most allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Plan {
+    Free,
+    Invited,
+    Paid,
+}
+
+impl Plan {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Free => "free",
+            Self::Invited => "invited",
+            Self::Paid => "paid",
+        }
+    }
+}
+
+/// Usage limits; `None` is unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaLimits {
+    pub daily_tokens: Option<i64>,
+    pub monthly_tokens: Option<i64>,
+    pub daily_cents: Option<i64>,
+    pub monthly_cents: Option<i64>,
+}
+
+impl QuotaLimits {
+    /// Parse `name=value` pairs, comma separated, such as
+    /// `daily_tokens=100000,monthly_cents=500`
+    fn parse(variable: &str, value: &str) -> AppResult<Self> {
+        let mut limits = Self::default();
+        for pair in value.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let invalid = || {
+                AppError::ConfigError(format!(
+                    "{variable} entries must be 'limit=number' with limit one of daily_tokens, \
+                     monthly_tokens, daily_cents or monthly_cents, got '{pair}'"
+                ))
+            };
+            let (name, number) = pair.split_once('=').ok_or_else(invalid)?;
+            let number: i64 = number
+                .trim()
+                .parse()
+                .ok()
+                .filter(|number| *number >= 0)
+                .ok_or_else(invalid)?;
+            let limit = match name.trim() {
+                "daily_tokens" => &mut limits.daily_tokens,
+                "monthly_tokens" => &mut limits.monthly_tokens,
+                "daily_cents" => &mut limits.daily_cents,
+                "monthly_cents" => &mut limits.monthly_cents,
+                _ => return Err(invalid()),
+            };
+            *limit = Some(number);
+        }
+        Ok(limits)
+    }
+
+    /// These limits, taking `other`'s where these are unset
+    #[must_use]
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            daily_tokens: self.daily_tokens.or(other.daily_tokens),
+            monthly_tokens: self.monthly_tokens.or(other.monthly_tokens),
+            daily_cents: self.daily_cents.or(other.daily_cents),
+            monthly_cents: self.monthly_cents.or(other.monthly_cents),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Limits of each plan
+#[derive(Debug, Clone)]
+pub struct QuotaPlans {
+    pub free: QuotaLimits,
+    pub invited: QuotaLimits,
+    pub paid: QuotaLimits,
+}
+
+impl Default for QuotaPlans {
+    fn default() -> Self {
+        Self {
+            free: QuotaLimits {
+                daily_tokens: Some(20_000),
+                monthly_tokens: Some(200_000),
+                daily_cents: Some(10),
+                monthly_cents: Some(100),
+            },
+            invited: QuotaLimits {
+                daily_tokens: Some(200_000),
+                monthly_tokens: Some(2_000_000),
+                daily_cents: Some(100),
+                monthly_cents: Some(1_000),
+            },
+            paid: QuotaLimits {
+                daily_tokens: Some(2_000_000),
+                monthly_tokens: Some(20_000_000),
+                daily_cents: Some(1_000),
+                monthly_cents: Some(10_000),
+            },
+        }
+    }
+}
+
+impl QuotaPlans {
+    /// Load plan limits from environment configuration
+    ///
+    /// - `AI_QUOTA_FREE`, `AI_QUOTA_INVITED`, `AI_QUOTA_PAID`: a plan's limits
+    ///   as `limit=number` pairs, e.g. `daily_tokens=100000,monthly_cents=500`.
+    ///   Setting one replaces that plan's defaults, and limits it leaves out
+    ///   are unlimited.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ConfigError` if a variable is malformed
+    pub fn from_env() -> AppResult<Self> {
+        let mut plans = Self::default();
+        for (variable, limits) in [
+            ("AI_QUOTA_FREE", &mut plans.free),
+            ("AI_QUOTA_INVITED", &mut plans.invited),
+            ("AI_QUOTA_PAID", &mut plans.paid),
+        ] {
+            if let Ok(value) = std::env::var(variable) {
+                *limits = QuotaLimits::parse(variable, &value)?;
+            }
+        }
+        Ok(plans)
+    }
+
+    #[must_use]
+    pub fn limits(&self, plan: Plan) -> QuotaLimits {
+        match plan {
+            Plan::Free => self.free,
+            Plan::Invited => self.invited,
+            Plan::Paid => self.paid,
+        }
+    }
+}
+
+/// Usage counted against the limits
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct QuotaUsage {
+    pub daily_tokens: i64,
+    pub monthly_tokens: i64,
+    pub daily_microcents: i64,
+    pub monthly_microcents: i64,
+}
+
+/// A user's limits, their usage and what remains of it
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaStatus {
+    pub plan: Plan,
+    pub limits: QuotaLimits,
+    pub used: QuotaUsage,
+    /// What is left before each limit is reached; `None` where unlimited
+    pub remaining: QuotaLimits,
+    pub daily_reset_at: DateTime<Utc>,
+    pub monthly_reset_at: DateTime<Utc>,
+}
+
+impl QuotaStatus {
+    fn new(plan: Plan, limits: QuotaLimits, used: QuotaUsage, now: DateTime<Utc>) -> Self {
+        let tokens_left = |limit: Option<i64>, used: i64| limit.map(|limit| (limit - used).max(0));
+        let cents_left = |limit: Option<i64>, used: i64| {
+            limit.map(|limit| (limit * MICROCENTS_PER_CENT - used).max(0) / MICROCENTS_PER_CENT)
+        };
+        let (daily_reset_at, monthly_reset_at) = next_resets(now);
+
+        Self {
+            plan,
+            limits,
+            remaining: QuotaLimits {
+                daily_tokens: tokens_left(limits.daily_tokens, used.daily_tokens),
+                monthly_tokens: tokens_left(limits.monthly_tokens, used.monthly_tokens),
+                daily_cents: cents_left(limits.daily_cents, used.daily_microcents),
+                monthly_cents: cents_left(limits.monthly_cents, used.monthly_microcents),
+            },
+            used,
+            daily_reset_at,
+            monthly_reset_at,
+        }
+    }
+
+    fn daily_exceeded(&self) -> bool {
+        self.limits
+            .daily_tokens
+            .is_some_and(|limit| self.used.daily_tokens >= limit)
+            || self
+                .limits
+                .daily_cents
+                .is_some_and(|limit| self.used.daily_microcents >= limit * MICROCENTS_PER_CENT)
+    }
+
+    fn monthly_exceeded(&self) -> bool {
+        self.limits
+            .monthly_tokens
+            .is_some_and(|limit| self.used.monthly_tokens >= limit)
+            || self
+                .limits
+                .monthly_cents
+                .is_some_and(|limit| self.used.monthly_microcents >= limit * MICROCENTS_PER_CENT)
+    }
+
+    /// When requests are allowed again, if a limit has been reached
+    #[must_use]
+    pub fn blocked_until(&self) -> Option<DateTime<Utc>> {
+        if self.monthly_exceeded() {
+            Some(self.monthly_reset_at)
+        } else if self.daily_exceeded() {
+            Some(self.daily_reset_at)
+        } else {
+            None
+        }
+    }
+
+    /// Response headers describing the plan and what remains of each limit
+    #[must_use]
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![("X-Quota-Plan", self.plan.as_str().to_string())];
+        for (name, remaining) in [
+            ("X-Quota-Remaining-Tokens-Day", self.remaining.daily_tokens),
+            (
+                "X-Quota-Remaining-Tokens-Month",
+                self.remaining.monthly_tokens,
+            ),
+            ("X-Quota-Remaining-Cents-Day", self.remaining.daily_cents),
+            (
+                "X-Quota-Remaining-Cents-Month",
+                self.remaining.monthly_cents,
+            ),
+        ] {
+            if let Some(remaining) = remaining {
+                headers.push((name, remaining.to_string()));
+            }
+        }
+        headers
+    }
+}
+
+/// Starts of the next UTC day and month
+fn next_resets(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let today = now.date_naive();
+    let tomorrow = today + Duration::days(1);
+    let next_month = if today.month() == 12 {
+        NaiveDate::from_ymd_opt(today.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(today.year(), today.month() + 1, 1)
+    }
+    .unwrap_or(tomorrow);
+    (
+        tomorrow.and_time(chrono::NaiveTime::MIN).and_utc(),
+        next_month.and_time(chrono::NaiveTime::MIN).and_utc(),
+    )
+}
+
+pub struct QuotaService {
+    db: SqlitePool,
+    plans: QuotaPlans,
+}
+
+impl QuotaService {
+    /// Create the service with the default plan limits
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        Self {
+            db,
+            plans: QuotaPlans::default(),
+        }
+    }
+
+    /// Use the given plan limits
+    #[must_use]
+    pub fn with_plans(mut self, plans: QuotaPlans) -> Self {
+        self.plans = plans;
+        self
+    }
+
+    /// A user's limits, usage and what remains of it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails
+    pub async fn status(&self, user_id: &str, plan: Plan) -> AppResult<QuotaStatus> {
+        let limits = self
+            .get_user_limits(user_id)
+            .await?
+            .unwrap_or_default()
+            .or(self.plans.limits(plan));
+
+        let now = Utc::now();
+        let today = now.date_naive();
+        let month_start = today.with_day(1).unwrap_or(today);
+        // `ai_usage.created_at` is written by `to_rfc3339`, so these compare as text
+        let day_start = today
+            .and_time(chrono::NaiveTime::MIN)
+            .and_utc()
+            .to_rfc3339();
+        let month_start = month_start
+            .and_time(chrono::NaiveTime::MIN)
+            .and_utc()
+            .to_rfc3339();
+
+        let usage = sqlx::query!(
+            r#"
+            SELECT
+                COALESCE(SUM(CASE WHEN created_at >= ?2 THEN total_tokens END), 0) as "daily_tokens!: i64",
+                COALESCE(SUM(total_tokens), 0) as "monthly_tokens!: i64",
+                COALESCE(SUM(CASE WHEN created_at >= ?2 THEN cost_microcents END), 0) as "daily_microcents!: i64",
+                COALESCE(SUM(cost_microcents), 0) as "monthly_microcents!: i64"
+            FROM ai_usage
+            WHERE user_id = ?1 AND created_at >= ?3
+            "#,
+            user_id,
+            day_start,
+            month_start
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        let used = QuotaUsage {
+            daily_tokens: usage.daily_tokens,
+            monthly_tokens: usage.monthly_tokens,
+            daily_microcents: usage.daily_microcents,
+            monthly_microcents: usage.monthly_microcents,
+        };
+        Ok(QuotaStatus::new(plan, limits, used, now))
+    }
+
+    /// Check that a user may make another AI request
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::TooManyRequests`, with the remaining budget as
+    /// headers, if a limit has been reached, or an error if the database
+    /// operation fails
+    pub async fn check(&self, user_id: &str, plan: Plan) -> AppResult<QuotaStatus> {
+        let status = self.status(user_id, plan).await?;
+        let Some(blocked_until) = status.blocked_until() else {
+            return Ok(status);
+        };
+
+        tracing::info!(
+            "AI quota of user {user_id} on the {} plan is used up until {blocked_until}",
+            plan.as_str()
+        );
+        let retry_after = (blocked_until - Utc::now()).num_seconds().max(1);
+        let mut headers = status.headers();
+        headers.push(("Retry-After", retry_after.to_string()));
+        Err(AppError::TooManyRequests {
+            message: format!(
+                "AI usage quota exceeded; it resets at {}",
+                blocked_until.to_rfc3339()
+            ),
+            headers,
+        })
+    }
+
+    /// Limits set for a single user, if any
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails
+    pub async fn get_user_limits(&self, user_id: &str) -> AppResult<Option<QuotaLimits>> {
+        let limits = sqlx::query_as!(
+            QuotaLimits,
+            r#"
+            SELECT daily_tokens, monthly_tokens, daily_cents, monthly_cents
+            FROM ai_user_quotas
+            WHERE user_id = ?1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(limits)
+    }
+
+    /// Set a user's own limits; limits left unset are their plan's, and
+    /// leaving all of them unset puts the user back on their plan's limits
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::ValidationError` if a limit is negative, or an error
+    /// if the database operation fails
+    pub async fn set_user_limits(
+        &self,
+        user_id: &str,
+        limits: &QuotaLimits,
+        updated_by: &str,
+    ) -> AppResult<()> {
+        let values = [
+            limits.daily_tokens,
+            limits.monthly_tokens,
+            limits.daily_cents,
+            limits.monthly_cents,
+        ];
+        if values.into_iter().flatten().any(|limit| limit < 0) {
+            return Err(AppError::ValidationError(
+                "Quota limits cannot be negative".to_string(),
+            ));
+        }
+
+        if limits.is_empty() {
+            sqlx::query!("DELETE FROM ai_user_quotas WHERE user_id = ?1", user_id)
+                .execute(&self.db)
+                .await?;
+            return Ok(());
+        }
+
+        let now = Utc::now().to_rfc3339();
+        sqlx::query!(
+            r#"
+            INSERT INTO ai_user_quotas (user_id, daily_tokens, monthly_tokens, daily_cents, monthly_cents, updated_by, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT(user_id) DO UPDATE SET
+                daily_tokens = excluded.daily_tokens,
+                monthly_tokens = excluded.monthly_tokens,
+                daily_cents = excluded.daily_cents,
+                monthly_cents = excluded.monthly_cents,
+                updated_by = excluded.updated_by,
+                updated_at = excluded.updated_at
+            "#,
+            user_id,
+            limits.daily_tokens,
+            limits.monthly_tokens,
+            limits.daily_cents,
+            limits.monthly_cents,
+            updated_by,
+            now
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "quota_service_tests.rs"]
+mod quota_service_tests;
@@ -0,0 +1,241 @@
+//! Tests for quota service
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::{ModelPrice, ModelPricing};
+    use crate::errors::AppError;
+    use crate::services::AiDataService;
+    use crate::services::ai_data_service::UsageRecord;
+    use crate::services::quota_service::{Plan, QuotaLimits, QuotaPlans, QuotaService};
+    use chrono::{Duration, Utc};
+    use sqlx::SqlitePool;
+    use uuid::Uuid;
+
+    async fn create_test_user(pool: &SqlitePool) -> String {
+        let user_id = Uuid::new_v4().to_string();
+        let email = format!("test+{user_id}@example.com");
+        let now = chrono::Utc::now().to_rfc3339();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, email, hashed_password, provider, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            user_id,
+            email,
+            "hashed_password",
+            "local",
+            now,
+            now
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to create test user");
+
+        user_id
+    }
+
+    /// Plans small enough to reach in a test; paid users are unlimited
+    fn test_plans() -> QuotaPlans {
+        QuotaPlans {
+            free: QuotaLimits {
+                daily_tokens: Some(1_000),
+                monthly_tokens: Some(5_000),
+                daily_cents: Some(10),
+                monthly_cents: None,
+            },
+            invited: QuotaLimits {
+                daily_tokens: Some(10_000),
+                ..QuotaLimits::default()
+            },
+            paid: QuotaLimits::default(),
+        }
+    }
+
+    /// Record usage of a model priced at 1 cent per 1000 tokens
+    async fn use_tokens(pool: &SqlitePool, user_id: &str, tokens: i64) {
+        let price = ModelPrice {
+            prompt: 1_000.0,
+            completion: 1_000.0,
+        };
+        AiDataService::new(pool.clone())
+            .with_pricing(ModelPricing::default().with_model("test-model", price))
+            .record_usage(UsageRecord {
+                conversation_id: None,
+                user_id,
+                model: "test-model",
+                prompt_tokens: tokens,
+                completion_tokens: 0,
+                request_id: None,
+                duration_ms: None,
+            })
+            .await
+            .expect("Failed to record usage");
+    }
+
+    /// Move all of a user's usage back in time
+    async fn age_usage(pool: &SqlitePool, user_id: &str, days: i64) {
+        let created_at = (Utc::now() - Duration::days(days)).to_rfc3339();
+        sqlx::query!(
+            "UPDATE ai_usage SET created_at = ?1 WHERE user_id = ?2",
+            created_at,
+            user_id
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to age usage");
+    }
+
+    #[sqlx::test]
+    async fn test_status_counts_usage_against_plan(pool: SqlitePool) {
+        let service = QuotaService::new(pool.clone()).with_plans(test_plans());
+        let user_id = create_test_user(&pool).await;
+
+        let status = service.status(&user_id, Plan::Free).await.expect("status");
+        assert_eq!(status.limits, test_plans().free);
+        assert_eq!(status.remaining.daily_tokens, Some(1_000));
+        assert_eq!(status.remaining.monthly_cents, None);
+
+        use_tokens(&pool, &user_id, 400).await;
+        let status = service.status(&user_id, Plan::Free).await.expect("status");
+        assert_eq!(status.used.daily_tokens, 400);
+        assert_eq!(status.used.daily_microcents, 400_000);
+        assert_eq!(status.remaining.daily_tokens, Some(600));
+        assert_eq!(status.remaining.monthly_tokens, Some(4_600));
+        assert_eq!(status.remaining.daily_cents, Some(9));
+        assert!(status.blocked_until().is_none());
+
+        let headers = status.headers();
+        assert!(headers.contains(&("X-Quota-Plan", "free".to_string())));
+        assert!(headers.contains(&("X-Quota-Remaining-Tokens-Day", "600".to_string())));
+        assert!(
+            !headers
+                .iter()
+                .any(|(name, _)| *name == "X-Quota-Remaining-Cents-Month")
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_check_rejects_exhausted_quota(pool: SqlitePool) {
+        let service = QuotaService::new(pool.clone()).with_plans(test_plans());
+        let user_id = create_test_user(&pool).await;
+
+        use_tokens(&pool, &user_id, 1_000).await;
+        let Err(AppError::TooManyRequests { headers, .. }) =
+            service.check(&user_id, Plan::Free).await
+        else {
+            panic!("Expected the daily token limit to be enforced");
+        };
+        assert!(headers.contains(&("X-Quota-Remaining-Tokens-Day", "0".to_string())));
+        let retry_after: i64 = headers
+            .iter()
+            .find(|(name, _)| *name == "Retry-After")
+            .and_then(|(_, value)| value.parse().ok())
+            .expect("Retry-After header");
+        assert!((1..=86_400).contains(&retry_after));
+
+        // Higher plans have more to spend
+        assert!(service.check(&user_id, Plan::Invited).await.is_ok());
+        assert!(service.check(&user_id, Plan::Paid).await.is_ok());
+
+        // Yesterday's usage no longer counts against the day
+        age_usage(&pool, &user_id, 1).await;
+        let status = service.status(&user_id, Plan::Free).await.expect("status");
+        assert_eq!(status.used.daily_tokens, 0);
+        assert!(service.check(&user_id, Plan::Free).await.is_ok());
+    }
+
+    #[sqlx::test]
+    async fn test_check_rejects_exhausted_spend(pool: SqlitePool) {
+        let plans = QuotaPlans {
+            free: QuotaLimits {
+                daily_cents: Some(5),
+                ..QuotaLimits::default()
+            },
+            ..test_plans()
+        };
+        let service = QuotaService::new(pool.clone()).with_plans(plans);
+        let user_id = create_test_user(&pool).await;
+
+        use_tokens(&pool, &user_id, 4_999).await;
+        assert!(service.check(&user_id, Plan::Free).await.is_ok());
+        use_tokens(&pool, &user_id, 1).await;
+        assert!(matches!(
+            service.check(&user_id, Plan::Free).await,
+            Err(AppError::TooManyRequests { .. })
+        ));
+
+        // Last month's spending does not count
+        age_usage(&pool, &user_id, 40).await;
+        let status = service.status(&user_id, Plan::Free).await.expect("status");
+        assert_eq!(status.used.monthly_microcents, 0);
+    }
+
+    #[sqlx::test]
+    async fn test_user_limits_override_plan(pool: SqlitePool) {
+        let service = QuotaService::new(pool.clone()).with_plans(test_plans());
+        let user_id = create_test_user(&pool).await;
+        use_tokens(&pool, &user_id, 2_000).await;
+        assert!(service.check(&user_id, Plan::Free).await.is_err());
+
+        let limits = QuotaLimits {
+            daily_tokens: Some(3_000),
+            ..QuotaLimits::default()
+        };
+        service
+            .set_user_limits(&user_id, &limits, "admin")
+            .await
+            .expect("limits set");
+        assert_eq!(
+            service.get_user_limits(&user_id).await.expect("limits"),
+            Some(limits)
+        );
+        let status = service.check(&user_id, Plan::Free).await.expect("allowed");
+        assert_eq!(status.limits.daily_tokens, Some(3_000));
+        // Limits the user has none of are still the plan's
+        assert_eq!(status.limits.monthly_tokens, Some(5_000));
+
+        service
+            .set_user_limits(&user_id, &QuotaLimits::default(), "admin")
+            .await
+            .expect("limits cleared");
+        assert_eq!(
+            service.get_user_limits(&user_id).await.expect("limits"),
+            None
+        );
+        assert!(service.check(&user_id, Plan::Free).await.is_err());
+
+        let negative = QuotaLimits {
+            monthly_cents: Some(-1),
+            ..QuotaLimits::default()
+        };
+        assert!(matches!(
+            service.set_user_limits(&user_id, &negative, "admin").await,
+            Err(AppError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let limits = QuotaLimits::parse("AI_QUOTA_FREE", "daily_tokens=100, monthly_cents=5")
+            .expect("limits parsed");
+        assert_eq!(
+            limits,
+            QuotaLimits {
+                daily_tokens: Some(100),
+                monthly_cents: Some(5),
+                ..QuotaLimits::default()
+            }
+        );
+        assert_eq!(
+            QuotaLimits::parse("AI_QUOTA_FREE", "").expect("limits parsed"),
+            QuotaLimits::default()
+        );
+        for invalid in ["daily_tokens", "weekly_tokens=5", "daily_cents=-1"] {
+            assert!(matches!(
+                QuotaLimits::parse("AI_QUOTA_FREE", invalid),
+                Err(AppError::ConfigError(_))
+            ));
+        }
+    }
+}
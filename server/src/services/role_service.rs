@@ -0,0 +1,356 @@
+//! Role management service
+//!
+//! Stores role assignments in the `user_roles` table and resolves a user's
+//! current roles for token issuing and permission checks.
+
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    errors::{AppError, AppResult},
+    models::role::{Role, RoleAssignment},
+};
+
+/// Marker used as `granted_by` for roles assigned at startup
+pub const SYSTEM_GRANTOR: &str = "system";
+
+pub struct RoleService {
+    db: SqlitePool,
+}
+
+impl RoleService {
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Get the roles currently assigned to a user
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn get_roles(&self, user_id: Uuid) -> AppResult<Vec<Role>> {
+        Ok(self
+            .list_assignments(user_id)
+            .await?
+            .into_iter()
+            .map(|assignment| assignment.role)
+            .collect())
+    }
+
+    /// List a user's role assignments with grant details
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn list_assignments(&self, user_id: Uuid) -> AppResult<Vec<RoleAssignment>> {
+        let user_id_str = user_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT user_id, role, granted_by, created_at as "created_at: DateTime<Utc>"
+            FROM user_roles
+            WHERE user_id = ?1
+            ORDER BY created_at
+            "#,
+            user_id_str
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| match row.role.parse::<Role>() {
+                Ok(role) => Some(RoleAssignment {
+                    user_id: row.user_id,
+                    role,
+                    granted_by: row.granted_by,
+                    created_at: row.created_at,
+                }),
+                Err(e) => {
+                    tracing::warn!("Ignoring role assignment for user {}: {}", row.user_id, e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Grant a role to a user
+    ///
+    /// Returns `true` if the role was newly granted, `false` if the user already had it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::UserNotFound` if the user does not exist, or `AppError`
+    /// if the database operation fails
+    pub async fn grant_role(
+        &self,
+        user_id: Uuid,
+        role: Role,
+        granted_by: Option<&str>,
+    ) -> AppResult<bool> {
+        let user_id_str = user_id.to_string();
+
+        let user_exists = sqlx::query!("SELECT id FROM users WHERE id = ?1", user_id_str)
+            .fetch_optional(&self.db)
+            .await?
+            .is_some();
+        if !user_exists {
+            return Err(AppError::UserNotFound);
+        }
+
+        let role_str = role.as_str();
+        let now = Utc::now();
+        let result = sqlx::query!(
+            r#"
+            INSERT OR IGNORE INTO user_roles (user_id, role, granted_by, created_at)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+            user_id_str,
+            role_str,
+            granted_by,
+            now
+        )
+        .execute(&self.db)
+        .await?;
+
+        let granted = result.rows_affected() > 0;
+        if granted {
+            tracing::info!(
+                "Granted role {} to user {} (by {})",
+                role,
+                user_id,
+                granted_by.unwrap_or("unknown")
+            );
+        }
+        Ok(granted)
+    }
+
+    /// Revoke a role from a user
+    ///
+    /// Returns `true` if the role was removed, `false` if the user did not have it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn revoke_role(&self, user_id: Uuid, role: Role) -> AppResult<bool> {
+        let user_id_str = user_id.to_string();
+        let role_str = role.as_str();
+
+        let result = sqlx::query!(
+            "DELETE FROM user_roles WHERE user_id = ?1 AND role = ?2",
+            user_id_str,
+            role_str
+        )
+        .execute(&self.db)
+        .await?;
+
+        let revoked = result.rows_affected() > 0;
+        if revoked {
+            tracing::info!("Revoked role {} from user {}", role, user_id);
+        }
+        Ok(revoked)
+    }
+
+    /// Grant the admin role to existing users with the given emails
+    ///
+    /// Used at startup to bootstrap the first administrators. Emails match
+    /// regardless of case, and only users who verified their email qualify, so
+    /// registering an admin's address first does not make anyone an admin.
+    /// Emails without such a user are skipped. Returns the number of newly
+    /// granted roles.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if a database operation fails
+    pub async fn bootstrap_admins(&self, emails: &[String]) -> AppResult<usize> {
+        let mut granted = 0;
+
+        for email in emails {
+            let users = sqlx::query_scalar!(
+                r#"
+                SELECT id as "id!" FROM users
+                WHERE lower(email) = lower(?1) AND email_verified_at IS NOT NULL
+                "#,
+                email
+            )
+            .fetch_all(&self.db)
+            .await?;
+
+            if users.is_empty() {
+                tracing::debug!("Admin bootstrap: no verified user registered for {}", email);
+            }
+
+            for user_id in users {
+                let user_id = Uuid::parse_str(&user_id).map_err(|e| {
+                    AppError::InternalServerError(format!("Invalid user ID in database: {e}"))
+                })?;
+
+                if self
+                    .grant_role(user_id, Role::Admin, Some(SYSTEM_GRANTOR))
+                    .await?
+                {
+                    granted += 1;
+                }
+            }
+        }
+
+        Ok(granted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::auth_handler::RegisterUserPayload;
+    use crate::services::UserServiceImpl;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect("sqlite::memory:")
+            .await
+            .expect("Failed to create test database");
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .expect("Failed to run migrations");
+
+        pool
+    }
+
+    async fn create_user(pool: &SqlitePool, email: &str) -> Uuid {
+        UserServiceImpl::new(pool.clone())
+            .create_user(&RegisterUserPayload {
+                email: email.to_string(),
+                password: "password123".to_string(),
+            })
+            .await
+            .expect("Failed to create user")
+            .id
+    }
+
+    async fn verify_email(pool: &SqlitePool, user_id: Uuid) {
+        sqlx::query("UPDATE users SET email_verified_at = CURRENT_TIMESTAMP WHERE id = ?1")
+            .bind(user_id.to_string())
+            .execute(pool)
+            .await
+            .expect("Failed to verify email");
+    }
+
+    #[tokio::test]
+    async fn test_grant_and_revoke_role() {
+        let pool = setup_test_db().await;
+        let service = RoleService::new(pool.clone());
+        let user_id = create_user(&pool, "roles@example.com").await;
+
+        assert!(service.get_roles(user_id).await.expect("roles").is_empty());
+
+        assert!(
+            service
+                .grant_role(user_id, Role::InviteManager, Some("admin"))
+                .await
+                .expect("grant")
+        );
+        assert!(
+            !service
+                .grant_role(user_id, Role::InviteManager, Some("admin"))
+                .await
+                .expect("second grant")
+        );
+        assert_eq!(
+            service.get_roles(user_id).await.expect("roles"),
+            vec![Role::InviteManager]
+        );
+
+        assert!(
+            service
+                .revoke_role(user_id, Role::InviteManager)
+                .await
+                .expect("revoke")
+        );
+        assert!(
+            !service
+                .revoke_role(user_id, Role::InviteManager)
+                .await
+                .expect("second revoke")
+        );
+        assert!(service.get_roles(user_id).await.expect("roles").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_grant_role_to_unknown_user() {
+        let pool = setup_test_db().await;
+        let service = RoleService::new(pool);
+
+        let result = service.grant_role(Uuid::new_v4(), Role::Admin, None).await;
+        assert!(matches!(result, Err(AppError::UserNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_admins() {
+        let pool = setup_test_db().await;
+        let service = RoleService::new(pool.clone());
+        let user_id = create_user(&pool, "owner@example.com").await;
+        verify_email(&pool, user_id).await;
+
+        let emails = vec![
+            "owner@example.com".to_string(),
+            "nobody@example.com".to_string(),
+        ];
+        assert_eq!(
+            service.bootstrap_admins(&emails).await.expect("bootstrap"),
+            1
+        );
+        assert_eq!(
+            service.bootstrap_admins(&emails).await.expect("bootstrap"),
+            0
+        );
+
+        let assignments = service
+            .list_assignments(user_id)
+            .await
+            .expect("assignments");
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].role, Role::Admin);
+        assert_eq!(assignments[0].granted_by.as_deref(), Some(SYSTEM_GRANTOR));
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_admins_skips_unverified_emails() {
+        let pool = setup_test_db().await;
+        let service = RoleService::new(pool.clone());
+        let user_id = create_user(&pool, "owner@example.com").await;
+
+        let emails = vec!["owner@example.com".to_string()];
+        assert_eq!(
+            service.bootstrap_admins(&emails).await.expect("bootstrap"),
+            0
+        );
+        assert!(service.get_roles(user_id).await.expect("roles").is_empty());
+
+        verify_email(&pool, user_id).await;
+        assert_eq!(
+            service.bootstrap_admins(&emails).await.expect("bootstrap"),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_admins_ignores_email_case() {
+        let pool = setup_test_db().await;
+        let service = RoleService::new(pool.clone());
+        let user_id = create_user(&pool, "Owner@Example.com").await;
+        verify_email(&pool, user_id).await;
+
+        let emails = vec!["owner@example.com".to_string()];
+        assert_eq!(
+            service.bootstrap_admins(&emails).await.expect("bootstrap"),
+            1
+        );
+        assert_eq!(
+            service.get_roles(user_id).await.expect("roles"),
+            vec![Role::Admin]
+        );
+    }
+}
@@ -0,0 +1,506 @@
+//! Session and refresh token service
+//!
+//! Sessions are stored in `cli_devices` and their refresh tokens in
+//! `cli_refresh_tokens`. Refresh tokens are single use: each refresh revokes the
+//! presented token and issues a new one. Presenting an already used token is
+//! treated as theft and revokes the whole session.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::{
+    core::token_utils::{generate_opaque_token, hash_token},
+    errors::{AppError, AppResult},
+    models::session::{DeviceInfo, SessionInfo},
+};
+
+/// Lifetime of a refresh token in days
+pub const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+/// Result of rotating a refresh token
+#[derive(Debug, Clone)]
+pub struct RotatedSession {
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+    pub refresh_token: String,
+}
+
+pub struct SessionService {
+    db: SqlitePool,
+}
+
+fn invalid_refresh_token() -> AppError {
+    AppError::Unauthorized("Invalid or expired refresh token".to_string())
+}
+
+fn parse_uuid(value: &str) -> AppResult<Uuid> {
+    Uuid::parse_str(value)
+        .map_err(|e| AppError::InternalServerError(format!("Invalid ID in database: {e}")))
+}
+
+impl SessionService {
+    #[must_use]
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Create a session for a user and issue its first refresh token
+    ///
+    /// Returns the session ID and the plaintext refresh token.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn create_session(
+        &self,
+        user_id: Uuid,
+        device: &DeviceInfo,
+    ) -> AppResult<(Uuid, String)> {
+        let session_id = Uuid::new_v4();
+        let session_id_str = session_id.to_string();
+        let user_id_str = user_id.to_string();
+        let now = Utc::now();
+
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO cli_devices (id, user_id, device_name, device_fingerprint, last_used_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+            "#,
+            session_id_str,
+            user_id_str,
+            device.name,
+            device.fingerprint,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let refresh_token = Self::insert_refresh_token(&mut tx, &session_id_str, now).await?;
+
+        tx.commit().await?;
+
+        tracing::info!("Created session {} for user {}", session_id, user_id);
+        Ok((session_id, refresh_token))
+    }
+
+    async fn insert_refresh_token(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        session_id: &str,
+        now: DateTime<Utc>,
+    ) -> AppResult<String> {
+        let token = generate_opaque_token();
+        let token_hash = hash_token(&token);
+        let token_id = Uuid::new_v4().to_string();
+        let expires_at = now + Duration::days(REFRESH_TOKEN_TTL_DAYS);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO cli_refresh_tokens (id, device_id, token_hash, expires_at, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+            token_id,
+            session_id,
+            token_hash,
+            expires_at,
+            now
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Exchange a refresh token for the next token in its chain
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Unauthorized` if the token is unknown, expired, already
+    /// used or belongs to a revoked session. Reusing a token revokes its session.
+    /// Returns `AppError` if a database operation fails.
+    pub async fn rotate_refresh_token(&self, refresh_token: &str) -> AppResult<RotatedSession> {
+        let token_hash = hash_token(refresh_token);
+        let now = Utc::now();
+
+        let mut tx = self.db.begin().await?;
+
+        let record = sqlx::query!(
+            r#"
+            SELECT t.id, t.device_id, d.user_id,
+                   t.expires_at as "expires_at: DateTime<Utc>",
+                   t.revoked_at as "token_revoked_at: DateTime<Utc>",
+                   d.revoked_at as "session_revoked_at: DateTime<Utc>"
+            FROM cli_refresh_tokens t
+            JOIN cli_devices d ON d.id = t.device_id
+            WHERE t.token_hash = ?1
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(invalid_refresh_token)?;
+
+        if record.session_revoked_at.is_some() {
+            tracing::warn!("Refresh attempted for revoked session {}", record.device_id);
+            return Err(invalid_refresh_token());
+        }
+
+        if record.token_revoked_at.is_some() {
+            tracing::warn!(
+                "Refresh token reuse detected for session {}; revoking session",
+                record.device_id
+            );
+            Self::revoke_session_tokens(&mut tx, &record.device_id, now).await?;
+            tx.commit().await?;
+            return Err(invalid_refresh_token());
+        }
+
+        if record.expires_at < now {
+            return Err(invalid_refresh_token());
+        }
+
+        // Guard against a concurrent refresh with the same token
+        let used = sqlx::query!(
+            r#"
+            UPDATE cli_refresh_tokens SET revoked_at = ?1, last_used_at = ?1
+            WHERE id = ?2 AND revoked_at IS NULL
+            "#,
+            now,
+            record.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        if used.rows_affected() == 0 {
+            return Err(invalid_refresh_token());
+        }
+
+        sqlx::query!(
+            "UPDATE cli_devices SET last_used_at = ?1 WHERE id = ?2",
+            now,
+            record.device_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let refresh_token = Self::insert_refresh_token(&mut tx, &record.device_id, now).await?;
+
+        tx.commit().await?;
+
+        Ok(RotatedSession {
+            user_id: parse_uuid(&record.user_id)?,
+            session_id: parse_uuid(&record.device_id)?,
+            refresh_token,
+        })
+    }
+
+    async fn revoke_session_tokens(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        session_id: &str,
+        now: DateTime<Utc>,
+    ) -> AppResult<bool> {
+        let session = sqlx::query!(
+            "UPDATE cli_devices SET revoked_at = ?1 WHERE id = ?2 AND revoked_at IS NULL",
+            now,
+            session_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE cli_refresh_tokens SET revoked_at = ?1 WHERE device_id = ?2 AND revoked_at IS NULL",
+            now,
+            session_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(session.rows_affected() > 0)
+    }
+
+    /// Check whether a session exists and has not been revoked
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn is_session_active(&self, session_id: Uuid) -> AppResult<bool> {
+        let session_id_str = session_id.to_string();
+
+        let session = sqlx::query!(
+            "SELECT id FROM cli_devices WHERE id = ?1 AND revoked_at IS NULL",
+            session_id_str
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(session.is_some())
+    }
+
+    /// List a user's active sessions, most recently used first
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database query fails
+    pub async fn list_sessions(
+        &self,
+        user_id: Uuid,
+        current_session: Option<Uuid>,
+    ) -> AppResult<Vec<SessionInfo>> {
+        let user_id_str = user_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, device_name,
+                   created_at as "created_at: DateTime<Utc>",
+                   last_used_at as "last_used_at: DateTime<Utc>"
+            FROM cli_devices
+            WHERE user_id = ?1 AND revoked_at IS NULL
+            ORDER BY COALESCE(last_used_at, created_at) DESC
+            "#,
+            user_id_str
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let id = parse_uuid(&row.id)?;
+                Ok(SessionInfo {
+                    id,
+                    device_name: row.device_name,
+                    created_at: row.created_at,
+                    last_used_at: row.last_used_at,
+                    current: current_session == Some(id),
+                })
+            })
+            .collect()
+    }
+
+    /// Revoke one of a user's sessions along with all of its refresh tokens
+    ///
+    /// Returns `false` if the session does not exist, belongs to another user or
+    /// was already revoked.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn revoke_session(&self, user_id: Uuid, session_id: Uuid) -> AppResult<bool> {
+        let user_id_str = user_id.to_string();
+        let session_id_str = session_id.to_string();
+        let now = Utc::now();
+
+        let mut tx = self.db.begin().await?;
+
+        let owned = sqlx::query!(
+            "SELECT id FROM cli_devices WHERE id = ?1 AND user_id = ?2",
+            session_id_str,
+            user_id_str
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .is_some();
+        if !owned {
+            return Ok(false);
+        }
+
+        let revoked = Self::revoke_session_tokens(&mut tx, &session_id_str, now).await?;
+        tx.commit().await?;
+
+        if revoked {
+            tracing::info!("Revoked session {} for user {}", session_id, user_id);
+        }
+        Ok(revoked)
+    }
+
+    /// Revoke all of a user's sessions, including CLI devices, except `keep`
+    ///
+    /// Returns the number of sessions revoked.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError` if the database operation fails
+    pub async fn revoke_all_sessions(&self, user_id: Uuid, keep: Option<Uuid>) -> AppResult<u64> {
+        let user_id_str = user_id.to_string();
+        let keep = keep.map(|session_id| session_id.to_string());
+        let now = Utc::now();
+
+        let mut tx = self.db.begin().await?;
+        let sessions = sqlx::query_scalar!(
+            r#"
+            SELECT id as "id!" FROM cli_devices
+            WHERE user_id = ?1 AND revoked_at IS NULL AND (?2 IS NULL OR id != ?2)
+            "#,
+            user_id_str,
+            keep
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut revoked = 0;
+        for session_id in &sessions {
+            if Self::revoke_session_tokens(&mut tx, session_id, now).await? {
+                revoked += 1;
+            }
+        }
+        tx.commit().await?;
+
+        tracing::info!("Revoked {} sessions for user {}", revoked, user_id);
+        Ok(revoked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::auth_handler::RegisterUserPayload;
+    use crate::services::UserServiceImpl;
+
+    async fn setup_test_db() -> SqlitePool {
+        let pool = SqlitePool::connect("sqlite::memory:")
+            .await
+            .expect("Failed to create test database");
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .expect("Failed to run migrations");
+
+        pool
+    }
+
+    async fn create_user(pool: &SqlitePool, email: &str) -> Uuid {
+        UserServiceImpl::new(pool.clone())
+            .create_user(&RegisterUserPayload {
+                email: email.to_string(),
+                password: "password123".to_string(),
+            })
+            .await
+            .expect("Failed to create user")
+            .id
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_rotation() {
+        let pool = setup_test_db().await;
+        let service = SessionService::new(pool.clone());
+        let user_id = create_user(&pool, "rotate@example.com").await;
+
+        let (session_id, first) = service
+            .create_session(user_id, &DeviceInfo::web(None))
+            .await
+            .expect("create session");
+
+        let rotated = service
+            .rotate_refresh_token(&first)
+            .await
+            .expect("rotate token");
+        assert_eq!(rotated.user_id, user_id);
+        assert_eq!(rotated.session_id, session_id);
+        assert_ne!(rotated.refresh_token, first);
+
+        let rotated_again = service
+            .rotate_refresh_token(&rotated.refresh_token)
+            .await
+            .expect("rotate new token");
+        assert_eq!(rotated_again.session_id, session_id);
+        assert!(service.is_session_active(session_id).await.expect("active"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_reuse_revokes_session() {
+        let pool = setup_test_db().await;
+        let service = SessionService::new(pool.clone());
+        let user_id = create_user(&pool, "reuse@example.com").await;
+
+        let (session_id, first) = service
+            .create_session(user_id, &DeviceInfo::web(None))
+            .await
+            .expect("create session");
+        let rotated = service
+            .rotate_refresh_token(&first)
+            .await
+            .expect("rotate token");
+
+        // Replaying the used token revokes the whole chain
+        let replay = service.rotate_refresh_token(&first).await;
+        assert!(matches!(replay, Err(AppError::Unauthorized(_))));
+        assert!(!service.is_session_active(session_id).await.expect("active"));
+
+        let latest = service.rotate_refresh_token(&rotated.refresh_token).await;
+        assert!(matches!(latest, Err(AppError::Unauthorized(_))));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_refresh_token_rejected() {
+        let pool = setup_test_db().await;
+        let service = SessionService::new(pool);
+
+        let result = service.rotate_refresh_token("not-a-token").await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+    }
+
+    #[tokio::test]
+    async fn test_list_and_revoke_sessions() {
+        let pool = setup_test_db().await;
+        let service = SessionService::new(pool.clone());
+        let user_id = create_user(&pool, "owner@example.com").await;
+        let other_id = create_user(&pool, "other@example.com").await;
+
+        let (first_id, first_token) = service
+            .create_session(user_id, &DeviceInfo::web(Some("Laptop")))
+            .await
+            .expect("create session");
+        let (second_id, _) = service
+            .create_session(user_id, &DeviceInfo::web(Some("Phone")))
+            .await
+            .expect("create session");
+
+        let sessions = service
+            .list_sessions(user_id, Some(first_id))
+            .await
+            .expect("list sessions");
+        assert_eq!(sessions.len(), 2);
+        assert!(
+            sessions
+                .iter()
+                .any(|session| session.id == first_id && session.current)
+        );
+        assert!(
+            sessions
+                .iter()
+                .any(|session| session.id == second_id && !session.current)
+        );
+
+        // Other users cannot revoke the session
+        assert!(
+            !service
+                .revoke_session(other_id, first_id)
+                .await
+                .expect("revoke")
+        );
+
+        assert!(
+            service
+                .revoke_session(user_id, first_id)
+                .await
+                .expect("revoke")
+        );
+        assert!(
+            !service
+                .revoke_session(user_id, first_id)
+                .await
+                .expect("second revoke")
+        );
+
+        let sessions = service
+            .list_sessions(user_id, None)
+            .await
+            .expect("list sessions");
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, second_id);
+
+        let result = service.rotate_refresh_token(&first_token).await;
+        assert!(matches!(result, Err(AppError::Unauthorized(_))));
+    }
+}
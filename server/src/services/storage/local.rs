@@ -0,0 +1,101 @@
+//! Storage backend on the local filesystem
+
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+use super::{FileStorage, storage_failed, validate_key};
+use crate::errors::{AppError, AppResult};
+
+/// Stores each key as a file under a root directory
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    /// Create the backend; the root directory is created on first write
+    #[must_use]
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> AppResult<PathBuf> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl FileStorage for LocalStorage {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn put(&self, key: &str, data: &[u8]) -> AppResult<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| storage_failed("store", key, &e))?;
+        }
+
+        // Write to a temporary file first so readers never see a partial file
+        let temp = path.with_extension(format!("{}.tmp", Uuid::new_v4().simple()));
+        tokio::fs::write(&temp, data)
+            .await
+            .map_err(|e| storage_failed("store", key, &e))?;
+        if let Err(e) = tokio::fs::rename(&temp, &path).await {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(storage_failed("store", key, &e));
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> AppResult<Vec<u8>> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                Err(AppError::NotFound("File not found".to_string()))
+            }
+            Err(e) => Err(storage_failed("read", key, &e)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> AppResult<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(storage_failed("delete", key, &e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_storage_round_trip() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let storage = LocalStorage::new(dir.path());
+
+        storage
+            .put("objects/ab/abc", b"hello")
+            .await
+            .expect("stored");
+        assert_eq!(storage.get("objects/ab/abc").await.expect("read"), b"hello");
+        assert!(dir.path().join("objects/ab/abc").is_file());
+
+        storage.delete("objects/ab/abc").await.expect("deleted");
+        storage
+            .delete("objects/ab/abc")
+            .await
+            .expect("deleted again");
+        assert!(matches!(
+            storage.get("objects/ab/abc").await,
+            Err(AppError::NotFound(_))
+        ));
+        assert!(storage.put("../escape", b"x").await.is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Pluggable storage for uploaded file contents
+//!
+//! `FileStorage` stores bytes under string keys. `FileService` decides the keys
+//! and keeps track of what is stored in the database; the backends only move
+//! bytes. `STORAGE_BACKEND` selects the backend:
+//!
+//! - `local` (default): files under `STORAGE_LOCAL_DIR` (`./uploads`)
+//! - `s3`: a bucket on Amazon S3 or any S3-compatible service, see `S3Storage`
+//! - `memory`: kept in memory, for tests
+
+mod local;
+mod memory;
+mod s3;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::errors::{AppError, AppResult};
+
+pub use local::LocalStorage;
+pub use memory::MemoryStorage;
+pub use s3::S3Storage;
+
+/// Default directory of the local backend
+const DEFAULT_LOCAL_DIR: &str = "./uploads";
+
+/// Trait that all storage backends must implement
+///
+/// Keys are relative paths of `/`-separated segments made of ASCII letters,
+/// digits, `-`, `_` and `.`.
+#[async_trait]
+pub trait FileStorage: Send + Sync {
+    /// Get the backend name
+    fn name(&self) -> &'static str;
+
+    /// Store bytes under a key, replacing anything stored there
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is invalid or the bytes cannot be stored
+    async fn put(&self, key: &str, data: &[u8]) -> AppResult<()>;
+
+    /// Read the bytes stored under a key
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotFound` if nothing is stored under the key, or an
+    /// error if it cannot be read
+    async fn get(&self, key: &str) -> AppResult<Vec<u8>>;
+
+    /// Delete what is stored under a key; deleting a missing key succeeds
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is invalid or the bytes cannot be deleted
+    async fn delete(&self, key: &str) -> AppResult<()>;
+}
+
+/// Create the storage backend selected by `STORAGE_BACKEND`
+///
+/// # Errors
+///
+/// Returns `AppError::ConfigError` if the backend is unknown or its settings
+/// are missing
+pub fn storage_from_env() -> AppResult<Arc<dyn FileStorage>> {
+    let backend = std::env::var("STORAGE_BACKEND").unwrap_or_else(|_| "local".to_string());
+    match backend.to_ascii_lowercase().as_str() {
+        "local" => {
+            let root = std::env::var("STORAGE_LOCAL_DIR")
+                .unwrap_or_else(|_| DEFAULT_LOCAL_DIR.to_string());
+            Ok(Arc::new(LocalStorage::new(root)))
+        }
+        "s3" => Ok(Arc::new(S3Storage::from_env()?)),
+        "memory" => Ok(Arc::new(MemoryStorage::new())),
+        other => Err(AppError::ConfigError(format!(
+            "Unknown STORAGE_BACKEND '{other}', expected local, s3 or memory"
+        ))),
+    }
+}
+
+/// Check a key is a relative path of safe segments
+fn validate_key(key: &str) -> AppResult<()> {
+    let valid = !key.is_empty()
+        && key.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment != "."
+                && segment != ".."
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::InternalServerError(format!(
+            "Invalid storage key '{key}'"
+        )))
+    }
+}
+
+/// Error for a storage operation that failed
+fn storage_failed(operation: &str, key: &str, error: &dyn std::fmt::Display) -> AppError {
+    tracing::error!("Failed to {} {} in file storage: {}", operation, key, error);
+    AppError::InternalServerError("File storage failed".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("objects/ab/abcdef").is_ok());
+        assert!(validate_key("a-b_c.d").is_ok());
+        for key in ["", "/abs", "a//b", "../up", "a/./b", "a/b/", "sp ace", "ü"] {
+            assert!(validate_key(key).is_err(), "{key:?} is rejected");
+        }
+    }
+}
@@ -149,6 +149,38 @@ impl UserServiceImpl {
         }
     }
 
+    /// Finds a user by their ID
+    ///
+    /// # Errors
+    /// Returns `AppError::UserNotFound` if no user with the given ID exists
+    /// Returns `AppError::SqlxError` for database errors
+    #[instrument(skip(self), fields(user_id = %user_id), err(Debug))]
+    pub async fn find_by_id(&self, user_id: Uuid) -> AppResult<User> {
+        let user_id_str = user_id.to_string();
+
+        let db_user = sqlx::query_as!(
+            UserFromDb,
+            "SELECT id, email, hashed_password, provider, provider_user_id, created_at, updated_at FROM users WHERE id = $1",
+            user_id_str
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Database error while searching for user {}: {}", user_id, e);
+            AppError::SqlxError(e)
+        })?
+        .ok_or(AppError::UserNotFound)?;
+
+        User::try_from(db_user).map_err(|conv_err: UserConversionError| {
+            tracing::error!(
+                "Failed to convert DB user to domain model {}: {}",
+                user_id,
+                conv_err
+            );
+            AppError::InternalServerError(format!("User data conversion error: {conv_err}"))
+        })
+    }
+
     /// Create a user for testing purposes
     ///
     /// # Errors
@@ -1,9 +1,15 @@
 //! Common test helpers and utilities
 
 use crate::{
+    ai::{AiProvider, providers::MockProvider},
     core::AppState,
+    middleware::{AuthenticatedUser, JwtAuth},
+    models::User,
     services::{
-        AiDataService, AiService, AuthService, InviteService, PaymentService, UserServiceImpl,
+        AccountEmailService, AiDataService, AiService, AiSessionService, AuthService, BoardService,
+        CliAuthService, ContextService, DocumentService, EmbeddingService, FileService,
+        IdentityService, InviteService, IssueService, MfaService, PaymentService, QuotaService,
+        RoleService, SessionService, UserServiceImpl, mail::OutboxMailer, storage::MemoryStorage,
     },
 };
 use sqlx::SqlitePool;
@@ -19,26 +25,81 @@ pub struct TestServices {
     pub ai_service: Arc<RwLock<AiService>>,
     pub ai_data_service: Arc<AiDataService>,
     pub payment_service: Arc<PaymentService>,
+    pub role_service: Arc<RoleService>,
+    pub session_service: Arc<SessionService>,
+    pub cli_auth_service: Arc<CliAuthService>,
+    pub ai_session_service: Arc<AiSessionService>,
+    pub board_service: Arc<BoardService>,
+    pub issue_service: Arc<IssueService>,
+    pub embedding_service: Arc<EmbeddingService>,
+    pub document_service: Arc<DocumentService>,
+    pub file_service: Arc<FileService>,
+    pub context_service: Arc<ContextService>,
+    pub quota_service: Arc<QuotaService>,
+    pub identity_service: Arc<IdentityService>,
+    pub account_email_service: Arc<AccountEmailService>,
+    pub mfa_service: Arc<MfaService>,
 }
 
 /// Create test services with all dependencies initialized
 ///
 /// This centralized function ensures all tests use the same service configuration
-/// and makes it easy to add new services in one place.
+/// and makes it easy to add new services in one place. The AI service is backed by
+/// a `MockProvider`, so tests never reach the network, files are stored in
+/// memory, and emails are written to an outbox in the temp directory.
 ///
 /// # Panics
 ///
 /// Panics if any of the services fail to initialize (auth, AI, or payment services)
 #[must_use]
 pub fn create_test_services(pool: &SqlitePool) -> TestServices {
+    create_test_services_with_provider(pool, Arc::new(MockProvider::new()))
+}
+
+/// Create test services whose AI service uses the given provider
+///
+/// Pass a scripted `MockProvider` to control what the AI returns.
+///
+/// # Panics
+///
+/// Panics if any of the services fail to initialize (auth, AI, or payment services)
+#[must_use]
+pub fn create_test_services_with_provider(
+    pool: &SqlitePool,
+    provider: Arc<dyn AiProvider>,
+) -> TestServices {
     let user_service = Arc::new(UserServiceImpl::new(pool.clone()));
     let auth_service = Arc::new(AuthService::new().expect("Failed to create auth service"));
     let invite_service = Arc::new(InviteService::new(pool.clone()));
-    let ai_service_inner = AiService::new().expect("Failed to create AI service");
+    let ai_service_inner = AiService::with_provider(provider).expect("Failed to create AI service");
     let ai_service = Arc::new(RwLock::new(ai_service_inner));
     let ai_data_service = Arc::new(AiDataService::new(pool.clone()));
     let payment_service =
         Arc::new(PaymentService::new(pool.clone()).expect("Failed to create payment service"));
+    let role_service = Arc::new(RoleService::new(pool.clone()));
+    let session_service = Arc::new(SessionService::new(pool.clone()));
+    let cli_auth_service = Arc::new(CliAuthService::new(pool.clone()));
+    let ai_session_service = Arc::new(AiSessionService::new(pool.clone()));
+    let board_service = Arc::new(BoardService::new(pool.clone()));
+    let issue_service = Arc::new(IssueService::new(pool.clone()));
+    let embedding_service = Arc::new(EmbeddingService::new(pool.clone()));
+    let document_service = Arc::new(DocumentService::new(pool.clone()));
+    let file_service = Arc::new(FileService::new(
+        pool.clone(),
+        Arc::new(MemoryStorage::new()),
+        b"test-file-url-secret",
+    ));
+    let context_service = Arc::new(ContextService::new(pool.clone()));
+    let quota_service = Arc::new(QuotaService::new(pool.clone()));
+    let identity_service = Arc::new(IdentityService::new(pool.clone()));
+    let account_email_service = Arc::new(AccountEmailService::new(
+        pool.clone(),
+        Arc::new(OutboxMailer::new(
+            std::env::temp_dir().join("kanbain-test-outbox"),
+        )),
+        "http://localhost:8080",
+    ));
+    let mfa_service = Arc::new(MfaService::new(pool.clone()));
     let app_state = Arc::new(AppState {
         user: user_service.clone(),
         auth: auth_service.clone(),
@@ -46,6 +107,20 @@ pub fn create_test_services(pool: &SqlitePool) -> TestServices {
         ai: ai_service.clone(),
         ai_data: ai_data_service.clone(),
         payment: payment_service.clone(),
+        role: role_service.clone(),
+        session: session_service.clone(),
+        cli_auth: cli_auth_service.clone(),
+        ai_session: ai_session_service.clone(),
+        boards: board_service.clone(),
+        issues: issue_service.clone(),
+        embeddings: embedding_service.clone(),
+        documents: document_service.clone(),
+        files: file_service.clone(),
+        context: context_service.clone(),
+        quotas: quota_service.clone(),
+        identities: identity_service.clone(),
+        account_email: account_email_service.clone(),
+        mfa: mfa_service.clone(),
     });
 
     TestServices {
@@ -56,6 +131,20 @@ pub fn create_test_services(pool: &SqlitePool) -> TestServices {
         ai_service,
         ai_data_service,
         payment_service,
+        role_service,
+        session_service,
+        cli_auth_service,
+        ai_session_service,
+        board_service,
+        issue_service,
+        embedding_service,
+        document_service,
+        file_service,
+        context_service,
+        quota_service,
+        identity_service,
+        account_email_service,
+        mfa_service,
     }
 }
 
@@ -66,3 +155,16 @@ pub fn create_test_services(pool: &SqlitePool) -> TestServices {
 pub fn create_test_app_state(pool: &SqlitePool) -> Arc<AppState> {
     create_test_services(pool).app_state
 }
+
+/// Authenticate as `user`, as the `JwtAuth` extractor would for a valid token
+#[must_use]
+pub fn jwt_auth(user: &User) -> JwtAuth {
+    JwtAuth {
+        user: AuthenticatedUser {
+            user_id: user.id,
+            email: user.email.clone(),
+            roles: Vec::new(),
+            session_id: None,
+        },
+    }
+}
@@ -0,0 +1,97 @@
+//! Helpers for tests that send requests to the app's router
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, header},
+    response::Response,
+};
+use serde_json::Value;
+use tower::ServiceExt; // for `oneshot`
+
+use server::models::User;
+use server::routes::create_router;
+
+use super::TestContext;
+
+/// User agent sent with every request, which sessions are named after
+pub const TEST_USER_AGENT: &str = "Endpoint Test Browser";
+
+/// Create the app's router along with the test context it runs on
+pub async fn create_test_app() -> (Router, TestContext) {
+    let ctx = TestContext::new().await;
+
+    let router = create_router(
+        ctx.user_service.clone(),
+        ctx.auth_service.clone(),
+        ctx.invite_service.clone(),
+        ctx.oauth_service.clone(),
+        &ctx.pool,
+    )
+    .expect("Failed to create router");
+
+    (router, ctx)
+}
+
+/// Send a request with an optional bearer token and JSON body
+pub async fn send_json_request(
+    app: Router,
+    method: Method,
+    uri: &str,
+    token: Option<&str>,
+    body: Option<Value>,
+) -> Response {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::USER_AGENT, TEST_USER_AGENT);
+
+    if let Some(token) = token {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+
+    let request = if let Some(body_value) = body {
+        builder
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::to_string(&body_value).expect("Failed to serialize body"),
+            ))
+            .expect("Failed to build request")
+    } else {
+        builder
+            .body(Body::empty())
+            .expect("Failed to build request")
+    };
+
+    app.oneshot(request).await.expect("Failed to send request")
+}
+
+/// Read a response body as JSON
+pub async fn extract_json_response(response: Response) -> Value {
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("Failed to read response body");
+    serde_json::from_slice(&body).expect("Response body is not JSON")
+}
+
+/// Create a user and return their ID and an access token for them
+pub async fn user_token(ctx: &TestContext, email: &str) -> (String, String) {
+    let user = ctx.create_test_user(email).await;
+    token_for(ctx, &user)
+}
+
+/// Create a user with a verified email and return their ID and an access token
+/// for them
+pub async fn verified_user_token(ctx: &TestContext, email: &str) -> (String, String) {
+    let user = ctx.create_test_user(email).await;
+    ctx.verify_email(user.id).await;
+    token_for(ctx, &user)
+}
+
+fn token_for(ctx: &TestContext, user: &User) -> (String, String) {
+    let token = ctx
+        .auth_service
+        .generate_token(user.id, &user.email, &[])
+        .expect("Failed to generate token");
+    (user.id.to_string(), token)
+}
@@ -34,6 +34,13 @@ pub async fn setup_test_database() -> Pool<Sqlite> {
     pool
 }
 
+#[allow(dead_code)]
+pub mod app;
 pub mod test_context;
 #[allow(unused_imports)]
-pub use test_context::TestContext;
+pub use app::{
+    TEST_USER_AGENT, create_test_app, extract_json_response, send_json_request, user_token,
+    verified_user_token,
+};
+#[allow(unused_imports)]
+pub use test_context::{TestContext, latest_email_token};
@@ -9,7 +9,10 @@ use server::{
     core::AppState,
     handlers::auth_handler::RegisterUserPayload,
     models::User,
-    services::{AuthService, InviteService, OAuthService, PaymentService, UserServiceImpl},
+    services::{
+        AccountEmailService, AuthService, CliAuthService, InviteService, OAuthService,
+        PaymentService, RoleService, SessionService, UserServiceImpl, mail::OutboxMailer,
+    },
 };
 use sqlx::SqlitePool;
 
@@ -21,6 +24,10 @@ pub struct TestContext {
     pub invite_service: Arc<InviteService>,
     pub payment_service: Arc<PaymentService>,
     pub auth_service: Arc<AuthService>,
+    pub role_service: Arc<RoleService>,
+    pub session_service: Arc<SessionService>,
+    pub cli_auth_service: Arc<CliAuthService>,
+    pub account_email_service: Arc<AccountEmailService>,
     pub pool: SqlitePool,
 }
 
@@ -41,6 +48,14 @@ impl TestContext {
         let payment_service =
             Arc::new(PaymentService::new(pool.clone()).expect("Failed to create Payment service"));
         let auth_service = Arc::new(AuthService::new().expect("Failed to create Auth service"));
+        let role_service = Arc::new(RoleService::new(pool.clone()));
+        let session_service = Arc::new(SessionService::new(pool.clone()));
+        let cli_auth_service = Arc::new(CliAuthService::new(pool.clone()));
+        let account_email_service = Arc::new(AccountEmailService::new(
+            pool.clone(),
+            Arc::new(OutboxMailer::new(test_outbox_dir())),
+            "http://localhost:8080",
+        ));
 
         Self {
             user_service,
@@ -48,6 +63,10 @@ impl TestContext {
             invite_service,
             payment_service,
             auth_service,
+            role_service,
+            session_service,
+            cli_auth_service,
+            account_email_service,
             pool,
         }
     }
@@ -82,6 +101,14 @@ impl TestContext {
             .expect("Failed to create test invite");
     }
 
+    /// Mark a user's email verified, as if they followed the emailed link
+    pub async fn verify_email(&self, user_id: uuid::Uuid) {
+        self.account_email_service
+            .mark_email_verified(user_id)
+            .await
+            .expect("Failed to verify email");
+    }
+
     /// Create a new test context with an existing pool
     pub fn new_with_pool(pool: SqlitePool) -> Self {
         // Set up required environment variables for services
@@ -95,6 +122,14 @@ impl TestContext {
         let payment_service =
             Arc::new(PaymentService::new(pool.clone()).expect("Failed to create Payment service"));
         let auth_service = Arc::new(AuthService::new().expect("Failed to create Auth service"));
+        let role_service = Arc::new(RoleService::new(pool.clone()));
+        let session_service = Arc::new(SessionService::new(pool.clone()));
+        let cli_auth_service = Arc::new(CliAuthService::new(pool.clone()));
+        let account_email_service = Arc::new(AccountEmailService::new(
+            pool.clone(),
+            Arc::new(OutboxMailer::new(test_outbox_dir())),
+            "http://localhost:8080",
+        ));
 
         Self {
             user_service,
@@ -102,6 +137,10 @@ impl TestContext {
             invite_service,
             payment_service,
             auth_service,
+            role_service,
+            session_service,
+            cli_auth_service,
+            account_email_service,
             pool,
         }
     }
@@ -115,14 +154,56 @@ impl TestContext {
             auth: self.auth_service.clone(),
             invite: self.invite_service.clone(),
             ai: Arc::new(tokio::sync::RwLock::new(
-                server::services::AiService::new().expect("Failed to create AI service"),
+                server::services::AiService::with_provider(Arc::new(
+                    server::ai::providers::MockProvider::new(),
+                ))
+                .expect("Failed to create AI service"),
             )),
             ai_data: Arc::new(server::services::AiDataService::new(self.pool.clone())),
             payment: self.payment_service.clone(),
+            role: self.role_service.clone(),
+            session: self.session_service.clone(),
+            cli_auth: self.cli_auth_service.clone(),
+            ai_session: Arc::new(server::services::AiSessionService::new(self.pool.clone())),
+            boards: Arc::new(server::services::BoardService::new(self.pool.clone())),
+            issues: Arc::new(server::services::IssueService::new(self.pool.clone())),
+            embeddings: Arc::new(server::services::EmbeddingService::new(self.pool.clone())),
+            documents: Arc::new(server::services::DocumentService::new(self.pool.clone())),
+            files: Arc::new(server::services::FileService::new(
+                self.pool.clone(),
+                Arc::new(server::services::storage::MemoryStorage::new()),
+                b"test-file-url-secret",
+            )),
+            context: Arc::new(server::services::ContextService::new(self.pool.clone())),
+            quotas: Arc::new(server::services::QuotaService::new(self.pool.clone())),
+            identities: Arc::new(server::services::IdentityService::new(self.pool.clone())),
+            account_email: self.account_email_service.clone(),
+            mfa: Arc::new(server::services::MfaService::new(self.pool.clone())),
         })
     }
 }
 
+/// Outbox the emails of all tests are written to
+pub fn test_outbox_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join("kanbain-test-outbox")
+}
+
+/// Token in the latest email sent to an address, if any
+///
+/// Emails of all tests share one outbox, so tests use addresses of their own.
+#[allow(dead_code)]
+pub fn latest_email_token(email: &str) -> Option<String> {
+    let suffix = format!("-{email}.eml");
+    let latest = std::fs::read_dir(test_outbox_dir())
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(&suffix))
+        .max_by_key(std::fs::DirEntry::file_name)?;
+    let message = std::fs::read_to_string(latest.path()).ok()?;
+    let (_, rest) = message.split_once("?token=")?;
+    Some(rest.split_whitespace().next()?.to_string())
+}
+
 #[allow(dead_code)]
 fn setup_test_env() {
     use std::env;
@@ -153,5 +234,15 @@ fn setup_test_env() {
         // Payment service requirements
         env::set_var("STRIPE_SECRET_KEY", "test_stripe_key");
         env::set_var("STRIPE_WEBHOOK_ENDPOINT_SECRET", "test_webhook_secret");
+
+        // AI requests go to the offline mock provider
+        env::set_var("AI_MOCK_PROVIDER", "true");
+
+        // Uploaded files are kept in memory
+        env::set_var("STORAGE_BACKEND", "memory");
+
+        // Emails are written to a shared outbox
+        env::set_var("MAIL_TRANSPORT", "outbox");
+        env::set_var("MAIL_OUTBOX_DIR", test_outbox_dir());
     }
 }
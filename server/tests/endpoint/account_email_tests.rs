@@ -0,0 +1,224 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for email verification and password reset endpoints
+//!
+//! Emails go to the test outbox, where the tests read the mailed tokens from.
+//! All tests share the outbox, so each uses addresses of its own.
+
+use axum::{
+    Router,
+    http::{Method, StatusCode},
+};
+use serde_json::{Value, json};
+
+const TEST_SECURE_PASS: &str = "secure_password_123";
+
+use crate::common::{
+    create_test_app, extract_json_response, latest_email_token, send_json_request,
+};
+
+/// Register a user and return the registration response
+async fn register(app: &Router, email: &str) -> Value {
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/register",
+        None,
+        Some(json!({ "email": email, "password": TEST_SECURE_PASS })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    extract_json_response(response).await
+}
+
+/// Test that registering mails a verification link that verifies the email once
+#[tokio::test]
+async fn test_verify_email_after_registration() {
+    let (app, _ctx) = create_test_app().await;
+    let email = format!("verify-{}@example.com", uuid::Uuid::new_v4());
+
+    let registered = register(&app, &email).await;
+    assert_eq!(registered["auth_user"]["email_verified"], false);
+    let token = latest_email_token(&email).expect("verification email");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/verify-email/confirm",
+        None,
+        Some(json!({ "token": token })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The token works only once
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/verify-email/confirm",
+        None,
+        Some(json!({ "token": token })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let auth_token = registered["auth_token"].as_str().unwrap();
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/users/me",
+        Some(auth_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let me = extract_json_response(response).await;
+    assert_eq!(me["auth_user"]["email_verified"], true);
+
+    // Verified users cannot ask for another email
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/verify-email/request",
+        Some(auth_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Test that unverified users cannot add board members until they verify
+#[tokio::test]
+async fn test_unverified_users_are_limited() {
+    let (app, ctx) = create_test_app().await;
+    let email = format!("unverified-{}@example.com", uuid::Uuid::new_v4());
+    let registered = register(&app, &email).await;
+    let auth_token = registered["auth_token"].as_str().unwrap();
+    ctx.create_test_user("limited-member@example.com").await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/boards",
+        Some(auth_token),
+        Some(json!({ "name": "Roadmap" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let board_id = extract_json_response(response).await["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let members_uri = format!("/api/boards/{board_id}/members");
+    let add_member = || {
+        send_json_request(
+            app.clone(),
+            Method::POST,
+            &members_uri,
+            Some(auth_token),
+            Some(json!({ "email": "limited-member@example.com" })),
+        )
+    };
+    assert_eq!(add_member().await.status(), StatusCode::FORBIDDEN);
+
+    let user_id = registered["auth_user"]["id"].as_str().unwrap();
+    ctx.verify_email(user_id.parse().unwrap()).await;
+    assert_eq!(add_member().await.status(), StatusCode::CREATED);
+}
+
+/// Test that a password reset sets the new password and ends all sessions
+#[tokio::test]
+async fn test_password_reset() {
+    let (app, _ctx) = create_test_app().await;
+    let email = format!("reset-{}@example.com", uuid::Uuid::new_v4());
+    let registered = register(&app, &email).await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/password-reset/request",
+        None,
+        Some(json!({ "email": email })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let token = latest_email_token(&email).expect("reset email");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/password-reset/confirm",
+        None,
+        Some(json!({ "token": token, "password": "short" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/password-reset/confirm",
+        None,
+        Some(json!({ "token": token, "password": "new_secure_password_456" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Sessions from before the reset are revoked
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/users/me",
+        registered["auth_token"].as_str(),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let login = |password: &str| {
+        send_json_request(
+            app.clone(),
+            Method::POST,
+            "/api/auth/login",
+            None,
+            Some(json!({ "email": email, "password": password })),
+        )
+    };
+    assert_eq!(
+        login(TEST_SECURE_PASS).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    let response = login("new_secure_password_456").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = extract_json_response(response).await;
+    assert_eq!(body["auth_user"]["email_verified"], true);
+}
+
+/// Test that requesting a reset for an unknown email looks like any other request
+#[tokio::test]
+async fn test_password_reset_unknown_email() {
+    let (app, _ctx) = create_test_app().await;
+    let email = format!("nobody-{}@example.com", uuid::Uuid::new_v4());
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/password-reset/request",
+        None,
+        Some(json!({ "email": email })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(latest_email_token(&email).is_none());
+
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/password-reset/request",
+        None,
+        Some(json!({ "email": "not-an-email" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
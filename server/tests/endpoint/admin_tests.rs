@@ -0,0 +1,257 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for admin endpoints
+//!
+//! These tests verify that invite and role management endpoints enforce
+//! role-based authorization, and that roles can be granted and revoked.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt; // for `oneshot` and `ready`
+
+use server::models::{Role, User};
+
+const ADMIN_EMAIL: &str = "admin@example.com";
+const MANAGER_EMAIL: &str = "manager@example.com";
+const MEMBER_EMAIL: &str = "member@example.com";
+const INVITEE_EMAIL: &str = "invitee@example.com";
+
+use crate::common::{TestContext, create_test_app, extract_json_response};
+
+/// Create a user holding the given roles and return it with a token carrying them
+async fn create_user_with_roles(ctx: &TestContext, email: &str, roles: &[Role]) -> (User, String) {
+    let user = ctx.create_test_user(email).await;
+    for role in roles {
+        ctx.role_service
+            .grant_role(user.id, *role, Some("test"))
+            .await
+            .expect("Failed to grant role");
+    }
+    let token = ctx
+        .auth_service
+        .generate_token(user.id, &user.email, roles)
+        .expect("Failed to generate token");
+    (user, token)
+}
+
+/// Helper function to create a request with authentication
+async fn send_authenticated_request(
+    app: Router,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+    token: &str,
+) -> Response {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"));
+
+    if body.is_some() {
+        builder = builder.header(header::CONTENT_TYPE, "application/json");
+    }
+
+    let request = if let Some(body_value) = body {
+        builder
+            .body(Body::from(serde_json::to_string(&body_value).unwrap()))
+            .unwrap()
+    } else {
+        builder.body(Body::empty()).unwrap()
+    };
+
+    app.oneshot(request).await.unwrap()
+}
+
+/// Test GET /api/admin/invites without authentication (should return 401)
+#[tokio::test]
+async fn test_list_invites_unauthenticated() {
+    let (app, _ctx) = create_test_app().await;
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/api/admin/invites")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test GET /api/admin/invites as a user without roles (should return 403)
+#[tokio::test]
+async fn test_list_invites_forbidden_without_role() {
+    let (app, ctx) = create_test_app().await;
+    let (_user, token) = create_user_with_roles(&ctx, MEMBER_EMAIL, &[]).await;
+
+    let response =
+        send_authenticated_request(app, Method::GET, "/api/admin/invites", None, &token).await;
+
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+/// Test invite management as an invite manager (create, list, delete)
+#[tokio::test]
+async fn test_invite_manager_can_manage_invites() {
+    let (app, ctx) = create_test_app().await;
+    let (_user, token) = create_user_with_roles(&ctx, MANAGER_EMAIL, &[Role::InviteManager]).await;
+
+    let response = send_authenticated_request(
+        app.clone(),
+        Method::POST,
+        "/api/admin/invites",
+        Some(json!({ "email": INVITEE_EMAIL })),
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let created = extract_json_response(response).await;
+    assert_eq!(created["invite"]["invited_by"], MANAGER_EMAIL);
+    let invite_id = created["invite"]["id"].as_str().unwrap().to_string();
+
+    let response =
+        send_authenticated_request(app.clone(), Method::GET, "/api/admin/invites", None, &token)
+            .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let listed = extract_json_response(response).await;
+    assert!(
+        listed["invites"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|invite| invite["id"] == invite_id.as_str())
+    );
+
+    let response = send_authenticated_request(
+        app,
+        Method::DELETE,
+        &format!("/api/admin/invites/{invite_id}"),
+        None,
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// Test that a role revoked after the token was issued no longer grants access
+#[tokio::test]
+async fn test_revoked_role_is_rejected_immediately() {
+    let (app, ctx) = create_test_app().await;
+    let (user, token) = create_user_with_roles(&ctx, MANAGER_EMAIL, &[Role::InviteManager]).await;
+
+    ctx.role_service
+        .revoke_role(user.id, Role::InviteManager)
+        .await
+        .unwrap();
+
+    let response = send_authenticated_request(
+        app,
+        Method::POST,
+        "/api/admin/invites",
+        Some(json!({ "email": INVITEE_EMAIL })),
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+/// Test granting, listing and revoking roles as an admin
+#[tokio::test]
+async fn test_admin_grants_and_revokes_roles() {
+    let (app, ctx) = create_test_app().await;
+    let (_admin, token) = create_user_with_roles(&ctx, ADMIN_EMAIL, &[Role::Admin]).await;
+    let member = ctx.create_test_user(MEMBER_EMAIL).await;
+    let member_id = member.id;
+
+    let response = send_authenticated_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/admin/users/{member_id}/roles"),
+        Some(json!({ "role": "invite_manager" })),
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // Granting again is idempotent
+    let response = send_authenticated_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/admin/users/{member_id}/roles"),
+        Some(json!({ "role": "invite_manager" })),
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send_authenticated_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/admin/users/{member_id}/roles"),
+        None,
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let roles = extract_json_response(response).await;
+    assert_eq!(roles["roles"][0]["role"], "invite_manager");
+
+    let response = send_authenticated_request(
+        app.clone(),
+        Method::DELETE,
+        &format!("/api/admin/users/{member_id}/roles/invite_manager"),
+        None,
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send_authenticated_request(
+        app,
+        Method::DELETE,
+        &format!("/api/admin/users/{member_id}/roles/invite_manager"),
+        None,
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Test that invite managers cannot grant roles
+#[tokio::test]
+async fn test_invite_manager_cannot_grant_roles() {
+    let (app, ctx) = create_test_app().await;
+    let (manager, token) =
+        create_user_with_roles(&ctx, MANAGER_EMAIL, &[Role::InviteManager]).await;
+
+    let response = send_authenticated_request(
+        app,
+        Method::POST,
+        &format!("/api/admin/users/{}/roles", manager.id),
+        Some(json!({ "role": "admin" })),
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+/// Test that admins cannot lock themselves out by revoking their own admin role
+#[tokio::test]
+async fn test_admin_cannot_revoke_own_admin_role() {
+    let (app, ctx) = create_test_app().await;
+    let (admin, token) = create_user_with_roles(&ctx, ADMIN_EMAIL, &[Role::Admin]).await;
+
+    let response = send_authenticated_request(
+        app,
+        Method::DELETE,
+        &format!("/api/admin/users/{}/roles/admin", admin.id),
+        None,
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,160 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for conversational issue-creation session endpoints
+//!
+//! The AI provider is the mock provider, which echoes the last prompt back, so
+//! these tests cover the session lifecycle rather than the persona's replies.
+
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+use crate::common::{create_test_app, extract_json_response, send_json_request, user_token};
+
+/// Test a session from creation through messages and preview to cancellation
+#[tokio::test]
+async fn test_ai_session_lifecycle() {
+    let (app, ctx) = create_test_app().await;
+    let (_, token) = user_token(&ctx, "session-user@example.com").await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/ai/sessions",
+        Some(&token),
+        Some(json!({"persona": "project_manager", "initial_input": "Plan the Q3 release"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created = extract_json_response(response).await;
+    assert_eq!(created["status"], "active");
+    let session_id = created["session_id"].as_str().unwrap().to_string();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/ai/sessions/{session_id}/messages"),
+        Some(&token),
+        Some(json!({"message": "It ships in September"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let reply = extract_json_response(response).await;
+    assert!(reply["message_id"].as_str().unwrap().starts_with("msg_"));
+    assert_eq!(reply["draft_updated"], false);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/sessions/{session_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let details = extract_json_response(response).await;
+    assert_eq!(details["persona"], "project_manager");
+    assert_eq!(details["messages"].as_array().unwrap().len(), 4);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/sessions/{session_id}/preview"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let preview = extract_json_response(response).await;
+    assert_eq!(preview["title"], "");
+    assert_eq!(preview["priority"], "medium");
+
+    // Nothing has been drafted, so the issue cannot be created yet
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/ai/sessions/{session_id}/finalize"),
+        Some(&token),
+        Some(json!({"action": "create_issue", "board_id": "board_1"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/ai/sessions/{session_id}/finalize"),
+        Some(&token),
+        Some(json!({"action": "cancel", "board_id": ""})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let finalized = extract_json_response(response).await;
+    assert_eq!(finalized["status"], "cancelled");
+
+    // A cancelled session takes no more messages
+    let response = send_json_request(
+        app,
+        Method::POST,
+        &format!("/api/ai/sessions/{session_id}/messages"),
+        Some(&token),
+        Some(json!({"message": "Are you still there?"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Test that sessions require authentication and belong to their creator
+#[tokio::test]
+async fn test_ai_session_access_control() {
+    let (app, ctx) = create_test_app().await;
+    let (_, owner_token) = user_token(&ctx, "session-owner@example.com").await;
+    let (_, other_token) = user_token(&ctx, "session-other@example.com").await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/ai/sessions",
+        None,
+        Some(json!({})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/ai/sessions",
+        Some(&owner_token),
+        Some(json!({})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created = extract_json_response(response).await;
+    assert!(
+        created["assistant_response"]
+            .as_str()
+            .unwrap()
+            .contains("Business Analyst")
+    );
+    let session_id = created["session_id"].as_str().unwrap().to_string();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/sessions/{session_id}"),
+        Some(&other_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send_json_request(
+        app,
+        Method::POST,
+        &format!("/api/ai/sessions/{session_id}/messages"),
+        Some(&other_token),
+        Some(json!({"message": "Let me in"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
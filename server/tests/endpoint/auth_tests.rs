@@ -15,9 +15,6 @@ use axum::{
 use serde_json::{Value, json};
 use tower::ServiceExt; // for `oneshot` and `ready`
 
-// Import the application modules we need for testing
-use server::routes::create_router;
-
 // Test constants to avoid gitleaks false positives
 const TEST_EMAIL: &str = "test@example.com";
 const TEST_SECURE_PASS: &str = "secure_password_123";
@@ -26,23 +23,7 @@ const TEST_WRONG_PASS: &str = "wrong_password_123";
 const TEST_CORRECT_PASS: &str = "correct_password_123";
 const TEST_EMPTY_PASS: &str = "";
 
-use crate::common::TestContext;
-
-/// Helper function to create the test app
-async fn create_test_app() -> (Router, TestContext) {
-    let ctx = TestContext::new().await;
-
-    let router = create_router(
-        ctx.user_service.clone(),
-        ctx.auth_service.clone(),
-        ctx.invite_service.clone(),
-        ctx.oauth_service.clone(),
-        &ctx.pool,
-    )
-    .expect("Failed to create router");
-
-    (router, ctx)
-}
+use crate::common::{TestContext, create_test_app};
 
 /// Helper function to send a JSON request to the test app
 async fn send_json_request(app: Router, method: Method, uri: &str, body: Value) -> Response<Body> {
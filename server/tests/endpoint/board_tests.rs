@@ -0,0 +1,319 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for board, board member and issue endpoints
+
+use axum::{
+    Router,
+    http::{Method, StatusCode},
+};
+use serde_json::json;
+
+use crate::common::{
+    create_test_app, extract_json_response, send_json_request, verified_user_token,
+};
+
+/// Create a board as the given user and return its ID
+async fn create_board(app: Router, token: &str) -> String {
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/boards",
+        Some(token),
+        Some(json!({"name": "Roadmap", "description": "Next quarter"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let board = extract_json_response(response).await;
+    assert_eq!(board["role"], "owner");
+    board["id"].as_str().unwrap().to_string()
+}
+
+/// Test creating boards and managing their members
+#[tokio::test]
+async fn test_board_membership() {
+    let (app, ctx) = create_test_app().await;
+    let (_, owner_token) = verified_user_token(&ctx, "board-owner@example.com").await;
+    let (member_id, member_token) = verified_user_token(&ctx, "board-member@example.com").await;
+
+    let response = send_json_request(app.clone(), Method::GET, "/api/boards", None, None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let board_id = create_board(app.clone(), &owner_token).await;
+
+    // Non-members cannot see the board
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/boards/{board_id}"),
+        Some(&member_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/boards/{board_id}/members"),
+        Some(&owner_token),
+        Some(json!({"email": "board-member@example.com"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let member = extract_json_response(response).await;
+    assert_eq!(member["user_id"], member_id.as_str());
+    assert_eq!(member["role"], "member");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/boards",
+        Some(&member_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let boards = extract_json_response(response).await;
+    assert_eq!(boards.as_array().unwrap().len(), 1);
+    assert_eq!(boards[0]["role"], "member");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/boards/{board_id}/members"),
+        Some(&member_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let members = extract_json_response(response).await;
+    assert_eq!(members.as_array().unwrap().len(), 2);
+    assert_eq!(members[0]["role"], "owner");
+
+    // Only the owner may delete the board
+    let response = send_json_request(
+        app.clone(),
+        Method::DELETE,
+        &format!("/api/boards/{board_id}"),
+        Some(&member_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::DELETE,
+        &format!("/api/boards/{board_id}/members/{member_id}"),
+        Some(&owner_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/boards/{board_id}"),
+        Some(&member_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::DELETE,
+        &format!("/api/boards/{board_id}"),
+        Some(&owner_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = send_json_request(
+        app,
+        Method::GET,
+        &format!("/api/boards/{board_id}"),
+        Some(&owner_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Test an issue's lifecycle from creation through the workflow to deletion
+#[tokio::test]
+async fn test_issue_lifecycle() {
+    let (app, ctx) = create_test_app().await;
+    let (_, token) = verified_user_token(&ctx, "issue-user@example.com").await;
+    let (_, outsider_token) = verified_user_token(&ctx, "issue-outsider@example.com").await;
+    let board_id = create_board(app.clone(), &token).await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/boards/{board_id}/issues"),
+        Some(&token),
+        Some(json!({"title": "Fix login", "type": "defect", "priority": "high"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let issue = extract_json_response(response).await;
+    assert_eq!(issue["status"], "backlog");
+    assert_eq!(issue["type"], "defect");
+    let issue_id = issue["id"].as_str().unwrap().to_string();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/issues/{issue_id}"),
+        Some(&outsider_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::PUT,
+        &format!("/api/issues/{issue_id}"),
+        Some(&token),
+        Some(json!({"description": "Users see a blank page", "size_estimate": 4})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let updated = extract_json_response(response).await;
+    assert_eq!(updated["title"], "Fix login");
+    assert_eq!(updated["size_estimate"], 4);
+
+    // Issues cannot skip a column
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/issues/{issue_id}/status"),
+        Some(&token),
+        Some(json!({"status": "review"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/issues/{issue_id}/status"),
+        Some(&token),
+        Some(json!({"status": "current_release"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let moved = extract_json_response(response).await;
+    assert_eq!(moved["status"], "current_release");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/issues/{issue_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let fetched = extract_json_response(response).await;
+    assert_eq!(fetched["description"], "Users see a blank page");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::DELETE,
+        &format!("/api/issues/{issue_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = send_json_request(
+        app,
+        Method::GET,
+        &format!("/api/issues/{issue_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Test listing issues with filters and cursor pagination
+#[tokio::test]
+async fn test_list_issues() {
+    let (app, ctx) = create_test_app().await;
+    let (_, token) = verified_user_token(&ctx, "issue-lister@example.com").await;
+    let (_, outsider_token) = verified_user_token(&ctx, "issue-peeker@example.com").await;
+    let board_id = create_board(app.clone(), &token).await;
+
+    for (title, priority) in [("One", "low"), ("Two", "critical"), ("Three", "high")] {
+        let response = send_json_request(
+            app.clone(),
+            Method::POST,
+            &format!("/api/boards/{board_id}/issues"),
+            Some(&token),
+            Some(json!({"title": title, "type": "task", "priority": priority})),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/boards/{board_id}/issues?sort=priority&order=asc&limit=2"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let page = extract_json_response(response).await;
+    let titles: Vec<&str> = page["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|issue| issue["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["Two", "Three"]);
+    let cursor = page["next_cursor"].as_str().unwrap().to_string();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/boards/{board_id}/issues?sort=priority&order=asc&limit=2&cursor={cursor}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let page = extract_json_response(response).await;
+    assert_eq!(page["issues"][0]["title"], "One");
+    assert!(page["next_cursor"].is_null());
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/boards/{board_id}/issues?priority=critical"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let page = extract_json_response(response).await;
+    assert_eq!(page["issues"].as_array().unwrap().len(), 1);
+
+    let response = send_json_request(
+        app,
+        Method::GET,
+        &format!("/api/boards/{board_id}/issues"),
+        Some(&outsider_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
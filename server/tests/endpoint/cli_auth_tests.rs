@@ -0,0 +1,205 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for CLI device login endpoints
+//!
+//! These tests walk through the PKCE flow: the CLI starts a flow, a signed-in
+//! user approves it, and the CLI exchanges the code and verifier for tokens.
+
+use axum::http::{Method, StatusCode};
+use oauth2::PkceCodeChallenge;
+use serde_json::json;
+
+const TEST_EMAIL: &str = "cli-user@example.com";
+
+use crate::common::{TestContext, create_test_app, extract_json_response, send_json_request};
+
+/// Create a user and return a browser access token for them
+async fn browser_token(ctx: &TestContext) -> String {
+    let user = ctx.create_test_user(TEST_EMAIL).await;
+    ctx.auth_service
+        .generate_token(user.id, &user.email, &[])
+        .unwrap()
+}
+
+/// Test the complete CLI login flow
+#[tokio::test]
+async fn test_cli_login_flow() {
+    let (app, ctx) = create_test_app().await;
+    let token = browser_token(&ctx).await;
+    let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+
+    // 1. The CLI starts a flow
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/cli/flows",
+        None,
+        Some(json!({
+            "device_name": "Build server",
+            "device_fingerprint": "fp-001",
+            "code_challenge": challenge.as_str(),
+            "code_challenge_method": "S256",
+            "redirect_uri": "http://127.0.0.1:9000/callback"
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let started = extract_json_response(response).await;
+    let flow_state = started["state"].as_str().unwrap().to_string();
+    assert!(
+        started["verification_url"]
+            .as_str()
+            .unwrap()
+            .contains(&flow_state)
+    );
+
+    // 2. The user reviews and approves it in the browser
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/auth/cli/flows/{flow_state}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let flow = extract_json_response(response).await;
+    assert_eq!(flow["device_name"], "Build server");
+    assert_eq!(flow["status"], "pending");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/auth/cli/flows/{flow_state}/approve"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let approved = extract_json_response(response).await;
+    let code = approved["code"].as_str().unwrap().to_string();
+    assert!(
+        approved["redirect_url"]
+            .as_str()
+            .unwrap()
+            .starts_with("http://127.0.0.1:9000/callback?code=")
+    );
+
+    // 3. The CLI exchanges the code and verifier for tokens
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/cli/token",
+        None,
+        Some(json!({
+            "state": flow_state,
+            "code": code,
+            "code_verifier": verifier.secret()
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let tokens = extract_json_response(response).await;
+    let cli_token = tokens["auth_token"].as_str().unwrap().to_string();
+    assert!(tokens["refresh_token"].as_str().is_some());
+
+    // The CLI token is bound to a device session named after the CLI
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/auth/sessions",
+        Some(&cli_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sessions = extract_json_response(response).await;
+    assert_eq!(sessions["sessions"][0]["device_name"], "Build server");
+    assert_eq!(sessions["sessions"][0]["current"], true);
+
+    // The code cannot be exchanged twice
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/cli/token",
+        None,
+        Some(json!({
+            "state": flow_state,
+            "code": code,
+            "code_verifier": verifier.secret()
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test that starting a flow rejects a malformed code challenge
+#[tokio::test]
+async fn test_start_cli_flow_invalid_challenge() {
+    let (app, _ctx) = create_test_app().await;
+
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/cli/flows",
+        None,
+        Some(json!({
+            "device_name": "Build server",
+            "device_fingerprint": "fp-001",
+            "code_challenge": "not-a-challenge"
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Test that approving a flow requires a signed-in user
+#[tokio::test]
+async fn test_approve_cli_flow_requires_auth() {
+    let (app, _ctx) = create_test_app().await;
+
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/cli/flows/some-state/approve",
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test that exchanging a code for an unapproved flow fails
+#[tokio::test]
+async fn test_cli_token_for_unapproved_flow() {
+    let (app, _ctx) = create_test_app().await;
+    let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/cli/flows",
+        None,
+        Some(json!({
+            "device_name": "Build server",
+            "device_fingerprint": "fp-001",
+            "code_challenge": challenge.as_str()
+        })),
+    )
+    .await;
+    let started = extract_json_response(response).await;
+
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/cli/token",
+        None,
+        Some(json!({
+            "state": started["state"],
+            "code": "guessed-code",
+            "code_verifier": verifier.secret()
+        })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,162 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for uploaded documents and chat answers drawn from them
+//!
+//! The AI provider is the mock provider, which echoes the last prompt back, so
+//! its answers cite no sources and every retrieved chunk is returned as one.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+};
+use serde_json::json;
+use tower::ServiceExt; // for `oneshot` and `ready`
+
+use crate::common::{create_test_app, extract_json_response, send_json_request, user_token};
+
+/// Upload a text file and return the ID of the document stored for it
+async fn upload_text_file(app: Router, token: &str, name: &str, text: &str) -> String {
+    let boundary = "document-test-boundary";
+    let body = format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\n\
+         Content-Type: text/plain\r\n\r\n{text}\r\n--{boundary}--\r\n"
+    );
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/api/ai/upload")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(Body::from(body))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let result = extract_json_response(response).await;
+    assert_eq!(result["documents"][0]["name"], name);
+    result["files"][0]["document_id"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Test uploading, listing, fetching and deleting documents
+#[tokio::test]
+async fn test_document_lifecycle() {
+    let (app, ctx) = create_test_app().await;
+    let (_, token) = user_token(&ctx, "documents@example.com").await;
+    let (_, outsider_token) = user_token(&ctx, "documents-outsider@example.com").await;
+
+    let document_id = upload_text_file(
+        app.clone(),
+        &token,
+        "handbook.txt",
+        "Employees receive twenty five days of paid vacation each year.",
+    )
+    .await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/ai/documents",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let result = extract_json_response(response).await;
+    assert_eq!(result[0]["id"], document_id.as_str());
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/documents/{document_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let result = extract_json_response(response).await;
+    assert_eq!(result["name"], "handbook.txt");
+    assert_eq!(result["chunk_count"], 1);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/documents/{document_id}"),
+        Some(&outsider_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::DELETE,
+        &format!("/api/ai/documents/{document_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = send_json_request(
+        app,
+        Method::GET,
+        &format!("/api/ai/documents/{document_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Test chatting with documents as sources
+#[tokio::test]
+async fn test_chat_with_documents() {
+    let (app, ctx) = create_test_app().await;
+    let (_, token) = user_token(&ctx, "documents-chat@example.com").await;
+    let (_, outsider_token) = user_token(&ctx, "documents-chat-outsider@example.com").await;
+
+    let document_id = upload_text_file(
+        app.clone(),
+        &token,
+        "handbook.txt",
+        "Employees receive twenty five days of paid vacation each year.",
+    )
+    .await;
+    let request = json!({
+        "messages": [{"role": "user", "content": "How much vacation do employees get?"}],
+        "document_ids": [document_id],
+    });
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/ai/chat",
+        Some(&token),
+        Some(request.clone()),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let result = extract_json_response(response).await;
+    let citations = result["citations"].as_array().unwrap();
+    assert_eq!(citations.len(), 1);
+    assert_eq!(citations[0]["index"], 1);
+    assert_eq!(citations[0]["document_id"], document_id.as_str());
+    assert_eq!(citations[0]["document_name"], "handbook.txt");
+
+    // Another user cannot use the document
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/ai/chat",
+        Some(&outsider_token),
+        Some(request),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,186 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for duplicate issue detection and conversation search
+
+use axum::{
+    Router,
+    http::{Method, StatusCode},
+};
+use serde_json::json;
+
+use server::models::{CreateConversationRequest, CreateMessageRequest};
+use server::services::AiDataService;
+
+use crate::common::{create_test_app, extract_json_response, send_json_request, user_token};
+
+/// Create an issue on a board and return its ID
+async fn create_issue(
+    app: Router,
+    token: &str,
+    board_id: &str,
+    title: &str,
+    description: &str,
+) -> String {
+    let response = send_json_request(
+        app,
+        Method::POST,
+        &format!("/api/boards/{board_id}/issues"),
+        Some(token),
+        Some(json!({"title": title, "description": description, "type": "feature", "priority": "medium"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    extract_json_response(response).await["id"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Test finding likely duplicates of a draft and of an existing issue
+#[tokio::test]
+async fn test_duplicate_detection() {
+    let (app, ctx) = create_test_app().await;
+    let (_, token) = user_token(&ctx, "duplicates@example.com").await;
+    let (_, outsider_token) = user_token(&ctx, "duplicates-outsider@example.com").await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/boards",
+        Some(&token),
+        Some(json!({"name": "Roadmap"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let board_id = extract_json_response(response).await["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let login = create_issue(
+        app.clone(),
+        &token,
+        &board_id,
+        "Google login fails",
+        "Users cannot log in with their Google account",
+    )
+    .await;
+    let login_again = create_issue(
+        app.clone(),
+        &token,
+        &board_id,
+        "Login with Google is broken",
+        "Google account login fails for users",
+    )
+    .await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/boards/{board_id}/duplicates"),
+        Some(&token),
+        Some(json!({"title": "Cannot log in with Google", "limit": 1})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let result = extract_json_response(response).await;
+    assert_eq!(result["duplicates"].as_array().unwrap().len(), 1);
+    assert!(result["uniqueness_score"].as_f64().unwrap() < 0.6);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/boards/{board_id}/duplicates"),
+        Some(&outsider_token),
+        Some(json!({"title": "Cannot log in with Google"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/issues/{login}/duplicates"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let result = extract_json_response(response).await;
+    let duplicates = result["duplicates"].as_array().unwrap();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0]["issue_id"], login_again.as_str());
+    assert_eq!(duplicates[0]["status"], "backlog");
+
+    let response = send_json_request(
+        app,
+        Method::GET,
+        &format!("/api/issues/{login}/duplicates"),
+        Some(&outsider_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Test searching a user's conversations
+#[tokio::test]
+async fn test_search_conversations() {
+    let (app, ctx) = create_test_app().await;
+    let (user_id, token) = user_token(&ctx, "conversation-search@example.com").await;
+
+    let ai_data = AiDataService::new(ctx.pool.clone());
+    for (title, content) in [
+        ("Deployment", "How do I deploy the server to Fly.io?"),
+        ("Recipes", "Suggest a dinner with pasta and tomatoes"),
+    ] {
+        let conversation = ai_data
+            .create_conversation(
+                &user_id,
+                CreateConversationRequest {
+                    title: Some(title.to_string()),
+                    model: "mock".to_string(),
+                    system_prompt: None,
+                },
+            )
+            .await
+            .unwrap();
+        ai_data
+            .add_message(
+                &conversation.id,
+                &user_id,
+                CreateMessageRequest {
+                    role: "user".to_string(),
+                    content: content.to_string(),
+                    token_count: None,
+                    parent_message_id: None,
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/ai/conversations/search?q=deploying%20the%20server",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let result = extract_json_response(response).await;
+    let conversations = result["conversations"].as_array().unwrap();
+    assert_eq!(conversations.len(), 1);
+    assert_eq!(conversations[0]["title"], "Deployment");
+
+    let response = send_json_request(
+        app,
+        Method::GET,
+        "/api/ai/conversations/search?q=deploy",
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
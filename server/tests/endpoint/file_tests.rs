@@ -0,0 +1,306 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for stored files, session assets and signed downloads
+//!
+//! The test context stores file contents in memory.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+    response::Response,
+};
+use serde_json::json;
+use std::fmt::Write;
+use tower::ServiceExt; // for `oneshot` and `ready`
+
+use crate::common::{create_test_app, extract_json_response, send_json_request, user_token};
+
+/// Send files as multipart form data and return the response
+async fn send_multipart_request(
+    app: Router,
+    method: Method,
+    uri: &str,
+    token: &str,
+    files: &[(&str, &str)],
+) -> Response {
+    let boundary = "file-test-boundary";
+    let mut body = String::new();
+    for (name, text) in files {
+        let _ = write!(
+            body,
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{name}\"\r\n\
+             Content-Type: text/plain\r\n\r\n{text}\r\n"
+        );
+    }
+    let _ = write!(body, "--{boundary}--\r\n");
+
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .body(Body::from(body))
+        .unwrap();
+    app.oneshot(request).await.unwrap()
+}
+
+/// Download a file through its signed URL
+async fn download(app: Router, url: &str) -> Response {
+    let request = Request::builder().uri(url).body(Body::empty()).unwrap();
+    app.oneshot(request).await.unwrap()
+}
+
+/// Create a session for the user and return its ID
+async fn create_session(app: Router, token: &str) -> String {
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/ai/sessions",
+        Some(token),
+        Some(json!({"persona": "project_manager", "initial_input": "Fix the login page"})),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    extract_json_response(response).await["session_id"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Test uploading session assets, then listing, downloading and deleting them
+#[tokio::test]
+async fn test_session_asset_lifecycle() {
+    let (app, ctx) = create_test_app().await;
+    let (_, token) = user_token(&ctx, "files@example.com").await;
+    let (_, outsider_token) = user_token(&ctx, "files-outsider@example.com").await;
+
+    let session_id = create_session(app.clone(), &token).await;
+
+    let assets_uri = format!("/api/ai/sessions/{session_id}/assets");
+    let response = send_multipart_request(
+        app.clone(),
+        Method::POST,
+        &assets_uri,
+        &outsider_token,
+        &[("a.txt", "a")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response =
+        send_multipart_request(app.clone(), Method::POST, &assets_uri, &token, &[]).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = send_multipart_request(
+        app.clone(),
+        Method::POST,
+        &assets_uri,
+        &token,
+        &[("steps.txt", "Click login twice"), ("log.txt", "500 error")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let uploaded = extract_json_response(response).await;
+    assert_eq!(uploaded.as_array().unwrap().len(), 2);
+    assert_eq!(uploaded[0]["session_id"], session_id.as_str());
+    let file_id = uploaded[0]["id"].as_str().unwrap().to_string();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/files?session_id={session_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        extract_json_response(response)
+            .await
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/files/{file_id}"),
+        Some(&outsider_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/files/{file_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let file = extract_json_response(response).await;
+    assert_eq!(file["name"], "steps.txt");
+
+    // The signed URL needs no token
+    let response = download(app.clone(), file["download_url"].as_str().unwrap()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"steps.txt\""
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], b"Click login twice");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/files/{file_id}/content?expires=4102444800&signature=00"),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::DELETE,
+        &format!("/api/ai/files/{file_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = download(app.clone(), file["download_url"].as_str().unwrap()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Test that files are only linked to the user's own sessions
+#[tokio::test]
+async fn test_session_assets_link_to_own_sessions() {
+    let (app, ctx) = create_test_app().await;
+    let (_, token) = user_token(&ctx, "assets-owner@example.com").await;
+    let (_, other_token) = user_token(&ctx, "assets-other@example.com").await;
+    let session_id = create_session(app.clone(), &token).await;
+    let other_session_id = create_session(app.clone(), &other_token).await;
+
+    let response = send_multipart_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/ai/sessions/{session_id}/assets"),
+        &token,
+        &[("trace.txt", "Stack trace")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let uploaded = extract_json_response(response).await;
+    assert_eq!(uploaded[0]["session_id"], session_id.as_str());
+    let file_id = uploaded[0]["id"].as_str().unwrap().to_string();
+
+    // Someone else's session is refused, and nothing is stored for it
+    let response = send_multipart_request(
+        app.clone(),
+        Method::POST,
+        &format!("/api/ai/sessions/{other_session_id}/assets"),
+        &token,
+        &[("trace.txt", "Stack trace")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/files?session_id={other_session_id}"),
+        Some(&other_token),
+        None,
+    )
+    .await;
+    assert_eq!(extract_json_response(response).await, json!([]));
+
+    // Nor can the other user see or delete the file linked to the session
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/ai/files?session_id={session_id}"),
+        Some(&other_token),
+        None,
+    )
+    .await;
+    assert_eq!(extract_json_response(response).await, json!([]));
+    let response = send_json_request(
+        app.clone(),
+        Method::DELETE,
+        &format!("/api/ai/files/{file_id}"),
+        Some(&other_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send_json_request(
+        app,
+        Method::GET,
+        &format!("/api/ai/files?session_id={session_id}"),
+        Some(&token),
+        None,
+    )
+    .await;
+    let files = extract_json_response(response).await;
+    assert_eq!(files.as_array().unwrap().len(), 1);
+    assert_eq!(files[0]["id"], file_id.as_str());
+}
+
+/// Test that chat uploads are stored as files of the conversation
+#[tokio::test]
+async fn test_upload_stores_files() {
+    let (app, ctx) = create_test_app().await;
+    let (_, token) = user_token(&ctx, "file-upload@example.com").await;
+
+    let response = send_multipart_request(
+        app.clone(),
+        Method::POST,
+        "/api/ai/upload?conversation_id=missing",
+        &token,
+        &[("notes.txt", "Release on Friday")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send_multipart_request(
+        app.clone(),
+        Method::POST,
+        "/api/ai/upload",
+        &token,
+        &[("notes.txt", "Release on Friday")],
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let result = extract_json_response(response).await;
+    let file_id = result["files"][0]["file_id"].as_str().unwrap();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/ai/files",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let files = extract_json_response(response).await;
+    assert_eq!(files[0]["id"], file_id);
+    assert_eq!(files[0]["document_id"], result["files"][0]["document_id"]);
+
+    let response = download(app, result["files"][0]["download_url"].as_str().unwrap()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/plain");
+}
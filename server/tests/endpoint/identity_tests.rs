@@ -0,0 +1,166 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for linked sign-in identity endpoints
+//!
+//! These tests verify that signed-in users can list, start linking and unlink
+//! OAuth provider accounts, and that the endpoints require authentication.
+
+use axum::http::{Method, StatusCode};
+// for `oneshot` and `ready`
+
+use server::models::{
+    User,
+    oauth::{OAuthProvider, OAuthUserInfo},
+};
+use server::services::IdentityService;
+
+const TEST_EMAIL: &str = "identities@example.com";
+
+use crate::common::{TestContext, create_test_app, extract_json_response, send_json_request};
+
+/// Create the test user and a token for them
+async fn create_user_with_token(ctx: &TestContext) -> (User, String) {
+    let user = ctx.create_test_user(TEST_EMAIL).await;
+    let token = ctx
+        .auth_service
+        .generate_token(user.id, &user.email, &[])
+        .expect("Failed to generate token");
+    (user, token)
+}
+
+/// Test that the identity endpoints require authentication
+#[tokio::test]
+async fn test_identities_unauthenticated() {
+    let (app, _ctx) = create_test_app().await;
+
+    let response =
+        send_json_request(app.clone(), Method::GET, "/api/auth/identities", None, None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/identities/github",
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send_json_request(
+        app,
+        Method::DELETE,
+        "/api/auth/identities/github",
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test that starting to link an account stores a state bound to the current user
+#[tokio::test]
+async fn test_link_identity_returns_authorization_url() {
+    let (app, ctx) = create_test_app().await;
+    let (user, token) = create_user_with_token(&ctx).await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/identities/github",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let link = extract_json_response(response).await;
+    let state = link["state"].as_str().unwrap();
+    let authorization_url = link["authorization_url"].as_str().unwrap();
+    assert!(authorization_url.starts_with("https://github.com/login/oauth/authorize"));
+    assert!(authorization_url.contains(state));
+
+    // The callback links the account to the user instead of signing in
+    let oauth_state = ctx
+        .oauth_service
+        .validate_oauth_state(state, OAuthProvider::GitHub)
+        .await
+        .expect("Link state should be valid");
+    assert_eq!(oauth_state.link_user_id, Some(user.id));
+
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/identities/myspace",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Test listing and unlinking the current user's accounts
+#[tokio::test]
+async fn test_list_and_unlink_identities() {
+    let (app, ctx) = create_test_app().await;
+    let (user, token) = create_user_with_token(&ctx).await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/auth/identities",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let listed = extract_json_response(response).await;
+    assert_eq!(listed["identities"].as_array().unwrap().len(), 0);
+
+    IdentityService::new(ctx.pool.clone())
+        .link_identity(
+            user.id,
+            &OAuthUserInfo {
+                id: "12345".to_string(),
+                email: TEST_EMAIL.to_string(),
+                name: None,
+                picture: None,
+                provider: OAuthProvider::GitHub,
+                email_verified: true,
+            },
+        )
+        .await
+        .expect("Failed to link identity");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/auth/identities",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let listed = extract_json_response(response).await;
+    assert_eq!(listed["identities"][0]["provider"], "github");
+    assert_eq!(listed["identities"][0]["provider_user_id"], "12345");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::DELETE,
+        "/api/auth/identities/github",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send_json_request(
+        app,
+        Method::DELETE,
+        "/api/auth/identities/github",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
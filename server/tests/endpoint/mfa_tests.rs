@@ -0,0 +1,333 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for two-factor authentication endpoints
+//!
+//! These tests enroll an authenticator app, compute its codes from the returned
+//! secret, and check that signing in then takes a second step, and that admins
+//! can require two-factor authentication for privileged roles.
+
+use axum::{
+    Router,
+    http::{Method, StatusCode},
+};
+use serde_json::json;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use server::models::Role;
+
+const TEST_SECURE_PASS: &str = "secure_password_123";
+
+use crate::common::{create_test_app, extract_json_response, send_json_request};
+
+/// Code the authenticator app shows `offset_steps` steps from now
+fn app_code(secret: &str, offset_steps: i64) -> String {
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        30,
+        Secret::Encoded(secret.to_string()).to_bytes().unwrap(),
+        None,
+        String::new(),
+    )
+    .unwrap();
+    let time = u64::try_from(chrono::Utc::now().timestamp() + offset_steps * 30).unwrap();
+    totp.generate(time)
+}
+
+/// Register a user and return their auth token
+async fn register(app: &Router, email: &str) -> String {
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/register",
+        None,
+        Some(json!({ "email": email, "password": TEST_SECURE_PASS })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    extract_json_response(response).await["auth_token"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Enroll and enable an authenticator app, returning its secret and the recovery codes
+async fn enable_mfa(app: &Router, token: &str) -> (String, Vec<String>) {
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/mfa/enroll",
+        Some(token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let enrollment = extract_json_response(response).await;
+    let secret = enrollment["secret"].as_str().unwrap().to_string();
+    assert!(
+        enrollment["otpauth_uri"]
+            .as_str()
+            .unwrap()
+            .starts_with("otpauth://totp/")
+    );
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/mfa/enable",
+        Some(token),
+        Some(json!({ "code": app_code(&secret, 0) })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = extract_json_response(response).await;
+    let recovery_codes = body["recovery_codes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|code| code.as_str().unwrap().to_string())
+        .collect();
+
+    (secret, recovery_codes)
+}
+
+/// Sign in with a password and return the `mfa_pending` token
+async fn login_pending(app: &Router, email: &str) -> String {
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/login",
+        None,
+        Some(json!({ "email": email, "password": TEST_SECURE_PASS })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = extract_json_response(response).await;
+    assert_eq!(body["status"], "mfa_pending");
+    assert!(body.get("auth_token").is_none());
+    body["mfa_token"].as_str().unwrap().to_string()
+}
+
+/// Test that signing in with two-factor authentication enabled takes two steps
+#[tokio::test]
+async fn test_two_step_login() {
+    let (app, _ctx) = create_test_app().await;
+    let email = "two-step@example.com";
+    let token = register(&app, email).await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/auth/mfa",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(extract_json_response(response).await["enabled"], false);
+
+    let (secret, recovery_codes) = enable_mfa(&app, &token).await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    let mfa_token = login_pending(&app, email).await;
+
+    // The pending token is not a session
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/users/me",
+        Some(&mfa_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let verify = |code: String| {
+        send_json_request(
+            app.clone(),
+            Method::POST,
+            "/api/auth/mfa/verify",
+            None,
+            Some(json!({ "mfa_token": mfa_token, "code": code })),
+        )
+    };
+    assert_eq!(
+        verify("not-a-code".to_string()).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    let response = verify(app_code(&secret, 1)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = extract_json_response(response).await;
+    let session_token = body["auth_token"].as_str().unwrap();
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/auth/mfa",
+        Some(session_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(extract_json_response(response).await["enabled"], true);
+
+    // The pending token works only once
+    assert_eq!(
+        verify(recovery_codes[0].clone()).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // A recovery code completes a new sign-in, but only once
+    let mfa_token = login_pending(&app, email).await;
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/mfa/verify",
+        None,
+        Some(json!({ "mfa_token": mfa_token, "code": recovery_codes[0] })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mfa_token = login_pending(&app, email).await;
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/mfa/verify",
+        None,
+        Some(json!({ "mfa_token": mfa_token, "code": recovery_codes[0] })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test replacing recovery codes and disabling two-factor authentication
+#[tokio::test]
+async fn test_regenerate_codes_and_disable() {
+    let (app, _ctx) = create_test_app().await;
+    let email = "disable@example.com";
+    let token = register(&app, email).await;
+    let (_, recovery_codes) = enable_mfa(&app, &token).await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/mfa/recovery-codes",
+        Some(&token),
+        Some(json!({ "code": recovery_codes[0] })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let new_codes = extract_json_response(response).await["recovery_codes"].clone();
+
+    // Old recovery codes no longer work
+    let disable = |code: &str| {
+        send_json_request(
+            app.clone(),
+            Method::POST,
+            "/api/auth/mfa/disable",
+            Some(&token),
+            Some(json!({ "code": code })),
+        )
+    };
+    assert_eq!(
+        disable(&recovery_codes[1]).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        disable(new_codes[0].as_str().unwrap()).await.status(),
+        StatusCode::OK
+    );
+
+    // Signing in takes one step again
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/login",
+        None,
+        Some(json!({ "email": email, "password": TEST_SECURE_PASS })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(extract_json_response(response).await["auth_token"].is_string());
+}
+
+/// Test that admins can require two-factor authentication for privileged roles
+#[tokio::test]
+async fn test_admin_requires_mfa_for_roles() {
+    let (app, ctx) = create_test_app().await;
+    let admin = ctx.create_test_user("mfa-admin@example.com").await;
+    ctx.role_service
+        .grant_role(admin.id, Role::Admin, Some("test"))
+        .await
+        .expect("Failed to grant role");
+    let token = ctx
+        .auth_service
+        .generate_token(admin.id, &admin.email, &[Role::Admin])
+        .expect("Failed to generate token");
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/admin/mfa-policy",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        extract_json_response(response).await["required_roles"],
+        json!([])
+    );
+
+    let response = send_json_request(
+        app.clone(),
+        Method::PUT,
+        "/api/admin/mfa-policy",
+        Some(&token),
+        Some(json!({ "required_roles": ["admin"] })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        extract_json_response(response).await["required_roles"],
+        json!(["admin"])
+    );
+
+    // Without a second factor the admin role can no longer be used
+    let get_policy = || {
+        send_json_request(
+            app.clone(),
+            Method::GET,
+            "/api/admin/mfa-policy",
+            Some(&token),
+            None,
+        )
+    };
+    assert_eq!(get_policy().await.status(), StatusCode::FORBIDDEN);
+
+    let (secret, _) = enable_mfa(&app, &token).await;
+    assert_eq!(get_policy().await.status(), StatusCode::OK);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/auth/mfa",
+        Some(&token),
+        None,
+    )
+    .await;
+    assert_eq!(extract_json_response(response).await["required"], true);
+
+    // Nor can the factor be turned off while the role requires it
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/mfa/disable",
+        Some(&token),
+        Some(json!({ "code": app_code(&secret, 1) })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
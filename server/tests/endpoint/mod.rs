@@ -2,6 +2,18 @@
 //!
 //! This module declares all endpoint test submodules to make them discoverable by Cargo's test runner.
 
+pub mod account_email_tests;
+pub mod admin_tests;
+pub mod ai_session_tests;
 pub mod auth_tests;
+pub mod board_tests;
+pub mod cli_auth_tests;
+pub mod document_tests;
+pub mod embedding_tests;
+pub mod file_tests;
+pub mod identity_tests;
+pub mod mfa_tests;
 pub mod payment_tests;
+pub mod quota_tests;
 pub mod route_coverage_test;
+pub mod session_tests;
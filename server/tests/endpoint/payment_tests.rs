@@ -16,7 +16,6 @@ use tower::ServiceExt; // for `oneshot` and `ready`
 
 // Import the application modules we need for testing
 use server::core::password_utils::hash_password;
-use server::routes::create_router;
 
 // Test constants to avoid gitleaks false positives
 const TEST_EMAIL: &str = "test@example.com";
@@ -25,23 +24,7 @@ const INVITED_EMAIL: &str = "invited@example.com";
 const PAID_EMAIL: &str = "paid@example.com";
 const LAPSED_EMAIL: &str = "lapsed@example.com";
 
-use crate::common::TestContext;
-
-/// Helper function to create the test app
-async fn create_test_app() -> (Router, TestContext) {
-    let ctx = TestContext::new().await;
-
-    let router = create_router(
-        ctx.user_service.clone(),
-        ctx.auth_service.clone(),
-        ctx.invite_service.clone(),
-        ctx.oauth_service.clone(),
-        &ctx.pool,
-    )
-    .expect("Failed to create router");
-
-    (router, ctx)
-}
+use crate::common::create_test_app;
 
 /// Helper function to create a request with authentication
 async fn send_authenticated_request(
@@ -339,7 +322,7 @@ async fn test_payment_status_unauthenticated() {
 /// Test POST /api/payment/create-intent with valid request
 #[tokio::test]
 async fn test_create_payment_intent_success() {
-    let (app, _ctx) = create_test_app().await;
+    let (app, ctx) = create_test_app().await;
 
     // Register a user
     let register_body = json!({
@@ -362,6 +345,8 @@ async fn test_create_payment_intent_success() {
         .unwrap();
     let register_response: Value = serde_json::from_slice(&body).unwrap();
     let token = register_response["auth_token"].as_str().unwrap();
+    let user_id = register_response["auth_user"]["id"].as_str().unwrap();
+    ctx.verify_email(user_id.parse().unwrap()).await;
 
     // Create payment intent
     let intent_body = json!({
@@ -408,7 +393,7 @@ async fn test_create_payment_intent_unauthenticated() {
 /// Test POST /api/payment/create-intent with invalid request body
 #[tokio::test]
 async fn test_create_payment_intent_invalid_body() {
-    let (app, _ctx) = create_test_app().await;
+    let (app, ctx) = create_test_app().await;
 
     // Register a user
     let register_body = json!({
@@ -431,6 +416,8 @@ async fn test_create_payment_intent_invalid_body() {
         .unwrap();
     let register_response: Value = serde_json::from_slice(&body).unwrap();
     let token = register_response["auth_token"].as_str().unwrap();
+    let user_id = register_response["auth_user"]["id"].as_str().unwrap();
+    ctx.verify_email(user_id.parse().unwrap()).await;
 
     // Create payment intent with missing fields
     let intent_body = json!({
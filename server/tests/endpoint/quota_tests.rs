@@ -0,0 +1,210 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for AI quota endpoints
+//!
+//! These tests verify that users can see their quota, that admins can set a
+//! user's limits, and that AI requests over the limit are rejected with 429.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt; // for `oneshot` and `ready`
+
+use server::models::{Role, User};
+
+const ADMIN_EMAIL: &str = "admin@example.com";
+const MEMBER_EMAIL: &str = "member@example.com";
+
+use crate::common::{TestContext, create_test_app, extract_json_response};
+
+/// Create a user holding the given roles and return it with a token carrying them
+async fn create_user_with_roles(ctx: &TestContext, email: &str, roles: &[Role]) -> (User, String) {
+    let user = ctx.create_test_user(email).await;
+    for role in roles {
+        ctx.role_service
+            .grant_role(user.id, *role, Some("test"))
+            .await
+            .expect("Failed to grant role");
+    }
+    let token = ctx
+        .auth_service
+        .generate_token(user.id, &user.email, roles)
+        .expect("Failed to generate token");
+    (user, token)
+}
+
+/// Helper function to create a request with authentication
+async fn send_authenticated_request(
+    app: Router,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+    token: &str,
+) -> Response {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"));
+
+    if body.is_some() {
+        builder = builder.header(header::CONTENT_TYPE, "application/json");
+    }
+
+    let request = if let Some(body_value) = body {
+        builder
+            .body(Body::from(serde_json::to_string(&body_value).unwrap()))
+            .unwrap()
+    } else {
+        builder.body(Body::empty()).unwrap()
+    };
+
+    app.oneshot(request).await.unwrap()
+}
+
+/// Test GET /api/ai/quota without authentication (should return 401)
+#[tokio::test]
+async fn test_get_quota_unauthenticated() {
+    let (app, _ctx) = create_test_app().await;
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/api/ai/quota")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test that users see the quota of their plan, with invitees above free users
+#[tokio::test]
+async fn test_get_quota_reports_plan() {
+    let (app, ctx) = create_test_app().await;
+    let (_member, member_token) = create_user_with_roles(&ctx, MEMBER_EMAIL, &[]).await;
+    ctx.create_test_invite("invitee@example.com", None).await;
+    let (_invitee, invitee_token) = create_user_with_roles(&ctx, "invitee@example.com", &[]).await;
+
+    let response = send_authenticated_request(
+        app.clone(),
+        Method::GET,
+        "/api/ai/quota",
+        None,
+        &member_token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let free = extract_json_response(response).await;
+    assert_eq!(free["plan"], "free");
+    assert_eq!(free["used"]["daily_tokens"], 0);
+    assert_eq!(
+        free["remaining"]["daily_tokens"],
+        free["limits"]["daily_tokens"]
+    );
+
+    let response =
+        send_authenticated_request(app, Method::GET, "/api/ai/quota", None, &invitee_token).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let invited = extract_json_response(response).await;
+    assert_eq!(invited["plan"], "invited");
+    assert!(
+        invited["limits"]["daily_tokens"].as_i64().unwrap()
+            > free["limits"]["daily_tokens"].as_i64().unwrap()
+    );
+}
+
+/// Test that only admins can see and set a user's quota
+#[tokio::test]
+async fn test_user_quota_requires_admin() {
+    let (app, ctx) = create_test_app().await;
+    let (member, token) = create_user_with_roles(&ctx, MEMBER_EMAIL, &[]).await;
+    let member_id = member.id;
+
+    let response = send_authenticated_request(
+        app.clone(),
+        Method::GET,
+        &format!("/api/admin/users/{member_id}/ai-quota"),
+        None,
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send_authenticated_request(
+        app,
+        Method::PUT,
+        &format!("/api/admin/users/{member_id}/ai-quota"),
+        Some(json!({ "daily_tokens": 1_000_000 })),
+        &token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+/// Test that AI requests over a user's limit are rejected with their remaining budget
+#[tokio::test]
+async fn test_requests_over_quota_are_rejected() {
+    let (app, ctx) = create_test_app().await;
+    let (_admin, admin_token) = create_user_with_roles(&ctx, ADMIN_EMAIL, &[Role::Admin]).await;
+    let (member, member_token) = create_user_with_roles(&ctx, MEMBER_EMAIL, &[]).await;
+    let member_id = member.id;
+
+    let response = send_authenticated_request(
+        app.clone(),
+        Method::PUT,
+        &format!("/api/admin/users/{member_id}/ai-quota"),
+        Some(json!({ "daily_tokens": 0 })),
+        &admin_token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let quota = extract_json_response(response).await;
+    assert_eq!(quota["limits"]["daily_tokens"], 0);
+    assert_eq!(quota["status"]["remaining"]["daily_tokens"], 0);
+
+    let response = send_authenticated_request(
+        app.clone(),
+        Method::POST,
+        "/api/ai/analyze/code",
+        Some(json!({ "code": "fn main() {}", "language": "rust" })),
+        &member_token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["x-quota-plan"], "free");
+    assert_eq!(response.headers()["x-quota-remaining-tokens-day"], "0");
+    assert!(response.headers().contains_key(header::RETRY_AFTER));
+
+    // Clearing the user's limits puts them back on their plan's
+    let response = send_authenticated_request(
+        app.clone(),
+        Method::PUT,
+        &format!("/api/admin/users/{member_id}/ai-quota"),
+        Some(json!({})),
+        &admin_token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send_authenticated_request(
+        app,
+        Method::GET,
+        &format!("/api/admin/users/{member_id}/ai-quota"),
+        None,
+        &admin_token,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let quota = extract_json_response(response).await;
+    assert!(quota["limits"].is_null());
+    assert_eq!(quota["status"]["plan"], "free");
+    assert!(
+        quota["status"]["remaining"]["daily_tokens"]
+            .as_i64()
+            .unwrap()
+            > 0
+    );
+}
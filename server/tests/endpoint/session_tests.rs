@@ -0,0 +1,443 @@
+#![allow(clippy::unwrap_used)]
+
+//! Integration tests for session endpoints
+//!
+//! These tests verify refresh token rotation and reuse detection, logout, and
+//! listing and revoking sessions.
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+    response::Response,
+};
+use serde_json::{Value, json};
+use tower::ServiceExt; // for `oneshot` and `ready`
+
+const TEST_EMAIL: &str = "sessions@example.com";
+const TEST_SECURE_PASS: &str = "secure_password_123";
+
+use crate::common::{TEST_USER_AGENT, create_test_app, extract_json_response, send_json_request};
+
+/// Register the test user and log in, returning the login response
+async fn login(app: &Router) -> Value {
+    let credentials = json!({ "email": TEST_EMAIL, "password": TEST_SECURE_PASS });
+
+    // Registration may already have happened for this app
+    let _ = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/register",
+        None,
+        Some(credentials.clone()),
+    )
+    .await;
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/login",
+        None,
+        Some(credentials),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    extract_json_response(response).await
+}
+
+/// Test that login returns a refresh token alongside a short-lived access token
+#[tokio::test]
+async fn test_login_returns_refresh_token() {
+    let (app, _ctx) = create_test_app().await;
+
+    let body = login(&app).await;
+    assert!(body["auth_token"].as_str().is_some());
+    assert!(body["refresh_token"].as_str().is_some());
+    assert!(body["expires_in"].as_i64().unwrap() > 0);
+}
+
+/// Test POST /api/auth/refresh rotates the refresh token and detects reuse
+#[tokio::test]
+async fn test_refresh_token_rotation_and_reuse() {
+    let (app, _ctx) = create_test_app().await;
+    let body = login(&app).await;
+    let first_refresh = body["refresh_token"].as_str().unwrap().to_string();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/refresh",
+        None,
+        Some(json!({ "refresh_token": first_refresh })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let refreshed = extract_json_response(response).await;
+    let access_token = refreshed["auth_token"].as_str().unwrap().to_string();
+    let second_refresh = refreshed["refresh_token"].as_str().unwrap().to_string();
+    assert_ne!(first_refresh, second_refresh);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/users/me",
+        Some(&access_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Replaying the first refresh token revokes the whole session
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/refresh",
+        None,
+        Some(json!({ "refresh_token": first_refresh })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/refresh",
+        None,
+        Some(json!({ "refresh_token": second_refresh })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response =
+        send_json_request(app, Method::GET, "/api/users/me", Some(&access_token), None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test POST /api/auth/refresh with an unknown token
+#[tokio::test]
+async fn test_refresh_with_invalid_token() {
+    let (app, _ctx) = create_test_app().await;
+
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/refresh",
+        None,
+        Some(json!({ "refresh_token": "not-a-valid-token" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test POST /api/auth/logout revokes the access and refresh tokens of the session
+#[tokio::test]
+async fn test_logout_revokes_session() {
+    let (app, _ctx) = create_test_app().await;
+    let body = login(&app).await;
+    let access_token = body["auth_token"].as_str().unwrap();
+    let refresh_token = body["refresh_token"].as_str().unwrap();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/logout",
+        Some(access_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/users/me",
+        Some(access_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/auth/refresh",
+        None,
+        Some(json!({ "refresh_token": refresh_token })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test that AI endpoints stop accepting the access token of a revoked session
+#[tokio::test]
+async fn test_ai_endpoints_reject_revoked_session() {
+    let (app, _ctx) = create_test_app().await;
+    let body = login(&app).await;
+    let access_token = body["auth_token"].as_str().unwrap();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/ai/conversations",
+        Some(access_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/logout",
+        Some(access_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    for (method, uri) in [
+        (Method::GET, "/api/ai/conversations"),
+        (Method::GET, "/api/ai/usage"),
+        (Method::GET, "/api/auth/verify"),
+    ] {
+        let response = send_json_request(app.clone(), method, uri, Some(access_token), None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{uri}");
+    }
+    let response = send_json_request(
+        app,
+        Method::POST,
+        "/api/ai/chat",
+        Some(access_token),
+        Some(json!({ "messages": [{ "role": "user", "content": "Hello" }] })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test POST /api/auth/logout without authentication
+#[tokio::test]
+async fn test_logout_unauthenticated() {
+    let (app, _ctx) = create_test_app().await;
+
+    let response = send_json_request(app, Method::POST, "/api/auth/logout", None, None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Test listing sessions and revoking another session
+#[tokio::test]
+async fn test_list_and_revoke_sessions() {
+    let (app, _ctx) = create_test_app().await;
+    let first = login(&app).await;
+    let second = login(&app).await;
+    let first_token = first["auth_token"].as_str().unwrap();
+    let second_token = second["auth_token"].as_str().unwrap();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/auth/sessions",
+        Some(first_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let listed = extract_json_response(response).await;
+    let sessions = listed["sessions"].as_array().unwrap();
+    // Registration signs the user in too, so there are three sessions
+    assert_eq!(sessions.len(), 3);
+    assert!(
+        sessions
+            .iter()
+            .all(|session| session["device_name"] == TEST_USER_AGENT)
+    );
+
+    assert_eq!(
+        sessions
+            .iter()
+            .filter(|session| session["current"] == true)
+            .count(),
+        1
+    );
+
+    // Find the second login's session through its own listing
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/auth/sessions",
+        Some(second_token),
+        None,
+    )
+    .await;
+    let listed = extract_json_response(response).await;
+    let other_session = listed["sessions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|session| session["current"] == true)
+        .unwrap()
+        .clone();
+    let other_id = other_session["id"].as_str().unwrap();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::DELETE,
+        &format!("/api/auth/sessions/{other_id}"),
+        Some(first_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The revoked session's access token no longer works
+    let response = send_json_request(
+        app.clone(),
+        Method::GET,
+        "/api/auth/sessions",
+        Some(second_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Revoking it again reports that it no longer exists
+    let response = send_json_request(
+        app,
+        Method::DELETE,
+        &format!("/api/auth/sessions/{other_id}"),
+        Some(first_token),
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+/// Cookie header sending back the cookies a response set
+fn cookie_header(response: &Response) -> String {
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().split(';').next().unwrap())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Helper function to send a request authenticated by session cookies
+async fn send_cookie_request(
+    app: Router,
+    method: Method,
+    uri: &str,
+    cookies: &str,
+    body: Value,
+) -> Response {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::COOKIE, cookies)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    app.oneshot(request).await.unwrap()
+}
+
+/// Test a session handed over by an OAuth login code in the cookie session mode
+#[tokio::test]
+async fn test_cookie_session_from_oauth_login_code() {
+    let (app, ctx) = create_test_app().await;
+    let user_id = login(&app).await["auth_user"]["id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let code = ctx
+        .oauth_service
+        .create_login_code(user_id, false)
+        .await
+        .unwrap();
+
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/oauth/exchange",
+        None,
+        Some(json!({ "code": code, "session": "cookie" })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookies = cookie_header(&response);
+    let body = extract_json_response(response).await;
+    assert_eq!(body["auth_token"], "");
+    assert!(body.get("refresh_token").is_none());
+
+    // The code is single-use
+    let response = send_json_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/oauth/exchange",
+        None,
+        Some(json!({ "code": code })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send_cookie_request(
+        app.clone(),
+        Method::GET,
+        "/api/auth/sessions",
+        &cookies,
+        json!({}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // AI endpoints accept the access token cookie too
+    let response = send_cookie_request(
+        app.clone(),
+        Method::GET,
+        "/api/ai/conversations",
+        &cookies,
+        json!({}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Refreshing without a token in the body uses and replaces the cookies
+    let response = send_cookie_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/refresh",
+        &cookies,
+        json!({}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let refreshed = cookie_header(&response);
+    assert_ne!(refreshed, cookies);
+    assert!(
+        extract_json_response(response).await["expires_in"]
+            .as_i64()
+            .unwrap()
+            > 0
+    );
+
+    let response = send_cookie_request(
+        app.clone(),
+        Method::POST,
+        "/api/auth/logout",
+        &refreshed,
+        json!({}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(cookie_header(&response).contains("kanbain_access=;"));
+
+    let response = send_cookie_request(
+        app,
+        Method::GET,
+        "/api/auth/sessions",
+        &refreshed,
+        json!({}),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}